@@ -0,0 +1,72 @@
+//! Benchmark for the number of allocations made per request.
+//!
+//! Only allocations made through the Rust global allocator are counted, which
+//! leaves out those made by libcurl itself.
+
+use isahc::prelude::*;
+use isahc_benchmarks::TestServer;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::sink,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static DATA: [u8; 0x400] = [1; 0x400]; // 1K
+
+const WARMUP_REQUESTS: usize = 100;
+const REQUESTS: usize = 1000;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+    let server = TestServer::static_response(&DATA);
+    let endpoint = server.endpoint();
+    let client = HttpClient::new().unwrap();
+
+    let send = || {
+        client.get(&endpoint).unwrap().copy_to(sink()).unwrap();
+    };
+
+    // Let connections, caches and pools fill up first.
+    for _ in 0..WARMUP_REQUESTS {
+        send();
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+
+    for _ in 0..REQUESTS {
+        send();
+    }
+
+    // The test server allocates as well, so these are upper bounds.
+    println!(
+        "download 1K: isahc: {} allocations ({} bytes) per request",
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / REQUESTS,
+        (ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes) / REQUESTS,
+    );
+}
@@ -7,10 +7,20 @@
 //!
 //! Since request executions are driven through futures, the agent also acts as
 //! a specialized task executor for tasks related to requests.
+//!
+//! # Availability
+//!
+//! The public API in this module is only available when the
+//! [`unstable-agent`](../index.html#unstable-agent) feature is enabled. It is
+//! an unstable feature whose interface may change between patch releases.
 
 use crate::{
-    error::Error,
+    error::{Error, ErrorKind},
     handler::RequestHandler,
+    metrics::{AgentStats, AgentStatsInner, PoolStats},
+    config::internal::SetOpt,
+    pool::{Connections, Partitions},
+    push::Pushes,
     task::{UdpWaker, WakerExt},
 };
 use crossbeam_utils::sync::WaitGroup;
@@ -18,25 +28,152 @@ use curl::multi::WaitFd;
 use flume::{Receiver, Sender};
 use slab::Slab;
 use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+    future::Future,
     io,
     net::UdpSocket,
-    sync::Mutex,
-    task::Waker,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
     thread,
     time::{Duration, Instant},
 };
 
 const WAIT_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// How long after the timeout of a request elapsed the agent waits for curl to
+/// report it before aborting the request itself.
+const FORCED_TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Maximum number of curl handles kept around for reuse by later requests.
+const MAX_IDLE_HANDLES: usize = 64;
+
 type EasyHandle = curl::easy::Easy2<RequestHandler>;
 type MultiMessage = (usize, Result<(), curl::Error>);
 
+/// A curl event loop running in a background thread, which can be used to
+/// execute custom curl transfers.
+///
+/// This is the same event loop that drives the requests of an
+/// [`HttpClient`](crate::HttpClient). It can be used to perform transfers that
+/// Isahc does not support directly, such as FTP or SMTP, without having to
+/// drive a curl multi handle yourself.
+///
+/// Dropping the agent, or calling [`Agent::shutdown`], stops the background
+/// thread and aborts any transfers still in progress.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "unstable-agent", doc = "```no_run")]
+#[cfg_attr(not(feature = "unstable-agent"), doc = "```ignore")]
+/// use isahc::agent::Agent;
+/// use std::sync::{Arc, Mutex};
+///
+/// let agent = Agent::spawn()?;
+/// let listing = Arc::new(Mutex::new(Vec::new()));
+///
+/// let mut easy = curl::easy::Easy::new();
+/// easy.url("ftp://ftp.example.org/")?;
+/// easy.write_function({
+///     let listing = listing.clone();
+///     move |data| {
+///         listing.lock().unwrap().extend_from_slice(data);
+///         Ok(data.len())
+///     }
+/// })?;
+///
+/// let easy = futures_lite::future::block_on(agent.submit(easy))?;
+/// println!("response code: {}", easy.response_code()?);
+///
+/// agent.shutdown();
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct Agent {
+    handle: Handle,
+}
+
+impl Agent {
+    /// Spawn a new agent in a background thread.
+    pub fn spawn() -> Result<Self, Error> {
+        Ok(Self {
+            handle: AgentBuilder::default()
+                .spawn()
+                .map_err(|e| Error::new(ErrorKind::ClientInitialization, e))?,
+        })
+    }
+
+    /// Submit a prepared curl easy handle to be executed by the agent.
+    ///
+    /// Returns a future that resolves once the transfer is complete, giving
+    /// the easy handle back so that information about the transfer can be
+    /// retrieved from it.
+    ///
+    /// The callbacks of the easy handle are invoked on the agent thread, so
+    /// they should not block. Pausing a transfer from a callback is not
+    /// supported, as there is no way to resume it.
+    pub fn submit(&self, easy: curl::easy::Easy) -> TransferFuture {
+        self.handle.submit_transfer(easy)
+    }
+
+    /// Submit a prepared curl easy handle with a custom handler to be executed
+    /// by the agent.
+    ///
+    /// This behaves just like [`Agent::submit`], but for transfers using the
+    /// [`Handler`](curl::easy::Handler) trait for their callbacks.
+    pub fn submit2<H>(&self, easy: curl::easy::Easy2<H>) -> TransferFuture<curl::easy::Easy2<H>>
+    where
+        H: curl::easy::Handler + Send + 'static,
+    {
+        self.handle.submit_transfer(easy)
+    }
+
+    /// Shut down the agent, aborting any transfers still in progress and
+    /// waiting for the background thread to exit.
+    pub fn shutdown(self) {}
+
+    /// Get a snapshot of statistics about the agent's event loop.
+    pub fn stats(&self) -> AgentStats {
+        self.handle.stats()
+    }
+}
+
+/// A future for a custom transfer submitted to an [`Agent`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TransferFuture<E: 'static = curl::easy::Easy> {
+    receiver: flume::r#async::RecvFut<'static, Result<E, Error>>,
+}
+
+impl<E: 'static> Future for TransferFuture<E> {
+    type Output = Result<E, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(Error::new(ErrorKind::Unknown, e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<E: 'static> fmt::Debug for TransferFuture<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransferFuture").finish()
+    }
+}
+
 /// Builder for configuring and spawning an agent.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub(crate) struct AgentBuilder {
     max_connections: usize,
     max_connections_per_host: usize,
+    max_concurrent_streams: usize,
+    max_concurrent_requests: usize,
     connection_cache_size: usize,
+    enable_push: bool,
+    shared_connections: bool,
+    handle_pool: HandlePool,
 }
 
 impl AgentBuilder {
@@ -50,11 +187,44 @@ impl AgentBuilder {
         self
     }
 
+    pub(crate) fn max_concurrent_streams(mut self, max: usize) -> Self {
+        self.max_concurrent_streams = max;
+        self
+    }
+
+    pub(crate) fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = max;
+        self
+    }
+
     pub(crate) fn connection_cache_size(mut self, size: usize) -> Self {
         self.connection_cache_size = size;
         self
     }
 
+    pub(crate) fn enable_push(mut self, enable: bool) -> Self {
+        self.enable_push = enable;
+        self
+    }
+
+    /// Pool connections in a share handle instead of in the agent, which
+    /// prevents the agent from keeping track of them.
+    pub(crate) fn shared_connections(mut self, shared: bool) -> Self {
+        self.shared_connections = shared;
+        self
+    }
+
+    /// Get the pool of curl handles that agents spawned by this builder return
+    /// the handles of completed requests to.
+    pub(crate) fn handle_pool(&self) -> &HandlePool {
+        &self.handle_pool
+    }
+
+    /// Check whether agents spawned by this builder accept pushed responses.
+    pub(crate) fn is_push_enabled(&self) -> bool {
+        self.enable_push
+    }
+
     /// Spawn a new agent using the configuration in this builder and return a
     /// handle for communicating with the agent.
     pub(crate) fn spawn(&self) -> io::Result<Handle> {
@@ -85,9 +255,17 @@ impl AgentBuilder {
         let wait_group = WaitGroup::new();
         let wait_group_thread = wait_group.clone();
 
+        let stats = Arc::new(AgentStatsInner::default());
+        let stats_thread = stats.clone();
+
         let max_connections = self.max_connections;
         let max_connections_per_host = self.max_connections_per_host;
+        let max_concurrent_streams = self.max_concurrent_streams;
+        let max_concurrent_requests = self.max_concurrent_requests;
         let connection_cache_size = self.connection_cache_size;
+        let enable_push = self.enable_push;
+        let shared_connections = self.shared_connections;
+        let handle_pool = self.handle_pool.clone();
 
         // Create a span for the agent thread that outlives this method call,
         // but rather was caused by it.
@@ -97,6 +275,7 @@ impl AgentBuilder {
         let handle = Handle {
             message_tx: message_tx.clone(),
             waker: waker.clone(),
+            stats,
             join_handle: Mutex::new(Some(
                 thread::Builder::new()
                     .name(format!("isahc-agent-{}", port))
@@ -104,6 +283,8 @@ impl AgentBuilder {
                         let _enter = agent_span.enter();
                         let mut multi = curl::multi::Multi::new();
 
+                        set_quick_exit(&multi)?;
+
                         if max_connections > 0 {
                             multi.set_max_total_connections(max_connections)?;
                         }
@@ -112,22 +293,46 @@ impl AgentBuilder {
                             multi.set_max_host_connections(max_connections_per_host)?;
                         }
 
+                        if max_concurrent_streams > 0 {
+                            multi.set_max_concurrent_streams(max_concurrent_streams)?;
+                        }
+
                         // Only set maxconnects if greater than 0, because 0 actually means unlimited.
                         if connection_cache_size > 0 {
                             multi.set_max_connects(connection_cache_size)?;
                         }
 
-                        let agent = AgentContext {
+                        let mut agent = AgentContext {
                             multi,
                             multi_messages: flume::unbounded(),
                             message_tx,
                             message_rx,
                             wake_socket,
                             requests: Slab::new(),
+                            request_wakers: Vec::new(),
+                            handle_pool,
+                            queued_requests: VecDeque::new(),
+                            max_concurrent_requests,
+                            deadlines: Deadlines::default(),
+                            queue_deadlines: Deadlines::default(),
+                            headers_deadlines: Deadlines::default(),
                             close_requested: false,
                             waker,
+                            stats: stats_thread,
+                            last_upkeep: Instant::now(),
+                            pushes: Box::default(),
+                            partitions: Partitions::default(),
+                            connections: if shared_connections {
+                                None
+                            } else {
+                                Some(Box::default())
+                            },
                         };
 
+                        if enable_push {
+                            agent.pushes.register(&agent.multi)?;
+                        }
+
                         drop(wait_group_thread);
 
                         tracing::debug!("agent took {:?} to start up", create_start.elapsed());
@@ -162,6 +367,9 @@ pub(crate) struct Handle {
     /// A waker that can wake up the agent thread while it is polling.
     waker: Waker,
 
+    /// Statistics recorded by the agent thread.
+    stats: Arc<AgentStatsInner>,
+
     /// A join handle for the agent thread.
     join_handle: Mutex<Option<thread::JoinHandle<Result<(), Error>>>>,
 }
@@ -187,14 +395,324 @@ struct AgentContext {
     /// Used to wake up the agent when polling.
     wake_socket: UdpSocket,
 
-    /// Contains all of the active requests.
-    requests: Slab<curl::multi::Easy2Handle<RequestHandler>>,
+    /// Contains all of the active transfers.
+    requests: Slab<Transfer>,
+
+    /// Wakers for the requests in each slot of `requests`, created the first
+    /// time the slot is used and shared by every request stored in it since.
+    request_wakers: Vec<RequestWakers>,
+
+    /// Where the handles of completed requests are returned to.
+    handle_pool: HandlePool,
+
+    /// Requests waiting for the number of active transfers to drop below the
+    /// limit before they are started, in the order they were submitted.
+    queued_requests: VecDeque<EasyHandle>,
+
+    /// The maximum number of active transfers, or zero if unlimited.
+    max_concurrent_requests: usize,
+
+    /// Deadlines of the active requests that have a timeout.
+    deadlines: Deadlines,
+
+    /// Deadlines for curl to start the active requests that have a queue
+    /// timeout.
+    queue_deadlines: Deadlines,
+
+    /// Deadlines for the responses of the active requests that have a
+    /// headers timeout to start arriving.
+    headers_deadlines: Deadlines,
 
     /// Indicates if the thread has been requested to stop.
     close_requested: bool,
 
     /// A waker that can wake up the agent thread while it is polling.
     waker: Waker,
+
+    /// Statistics about the event loop, shared with agent handles.
+    stats: Arc<AgentStatsInner>,
+
+    /// When curl last performed upkeep on cached connections.
+    last_upkeep: Instant,
+
+    /// Responses being pushed by servers, if accepted. Curl holds a pointer
+    /// to them, so they are boxed.
+    pushes: Box<Pushes>,
+
+    /// Connection pools of requests with a connection pool partition key.
+    /// Dropped before the connections they hold are no longer tracked.
+    partitions: Partitions,
+
+    /// The connections kept open by curl, unless they are pooled in a share
+    /// handle. Curl holds a pointer to them until the multi handle is
+    /// dropped, so they are boxed and dropped after it.
+    connections: Option<Box<Connections>>,
+}
+
+/// Wakers given to the handler of a request, which resume or abort the request
+/// with the same ID.
+#[derive(Clone)]
+struct RequestWakers {
+    read: Waker,
+    write: Waker,
+    abandoned: Waker,
+}
+
+impl RequestWakers {
+    fn new(waker: &Waker, message_tx: &Sender<Message>, id: usize) -> Self {
+        Self {
+            read: {
+                let tx = message_tx.clone();
+
+                waker.chain(move |inner| match tx.send(Message::UnpauseRead(id)) {
+                    Ok(()) => inner.wake_by_ref(),
+                    Err(_) => tracing::warn!(
+                        "agent went away while resuming read for request [id={}]",
+                        id
+                    ),
+                })
+            },
+            write: {
+                let tx = message_tx.clone();
+
+                waker.chain(move |inner| match tx.send(Message::UnpauseWrite(id)) {
+                    Ok(()) => inner.wake_by_ref(),
+                    Err(_) => tracing::warn!(
+                        "agent went away while resuming write for request [id={}]",
+                        id
+                    ),
+                })
+            },
+            abandoned: {
+                let tx = message_tx.clone();
+
+                waker.chain(move |inner| match tx.send(Message::Abandoned(id)) {
+                    Ok(()) => inner.wake_by_ref(),
+                    Err(_) => {
+                        tracing::debug!("agent went away before request was abandoned [id={}]", id)
+                    }
+                })
+            },
+        }
+    }
+}
+
+/// Curl handles of completed requests, which can be reused by later requests
+/// instead of creating new ones.
+///
+/// Creating a curl handle takes a number of allocations, both in curl and for
+/// the box that holds the request handler. A handle that is returned to the
+/// pool has been reset, and its handler no longer refers to the request it
+/// completed.
+#[derive(Clone, Default)]
+pub(crate) struct HandlePool(Arc<Mutex<Vec<EasyHandle>>>);
+
+impl HandlePool {
+    /// Take an idle handle out of the pool, if there is one.
+    pub(crate) fn take(&self) -> Option<EasyHandle> {
+        self.0.lock().unwrap().pop()
+    }
+
+    /// Return the handle of a completed request to the pool, if it can be
+    /// reused.
+    fn put(&self, mut handle: EasyHandle) {
+        if !handle.get_ref().is_reusable() || self.0.lock().unwrap().len() >= MAX_IDLE_HANDLES {
+            return;
+        }
+
+        handle.reset();
+        handle.get_mut().release();
+
+        self.0.lock().unwrap().push(handle);
+    }
+}
+
+impl fmt::Debug for HandlePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlePool")
+            .field("idle", &self.0.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// Deadlines by which requests must complete, ordered by time.
+///
+/// Entries are not removed when a request completes early. Instead, an expired
+/// entry is ignored if the request it refers to has already completed, which
+/// is detected by comparing the deadline with the one stored in the request.
+#[derive(Debug, Default)]
+struct Deadlines(BTreeSet<(Instant, usize)>);
+
+impl Deadlines {
+    fn insert(&mut self, deadline: Instant, token: usize) {
+        self.0.insert((deadline, token));
+    }
+
+    /// Remove and return all deadlines that have passed at the given instant.
+    fn pop_expired(&mut self, now: Instant) -> BTreeSet<(Instant, usize)> {
+        let pending = self.0.split_off(&(now, usize::MAX));
+
+        std::mem::replace(&mut self.0, pending)
+    }
+}
+
+/// Don't wait for name resolutions still running in the background when the
+/// agent shuts down, since a lookup may hang for a long time.
+#[allow(unsafe_code)]
+fn set_quick_exit(multi: &curl::multi::Multi) -> Result<(), Error> {
+    // Not yet defined by curl-sys.
+    const CURLMOPT_QUICK_EXIT: curl_sys::CURLMoption = curl_sys::CURLOPTTYPE_LONG + 21;
+
+    let value: std::os::raw::c_long = 1;
+
+    unsafe {
+        match curl_sys::curl_multi_setopt(multi.raw(), CURLMOPT_QUICK_EXIT, value) {
+            // Older versions of curl always wait.
+            curl_sys::CURLM_OK | curl_sys::CURLM_UNKNOWN_OPTION => Ok(()),
+            code => Err(curl::MultiError::new(code).into()),
+        }
+    }
+}
+
+fn queue_timeout_error() -> Error {
+    Error::new(
+        ErrorKind::QueueTimeout,
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            "request was not started within the queue timeout",
+        ),
+    )
+}
+
+fn headers_timeout_error() -> Error {
+    Error::new(
+        ErrorKind::HeadersTimeout,
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            "no response was received within the headers timeout",
+        ),
+    )
+}
+
+/// An active transfer being executed by the agent.
+enum Transfer {
+    /// An HTTP request sent by a client.
+    Request(curl::multi::Easy2Handle<RequestHandler>),
+
+    /// A custom transfer submitted through the public agent API.
+    Custom(Box<dyn ActiveTransfer>),
+}
+
+/// A curl easy handle that can be executed as a custom transfer.
+trait Attach: Send + Sized + 'static {
+    /// The handle type returned when added to a multi handle.
+    type Handle;
+
+    /// Add the easy handle to the given multi handle.
+    fn attach(self, multi: &curl::multi::Multi, token: usize) -> Result<Self::Handle, Error>;
+
+    /// Remove the easy handle from the given multi handle.
+    fn detach(multi: &curl::multi::Multi, handle: Self::Handle) -> Result<Self, Error>;
+}
+
+impl Attach for curl::easy::Easy {
+    type Handle = curl::multi::EasyHandle;
+
+    fn attach(self, multi: &curl::multi::Multi, token: usize) -> Result<Self::Handle, Error> {
+        let mut handle = multi.add(self)?;
+        handle.set_token(token)?;
+
+        Ok(handle)
+    }
+
+    fn detach(multi: &curl::multi::Multi, handle: Self::Handle) -> Result<Self, Error> {
+        Ok(multi.remove(handle)?)
+    }
+}
+
+impl<H: curl::easy::Handler + Send + 'static> Attach for curl::easy::Easy2<H> {
+    type Handle = curl::multi::Easy2Handle<H>;
+
+    fn attach(self, multi: &curl::multi::Multi, token: usize) -> Result<Self::Handle, Error> {
+        let mut handle = multi.add2(self)?;
+        handle.set_token(token)?;
+
+        Ok(handle)
+    }
+
+    fn detach(multi: &curl::multi::Multi, handle: Self::Handle) -> Result<Self, Error> {
+        Ok(multi.remove2(handle)?)
+    }
+}
+
+/// A custom transfer, along with where to send the easy handle back to once
+/// complete.
+struct CustomTransfer<E, T> {
+    easy: T,
+    sender: Sender<Result<E, Error>>,
+}
+
+/// A custom transfer that has been submitted, but not yet started.
+trait PendingTransfer: Send {
+    /// Start the transfer, or report back to the submitter if it cannot be
+    /// started.
+    fn start(
+        self: Box<Self>,
+        multi: &curl::multi::Multi,
+        token: usize,
+    ) -> Option<Box<dyn ActiveTransfer>>;
+}
+
+/// A custom transfer that is currently in progress.
+trait ActiveTransfer {
+    /// Complete the transfer and send the easy handle back to the submitter.
+    fn complete(
+        self: Box<Self>,
+        multi: &curl::multi::Multi,
+        result: Result<(), curl::Error>,
+    ) -> Result<(), Error>;
+}
+
+impl fmt::Debug for dyn PendingTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingTransfer").finish()
+    }
+}
+
+impl<E: Attach> PendingTransfer for CustomTransfer<E, E> {
+    fn start(
+        self: Box<Self>,
+        multi: &curl::multi::Multi,
+        token: usize,
+    ) -> Option<Box<dyn ActiveTransfer>> {
+        match self.easy.attach(multi, token) {
+            Ok(handle) => Some(Box::new(CustomTransfer::<E, _> {
+                easy: handle,
+                sender: self.sender,
+            })),
+            Err(e) => {
+                let _ = self.sender.send(Err(e));
+                None
+            }
+        }
+    }
+}
+
+impl<E: Attach> ActiveTransfer for CustomTransfer<E, E::Handle> {
+    fn complete(
+        self: Box<Self>,
+        multi: &curl::multi::Multi,
+        result: Result<(), curl::Error>,
+    ) -> Result<(), Error> {
+        let easy = E::detach(multi, self.easy)?;
+        let result = result.map(|()| easy).map_err(Error::from);
+
+        if self.sender.send(result).is_err() {
+            tracing::debug!("transfer future dropped before completion");
+        }
+
+        Ok(())
+    }
 }
 
 /// A message sent from the main thread to the agent thread.
@@ -206,6 +724,9 @@ enum Message {
     /// Begin executing a new request.
     Execute(EasyHandle),
 
+    /// Begin executing a custom transfer.
+    Transfer(Box<dyn PendingTransfer>),
+
     /// Request to resume reading the request body for the request with the
     /// given ID.
     UnpauseRead(usize),
@@ -213,6 +734,13 @@ enum Message {
     /// Request to resume writing the response body for the request with the
     /// given ID.
     UnpauseWrite(usize),
+
+    /// The response future or body of the request with the given ID was
+    /// dropped before the transfer completed.
+    Abandoned(usize),
+
+    /// Reply with a snapshot of the connection pool.
+    QueryStats(Sender<PoolStats>),
 }
 
 #[derive(Debug)]
@@ -229,6 +757,40 @@ impl Handle {
         self.send_message(Message::Execute(request))
     }
 
+    /// Begin executing a custom transfer with this agent.
+    fn submit_transfer<E: Attach>(&self, easy: E) -> TransferFuture<E> {
+        let (sender, receiver) = flume::bounded(1);
+        let transfer = Box::new(CustomTransfer::<E, E> {
+            easy,
+            sender: sender.clone(),
+        });
+
+        if let Err(e) = self.send_message(Message::Transfer(transfer)) {
+            let _ = sender.send(Err(e));
+        }
+
+        TransferFuture {
+            receiver: receiver.into_recv_async(),
+        }
+    }
+
+    /// Get a snapshot of statistics about the agent thread.
+    pub(crate) fn stats(&self) -> AgentStats {
+        self.stats.snapshot(self.message_tx.len())
+    }
+
+    /// Get a snapshot of the connection pool, gathered by the agent thread.
+    pub(crate) async fn pool_stats(&self) -> Result<PoolStats, Error> {
+        let (sender, receiver) = flume::bounded(1);
+
+        self.send_message(Message::QueryStats(sender))?;
+
+        receiver
+            .recv_async()
+            .await
+            .map_err(|e| Error::new(ErrorKind::Unknown, e))
+    }
+
     /// Send a message to the agent thread.
     ///
     /// If the agent is not connected, an error is returned.
@@ -280,63 +842,313 @@ impl Drop for Handle {
 }
 
 impl AgentContext {
+    /// Start a request submitted by a client, unless too many requests are
+    /// active already, in which case it waits for its turn in a queue.
     #[tracing::instrument(level = "trace", skip(self))]
     fn begin_request(&mut self, mut request: EasyHandle) -> Result<(), Error> {
+        // Keep the connections of a partitioned request apart from those of
+        // other requests. The handler keeps the share handle alive.
+        if let Some(key) = request.get_mut().take_pool_partition() {
+            let result = self.partitions.get(&key).and_then(|share| {
+                share.set_opt(&mut request)?;
+                request.get_mut().set_share_handle(share);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                request.get_mut().set_result(Err(e));
+
+                return Ok(());
+            }
+        }
+
+        if self.max_concurrent_requests == 0 {
+            return self.start_request(request);
+        }
+
+        self.queued_requests.push_back(request);
+        self.start_queued_requests()
+    }
+
+    /// Start as many queued requests as the concurrent request limit allows,
+    /// oldest first.
+    fn start_queued_requests(&mut self) -> Result<(), Error> {
+        while self.requests.len() < self.max_concurrent_requests {
+            match self.queued_requests.pop_front() {
+                Some(request) => self.start_request(request)?,
+                None => break,
+            }
+        }
+
+        self.stats.set_queued_requests(self.queued_requests.len());
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn start_request(&mut self, mut request: EasyHandle) -> Result<(), Error> {
+        // Don't bother starting a request that already spent its entire queue
+        // timeout waiting for the agent.
+        let queue_deadline = request.get_ref().queue_deadline();
+
+        if queue_deadline.map(|deadline| deadline <= Instant::now()) == Some(true) {
+            tracing::debug!("request was not started within its queue timeout");
+            request.get_mut().set_result(Err(queue_timeout_error()));
+
+            return Ok(());
+        }
+
         // Prepare an entry for storing this request while it executes.
         let entry = self.requests.vacant_entry();
         let id = entry.key();
         let handle = request.raw();
 
-        // Initialize the handler.
-        request.get_mut().init(
-            id,
-            handle,
-            {
-                let tx = self.message_tx.clone();
-
-                self.waker
-                    .chain(move |inner| match tx.send(Message::UnpauseRead(id)) {
-                        Ok(()) => inner.wake_by_ref(),
-                        Err(_) => tracing::warn!(
-                            "agent went away while resuming read for request [id={}]",
-                            id
-                        ),
-                    })
-            },
-            {
-                let tx = self.message_tx.clone();
-
-                self.waker
-                    .chain(move |inner| match tx.send(Message::UnpauseWrite(id)) {
-                        Ok(()) => inner.wake_by_ref(),
-                        Err(_) => tracing::warn!(
-                            "agent went away while resuming write for request [id={}]",
-                            id
-                        ),
-                    })
-            },
-        );
+        if let Some(connections) = &mut self.connections {
+            connections.watch(handle)?;
+        }
+
+        // Initialize the handler. Slots are filled in order, so the wakers
+        // for this one are created right after those of the previous slots.
+        while self.request_wakers.len() <= id {
+            let slot = self.request_wakers.len();
+
+            self.request_wakers
+                .push(RequestWakers::new(&self.waker, &self.message_tx, slot));
+        }
+
+        let wakers = self.request_wakers[id].clone();
+
+        request
+            .get_mut()
+            .init(id, handle, wakers.read, wakers.write, wakers.abandoned);
+
+        // Curl enforces the timeout of the request itself, but keep track of
+        // it independently as well in case curl fails to report it.
+        if let Some(timeout) = request.get_ref().timeout() {
+            let deadline = Instant::now() + timeout + FORCED_TIMEOUT_GRACE;
+
+            request.get_mut().set_deadline(deadline);
+            self.deadlines.insert(deadline, id);
+        }
+
+        if let Some(deadline) = queue_deadline {
+            self.queue_deadlines.insert(deadline, id);
+        }
+
+        if let Some(timeout) = request.get_ref().headers_timeout() {
+            let deadline = Instant::now() + timeout;
+
+            request.get_mut().set_headers_deadline(deadline);
+            self.headers_deadlines.insert(deadline, id);
+        }
 
         // Register the request with curl.
         let mut handle = self.multi.add2(request)?;
         handle.set_token(id)?;
 
         // Add the handle to our bookkeeping structure.
-        entry.insert(handle);
+        entry.insert(Transfer::Request(handle));
 
         Ok(())
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    fn complete_request(
+    fn begin_transfer(&mut self, transfer: Box<dyn PendingTransfer>) {
+        let entry = self.requests.vacant_entry();
+
+        // A transfer that cannot be started is reported back to the caller
+        // instead of shutting down the agent.
+        if let Some(transfer) = transfer.start(&self.multi, entry.key()) {
+            entry.insert(Transfer::Custom(transfer));
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn complete_transfer(
         &mut self,
         token: usize,
         result: Result<(), curl::Error>,
     ) -> Result<(), Error> {
-        let handle = self.requests.remove(token);
+        if Pushes::is_push_token(token) {
+            self.pushes.complete(token, result);
+
+            return Ok(());
+        }
+
+        match self.requests.remove(token) {
+            Transfer::Request(handle) => self.complete_request(handle, result),
+            Transfer::Custom(transfer) => transfer.complete(&self.multi, result),
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip(self, handle))]
+    fn complete_request(
+        &mut self,
+        handle: curl::multi::Easy2Handle<RequestHandler>,
+        result: Result<(), curl::Error>,
+    ) -> Result<(), Error> {
+        if let Some(connections) = &mut self.connections {
+            connections.record(handle.raw());
+        }
+
         let mut handle = self.multi.remove2(handle)?;
 
-        handle.get_mut().set_result(result.map_err(Error::from));
+        if let Err(e) = &result {
+            // Curl reports the number of new connections that had to be
+            // created for this transfer, so zero means a cached connection
+            // was reused.
+            let connection_reused = handle.num_connects().map(|n| n == 0).unwrap_or(false);
+
+            if handle.get_mut().prepare_stale_connection_retry(e, connection_reused) {
+                // The retry takes the place of the failed attempt, so it
+                // does not queue up again.
+                return self.start_request(handle);
+            }
+        }
+
+        let result = result.map_err(|mut error| {
+            if error.is_operation_timedout() {
+                // Errors reported through the multi interface lack curl's
+                // detailed message, which is needed to tell the timeouts
+                // apart.
+                if error.extra_description().is_none() {
+                    if let Some(message) = handle.take_error_buf() {
+                        error.set_extra(message);
+                    }
+                }
+
+                // Curl does not tell us which timeout was reached, so figure
+                // it out from how far the transfer got.
+                let connected = handle
+                    .connect_time()
+                    .map(|time| time > Duration::from_secs(0))
+                    .unwrap_or(false);
+
+                Error::from_timeout(
+                    error,
+                    connected,
+                    handle.upload_size().unwrap_or(0.0) as u64,
+                    handle.download_size().unwrap_or(0.0) as u64,
+                )
+            } else {
+                Error::from(error)
+            }
+        });
+
+        handle.get_mut().set_result(result);
+        self.handle_pool.put(handle);
+
+        Ok(())
+    }
+
+    /// Abort any requests that are still running well after their timeout
+    /// elapsed, which only happens if curl failed to notice.
+    fn enforce_deadlines(&mut self) -> Result<(), Error> {
+        let now = Instant::now();
+
+        for (deadline, token) in self.deadlines.pop_expired(now) {
+            let expired = match self.requests.get(token) {
+                Some(Transfer::Request(handle)) => handle.get_ref().deadline() == Some(deadline),
+                _ => false,
+            };
+
+            if !expired {
+                continue;
+            }
+
+            tracing::warn!(
+                "request [id={}] exceeded its timeout without curl reporting it, aborting",
+                token
+            );
+
+            self.stats.record_forced_timeout();
+
+            if let Transfer::Request(handle) = self.requests.remove(token) {
+                let mut error = curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT);
+                error.set_extra(String::from("timeout enforced by the agent"));
+
+                self.complete_request(handle, Err(error))?;
+            }
+        }
+
+        // Curl keeps requests pending while connection limits are reached and
+        // only times them out as if connecting took too long, so abort those
+        // that waited longer than allowed with an error saying so.
+        for (deadline, token) in self.queue_deadlines.pop_expired(now) {
+            let expired = match self.requests.get(token) {
+                Some(Transfer::Request(handle)) => {
+                    handle.get_ref().queue_deadline() == Some(deadline)
+                }
+                _ => false,
+            };
+
+            if !expired {
+                continue;
+            }
+
+            tracing::debug!(
+                "request [id={}] was not started within its queue timeout, aborting",
+                token
+            );
+
+            if let Transfer::Request(mut handle) = self.requests.remove(token) {
+                handle.get_mut().set_result(Err(queue_timeout_error()));
+
+                let error = curl::Error::new(curl_sys::CURLE_ABORTED_BY_CALLBACK);
+                self.complete_request(handle, Err(error))?;
+            }
+        }
+
+        // Curl has no timeout for waiting on the server to respond, so abort
+        // requests whose response did not start to arrive in time ourselves.
+        for (deadline, token) in self.headers_deadlines.pop_expired(now) {
+            let expired = match self.requests.get(token) {
+                Some(Transfer::Request(handle)) => {
+                    handle.get_ref().headers_deadline() == Some(deadline)
+                }
+                _ => false,
+            };
+
+            if !expired {
+                continue;
+            }
+
+            tracing::debug!(
+                "request [id={}] received no response within its headers timeout, aborting",
+                token
+            );
+
+            if let Transfer::Request(mut handle) = self.requests.remove(token) {
+                handle.get_mut().set_result(Err(headers_timeout_error()));
+
+                let error = curl::Error::new(curl_sys::CURLE_ABORTED_BY_CALLBACK);
+                self.complete_request(handle, Err(error))?;
+            }
+        }
+
+        // Requests waiting in our own queue are not known to curl yet, so
+        // they are checked here instead.
+        let mut i = 0;
+
+        while i < self.queued_requests.len() {
+            let request = &mut self.queued_requests[i];
+
+            if request.get_ref().queue_deadline().map(|d| d <= now) == Some(true) {
+                tracing::debug!("queued request was not started within its queue timeout");
+                request.get_mut().set_result(Err(queue_timeout_error()));
+            } else if request.get_mut().is_future_canceled() {
+                tracing::debug!("response future dropped, removing queued request");
+
+                if let Some(canceled_at) = request.get_ref().canceled_at() {
+                    self.stats.record_canceled_transfer(canceled_at.elapsed());
+                }
+            } else {
+                i += 1;
+                continue;
+            }
+
+            self.queued_requests.remove(i);
+        }
 
         Ok(())
     }
@@ -363,13 +1175,20 @@ impl AgentContext {
 
     /// Polls the message channel for new messages from any agent handles.
     ///
-    /// If there are no active requests right now, this function will block
-    /// until a message is received.
+    /// If there are no active requests or pushed transfers right now, this
+    /// function will block until a message is received. Returns the amount of
+    /// time spent blocked.
     #[tracing::instrument(level = "trace", skip(self))]
-    fn poll_messages(&mut self) -> Result<(), Error> {
+    fn poll_messages(&mut self) -> Result<Duration, Error> {
+        let mut idle = Duration::from_secs(0);
+
         while !self.close_requested {
-            if self.requests.is_empty() {
-                match self.message_rx.recv() {
+            if self.requests.is_empty() && self.pushes.is_empty() {
+                let recv_start = Instant::now();
+                let result = self.message_rx.recv();
+                idle += recv_start.elapsed();
+
+                match result {
                     Ok(message) => self.handle_message(message)?,
                     _ => {
                         tracing::warn!("agent handle disconnected without close message");
@@ -390,7 +1209,7 @@ impl AgentContext {
             }
         }
 
-        Ok(())
+        Ok(idle)
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
@@ -400,8 +1219,9 @@ impl AgentContext {
         match message {
             Message::Close => self.close_requested = true,
             Message::Execute(request) => self.begin_request(request)?,
+            Message::Transfer(transfer) => self.begin_transfer(transfer),
             Message::UnpauseRead(token) => {
-                if let Some(request) = self.requests.get(token) {
+                if let Some(Transfer::Request(request)) = self.requests.get(token) {
                     if let Err(e) = request.unpause_read() {
                         // If unpausing returned an error, it is likely because
                         // curl called our callback inline and the callback
@@ -419,8 +1239,28 @@ impl AgentContext {
                     );
                 }
             }
+            Message::Abandoned(token) => self.abort_abandoned_request(token)?,
+            Message::QueryStats(sender) => {
+                let mut stats = match &self.connections {
+                    Some(connections) => {
+                        connections.stats(self.requests.iter().filter_map(|(_, transfer)| {
+                            match transfer {
+                                Transfer::Request(handle) => Some(handle.raw()),
+                                Transfer::Custom(_) => None,
+                            }
+                        }))
+                    }
+                    None => PoolStats::default(),
+                };
+
+                // Custom transfers are not tracked, but still count as
+                // active transfers.
+                stats.active_transfers = self.requests.len();
+
+                let _ = sender.send(stats);
+            }
             Message::UnpauseWrite(token) => {
-                if let Some(request) = self.requests.get(token) {
+                if let Some(Transfer::Request(request)) = self.requests.get(token) {
                     if let Err(e) = request.unpause_write() {
                         // If unpausing returned an error, it is likely because
                         // curl called our callback inline and the callback
@@ -443,10 +1283,63 @@ impl AgentContext {
         Ok(())
     }
 
+    /// Abort the request with the given ID if nobody is interested in the
+    /// rest of the response anymore.
+    fn abort_abandoned_request(&mut self, token: usize) -> Result<(), Error> {
+        // The request may have completed in the meantime, and its ID may even
+        // have been reused by another request.
+        let request = match self.requests.get_mut(token) {
+            Some(Transfer::Request(request)) => request,
+            _ => return Ok(()),
+        };
+
+        let (error, canceled_at) = if request.get_ref().is_response_body_dropped() {
+            tracing::debug!(
+                "response body dropped before the transfer completed, aborting request [id={}]",
+                token
+            );
+
+            self.stats.record_reaped_transfer();
+
+            (curl_sys::CURLE_WRITE_ERROR, None)
+        } else if request.get_mut().is_future_canceled() {
+            tracing::debug!("response future dropped, aborting request [id={}]", token);
+
+            (
+                curl_sys::CURLE_ABORTED_BY_CALLBACK,
+                request.get_ref().canceled_at(),
+            )
+        } else {
+            return Ok(());
+        };
+
+        match self.requests.remove(token) {
+            Transfer::Request(handle) => {
+                self.complete_request(handle, Err(curl::Error::new(error)))?;
+            }
+            Transfer::Custom(_) => unreachable!(),
+        }
+
+        if let Some(canceled_at) = canceled_at {
+            self.stats.record_canceled_transfer(canceled_at.elapsed());
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     fn dispatch(&mut self) -> Result<(), Error> {
         self.multi.perform()?;
 
+        // Pushed responses are handed to the requests they belong to while
+        // those are still known to be active.
+        let requests = &self.requests;
+
+        self.pushes.claim(|token| match requests.get(token) {
+            Some(Transfer::Request(handle)) => handle.get_ref().push_sender(),
+            _ => None,
+        });
+
         // Collect messages from curl about requests that have completed,
         // whether successfully or with an error.
         self.multi.messages(|message| {
@@ -460,7 +1353,7 @@ impl AgentContext {
         loop {
             match self.multi_messages.1.try_recv() {
                 // A request completed.
-                Ok((token, result)) => self.complete_request(token, result)?,
+                Ok((token, result)) => self.complete_transfer(token, result)?,
                 Err(flume::TryRecvError::Empty) => break,
                 Err(flume::TryRecvError::Disconnected) => unreachable!(),
             }
@@ -469,6 +1362,45 @@ impl AgentContext {
         Ok(())
     }
 
+    /// Have curl send keepalive pings on cached connections, if any active
+    /// request asked for them.
+    ///
+    /// Curl performs upkeep on the connection cache of the multi handle using
+    /// the upkeep interval of the transfer it is called with, and only pings
+    /// connections that have been quiet for that long. Upkeep therefore needs
+    /// an active request to be called with, and the one with the shortest
+    /// interval is used.
+    #[allow(unsafe_code)]
+    fn upkeep(&mut self) {
+        let request = self
+            .requests
+            .iter()
+            .filter_map(|(_, transfer)| match transfer {
+                Transfer::Request(handle) => handle
+                    .get_ref()
+                    .upkeep_interval()
+                    .map(|interval| (interval, handle.raw())),
+                Transfer::Custom(_) => None,
+            })
+            .min_by_key(|(interval, _)| *interval);
+
+        if let Some((interval, raw)) = request {
+            let now = Instant::now();
+
+            if now >= self.last_upkeep + interval {
+                self.last_upkeep = now;
+
+                // Use unsafe API, because the safe API is not available for
+                // handles owned by a multi handle.
+                let code = unsafe { curl_sys::curl_easy_upkeep(raw) };
+
+                if code != curl_sys::CURLE_OK {
+                    tracing::debug!("connection upkeep failed: {}", curl::Error::new(code));
+                }
+            }
+        }
+    }
+
     /// Run the agent in the current thread until requested to stop.
     fn run(mut self) -> Result<(), Error> {
         let mut wait_fds = self.get_wait_fds();
@@ -478,7 +1410,8 @@ impl AgentContext {
 
         // Agent main loop.
         loop {
-            self.poll_messages()?;
+            let iteration_start = Instant::now();
+            let idle = self.poll_messages()?;
 
             if self.close_requested {
                 break;
@@ -486,9 +1419,29 @@ impl AgentContext {
 
             // Perform any pending reads or writes and handle any state changes.
             self.dispatch()?;
+            self.enforce_deadlines()?;
+            self.start_queued_requests()?;
+            self.upkeep();
+
+            let loop_latency = iteration_start.elapsed() - idle;
 
             // Block until activity is detected or the timeout passes.
+            let wait_start = Instant::now();
             self.multi.wait(&mut wait_fds, WAIT_TIMEOUT)?;
+            let poll_wait = wait_start.elapsed();
+
+            self.stats
+                .record_iteration(loop_latency, poll_wait, self.requests.len());
+
+            tracing::trace!(
+                target: "isahc::agent::stats",
+                ?loop_latency,
+                ?poll_wait,
+                queue_depth = self.message_rx.len(),
+                queued_requests = self.queued_requests.len(),
+                active_transfers = self.requests.len(),
+                "agent loop iteration complete",
+            );
 
             // We might have woken up early from the notify fd, so drain the
             // socket to clear it.
@@ -506,6 +1459,7 @@ impl AgentContext {
         tracing::debug!("agent shutting down");
 
         self.requests.clear();
+        self.queued_requests.clear();
 
         Ok(())
     }
@@ -517,4 +1471,27 @@ mod tests {
 
     static_assertions::assert_impl_all!(Handle: Send, Sync);
     static_assertions::assert_impl_all!(Message: Send);
+    static_assertions::assert_impl_all!(Agent: Send, Sync);
+    static_assertions::assert_impl_all!(TransferFuture: Send);
+
+    #[test]
+    fn expired_deadlines_are_popped_in_order() {
+        let now = Instant::now();
+        let mut deadlines = Deadlines::default();
+
+        deadlines.insert(now + Duration::from_secs(2), 1);
+        deadlines.insert(now, 2);
+        deadlines.insert(now + Duration::from_secs(5), 3);
+        deadlines.insert(now + Duration::from_secs(1), 4);
+
+        let expired = deadlines
+            .pop_expired(now + Duration::from_secs(2))
+            .into_iter()
+            .map(|(_, token)| token)
+            .collect::<Vec<_>>();
+
+        assert_eq!(expired, vec![2, 4, 1]);
+        assert_eq!(deadlines.0.len(), 1);
+        assert!(deadlines.pop_expired(now).is_empty());
+    }
 }
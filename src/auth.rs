@@ -10,8 +10,8 @@ use std::{
 /// used to establish user identity.
 #[derive(Clone)]
 pub struct Credentials {
-    username: String,
-    password: String,
+    pub(crate) username: String,
+    pub(crate) password: String,
 }
 
 impl Credentials {
@@ -75,6 +75,11 @@ impl Authentication {
             all |= Self::negotiate().0;
         }
 
+        #[cfg(feature = "ntlm")]
+        {
+            all |= Self::ntlm().0;
+        }
+
         Authentication(all)
     }
 
@@ -116,7 +121,24 @@ impl Authentication {
         Authentication(0b0100)
     }
 
-    const fn contains(&self, other: Self) -> bool {
+    /// NTLM authentication.
+    ///
+    /// NTLM is a proprietary challenge-response protocol by Microsoft, mostly
+    /// found on Windows servers and corporate proxies. It authenticates a
+    /// connection rather than a single request, so connections are not
+    /// reused for other credentials. Prefer Negotiate authentication where
+    /// the server supports it.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the [`ntlm`](../index.html#ntlm)
+    /// feature is enabled.
+    #[cfg(feature = "ntlm")]
+    pub const fn ntlm() -> Self {
+        Authentication(0b1000)
+    }
+
+    pub(crate) const fn contains(&self, other: Self) -> bool {
         (self.0 & other.0) == other.0
     }
 
@@ -138,6 +160,13 @@ impl Authentication {
             }
         }
 
+        #[cfg(feature = "ntlm")]
+        {
+            if self.contains(Authentication::ntlm()) {
+                auth.ntlm(true);
+            }
+        }
+
         auth
     }
 }
@@ -0,0 +1,242 @@
+use futures_lite::{
+    future::{block_on, poll_fn, Future},
+    io::{AsyncRead, AsyncWrite},
+    ready,
+    stream::Stream,
+};
+use std::{
+    fmt,
+    io::{self, Cursor, Read, Write},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// Maximum number of chunks that may be queued in a body channel before the
+/// sender must wait for the receiver to catch up.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Create a new connected sink and reader pair.
+pub(super) fn channel() -> (BodySink, ChannelReader) {
+    let (sender, receiver) = flume::bounded(CHANNEL_CAPACITY);
+    let closed = Arc::new(AtomicBool::new(false));
+
+    let sink = BodySink {
+        sender,
+        closed: closed.clone(),
+    };
+
+    let reader = ChannelReader {
+        receiver: receiver.into_stream(),
+        chunk: Cursor::new(Vec::new()),
+        closed,
+    };
+
+    (sink, reader)
+}
+
+/// The sending half of a request body created with
+/// [`AsyncBody::channel`](super::AsyncBody::channel).
+///
+/// Chunks of data sent into the sink are streamed to the server as part of the
+/// request body in the order they were sent. The sink only buffers a small
+/// number of chunks; once the buffer is full, [`BodySink::send`] waits until
+/// the request has consumed some of the data, providing backpressure to the
+/// producer.
+///
+/// The body is complete once [`BodySink::close`] is called. If the sink is
+/// dropped without being closed, or if [`BodySink::abort`] is called, the
+/// request body is treated as incomplete and the request will fail.
+pub struct BodySink {
+    sender: flume::Sender<Vec<u8>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl BodySink {
+    /// Send a chunk of data to be written to the request body.
+    ///
+    /// If the internal buffer is full, this waits until there is room for the
+    /// chunk. Returns an error if the request has already completed or was
+    /// canceled and the data can no longer be sent.
+    pub async fn send(&self, chunk: impl Into<Vec<u8>>) -> io::Result<()> {
+        let chunk = chunk.into();
+
+        // Empty chunks carry no data and would look like the end of the body
+        // to the reader, so just skip them.
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        self.sender
+            .send_async(chunk)
+            .await
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+
+    /// Mark the request body as complete.
+    ///
+    /// Any chunks already sent will still be delivered before the end of the
+    /// body is reached.
+    pub fn close(self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// Abort the request body, causing the request to fail.
+    pub fn abort(self) {}
+}
+
+impl fmt::Debug for BodySink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodySink").finish()
+    }
+}
+
+/// A writer connected to a request body created with
+/// [`Body::writer`](super::Body::writer) or
+/// [`AsyncBody::writer`](super::AsyncBody::writer).
+///
+/// Bytes written are streamed to the server as part of the request body,
+/// which lets existing code that writes into a [`Write`] or [`AsyncWrite`]
+/// sink, such as serializers or encoders, upload data without first
+/// collecting it into an intermediate buffer. Writes wait for the request to
+/// consume previously written data once a small internal buffer is full.
+///
+/// The body is complete once the writer is finished with
+/// [`BodyWriter::finish`] or closed with [`AsyncWriteExt::close`]. If the
+/// writer is dropped before then, the request body is treated as incomplete
+/// and the request will fail.
+///
+/// [`AsyncWriteExt::close`]: futures_lite::io::AsyncWriteExt::close
+pub struct BodyWriter {
+    sink: Option<BodySink>,
+    pending: Option<flume::r#async::SendFut<'static, Vec<u8>>>,
+}
+
+impl BodyWriter {
+    pub(super) fn new(sink: BodySink) -> Self {
+        Self {
+            sink: Some(sink),
+            pending: None,
+        }
+    }
+
+    /// Flush any buffered data and mark the request body as complete.
+    ///
+    /// This blocks the current thread until all written data has been handed
+    /// off to the request.
+    pub fn finish(mut self) -> io::Result<()> {
+        block_on(poll_fn(|cx| Pin::new(&mut self).poll_close(cx)))
+    }
+}
+
+impl AsyncWrite for BodyWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        let sender = match self.sink.as_ref() {
+            Some(sink) => sink.sender.clone(),
+            None => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        };
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        // The data is copied into the send future, so we can report it as
+        // written right away and wait for the send to complete on the next
+        // write or flush.
+        let mut send = sender.into_send_async(buf.to_vec());
+
+        match Pin::new(&mut send).poll(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(_)) => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+            Poll::Pending => self.pending = Some(send),
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(send) = self.pending.as_mut() {
+            let result = ready!(Pin::new(send).poll(cx));
+            self.pending = None;
+
+            if result.is_err() {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        if let Some(sink) = self.sink.take() {
+            sink.close();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        block_on(poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)))
+    }
+}
+
+impl fmt::Debug for BodyWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyWriter").finish()
+    }
+}
+
+/// The receiving half of a body channel, read from by the request.
+pub(super) struct ChannelReader {
+    receiver: flume::r#async::RecvStream<'static, Vec<u8>>,
+    chunk: Cursor<Vec<u8>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl AsyncRead for ChannelReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let len = self.chunk.read(buf)?;
+
+            if len > 0 || buf.is_empty() {
+                return Poll::Ready(Ok(len));
+            }
+
+            match Pin::new(&mut self.receiver).poll_next(cx) {
+                Poll::Ready(Some(chunk)) => self.chunk = Cursor::new(chunk),
+                Poll::Ready(None) => {
+                    return Poll::Ready(if self.closed.load(Ordering::SeqCst) {
+                        Ok(0)
+                    } else {
+                        Err(io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            "request body was aborted before it was complete",
+                        ))
+                    });
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -4,14 +4,22 @@ use futures_lite::io::{AsyncRead, BlockOn};
 use std::{
     borrow::Cow,
     fmt,
-    io::{self, Cursor, Read},
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::Path,
     pin::Pin,
     str,
     task::{Context, Poll},
 };
 
+mod channel;
+mod multipart;
 mod sync;
 
+#[allow(unreachable_pub)]
+pub use channel::{BodySink, BodyWriter};
+#[allow(unreachable_pub)]
+pub use multipart::{FormPart, MultipartBody};
 #[allow(unreachable_pub)]
 pub use sync::Body;
 
@@ -40,6 +48,12 @@ enum Inner {
 
     /// An asynchronous reader.
     Reader(Pin<Box<dyn AsyncRead + Send + Sync>>, Option<u64>),
+
+    /// A file on disk with a known length.
+    File(File, u64),
+
+    /// A `multipart/form-data` body.
+    Multipart(MultipartBody),
 }
 
 impl AsyncBody {
@@ -96,6 +110,24 @@ impl AsyncBody {
     /// [chunked transfer
     /// encoding](https://tools.ietf.org/html/rfc7230#section-4.1) might be used
     /// to send the request.
+    ///
+    /// The reader is read from as the request is sent, so large bodies are
+    /// uploaded without being loaded into memory first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, AsyncBody};
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let reader = futures_lite::io::Cursor::new(b"hello world".to_vec());
+    ///
+    /// let response = isahc::post_async(
+    ///     "https://httpbin.org/post",
+    ///     AsyncBody::from_reader(reader),
+    /// ).await?;
+    /// # Ok(()) }
+    /// ```
     pub fn from_reader<R>(read: R) -> Self
     where
         R: AsyncRead + Send + Sync + 'static,
@@ -112,6 +144,24 @@ impl AsyncBody {
     /// Giving a value for `length` that doesn't actually match how much data
     /// the reader will produce may result in errors when sending the body in a
     /// request.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures_lite::io::AsyncRead;
+    /// use isahc::{prelude::*, AsyncBody};
+    ///
+    /// # async fn run(
+    /// #     file: impl AsyncRead + Send + Sync + 'static,
+    /// #     len: u64,
+    /// # ) -> Result<(), isahc::Error> {
+    /// // Upload a file opened using an async runtime of your choice.
+    /// let response = isahc::put_async(
+    ///     "https://httpbin.org/put",
+    ///     AsyncBody::from_reader_sized(file, len),
+    /// ).await?;
+    /// # Ok(()) }
+    /// ```
     pub fn from_reader_sized<R>(read: R, length: u64) -> Self
     where
         R: AsyncRead + Send + Sync + 'static,
@@ -119,6 +169,90 @@ impl AsyncBody {
         Self(Inner::Reader(Box::pin(read), Some(length)))
     }
 
+    /// Create a streaming body that reads the contents of the file at the
+    /// given path.
+    ///
+    /// The length of the body is taken from the file's metadata, and the file
+    /// contents are streamed as the request is sent rather than being loaded
+    /// into memory first. Unlike a body created from an arbitrary reader, a
+    /// file body can be rewound, so it can be re-sent if a request needs to
+    /// be retried or redirected.
+    ///
+    /// Reads from the file are performed synchronously, which is usually fast
+    /// for regular files on local disks. For slow or remote file systems,
+    /// consider using [`AsyncBody::from_reader_sized`] with an asynchronous
+    /// file reader instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, AsyncBody};
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let response = isahc::put_async(
+    ///     "https://httpbin.org/put",
+    ///     AsyncBody::from_file("upload.bin")?,
+    /// ).await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        Ok(Self(Inner::File(file, len)))
+    }
+
+    /// Create a streaming body whose contents are produced on the fly by
+    /// sending chunks into the returned [`BodySink`].
+    ///
+    /// The body has an unknown length, so requests using it will typically be
+    /// sent using chunked transfer encoding. The body ends once the sink is
+    /// closed with [`BodySink::close`]; if the sink is dropped or aborted
+    /// before then, the request fails instead of sending a truncated body.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, AsyncBody};
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let (sink, body) = AsyncBody::channel();
+    ///
+    /// let response = futures_lite::future::zip(
+    ///     isahc::post_async("https://httpbin.org/post", body),
+    ///     async move {
+    ///         for line in &["first\n", "second\n"] {
+    ///             sink.send(*line).await?;
+    ///         }
+    ///         sink.close();
+    ///         Ok::<(), std::io::Error>(())
+    ///     },
+    /// ).await.0?;
+    /// # Ok(()) }
+    /// ```
+    pub fn channel() -> (BodySink, Self) {
+        let (sink, reader) = channel::channel();
+
+        (sink, Self::from_reader(reader))
+    }
+
+    /// Create a streaming body whose contents are written into the returned
+    /// [`BodyWriter`], which implements [`AsyncWrite`](futures_lite::io::AsyncWrite).
+    ///
+    /// This works like [`AsyncBody::channel`], but is convenient for code that
+    /// already knows how to write into an I/O sink. The body ends once the
+    /// writer is closed; if the writer is dropped before then, the request
+    /// fails instead of sending a truncated body.
+    pub fn writer() -> (BodyWriter, Self) {
+        let (sink, body) = Self::channel();
+
+        (BodyWriter::new(sink), body)
+    }
+
+    pub(crate) fn from_file_impl(file: File, len: u64) -> Self {
+        Self(Inner::File(file, len))
+    }
+
     /// Report if this body is empty.
     ///
     /// This is not necessarily the same as checking for `self.len() ==
@@ -150,6 +284,8 @@ impl AsyncBody {
             Inner::Empty => Some(0),
             Inner::Buffer(bytes) => Some(bytes.get_ref().len() as u64),
             Inner::Reader(_, len) => *len,
+            Inner::File(_, len) => Some(*len),
+            Inner::Multipart(multipart) => multipart.len(),
         }
     }
 
@@ -163,6 +299,16 @@ impl AsyncBody {
                 true
             }
             Inner::Reader(_, _) => false,
+            Inner::File(file, _) => file.seek(SeekFrom::Start(0)).is_ok(),
+            Inner::Multipart(multipart) => multipart.reset(),
+        }
+    }
+
+    /// Get the content type implied by this body, if any.
+    pub(crate) fn content_type(&self) -> Option<String> {
+        match &self.0 {
+            Inner::Multipart(multipart) => Some(multipart.content_type()),
+            _ => None,
         }
     }
 
@@ -181,6 +327,33 @@ impl AsyncBody {
                 sync::Body::from_reader_sized(BlockOn::new(reader), len)
             }
             Inner::Reader(reader, None) => sync::Body::from_reader(BlockOn::new(reader)),
+            Inner::File(file, len) => sync::Body::from_file_impl(file, len),
+            Inner::Multipart(multipart) => multipart.into(),
+        }
+    }
+
+    /// Get up to `len` bytes from the remaining content of this body without
+    /// consuming them, if it is stored in memory.
+    pub(crate) fn peek_buffered(&self, len: usize) -> Option<&[u8]> {
+        match &self.0 {
+            Inner::Empty => Some(&[]),
+            Inner::Buffer(cursor) => {
+                let remaining = &cursor.get_ref()[cursor.position() as usize..];
+                Some(&remaining[..len.min(remaining.len())])
+            }
+            _ => None,
+        }
+    }
+
+    /// Create a copy of this body if it is stored in memory, starting from the
+    /// beginning of the content.
+    pub(crate) fn try_clone(&self) -> Option<Self> {
+        match &self.0 {
+            Inner::Empty => Some(Self::empty()),
+            Inner::Buffer(cursor) => {
+                Some(Self(Inner::Buffer(Cursor::new(cursor.get_ref().clone()))))
+            }
+            _ => None,
         }
     }
 }
@@ -195,6 +368,8 @@ impl AsyncRead for AsyncBody {
             Inner::Empty => Poll::Ready(Ok(0)),
             Inner::Buffer(cursor) => Poll::Ready(cursor.read(buf)),
             Inner::Reader(read, _) => AsyncRead::poll_read(read.as_mut(), cx, buf),
+            Inner::File(file, _) => Poll::Ready(file.read(buf)),
+            Inner::Multipart(multipart) => Poll::Ready(multipart.read(buf)),
         }
     }
 }
@@ -235,6 +410,12 @@ impl From<&'_ str> for AsyncBody {
     }
 }
 
+impl From<MultipartBody> for AsyncBody {
+    fn from(body: MultipartBody) -> Self {
+        Self(Inner::Multipart(body))
+    }
+}
+
 impl<T: Into<Self>> From<Option<T>> for AsyncBody {
     fn from(body: Option<T>) -> Self {
         match body {
@@ -258,10 +439,12 @@ mod tests {
     use super::*;
     use futures_lite::{
         future::{block_on, zip},
-        io::AsyncReadExt,
+        io::{AsyncReadExt, AsyncWriteExt},
     };
 
     static_assertions::assert_impl_all!(AsyncBody: Send, Sync);
+    static_assertions::assert_impl_all!(BodySink: Send, Sync);
+    static_assertions::assert_impl_all!(BodyWriter: Send, Sync);
 
     #[test]
     fn empty_body() {
@@ -317,6 +500,79 @@ mod tests {
         assert_eq!(body.reset(), false);
     }
 
+    #[test]
+    fn reset_file_body() {
+        block_on(async {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            std::io::Write::write_all(&mut file, b"hello world").unwrap();
+
+            let mut body = AsyncBody::from_file(file.path()).unwrap();
+            let mut buf = String::new();
+
+            assert_eq!(body.len(), Some(11));
+            assert_eq!(body.read_to_string(&mut buf).await.unwrap(), 11);
+            assert!(body.reset());
+            buf.clear();
+            assert_eq!(body.read_to_string(&mut buf).await.unwrap(), 11);
+            assert_eq!(buf, "hello world");
+        });
+    }
+
+    #[test]
+    fn channel_body_streams_chunks_until_closed() {
+        block_on(async {
+            let (sink, mut body) = AsyncBody::channel();
+
+            assert_eq!(body.len(), None);
+
+            sink.send("hello ").await.unwrap();
+            sink.send(b"world".to_vec()).await.unwrap();
+            sink.close();
+
+            let mut buf = String::new();
+            body.read_to_string(&mut buf).await.unwrap();
+            assert_eq!(buf, "hello world");
+        });
+    }
+
+    #[test]
+    fn channel_body_aborted_returns_error() {
+        block_on(async {
+            let (sink, mut body) = AsyncBody::channel();
+
+            sink.send("partial").await.unwrap();
+            sink.abort();
+
+            let mut buf = String::new();
+            let error = body.read_to_string(&mut buf).await.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::ConnectionAborted);
+        });
+    }
+
+    #[test]
+    fn writer_body_streams_written_bytes() {
+        block_on(async {
+            let (mut writer, mut body) = AsyncBody::writer();
+
+            let (_, buf) = zip(
+                async move {
+                    for _ in 0..100 {
+                        writer.write_all(b"hello world\n").await.unwrap();
+                    }
+                    writer.close().await.unwrap();
+                },
+                async move {
+                    let mut buf = String::new();
+                    body.read_to_string(&mut buf).await.unwrap();
+                    buf
+                },
+            )
+            .await;
+
+            assert_eq!(buf, "hello world\n".repeat(100));
+        });
+    }
+
     #[test]
     fn sync_memory_into_async() {
         let (body, writer) = Body::from("hello world").into_async();
@@ -0,0 +1,429 @@
+//! Streaming `multipart/form-data` request bodies.
+
+use crate::random::random;
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A `multipart/form-data` request body, as described in [RFC
+/// 7578](https://tools.ietf.org/html/rfc7578).
+///
+/// A multipart body is composed of form fields and files, each of which is
+/// sent as a separate [`FormPart`]. The parts are streamed as the request is
+/// sent, so the contents of files and readers are never buffered in memory as
+/// a whole.
+///
+/// A multipart body can be converted into a [`Body`](crate::Body) or an
+/// [`AsyncBody`](crate::AsyncBody). When a request with a multipart body is
+/// sent, its `Content-Type` header is set to `multipart/form-data` along with
+/// the boundary separating the parts, unless the request already has one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::{prelude::*, FormPart, MultipartBody};
+///
+/// let body = MultipartBody::new()
+///     .text("title", "Holiday pictures")
+///     .file("picture", "beach.jpg")?
+///     .part(FormPart::bytes("thumbnail", vec![0; 16])
+///         .filename("thumbnail.png")
+///         .content_type("image/png"));
+///
+/// let response = Request::post("https://httpbin.org/post")
+///     .body(body)?
+///     .send()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+pub struct MultipartBody {
+    boundary: String,
+
+    /// Everything that comes before the closing delimiter, in order.
+    segments: Vec<Segment>,
+
+    /// Index of the segment currently being read.
+    current: usize,
+
+    /// The delimiter that ends the body.
+    closing: Cursor<Vec<u8>>,
+}
+
+impl MultipartBody {
+    /// Create a new multipart body without any parts, using a randomly
+    /// generated boundary.
+    pub fn new() -> Self {
+        let boundary = generate_boundary();
+        let closing = format!("--{}--\r\n", boundary).into_bytes();
+
+        Self {
+            boundary,
+            segments: Vec::new(),
+            current: 0,
+            closing: Cursor::new(closing),
+        }
+    }
+
+    /// Add a text field with the given name and value.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(FormPart::text(name, value))
+    }
+
+    /// Add a file upload with the given name, streaming the contents of the
+    /// file at the given path.
+    ///
+    /// See [`FormPart::file`] for details.
+    pub fn file(self, name: impl Into<String>, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(self.part(FormPart::file(name, path)?))
+    }
+
+    /// Add a part to the end of this body.
+    pub fn part(mut self, part: FormPart) -> Self {
+        let head = Cursor::new(Cow::Owned(part.head(&self.boundary).into_bytes()));
+        let tail = Cursor::new(Cow::Borrowed(&b"\r\n"[..]));
+
+        self.segments.push(Segment::Buffer(head));
+        self.segments.push(part.content);
+        self.segments.push(Segment::Buffer(tail));
+        self
+    }
+
+    /// Get the boundary separating the parts of this body.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Get the value of the `Content-Type` header describing this body.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Check if this body has no parts.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Get the size of the body, if known.
+    ///
+    /// The size is only known if the size of the contents of each part is
+    /// known.
+    pub fn len(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .try_fold(self.closing.get_ref().len() as u64, |len, segment| {
+                Some(len + segment.len()?)
+            })
+    }
+
+    /// Check if the contents of every part can be read again from the start.
+    pub(crate) fn is_rewindable(&self) -> bool {
+        self.segments.iter().all(|segment| match segment {
+            Segment::Reader(..) => false,
+            _ => true,
+        })
+    }
+
+    /// Reset the body back to the start, if the contents of every part can be
+    /// read again. Returns `false` if the body cannot be reset.
+    pub(crate) fn reset(&mut self) -> bool {
+        if !self.is_rewindable() {
+            return false;
+        }
+
+        for segment in &mut self.segments {
+            match segment {
+                Segment::Buffer(cursor) => cursor.set_position(0),
+                Segment::File(file, _) => {
+                    if file.seek(SeekFrom::Start(0)).is_err() {
+                        return false;
+                    }
+                }
+                Segment::Reader(..) => unreachable!(),
+            }
+        }
+
+        self.current = 0;
+        self.closing.set_position(0);
+
+        true
+    }
+}
+
+impl Default for MultipartBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Read for MultipartBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while let Some(segment) = self.segments.get_mut(self.current) {
+            match segment.read(buf)? {
+                0 => self.current += 1,
+                len => return Ok(len),
+            }
+        }
+
+        self.closing.read(buf)
+    }
+}
+
+impl fmt::Debug for MultipartBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultipartBody")
+            .field("boundary", &self.boundary)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A single field or file of a [`MultipartBody`].
+pub struct FormPart {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    content: Segment,
+}
+
+impl FormPart {
+    /// Create a text field with the given name and value.
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::bytes(name, value.into().into_bytes())
+    }
+
+    /// Create a part with the given name containing the given bytes.
+    pub fn bytes(name: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self::new(name, Segment::Buffer(Cursor::new(Cow::Owned(bytes.into()))))
+    }
+
+    /// Create a file upload with the given name, streaming the contents of
+    /// the file at the given path.
+    ///
+    /// The file is opened right away, and its size is taken from its
+    /// metadata. The file name sent defaults to the last component of the
+    /// path, and the content type defaults to `application/octet-stream`.
+    pub fn file(name: impl Into<String>, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        let mut part =
+            Self::new(name, Segment::File(file, len)).content_type("application/octet-stream");
+
+        if let Some(filename) = path.file_name() {
+            part = part.filename(filename.to_string_lossy());
+        }
+
+        Ok(part)
+    }
+
+    /// Create a part with the given name that streams the contents of the
+    /// given reader.
+    ///
+    /// The part has an unknown length, so the body it is added to does too.
+    /// Unlike other parts, a reader cannot be rewound, so a request with such
+    /// a part cannot be re-sent if it needs to be redirected.
+    ///
+    /// For asynchronous requests, the reader is read from synchronously just
+    /// like files are, so it should not block for long.
+    pub fn reader<R>(name: impl Into<String>, reader: R) -> Self
+    where
+        R: Read + Send + Sync + 'static,
+    {
+        Self::new(name, Segment::Reader(Box::new(reader), None))
+    }
+
+    /// Create a part with the given name that streams the contents of the
+    /// given reader, which produces exactly `length` bytes.
+    ///
+    /// See [`FormPart::reader`] for details.
+    pub fn reader_sized<R>(name: impl Into<String>, reader: R, length: u64) -> Self
+    where
+        R: Read + Send + Sync + 'static,
+    {
+        Self::new(name, Segment::Reader(Box::new(reader), Some(length)))
+    }
+
+    /// Set the file name sent with this part.
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    /// Set the content type of this part.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    fn new(name: impl Into<String>, content: Segment) -> Self {
+        Self {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            content,
+        }
+    }
+
+    /// Render the delimiter and headers preceding the contents of this part.
+    fn head(&self, boundary: &str) -> String {
+        let mut head = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            boundary,
+            escape(&self.name)
+        );
+
+        if let Some(filename) = self.filename.as_ref() {
+            head.push_str(&format!("; filename=\"{}\"", escape(filename)));
+        }
+
+        if let Some(content_type) = self.content_type.as_ref() {
+            head.push_str(&format!("\r\nContent-Type: {}", content_type));
+        }
+
+        head.push_str("\r\n\r\n");
+        head
+    }
+}
+
+impl fmt::Debug for FormPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FormPart")
+            .field("name", &self.name)
+            .field("filename", &self.filename)
+            .field("content_type", &self.content_type)
+            .field("len", &self.content.len())
+            .finish()
+    }
+}
+
+/// A contiguous piece of a multipart body.
+enum Segment {
+    Buffer(Cursor<Cow<'static, [u8]>>),
+    File(File, u64),
+    Reader(Box<dyn Read + Send + Sync>, Option<u64>),
+}
+
+impl Segment {
+    fn len(&self) -> Option<u64> {
+        match self {
+            Segment::Buffer(cursor) => Some(cursor.get_ref().len() as u64),
+            Segment::File(_, len) => Some(*len),
+            Segment::Reader(_, len) => *len,
+        }
+    }
+}
+
+impl Read for Segment {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Segment::Buffer(cursor) => cursor.read(buf),
+            Segment::File(file, _) => file.read(buf),
+            Segment::Reader(reader, _) => reader.read(buf),
+        }
+    }
+}
+
+/// Escape a name or file name for use in a quoted `Content-Disposition`
+/// parameter, the same way browsers do.
+fn escape(value: &str) -> Cow<'_, str> {
+    if value.contains(&['"', '\r', '\n'][..]) {
+        Cow::Owned(
+            value
+                .replace('"', "%22")
+                .replace('\r', "%0D")
+                .replace('\n', "%0A"),
+        )
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Generate a boundary that is unlikely to appear in the contents of any
+/// part.
+fn generate_boundary() -> String {
+    format!("isahc-boundary-{:016x}{:016x}", random(), random())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_to_string(body: &mut MultipartBody) -> String {
+        let mut buf = String::new();
+        body.read_to_string(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn encode_fields_and_files() {
+        let mut body = MultipartBody::new().text("title", "hello").part(
+            FormPart::bytes("upload", "contents")
+                .filename("a.txt")
+                .content_type("text/plain"),
+        );
+        let boundary = body.boundary().to_owned();
+        let expected = format!(
+            "--{0}\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             hello\r\n\
+             --{0}\r\n\
+             Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             contents\r\n\
+             --{0}--\r\n",
+            boundary
+        );
+
+        assert_eq!(body.len(), Some(expected.len() as u64));
+        assert_eq!(read_to_string(&mut body), expected);
+    }
+
+    #[test]
+    fn boundaries_are_unique() {
+        let a = MultipartBody::new();
+        let b = MultipartBody::new();
+
+        assert_ne!(a.boundary(), b.boundary());
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let mut body = MultipartBody::new().text("a\"b\r\nc", "");
+
+        assert!(read_to_string(&mut body).contains("name=\"a%22b%0D%0Ac\""));
+    }
+
+    #[test]
+    fn file_part_streams_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"hello world").unwrap();
+
+        let mut body = MultipartBody::new().file("upload", file.path()).unwrap();
+        let filename = file.path().file_name().unwrap().to_str().unwrap();
+        let first = read_to_string(&mut body);
+
+        assert_eq!(body.len(), Some(first.len() as u64));
+        assert!(first.contains(&format!("filename=\"{}\"", filename)));
+        assert!(first.contains("Content-Type: application/octet-stream\r\n\r\nhello world\r\n"));
+
+        assert!(body.reset());
+        assert_eq!(read_to_string(&mut body), first);
+    }
+
+    #[test]
+    fn reader_part_has_unknown_length_and_cannot_be_reset() {
+        let mut body = MultipartBody::new().part(FormPart::reader("data", "abc".as_bytes()));
+
+        assert_eq!(body.len(), None);
+        assert!(read_to_string(&mut body).contains("\r\n\r\nabc\r\n"));
+        assert!(!body.reset());
+    }
+}
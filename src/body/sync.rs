@@ -1,11 +1,15 @@
-use super::AsyncBody;
-use futures_lite::{future::yield_now, io::AsyncWriteExt};
+use super::{channel::ChannelReader, AsyncBody, BodyWriter, MultipartBody};
+use futures_lite::{
+    future::{block_on, yield_now},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
 use sluice::pipe::{pipe, PipeWriter};
 use std::{
     borrow::Cow,
     fmt,
     fs::File,
-    io::{Cursor, ErrorKind, Read, Result},
+    io::{Cursor, ErrorKind, Read, Result, Seek, SeekFrom},
+    path::Path,
 };
 
 /// Contains the body of a synchronous HTTP request or response.
@@ -23,6 +27,10 @@ enum Inner {
     Empty,
     Buffer(Cursor<Cow<'static, [u8]>>),
     Reader(Box<dyn Read + Send + Sync>, Option<u64>),
+    File(File, u64),
+    Channel(ChannelReader),
+    Peeked(Cursor<Vec<u8>>, Box<Body>),
+    Multipart(MultipartBody),
 }
 
 impl Body {
@@ -95,6 +103,69 @@ impl Body {
         Self(Inner::Reader(Box::new(reader), Some(length)))
     }
 
+    /// Create a streaming body that reads the contents of the file at the
+    /// given path.
+    ///
+    /// The length of the body is taken from the file's metadata, and the file
+    /// contents are streamed as the request is sent rather than being loaded
+    /// into memory first. Unlike a body created from an arbitrary reader, a
+    /// file body can be rewound, so it can be re-sent if a request needs to
+    /// be retried or redirected.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, Body};
+    ///
+    /// let response = isahc::put("https://httpbin.org/put", Body::from_file("upload.bin")?)?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        Ok(Self::from_file_impl(file, len))
+    }
+
+    /// Create a streaming body whose contents are written into the returned
+    /// [`BodyWriter`], which implements [`Write`](std::io::Write).
+    ///
+    /// This lets existing code that writes into an I/O sink, such as a
+    /// serializer or an encoder, stream the request body without collecting
+    /// it into a buffer first. Writing happens concurrently with the request,
+    /// so the writer is typically used from another thread. The body ends once
+    /// [`BodyWriter::finish`] is called; if the writer is dropped before then,
+    /// the request fails instead of sending a truncated body.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, Body};
+    /// use std::{io::Write, thread};
+    ///
+    /// let (mut writer, body) = Body::writer();
+    ///
+    /// let producer = thread::spawn(move || {
+    ///     for i in 0..10 {
+    ///         writeln!(writer, "line {}", i)?;
+    ///     }
+    ///     writer.finish()
+    /// });
+    ///
+    /// let response = isahc::post("https://httpbin.org/post", body)?;
+    /// producer.join().unwrap()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn writer() -> (BodyWriter, Self) {
+        let (sink, reader) = super::channel::channel();
+
+        (BodyWriter::new(sink), Self(Inner::Channel(reader)))
+    }
+
+    pub(super) fn from_file_impl(file: File, len: u64) -> Self {
+        Self(Inner::File(file, len))
+    }
+
     /// Report if this body is empty.
     ///
     /// This is not necessarily the same as checking for `self.len() ==
@@ -126,6 +197,10 @@ impl Body {
             Inner::Empty => Some(0),
             Inner::Buffer(bytes) => Some(bytes.get_ref().len() as u64),
             Inner::Reader(_, len) => *len,
+            Inner::File(_, len) => Some(*len),
+            Inner::Channel(_) => None,
+            Inner::Peeked(_, rest) => rest.len(),
+            Inner::Multipart(multipart) => multipart.len(),
         }
     }
 
@@ -138,10 +213,76 @@ impl Body {
                 cursor.set_position(0);
                 true
             }
+            Inner::File(file, _) => file.seek(SeekFrom::Start(0)).is_ok(),
+            Inner::Multipart(multipart) => multipart.reset(),
             _ => false,
         }
     }
 
+    /// Get the content type implied by this body, if any.
+    pub(crate) fn content_type(&self) -> Option<String> {
+        match &self.0 {
+            Inner::Multipart(multipart) => Some(multipart.content_type()),
+            Inner::Peeked(_, rest) => rest.content_type(),
+            _ => None,
+        }
+    }
+
+    /// Get up to `len` bytes from the remaining content of this body without
+    /// consuming them, if it is stored in memory.
+    pub(crate) fn peek_buffered(&self, len: usize) -> Option<&[u8]> {
+        match &self.0 {
+            Inner::Empty => Some(&[]),
+            Inner::Buffer(cursor) => {
+                let remaining = &cursor.get_ref()[cursor.position() as usize..];
+                Some(&remaining[..len.min(remaining.len())])
+            }
+            _ => None,
+        }
+    }
+
+    /// Read up to `len` bytes from the start of the remaining body without
+    /// consuming them.
+    ///
+    /// The bytes read are buffered internally, and are returned again by
+    /// subsequent reads. Fewer bytes are returned only if the end of the body
+    /// is reached first.
+    pub(crate) fn peek(&mut self, len: usize) -> Result<&[u8]> {
+        match self.0 {
+            Inner::Peeked(..) => {}
+            _ => {
+                let rest = Box::new(Self(std::mem::replace(&mut self.0, Inner::Empty)));
+                self.0 = Inner::Peeked(Cursor::new(Vec::new()), rest);
+            }
+        }
+
+        if let Inner::Peeked(buffer, rest) = &mut self.0 {
+            // Discard any previously peeked bytes that have since been read.
+            let consumed = buffer.position() as usize;
+            buffer.get_mut().drain(..consumed);
+            buffer.set_position(0);
+
+            let mut chunk = [0; 8192];
+
+            while buffer.get_ref().len() < len {
+                let read = match rest.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                buffer.get_mut().extend_from_slice(&chunk[..read]);
+            }
+
+            let buffered = buffer.get_ref();
+
+            Ok(&buffered[..len.min(buffered.len())])
+        } else {
+            unreachable!()
+        }
+    }
+
     /// Convert this body into an asynchronous one.
     ///
     /// Turning a synchronous operation into an asynchronous one can be quite
@@ -157,6 +298,22 @@ impl Body {
         match self.0 {
             Inner::Empty => (AsyncBody::empty(), None),
             Inner::Buffer(cursor) => (AsyncBody::from_bytes_static(cursor.into_inner()), None),
+            Inner::File(file, len) => (AsyncBody::from_file_impl(file, len), None),
+            Inner::Channel(reader) => (AsyncBody::from_reader(reader), None),
+            // Multipart bodies made of buffers and files only can be read
+            // from directly, like file bodies.
+            Inner::Multipart(multipart) if multipart.is_rewindable() => (multipart.into(), None),
+            Inner::Multipart(multipart) => {
+                let len = multipart.len();
+
+                Self(Inner::Reader(Box::new(multipart), len)).into_async()
+            }
+            Inner::Peeked(buffer, rest) => {
+                let len = rest.len();
+                let reader = Box::new(buffer.chain(*rest));
+
+                Self(Inner::Reader(reader, len)).into_async()
+            }
             Inner::Reader(reader, len) => {
                 let (pipe_reader, writer) = pipe();
 
@@ -182,6 +339,13 @@ impl Read for Body {
             Inner::Empty => Ok(0),
             Inner::Buffer(cursor) => cursor.read(buf),
             Inner::Reader(reader, _) => reader.read(buf),
+            Inner::File(file, _) => file.read(buf),
+            Inner::Channel(reader) => block_on(reader.read(buf)),
+            Inner::Multipart(multipart) => multipart.read(buf),
+            Inner::Peeked(buffer, rest) => match buffer.read(buf)? {
+                0 => rest.read(buf),
+                len => Ok(len),
+            },
         }
     }
 }
@@ -222,6 +386,12 @@ impl From<&'_ str> for Body {
     }
 }
 
+impl From<MultipartBody> for Body {
+    fn from(body: MultipartBody) -> Self {
+        Self(Inner::Multipart(body))
+    }
+}
+
 impl From<File> for Body {
     fn from(file: File) -> Self {
         if let Ok(metadata) = file.metadata() {
@@ -286,6 +456,32 @@ mod tests {
 
     static_assertions::assert_impl_all!(Body: Send, Sync);
 
+    #[test]
+    fn peek_does_not_consume_body() {
+        let mut body = Body::from_reader("hello world".as_bytes());
+
+        assert_eq!(body.peek(5).unwrap(), b"hello");
+        assert_eq!(body.peek(100).unwrap(), b"hello world");
+
+        let mut buf = String::new();
+        body.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "hello world");
+    }
+
+    #[test]
+    fn peek_after_partial_read() {
+        let mut body = Body::from("hello world");
+        let mut buf = [0; 6];
+
+        body.read_exact(&mut buf).unwrap();
+        assert_eq!(body.peek(3).unwrap(), b"wor");
+
+        let mut buf = String::new();
+        body.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "world");
+        assert_eq!(body.len(), Some(11));
+    }
+
     #[test]
     fn empty_body() {
         let body = Body::empty();
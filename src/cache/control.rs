@@ -0,0 +1,316 @@
+//! Freshness of cached responses, as described in [RFC 7234, section
+//! 4.2](https://tools.ietf.org/html/rfc7234#section-4.2).
+
+use crate::{
+    headers::{self, values},
+    parsing::unquote,
+};
+use http::{HeaderMap, StatusCode, header};
+use std::time::{Duration, SystemTime};
+
+/// Directives of the `Cache-Control` header of a request or response.
+#[derive(Debug, Default)]
+pub(crate) struct CacheControl {
+    pub(crate) no_store: bool,
+    pub(crate) no_cache: bool,
+    pub(crate) must_revalidate: bool,
+    pub(crate) only_if_cached: bool,
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) min_fresh: Option<Duration>,
+
+    /// How stale a response may be. `Some(None)` accepts any staleness.
+    pub(crate) max_stale: Option<Option<Duration>>,
+}
+
+impl CacheControl {
+    pub(crate) fn parse(headers: &HeaderMap) -> Self {
+        let mut control = Self::default();
+
+        for directive in values(headers, "cache-control") {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+            let seconds = parts
+                .next()
+                .and_then(|value| unquote(value.trim()).parse().ok())
+                .map(Duration::from_secs);
+
+            match name.as_str() {
+                "no-store" => control.no_store = true,
+                // A list of fields given for no-cache only applies to shared
+                // caches, so it is treated like a plain no-cache.
+                "no-cache" => control.no_cache = true,
+                "must-revalidate" => control.must_revalidate = true,
+                "only-if-cached" => control.only_if_cached = true,
+                // Invalid values are treated as if the response is stale.
+                "max-age" => control.max_age = Some(seconds.unwrap_or_default()),
+                "min-fresh" => control.min_fresh = seconds,
+                "max-stale" => control.max_stale = Some(seconds),
+                _ => {}
+            }
+        }
+
+        // The Pragma header is only used by requests without Cache-Control.
+        if !headers.contains_key(header::CACHE_CONTROL)
+            && values(headers, "pragma")
+                .iter()
+                .any(|value| value.eq_ignore_ascii_case("no-cache"))
+        {
+            control.no_cache = true;
+        }
+
+        control
+    }
+}
+
+/// Check whether a response with the given status code may be cached without
+/// explicit freshness information, as listed in [RFC 7231, section
+/// 6.1](https://tools.ietf.org/html/rfc7231#section-6.1).
+pub(crate) fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    match status.as_u16() {
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501 => true,
+        _ => false,
+    }
+}
+
+/// Get how long a response stays fresh after it was generated.
+pub(crate) fn freshness_lifetime(
+    status: StatusCode,
+    headers: &HeaderMap,
+    control: &CacheControl,
+    received: SystemTime,
+) -> Duration {
+    if let Some(max_age) = control.max_age {
+        return max_age;
+    }
+
+    let date = headers::date(headers, header::DATE).unwrap_or(received);
+
+    if let Some(expires) = headers.get(header::EXPIRES) {
+        // Invalid dates mean that the response has already expired.
+        return expires
+            .to_str()
+            .ok()
+            .and_then(crate::date::parse)
+            .and_then(|expires| expires.duration_since(date).ok())
+            .unwrap_or_default();
+    }
+
+    // Without explicit freshness, guess it as a fraction of the time that
+    // passed since the resource was last modified, as suggested by the RFC.
+    if is_heuristically_cacheable(status) {
+        if let Some(last_modified) = headers::date(headers, header::LAST_MODIFIED) {
+            if let Ok(unchanged) = date.duration_since(last_modified) {
+                return unchanged / 10;
+            }
+        }
+    }
+
+    Duration::from_secs(0)
+}
+
+/// Check whether a stored response can be used to satisfy a request without
+/// validating it with the server first.
+pub(crate) fn can_serve(
+    request: &CacheControl,
+    response: &CacheControl,
+    lifetime: Duration,
+    age: Duration,
+) -> bool {
+    if request.no_cache || response.no_cache {
+        return false;
+    }
+
+    if let Some(max_age) = request.max_age {
+        if age > max_age {
+            return false;
+        }
+    }
+
+    let required = age + request.min_fresh.unwrap_or_default();
+
+    if lifetime > required {
+        return true;
+    }
+
+    // A stale response may only be used if the request allows for it.
+    if response.must_revalidate {
+        return false;
+    }
+
+    match request.max_stale {
+        Some(None) => true,
+        Some(Some(max_stale)) => age <= lifetime + max_stale,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parse_directives() {
+        let control = CacheControl::parse(&headers(&[
+            ("cache-control", "No-Store, max-age=\"60\""),
+            ("cache-control", "max-stale, must-revalidate, private"),
+        ]));
+
+        assert!(control.no_store);
+        assert!(control.must_revalidate);
+        assert!(!control.no_cache);
+        assert_eq!(control.max_age, Some(secs(60)));
+        assert_eq!(control.max_stale, Some(None));
+    }
+
+    #[test]
+    fn invalid_max_age_is_zero() {
+        let control = CacheControl::parse(&headers(&[("cache-control", "max-age=soon")]));
+
+        assert_eq!(control.max_age, Some(secs(0)));
+    }
+
+    #[test]
+    fn pragma_no_cache_only_without_cache_control() {
+        assert!(CacheControl::parse(&headers(&[("pragma", "no-cache")])).no_cache);
+        assert!(
+            !CacheControl::parse(&headers(&[
+                ("pragma", "no-cache"),
+                ("cache-control", "max-age=10"),
+            ]))
+            .no_cache
+        );
+    }
+
+    #[test]
+    fn lifetime_prefers_max_age_over_expires() {
+        let headers = headers(&[
+            ("cache-control", "max-age=100"),
+            ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ("expires", "Sun, 06 Nov 1994 08:59:37 GMT"),
+        ]);
+        let control = CacheControl::parse(&headers);
+
+        assert_eq!(
+            freshness_lifetime(StatusCode::OK, &headers, &control, SystemTime::now()),
+            secs(100)
+        );
+    }
+
+    #[test]
+    fn lifetime_from_expires() {
+        let headers = headers(&[
+            ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ("expires", "Sun, 06 Nov 1994 08:59:37 GMT"),
+        ]);
+
+        assert_eq!(
+            freshness_lifetime(
+                StatusCode::OK,
+                &headers,
+                &CacheControl::default(),
+                SystemTime::now()
+            ),
+            secs(600)
+        );
+    }
+
+    #[test]
+    fn invalid_expires_means_expired() {
+        let headers = headers(&[
+            ("expires", "0"),
+            ("last-modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+        ]);
+
+        assert_eq!(
+            freshness_lifetime(
+                StatusCode::OK,
+                &headers,
+                &CacheControl::default(),
+                SystemTime::now()
+            ),
+            secs(0)
+        );
+    }
+
+    #[test]
+    fn heuristic_lifetime_from_last_modified() {
+        let headers = headers(&[
+            ("date", "Sun, 06 Nov 1994 18:49:37 GMT"),
+            ("last-modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+        ]);
+        let now = SystemTime::now();
+        let control = CacheControl::default();
+
+        assert_eq!(
+            freshness_lifetime(StatusCode::OK, &headers, &control, now),
+            secs(3600)
+        );
+        assert_eq!(
+            freshness_lifetime(StatusCode::FOUND, &headers, &control, now),
+            secs(0)
+        );
+    }
+
+    #[test]
+    fn fresh_responses_are_served() {
+        let none = CacheControl::default();
+
+        assert!(can_serve(&none, &none, secs(60), secs(10)));
+        assert!(!can_serve(&none, &none, secs(60), secs(60)));
+    }
+
+    #[test]
+    fn request_directives_limit_serving() {
+        let none = CacheControl::default();
+        let max_age = CacheControl {
+            max_age: Some(secs(5)),
+            ..Default::default()
+        };
+        let min_fresh = CacheControl {
+            min_fresh: Some(secs(30)),
+            ..Default::default()
+        };
+        let no_cache = CacheControl {
+            no_cache: true,
+            ..Default::default()
+        };
+
+        assert!(!can_serve(&max_age, &none, secs(60), secs(10)));
+        assert!(!can_serve(&min_fresh, &none, secs(60), secs(40)));
+        assert!(can_serve(&min_fresh, &none, secs(60), secs(20)));
+        assert!(!can_serve(&no_cache, &none, secs(60), secs(10)));
+    }
+
+    #[test]
+    fn stale_responses_are_served_only_if_allowed() {
+        let none = CacheControl::default();
+        let max_stale = CacheControl {
+            max_stale: Some(Some(secs(30))),
+            ..Default::default()
+        };
+        let must_revalidate = CacheControl {
+            must_revalidate: true,
+            ..Default::default()
+        };
+
+        assert!(!can_serve(&none, &none, secs(60), secs(70)));
+        assert!(can_serve(&max_stale, &none, secs(60), secs(70)));
+        assert!(!can_serve(&max_stale, &none, secs(60), secs(100)));
+        assert!(!can_serve(&max_stale, &must_revalidate, secs(60), secs(70)));
+    }
+}
@@ -0,0 +1,337 @@
+//! Interceptor that answers requests from the cache when possible, and stores
+//! responses in it.
+
+use super::{
+    CacheConfig,
+    CacheStatus,
+    control::{CacheControl, can_serve, freshness_lifetime},
+    store::{Entry, Store, vary_names},
+};
+use crate::{
+    body::AsyncBody,
+    error::Error,
+    headers,
+    interceptor::{Context, Interceptor, InterceptorFuture},
+    response::ResponseTimes,
+};
+use futures_lite::io::AsyncRead;
+use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, header};
+use std::{
+    io,
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::SystemTime,
+};
+
+/// Headers that describe the stored body itself, and so are not updated from
+/// a `304 Not Modified` response.
+const BODY_HEADERS: [header::HeaderName; 3] = [
+    header::CONTENT_ENCODING,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+];
+
+/// Request headers that make a request conditional. Responses to requests
+/// made conditional by the user are passed through unchanged.
+const CONDITIONAL_HEADERS: [header::HeaderName; 5] = [
+    header::IF_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_NONE_MATCH,
+    header::IF_RANGE,
+    header::IF_UNMODIFIED_SINCE,
+];
+
+#[derive(Debug)]
+pub(crate) struct CacheInterceptor {
+    store: Arc<Store>,
+}
+
+impl CacheInterceptor {
+    pub(crate) fn new(config: CacheConfig) -> io::Result<Self> {
+        let store = match config.dir {
+            Some(dir) => Store::disk(dir, config.max_size)?,
+            None => Store::memory(config.max_size),
+        };
+
+        Ok(Self {
+            store: Arc::new(store),
+        })
+    }
+}
+
+impl Interceptor for CacheInterceptor {
+    type Err = Error;
+
+    fn intercept<'a>(
+        &'a self,
+        mut request: Request<AsyncBody>,
+        ctx: Context<'a>,
+    ) -> InterceptorFuture<'a, Self::Err> {
+        Box::pin(async move {
+            let key = request.uri().to_string();
+            let request_control = CacheControl::parse(request.headers());
+
+            if !is_cacheable_request(&request) || request_control.no_store {
+                let invalidates = !request.method().is_safe();
+                let mut response = ctx.send(request).await?;
+                let status = response.status();
+
+                // A successful unsafe request likely changed the resource, so
+                // forget what is known about it.
+                if invalidates && !status.is_client_error() && !status.is_server_error() {
+                    self.store.remove(&key);
+                }
+
+                response.extensions_mut().insert(CacheStatus::Miss);
+
+                return Ok(response);
+            }
+
+            let stored = self
+                .store
+                .get(&key)
+                .filter(|entry| entry.matches(request.headers()));
+
+            if let Some(entry) = stored.as_ref() {
+                let now = SystemTime::now();
+                let age = headers::age(&entry.headers, Some(&entry.times), now).unwrap_or_default();
+                let control = CacheControl::parse(&entry.headers);
+                let lifetime = freshness_lifetime(
+                    entry.status,
+                    &entry.headers,
+                    &control,
+                    entry.times.received,
+                );
+
+                if can_serve(&request_control, &control, lifetime, age) {
+                    tracing::debug!(uri = ?request.uri(), "using cached response");
+                    return Ok(cached_response(entry, CacheStatus::Hit));
+                }
+            }
+
+            // Without contacting the server, there is nothing to respond with.
+            if request_control.only_if_cached {
+                let mut response = Response::new(AsyncBody::empty());
+                *response.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+                response.extensions_mut().insert(CacheStatus::Miss);
+
+                return Ok(response);
+            }
+
+            // Ask the server whether the stored response is still valid, if
+            // it can tell.
+            let mut validating = false;
+
+            if let Some(entry) = stored.as_ref() {
+                if let Some(etag) = entry.headers.get(header::ETAG) {
+                    request
+                        .headers_mut()
+                        .insert(header::IF_NONE_MATCH, etag.clone());
+                    validating = true;
+                }
+
+                if let Some(last_modified) = entry.headers.get(header::LAST_MODIFIED) {
+                    request
+                        .headers_mut()
+                        .insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+                    validating = true;
+                }
+            }
+
+            let vary = request.headers().clone();
+            let requested = SystemTime::now();
+            let mut response = ctx.send(request).await?;
+            let times = response
+                .extensions()
+                .get::<ResponseTimes>()
+                .cloned()
+                .unwrap_or_else(|| ResponseTimes {
+                    requested,
+                    received: SystemTime::now(),
+                });
+
+            if let Some(entry) = stored.filter(|_| validating) {
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    tracing::debug!("cached response was revalidated");
+
+                    let entry = updated_entry(&entry, response.headers(), times);
+                    let response = cached_response(&entry, CacheStatus::Revalidated);
+                    self.store.insert(&key, entry);
+
+                    return Ok(response);
+                }
+            }
+
+            if is_storable(&response, &request_control) {
+                let entry = Entry {
+                    status: response.status(),
+                    version: response.version(),
+                    headers: response.headers().clone(),
+                    body: Vec::new(),
+                    times,
+                    vary: select_vary(&vary, response.headers()),
+                };
+
+                let body = mem::take(response.body_mut());
+                let len = body.len();
+                let reader = CachingReader {
+                    inner: body,
+                    buffer: Vec::new(),
+                    pending: Some((key, entry)),
+                    store: self.store.clone(),
+                };
+
+                *response.body_mut() = match len {
+                    Some(len) => AsyncBody::from_reader_sized(reader, len),
+                    None => AsyncBody::from_reader(reader),
+                };
+            }
+
+            response.extensions_mut().insert(CacheStatus::Miss);
+
+            Ok(response)
+        })
+    }
+}
+
+/// Check whether a response to the given request could be taken from the
+/// cache or stored in it.
+fn is_cacheable_request(request: &Request<AsyncBody>) -> bool {
+    request.method() == Method::GET
+        && !request.headers().contains_key(header::RANGE)
+        && !CONDITIONAL_HEADERS
+            .iter()
+            .any(|name| request.headers().contains_key(name))
+}
+
+/// Check whether a response may be stored in the cache, as described in [RFC
+/// 7234, section 3](https://tools.ietf.org/html/rfc7234#section-3).
+fn is_storable(response: &Response<AsyncBody>, request_control: &CacheControl) -> bool {
+    let control = CacheControl::parse(response.headers());
+
+    if request_control.no_store || control.no_store {
+        return false;
+    }
+
+    // A response varying on everything can never be reused.
+    if vary_names(response.headers())
+        .iter()
+        .any(|name| name == "*")
+    {
+        return false;
+    }
+
+    let has_validator = response.headers().contains_key(header::ETAG)
+        || response.headers().contains_key(header::LAST_MODIFIED);
+    let has_expiration =
+        control.max_age.is_some() || response.headers().contains_key(header::EXPIRES);
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT | StatusCode::NOT_MODIFIED => false,
+        status if super::control::is_heuristically_cacheable(status) => {
+            has_expiration || has_validator
+        }
+        status => has_expiration && !status.is_informational(),
+    }
+}
+
+/// Get the values of the request headers that the response varies on.
+fn select_vary(request_headers: &HeaderMap, response_headers: &HeaderMap) -> HeaderMap {
+    let mut vary = HeaderMap::new();
+
+    for name in vary_names(response_headers) {
+        for value in request_headers.get_all(name.as_str()) {
+            if let Ok(name) = header::HeaderName::from_bytes(name.as_bytes()) {
+                vary.append(name, value.clone());
+            }
+        }
+    }
+
+    vary
+}
+
+/// Update a stored response with the headers of a `304 Not Modified`
+/// response, as described in [RFC 7234, section
+/// 4.3.4](https://tools.ietf.org/html/rfc7234#section-4.3.4).
+fn updated_entry(entry: &Entry, headers: &HeaderMap, times: ResponseTimes) -> Entry {
+    let mut entry = entry.clone();
+
+    for name in headers.keys() {
+        if !BODY_HEADERS.contains(name) {
+            entry.headers.remove(name);
+
+            for value in headers.get_all(name) {
+                entry.headers.append(name, value.clone());
+            }
+        }
+    }
+
+    entry.times = times;
+    entry
+}
+
+/// Create a response from a stored one.
+fn cached_response(entry: &Entry, status: CacheStatus) -> Response<AsyncBody> {
+    let mut response = Response::new(AsyncBody::from(entry.body.clone()));
+
+    *response.status_mut() = entry.status;
+    *response.version_mut() = entry.version;
+    *response.headers_mut() = entry.headers.clone();
+
+    if let Some(age) = headers::age(&entry.headers, Some(&entry.times), SystemTime::now()) {
+        if let Ok(value) = HeaderValue::from_str(&age.as_secs().to_string()) {
+            response.headers_mut().insert(header::AGE, value);
+        }
+    }
+
+    response.extensions_mut().insert(entry.times);
+    response.extensions_mut().insert(status);
+    response
+}
+
+/// Response body reader that stores the response in the cache once the whole
+/// body has been read.
+struct CachingReader {
+    inner: AsyncBody,
+    buffer: Vec<u8>,
+    pending: Option<(String, Entry)>,
+    store: Arc<Store>,
+}
+
+impl AsyncRead for CachingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let result = futures_lite::ready!(Pin::new(&mut this.inner).poll_read(cx, buf));
+
+        match &result {
+            Ok(0) => {
+                if let Some((key, mut entry)) = this.pending.take() {
+                    entry.body = mem::take(&mut this.buffer);
+                    this.store.insert(&key, entry);
+                }
+            }
+            Ok(len) if this.pending.is_some() => {
+                // Give up on storing bodies that are too large.
+                if (this.buffer.len() + len) as u64 > this.store.max_size() {
+                    this.pending = None;
+                    this.buffer = Vec::new();
+                } else {
+                    this.buffer.extend_from_slice(&buf[..*len]);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => {
+                this.pending = None;
+                this.buffer = Vec::new();
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
@@ -0,0 +1,97 @@
+//! HTTP response caching.
+//!
+//! A client can keep responses to `GET` requests in a cache and reuse them
+//! for later requests, following the rules of [RFC
+//! 7234](https://tools.ietf.org/html/rfc7234). Responses are reused for as
+//! long as they are fresh according to their `Cache-Control`, `Expires` and
+//! `Last-Modified` headers. Once they become stale, they are revalidated with
+//! the server using their `ETag` or `Last-Modified` header, so that unchanged
+//! responses do not need to be downloaded again.
+//!
+//! The cache acts like a private cache, such as the one of a browser. To
+//! enable it, pass a [`CacheConfig`] to
+//! [`HttpClientBuilder::cache`](crate::HttpClientBuilder::cache). Use
+//! [`ResponseExt::cache_status`](crate::ResponseExt::cache_status) to find out
+//! whether a response came from the cache.
+//!
+//! This module is only available when the
+//! [`http-cache`](../index.html#http-cache) feature is enabled.
+
+use std::path::PathBuf;
+
+mod control;
+pub(crate) mod interceptor;
+mod store;
+
+/// Configuration of an HTTP response cache.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{cache::CacheConfig, HttpClient};
+///
+/// let client = HttpClient::builder()
+///     .cache(CacheConfig::in_memory().max_size(16 * 1024 * 1024))
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+    dir: Option<PathBuf>,
+    max_size: u64,
+}
+
+impl CacheConfig {
+    /// Keep cached responses in memory, for as long as the client lives.
+    pub fn in_memory() -> Self {
+        Self {
+            dir: None,
+            max_size: 64 * 1024 * 1024,
+        }
+    }
+
+    /// Keep cached responses in files in the given directory, so that they
+    /// can be reused by later processes. The directory is created when the
+    /// client is built if it does not exist.
+    ///
+    /// The directory should be dedicated to the cache, as files in it may be
+    /// deleted to make room for new responses. Several clients may share the
+    /// same directory.
+    pub fn on_disk(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..Self::in_memory()
+        }
+    }
+
+    /// Set the maximum total size in bytes of the response bodies kept in the
+    /// cache. The default is 64 MiB.
+    ///
+    /// Once the limit is reached, the least recently used responses are
+    /// evicted to make room for new ones. For a cache on disk, the responses
+    /// stored longest ago are evicted instead. Responses larger than the
+    /// limit are never stored.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+}
+
+/// Where a response came from, with respect to the cache.
+///
+/// See [`ResponseExt::cache_status`](crate::ResponseExt::cache_status).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CacheStatus {
+    /// The response was fresh in the cache and was returned without
+    /// contacting the server.
+    Hit,
+
+    /// The response was received from the server, either because it was not
+    /// in the cache or because the request could not use the cache.
+    Miss,
+
+    /// The response was stale in the cache, and the server confirmed that it
+    /// is still valid. Only the headers were received from the server.
+    Revalidated,
+}
@@ -0,0 +1,484 @@
+//! Storage of cached responses, either in memory or in files on disk.
+
+use crate::{
+    digest::Sha256,
+    parsing::{parse_header, parse_status_line},
+    response::ResponseTimes,
+};
+use http::{HeaderMap, StatusCode, Version};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// First line of every cache file, to recognize files written by a different
+/// version of the format.
+const MAGIC: &str = "isahc-cache 1";
+
+/// A response kept in the cache.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+    pub(crate) times: ResponseTimes,
+
+    /// The request headers named by the `Vary` header of the response, as
+    /// sent in the request the response was received for.
+    pub(crate) vary: HeaderMap,
+}
+
+impl Entry {
+    /// Check whether this response can be used for a request with the given
+    /// headers, according to its `Vary` header.
+    pub(crate) fn matches(&self, request_headers: &HeaderMap) -> bool {
+        vary_names(&self.headers).iter().all(|name| {
+            self.vary
+                .get_all(name.as_str())
+                .iter()
+                .eq(request_headers.get_all(name.as_str()).iter())
+        })
+    }
+}
+
+/// Get the names of the request headers listed in the `Vary` header of a
+/// response, in lowercase.
+pub(crate) fn vary_names(headers: &HeaderMap) -> Vec<String> {
+    crate::headers::values(headers, "vary")
+        .into_iter()
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+/// A cache of responses, keyed by URI.
+pub(crate) struct Store {
+    max_size: u64,
+    backend: Backend,
+}
+
+enum Backend {
+    Memory(Mutex<Memory>),
+    Disk(PathBuf),
+}
+
+#[derive(Default)]
+struct Memory {
+    entries: HashMap<String, (Arc<Entry>, u64)>,
+    size: u64,
+
+    /// Incremented on every access, to find the least recently used entry.
+    clock: u64,
+}
+
+impl Store {
+    pub(crate) fn memory(max_size: u64) -> Self {
+        Self {
+            max_size,
+            backend: Backend::Memory(Mutex::default()),
+        }
+    }
+
+    /// Create a store keeping entries in the given directory, creating it if
+    /// it does not exist.
+    pub(crate) fn disk(dir: PathBuf, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            max_size,
+            backend: Backend::Disk(dir),
+        })
+    }
+
+    pub(crate) fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<Entry>> {
+        match &self.backend {
+            Backend::Memory(memory) => {
+                let mut memory = memory.lock().unwrap_or_else(|e| e.into_inner());
+                memory.clock += 1;
+
+                let clock = memory.clock;
+                let (entry, last_used) = memory.entries.get_mut(key)?;
+                *last_used = clock;
+
+                Some(entry.clone())
+            }
+            Backend::Disk(dir) => match read_file(&entry_path(dir, key), key) {
+                Ok(entry) => entry.map(Arc::new),
+                Err(e) => {
+                    tracing::debug!("failed to read cached response: {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    pub(crate) fn insert(&self, key: &str, entry: Entry) {
+        let size = entry.body.len() as u64;
+
+        if size > self.max_size {
+            return;
+        }
+
+        match &self.backend {
+            Backend::Memory(memory) => {
+                let mut memory = memory.lock().unwrap_or_else(|e| e.into_inner());
+
+                if let Some((old, _)) = memory.entries.remove(key) {
+                    memory.size -= old.body.len() as u64;
+                }
+
+                // Evict the least recently used entries until the new one
+                // fits.
+                while memory.size + size > self.max_size {
+                    let oldest = memory
+                        .entries
+                        .iter()
+                        .min_by_key(|(_, (_, last_used))| *last_used)
+                        .map(|(key, _)| key.clone());
+
+                    match oldest.and_then(|key| memory.entries.remove(&key)) {
+                        Some((old, _)) => memory.size -= old.body.len() as u64,
+                        None => break,
+                    }
+                }
+
+                memory.clock += 1;
+                memory.size += size;
+
+                let clock = memory.clock;
+                memory
+                    .entries
+                    .insert(key.to_owned(), (Arc::new(entry), clock));
+            }
+            Backend::Disk(dir) => {
+                if let Err(e) = evict_files(dir, self.max_size.saturating_sub(size)) {
+                    tracing::debug!("failed to evict cached responses: {}", e);
+                }
+
+                if let Err(e) = write_file(dir, key, &entry) {
+                    tracing::debug!("failed to write cached response: {}", e);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        match &self.backend {
+            Backend::Memory(memory) => {
+                let mut memory = memory.lock().unwrap_or_else(|e| e.into_inner());
+
+                if let Some((old, _)) = memory.entries.remove(key) {
+                    memory.size -= old.body.len() as u64;
+                }
+            }
+            Backend::Disk(dir) => {
+                let _ = fs::remove_file(entry_path(dir, key));
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Store");
+
+        if let Backend::Disk(dir) = &self.backend {
+            debug.field("dir", dir);
+        }
+
+        debug.field("max_size", &self.max_size).finish()
+    }
+}
+
+/// Get the path of the file holding the entry for the given key. Keys are
+/// hashed, since URIs can contain characters that are not allowed in file
+/// names.
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    let name = Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    dir.join(name)
+}
+
+/// Delete the files stored longest ago from the given directory until the
+/// files left take up at most the given number of bytes.
+fn evict_files(dir: &Path, max_size: u64) -> io::Result<()> {
+    let mut files = Vec::new();
+    let mut size = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_file() {
+            size += metadata.len();
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    files.sort();
+
+    for (_, len, path) in files {
+        if size <= max_size {
+            break;
+        }
+
+        fs::remove_file(path)?;
+        size -= len;
+    }
+
+    Ok(())
+}
+
+/// Write an entry to a file, in a format resembling an HTTP response preceded
+/// by some metadata.
+fn write_file(dir: &Path, key: &str, entry: &Entry) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(entry.body.len() + 1024);
+
+    writeln!(buf, "{}", MAGIC)?;
+    writeln!(buf, "{}", key)?;
+    writeln!(
+        buf,
+        "{} {}",
+        to_nanos(entry.times.requested),
+        to_nanos(entry.times.received)
+    )?;
+    write_headers(&mut buf, &entry.vary)?;
+    writeln!(
+        buf,
+        "{} {}",
+        version_str(entry.version),
+        entry.status.as_u16()
+    )?;
+    write_headers(&mut buf, &entry.headers)?;
+    buf.extend_from_slice(&entry.body);
+
+    // Write to a temporary file first, so that readers never see a partially
+    // written entry.
+    let path = entry_path(dir, key);
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(&buf)?;
+    temp.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+fn write_headers(buf: &mut Vec<u8>, headers: &HeaderMap) -> io::Result<()> {
+    for (name, value) in headers {
+        write!(buf, "{}: ", name)?;
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(b'\n');
+    }
+
+    buf.push(b'\n');
+
+    Ok(())
+}
+
+/// Read the entry stored in a file for the given key, if there is one.
+fn read_file(path: &Path, key: &str) -> io::Result<Option<Entry>> {
+    let buf = match fs::read(path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid cache file");
+    let mut rest = &buf[..];
+
+    if next_line(&mut rest) != Some(MAGIC.as_bytes()) {
+        return Err(invalid());
+    }
+
+    // Files are named after a hash of the key, so check that the file is
+    // really for this key.
+    if next_line(&mut rest) != Some(key.as_bytes()) {
+        return Ok(None);
+    }
+
+    let times = next_line(&mut rest)
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .and_then(|line| {
+            let mut times = line
+                .split(' ')
+                .map(|time| time.parse().ok().map(from_nanos));
+
+            Some(ResponseTimes {
+                requested: times.next()??,
+                received: times.next()??,
+            })
+        })
+        .ok_or_else(invalid)?;
+
+    let vary = read_headers(&mut rest).ok_or_else(invalid)?;
+    let (version, status) = next_line(&mut rest)
+        .and_then(parse_status_line)
+        .ok_or_else(invalid)?;
+    let headers = read_headers(&mut rest).ok_or_else(invalid)?;
+
+    Ok(Some(Entry {
+        status,
+        version,
+        headers,
+        body: rest.to_vec(),
+        times,
+        vary,
+    }))
+}
+
+/// Take the next line from the start of a buffer.
+fn next_line<'a>(rest: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let end = rest.iter().position(|&b| b == b'\n')?;
+    let line = &rest[..end];
+    *rest = &rest[end + 1..];
+
+    Some(line)
+}
+
+/// Take header lines from the start of a buffer, up to a blank line.
+fn read_headers(rest: &mut &[u8]) -> Option<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    loop {
+        match next_line(rest)? {
+            b"" => return Some(headers),
+            line => {
+                let (name, value) = parse_header(line)?;
+                headers.append(name, value);
+            }
+        }
+    }
+}
+
+fn version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+}
+
+fn to_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn from_nanos(nanos: u128) -> SystemTime {
+    let secs = (nanos / 1_000_000_000) as u64;
+    let nanos = (nanos % 1_000_000_000) as u32;
+
+    UNIX_EPOCH + Duration::new(secs, nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn entry(body: &str) -> Entry {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        headers.append("vary", HeaderValue::from_static("Accept-Language"));
+        headers.append("x-multi", HeaderValue::from_static("a"));
+        headers.append("x-multi", HeaderValue::from_static("b"));
+
+        let mut vary = HeaderMap::new();
+        vary.insert("accept-language", HeaderValue::from_static("en"));
+
+        Entry {
+            status: StatusCode::OK,
+            version: Version::HTTP_2,
+            headers,
+            body: body.as_bytes().to_vec(),
+            times: ResponseTimes {
+                requested: UNIX_EPOCH + Duration::new(1_600_000_000, 123),
+                received: UNIX_EPOCH + Duration::new(1_600_000_001, 456),
+            },
+            vary,
+        }
+    }
+
+    #[test]
+    fn vary_matching() {
+        let entry = entry("hello");
+        let mut headers = HeaderMap::new();
+
+        assert!(!entry.matches(&headers));
+
+        headers.insert("accept-language", HeaderValue::from_static("en"));
+        assert!(entry.matches(&headers));
+
+        headers.insert("accept-language", HeaderValue::from_static("de"));
+        assert!(!entry.matches(&headers));
+    }
+
+    #[test]
+    fn memory_evicts_least_recently_used() {
+        let store = Store::memory(10);
+
+        store.insert("a", entry("1234"));
+        store.insert("b", entry("1234"));
+        assert!(store.get("a").is_some());
+
+        store.insert("c", entry("1234"));
+        assert!(store.get("a").is_some());
+        assert!(store.get("b").is_none());
+        assert!(store.get("c").is_some());
+
+        store.insert("d", entry("12345678901"));
+        assert!(store.get("d").is_none());
+
+        store.remove("a");
+        assert!(store.get("a").is_none());
+    }
+
+    #[test]
+    fn disk_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::disk(dir.path().join("cache"), 1024).unwrap();
+
+        store.insert("https://example.org/a?b", entry("hello\nworld\n\n"));
+
+        let read = store.get("https://example.org/a?b").unwrap();
+        let original = entry("hello\nworld\n\n");
+
+        assert_eq!(read.status, original.status);
+        assert_eq!(read.version, original.version);
+        assert_eq!(read.headers, original.headers);
+        assert_eq!(read.body, original.body);
+        assert_eq!(read.times.requested, original.times.requested);
+        assert_eq!(read.times.received, original.times.received);
+        assert_eq!(read.vary, original.vary);
+
+        assert!(store.get("https://example.org/other").is_none());
+
+        store.remove("https://example.org/a?b");
+        assert!(store.get("https://example.org/a?b").is_none());
+    }
+
+    #[test]
+    fn disk_evicts_to_make_room() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::disk(dir.path().to_owned(), 300).unwrap();
+
+        store.insert("a", entry(&"x".repeat(100)));
+        store.insert("b", entry(&"x".repeat(100)));
+
+        assert!(store.get("a").is_none());
+        assert!(store.get("b").is_some());
+    }
+}
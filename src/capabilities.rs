@@ -0,0 +1,211 @@
+//! Runtime information about what the linked libcurl supports.
+
+use once_cell::sync::Lazy;
+
+/// A report of the features supported by the libcurl that Isahc is linked
+/// against, and the versions of the libraries it uses.
+///
+/// Depending on how Isahc was built, libcurl may be compiled from source with
+/// a known set of features, or it may be the libcurl installed on the system,
+/// whose features are only known at runtime. This report can be used to
+/// decide whether to use a feature before sending a request that requires it.
+///
+/// Use [`capabilities`](crate::capabilities) to get the report.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::config::VersionNegotiation;
+///
+/// let negotiation = if isahc::capabilities().http2() {
+///     VersionNegotiation::http2()
+/// } else {
+///     VersionNegotiation::http11()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    isahc_version: &'static str,
+    curl_version: String,
+    ssl_version: Option<String>,
+    nghttp2_version: Option<String>,
+    quic_version: Option<String>,
+    brotli_version: Option<String>,
+    zstd_version: Option<String>,
+    libz_version: Option<String>,
+    protocols: Vec<String>,
+    ipv6: bool,
+    async_dns: bool,
+    idn: bool,
+    unix_sockets: bool,
+    ntlm: bool,
+    spnego: bool,
+    http2: bool,
+    http3: bool,
+    brotli: bool,
+    zstd: bool,
+}
+
+impl Capabilities {
+    fn probe() -> Self {
+        let version = curl::Version::get();
+        let owned = |s: Option<&str>| s.map(String::from);
+
+        Self {
+            isahc_version: env!("CARGO_PKG_VERSION"),
+            curl_version: version.version().to_owned(),
+            ssl_version: owned(version.ssl_version()),
+            nghttp2_version: owned(version.nghttp2_version()),
+            quic_version: owned(version.quic_version()),
+            brotli_version: owned(version.brotli_version()),
+            zstd_version: owned(version.zstd_version()),
+            libz_version: owned(version.libz_version()),
+            protocols: version.protocols().map(String::from).collect(),
+            ipv6: version.feature_ipv6(),
+            async_dns: version.feature_async_dns(),
+            idn: version.feature_idn(),
+            unix_sockets: version.feature_unix_domain_socket(),
+            ntlm: version.feature_ntlm(),
+            spnego: version.feature_spnego(),
+            http2: version.feature_http2(),
+            http3: version.feature_http3(),
+            brotli: version.feature_brotli() || cfg!(feature = "brotli"),
+            zstd: version.feature_zstd() || cfg!(feature = "zstd"),
+        }
+    }
+
+    /// Get the version of Isahc.
+    pub fn isahc_version(&self) -> &str {
+        self.isahc_version
+    }
+
+    /// Get the version of libcurl, such as `7.74.0`.
+    pub fn curl_version(&self) -> &str {
+        &self.curl_version
+    }
+
+    /// Get the name and version of the TLS backend used by libcurl, such as
+    /// `OpenSSL/1.1.1i`, or `None` if libcurl was built without TLS support.
+    pub fn ssl_version(&self) -> Option<&str> {
+        self.ssl_version.as_deref()
+    }
+
+    /// Get the version of the nghttp2 library used for HTTP/2, if any.
+    pub fn nghttp2_version(&self) -> Option<&str> {
+        self.nghttp2_version.as_deref()
+    }
+
+    /// Get the name and version of the QUIC library used for HTTP/3, if any.
+    pub fn quic_version(&self) -> Option<&str> {
+        self.quic_version.as_deref()
+    }
+
+    /// Get the version of the brotli library, if any.
+    pub fn brotli_version(&self) -> Option<&str> {
+        self.brotli_version.as_deref()
+    }
+
+    /// Get the version of the zstd library, if any.
+    pub fn zstd_version(&self) -> Option<&str> {
+        self.zstd_version.as_deref()
+    }
+
+    /// Get the version of the zlib library, if any.
+    pub fn libz_version(&self) -> Option<&str> {
+        self.libz_version.as_deref()
+    }
+
+    /// Get the URI schemes supported by libcurl, such as `http` and `https`.
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    /// Check whether the given URI scheme is supported by libcurl.
+    pub fn supports_protocol(&self, scheme: &str) -> bool {
+        self.protocols
+            .iter()
+            .any(|protocol| protocol.eq_ignore_ascii_case(scheme))
+    }
+
+    /// Check whether TLS is supported.
+    pub fn tls(&self) -> bool {
+        self.ssl_version.is_some()
+    }
+
+    /// Check whether IPv6 is supported.
+    pub fn ipv6(&self) -> bool {
+        self.ipv6
+    }
+
+    /// Check whether host names are resolved asynchronously.
+    pub fn async_dns(&self) -> bool {
+        self.async_dns
+    }
+
+    /// Check whether internationalized domain names are supported.
+    pub fn idn(&self) -> bool {
+        self.idn
+    }
+
+    /// Check whether connecting to Unix sockets is supported. See
+    /// [`Dialer::unix_socket`](crate::config::Dialer::unix_socket).
+    pub fn unix_sockets(&self) -> bool {
+        self.unix_sockets
+    }
+
+    /// Check whether NTLM authentication is supported.
+    pub fn ntlm(&self) -> bool {
+        self.ntlm
+    }
+
+    /// Check whether SPNEGO (Negotiate) authentication is supported.
+    pub fn spnego(&self) -> bool {
+        self.spnego
+    }
+
+    /// Check whether HTTP/2 is supported.
+    pub fn http2(&self) -> bool {
+        self.http2
+    }
+
+    /// Check whether HTTP/3 is supported.
+    pub fn http3(&self) -> bool {
+        self.http3
+    }
+
+    /// Check whether responses compressed with brotli can be decompressed,
+    /// either by libcurl or by Isahc itself if the `brotli` feature is
+    /// enabled.
+    pub fn brotli(&self) -> bool {
+        self.brotli
+    }
+
+    /// Check whether responses compressed with zstd can be decompressed,
+    /// either by libcurl or by Isahc itself if the `zstd` feature is enabled.
+    pub fn zstd(&self) -> bool {
+        self.zstd
+    }
+}
+
+/// Get the capabilities report for the linked libcurl. The report is created
+/// the first time this is called.
+pub(crate) fn get() -> &'static Capabilities {
+    static CAPABILITIES: Lazy<Capabilities> = Lazy::new(Capabilities::probe);
+
+    &CAPABILITIES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_matches_curl() {
+        let capabilities = get();
+
+        assert_eq!(capabilities.curl_version(), curl::Version::get().version());
+        assert!(capabilities.supports_protocol("http"));
+        assert!(capabilities.supports_protocol("HTTP"));
+        assert!(!capabilities.supports_protocol("nonexistent"));
+    }
+}
@@ -5,44 +5,82 @@ use crate::{
     auth::{Authentication, Credentials},
     body::{AsyncBody, Body},
     config::{
+        digest::{SendDigest, VerifyDigest},
+        dns::PrefetchedHosts,
+        fallback::DowngradedHosts,
         internal::{ConfigurableBase, SetOpt},
         *,
     },
+    curl_command::Options,
     default_headers::DefaultHeadersInterceptor,
-    error::{Error, ErrorKind},
+    digest::{self, Sha256},
+    download::{self, DownloadOutcome},
+    error::{ConfigValidationError, Error, ErrorKind},
     handler::{RequestHandler, ResponseBodyReader},
     headers::HasHeaders,
+    hsts::{transfer::HstsTransfer, HstsStore},
     interceptor::{self, Interceptor, InterceptorObj},
+    intermediary::AltUsed,
+    metrics::{AgentStats, PoolStats},
     parsing::header_to_curl_string,
+    range::RequestedRange,
+    request::{RequestExt, Route, Tags},
+    response::{ContentDecoded, ResponseExt},
+    share::ShareHandle,
+    task::{block_on_timeout, Delay, ThreadPool},
+    validate::{header_list_size, validate_request},
 };
 use futures_lite::{
-    future::{block_on, try_zip},
-    io::AsyncRead,
+    future::{self, block_on, try_zip},
+    io::{AsyncRead, AsyncReadExt},
+    ready,
+    stream::Stream,
 };
 use http::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Request,
     Response,
+    StatusCode,
+    Uri,
 };
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use std::{
     convert::TryFrom,
     fmt,
     future::Future,
     io,
+    path::Path,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Condvar, Mutex},
     task::{Context, Poll},
     time::Duration,
 };
 use tracing_futures::Instrument;
 
-static USER_AGENT: Lazy<String> = Lazy::new(|| {
-    format!(
-        "curl/{} isahc/{}",
-        curl::Version::get().version(),
-        env!("CARGO_PKG_VERSION")
-    )
+/// The global client instance used by the free request functions.
+static SHARED: OnceCell<HttpClient> = OnceCell::new();
+
+/// Threads that hand detached requests over to the agent of their client, so
+/// that sending one does not block the caller.
+static DETACHED_POOL: ThreadPool = ThreadPool::new("isahc-detached", 4);
+
+/// How to create the global client instance.
+static SHARED_SETUP: Lazy<Mutex<SharedSetup>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct SharedSetup {
+    /// Function to configure the builder of the global client with.
+    configure: Option<Box<dyn FnOnce(HttpClientBuilder) -> HttpClientBuilder + Send>>,
+
+    /// Set once the global client has been or is being created, after which
+    /// it can no longer be changed.
+    started: bool,
+}
+
+static DEFAULT_USER_AGENT: Lazy<HeaderValue> = Lazy::new(|| {
+    UserAgent::expand(UserAgent::DEFAULT_TEMPLATE)
+        .parse()
+        .unwrap()
 });
 
 /// An HTTP client builder, capable of creating custom [`HttpClient`] instances
@@ -76,6 +114,9 @@ pub struct HttpClientBuilder {
 
     #[cfg(feature = "cookies")]
     cookie_jar: Option<crate::cookies::CookieJar>,
+
+    #[cfg(feature = "http-cache")]
+    cache: Option<crate::cache::CacheConfig>,
 }
 
 impl Default for HttpClientBuilder {
@@ -102,6 +143,9 @@ impl HttpClientBuilder {
         // Erase curl's default auth method of Basic.
         defaults.insert(Authentication::default());
 
+        // Retry requests that fail on stale pooled connections by default.
+        defaults.insert(RetryStaleConnection(true));
+
         Self {
             agent_builder: AgentBuilder::default(),
             defaults,
@@ -117,6 +161,9 @@ impl HttpClientBuilder {
 
             #[cfg(feature = "cookies")]
             cookie_jar: None,
+
+            #[cfg(feature = "http-cache")]
+            cache: None,
         }
     }
 
@@ -158,14 +205,62 @@ impl HttpClientBuilder {
         self.cookie_jar(Default::default())
     }
 
-    /// Add a request interceptor to the client.
+    /// Enable caching of responses using the given cache configuration.
+    ///
+    /// Responses to `GET` requests are stored in the cache and reused by later
+    /// requests for as long as they are fresh, following the rules of [RFC
+    /// 7234](https://tools.ietf.org/html/rfc7234). Stale responses are
+    /// revalidated with the server before being reused. See the
+    /// [`cache`](crate::cache) module for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{cache::{CacheConfig, CacheStatus}, prelude::*, HttpClient};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .cache(CacheConfig::on_disk("/tmp/isahc-cache"))
+    ///     .build()?;
+    ///
+    /// let response = client.get("https://example.org")?;
+    ///
+    /// if response.cache_status() == Some(CacheStatus::Hit) {
+    ///     println!("response was cached");
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
     ///
     /// # Availability
     ///
     /// This method is only available when the
-    /// [`unstable-interceptors`](index.html#unstable-interceptors) feature is
-    /// enabled.
-    #[cfg(feature = "unstable-interceptors")]
+    /// [`http-cache`](index.html#http-cache) feature is enabled.
+    #[cfg(feature = "http-cache")]
+    pub fn cache(mut self, config: crate::cache::CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Add a request interceptor to the client.
+    ///
+    /// Interceptors can inspect and modify every request sent by the client
+    /// and every response it receives. They are invoked in the order they are
+    /// added, so the first interceptor added sees each request first and each
+    /// response last. See the [`interceptor`](crate::interceptor) module for
+    /// how to implement one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .interceptor(isahc::interceptor!(request, ctx, {
+    ///         println!("sending request to {}", request.uri());
+    ///         ctx.send(request).await
+    ///     }))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
     #[inline]
     pub fn interceptor(self, interceptor: impl Interceptor + 'static) -> Self {
         self.interceptor_impl(interceptor)
@@ -237,6 +332,101 @@ impl HttpClientBuilder {
         self
     }
 
+    /// Set the maximum number of requests that may be sent concurrently over
+    /// a single HTTP/2 connection.
+    ///
+    /// This is advertised to servers with the `SETTINGS_MAX_CONCURRENT_STREAMS`
+    /// setting. Once a connection carries this many requests, further
+    /// requests to the same host open another connection, or stay in a
+    /// "pending" state if the connection limits do not allow that.
+    ///
+    /// Setting this value to `0` uses the libcurl default, which is `100`.
+    ///
+    /// Other HTTP/2 settings, such as the initial flow control window size,
+    /// cannot be configured with libcurl. To limit the size of request
+    /// headers, see
+    /// [`Configurable::max_header_list_size`](crate::config::Configurable::max_header_list_size).
+    pub fn http2_max_concurrent_streams(mut self, max: usize) -> Self {
+        self.agent_builder = self.agent_builder.max_concurrent_streams(max);
+        self
+    }
+
+    /// Set a maximum number of requests that this client executes at one
+    /// time.
+    ///
+    /// If set to a value greater than zero, requests sent while `max`
+    /// requests are already in progress wait in a queue inside the client
+    /// until an earlier request completes. Queued requests are started in the
+    /// order they were sent. Unlike the connection limits, this also bounds
+    /// the amount of work done by the client at any one time, since queued
+    /// requests are not handed to libcurl at all until they start.
+    ///
+    /// The number of queued requests is available from
+    /// [`AgentStats::queued_requests`](crate::AgentStats::queued_requests),
+    /// which can be used to stop sending new requests while the client is
+    /// busy. Queued requests are subject to the
+    /// [`queue_timeout`](crate::config::Configurable::queue_timeout).
+    ///
+    /// Setting this value to `0` disables the limit entirely. By default this
+    /// value is `0` and no limit is enforced.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .max_concurrent_requests(16)
+    ///     .build()?;
+    ///
+    /// if client.agent_stats().queued_requests() < 100 {
+    ///     client.get("https://example.org")?;
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.agent_builder = self.agent_builder.max_concurrent_requests(max);
+        self
+    }
+
+    /// Accept responses pushed by HTTP/2 servers.
+    ///
+    /// Servers may push responses to requests they expect the client to make
+    /// next along with a response, such as stylesheets along with a web page.
+    /// By default pushed responses are refused. Once enabled, they are
+    /// received along with every response sent over HTTP/2 and made available
+    /// using
+    /// [`ResponseExt::pushed_responses`](crate::ResponseExt::pushed_responses).
+    ///
+    /// Pushed responses are buffered in memory until they are taken, and are
+    /// dropped if nobody is listening for them. They are not used to answer
+    /// later requests automatically.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::VersionNegotiation, prelude::*, HttpClient};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .version_negotiation(VersionNegotiation::http2())
+    ///     .enable_push()
+    ///     .build()?;
+    ///
+    /// let mut response = client.get("https://example.org")?;
+    /// let page = response.text()?;
+    ///
+    /// if let Some(pushed) = response.pushed_responses() {
+    ///     for pushed in pushed.clone() {
+    ///         println!("{} was pushed", pushed.request().uri());
+    ///     }
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn enable_push(mut self) -> Self {
+        self.agent_builder = self.agent_builder.enable_push(true);
+        self
+    }
+
     /// Set the size of the connection cache.
     ///
     /// After requests are completed, if the underlying connection is reusable,
@@ -254,6 +444,37 @@ impl HttpClientBuilder {
         self
     }
 
+    /// Share caches with other clients using the given share handle.
+    ///
+    /// All clients built with clones of the same [`ShareHandle`] use the same
+    /// DNS cache, TLS session cache, and connection pool, depending on what
+    /// the handle was configured to share. This avoids repeating DNS lookups
+    /// and TLS handshakes in applications that use many clients.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{HttpClient, ShareHandle};
+    ///
+    /// let share = ShareHandle::new()?;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .share(share.clone())
+    ///     .build()?;
+    ///
+    /// let other_client = HttpClient::builder()
+    ///     .share(share)
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn share(mut self, handle: ShareHandle) -> Self {
+        self.agent_builder = self
+            .agent_builder
+            .shared_connections(handle.shares_connections());
+        self.defaults.insert(handle);
+        self
+    }
+
     /// Configure DNS caching.
     ///
     /// By default, DNS entries are cached by the client executing the request
@@ -290,34 +511,6 @@ impl HttpClientBuilder {
         self.configure(cache.into())
     }
 
-    /// Set a mapping of DNS resolve overrides.
-    ///
-    /// Entries in the given map will be used first before using the default DNS
-    /// resolver for host+port pairs.
-    ///
-    /// Note that DNS resolving is only performed when establishing a new
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use isahc::config::ResolveMap;
-    /// # use isahc::prelude::*;
-    /// # use std::net::IpAddr;
-    /// #
-    /// let client = HttpClient::builder()
-    ///     .dns_resolve(ResolveMap::new()
-    ///         // Send requests for example.org on port 80 to 127.0.0.1.
-    ///         .add("example.org", 80, [127, 0, 0, 1]))
-    ///     .build()?;
-    /// # Ok::<(), Box<dyn std::error::Error>>(())
-    /// ```
-    pub fn dns_resolve(self, map: ResolveMap) -> Self {
-        // Similar to the dns_cache option, this operation actually affects all
-        // requests in a multi handle so we do not expose it per-request to
-        // avoid confusing behavior.
-        self.configure(map)
-    }
-
     /// Add a default header to be passed with every request.
     ///
     /// If a default header value is already defined for the given key, then a
@@ -363,6 +556,51 @@ impl HttpClientBuilder {
         self
     }
 
+    /// Set the `User-Agent` header to send with requests that do not set one
+    /// explicitly.
+    ///
+    /// The following tokens in the given template are replaced with version
+    /// numbers:
+    ///
+    /// - `{isahc_version}`: The version of Isahc.
+    /// - `{curl_version}`: The version of libcurl in use.
+    ///
+    /// The default is `curl/{curl_version} isahc/{isahc_version}`. An
+    /// individual request can override this using
+    /// [`RequestBuilderExt::user_agent`](crate::RequestBuilderExt::user_agent).
+    ///
+    /// If the resulting value is not a valid header value,
+    /// [`HttpClientBuilder::build`] will return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::HttpClient;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .user_agent("my-app/1.0 isahc/{isahc_version}")
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn user_agent(mut self, template: &str) -> Self {
+        match HeaderValue::try_from(UserAgent::expand(template)) {
+            Ok(value) => {
+                self.defaults.insert(UserAgent(Some(value)));
+            }
+            Err(e) => {
+                self.error = Some(Error::new(ErrorKind::ClientInitialization, e));
+            }
+        }
+        self
+    }
+
+    /// Do not send a `User-Agent` header with requests, unless one is set
+    /// explicitly.
+    pub fn no_user_agent(mut self) -> Self {
+        self.defaults.insert(UserAgent(None));
+        self
+    }
+
     /// Set the default headers to include in every request, replacing any
     /// previously set default headers.
     ///
@@ -435,8 +673,61 @@ impl HttpClientBuilder {
         self
     }
 
+    /// Apply the options in a curl config file, also known as a `.curlrc`
+    /// file, to this builder.
+    ///
+    /// This makes it possible to share configuration with users of the curl
+    /// command line tool, such as a configuration described in an operations
+    /// runbook. Options are applied on top of any options already set, and
+    /// options set afterwards take precedence. Each option has to be given on
+    /// a line of its own, with its long name or a common short name such as
+    /// `-H`, and a value that is either quoted or ends at the first
+    /// whitespace.
+    ///
+    /// Options for timeouts, redirects, HTTP versions, default headers, the
+    /// user agent, authentication, proxies and TLS are supported. Options that
+    /// only affect the output of curl, such as `silent`, are ignored. If any
+    /// other options are found, or any values are invalid,
+    /// [`HttpClientBuilder::build`] returns an error listing each problem.
+    ///
+    /// See [`HttpClient::to_curlrc`] for the reverse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::HttpClient;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .curlrc(
+    ///         r#"
+    ///         max-time = 30
+    ///         location
+    ///         header = "X-Team: platform"
+    ///         proxy = "http://proxy.internal:3128"
+    ///         "#,
+    ///     )
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn curlrc(self, config: &str) -> Self {
+        let (mut builder, problems) = crate::config::curlrc::import(self, config);
+
+        if !problems.is_empty() && builder.error.is_none() {
+            builder.error = Some(ConfigValidationError::new(problems).into());
+        }
+
+        builder
+    }
+
     /// Build an [`HttpClient`] using the configured options.
     ///
+    /// The configured options are validated before the client is created. If
+    /// any options cannot be used together, or are not supported by the
+    /// libcurl in use, an error of kind
+    /// [`ErrorKind::ClientInitialization`](crate::error::ErrorKind::ClientInitialization)
+    /// is returned with a [`ConfigValidationError`](crate::error::ConfigValidationError)
+    /// as its source, listing every problem that was found.
+    ///
     /// If the client fails to initialize, an error will be returned.
     #[allow(unused_mut)]
     pub fn build(mut self) -> Result<HttpClient, Error> {
@@ -444,6 +735,20 @@ impl HttpClientBuilder {
             return Err(err);
         }
 
+        crate::config::validate::validate(&self.defaults)?;
+
+        // Add the cache interceptor if enabled, before any interceptors that
+        // modify requests or responses, so that cached responses are handled
+        // just like fresh ones.
+        #[cfg(feature = "http-cache")]
+        {
+            if let Some(config) = self.cache.take() {
+                let interceptor = crate::cache::interceptor::CacheInterceptor::new(config)
+                    .map_err(|e| Error::new(ErrorKind::ClientInitialization, e))?;
+                self = self.interceptor_impl(interceptor);
+            }
+        }
+
         // Add cookie interceptor if enabled.
         #[cfg(feature = "cookies")]
         {
@@ -452,9 +757,9 @@ impl HttpClientBuilder {
         }
 
         // Add default header interceptor if any default headers were specified.
-        if !self.default_headers.is_empty() {
-            let default_headers = std::mem::take(&mut self.default_headers);
-            self = self.interceptor_impl(DefaultHeadersInterceptor::from(default_headers));
+        let default_headers = std::mem::take(&mut self.default_headers);
+        if !default_headers.is_empty() {
+            self = self.interceptor_impl(DefaultHeadersInterceptor::from(default_headers.clone()));
         }
 
         #[cfg(not(feature = "cookies"))]
@@ -463,8 +768,12 @@ impl HttpClientBuilder {
                 .agent_builder
                 .spawn()
                 .map_err(|e| Error::new(ErrorKind::ClientInitialization, e))?,
+            agent_builder: self.agent_builder,
             defaults: self.defaults,
+            default_headers,
             interceptors: self.interceptors,
+            downgraded_hosts: DowngradedHosts::default(),
+            prefetched_hosts: PrefetchedHosts::default(),
         };
 
         #[cfg(feature = "cookies")]
@@ -473,13 +782,18 @@ impl HttpClientBuilder {
                 .agent_builder
                 .spawn()
                 .map_err(|e| Error::new(ErrorKind::ClientInitialization, e))?,
+            agent_builder: self.agent_builder,
             defaults: self.defaults,
+            default_headers,
             interceptors: self.interceptors,
+            downgraded_hosts: DowngradedHosts::default(),
+            prefetched_hosts: PrefetchedHosts::default(),
             cookie_jar: self.cookie_jar,
         };
 
         Ok(HttpClient {
             inner: Arc::new(inner),
+            overrides: None,
         })
     }
 }
@@ -596,24 +910,82 @@ impl<'a, K: Copy, V: Copy> HeaderPair<K, V> for &'a (K, V) {
 #[derive(Clone)]
 pub struct HttpClient {
     inner: Arc<Inner>,
+
+    /// Configuration overriding the shared defaults, if this client was
+    /// created with [`HttpClient::try_clone_with`].
+    overrides: Option<Arc<Overrides>>,
 }
 
 struct Inner {
     /// This is how we talk to our background agent thread.
     agent: agent::Handle,
 
+    /// Builder the agent was spawned with.
+    agent_builder: AgentBuilder,
+
     /// Map of config values that should be used to configure execution if not
     /// specified in a request.
     defaults: http::Extensions,
 
+    /// Headers added to requests that do not set them. These are applied by
+    /// an interceptor, and only kept here for reference.
+    default_headers: HeaderMap<HeaderValue>,
+
     /// Registered interceptors that requests should pass through.
     interceptors: Vec<InterceptorObj>,
 
+    /// Hosts that recently failed over HTTP/2, and should be contacted using
+    /// HTTP/1.1 for now.
+    downgraded_hosts: DowngradedHosts,
+
+    /// Host names resolved ahead of time using `prefetch_dns`.
+    prefetched_hosts: PrefetchedHosts,
+
     /// Configured cookie jar, if any.
     #[cfg(feature = "cookies")]
     cookie_jar: Option<crate::cookies::CookieJar>,
 }
 
+/// Configuration specific to a sibling client.
+struct Overrides {
+    defaults: http::Extensions,
+    default_headers: HeaderMap<HeaderValue>,
+
+    /// Overrides of the client this sibling was created from, if it was a
+    /// sibling itself.
+    parent: Option<Arc<Overrides>>,
+}
+
+impl Overrides {
+    /// Iterate over these overrides followed by those of each parent, from the
+    /// most specific to the least specific.
+    fn chain(&self) -> impl Iterator<Item = &Self> {
+        std::iter::successors(Some(self), |overrides| overrides.parent.as_deref())
+    }
+}
+
+/// A view of the default configuration values of a client, taking any
+/// overrides into account.
+#[derive(Clone, Copy)]
+pub(crate) struct Defaults<'a> {
+    overrides: Option<&'a Overrides>,
+    shared: &'a http::Extensions,
+}
+
+impl<'a> Defaults<'a> {
+    pub(crate) fn get<T: Send + Sync + 'static>(self) -> Option<&'a T> {
+        self.overrides
+            .and_then(|overrides| overrides.chain().find_map(|o| o.defaults.get()))
+            .or_else(|| self.shared.get())
+    }
+}
+
+impl Options for Defaults<'_> {
+    fn option<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.get()
+    }
+}
+
 impl HttpClient {
     /// Create a new HTTP client using the default configuration.
     ///
@@ -626,10 +998,53 @@ impl HttpClient {
     ///
     /// TODO: Stabilize.
     pub(crate) fn shared() -> &'static Self {
-        static SHARED: Lazy<HttpClient> =
-            Lazy::new(|| HttpClient::new().expect("shared client failed to initialize"));
+        SHARED.get_or_init(|| {
+            let configure = {
+                let mut setup = SHARED_SETUP.lock().unwrap();
+                setup.started = true;
+                setup.configure.take()
+            };
+
+            let builder = match configure {
+                Some(configure) => configure(HttpClientBuilder::default()),
+                None => HttpClientBuilder::default(),
+            };
+
+            builder.build().expect("shared client failed to initialize")
+        })
+    }
+
+    /// Use the given client as the global client instance, unless it was
+    /// created already.
+    pub(crate) fn set_shared(client: Self) -> Result<(), Self> {
+        let mut setup = SHARED_SETUP.lock().unwrap();
+
+        if setup.started {
+            return Err(client);
+        }
+
+        setup.started = true;
+        setup.configure = None;
+        drop(setup);
+
+        SHARED.set(client)
+    }
+
+    /// Configure the global client instance with the given function once it
+    /// is created, unless it was created already.
+    pub(crate) fn configure_shared<F>(configure: F) -> Result<(), F>
+    where
+        F: FnOnce(HttpClientBuilder) -> HttpClientBuilder + Send + 'static,
+    {
+        let mut setup = SHARED_SETUP.lock().unwrap();
+
+        if setup.started {
+            return Err(configure);
+        }
 
-        &SHARED
+        setup.configure = Some(Box::new(configure));
+
+        Ok(())
     }
 
     /// Create a new [`HttpClientBuilder`] for building a custom client.
@@ -637,75 +1052,585 @@ impl HttpClient {
         HttpClientBuilder::default()
     }
 
-    /// Get the configured cookie jar for this HTTP client, if any.
+    /// Create a sibling client that shares the connection pool and agent
+    /// thread of this client, but with some configuration overridden.
     ///
-    /// # Availability
+    /// Cloning an [`HttpClient`] is cheap, as clones share the same
+    /// underlying client through reference counting. This method is just as
+    /// cheap, but lets different layers of an application customize the
+    /// per-request configuration they use, such as default headers or
+    /// timeouts, without creating a whole new client and its resources.
     ///
-    /// This method is only available when the [`cookies`](index.html#cookies)
-    /// feature is enabled.
-    #[cfg(feature = "cookies")]
-    pub fn cookie_jar(&self) -> Option<&crate::cookies::CookieJar> {
-        self.inner.cookie_jar.as_ref()
-    }
-
-    /// Send a GET request to the given URI.
+    /// The given closure receives a [`SiblingClientBuilder`] for setting the
+    /// overrides. Any configuration not overridden is inherited from this
+    /// client, and options set on individual requests still take precedence.
+    /// Options that apply to the client as a whole, such as connection limits
+    /// and interceptors, are always shared.
     ///
-    /// To customize the request further, see [`HttpClient::send`]. To execute
-    /// the request asynchronously, see [`HttpClient::get_async`].
+    /// Returns an error if any of the overrides are invalid.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use isahc::prelude::*;
+    /// use std::time::Duration;
     ///
-    /// # let client = HttpClient::new()?;
-    /// let mut response = client.get("https://example.org")?;
-    /// println!("{}", response.text()?);
+    /// let client = HttpClient::new()?;
+    ///
+    /// let api_client = client.try_clone_with(|builder| {
+    ///     builder
+    ///         .default_header("authorization", "Bearer my-token")
+    ///         .timeout(Duration::from_secs(5))
+    /// })?;
+    ///
+    /// let response = api_client.get("https://example.org/api")?;
     /// # Ok::<(), isahc::Error>(())
     /// ```
-    #[inline]
-    pub fn get<U>(&self, uri: U) -> Result<Response<Body>, Error>
+    pub fn try_clone_with<F>(&self, f: F) -> Result<Self, Error>
     where
-        http::Uri: TryFrom<U>,
-        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+        F: FnOnce(SiblingClientBuilder) -> SiblingClientBuilder,
     {
-        match http::Request::get(uri).body(()) {
-            Ok(request) => self.send(request),
-            Err(e) => Err(Error::from_any(e)),
+        let builder = f(SiblingClientBuilder {
+            defaults: http::Extensions::new(),
+            default_headers: HeaderMap::new(),
+            error: None,
+        });
+
+        if let Some(err) = builder.error {
+            return Err(err);
         }
+
+        Ok(Self {
+            inner: self.inner.clone(),
+            overrides: Some(Arc::new(Overrides {
+                defaults: builder.defaults,
+                default_headers: builder.default_headers,
+                parent: self.overrides.clone(),
+            })),
+        })
     }
 
-    /// Send a GET request to the given URI asynchronously.
-    ///
-    /// To customize the request further, see [`HttpClient::send_async`]. To
-    /// execute the request synchronously, see [`HttpClient::get`].
-    pub fn get_async<U>(&self, uri: U) -> ResponseFuture<'_>
-    where
-        http::Uri: TryFrom<U>,
-        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
-    {
-        match http::Request::get(uri).body(()) {
-            Ok(request) => self.send_async(request),
-            Err(e) => ResponseFuture::error(Error::from_any(e)),
+    /// Wait for the response to a request that has already been submitted. If
+    /// no response is received within the given delay, also submit the given
+    /// backup request and use whichever response is received first.
+    async fn hedge(
+        &self,
+        primary: impl Future<Output = Result<Response<ResponseBodyReader>, Error>>,
+        delay: Duration,
+        mut backup: Request<AsyncBody>,
+        partition: Option<PoolPartition>,
+    ) -> Result<Response<ResponseBodyReader>, Error> {
+        let mut primary = Box::pin(primary);
+
+        let early = future::or(async { Some(primary.as_mut().await) }, async {
+            Delay::new(delay).await;
+            None
+        })
+        .await;
+
+        if let Some(result) = early {
+            return result;
         }
-    }
 
-    /// Send a HEAD request to the given URI.
-    ///
-    /// To customize the request further, see [`HttpClient::send`]. To execute
-    /// the request asynchronously, see [`HttpClient::head_async`].
-    ///
-    /// # Examples
-    ///
-    /// ```no_run
-    /// # use isahc::prelude::*;
-    /// # let client = HttpClient::new()?;
-    /// let response = client.head("https://example.org")?;
-    /// println!("Page size: {:?}", response.headers()["content-length"]);
-    /// # Ok::<(), isahc::Error>(())
-    /// ```
-    #[inline]
-    pub fn head<U>(&self, uri: U) -> Result<Response<Body>, Error>
+        tracing::debug!(
+            "no response received after {:?}, sending backup request",
+            delay
+        );
+
+        // Keep waiting for the original request while the backup request
+        // connects using a custom dialer, if any.
+        let backup = async move {
+            self.dial(&mut backup).await?;
+
+            let (easy, backup) = self.create_easy_handle(backup)?;
+            self.submit_request(easy, partition)?;
+
+            backup.await
+        };
+
+        let mut backup = Box::pin(backup);
+        let mut primary = Some(primary);
+        let mut backup = Some(backup.as_mut());
+
+        // Take the first successful response. If one of the requests fails,
+        // give the other one a chance to succeed before reporting the error.
+        // The losing request is aborted when its future is dropped.
+        future::poll_fn(|cx| {
+            if let Some(future) = primary.as_mut() {
+                if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                    primary = None;
+
+                    if result.is_ok() || backup.is_none() {
+                        return Poll::Ready(result);
+                    }
+
+                    tracing::debug!("original request failed, waiting for backup request");
+                }
+            }
+
+            if let Some(future) = backup.as_mut() {
+                if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                    backup = None;
+
+                    if result.is_ok() || primary.is_none() {
+                        tracing::debug!("backup request completed first");
+                        return Poll::Ready(result);
+                    }
+
+                    tracing::debug!("backup request failed, waiting for original request");
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Submit a request to the agent, using the given connection pool
+    /// partition.
+    fn submit_request(
+        &self,
+        mut easy: curl::easy::Easy2<RequestHandler>,
+        partition: Option<PoolPartition>,
+    ) -> Result<(), Error> {
+        if let Some(PoolPartition(key)) = partition {
+            easy.get_mut().set_pool_partition(key);
+        }
+
+        self.inner.agent.submit_request(easy)
+    }
+
+    fn defaults(&self) -> Defaults<'_> {
+        Defaults {
+            overrides: self.overrides.as_deref(),
+            shared: &self.inner.defaults,
+        }
+    }
+
+    /// Get the default headers of this client, taking any overrides into
+    /// account.
+    fn default_headers(&self) -> HeaderMap<HeaderValue> {
+        let overrides = self
+            .overrides
+            .iter()
+            .flat_map(|overrides| overrides.chain())
+            .map(|overrides| &overrides.default_headers);
+        let mut headers = HeaderMap::new();
+
+        // Headers of more specific overrides replace those of less specific
+        // ones entirely.
+        for defaults in overrides.chain(Some(&self.inner.default_headers)) {
+            for name in defaults.keys() {
+                if !headers.contains_key(name) {
+                    for value in defaults.get_all(name) {
+                        headers.append(name, value.clone());
+                    }
+                }
+            }
+        }
+
+        headers
+    }
+
+    /// Get the configuration of this client as a curl config file, also known
+    /// as a `.curlrc` file.
+    ///
+    /// This makes it possible to reproduce the behavior of the client with the
+    /// curl command line tool, for example when debugging a problem by hand.
+    /// The default headers of the client are included, along with options for
+    /// timeouts, redirects, HTTP versions, the user agent, authentication,
+    /// proxies and TLS. Options without an equivalent in curl are left out.
+    ///
+    /// Header values and credentials are included as they are, so make sure
+    /// to remove any secrets before sharing the file with others.
+    ///
+    /// A config file can be imported again using
+    /// [`HttpClientBuilder::curlrc`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::RedirectPolicy, prelude::*, HttpClient};
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .no_user_agent()
+    ///     .timeout(Duration::from_secs(30))
+    ///     .redirect_policy(RedirectPolicy::Follow)
+    ///     .default_header("X-Team", "platform")
+    ///     .build()?;
+    ///
+    /// assert_eq!(
+    ///     client.to_curlrc(),
+    ///     "user-agent = \"\"\ncompressed\nmax-time = \"30\"\nlocation\nheader = \"x-team: platform\"\n",
+    /// );
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn to_curlrc(&self) -> String {
+        crate::config::curlrc::export(&self.defaults(), &self.default_headers())
+    }
+
+    /// Get a snapshot of statistics about the background agent thread that
+    /// drives the requests sent by this client.
+    ///
+    /// The same statistics are also emitted as trace-level events with the
+    /// `isahc::agent::stats` target on every iteration of the agent's event
+    /// loop, if a tracing subscriber or logger is interested in them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::HttpClient;
+    ///
+    /// let client = HttpClient::new()?;
+    /// let stats = client.agent_stats();
+    ///
+    /// println!("agent loop latency: {:?}", stats.loop_latency());
+    /// println!("active transfers: {}", stats.active_transfers());
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn agent_stats(&self) -> AgentStats {
+        self.inner.agent.stats()
+    }
+
+    /// Get a snapshot of the pool of connections kept open by this client,
+    /// such as how many idle connections are ready to be reused for each
+    /// host.
+    ///
+    /// The snapshot is taken by the background agent thread in between
+    /// transfers, so this waits for the agent to respond.
+    ///
+    /// Connections pooled in a [`ShareHandle`] that shares connections are
+    /// not counted, as they do not belong to any single client.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::new()?;
+    /// client.get("https://example.org")?;
+    ///
+    /// let stats = client.pool_stats()?;
+    ///
+    /// println!("open connections: {}", stats.open_connections());
+    ///
+    /// for (host, idle) in stats.idle_connections_by_host() {
+    ///     println!("{} idle connections to {}", idle, host);
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn pool_stats(&self) -> Result<PoolStats, Error> {
+        block_on(self.pool_stats_async())
+    }
+
+    /// Get a snapshot of the pool of connections kept open by this client
+    /// asynchronously.
+    ///
+    /// This method is the asynchronous version of [`HttpClient::pool_stats`].
+    pub async fn pool_stats_async(&self) -> Result<PoolStats, Error> {
+        self.inner.agent.pool_stats().await
+    }
+
+    /// Get the configured cookie jar for this HTTP client, if any.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the [`cookies`](index.html#cookies)
+    /// feature is enabled.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_jar(&self) -> Option<&crate::cookies::CookieJar> {
+        self.inner.cookie_jar.as_ref()
+    }
+
+    /// Check that the configured proxy can be used to connect to the given
+    /// target, without sending a request.
+    ///
+    /// A connection to the target is established through the proxy configured
+    /// with [`Configurable::proxy`], including any handshake and
+    /// authentication with the proxy, and is then closed again. For HTTP
+    /// proxies, a tunnel is always requested with `CONNECT`, so the proxy must
+    /// allow tunneling to the target. If the target is an `https` URI, the TLS
+    /// handshake with the target is performed as well.
+    ///
+    /// This can be used to validate proxy settings, for example while they are
+    /// being entered by a user. The kind of the returned error indicates what
+    /// went wrong:
+    ///
+    /// - [`ErrorKind::InvalidRequest`]: No proxy is configured.
+    /// - [`ErrorKind::NameResolution`]: The host name of the proxy could not be
+    ///   resolved.
+    /// - [`ErrorKind::ConnectionFailed`]: The proxy could not be reached, or it
+    ///   refused to connect to the target.
+    /// - [`ErrorKind::InvalidCredentials`]: The proxy rejected the configured
+    ///   proxy credentials.
+    /// - [`ErrorKind::Timeout`]: The check took longer than the configured
+    ///   timeout.
+    ///
+    /// Proxies given by environment variables are not checked. Note that this
+    /// method blocks the current thread until the check is complete.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{error::ErrorKind, prelude::*};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .proxy(Some("http://proxy.example.org:3128".parse()?))
+    ///     .build()?;
+    ///
+    /// match client.check_proxy("https://example.org") {
+    ///     Ok(()) => println!("proxy works"),
+    ///     Err(e) if e.kind() == &ErrorKind::InvalidCredentials => {
+    ///         println!("wrong proxy password")
+    ///     }
+    ///     Err(e) => println!("proxy check failed: {}", e),
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_proxy<U>(&self, target: U) -> Result<(), Error>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        let target = http::Uri::try_from(target).map_err(|e| Error::from_any(e.into()))?;
+
+        crate::proxy_check::check(&target, self.defaults())
+    }
+
+    /// Resolve the given host name ahead of time, so that the first request to
+    /// it does not have to wait for the name to be resolved.
+    ///
+    /// The resolved addresses are used by new connections to the host for as
+    /// long as entries are kept in the DNS cache, as configured by
+    /// [`HttpClientBuilder::dns_cache`]. If DNS caching is disabled, the
+    /// addresses are not kept. Overrides given by
+    /// [`Configurable::dns_resolve`] take precedence over them.
+    ///
+    /// This method blocks the current thread until the name is resolved. To
+    /// resolve it asynchronously, see [`HttpClient::prefetch_dns_async`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::new()?;
+    ///
+    /// client.prefetch_dns("example.org")?;
+    ///
+    /// // Connecting to the host no longer involves resolving its name.
+    /// let response = client.get("https://example.org")?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn prefetch_dns(&self, host: &str) -> Result<(), Error> {
+        block_on(self.prefetch_dns_async(host))
+    }
+
+    /// Resolve the given host name ahead of time asynchronously, so that the
+    /// first request to it does not have to wait for the name to be resolved.
+    ///
+    /// This is the async version of [`HttpClient::prefetch_dns`]. The name is
+    /// resolved on a background thread, so the returned future can be left
+    /// to run alongside other work during startup.
+    pub async fn prefetch_dns_async(&self, host: &str) -> Result<(), Error> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        // Addresses do not need to be resolved.
+        if host.parse::<std::net::IpAddr>().is_ok() {
+            return Ok(());
+        }
+
+        let addrs = crate::config::dns::resolve(host.to_owned()).await?;
+        let cache = self.defaults().get::<DnsCache>().cloned();
+
+        tracing::debug!(host, ?addrs, "prefetched host addresses");
+        self.inner
+            .prefetched_hosts
+            .insert(host, addrs, &cache.unwrap_or_default());
+
+        Ok(())
+    }
+
+    /// Open a WebSocket connection to the given URI.
+    ///
+    /// The URI should use the `ws` or `wss` scheme. The connection is upgraded
+    /// from HTTP/1.1 using the proxy, TLS, timeout and default header settings
+    /// of this client. Interceptors, redirects and cookies do not apply to
+    /// the upgrade request.
+    ///
+    /// This method blocks the current thread until the connection is
+    /// established. To connect asynchronously, see
+    /// [`HttpClient::websocket_async`].
+    ///
+    /// This method is only available when the
+    /// [`websocket`](index.html#websocket) feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, websocket::Message};
+    ///
+    /// let client = HttpClient::new()?;
+    /// let socket = client.websocket("wss://echo.example.org")?;
+    ///
+    /// socket.send("hello")?;
+    ///
+    /// if let Some(Message::Text(text)) = socket.recv()? {
+    ///     println!("received {}", text);
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn websocket<U>(&self, uri: U) -> Result<crate::websocket::WebSocket, Error>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        block_on(self.websocket_async(uri))
+    }
+
+    /// Open a WebSocket connection to the given URI asynchronously.
+    ///
+    /// This is the async version of [`HttpClient::websocket`].
+    ///
+    /// This method is only available when the
+    /// [`websocket`](index.html#websocket) feature is enabled.
+    #[cfg(feature = "websocket")]
+    pub async fn websocket_async<U>(&self, uri: U) -> Result<crate::websocket::WebSocket, Error>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        let uri = http::Uri::try_from(uri).map_err(|e| Error::from_any(e.into()))?;
+        let mut headers = self.default_headers();
+
+        if !headers.contains_key(http::header::USER_AGENT) {
+            let user_agent = match self.defaults().get::<UserAgent>() {
+                Some(UserAgent(user_agent)) => user_agent.clone(),
+                None => Some(DEFAULT_USER_AGENT.clone()),
+            };
+
+            if let Some(user_agent) = user_agent {
+                headers.insert(http::header::USER_AGENT, user_agent);
+            }
+        }
+
+        crate::websocket::connect(&uri, self.defaults(), &headers).await
+    }
+
+    /// Open a raw tunnel to the given authority through the configured HTTP
+    /// proxy.
+    ///
+    /// The authority is a host and port, such as `example.org:22`. A
+    /// `CONNECT` request is sent to the proxy using the proxy, proxy
+    /// authentication and timeout settings of this client, and once the proxy
+    /// has established the tunnel, a [`Tunnel`](crate::Tunnel) is returned
+    /// that bytes can be written to and read from, so that protocols other
+    /// than HTTP can be used through the proxy. If no proxy is configured for
+    /// the authority, a plain TCP connection is made to it instead.
+    ///
+    /// This method blocks the current thread until the tunnel is established.
+    /// To connect asynchronously, see [`HttpClient::connect_tunnel_async`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::Configurable, HttpClient};
+    /// use std::io::{Read, Write};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .proxy(Some("http://proxy.example.org:3128".parse()?))
+    ///     .build()?;
+    ///
+    /// let mut tunnel = client.connect_tunnel("example.org:7")?;
+    /// tunnel.write_all(b"hello")?;
+    ///
+    /// let mut echo = [0; 5];
+    /// tunnel.read_exact(&mut echo)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn connect_tunnel<A>(&self, authority: A) -> Result<crate::Tunnel, Error>
+    where
+        http::uri::Authority: TryFrom<A>,
+        <http::uri::Authority as TryFrom<A>>::Error: Into<http::Error>,
+    {
+        block_on(self.connect_tunnel_async(authority))
+    }
+
+    /// Open a raw tunnel to the given authority through the configured HTTP
+    /// proxy asynchronously.
+    ///
+    /// This is the async version of [`HttpClient::connect_tunnel`].
+    pub async fn connect_tunnel_async<A>(&self, authority: A) -> Result<crate::Tunnel, Error>
+    where
+        http::uri::Authority: TryFrom<A>,
+        <http::uri::Authority as TryFrom<A>>::Error: Into<http::Error>,
+    {
+        let authority = http::uri::Authority::try_from(authority)
+            .map_err(|e| Error::from_any(e.into()))?;
+
+        crate::tunnel::connect(&authority, self.defaults()).await
+    }
+
+    /// Send a GET request to the given URI.
+    ///
+    /// To customize the request further, see [`HttpClient::send`]. To execute
+    /// the request asynchronously, see [`HttpClient::get_async`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// # let client = HttpClient::new()?;
+    /// let mut response = client.get("https://example.org")?;
+    /// println!("{}", response.text()?);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[inline]
+    pub fn get<U>(&self, uri: U) -> Result<Response<Body>, Error>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        match http::Request::get(uri).body(()) {
+            Ok(request) => self.send(request),
+            Err(e) => Err(Error::from_any(e)),
+        }
+    }
+
+    /// Send a GET request to the given URI asynchronously.
+    ///
+    /// To customize the request further, see [`HttpClient::send_async`]. To
+    /// execute the request synchronously, see [`HttpClient::get`].
+    pub fn get_async<U>(&self, uri: U) -> ResponseFuture<'static>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        match http::Request::get(uri).body(()) {
+            Ok(request) => self.send_async(request),
+            Err(e) => ResponseFuture::error(Error::from_any(e)),
+        }
+    }
+
+    /// Send a HEAD request to the given URI.
+    ///
+    /// To customize the request further, see [`HttpClient::send`]. To execute
+    /// the request asynchronously, see [`HttpClient::head_async`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use isahc::prelude::*;
+    /// # let client = HttpClient::new()?;
+    /// let response = client.head("https://example.org")?;
+    /// println!("Page size: {:?}", response.headers()["content-length"]);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[inline]
+    pub fn head<U>(&self, uri: U) -> Result<Response<Body>, Error>
     where
         http::Uri: TryFrom<U>,
         <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -720,7 +1645,7 @@ impl HttpClient {
     ///
     /// To customize the request further, see [`HttpClient::send_async`]. To
     /// execute the request synchronously, see [`HttpClient::head`].
-    pub fn head_async<U>(&self, uri: U) -> ResponseFuture<'_>
+    pub fn head_async<U>(&self, uri: U) -> ResponseFuture<'static>
     where
         http::Uri: TryFrom<U>,
         <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -766,7 +1691,7 @@ impl HttpClient {
     ///
     /// To customize the request further, see [`HttpClient::send_async`]. To
     /// execute the request synchronously, see [`HttpClient::post`].
-    pub fn post_async<U, B>(&self, uri: U, body: B) -> ResponseFuture<'_>
+    pub fn post_async<U, B>(&self, uri: U, body: B) -> ResponseFuture<'static>
     where
         http::Uri: TryFrom<U>,
         <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -814,7 +1739,7 @@ impl HttpClient {
     ///
     /// To customize the request further, see [`HttpClient::send_async`]. To
     /// execute the request synchronously, see [`HttpClient::put`].
-    pub fn put_async<U, B>(&self, uri: U, body: B) -> ResponseFuture<'_>
+    pub fn put_async<U, B>(&self, uri: U, body: B) -> ResponseFuture<'static>
     where
         http::Uri: TryFrom<U>,
         <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -846,7 +1771,7 @@ impl HttpClient {
     ///
     /// To customize the request further, see [`HttpClient::send_async`]. To
     /// execute the request synchronously, see [`HttpClient::delete`].
-    pub fn delete_async<U>(&self, uri: U) -> ResponseFuture<'_>
+    pub fn delete_async<U>(&self, uri: U) -> ResponseFuture<'static>
     where
         http::Uri: TryFrom<U>,
         <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -909,42 +1834,104 @@ impl HttpClient {
     where
         B: Into<Body>,
     {
-        let span = tracing::debug_span!(
-            "send",
-            method = ?request.method(),
-            uri = ?request.uri(),
-        );
-
-        let mut writer_maybe = None;
+        self.send_blocking(request, None)
+    }
 
-        let request = request.map(|body| {
-            let (async_body, writer) = body.into().into_async();
+    /// Send an HTTP request and return the HTTP response, giving up if the
+    /// response is not received within the given timeout.
+    ///
+    /// This is the same as [`HttpClient::send`], except that the calling
+    /// thread is only blocked for as long as the timeout allows, which is
+    /// useful for call sites in synchronous code that need a bounded wait.
+    /// Only sending the request and receiving the response headers are
+    /// subject to the timeout; the response body can be read at any pace
+    /// afterwards. To limit the time taken by the entire request and response
+    /// instead, see [`Configurable::timeout`].
+    ///
+    /// If the timeout passes first, the request is canceled according to its
+    /// [cancel policy](Configurable::cancel_policy) and a
+    /// [`Timeout`](crate::error::ErrorKind::Timeout) error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::new()?;
+    ///
+    /// let response = client.send_with_timeout(
+    ///     Request::get("https://example.org").body(())?,
+    ///     Duration::from_secs(5),
+    /// )?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn send_with_timeout<B>(
+        &self,
+        request: Request<B>,
+        timeout: Duration,
+    ) -> Result<Response<Body>, Error>
+    where
+        B: Into<Body>,
+    {
+        self.send_blocking(request, Some(timeout))
+    }
+
+    /// Send a request while blocking the current thread, waiting for the
+    /// response for up to the given timeout if any.
+    fn send_blocking<B>(
+        &self,
+        request: Request<B>,
+        timeout: Option<Duration>,
+    ) -> Result<Response<Body>, Error>
+    where
+        B: Into<Body>,
+    {
+        let span = tracing::debug_span!(
+            "send",
+            method = ?request.method(),
+            uri = tracing::field::Empty,
+            route = tracing::field::Empty,
+        );
+        record_target(&span, &request);
+
+        let mut writer_maybe = None;
+
+        let mut request = request.map(Into::<Body>::into);
+        let content_type = request.body().content_type();
+        set_content_type(&mut request, content_type);
+
+        let request = request.map(|body| {
+            let (async_body, writer) = body.into_async();
             writer_maybe = writer;
             async_body
         });
 
-        let response = block_on(
-            async move {
-                // Instead of simply blocking the current thread until the response
-                // is received, we can use the current thread to read from the
-                // request body synchronously while concurrently waiting for the
-                // response.
-                if let Some(mut writer) = writer_maybe {
-                    // Note that the `send_async` future is given first; this
-                    // ensures that it is polled first and thus the request is
-                    // initiated before we attempt to write the request body.
-                    let (response, _) = try_zip(self.send_async_inner(request), async move {
-                        writer.write().await.map_err(Error::from)
-                    })
-                    .await?;
-
-                    Ok(response)
-                } else {
-                    self.send_async_inner(request).await
-                }
+        let future = async move {
+            // Instead of simply blocking the current thread until the response
+            // is received, we can use the current thread to read from the
+            // request body synchronously while concurrently waiting for the
+            // response.
+            if let Some(mut writer) = writer_maybe {
+                // Note that the `send_async` future is given first; this
+                // ensures that it is polled first and thus the request is
+                // initiated before we attempt to write the request body.
+                let (response, _) = try_zip(self.send_async_inner(request), async move {
+                    writer.write().await.map_err(Error::from)
+                })
+                .await?;
+
+                Ok(response)
+            } else {
+                self.send_async_inner(request).await
             }
-            .instrument(span),
-        )?;
+        }
+        .instrument(span);
+
+        let response = match timeout {
+            Some(timeout) => block_on_timeout(future, timeout).ok_or_else(|| timed_out(timeout))?,
+            None => block_on(future),
+        }?;
 
         Ok(response.map(|body| body.into_sync()))
     }
@@ -973,20 +1960,212 @@ impl HttpClient {
     /// # Ok(()) }
     /// ```
     #[inline]
-    pub fn send_async<B>(&self, request: Request<B>) -> ResponseFuture<'_>
+    pub fn send_async<B>(&self, request: Request<B>) -> ResponseFuture<'static>
     where
         B: Into<AsyncBody>,
     {
         let span = tracing::debug_span!(
             "send_async",
             method = ?request.method(),
-            uri = ?request.uri(),
+            uri = tracing::field::Empty,
+            route = tracing::field::Empty,
+        );
+        record_target(&span, &request);
+
+        // Hold on to a handle to the client so that the future does not borrow
+        // from it.
+        let client = self.clone();
+        let request = request.map(Into::into);
+
+        ResponseFuture::new(async move { client.send_async_inner(request).await }.instrument(span))
+    }
+
+    /// Send an HTTP request in the background without waiting for the
+    /// response.
+    ///
+    /// This returns right away, without waiting for the request to be sent.
+    /// Interceptors, custom dialers and the like are run on a background
+    /// thread, after which the transfer continues in the background as with
+    /// [`CancelPolicy::Detach`], and the response body is discarded, even if
+    /// the returned [`DetachedHandle`] is dropped right away. The handle can be
+    /// used to check on the outcome of the request if desired.
+    ///
+    /// Since nobody waits for the response, it is not passed back through
+    /// interceptors, so redirects are not followed and failed requests are not
+    /// retried. Requests are not hedged either.
+    ///
+    /// This is useful for requests whose response does not matter to the
+    /// caller, such as telemetry beacons or webhooks.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::new()?;
+    ///
+    /// client.send_detached(
+    ///     Request::post("https://example.org/events")
+    ///         .header("Content-Type", "application/json")
+    ///         .body(r#"{"event": "started"}"#)?,
+    /// )?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn send_detached<B>(&self, request: Request<B>) -> Result<DetachedHandle, Error>
+    where
+        B: Into<AsyncBody>,
+    {
+        let mut request = request.map(Into::into);
+        let outcome = Arc::new(DetachedOutcome::default());
+        let (started_tx, started_rx) = flume::bounded(1);
+
+        // The handler of the transfer records the outcome once it completes.
+        request.extensions_mut().insert(CancelPolicy::Detach);
+        request.extensions_mut().insert(DetachedRecorder {
+            outcome: outcome.clone(),
+            status: None,
+            started: Some(started_tx),
+        });
+
+        let span = tracing::debug_span!(
+            "send_detached",
+            method = ?request.method(),
+            uri = tracing::field::Empty,
+            route = tracing::field::Empty,
         );
+        record_target(&span, &request);
+
+        // Drive the request on a background thread only until the agent has
+        // started the transfer, after which dropping the future leaves the
+        // transfer running.
+        let client = self.clone();
+
+        DETACHED_POOL.execute({
+            let outcome = outcome.clone();
+
+            move || {
+                let started = block_on(
+                    future::or(
+                        async {
+                            // If the transfer never starts, the request future
+                            // completes instead.
+                            if started_rx.recv_async().await.is_err() {
+                                future::pending::<()>().await;
+                            }
+
+                            None
+                        },
+                        async { Some(client.send_async_inner(request).await) },
+                    )
+                    .instrument(span),
+                );
 
-        ResponseFuture::new(
-            self.send_async_inner(request.map(Into::into))
-                .instrument(span),
-        )
+                // The request failed before the transfer started, or the
+                // response was produced without one.
+                if let Some(result) = started {
+                    outcome.record(result.map(|response| response.status()));
+                }
+            }
+        })?;
+
+        Ok(DetachedHandle {
+            outcome,
+        })
+    }
+
+    /// Send a request for a paginated resource, returning a stream of
+    /// responses for each page.
+    ///
+    /// After each response is received, the next page is requested if the
+    /// response has a `Link` header pointing to it, as determined by
+    /// [`ResponseExt::next_page_uri`](crate::ResponseExt::next_page_uri). Each
+    /// subsequent page is requested using `GET` with the same headers and
+    /// configuration as the original request, except that sensitive headers
+    /// are removed according to the
+    /// [`RedirectHeaderPolicy`](crate::config::RedirectHeaderPolicy) of the
+    /// request if a page links to a different origin. The stream ends after
+    /// the last page, after the first error, or once [`Paginate::max_pages`]
+    /// pages have been received.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// use futures_lite::stream::StreamExt;
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::new()?;
+    /// let request = Request::get("https://api.github.com/repos/sagebind/isahc/issues")
+    ///     .body(())?;
+    ///
+    /// let mut pages = client.paginate(request).max_pages(5);
+    ///
+    /// while let Some(response) = pages.next().await {
+    ///     println!("{}", response?.text().await?);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn paginate<B>(&self, request: Request<B>) -> Paginate<'_>
+    where
+        B: Into<AsyncBody>,
+    {
+        let template = request
+            .to_builder()
+            .method(http::Method::GET)
+            .body(())
+            .map_err(Error::from);
+
+        Paginate {
+            client: self,
+            template,
+            in_flight: Some(self.send_async(request)),
+            visited: Vec::new(),
+            pages: 0,
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+
+    /// Download the resource at the given URI into a file, unless the file is
+    /// already up to date.
+    ///
+    /// The `ETag` and `Last-Modified` headers of the response are stored in a
+    /// sidecar file next to the downloaded file, named after it with a
+    /// `.validators` suffix. On later calls they are sent back to the server
+    /// with a conditional `GET` request, and if the server responds with `304
+    /// Not Modified` the file is left as it is and
+    /// [`DownloadOutcome::Unchanged`] is returned. Otherwise the response body
+    /// replaces the file and [`DownloadOutcome::Updated`] is returned. This is
+    /// useful for keeping configuration or assets in sync with a server
+    /// without downloading them in full every time.
+    ///
+    /// The body is first written to a file with a `.part` suffix, which then
+    /// replaces the file once complete, so a failed download does not leave a
+    /// partially written file behind. A response with an error status code
+    /// returns an error of kind
+    /// [`BadStatus`](crate::error::ErrorKind::BadStatus).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, DownloadOutcome};
+    ///
+    /// let client = HttpClient::new()?;
+    ///
+    /// match client.download_if_changed("https://example.org/config.json", "config.json")? {
+    ///     DownloadOutcome::Unchanged => println!("config is up to date"),
+    ///     DownloadOutcome::Updated(len) => println!("downloaded {} bytes of new config", len),
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn download_if_changed<U, P>(&self, uri: U, path: P) -> Result<DownloadOutcome, Error>
+    where
+        http::Uri: TryFrom<U>,
+        <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+        P: AsRef<Path>,
+    {
+        let uri = http::Uri::try_from(uri).map_err(|e| Error::from_any(e.into()))?;
+
+        download::download_if_changed(self, uri, path.as_ref())
     }
 
     /// Actually send the request. All the public methods go through here.
@@ -994,19 +2173,126 @@ impl HttpClient {
         &self,
         mut request: Request<AsyncBody>,
     ) -> Result<Response<AsyncBody>, Error> {
+        let content_type = request.body().content_type();
+        set_content_type(&mut request, content_type);
+
         // Set redirect policy if not specified.
         if request.extensions().get::<RedirectPolicy>().is_none() {
-            if let Some(policy) = self.inner.defaults.get::<RedirectPolicy>().cloned() {
+            if let Some(policy) = self.defaults().get::<RedirectPolicy>().cloned() {
+                request.extensions_mut().insert(policy);
+            }
+        }
+
+        if request.extensions().get::<RedirectHeaderPolicy>().is_none() {
+            if let Some(policy) = self.defaults().get::<RedirectHeaderPolicy>().cloned() {
+                request.extensions_mut().insert(policy);
+            }
+        }
+
+        if request.extensions().get::<HeaderMergePolicy>().is_none() {
+            if let Some(policy) = self.defaults().get::<HeaderMergePolicy>().cloned() {
                 request.extensions_mut().insert(policy);
             }
         }
 
+        // Carry the error body limit over to the response, where it is used.
+        if request.extensions().get::<ErrorBodyLimit>().is_none() {
+            if let Some(limit) = self.defaults().get::<ErrorBodyLimit>().cloned() {
+                request.extensions_mut().insert(limit);
+            }
+        }
+
+        if let Some(overrides) = self.overrides.as_ref() {
+            for overrides in overrides.chain() {
+                let headers = &overrides.default_headers;
+
+                for name in headers.keys() {
+                    if !request.headers().contains_key(name) {
+                        for value in headers.get_all(name) {
+                            request.headers_mut().append(name, value.clone());
+                        }
+                    }
+                }
+
+                // The cookie jar is read by an interceptor from the request,
+                // so pass it along that way.
+                #[cfg(feature = "cookies")]
+                {
+                    if request.extensions().get::<crate::cookies::CookieJar>().is_none() {
+                        if let Some(jar) = overrides.defaults.get::<crate::cookies::CookieJar>() {
+                            request.extensions_mut().insert(jar.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        let retry = request
+            .extensions()
+            .get::<retry::Retry>()
+            .or_else(|| self.defaults().get())
+            .cloned();
+
         let ctx = interceptor::Context {
             invoker: Arc::new(self),
             interceptors: &self.inner.interceptors,
+            last_attempt: Default::default(),
         };
 
-        ctx.send(request).await
+        match retry {
+            Some(retry) => {
+                let timeout = request
+                    .extensions()
+                    .get::<Timeout>()
+                    .or_else(|| self.defaults().get())
+                    .map(|timeout| timeout.0);
+
+                send_with_retries(&ctx, request, retry, timeout).await
+            }
+            None => ctx.send(request).await,
+        }
+    }
+
+    /// Open the connection of a request ahead of time if it uses a custom
+    /// dialer, so that the dialer is not called on the agent thread.
+    async fn dial(&self, request: &mut Request<AsyncBody>) -> Result<(), Error> {
+        let dial = match request
+            .extensions()
+            .get::<Dialer>()
+            .or_else(|| self.defaults().get())
+            .and_then(|dialer| dialer.dial(request.uri()))
+        {
+            Some(dial) => dial,
+            None => return Ok(()),
+        };
+
+        let timeout = request
+            .extensions()
+            .get::<Timeout>()
+            .or_else(|| self.defaults().get())
+            .map(|timeout| timeout.0);
+
+        let dialer = match timeout {
+            Some(timeout) => {
+                future::or(dial, async move {
+                    Delay::new(timeout).await;
+
+                    Err(Error::new(
+                        ErrorKind::Timeout,
+                        io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("custom dialer did not connect within {:?}", timeout),
+                        ),
+                    ))
+                })
+                .await?
+            }
+            None => dial.await?,
+        };
+
+        request.extensions_mut().insert(dialer);
+
+        Ok(())
     }
 
     fn create_easy_handle(
@@ -1024,15 +2310,82 @@ impl HttpClient {
         let body = std::mem::take(request.body_mut());
         let has_body = !body.is_empty();
         let body_length = body.len();
+
+        // The digest of a body in memory can be sent in a header right away,
+        // otherwise it can only be sent in a trailer after the body.
+        let mut digest_trailer = None;
+        let send_digest = request
+            .extensions()
+            .get::<SendDigest>()
+            .or_else(|| self.defaults().get())
+            .map(|v| v.0)
+            .filter(|header| has_body && !request.headers().contains_key(header.name()));
+
+        if let Some(header) = send_digest {
+            match body.peek_buffered(usize::MAX) {
+                Some(data) => {
+                    let value = digest::header_value(&Sha256::digest(data));
+
+                    if let Ok(value) = HeaderValue::from_str(&value) {
+                        request.headers_mut().insert(header.name(), value);
+                    }
+                }
+                None => digest_trailer = Some(header),
+            }
+        }
+
+        #[cfg(feature = "spill-to-disk")]
+        let (handler, future) = RequestHandler::new(
+            body,
+            request
+                .extensions()
+                .get::<SpillToDisk>()
+                .or_else(|| self.defaults().get()),
+        );
+
+        #[cfg(not(feature = "spill-to-disk"))]
         let (handler, future) = RequestHandler::new(body);
 
-        let mut easy = curl::easy::Easy2::new(handler);
+        // Reuse the curl handle of a completed request if there is one.
+        let (mut easy, recv_buffer) = match self.inner.agent_builder.handle_pool().take() {
+            Some(mut easy) => {
+                let recv_buffer = easy.get_mut().take_recv_buffer();
+                *easy.get_mut() = handler;
+                (easy, recv_buffer)
+            }
+            None => (curl::easy::Easy2::new(handler), None),
+        };
 
         // Set whether curl should generate verbose debug data for us to log.
         easy.verbose(easy.get_ref().is_debug_enabled())?;
 
         easy.signal(false)?;
 
+        // Connection reports are gathered from curl's debug messages, so make
+        // sure they are generated if a report is requested.
+        if request
+            .extensions()
+            .get::<EnableConnectionReport>()
+            .or_else(|| self.defaults().get())
+            .map(|v| v.0)
+            .unwrap_or(false)
+        {
+            easy.get_mut().enable_connection_report();
+            easy.verbose(true)?;
+        }
+
+        // The server may have processed a request that failed on a stale
+        // connection, so only requests that are safe to repeat are retried.
+        let retry_stale_connection = request
+            .extensions()
+            .get::<RetryStaleConnection>()
+            .or_else(|| self.defaults().get())
+            .map(|v| v.0)
+            .unwrap_or(false)
+            && request.method().is_idempotent();
+
+        easy.get_mut().set_retry_stale_connection(retry_stale_connection);
+
         // Macro to apply all config values given in the request or in defaults.
         macro_rules! set_opts {
             ($easy:expr, $extensions:expr, $defaults:expr, [$($option:ty,)*]) => {{
@@ -1047,17 +2400,20 @@ impl HttpClient {
         set_opts!(
             &mut easy,
             request.extensions(),
-            self.inner.defaults,
+            self.defaults(),
             [
                 Timeout,
                 ConnectTimeout,
+                IdleTimeout,
                 TcpKeepAlive,
                 TcpNoDelay,
+                HaproxyProtocol,
                 NetworkInterface,
                 Dialer,
                 AutomaticDecompression,
                 Authentication,
                 Credentials,
+                Http2Keepalive,
                 MaxAgeConn,
                 MaxUploadSpeed,
                 MaxDownloadSpeed,
@@ -1066,18 +2422,262 @@ impl HttpClient {
                 proxy::Blacklist,
                 proxy::Proxy<Authentication>,
                 proxy::Proxy<Credentials>,
+                proxy::Proxy<http::HeaderMap>,
                 DnsCache,
                 dns::ResolveMap,
                 ssl::Ciphers,
+                ssl::Engine,
                 ClientCertificate,
                 CaCertificate,
                 SslOption,
                 CloseConnection,
+                ReusePolicy,
                 EnableMetrics,
                 IpVersion,
+                ResponseBufferSize,
             ]
         );
 
+        if request
+            .extensions()
+            .get::<EnableMetrics>()
+            .or_else(|| self.defaults().get())
+            .map(|v| v.0)
+            .unwrap_or(false)
+        {
+            let route = request.extensions().get::<Route>().map(|r| r.0.clone());
+
+            easy.get_mut().enable_metrics(route);
+        }
+
+        #[cfg(feature = "tls-psk")]
+        {
+            if let Some(key) = request
+                .extensions()
+                .get::<PreSharedKey>()
+                .or_else(|| self.defaults().get())
+            {
+                if !crate::psk::is_supported() {
+                    return Err(Error::new(
+                        ErrorKind::TlsEngine,
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            "pre-shared keys require libcurl to use OpenSSL",
+                        ),
+                    ));
+                }
+
+                // The server does not present a certificate when using a PSK
+                // cipher suite, and the handshake can only succeed if the
+                // server knows the key.
+                easy.ssl_cipher_list("PSK")?;
+                easy.ssl_min_max_version(
+                    curl::easy::SslVersion::Default,
+                    curl::easy::SslVersion::Tlsv12,
+                )?;
+                easy.ssl_verify_peer(false)?;
+                easy.ssl_verify_host(false)?;
+
+                // Resuming a cached session skips the PSK callback, so a
+                // session established with another key could be resumed.
+                easy.ssl_sessionid_cache(false)?;
+
+                easy.get_mut().set_pre_shared_key(key.clone());
+            }
+        }
+
+        let decompress = request
+            .extensions()
+            .get::<AutomaticDecompression>()
+            .or_else(|| self.defaults().get())
+            .map(|v| v.0)
+            .unwrap_or(false);
+
+        // Curl fails on encodings it does not support, so if it lacks any of
+        // the ones we support, responses are decoded by the handler instead.
+        #[cfg(any(feature = "brotli", feature = "zstd"))]
+        {
+            if decompress && crate::decompress::is_needed() {
+                easy.accept_encoding(crate::decompress::ACCEPT_ENCODING)?;
+                easy.http_content_decoding(false)?;
+                easy.get_mut().set_decode_content();
+            }
+        }
+
+        // Decompression limits only apply if the response is decoded
+        // automatically.
+        if decompress {
+            easy.get_mut().set_decompression_limits(
+                request
+                    .extensions()
+                    .get::<MaxDecompressedSize>()
+                    .or_else(|| self.defaults().get())
+                    .map(|v| v.0),
+                request
+                    .extensions()
+                    .get::<MaxDecompressionRatio>()
+                    .or_else(|| self.defaults().get())
+                    .map(|v| v.0),
+            );
+        }
+
+        // A lingering request is aborted from within the progress callback
+        // once its grace period is over, so make sure it gets called.
+        if let Some(policy) = request
+            .extensions()
+            .get::<CancelPolicy>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_cancel_policy(*policy);
+
+            if let CancelPolicy::Linger(_) = policy {
+                easy.progress(true)?;
+            }
+        }
+
+        // The handler records the outcome of a detached request.
+        if let Some(recorder) = request.extensions_mut().remove::<DetachedRecorder>() {
+            easy.get_mut().set_detached_recorder(recorder);
+        }
+
+        if let Some(policy) = request
+            .extensions()
+            .get::<HeaderMergePolicy>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_header_merge_policy(*policy);
+        }
+
+        if let Some(on_headers) = request
+            .extensions()
+            .get::<headers::OnHeaders>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_on_headers(on_headers.clone());
+        }
+
+        // Progress is reported from the progress callback, so make sure it
+        // gets called.
+        if let Some(on_progress) = request
+            .extensions()
+            .get::<progress::OnProgress>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_on_progress(on_progress.clone());
+            easy.progress(true)?;
+        }
+
+        if self.inner.agent_builder.is_push_enabled() {
+            easy.get_mut().enable_push();
+        }
+
+        // Connected sockets and custom dialers open sockets in place of curl.
+        // The handler keeps the opener alive for as long as curl may call it.
+        if let Some(mut opener) = request
+            .extensions()
+            .get::<Dialer>()
+            .or_else(|| self.defaults().get())
+            .and_then(|dialer| dialer.socket_opener(request.uri()))
+        {
+            opener.set_callbacks(&mut easy)?;
+            easy.get_mut().set_socket_opener(opener);
+        }
+
+        // The handler keeps the HSTS exchange alive for as long as curl may
+        // call it, and it updates the store once the handle is cleaned up.
+        if let Some(store) = request
+            .extensions()
+            .get::<HstsStore>()
+            .or_else(|| self.defaults().get())
+        {
+            let mut hsts = HstsTransfer::new(store.clone(), request.uri().host());
+            hsts.set_callbacks(&mut easy)?;
+            easy.get_mut().set_hsts(hsts);
+        }
+
+        // Unless a fixed size is configured, the receive buffer is sized to
+        // fit how fast response bodies arrived during the previous transfers
+        // of the handle. Curl only supports setting the size before a
+        // transfer starts, so it is set here rather than as the body arrives.
+        if request
+            .extensions()
+            .get::<ResponseBufferSize>()
+            .or_else(|| self.defaults().get())
+            .is_none()
+        {
+            let buffer = recv_buffer.unwrap_or_default();
+            easy.buffer_size(buffer.size())?;
+            easy.get_mut().set_recv_buffer(buffer);
+        }
+
+        // Responses to HEAD requests have no body to check.
+        if request.method() != http::Method::HEAD
+            && request
+                .extensions()
+                .get::<VerifyDigest>()
+                .or_else(|| self.defaults().get())
+                .map(|v| v.0)
+                .unwrap_or(false)
+        {
+            easy.get_mut().set_verify_digest(decompress);
+        }
+
+        // The handler keeps the share alive for as long as curl may use it.
+        if let Some(share) = self.defaults().get::<ShareHandle>() {
+            share.set_opt(&mut easy)?;
+            easy.get_mut().set_share_handle(share.clone());
+        }
+
+        // The agent enforces the timeout as well, in case curl never reports
+        // it. A zero timeout means no timeout to curl.
+        if let Some(timeout) = request
+            .extensions()
+            .get::<Timeout>()
+            .or_else(|| self.defaults().get())
+            .filter(|timeout| timeout.0 > Duration::from_secs(0))
+        {
+            easy.get_mut().set_timeout(timeout.0);
+        }
+
+        // The agent sends the pings while performing connection upkeep.
+        if let Some(keepalive) = request
+            .extensions()
+            .get::<Http2Keepalive>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_upkeep_interval(keepalive.interval);
+        }
+
+        // The agent finds out that curl started the transfer from the progress
+        // callback, so make sure it gets called.
+        if let Some(timeout) = request
+            .extensions()
+            .get::<QueueTimeout>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_queue_timeout(timeout.0);
+            easy.progress(true)?;
+        }
+
+        // The read timeout is checked by the handler from within the progress
+        // callback, so make sure it gets called.
+        if let Some(timeout) = request
+            .extensions()
+            .get::<ReadTimeout>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_read_timeout(timeout.0);
+            easy.progress(true)?;
+        }
+
+        if let Some(timeout) = request
+            .extensions()
+            .get::<HeadersTimeout>()
+            .or_else(|| self.defaults().get())
+        {
+            easy.get_mut().set_headers_timeout(timeout.0);
+        }
+
         // Set the HTTP method to use. Curl ties in behavior with the request
         // method, so we need to configure this carefully.
         #[allow(indirect_structural_match)]
@@ -1105,13 +2705,96 @@ impl HttpClient {
             }
         }
 
-        easy.url(&uri_to_string(request.uri()))?;
+        // Connections are redirected by giving curl a list of mappings. To
+        // present a different name during the TLS handshake, hand curl a URI
+        // with that name and map it to the original host. A custom dialer
+        // already decides where to connect to by itself.
+        let connect_to = request
+            .extensions()
+            .get::<dial::ConnectTo>()
+            .or_else(|| self.defaults().get());
+        let mut routes = Vec::new();
 
-        // If the request has a body, then we either need to tell curl how large
-        // the body is if we know it, or tell curl to use chunked encoding. If
-        // we do neither, curl will simply not send the body without warning.
-        if has_body {
-            // Use length given in Content-Length header, or the size defined by
+        let uri = match request
+            .extensions()
+            .get::<ssl::TlsServerName>()
+            .or_else(|| self.defaults().get())
+        {
+            Some(server_name) => {
+                let (uri, route) = server_name.route(request.uri()).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidRequest,
+                        io::Error::new(io::ErrorKind::Other, "invalid TLS server name"),
+                    )
+                })?;
+
+                routes.push(match connect_to {
+                    Some(connect_to) => route.followed_by(connect_to),
+                    None => route,
+                });
+
+                uri
+            }
+            None => request.uri().clone(),
+        };
+
+        routes.extend(connect_to.cloned());
+
+        if !routes.is_empty()
+            && request
+                .extensions()
+                .get::<Dialer>()
+                .or_else(|| self.defaults().get())
+                .map(Dialer::is_default)
+                .unwrap_or(true)
+        {
+            let mut list = curl::easy::List::new();
+
+            for route in routes {
+                list.append(&route.to_string())?;
+            }
+
+            easy.connect_to(list)?;
+        }
+
+        if ssl::is_ip_host(&uri)
+            && request
+                .extensions()
+                .get::<SslOption>()
+                .or_else(|| self.defaults().get())
+                .map(|options| options.contains(SslOption::DANGER_ACCEPT_INVALID_IP_HOSTS))
+                .unwrap_or(false)
+        {
+            easy.ssl_verify_host(false)?;
+        }
+
+        // Use addresses resolved ahead of time, if any.
+        if let (Some(host), Some(addrs)) = (uri.host(), self.inner.prefetched_hosts.get(&uri)) {
+            let port = uri.port_u16().unwrap_or_else(|| {
+                if uri.scheme_str() == Some("http") {
+                    80
+                } else {
+                    443
+                }
+            });
+
+            request
+                .extensions()
+                .get::<ResolveMap>()
+                .or_else(|| self.defaults().get())
+                .cloned()
+                .unwrap_or_default()
+                .add_prefetched(host, port, &addrs)
+                .set_opt(&mut easy)?;
+        }
+
+        easy.url(&uri_to_string(&uri))?;
+
+        // If the request has a body, then we either need to tell curl how large
+        // the body is if we know it, or tell curl to use chunked encoding. If
+        // we do neither, curl will simply not send the body without warning.
+        if has_body {
+            // Use length given in Content-Length header, or the size defined by
             // the body itself.
             let body_length = request
                 .headers()
@@ -1126,6 +2809,10 @@ impl HttpClient {
                 } else {
                     easy.in_filesize(len)?;
                 }
+
+                if digest_trailer.is_some() {
+                    tracing::debug!("not sending a digest of a streaming body of known length");
+                }
             } else {
                 // Set the Transfer-Encoding header to instruct curl to use
                 // chunked encoding. Replaces any existing values that may be
@@ -1134,6 +2821,13 @@ impl HttpClient {
                     "Transfer-Encoding",
                     http::header::HeaderValue::from_static("chunked"),
                 );
+
+                if let Some(header) = digest_trailer {
+                    request
+                        .headers_mut()
+                        .insert(http::header::TRAILER, header.name().into());
+                    digest::send_trailer(&mut easy, header)?;
+                }
             }
         }
 
@@ -1143,69 +2837,453 @@ impl HttpClient {
         let title_case = request
             .extensions()
             .get::<TitleCaseHeaders>()
-            .or_else(|| self.inner.defaults.get())
+            .or_else(|| self.defaults().get())
             .map(|v| v.0)
             .unwrap_or(false);
 
-        for (name, value) in request.headers().iter() {
+        let header_order = request
+            .extensions()
+            .get::<HeaderOrder>()
+            .or_else(|| self.defaults().get());
+
+        let mut request_headers = request.headers().iter().collect::<Vec<_>>();
+
+        if let Some(order) = header_order {
+            order.sort(&mut request_headers);
+        }
+
+        for (name, value) in request_headers {
             headers.append(&header_to_curl_string(name, value, title_case))?;
         }
 
+        // When an explicit header order is given, only send the headers we
+        // were asked to send. An empty header value tells curl to remove its
+        // own default.
+        if header_order.is_some() && !request.headers().contains_key(http::header::ACCEPT) {
+            headers.append("Accept:")?;
+        }
+
+        // An explicit list of encodings replaces the header curl generates
+        // from the encodings it supports, while curl still decodes the
+        // response.
+        if !request.headers().contains_key(http::header::ACCEPT_ENCODING) {
+            if let Some(AcceptEncoding(encodings)) = request
+                .extensions()
+                .get()
+                .or_else(|| self.defaults().get())
+            {
+                if encodings.is_empty() {
+                    headers.append("Accept-Encoding:")?;
+                } else {
+                    let value = HeaderValue::from_str(encodings)
+                        .map_err(|e| Error::new(ErrorKind::InvalidRequest, e))?;
+
+                    headers.append(&header_to_curl_string(
+                        &http::header::ACCEPT_ENCODING,
+                        &value,
+                        title_case,
+                    ))?;
+                }
+            }
+        }
+
         easy.http_headers(headers)?;
 
+        // Remember which alternative service the request was sent to, if
+        // any, so that it can be reported on the response.
+        if let Some(alt_used) = request
+            .headers()
+            .get("alt-used")
+            .and_then(|value| value.to_str().ok())
+        {
+            let alt_used = AltUsed(alt_used.to_owned());
+            request.extensions_mut().insert(alt_used);
+        }
+
+        // Remember the requested range so that the response can be checked
+        // against it.
+        if let Some(range) = request
+            .headers()
+            .get(http::header::RANGE)
+            .and_then(|value| value.to_str().ok())
+        {
+            let range = RequestedRange(range.to_owned());
+            request.extensions_mut().insert(range);
+        }
+
+        // Hand over the request extensions so that they can be preserved on
+        // the response.
+        easy.get_mut()
+            .set_request_extensions(std::mem::take(request.extensions_mut()));
+
         Ok((easy, future))
     }
 }
 
+/// Send a request, sending it again for as long as the given retry policy asks
+/// to after each attempt.
+async fn send_with_retries(
+    ctx: &interceptor::Context<'_>,
+    mut request: Request<AsyncBody>,
+    retry: retry::Retry,
+    timeout: Option<Duration>,
+) -> Result<Response<AsyncBody>, Error> {
+    let method = request.method().clone();
+    let deadline = retry::Deadline::start(&*retry.0);
+
+    loop {
+        // Sending the request again requires a copy of it, which can only be
+        // made if its body is in memory.
+        let copy = request
+            .body()
+            .try_clone()
+            .and_then(|body| request.to_builder().body(body).ok());
+
+        let result = match deadline {
+            Some(deadline) => send_before(ctx, request, deadline, timeout).await,
+            None => ctx.send(request).await,
+        };
+
+        let attempt = RetryAttempt::new(ctx.attempts(), &method, result.as_ref());
+        let delay = match retry.0.retry_delay(&attempt) {
+            Some(delay) => delay,
+            None => return result,
+        };
+
+        if let Some(deadline) = deadline {
+            if !deadline.allows_delay(delay) {
+                tracing::debug!("not retrying request because its total timeout would be exceeded");
+                return result;
+            }
+        }
+
+        request = match copy {
+            Some(copy) => copy,
+            None => {
+                tracing::debug!("not retrying request because its body cannot be sent again");
+                return result;
+            }
+        };
+
+        match &result {
+            Ok(response) => tracing::debug!(
+                "retrying request after {:?} because of status {}",
+                delay,
+                response.status()
+            ),
+            Err(e) => tracing::debug!("retrying request after {:?} because of error: {}", delay, e),
+        }
+
+        // Abort the transfer of the response body, if any, before waiting.
+        drop(result);
+
+        Delay::new(delay).await;
+    }
+}
+
+/// Make a single attempt at sending a request that must complete before the
+/// given deadline.
+///
+/// The attempt is given a timeout of whatever is left before the deadline,
+/// so that the transfer of the response body and any hedged backup request
+/// are limited by it too. Anything else the attempt involves, such as
+/// following redirects, is cut off once the deadline passes.
+async fn send_before(
+    ctx: &interceptor::Context<'_>,
+    mut request: Request<AsyncBody>,
+    deadline: retry::Deadline,
+    timeout: Option<Duration>,
+) -> Result<Response<AsyncBody>, Error> {
+    let timeout = deadline
+        .attempt_timeout(timeout)
+        .ok_or_else(total_timed_out)?;
+
+    request.extensions_mut().insert(Timeout(timeout));
+
+    future::or(ctx.send(request), async move {
+        Delay::new(timeout).await;
+
+        Err(total_timed_out())
+    })
+    .await
+}
+
+/// Create the error returned when the total timeout of a retry policy is
+/// reached.
+fn total_timed_out() -> Error {
+    Error::new(
+        ErrorKind::Timeout,
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            "request did not complete within the total timeout of its retry policy",
+        ),
+    )
+}
+
+/// Set the `Content-Type` header of a request to the one implied by its body,
+/// unless the request already has one.
+fn set_content_type<T>(request: &mut Request<T>, content_type: Option<String>) {
+    let headers = request.headers_mut();
+
+    if let Some(content_type) = content_type {
+        if !headers.contains_key(http::header::CONTENT_TYPE) {
+            if let Ok(value) = HeaderValue::from_str(&content_type) {
+                headers.insert(http::header::CONTENT_TYPE, value);
+            }
+        }
+    }
+}
+
+/// Record the target of a request in the given span. If the request has a
+/// route template, it is recorded instead of the URI, which may contain
+/// identifiers that would make every span unique.
+fn record_target<T>(span: &tracing::Span, request: &Request<T>) {
+    match request.extensions().get::<Route>() {
+        Some(route) => span.record("route", route.0.as_str()),
+        None => span.record("uri", tracing::field::debug(request.uri())),
+    };
+}
+
 impl crate::interceptor::Invoke for &HttpClient {
     fn invoke<'a>(
         &'a self,
         mut request: Request<AsyncBody>,
     ) -> crate::interceptor::InterceptorFuture<'a, Error> {
         Box::pin(async move {
+            if let Some(tags) = request.extensions().get::<Tags>() {
+                tracing::debug!(tags = ?tags.0, "sending tagged request");
+            }
+
+            // Add headers from the selected profile, if any.
+            if let Some(profile) = request
+                .extensions()
+                .get::<Profile>()
+                .or_else(|| self.defaults().get())
+                .cloned()
+            {
+                profile.apply_headers(request.headers_mut());
+            }
+
             // Set default user agent if not specified.
-            request
-                .headers_mut()
-                .entry(http::header::USER_AGENT)
-                .or_insert(USER_AGENT.parse().unwrap());
+            if !request.headers().contains_key(http::header::USER_AGENT) {
+                let user_agent = match request
+                    .extensions()
+                    .get::<UserAgent>()
+                    .or_else(|| self.defaults().get())
+                {
+                    Some(UserAgent(user_agent)) => user_agent.clone(),
+                    None => Some(DEFAULT_USER_AGENT.clone()),
+                };
+
+                if let Some(user_agent) = user_agent {
+                    request
+                        .headers_mut()
+                        .insert(http::header::USER_AGENT, user_agent);
+                }
+            }
+
+            // Validate the request as late as possible, so that any changes
+            // made by interceptors are checked as well.
+            let strict_validation = request
+                .extensions()
+                .get::<StrictValidation>()
+                .or_else(|| self.defaults().get())
+                .map(|v| v.0)
+                .unwrap_or(false);
+
+            validate_request(&mut request, strict_validation)?;
+
+            if let Some(MaxHeaderListSize(limit)) = request
+                .extensions()
+                .get()
+                .or_else(|| self.defaults().get())
+            {
+                let size = header_list_size(&request);
+
+                if size > *limit {
+                    return Err(Error::headers_too_large(size, *limit));
+                }
+            }
+
+            if let Some(MaxUriLength(limit)) = request
+                .extensions()
+                .get()
+                .or_else(|| self.defaults().get())
+            {
+                let length = request.uri().to_string().len();
+
+                if length > *limit {
+                    return Err(Error::uri_too_long(length, *limit));
+                }
+            }
+
+            // HTTP/1.0 has no chunked encoding, so a body of unknown length
+            // is read into memory to send it with a known length instead, up
+            // to a limit so that a huge or endless body cannot exhaust memory.
+            if !request.body().is_empty()
+                && request.body().len().is_none()
+                && request
+                    .extensions()
+                    .get::<VersionNegotiation>()
+                    .or_else(|| self.defaults().get())
+                    .map(VersionNegotiation::is_http10)
+                    .unwrap_or(false)
+            {
+                let mut buffer = Vec::new();
+                request
+                    .body_mut()
+                    .take(HTTP10_BODY_LIMIT + 1)
+                    .read_to_end(&mut buffer)
+                    .await?;
+
+                if buffer.len() as u64 > HTTP10_BODY_LIMIT {
+                    return Err(http10_body_too_large());
+                }
+
+                *request.body_mut() = AsyncBody::from(buffer);
+            }
 
             // Check if automatic decompression is enabled; we'll need to know
             // this later after the response is sent.
             let is_automatic_decompression = request
                 .extensions()
                 .get()
-                .or_else(|| self.inner.defaults.get())
+                .or_else(|| self.defaults().get())
                 .map(|AutomaticDecompression(enabled)| *enabled)
                 .unwrap_or(false);
 
+            let mut partition = request
+                .extensions()
+                .get::<PoolPartition>()
+                .or_else(|| self.defaults().get())
+                .cloned();
+
+            // Curl puts DNS overrides into the DNS cache of the agent, where
+            // they would apply to every later request. Requests with overrides
+            // of their own use a partition for each set of overrides, which
+            // has a DNS cache of its own.
+            if let Some(map) = request
+                .extensions()
+                .get::<ResolveMap>()
+                .or_else(|| self.defaults().get())
+                .filter(|&map| Some(map) != self.inner.defaults.get())
+            {
+                let key = partition.map(|PoolPartition(key)| key).unwrap_or_default();
+
+                partition = Some(PoolPartition(format!(
+                    "{}\0resolve {}",
+                    key,
+                    map.entries().join(" ")
+                )));
+            }
+
+            // Prepare to fall back to HTTP/1.1 if sending the request over
+            // HTTP/2 fails, unless a specific version was asked for. Hosts
+            // that failed recently are contacted using HTTP/1.1 right away.
+            let mut fallback = request
+                .extensions()
+                .get::<VersionFallback>()
+                .or_else(|| self.defaults().get())
+                .filter(|fallback| fallback.is_enabled())
+                .filter(|_| {
+                    request
+                        .extensions()
+                        .get::<VersionNegotiation>()
+                        .or_else(|| self.defaults().get())
+                        .filter(|negotiation| negotiation.is_strict())
+                        .is_none()
+                })
+                .cloned();
+
+            if fallback.is_some() && self.inner.downgraded_hosts.contains(request.uri()) {
+                request
+                    .extensions_mut()
+                    .insert(VersionNegotiation::http11());
+                fallback = None;
+            }
+
+            let uri = request.uri().clone();
+            let retry = fallback
+                .as_ref()
+                .and_then(|fallback| fallback.retry_request(&request));
+
+            // Prepare a backup copy of the request if it should be hedged.
+            // Whichever copy loses must be aborted, regardless of what should
+            // happen to the request when its response future is dropped, so
+            // detached requests are never hedged.
+            let backup = request
+                .extensions()
+                .get::<HedgePolicy>()
+                .or_else(|| self.defaults().get())
+                .filter(|_| request.extensions().get::<DetachedRecorder>().is_none())
+                .and_then(|policy| {
+                    let timeout = request
+                        .extensions()
+                        .get::<Timeout>()
+                        .or_else(|| self.defaults().get())
+                        .map(|timeout| timeout.0);
+
+                    policy
+                        .backup_request(&request, timeout)
+                        .map(|backup| (policy.delay(), backup))
+                })
+                .map(|(delay, mut backup)| {
+                    backup.extensions_mut().insert(CancelPolicy::Abort);
+                    request.extensions_mut().insert(CancelPolicy::Abort);
+                    (delay, backup)
+                });
+
+            // Connect using a custom dialer first, if any.
+            self.dial(&mut request).await?;
+
             // Create and configure a curl easy handle to fulfil the request.
             let (easy, future) = self.create_easy_handle(request)?;
 
             // Send the request to the agent to be executed.
-            self.inner.agent.submit_request(easy)?;
+            self.submit_request(easy, partition.clone())?;
 
             // Await for the response headers.
-            let response = future.await?;
-
-            // If a Content-Length header is present, include that information in
-            // the body as well.
-            let body_len = response.content_length().filter(|_| {
-                // If automatic decompression is enabled, and will likely be
-                // selected, then the value of Content-Length does not indicate
-                // the uncompressed body length and merely the compressed data
-                // length. If it looks like we are in this scenario then we
-                // ignore the Content-Length, since it can only cause confusion
-                // when included with the body.
-                if is_automatic_decompression {
-                    if let Some(value) = response.headers().get(http::header::CONTENT_ENCODING) {
-                        if value != "identity" {
-                            return false;
+            let result = match backup {
+                Some((delay, backup)) => self.hedge(future, delay, backup, partition.clone()).await,
+                None => future.await,
+            };
+
+            let mut response = match (result, fallback) {
+                (Err(e), Some(fallback)) if e.is_http2_error() => {
+                    self.inner
+                        .downgraded_hosts
+                        .insert(&uri, fallback.cool_down_period());
+
+                    match retry {
+                        Some(mut retry) => {
+                            tracing::debug!("retrying request using HTTP/1.1 after error: {}", e);
+
+                            self.dial(&mut retry).await?;
+
+                            let (easy, future) = self.create_easy_handle(retry)?;
+                            self.submit_request(easy, partition)?;
+                            future.await?
                         }
+                        None => return Err(e),
                     }
                 }
+                (result, _) => result?,
+            };
 
-                true
-            });
+            // If automatic decompression is enabled and the server sent an
+            // encoded body, then curl will have decoded it, undoing each of
+            // the encodings in reverse order if there are several.
+            let is_decoded =
+                is_automatic_decompression && !response.headers().content_encodings().is_empty();
+
+            response.extensions_mut().insert(ContentDecoded(is_decoded));
+
+            // If a Content-Length header is present, include that information in
+            // the body as well. If the body was decoded, then the value of
+            // Content-Length does not indicate the uncompressed body length and
+            // merely the compressed data length, so we ignore it since it can
+            // only cause confusion when included with the body.
+            let body_len = response.content_length().filter(|_| !is_decoded);
 
             // Convert the reader into an opaque Body.
             Ok(response.map(|reader| {
@@ -1226,6 +3304,62 @@ impl crate::interceptor::Invoke for &HttpClient {
     }
 }
 
+/// A builder for the configuration overrides of a sibling client created with
+/// [`HttpClient::try_clone_with`].
+///
+/// Any of the per-request options of [`Configurable`] can be overridden, as
+/// well as the default headers to include in each request.
+pub struct SiblingClientBuilder {
+    defaults: http::Extensions,
+    default_headers: HeaderMap<HeaderValue>,
+    error: Option<Error>,
+}
+
+impl SiblingClientBuilder {
+    /// Add a default header to be passed with every request, in addition to
+    /// the default headers inherited from the original client.
+    ///
+    /// If a default header with the same name is inherited, it is replaced by
+    /// the value given here.
+    pub fn default_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        match HeaderName::try_from(key) {
+            Ok(key) => match HeaderValue::try_from(value) {
+                Ok(value) => {
+                    self.default_headers.append(key, value);
+                }
+                Err(e) => {
+                    self.error = Some(Error::new(ErrorKind::InvalidRequest, e.into()));
+                }
+            },
+            Err(e) => {
+                self.error = Some(Error::new(ErrorKind::InvalidRequest, e.into()));
+            }
+        }
+        self
+    }
+}
+
+impl Configurable for SiblingClientBuilder {}
+
+impl ConfigurableBase for SiblingClientBuilder {
+    fn configure(mut self, option: impl Send + Sync + 'static) -> Self {
+        self.defaults.insert(option);
+        self
+    }
+}
+
+impl fmt::Debug for SiblingClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SiblingClientBuilder").finish()
+    }
+}
+
 impl fmt::Debug for HttpClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpClient").finish()
@@ -1233,13 +3367,20 @@ impl fmt::Debug for HttpClient {
 }
 
 /// A future for a request being executed.
+///
+/// The future is [`Send`], and futures returned by [`HttpClient::send_async`]
+/// and the other asynchronous request methods do not borrow from the client,
+/// so they can be stored as a `ResponseFuture<'static>` without boxing them
+/// again.
 #[must_use = "futures do nothing unless you `.await` or poll them"]
-pub struct ResponseFuture<'c>(Pin<Box<dyn Future<Output = <Self as Future>::Output> + 'c + Send>>);
+pub struct ResponseFuture<'c>(
+    Pin<Box<dyn Future<Output = Result<Response<AsyncBody>, Error>> + 'c + Send>>,
+);
 
 impl<'c> ResponseFuture<'c> {
     fn new<F>(future: F) -> Self
     where
-        F: Future<Output = <Self as Future>::Output> + Send + 'c,
+        F: Future<Output = Result<Response<AsyncBody>, Error>> + Send + 'c,
     {
         ResponseFuture(Box::pin(future))
     }
@@ -1249,6 +3390,52 @@ impl<'c> ResponseFuture<'c> {
     }
 }
 
+impl ResponseFuture<'_> {
+    /// Block the current thread until the response is received, or until the
+    /// given timeout has passed.
+    ///
+    /// This allows waiting for an asynchronous request from synchronous code
+    /// without an executor. As with [`HttpClient::send_with_timeout`], only
+    /// receiving the response headers is subject to the timeout, and the
+    /// returned response body is read synchronously.
+    ///
+    /// If the timeout passes first, the request is canceled according to its
+    /// [cancel policy](crate::config::Configurable::cancel_policy) and a
+    /// [`Timeout`](crate::error::ErrorKind::Timeout) error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let future = isahc::get_async("https://example.org");
+    ///
+    /// // Do something else in the meantime...
+    ///
+    /// let mut response = future.wait_timeout(Duration::from_secs(5))?;
+    /// println!("{}", response.text()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn wait_timeout(self, timeout: Duration) -> Result<Response<Body>, Error> {
+        let response = block_on_timeout(self, timeout).ok_or_else(|| timed_out(timeout))??;
+
+        Ok(response.map(|body| body.into_sync()))
+    }
+}
+
+/// Create the error returned when no response was received within the given
+/// timeout while blocking on a request.
+fn timed_out(timeout: Duration) -> Error {
+    Error::new(
+        ErrorKind::Timeout,
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no response received within {:?}", timeout),
+        ),
+    )
+}
+
 impl Future for ResponseFuture<'_> {
     type Output = Result<Response<AsyncBody>, Error>;
 
@@ -1263,6 +3450,260 @@ impl<'c> fmt::Debug for ResponseFuture<'c> {
     }
 }
 
+/// A handle to a request sent with [`HttpClient::send_detached`].
+///
+/// Dropping the handle does not affect the request, which always runs to
+/// completion in the background.
+pub struct DetachedHandle {
+    outcome: Arc<DetachedOutcome>,
+}
+
+impl DetachedHandle {
+    /// Check whether the request has completed, either successfully or with
+    /// an error.
+    pub fn is_finished(&self) -> bool {
+        self.outcome.result.lock().unwrap().is_some()
+    }
+
+    /// Get the outcome of the request if it has completed, which is the
+    /// status code of the response if one was received in full.
+    pub fn outcome(&self) -> Option<Result<StatusCode, Error>> {
+        self.outcome.result.lock().unwrap().clone()
+    }
+
+    /// Block the current thread until the request has completed, and return
+    /// its outcome.
+    pub fn wait(self) -> Result<StatusCode, Error> {
+        let mut result = self.outcome.result.lock().unwrap();
+
+        loop {
+            match result.take() {
+                Some(result) => return result,
+                None => result = self.outcome.completed.wait(result).unwrap(),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DetachedHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetachedHandle")
+            .field("outcome", &self.outcome())
+            .finish()
+    }
+}
+
+/// The outcome of a detached request, shared between its handle and the
+/// handler of its transfer.
+#[derive(Default)]
+pub(crate) struct DetachedOutcome {
+    result: Mutex<Option<Result<StatusCode, Error>>>,
+    completed: Condvar,
+}
+
+impl DetachedOutcome {
+    fn record(&self, result: Result<StatusCode, Error>) {
+        let mut slot = self.result.lock().unwrap();
+
+        if slot.is_none() {
+            if let Err(e) = &result {
+                tracing::debug!("detached request failed: {}", e);
+            }
+
+            *slot = Some(result);
+            self.completed.notify_all();
+        }
+    }
+}
+
+/// Records the outcome of a detached request from the handler of its transfer.
+///
+/// Travels with the request as an extension until the handler takes it. If it
+/// is dropped after the transfer started but before an outcome is recorded,
+/// the request is considered to have failed.
+pub(crate) struct DetachedRecorder {
+    outcome: Arc<DetachedOutcome>,
+    status: Option<StatusCode>,
+    started: Option<flume::Sender<()>>,
+}
+
+impl DetachedRecorder {
+    /// Note that the transfer has started, so that the caller can stop
+    /// waiting for it.
+    pub(crate) fn set_started(&mut self) {
+        if let Some(started) = self.started.take() {
+            let _ = started.send(());
+        }
+    }
+
+    /// Note the status code of the response received.
+    pub(crate) fn set_status(&mut self, status: StatusCode) {
+        self.status = Some(status);
+    }
+
+    /// Record the final result of the transfer.
+    pub(crate) fn record(&mut self, result: &Result<(), Error>) {
+        let result = match (result, self.status) {
+            (Ok(()), Some(status)) => Ok(status),
+            (Err(e), _) => Err(e.clone()),
+            (Ok(()), None) => Err(detached_incomplete()),
+        };
+
+        self.outcome.record(result);
+    }
+}
+
+impl Drop for DetachedRecorder {
+    fn drop(&mut self) {
+        if self.started.is_none() {
+            self.outcome.record(Err(detached_incomplete()));
+        }
+    }
+}
+
+fn detached_incomplete() -> Error {
+    Error::new(
+        ErrorKind::Unknown,
+        io::Error::new(io::ErrorKind::Other, "detached request did not complete"),
+    )
+}
+
+/// The largest request body of unknown length that is read into memory to
+/// send it over HTTP/1.0.
+const HTTP10_BODY_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Create the error returned when a request body of unknown length is too
+/// large to be read into memory to send it over HTTP/1.0.
+fn http10_body_too_large() -> Error {
+    Error::new(
+        ErrorKind::InvalidRequest,
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "request body of unknown length is larger than {} bytes, which is the most that \
+                 is buffered to send it over HTTP/1.0; set a known body length instead",
+                HTTP10_BODY_LIMIT
+            ),
+        ),
+    )
+}
+
+/// How many pages to fetch by default when paginating, to avoid an endless
+/// loop if a server keeps returning links to more pages.
+const DEFAULT_MAX_PAGES: usize = 1000;
+
+/// A stream of responses for each page of a paginated resource.
+///
+/// Created by [`HttpClient::paginate`].
+#[must_use = "streams do nothing unless polled"]
+pub struct Paginate<'c> {
+    client: &'c HttpClient,
+    template: Result<Request<()>, Error>,
+    in_flight: Option<ResponseFuture<'c>>,
+    visited: Vec<Uri>,
+    pages: usize,
+    max_pages: usize,
+}
+
+impl<'c> Paginate<'c> {
+    /// Set the maximum number of pages to fetch. The stream ends once this
+    /// many responses have been received, even if there are more pages.
+    ///
+    /// The default is 1000 pages.
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    fn next_request(&mut self, response: &Response<AsyncBody>) -> Option<ResponseFuture<'c>> {
+        if self.pages >= self.max_pages {
+            tracing::debug!("maximum of {} pages reached", self.max_pages);
+            return None;
+        }
+
+        let uri = response.next_page_uri()?;
+
+        // Guard against a server linking back to a page we already fetched.
+        if let Some(effective_uri) = response.effective_uri() {
+            self.visited.push(effective_uri.clone());
+        }
+
+        if self.visited.contains(&uri) {
+            tracing::debug!("next page {} was already visited", uri);
+            return None;
+        }
+
+        let client = self.client;
+
+        let request = match self.template.as_mut() {
+            Ok(template) => {
+                // Like when following a redirect, sensitive headers meant for
+                // the current page are not sent to another origin. Once
+                // removed, they stay removed for the rest of the pages.
+                if let Some(effective_uri) = response.effective_uri() {
+                    let changes = OriginChange::between(effective_uri, &uri);
+
+                    if !changes.is_empty() {
+                        let header_policy = template
+                            .extensions()
+                            .get::<RedirectHeaderPolicy>()
+                            .or_else(|| client.defaults().get())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        header_policy.strip(template.headers_mut(), &changes);
+
+                        if header_policy.is_stripped(&http::header::AUTHORIZATION, &changes) {
+                            template.extensions_mut().remove::<Credentials>();
+                        }
+                    }
+                }
+
+                template
+                    .to_builder()
+                    .uri(uri)
+                    .body(AsyncBody::empty())
+                    .map_err(Error::from)
+            }
+            Err(e) => Err(e.clone()),
+        };
+
+        Some(match request {
+            Ok(request) => self.client.send_async(request),
+            Err(e) => ResponseFuture::error(e),
+        })
+    }
+}
+
+impl Stream for Paginate<'_> {
+    type Item = Result<Response<AsyncBody>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let result = match self.in_flight.as_mut() {
+            Some(future) => ready!(Pin::new(future).poll(cx)),
+            None => return Poll::Ready(None),
+        };
+
+        self.pages += 1;
+        self.in_flight = None;
+
+        if let Ok(response) = result.as_ref() {
+            self.in_flight = self.next_request(response);
+        }
+
+        Poll::Ready(Some(result))
+    }
+}
+
+impl fmt::Debug for Paginate<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Paginate")
+            .field("pages", &self.pages)
+            .field("max_pages", &self.max_pages)
+            .finish()
+    }
+}
+
 /// Response body stream. Holds a reference to the agent to ensure it is kept
 /// alive until at least this transfer is complete.
 struct ResponseBody {
@@ -1311,6 +3752,7 @@ mod tests {
 
     static_assertions::assert_impl_all!(HttpClient: Send, Sync);
     static_assertions::assert_impl_all!(HttpClientBuilder: Send);
+    static_assertions::assert_impl_all!(ResponseFuture<'static>: Send);
 
     #[test]
     fn test_default_header() {
@@ -0,0 +1,686 @@
+//! Import and export of client configuration as curl config files, also known
+//! as `.curlrc` files.
+
+use super::{
+    dial::ConnectTo,
+    dns::ResolveMap,
+    internal::ConfigurableBase,
+    CaCertificate,
+    ClientCertificate,
+    Configurable,
+    IpVersion,
+    PrivateKey,
+    RedirectPolicy,
+    SslOption,
+    UserAgent,
+    VersionNegotiation,
+};
+use crate::{
+    auth::{Authentication, Credentials},
+    curl_command::{config_options, CurlOption, Options, SHORT_NAMES},
+    HttpClientBuilder,
+};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use std::{fmt::Write, time::Duration};
+
+/// Options that can be imported, and whether each takes a value.
+const SUPPORTED_OPTIONS: &[(&str, bool)] = &[
+    ("anyauth", false),
+    ("basic", false),
+    ("cacert", true),
+    ("cert", true),
+    ("cert-type", true),
+    ("ciphers", true),
+    ("compressed", false),
+    ("connect-timeout", true),
+    ("connect-to", true),
+    ("digest", false),
+    ("engine", true),
+    ("header", true),
+    ("http1.0", false),
+    ("http1.1", false),
+    ("http2", false),
+    ("http2-prior-knowledge", false),
+    ("http3", false),
+    ("insecure", false),
+    ("ipv4", false),
+    ("ipv6", false),
+    ("key", true),
+    ("key-type", true),
+    ("location", false),
+    ("max-redirs", true),
+    ("max-time", true),
+    ("negotiate", false),
+    ("noproxy", true),
+    ("ntlm", false),
+    ("pass", true),
+    ("proxy", true),
+    ("proxy-anyauth", false),
+    ("proxy-basic", false),
+    ("proxy-digest", false),
+    ("proxy-header", true),
+    ("proxy-negotiate", false),
+    ("proxy-ntlm", false),
+    ("proxy-user", true),
+    ("resolve", true),
+    ("ssl-no-revoke", false),
+    ("user", true),
+    ("user-agent", true),
+];
+
+/// Options that only affect the output of the curl command line tool, and are
+/// ignored when importing.
+const IGNORED_OPTIONS: &[&str] = &["no-progress-meter", "progress-bar", "show-error", "silent"];
+
+/// Write the given client configuration and default headers as a curl config
+/// file.
+pub(crate) fn export(defaults: &impl Options, default_headers: &HeaderMap) -> String {
+    let mut options = config_options(defaults);
+
+    // A client without a configured user agent sends its own default one,
+    // rather than the one of curl.
+    if defaults.option::<UserAgent>().is_none() {
+        options.insert(0, CurlOption {
+            name: "user-agent",
+            value: Some(UserAgent::expand(UserAgent::DEFAULT_TEMPLATE)),
+        });
+    }
+
+    options.extend(
+        default_headers
+            .iter()
+            .map(|(name, value)| CurlOption::header(name, value)),
+    );
+
+    let mut config = String::new();
+
+    for option in options {
+        match option.value {
+            Some(value) => writeln!(config, "{} = \"{}\"", option.name, escape(&value)),
+            None => writeln!(config, "{}", option.name),
+        }
+        .unwrap();
+    }
+
+    config
+}
+
+/// Apply the options in the given curl config file to a client builder.
+///
+/// Returns the builder along with a description of each problem found, such
+/// as options that are not supported.
+pub(crate) fn import(
+    mut builder: HttpClientBuilder,
+    config: &str,
+) -> (HttpClientBuilder, Vec<String>) {
+    let mut problems = Vec::new();
+
+    // Options that depend on each other are collected first, so that the
+    // order they are given in does not matter.
+    let mut follow_redirects = false;
+    let mut max_redirects = None;
+    let mut authentication = None;
+    let mut proxy_authentication = None;
+    let mut proxy_headers = HeaderMap::new();
+    let mut resolve_map = ResolveMap::new();
+    let mut no_proxy = None;
+    let mut ssl_options = None;
+    let mut certificate = None;
+    let mut certificate_type = String::from("PEM");
+    let mut key = None;
+    let mut key_type = String::from("PEM");
+    let mut password = None;
+
+    for (line, name, value) in parse(config, &mut problems) {
+        let value = value.unwrap_or_default();
+        let mut problem = |description: String| {
+            problems.push(format!("curl config line {}: {}", line, description));
+        };
+
+        builder = match name {
+            "anyauth" => {
+                authentication = Some(Authentication::all());
+                builder
+            }
+            "basic" | "digest" | "negotiate" | "ntlm" => {
+                if let Some(scheme) = authentication_scheme(name) {
+                    authentication = Some(authentication.unwrap_or_default() | scheme);
+                } else {
+                    problem(missing_scheme_feature(name));
+                }
+                builder
+            }
+            "proxy-anyauth" => {
+                proxy_authentication = Some(Authentication::all());
+                builder
+            }
+            "proxy-basic" | "proxy-digest" | "proxy-negotiate" | "proxy-ntlm" => {
+                if let Some(scheme) = authentication_scheme(&name["proxy-".len()..]) {
+                    proxy_authentication = Some(proxy_authentication.unwrap_or_default() | scheme);
+                } else {
+                    problem(missing_scheme_feature(name));
+                }
+                builder
+            }
+            "cacert" => builder.ssl_ca_certificate(CaCertificate::file(value)),
+            "cert" => {
+                certificate = Some(value);
+                builder
+            }
+            "cert-type" => {
+                certificate_type = value.to_ascii_uppercase();
+                builder
+            }
+            "ciphers" => builder.ssl_ciphers(value.split(':').map(String::from)),
+            "compressed" => builder.automatic_decompression(true),
+            "connect-timeout" => match seconds(&value) {
+                Some(timeout) => builder.connect_timeout(timeout),
+                None => {
+                    problem(format!("invalid timeout `{}`", value));
+                    builder
+                }
+            },
+            "connect-to" => match ConnectTo::parse(&value) {
+                Some(connect_to) => builder.configure(connect_to),
+                None => {
+                    problem(format!("unsupported connect-to mapping `{}`", value));
+                    builder
+                }
+            },
+            "engine" => builder.ssl_engine(value),
+            "header" => match value.find(&[':', ';'][..]) {
+                Some(i) => {
+                    let (name, rest) = value.split_at(i);
+                    builder.default_header(name.trim(), rest[1..].trim())
+                }
+                None => {
+                    problem(format!("invalid header `{}`", value));
+                    builder
+                }
+            },
+            "http1.0" => builder.version_negotiation(VersionNegotiation::http10()),
+            "http1.1" => builder.version_negotiation(VersionNegotiation::http11()),
+            "http2" => builder.version_negotiation(VersionNegotiation::latest_compatible()),
+            "http2-prior-knowledge" => builder.version_negotiation(VersionNegotiation::http2()),
+            "http3" => builder.version_negotiation(VersionNegotiation::http3()),
+            "insecure" => {
+                ssl_options = Some(
+                    ssl_options.unwrap_or_default()
+                        | SslOption::DANGER_ACCEPT_INVALID_CERTS
+                        | SslOption::DANGER_ACCEPT_INVALID_HOSTS,
+                );
+                builder
+            }
+            "ipv4" => builder.ip_version(IpVersion::V4),
+            "ipv6" => builder.ip_version(IpVersion::V6),
+            "key" => {
+                key = Some(value);
+                builder
+            }
+            "key-type" => {
+                key_type = value.to_ascii_uppercase();
+                builder
+            }
+            "location" => {
+                follow_redirects = true;
+                builder
+            }
+            "max-redirs" => {
+                match value.parse::<u32>() {
+                    Ok(max) => max_redirects = Some(max),
+                    Err(_) => problem(format!("invalid number of redirects `{}`", value)),
+                }
+                builder
+            }
+            "max-time" => match seconds(&value) {
+                Some(timeout) => builder.timeout(timeout),
+                None => {
+                    problem(format!("invalid timeout `{}`", value));
+                    builder
+                }
+            },
+            "noproxy" => {
+                no_proxy = Some(value);
+                builder
+            }
+            "pass" => {
+                password = Some(value);
+                builder
+            }
+            "proxy" => match value.parse() {
+                Ok(proxy) => builder.proxy(Some(proxy)),
+                Err(_) => {
+                    problem(format!("invalid proxy `{}`", value));
+                    builder
+                }
+            },
+            "proxy-header" => {
+                match header(&value) {
+                    Some((name, value)) => {
+                        proxy_headers.append(name, value);
+                    }
+                    None => problem(format!("invalid header `{}`", value)),
+                }
+                builder
+            }
+            "proxy-user" => builder.proxy_credentials(credentials(&value)),
+            "resolve" => {
+                match resolve_map.clone().add_parsed(&value) {
+                    Some(map) => resolve_map = map,
+                    None => problem(format!("unsupported resolve entry `{}`", value)),
+                }
+                builder
+            }
+            "ssl-no-revoke" => {
+                ssl_options =
+                    Some(ssl_options.unwrap_or_default() | SslOption::DANGER_ACCEPT_REVOKED_CERTS);
+                builder
+            }
+            "user" => builder.credentials(credentials(&value)),
+            "user-agent" if value.is_empty() => builder.no_user_agent(),
+            "user-agent" => builder.user_agent(&value),
+            _ => builder,
+        };
+    }
+
+    if follow_redirects {
+        builder = builder.redirect_policy(match max_redirects {
+            Some(max) => RedirectPolicy::Limit(max),
+            None => RedirectPolicy::Follow,
+        });
+    }
+
+    if let Some(authentication) = authentication {
+        builder = builder.authentication(authentication);
+    }
+
+    if let Some(authentication) = proxy_authentication {
+        builder = builder.proxy_authentication(authentication);
+    }
+
+    if !proxy_headers.is_empty() {
+        builder = builder.proxy_headers(proxy_headers);
+    }
+
+    if resolve_map != ResolveMap::new() {
+        builder = builder.dns_resolve(resolve_map);
+    }
+
+    match no_proxy.as_deref() {
+        Some("*") => builder = builder.proxy(None),
+        Some(hosts) => {
+            builder = builder.proxy_blacklist(
+                hosts
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty())
+                    .map(String::from),
+            );
+        }
+        None => {}
+    }
+
+    if let Some(ssl_options) = ssl_options {
+        builder = builder.ssl_options(ssl_options);
+    }
+
+    let key = match (key, key_type.as_str()) {
+        (None, _) => None,
+        (Some(path), "PEM") => Some(PrivateKey::pem_file(path, password.clone())),
+        (Some(path), "DER") => Some(PrivateKey::der_file(path, password.clone())),
+        (Some(id), "ENG") => Some(PrivateKey::engine(id, password.clone())),
+        (Some(uri), "PROV") => Some(PrivateKey::provider(uri, password.clone())),
+        (Some(_), key_type) => {
+            problems.push(format!("unsupported key type `{}`", key_type));
+            None
+        }
+    };
+
+    match (certificate, certificate_type.as_str()) {
+        (None, _) => {
+            if key.is_some() {
+                problems.push(String::from("a key was given without a certificate"));
+            }
+        }
+        (Some(path), "PEM") => {
+            builder = builder.ssl_client_certificate(ClientCertificate::pem_file(path, key));
+        }
+        (Some(path), "DER") => {
+            builder = builder.ssl_client_certificate(ClientCertificate::der_file(path, key));
+        }
+        (Some(path), "P12") => {
+            builder = builder.ssl_client_certificate(ClientCertificate::p12_file(path, password));
+        }
+        (Some(_), certificate_type) => {
+            problems.push(format!(
+                "unsupported certificate type `{}`",
+                certificate_type
+            ));
+        }
+    }
+
+    (builder, problems)
+}
+
+/// Parse the options in a curl config file, returning the line number, long
+/// name and value of each supported option.
+fn parse(config: &str, problems: &mut Vec<String>) -> Vec<(usize, &'static str, Option<String>)> {
+    let mut options = Vec::new();
+
+    for (i, line) in config.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Options given with dashes are separated from their value by
+        // whitespace only, while those without can also use `=` or `:`.
+        let (name, rest) = if let Some(line) = strip_prefix(line, "--") {
+            split_name(line, false)
+        } else if let Some(line) = strip_prefix(line, "-") {
+            let (short, rest) = split_name(line, false);
+
+            match SHORT_NAMES.iter().find(|(name, _)| *name == short) {
+                Some((_, name)) => (*name, rest),
+                None => (short, rest),
+            }
+        } else {
+            split_name(line, true)
+        };
+
+        if IGNORED_OPTIONS.contains(&name) {
+            continue;
+        }
+
+        let (name, takes_value) = match SUPPORTED_OPTIONS.iter().find(|(n, _)| *n == name) {
+            Some(option) => *option,
+            None => {
+                problems.push(format!(
+                    "curl config line {}: unsupported option `{}`",
+                    line_number, name
+                ));
+                continue;
+            }
+        };
+
+        let value = if takes_value {
+            match parse_value(rest) {
+                Some(value) => Some(value),
+                None => {
+                    problems.push(format!(
+                        "curl config line {}: invalid or missing value for `{}`",
+                        line_number, name
+                    ));
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        options.push((line_number, name, value));
+    }
+
+    options
+}
+
+/// Split the name of an option from the rest of the line.
+fn split_name(line: &str, allow_separator: bool) -> (&str, &str) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || allow_separator && (c == '=' || c == ':'))
+        .unwrap_or(line.len());
+
+    let (name, rest) = line.split_at(end);
+    let mut rest = rest.trim_start();
+
+    if allow_separator {
+        if let Some(stripped) = strip_prefix(rest, "=").or_else(|| strip_prefix(rest, ":")) {
+            rest = stripped.trim_start();
+        }
+    }
+
+    (name, rest)
+}
+
+/// Parse the value of an option, which is either quoted or ends at the first
+/// whitespace.
+fn parse_value(rest: &str) -> Option<String> {
+    let quoted = match strip_prefix(rest, "\"") {
+        Some(quoted) => quoted,
+        None => {
+            return rest.split_whitespace().next().map(String::from);
+        }
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => value.push(match chars.next()? {
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                'v' => '\x0b',
+                c => c,
+            }),
+            c => value.push(c),
+        }
+    }
+}
+
+/// Escape a value for use between double quotes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\x0b' => escaped.push_str("\\v"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// `str::strip_prefix` is not available in our minimum supported Rust version.
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+fn authentication_scheme(name: &str) -> Option<Authentication> {
+    match name {
+        "basic" => Some(Authentication::basic()),
+        "digest" => Some(Authentication::digest()),
+        #[cfg(feature = "spnego")]
+        "negotiate" => Some(Authentication::negotiate()),
+        #[cfg(feature = "ntlm")]
+        "ntlm" => Some(Authentication::ntlm()),
+        _ => None,
+    }
+}
+
+/// Describe the feature needed for the authentication scheme of the given
+/// option.
+fn missing_scheme_feature(name: &str) -> String {
+    let feature = if name.ends_with("ntlm") {
+        "ntlm"
+    } else {
+        "spnego"
+    };
+
+    format!("`{}` requires the `{}` feature", name, feature)
+}
+
+/// Parse a header given as `name: value`, or as `name;` for an empty value.
+fn header(value: &str) -> Option<(HeaderName, HeaderValue)> {
+    let i = value.find(&[':', ';'][..])?;
+    let (name, rest) = value.split_at(i);
+
+    Some((
+        HeaderName::from_bytes(name.trim().as_bytes()).ok()?,
+        HeaderValue::from_str(rest[1..].trim()).ok()?,
+    ))
+}
+
+fn credentials(user: &str) -> Credentials {
+    let mut parts = user.splitn(2, ':');
+    let username = parts.next().unwrap_or_default();
+    let password = parts.next().unwrap_or_default();
+
+    Credentials::new(username, password)
+}
+
+fn seconds(value: &str) -> Option<Duration> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0 && *seconds < 1e12)
+        .map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timeout;
+    use http::Extensions;
+
+    #[test]
+    fn parse_config_file() {
+        let mut problems = Vec::new();
+        let options = parse(
+            "# A comment\n\
+             \n\
+             location\n\
+             max-time = 2.5\n\
+             --header \"x-quoted: \\\"a\\\\b\\\"\"\n\
+             -H x-short:1\n\
+             user-agent: \"\"\n\
+             silent\n\
+             fail\n\
+             cacert\n",
+            &mut problems,
+        );
+
+        assert_eq!(options, vec![
+            (3, "location", None),
+            (4, "max-time", Some(String::from("2.5"))),
+            (5, "header", Some(String::from("x-quoted: \"a\\b\""))),
+            (6, "header", Some(String::from("x-short:1"))),
+            (7, "user-agent", Some(String::new())),
+        ]);
+        assert_eq!(problems, vec![
+            "curl config line 9: unsupported option `fail`",
+            "curl config line 10: invalid or missing value for `cacert`",
+        ]);
+    }
+
+    #[test]
+    fn export_config_file() {
+        let mut defaults = Extensions::new();
+        defaults.insert(Timeout(Duration::from_secs(5)));
+        defaults.insert(UserAgent(None));
+        defaults.insert(RedirectPolicy::Follow);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "\"secret\"".parse().unwrap());
+
+        assert_eq!(
+            export(&defaults, &headers),
+            "user-agent = \"\"\n\
+             compressed\n\
+             max-time = \"5\"\n\
+             location\n\
+             header = \"x-api-key: \\\"secret\\\"\"\n"
+        );
+    }
+
+    #[test]
+    fn import_config_file() {
+        let (builder, problems) = import(
+            HttpClientBuilder::new(),
+            "connect-timeout 1.5\n\
+             max-redirs = 3\n\
+             location\n\
+             -k\n\
+             proxy-user = \"admin:p@ss:word\"\n\
+             noproxy localhost,.internal\n\
+             header = \"x-empty;\"\n\
+             key client.key\n\
+             connect-to = example.org:443:[::1]:8443\n",
+        );
+
+        assert_eq!(problems, vec!["a key was given without a certificate"]);
+        assert_eq!(
+            builder.build().unwrap().to_curlrc(),
+            "user-agent = \"{}\"\n\
+             compressed\n\
+             connect-timeout = \"1.5\"\n\
+             location\n\
+             max-redirs = \"3\"\n\
+             connect-to = \"example.org:443:[::1]:8443\"\n\
+             noproxy = \"localhost,.internal\"\n\
+             proxy-user = \"admin:p@ss:word\"\n\
+             insecure\n\
+             header = \"x-empty;\"\n"
+                .replace("{}", &UserAgent::expand(UserAgent::DEFAULT_TEMPLATE))
+        );
+    }
+    #[test]
+    fn import_proxy_options() {
+        let (builder, problems) = import(
+            HttpClientBuilder::new(),
+            "proxy http://proxy:3128\n\
+             proxy-digest\n\
+             proxy-header = \"x-proxy-token: abc\"\n\
+             proxy-header = \"bad header\"\n",
+        );
+
+        assert_eq!(problems, vec![
+            "curl config line 4: invalid header `bad header`"
+        ]);
+        assert_eq!(
+            builder.build().unwrap().to_curlrc(),
+            "user-agent = \"{}\"\n\
+             compressed\n\
+             proxy = \"http://proxy:3128/\"\n\
+             proxy-digest\n\
+             proxy-header = \"x-proxy-token: abc\"\n"
+                .replace("{}", &UserAgent::expand(UserAgent::DEFAULT_TEMPLATE))
+        );
+    }
+
+    #[test]
+    fn import_resolve_entries() {
+        let (builder, problems) = import(
+            HttpClientBuilder::new(),
+            "resolve example.org:443:127.0.0.1\n\
+             resolve = \"example.com:80:192.0.2.1,[::1]\"\n\
+             resolve = example.net:80:backend\n",
+        );
+
+        assert_eq!(problems, vec![
+            "curl config line 3: unsupported resolve entry `example.net:80:backend`"
+        ]);
+        assert_eq!(
+            builder.build().unwrap().to_curlrc(),
+            "user-agent = \"{}\"\n\
+             compressed\n\
+             resolve = \"example.org:443:127.0.0.1\"\n\
+             resolve = \"example.com:80:192.0.2.1,[::1]\"\n"
+                .replace("{}", &UserAgent::expand(UserAgent::DEFAULT_TEMPLATE))
+        );
+    }
+}
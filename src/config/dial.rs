@@ -1,10 +1,29 @@
 //! Configuration for customizing how connections are established and sockets
 //! are opened.
 
+#![allow(unsafe_code)]
+
 use super::SetOpt;
+use crate::{
+    error::{Error, ErrorKind},
+    task::ThreadPool,
+};
 use curl::easy::{Easy2, List};
+use curl_sys::curl_socket_t;
 use http::Uri;
-use std::{convert::TryFrom, fmt, net::SocketAddr, str::FromStr};
+use std::{
+    convert::TryFrom,
+    fmt,
+    future::Future,
+    io,
+    net::{SocketAddr, TcpStream},
+    os::raw::{c_int, c_void},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// Threads that call custom dialers.
+static DIAL_POOL: ThreadPool = ThreadPool::new("isahc-dial", 8);
 
 /// An error which can be returned when parsing a dial address.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -27,6 +46,10 @@ impl std::error::Error for DialerParseError {}
 /// - `unix`: Connect to a Unix socket located on the file system, like
 ///   `unix:/path/to/my.sock`. This is only supported on Unix.
 ///
+/// A dialer can also hand over a socket that is already connected using
+/// [`Dialer::connected_socket`], or establish connections in a custom way
+/// using [`Dialer::custom`].
+///
 /// The [`Default`] dialer uses the hostname and port specified in each request
 /// as normal.
 ///
@@ -52,6 +75,55 @@ enum Inner {
 
     #[cfg(unix)]
     UnixSocket(std::path::PathBuf),
+
+    Connected(ConnectedSocket),
+
+    Custom(CustomDialer),
+
+    /// A connection opened by a custom dialer ahead of the request.
+    Dialed(ConnectedSocket),
+}
+
+/// A custom way of establishing TCP connections, used by [`Dialer::custom`].
+///
+/// This is implemented for functions and closures taking a [`DialTarget`], so
+/// simple dialers do not need a type of their own.
+pub trait Dial: Send + Sync {
+    /// Open a TCP connection through which the given target can be reached.
+    ///
+    /// The stream returned can be connected to the target itself, or to
+    /// anything that forwards data to and from the target, such as a tunnel
+    /// that has already been set up through a SOCKS proxy.
+    fn dial(&self, target: &DialTarget<'_>) -> io::Result<TcpStream>;
+}
+
+impl<F> Dial for F
+where
+    F: Fn(&DialTarget<'_>) -> io::Result<TcpStream> + Send + Sync,
+{
+    fn dial(&self, target: &DialTarget<'_>) -> io::Result<TcpStream> {
+        (self)(target)
+    }
+}
+
+/// The host and port a [`Dial`] implementation is asked to connect to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DialTarget<'a> {
+    host: &'a str,
+    port: u16,
+}
+
+impl DialTarget<'_> {
+    /// Get the host name or IP address to connect to, as it appears in the
+    /// request URI. IPv6 addresses are given without brackets.
+    pub fn host(&self) -> &str {
+        self.host
+    }
+
+    /// Get the port to connect to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
 }
 
 impl Dialer {
@@ -102,6 +174,213 @@ impl Dialer {
     pub fn unix_socket(path: impl Into<std::path::PathBuf>) -> Self {
         Self(Inner::UnixSocket(path.into()))
     }
+
+    /// Use a socket that is already connected instead of connecting to the
+    /// host of the request.
+    ///
+    /// This makes it possible to send requests over sockets obtained in other
+    /// ways, such as from systemd socket activation, after NAT hole punching, or
+    /// from a custom dialer, while still letting Isahc speak HTTP over them.
+    /// Any TCP or Unix socket can be given. Ownership of the socket passes to
+    /// the dialer, which closes it when the connection is done with or when the
+    /// dialer is dropped without having been used.
+    ///
+    /// The socket is only good for a single connection, so the dialer should
+    /// be set on an individual request rather than as a client default. The
+    /// connection is never reused by other requests, and any further attempt
+    /// to connect with the same dialer fails with
+    /// [`ErrorKind::ConnectionFailed`](crate::error::ErrorKind::ConnectionFailed).
+    /// The host name of the request is still resolved as normal, so using an IP
+    /// address or `localhost` in the URI avoids a pointless DNS lookup. The
+    /// resolved address is also what is reported as the remote address of the
+    /// response. TLS is established over the socket for `https` requests.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::Dialer, prelude::*};
+    /// use std::net::TcpStream;
+    ///
+    /// let stream = TcpStream::connect("127.0.0.1:8080")?;
+    ///
+    /// let response = Request::get("http://localhost/status")
+    ///     .dial(Dialer::connected_socket(stream))
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Availability
+    ///
+    /// This function is only available on Unix and Windows.
+    #[cfg(unix)]
+    pub fn connected_socket(socket: impl std::os::unix::io::IntoRawFd) -> Self {
+        let socket = socket.into_raw_fd() as curl_socket_t;
+
+        Self(Inner::Connected(ConnectedSocket::new(socket)))
+    }
+
+    /// Use a socket that is already connected instead of connecting to the
+    /// host of the request.
+    ///
+    /// See the Unix version of this function for details.
+    #[cfg(windows)]
+    pub fn connected_socket(socket: impl std::os::windows::io::IntoRawSocket) -> Self {
+        let socket = socket.into_raw_socket() as curl_socket_t;
+
+        Self(Inner::Connected(ConnectedSocket::new(socket)))
+    }
+
+    /// Establish connections using the given custom dialer.
+    ///
+    /// The dialer is asked to open a TCP stream whenever a new connection is
+    /// needed, which Isahc then uses for TLS and HTTP as it would any other
+    /// connection. This makes it possible to route connections in ways that
+    /// cannot be expressed using proxy options, such as through a SOCKS
+    /// implementation of your own, through Tor, or based on logic of your own
+    /// for each request.
+    ///
+    /// The host name of the request is not resolved before calling the dialer;
+    /// doing so, if needed, is up to the dialer. Connections opened by a custom
+    /// dialer are never reused by other requests, and requests using a custom
+    /// dialer always open a new connection. If the dialer returns an error, the
+    /// request fails with
+    /// [`ErrorKind::ConnectionFailed`](crate::error::ErrorKind::ConnectionFailed)
+    /// and the error as its source.
+    ///
+    /// The dialer is called on a background thread before the request is
+    /// started, so it is free to block. Dialers share a small, fixed number of
+    /// threads, so requests wait for a free thread while all of them are busy
+    /// dialing. The request
+    /// [timeout](super::Configurable::timeout) also applies to the time spent
+    /// waiting for the dialer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::Dialer, prelude::*};
+    /// use std::{net::TcpStream, time::Duration};
+    ///
+    /// let dialer = Dialer::custom(|target: &isahc::config::DialTarget<'_>| {
+    ///     println!("connecting to {}:{}", target.host(), target.port());
+    ///
+    ///     let addr = "127.0.0.1:8080".parse().unwrap();
+    ///     TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+    /// });
+    ///
+    /// let response = Request::get("http://example.org")
+    ///     .dial(dialer)
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    pub fn custom(dialer: impl Dial + 'static) -> Self {
+        Self(Inner::Custom(CustomDialer(Arc::new(dialer))))
+    }
+
+    /// Check whether this dialer connects to the host of each request as
+    /// normal.
+    pub(crate) fn is_default(&self) -> bool {
+        self.0 == Inner::Default
+    }
+
+    /// Check whether this dialer connects to a Unix socket.
+    pub(crate) fn is_unix_socket(&self) -> bool {
+        match &self.0 {
+            #[cfg(unix)]
+            Inner::UnixSocket(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Check whether this dialer uses a custom dialer.
+    pub(crate) fn is_custom(&self) -> bool {
+        match &self.0 {
+            Inner::Custom(_) | Inner::Dialed(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Get the socket opener to use for a request to the given URI instead of
+    /// letting curl open sockets, if any.
+    pub(crate) fn socket_opener(&self, uri: &Uri) -> Option<Box<SocketOpener>> {
+        let source = match &self.0 {
+            Inner::Connected(socket) | Inner::Dialed(socket) => Source::Connected(socket.clone()),
+            Inner::Custom(dialer) => Source::Custom {
+                dialer: dialer.0.clone(),
+                host: target_host(uri),
+                port: target_port(uri),
+            },
+            _ => return None,
+        };
+
+        Some(Box::new(SocketOpener {
+            source,
+            error: None,
+            peer_addr: None,
+        }))
+    }
+
+    /// Open a connection for a request to the given URI ahead of time, if this
+    /// is a custom dialer.
+    ///
+    /// The custom dialer is called on one of a few background threads, so that
+    /// a dialer that blocks does not hold up the thread driving all requests.
+    /// The future returned resolves to a dialer that hands the connection over
+    /// to curl.
+    pub(crate) fn dial(&self, uri: &Uri) -> Option<impl Future<Output = Result<Self, Error>>> {
+        let dialer = match &self.0 {
+            Inner::Custom(dialer) => dialer.0.clone(),
+            _ => return None,
+        };
+
+        let host = target_host(uri);
+        let port = target_port(uri);
+
+        Some(async move {
+            let (sender, receiver) = flume::bounded(1);
+
+            DIAL_POOL.execute(move || {
+                let target = DialTarget {
+                    host: &host,
+                    port,
+                };
+
+                let result = dialer.dial(&target);
+
+                if let Err(e) = &result {
+                    tracing::debug!("custom dialer failed to connect to {:?}: {}", target, e);
+                }
+
+                let _ = sender.send(result);
+            })?;
+
+            let stream = receiver
+                .recv_async()
+                .await
+                .map_err(|e| Error::new(ErrorKind::Unknown, e))?
+                .map_err(|e| Error::new(ErrorKind::ConnectionFailed, e))?;
+
+            Ok(Self(Inner::Dialed(ConnectedSocket::from_stream(stream))))
+        })
+    }
+}
+
+/// Get the host a custom dialer should connect to for the given URI.
+fn target_host(uri: &Uri) -> String {
+    uri.host()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_owned()
+}
+
+/// Get the port a custom dialer should connect to for the given URI.
+fn target_port(uri: &Uri) -> u16 {
+    uri.port_u16().unwrap_or_else(|| match uri.scheme_str() {
+        Some("http") | Some("ws") => 80,
+        _ => 443,
+    })
 }
 
 impl Default for Dialer {
@@ -173,8 +452,14 @@ impl SetOpt for Dialer {
     fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
         let mut connect_to = List::new();
 
-        if let Inner::IpSocket(addr) = &self.0 {
-            connect_to.append(addr)?;
+        match &self.0 {
+            Inner::IpSocket(addr) => connect_to.append(addr)?,
+
+            // Connecting to an IP address keeps curl from resolving the host
+            // name; the address is not used since the dialer opens the socket.
+            Inner::Custom(_) | Inner::Dialed(_) => connect_to.append("::127.0.0.1:")?,
+
+            _ => {}
         }
 
         easy.connect_to(connect_to)?;
@@ -185,8 +470,331 @@ impl SetOpt for Dialer {
             _ => None,
         })?;
 
+        // A connected socket must not end up in the connection cache where
+        // other requests could pick it up, and requests using it must not
+        // pick up connections from the cache either. The same goes for custom
+        // dialers, which may route each request differently.
+        if let Inner::Connected(_) | Inner::Custom(_) | Inner::Dialed(_) = &self.0 {
+            easy.fresh_connect(true)?;
+            easy.forbid_reuse(true)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A socket that is already connected, waiting to be handed over to curl.
+///
+/// The socket can be taken only once, even by clones of the same value.
+#[derive(Clone)]
+struct ConnectedSocket(Arc<SocketSlot>);
+
+struct SocketSlot {
+    socket: Mutex<Option<curl_socket_t>>,
+
+    /// The address of the peer, if known.
+    peer_addr: Option<SocketAddr>,
+}
+
+impl ConnectedSocket {
+    fn new(socket: curl_socket_t) -> Self {
+        Self(Arc::new(SocketSlot {
+            socket: Mutex::new(Some(socket)),
+            peer_addr: None,
+        }))
+    }
+
+    fn from_stream(stream: TcpStream) -> Self {
+        let peer_addr = stream.peer_addr().ok();
+
+        Self(Arc::new(SocketSlot {
+            socket: Mutex::new(Some(into_raw_socket(stream))),
+            peer_addr,
+        }))
+    }
+
+    /// Take the socket, unless it has been taken already.
+    fn take(&self) -> Option<curl_socket_t> {
+        self.0
+            .socket
+            .lock()
+            .ok()
+            .and_then(|mut socket| socket.take())
+    }
+}
+
+impl PartialEq for ConnectedSocket {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ConnectedSocket {}
+
+impl fmt::Debug for ConnectedSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ConnectedSocket")
+            .field(&self.0.socket.lock().ok().and_then(|socket| *socket))
+            .finish()
+    }
+}
+
+impl Drop for SocketSlot {
+    fn drop(&mut self) {
+        // Close the socket if curl never took it.
+        if let Some(socket) = self.socket.get_mut().ok().and_then(Option::take) {
+            #[cfg(unix)]
+            drop(unsafe {
+                <std::net::TcpStream as std::os::unix::io::FromRawFd>::from_raw_fd(socket as _)
+            });
+
+            #[cfg(windows)]
+            drop(unsafe {
+                <std::net::TcpStream as std::os::windows::io::FromRawSocket>::from_raw_socket(
+                    socket as _,
+                )
+            });
+        }
+    }
+}
+
+/// A custom dialer, compared by identity.
+#[derive(Clone)]
+struct CustomDialer(Arc<dyn Dial>);
+
+impl PartialEq for CustomDialer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CustomDialer {}
+
+impl fmt::Debug for CustomDialer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomDialer")
+    }
+}
+
+/// Opens sockets for a single curl handle in place of curl.
+pub(crate) struct SocketOpener {
+    source: Source,
+
+    /// The error returned by a custom dialer, if any.
+    error: Option<io::Error>,
+
+    /// The address of the peer of the stream opened by a custom dialer.
+    peer_addr: Option<SocketAddr>,
+}
+
+enum Source {
+    Connected(ConnectedSocket),
+    Custom {
+        dialer: Arc<dyn Dial>,
+        host: String,
+        port: u16,
+    },
+}
+
+impl SocketOpener {
+    /// Make curl use this opener for the connection of the given handle.
+    ///
+    /// Curl holds a pointer to the opener, so it must be kept alive and in
+    /// place for as long as the handle is.
+    pub(crate) fn set_callbacks<H>(&mut self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        let data = self as *mut SocketOpener as *mut c_void;
+
+        let open_callback: curl_sys::curl_opensocket_callback = open_socket_callback;
+        let sockopt_callback: extern "C" fn(*mut c_void, curl_socket_t, c_int) -> c_int =
+            sockopt_callback;
+
+        // Use unsafe API, because the safe API doesn't expose these options
+        // without a handler to go with them.
+        unsafe {
+            for &(option, value) in &[
+                (
+                    curl_sys::CURLOPT_OPENSOCKETFUNCTION,
+                    open_callback as *mut c_void,
+                ),
+                (curl_sys::CURLOPT_OPENSOCKETDATA, data),
+                (
+                    curl_sys::CURLOPT_SOCKOPTFUNCTION,
+                    sockopt_callback as *mut c_void,
+                ),
+            ] {
+                match curl_sys::curl_easy_setopt(easy.raw(), option, value) {
+                    curl_sys::CURLE_OK => {}
+                    code => return Err(curl::Error::new(code)),
+                }
+            }
+        }
+
         Ok(())
     }
+
+    /// Take the error returned by a custom dialer, if any.
+    pub(crate) fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Get the address of the peer of the stream opened by a custom dialer.
+    pub(crate) fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    fn open(&mut self) -> Option<curl_socket_t> {
+        match &self.source {
+            Source::Connected(socket) => {
+                self.peer_addr = socket.0.peer_addr;
+                socket.take()
+            }
+            Source::Custom {
+                dialer,
+                host,
+                port,
+            } => {
+                let target = DialTarget {
+                    host,
+                    port: *port,
+                };
+
+                match dialer.dial(&target) {
+                    Ok(stream) => {
+                        self.peer_addr = stream.peer_addr().ok();
+
+                        Some(into_raw_socket(stream))
+                    }
+                    Err(e) => {
+                        tracing::debug!("custom dialer failed to connect to {:?}: {}", target, e);
+                        self.error = Some(e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Give up ownership of a stream to hand it over to curl.
+fn into_raw_socket(stream: TcpStream) -> curl_socket_t {
+    #[cfg(unix)]
+    let socket = std::os::unix::io::IntoRawFd::into_raw_fd(stream);
+    #[cfg(windows)]
+    let socket = std::os::windows::io::IntoRawSocket::into_raw_socket(stream);
+
+    socket as curl_socket_t
+}
+
+/// Called by curl instead of opening a socket. Curl closes the socket when it
+/// is done with the connection.
+extern "C" fn open_socket_callback(
+    data: *mut c_void,
+    _purpose: curl_sys::curlsocktype,
+    _address: *mut curl_sys::curl_sockaddr,
+) -> curl_socket_t {
+    let opener = unsafe { &mut *(data as *mut SocketOpener) };
+
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| opener.open()))
+        .ok()
+        .and_then(|socket| socket)
+        .unwrap_or(curl_sys::CURL_SOCKET_BAD)
+}
+
+/// Called by curl after opening a socket, to tell it that the socket does not
+/// need to be connected.
+extern "C" fn sockopt_callback(_: *mut c_void, _: curl_socket_t, _: c_int) -> c_int {
+    const CURL_SOCKOPT_ALREADY_CONNECTED: c_int = 2;
+
+    CURL_SOCKOPT_ALREADY_CONNECTED
+}
+
+/// A mapping that makes connections to one host and port go to another host
+/// and port instead, without changing the request itself.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConnectTo {
+    host: String,
+    port: u16,
+    target_host: String,
+    target_port: u16,
+}
+
+impl ConnectTo {
+    pub(crate) fn new(host: &str, port: u16, target_host: &str, target_port: u16) -> Self {
+        Self {
+            host: bracket_ipv6(host),
+            port,
+            target_host: bracket_ipv6(target_host),
+            target_port,
+        }
+    }
+
+    /// Parse a mapping in the `HOST1:PORT1:HOST2:PORT2` format used by curl.
+    /// Mappings that leave out any of the hosts or ports are not supported.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        let (host, rest) = split_host(s)?;
+        let (port, rest) = split_port(rest)?;
+        let (target_host, rest) = split_host(rest)?;
+        let target_port = rest.parse().ok()?;
+
+        Some(Self::new(host, port, target_host, target_port))
+    }
+
+    /// Combine this mapping with a mapping that applies to its target, so that
+    /// connections go to the final target right away.
+    pub(crate) fn followed_by(self, next: &Self) -> Self {
+        if self.target_host.eq_ignore_ascii_case(&next.host) && self.target_port == next.port {
+            Self {
+                target_host: next.target_host.clone(),
+                target_port: next.target_port,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// Formats the mapping as expected by `CURLOPT_CONNECT_TO`.
+impl fmt::Display for ConnectTo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.host, self.port, self.target_host, self.target_port
+        )
+    }
+}
+
+/// Put an IPv6 address in brackets, as it appears in a URI.
+fn bracket_ipv6(host: &str) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]", host)
+    } else {
+        host.to_owned()
+    }
+}
+
+/// Split a non-empty host, which may be an IPv6 address in brackets, from the
+/// colon and the rest of the string following it.
+fn split_host(s: &str) -> Option<(&str, &str)> {
+    let end = if s.starts_with('[') {
+        s.find(']')? + 1
+    } else {
+        s.find(':')?
+    };
+
+    if end == 0 || !s[end..].starts_with(':') {
+        return None;
+    }
+
+    Some((&s[..end], &s[end + 1..]))
+}
+
+/// Split a port from the colon and the rest of the string following it.
+fn split_port(s: &str) -> Option<(u16, &str)> {
+    let end = s.find(':')?;
+
+    Some((s[..end].parse().ok()?, &s[end + 1..]))
 }
 
 #[cfg(test)]
@@ -223,4 +831,33 @@ mod tests {
 
         assert_eq!(dialer.0, Inner::UnixSocket("/path/to/my.sock".into()));
     }
+
+    #[test]
+    fn parse_connect_to() {
+        let connect_to = ConnectTo::parse("example.org:443:[::1]:8443").unwrap();
+
+        assert_eq!(connect_to, ConnectTo::new("example.org", 443, "::1", 8443));
+        assert_eq!(connect_to.to_string(), "example.org:443:[::1]:8443");
+
+        assert!(ConnectTo::parse("::127.0.0.1:8080").is_none());
+        assert!(ConnectTo::parse("example.org:https:backend:443").is_none());
+    }
+
+    #[test]
+    fn connect_to_mappings_are_chained() {
+        let first = ConnectTo::new("api.example.org", 443, "192.0.2.1", 443);
+
+        assert_eq!(
+            first
+                .clone()
+                .followed_by(&ConnectTo::new("192.0.2.1", 443, "192.0.2.2", 8443)),
+            ConnectTo::new("api.example.org", 443, "192.0.2.2", 8443)
+        );
+        assert_eq!(
+            first
+                .clone()
+                .followed_by(&ConnectTo::new("192.0.2.1", 80, "192.0.2.2", 8080)),
+            first
+        );
+    }
 }
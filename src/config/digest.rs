@@ -0,0 +1,38 @@
+use http::header::HeaderName;
+
+/// The header in which to send a digest of the body of a request, as
+/// described in [RFC 9530](https://www.rfc-editor.org/rfc/rfc9530).
+///
+/// Since request bodies are sent as they are, without applying any content
+/// coding, both headers hold the same digest unless the request is for part
+/// of a resource. They differ in what a server takes them to mean.
+///
+/// See [`Configurable::send_digest`](super::Configurable::send_digest).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DigestHeader {
+    /// Send the digest in the `Content-Digest` header, which covers the
+    /// content of the request exactly as it is sent.
+    Content,
+
+    /// Send the digest in the `Repr-Digest` header, which covers the selected
+    /// representation of the resource that the body holds.
+    Representation,
+}
+
+impl DigestHeader {
+    pub(crate) fn name(self) -> HeaderName {
+        match self {
+            DigestHeader::Content => HeaderName::from_static("content-digest"),
+            DigestHeader::Representation => HeaderName::from_static("repr-digest"),
+        }
+    }
+}
+
+/// Send a digest of the request body in the given header.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SendDigest(pub(crate) DigestHeader);
+
+/// Verify digests of response bodies against their digest headers.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct VerifyDigest(pub(crate) bool);
@@ -1,8 +1,16 @@
 //! Configuration of DNS resolution.
 
 use super::SetOpt;
+use crate::error::{Error, ErrorKind};
 use curl::easy::Easy2;
-use std::{net::IpAddr, time::Duration};
+use http::Uri;
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, ToSocketAddrs},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 /// DNS caching configuration.
 ///
@@ -60,8 +68,9 @@ impl SetOpt for DnsCache {
 ///
 /// Entries added to this map can be used to override how DNS is resolved for a
 /// request and use specific IP addresses instead of using the default name
-/// resolver.
-#[derive(Clone, Debug, Default)]
+/// resolver. See [`Configurable::dns_resolve`](super::Configurable::dns_resolve)
+/// for how to use it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct ResolveMap(Vec<String>);
 
 impl ResolveMap {
@@ -80,6 +89,82 @@ impl ResolveMap {
             .push(format!("{}:{}:{}", host.as_ref(), port, addr.into()));
         self
     }
+
+    /// Add a DNS mapping for a given host and port pair to several addresses.
+    ///
+    /// The addresses are tried in the given order when connecting, just like
+    /// the addresses returned by the name resolver. Adding no addresses at all
+    /// leaves the map unchanged.
+    pub fn add_all<H, I>(mut self, host: H, port: u16, addrs: I) -> Self
+    where
+        H: AsRef<str>,
+        I: IntoIterator,
+        I::Item: Into<IpAddr>,
+    {
+        let addrs = addrs.into_iter().map(Into::into).collect::<Vec<_>>();
+
+        if !addrs.is_empty() {
+            self.0
+                .push(format!("{}:{}:{}", host.as_ref(), port, join_addrs(&addrs)));
+        }
+
+        self
+    }
+
+    /// Add an entry in the `HOST:PORT:ADDRESS[,ADDRESS]...` format used by
+    /// curl, returning `None` if it is not in that format.
+    pub(crate) fn add_parsed(mut self, entry: &str) -> Option<Self> {
+        let mut parts = entry.splitn(3, ':');
+        let host = parts.next().filter(|host| !host.is_empty())?;
+        let port = parts.next()?.parse::<u16>().ok()?;
+        let addrs = parts
+            .next()?
+            .split(',')
+            .map(|addr| addr.trim_start_matches('[').trim_end_matches(']').parse())
+            .collect::<Result<Vec<IpAddr>, _>>()
+            .ok()?;
+
+        self.0
+            .push(format!("{}:{}:{}", host, port, join_addrs(&addrs)));
+
+        Some(self)
+    }
+
+    /// Get the entries of this map in the format used by curl.
+    pub(crate) fn entries(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Add addresses resolved ahead of time for a host and port pair, unless
+    /// this map already overrides it.
+    ///
+    /// The entry is prefixed with `+`, which makes curl put the addresses in
+    /// its DNS cache like an ordinary lookup result, so that they expire
+    /// according to the [`DnsCache`] setting.
+    pub(crate) fn add_prefetched(mut self, host: &str, port: u16, addrs: &[IpAddr]) -> Self {
+        let prefix = format!("{}:{}:", host, port);
+
+        if self.0.iter().any(|entry| {
+            entry.len() >= prefix.len() && entry[..prefix.len()].eq_ignore_ascii_case(&prefix)
+        }) {
+            return self;
+        }
+
+        self.0.push(format!("+{}{}", prefix, join_addrs(addrs)));
+        self
+    }
+}
+
+/// Join addresses into a list as expected by `CURLOPT_RESOLVE`.
+fn join_addrs(addrs: &[IpAddr]) -> String {
+    addrs
+        .iter()
+        .map(|addr| match addr {
+            IpAddr::V4(addr) => addr.to_string(),
+            IpAddr::V6(addr) => format!("[{}]", addr),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 impl SetOpt for ResolveMap {
@@ -93,3 +178,193 @@ impl SetOpt for ResolveMap {
         easy.resolve(list)
     }
 }
+
+/// Addresses of host names that were resolved ahead of time using
+/// [`HttpClient::prefetch_dns`](crate::HttpClient::prefetch_dns).
+#[derive(Debug, Default)]
+pub(crate) struct PrefetchedHosts {
+    hosts: Mutex<HashMap<String, Prefetched>>,
+}
+
+#[derive(Debug)]
+struct Prefetched {
+    addrs: Vec<IpAddr>,
+
+    /// When the addresses should no longer be used, if ever.
+    expires: Option<Instant>,
+}
+
+impl Prefetched {
+    fn is_expired(&self, now: Instant) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+}
+
+impl PrefetchedHosts {
+    /// Get the addresses resolved for the host of the given URI, if they
+    /// have not expired yet.
+    pub(crate) fn get(&self, uri: &Uri) -> Option<Vec<IpAddr>> {
+        let host = uri.host()?.to_ascii_lowercase();
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        match hosts.get(&host) {
+            Some(prefetched) if !prefetched.is_expired(Instant::now()) => {
+                Some(prefetched.addrs.clone())
+            }
+            Some(_) => {
+                hosts.remove(&host);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remember the addresses of a host for as long as the given DNS cache
+    /// setting keeps entries.
+    pub(crate) fn insert(&self, host: &str, addrs: Vec<IpAddr>, cache: &DnsCache) {
+        let now = Instant::now();
+        let expires = match cache {
+            // Curl would not keep the addresses either.
+            DnsCache::Disable => return,
+            DnsCache::Timeout(duration) => Some(now + *duration),
+            DnsCache::Forever => None,
+        };
+
+        let prefetched = Prefetched {
+            addrs,
+            expires,
+        };
+
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Forget about expired hosts, so that the map does not keep growing.
+        hosts.retain(|_, prefetched| !prefetched.is_expired(now));
+        hosts.insert(host.to_ascii_lowercase(), prefetched);
+    }
+}
+
+/// Resolve the addresses of a host name on a background thread.
+///
+/// The system resolver used by curl blocks, so this is done on a separate
+/// thread to avoid blocking the caller if it is asynchronous.
+pub(crate) async fn resolve(host: String) -> Result<Vec<IpAddr>, Error> {
+    let (sender, receiver) = flume::bounded(1);
+
+    std::thread::Builder::new()
+        .name(String::from("isahc-dns-prefetch"))
+        .spawn(move || {
+            let result = (host.as_str(), 0).to_socket_addrs().map(|addrs| {
+                let mut ips = Vec::new();
+
+                for addr in addrs {
+                    if !ips.contains(&addr.ip()) {
+                        ips.push(addr.ip());
+                    }
+                }
+
+                ips
+            });
+
+            let _ = sender.send(result);
+        })?;
+
+    let addrs = receiver
+        .recv_async()
+        .await
+        .map_err(|e| Error::new(ErrorKind::Unknown, e))?
+        .map_err(|e| Error::new(ErrorKind::NameResolution, e))?;
+
+    if addrs.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NameResolution,
+            io::Error::new(io::ErrorKind::NotFound, "host name has no addresses"),
+        ));
+    }
+
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn prefetched_addresses_are_added_to_resolve_map() {
+        let addrs = [
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ];
+        let map = ResolveMap::new().add_prefetched("example.org", 443, &addrs);
+
+        assert_eq!(map.0, vec!["+example.org:443:127.0.0.1,[::1]"]);
+    }
+
+    #[test]
+    fn resolve_map_overrides_prefetched_addresses() {
+        let addrs = [IpAddr::V4(Ipv4Addr::LOCALHOST)];
+        let map = ResolveMap::new()
+            .add("Example.org", 443, [10, 0, 0, 1])
+            .add_prefetched("example.org", 443, &addrs)
+            .add_prefetched("example.org", 80, &addrs);
+
+        let expected = vec!["Example.org:443:10.0.0.1", "+example.org:80:127.0.0.1"];
+
+        assert_eq!(map.0, expected);
+    }
+
+    #[test]
+    fn add_several_addresses_for_host() {
+        let map = ResolveMap::new()
+            .add_all("example.org", 443, vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ])
+            .add_all("example.com", 443, Vec::<IpAddr>::new());
+
+        assert_eq!(map.0, vec!["example.org:443:127.0.0.1,[::1]"]);
+    }
+
+    #[test]
+    fn parse_resolve_entries() {
+        let map = ResolveMap::new()
+            .add_parsed("example.org:443:127.0.0.1,[::1]")
+            .unwrap();
+
+        assert_eq!(map.0, vec!["example.org:443:127.0.0.1,[::1]"]);
+
+        assert!(ResolveMap::new().add_parsed("example.org:443").is_none());
+        assert!(ResolveMap::new().add_parsed(":443:127.0.0.1").is_none());
+        assert!(
+            ResolveMap::new()
+                .add_parsed("example.org:https:127.0.0.1")
+                .is_none()
+        );
+        assert!(
+            ResolveMap::new()
+                .add_parsed("example.org:443:backend")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn prefetched_hosts_follow_dns_cache_setting() {
+        let uri = "https://Example.org/".parse::<Uri>().unwrap();
+        let addrs = vec![IpAddr::V4(Ipv4Addr::LOCALHOST)];
+
+        let hosts = PrefetchedHosts::default();
+        hosts.insert("example.org", addrs.clone(), &DnsCache::Forever);
+        assert_eq!(hosts.get(&uri), Some(addrs.clone()));
+
+        let hosts = PrefetchedHosts::default();
+        hosts.insert("example.org", addrs.clone(), &DnsCache::Disable);
+        assert_eq!(hosts.get(&uri), None);
+
+        let hosts = PrefetchedHosts::default();
+        hosts.insert("example.org", addrs, &Duration::from_secs(0).into());
+        assert_eq!(hosts.get(&uri), None);
+    }
+}
@@ -0,0 +1,216 @@
+use super::VersionNegotiation;
+use crate::{body::AsyncBody, request::RequestExt};
+use http::{Request, Uri};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long a host is contacted using HTTP/1.1 only after an HTTP/2 failure,
+/// unless configured otherwise.
+const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Describes whether a request that fails over HTTP/2 should be retried over
+/// HTTP/1.1.
+///
+/// Some servers, as well as proxies and load balancers in front of them, have
+/// broken HTTP/2 implementations that reset streams or send invalid frames for
+/// some requests. Browsers work around this by retrying such requests using
+/// HTTP/1.1, and by remembering for a while that the server should only be
+/// contacted using HTTP/1.1. With the fallback enabled, Isahc does the same:
+/// when a request fails with an HTTP/2 protocol or stream error before a
+/// response is received, the host is marked as misbehaving and the request is
+/// sent again using HTTP/1.1. Further requests sent to the same host by the
+/// same client then use HTTP/1.1 right away, until the cool-down period has
+/// passed.
+///
+/// Since the request is sent twice, only requests whose method is idempotent,
+/// such as `GET` or `PUT`, and whose body is available in memory are retried.
+/// The host is marked as misbehaving either way.
+///
+/// The fallback only applies when the default
+/// [`VersionNegotiation::latest_compatible`] strategy is used. A specific HTTP
+/// version that was asked for explicitly is always honored.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{config::VersionFallback, prelude::*, HttpClient};
+/// use std::time::Duration;
+///
+/// let client = HttpClient::builder()
+///     .version_fallback(VersionFallback::http11().cool_down(Duration::from_secs(60)))
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct VersionFallback {
+    enabled: bool,
+    cool_down: Duration,
+}
+
+impl Default for VersionFallback {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl VersionFallback {
+    /// Retry requests that fail over HTTP/2 using HTTP/1.1, and keep using
+    /// HTTP/1.1 for the same host for five minutes.
+    pub fn http11() -> Self {
+        Self {
+            enabled: true,
+            cool_down: DEFAULT_COOL_DOWN,
+        }
+    }
+
+    /// Never retry requests using a different HTTP version. This is the
+    /// default.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            cool_down: DEFAULT_COOL_DOWN,
+        }
+    }
+
+    /// Set how long a host that failed over HTTP/2 is contacted using HTTP/1.1
+    /// only.
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Check whether falling back to HTTP/1.1 is enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Get how long a misbehaving host should be contacted using HTTP/1.1.
+    pub(crate) fn cool_down_period(&self) -> Duration {
+        self.cool_down
+    }
+
+    /// Create a copy of the given request to retry over HTTP/1.1, or `None` if
+    /// the request cannot be sent again.
+    pub(crate) fn retry_request(&self, request: &Request<AsyncBody>) -> Option<Request<AsyncBody>> {
+        if !request.method().is_idempotent() {
+            return None;
+        }
+
+        let body = request.body().try_clone()?;
+
+        let mut retry = request.to_builder().body(body).ok()?;
+        retry.extensions_mut().insert(VersionNegotiation::http11());
+
+        Some(retry)
+    }
+}
+
+/// Hosts of a client that recently failed over HTTP/2, and when each may be
+/// contacted using HTTP/2 again.
+#[derive(Debug, Default)]
+pub(crate) struct DowngradedHosts {
+    hosts: Mutex<HashMap<String, Instant>>,
+}
+
+impl DowngradedHosts {
+    /// Check whether requests to the host of the given URI should currently
+    /// use HTTP/1.1.
+    pub(crate) fn contains(&self, uri: &Uri) -> bool {
+        let key = match host_key(uri) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        match hosts.get(&key) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                hosts.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Mark the host of the given URI as misbehaving over HTTP/2 for the given
+    /// period.
+    pub(crate) fn insert(&self, uri: &Uri, cool_down: Duration) {
+        if let Some(key) = host_key(uri) {
+            tracing::debug!(host = %key, ?cool_down, "using HTTP/1.1 for misbehaving host");
+
+            let now = Instant::now();
+
+            let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+            // Forget about hosts whose cool-down has passed, so that the map
+            // does not keep growing.
+            hosts.retain(|_, until| *until > now);
+            hosts.insert(key, now + cool_down);
+        }
+    }
+}
+
+/// Get the key identifying the server a request to the given URI is sent to.
+fn host_key(uri: &Uri) -> Option<String> {
+    let host = uri.host()?.to_ascii_lowercase();
+    let port = uri.port_u16().unwrap_or_else(|| {
+        if uri.scheme_str() == Some("http") {
+            80
+        } else {
+            443
+        }
+    });
+
+    Some(format!("{}:{}", host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_idempotent_requests_with_buffered_bodies_are_retried() {
+        let fallback = VersionFallback::http11();
+
+        let request = Request::put("https://example.org")
+            .header("x-test", "1")
+            .body(AsyncBody::from("hello"))
+            .unwrap();
+        let retry = fallback.retry_request(&request).unwrap();
+        assert_eq!(retry.method(), "PUT");
+        assert_eq!(retry.headers()["x-test"], "1");
+        assert_eq!(retry.body().len(), Some(5));
+        assert!(retry.extensions().get::<VersionNegotiation>().is_some());
+
+        let request = Request::post("https://example.org")
+            .body(AsyncBody::from("hello"))
+            .unwrap();
+        assert!(fallback.retry_request(&request).is_none());
+
+        let request = Request::get("https://example.org")
+            .body(AsyncBody::from_reader(futures_lite::io::empty()))
+            .unwrap();
+        assert!(fallback.retry_request(&request).is_none());
+    }
+
+    #[test]
+    fn downgraded_hosts_expire() {
+        let hosts = DowngradedHosts::default();
+        let uri = "https://Example.org/a".parse::<Uri>().unwrap();
+
+        assert!(!hosts.contains(&uri));
+
+        hosts.insert(&uri, Duration::from_secs(60));
+        assert!(hosts.contains(&uri));
+        assert!(hosts.contains(&"https://example.org:443/b".parse().unwrap()));
+        assert!(!hosts.contains(&"https://example.org:8443/a".parse().unwrap()));
+        assert!(!hosts.contains(&"http://example.org/a".parse().unwrap()));
+
+        hosts.insert(&uri, Duration::from_secs(0));
+        assert!(!hosts.contains(&uri));
+    }
+}
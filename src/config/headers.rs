@@ -0,0 +1,53 @@
+use http::{response::Parts, Extensions};
+use std::{fmt, sync::Arc};
+
+/// What to do with a response once its headers have been received, as
+/// decided by a callback given to
+/// [`Configurable::on_headers`](super::Configurable::on_headers).
+#[non_exhaustive]
+pub enum HeaderAction {
+    /// Continue receiving the response.
+    Accept,
+
+    /// Abort the request before the response body is received. The request
+    /// fails with an error of kind
+    /// [`ResponseRejected`](crate::error::ErrorKind::ResponseRejected).
+    Abort,
+
+    /// Continue receiving the response after modifying its extensions with
+    /// the given function. This can be used to attach values computed from
+    /// the headers to the response.
+    Modify(Box<dyn FnOnce(&mut Extensions) + Send>),
+}
+
+impl HeaderAction {
+    /// Continue receiving the response after inserting the given value into
+    /// its extensions, replacing any previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(value: T) -> Self {
+        HeaderAction::Modify(Box::new(move |extensions| {
+            extensions.insert(value);
+        }))
+    }
+}
+
+impl fmt::Debug for HeaderAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderAction::Accept => f.write_str("Accept"),
+            HeaderAction::Abort => f.write_str("Abort"),
+            HeaderAction::Modify(_) => f.write_str("Modify(..)"),
+        }
+    }
+}
+
+/// Callback deciding what to do with a response once its headers have been
+/// received.
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub(crate) struct OnHeaders(pub(crate) Arc<dyn Fn(&Parts) -> HeaderAction + Send + Sync>);
+
+impl fmt::Debug for OnHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnHeaders")
+    }
+}
@@ -0,0 +1,202 @@
+use super::Timeout;
+use crate::{body::AsyncBody, request::RequestExt};
+use http::{Request, Uri};
+use std::time::Duration;
+
+/// Describes a policy for sending a backup copy of a request that is slow to
+/// receive a response.
+///
+/// A small fraction of requests often takes much longer than the rest, for
+/// example because the server handling them is busy or a packet was lost.
+/// With hedging enabled, if no response headers have been received after the
+/// configured delay, a second copy of the request is sent, and whichever of
+/// the two receives a response first is used. The other one is then aborted.
+/// Choosing a delay close to the 95th percentile of normal response times
+/// usually cuts the tail latency considerably, while only sending a few percent
+/// more requests.
+///
+/// Since the request is sent twice, only requests whose method is idempotent,
+/// such as `GET` or `PUT`, are hedged. The request body must also be available
+/// in memory so that it can be sent twice; requests with a streaming body are
+/// never hedged.
+///
+/// Hedging applies to each request sent, including each redirect that is
+/// followed.
+///
+/// A [timeout](super::Configurable::timeout) is treated as a budget for both
+/// copies together rather than for each copy, so hedging never makes a request
+/// take longer than the timeout. The backup request is only given the time
+/// that remains of the budget when it is sent, and it is not sent at all if
+/// the timeout is not longer than the hedging delay.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{config::HedgePolicy, prelude::*};
+/// use std::time::Duration;
+///
+/// let request = Request::get("https://example.org/search?q=isahc")
+///     .hedge(
+///         HedgePolicy::after(Duration::from_millis(200))
+///             .alternate("https://mirror.example.org".parse()?),
+///     )
+///     .body(())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct HedgePolicy {
+    delay: Duration,
+    alternate: Option<Uri>,
+}
+
+impl HedgePolicy {
+    /// Send a backup request if no response headers have been received after
+    /// the given delay.
+    pub fn after(delay: Duration) -> Self {
+        Self {
+            delay,
+            alternate: None,
+        }
+    }
+
+    /// Send the backup request to an alternate endpoint instead of the
+    /// original one.
+    ///
+    /// The scheme and authority of the given URI replace those of the request,
+    /// while the path and query of the request are kept.
+    pub fn alternate(mut self, endpoint: Uri) -> Self {
+        self.alternate = Some(endpoint);
+        self
+    }
+
+    /// Get how long to wait before sending a backup request.
+    pub(crate) fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Create a backup copy of the given request, or `None` if the request
+    /// should not be hedged.
+    ///
+    /// If the request has a timeout, the backup is given whatever remains of
+    /// it after the hedging delay.
+    pub(crate) fn backup_request(
+        &self,
+        request: &Request<AsyncBody>,
+        timeout: Option<Duration>,
+    ) -> Option<Request<AsyncBody>> {
+        if !request.method().is_idempotent() {
+            return None;
+        }
+
+        // A timeout of zero means no timeout at all to curl, so make sure
+        // there is actually some time left.
+        let remaining = match timeout {
+            Some(timeout) => match timeout.checked_sub(self.delay) {
+                Some(remaining) if remaining > Duration::from_secs(0) => Some(remaining),
+                _ => return None,
+            },
+            None => None,
+        };
+
+        let body = request.body().try_clone()?;
+
+        let mut backup = request
+            .to_builder()
+            .uri(self.backup_uri(request.uri()))
+            .body(body)
+            .ok()?;
+
+        if let Some(remaining) = remaining {
+            backup.extensions_mut().insert(Timeout(remaining));
+        }
+
+        Some(backup)
+    }
+
+    /// Get the URI the backup for a request to the given URI should be sent
+    /// to.
+    fn backup_uri(&self, uri: &Uri) -> Uri {
+        let alternate = match self.alternate.as_ref() {
+            Some(alternate) => alternate,
+            None => return uri.clone(),
+        };
+
+        let mut parts = uri.clone().into_parts();
+        parts.scheme = alternate.scheme().cloned().or(parts.scheme);
+        parts.authority = alternate.authority().cloned().or(parts.authority);
+
+        Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_uri_keeps_path_and_query() {
+        let uri = "http://example.org/search?q=isahc".parse::<Uri>().unwrap();
+
+        assert_eq!(
+            HedgePolicy::after(Duration::from_millis(1)).backup_uri(&uri),
+            uri
+        );
+        assert_eq!(
+            HedgePolicy::after(Duration::from_millis(1))
+                .alternate("https://mirror.example.org:8443".parse().unwrap())
+                .backup_uri(&uri),
+            "https://mirror.example.org:8443/search?q=isahc"
+        );
+    }
+
+    #[test]
+    fn only_idempotent_requests_with_buffered_bodies_are_hedged() {
+        let policy = HedgePolicy::after(Duration::from_millis(1));
+
+        let request = Request::put("http://example.org")
+            .header("x-test", "1")
+            .body(AsyncBody::from("hello"))
+            .unwrap();
+        let backup = policy.backup_request(&request, None).unwrap();
+        assert_eq!(backup.method(), "PUT");
+        assert_eq!(backup.headers()["x-test"], "1");
+        assert_eq!(backup.body().len(), Some(5));
+
+        let request = Request::post("http://example.org")
+            .body(AsyncBody::from("hello"))
+            .unwrap();
+        assert!(policy.backup_request(&request, None).is_none());
+
+        let request = Request::get("http://example.org")
+            .body(AsyncBody::from_reader(futures_lite::io::empty()))
+            .unwrap();
+        assert!(policy.backup_request(&request, None).is_none());
+    }
+
+    #[test]
+    fn backup_request_gets_remaining_timeout() {
+        let policy = HedgePolicy::after(Duration::from_millis(200));
+        let request = Request::get("http://example.org")
+            .body(AsyncBody::empty())
+            .unwrap();
+
+        let backup = policy
+            .backup_request(&request, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(
+            backup.extensions().get::<Timeout>().unwrap().0,
+            Duration::from_millis(800)
+        );
+
+        assert!(
+            policy
+                .backup_request(&request, Some(Duration::from_millis(200)))
+                .is_none()
+        );
+        assert!(
+            policy
+                .backup_request(&request, Some(Duration::from_millis(100)))
+                .is_none()
+        );
+    }
+}
@@ -18,18 +18,37 @@ use crate::auth::{Authentication, Credentials};
 use curl::easy::Easy2;
 use std::{iter::FromIterator, net::IpAddr, time::Duration};
 
+pub(crate) mod curlrc;
 pub(crate) mod dial;
+pub(crate) mod digest;
 pub(crate) mod dns;
+pub(crate) mod fallback;
+pub(crate) mod headers;
+pub(crate) mod hedge;
 pub(crate) mod internal;
+pub(crate) mod profile;
+pub(crate) mod progress;
 pub(crate) mod proxy;
 pub(crate) mod redirect;
+pub(crate) mod retry;
 pub(crate) mod ssl;
+pub(crate) mod validate;
 
-pub use dial::{Dialer, DialerParseError};
+pub use dial::{Dial, DialTarget, Dialer, DialerParseError};
+pub use digest::DigestHeader;
 pub use dns::{DnsCache, ResolveMap};
-pub use redirect::RedirectPolicy;
+pub use fallback::VersionFallback;
+pub use headers::HeaderAction;
+pub use hedge::HedgePolicy;
+pub use profile::Profile;
+pub use progress::Progress;
+pub use redirect::{OriginChange, RedirectHeaderPolicy, RedirectPolicy};
+pub use retry::{ExponentialBackoff, Jitter, RetryAttempt, RetryPolicy, TotalTimeout};
 pub use ssl::{CaCertificate, ClientCertificate, PrivateKey, SslOption};
 
+#[cfg(feature = "tls-psk")]
+pub use ssl::PreSharedKey;
+
 /// Provides additional methods when building a request for configuring various
 /// execution-related options on how the request should be sent.
 ///
@@ -84,6 +103,146 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(ConnectTimeout(timeout))
     }
 
+    /// Set a maximum amount of time to wait for the server to start sending a
+    /// response.
+    ///
+    /// This limits the time to the first byte of the response headers, which
+    /// is useful for failing fast on servers that accept connections but are
+    /// too busy to process requests. If no response has begun to arrive
+    /// within the given duration, the request is aborted with an error of
+    /// kind [`HeadersTimeout`](crate::error::ErrorKind::HeadersTimeout).
+    /// Once the response starts to arrive, this timeout no longer applies.
+    ///
+    /// The timer starts when the request is started, so the time taken to
+    /// connect to the server and to send the request body is included. When
+    /// following redirects, each request made applies the timeout anew. The
+    /// timeout is checked about ten times per second, so it is not very
+    /// precise for very short durations.
+    ///
+    /// If not set, no headers timeout will be enforced.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// // Give up quickly if the server does not respond, but allow the
+    /// // response body to take as long as it needs.
+    /// let response = Request::get("https://example.org")
+    ///     .headers_timeout(Duration::from_secs(5))
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn headers_timeout(self, timeout: Duration) -> Self {
+        self.configure(HeadersTimeout(timeout))
+    }
+
+    /// Set a maximum amount of time to wait between receiving successive
+    /// chunks of the response body.
+    ///
+    /// Unlike [`Configurable::timeout`], this does not limit the total time a
+    /// request may take, so a large download may take as long as it needs to
+    /// as long as data keeps arriving. If the server stops sending data for
+    /// longer than the given duration, the request is aborted with an error of
+    /// kind [`ReadTimeout`](crate::error::ErrorKind::ReadTimeout).
+    ///
+    /// The timer starts once the response headers have been received, and is
+    /// paused while the response body is not being read, so a slow consumer
+    /// will not cause the timeout to expire. The timeout is checked about once
+    /// per second, so it is not very precise for very short durations.
+    ///
+    /// If not set, no read timeout will be enforced.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// // Allow the download to take as long as it needs, but give up if the
+    /// // server stalls for more than 30 seconds.
+    /// let response = Request::get("https://example.org/large-file.iso")
+    ///     .read_timeout(Duration::from_secs(30))
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn read_timeout(self, timeout: Duration) -> Self {
+        self.configure(ReadTimeout(timeout))
+    }
+
+    /// Set a maximum amount of time that a connected transfer may go without
+    /// sending or receiving any data.
+    ///
+    /// Unlike [`Configurable::read_timeout`], which only covers the response
+    /// body, this applies to every stage of the transfer once connected to the
+    /// server, including sending the request body and waiting for the
+    /// response. If no data is transferred in either direction for longer
+    /// than the given duration, the request is aborted with an error of kind
+    /// [`IdleTimeout`](crate::error::ErrorKind::IdleTimeout). Time spent
+    /// waiting on a slow response body reader does not count.
+    ///
+    /// This is enforced by curl, which considers a transfer idle once its
+    /// recent average transfer rate drops below one byte per second, so a
+    /// transfer that stalls right after making progress may take somewhat
+    /// longer to be aborted. Curl also only supports whole seconds, so the
+    /// duration is rounded up to the nearest second.
+    ///
+    /// If not set, no idle timeout will be enforced.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .idle_timeout(Duration::from_secs(30))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn idle_timeout(self, timeout: Duration) -> Self {
+        self.configure(IdleTimeout(timeout))
+    }
+
+    /// Specify a maximum amount of time a request may wait to be started.
+    ///
+    /// Requests may have to wait before they are sent, such as when the
+    /// connection limits set with
+    /// [`HttpClientBuilder::max_connections`](crate::HttpClientBuilder::max_connections)
+    /// or
+    /// [`HttpClientBuilder::max_connections_per_host`](crate::HttpClientBuilder::max_connections_per_host)
+    /// or the request limit set with
+    /// [`HttpClientBuilder::max_concurrent_requests`](crate::HttpClientBuilder::max_concurrent_requests)
+    /// have been reached, or when the client is very busy. If the request
+    /// is not started within the given duration, it fails with an error of
+    /// kind [`QueueTimeout`](crate::error::ErrorKind::QueueTimeout) instead
+    /// of spending its [`timeout`](Configurable::timeout) waiting.
+    ///
+    /// The wait ends once the client starts connecting to the server or picks
+    /// a connection to reuse. The timeout is checked about ten times per
+    /// second, so it is not very precise for very short durations.
+    ///
+    /// If not set, requests may wait indefinitely.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .max_connections_per_host(4)
+    ///     .queue_timeout(Duration::from_secs(2))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn queue_timeout(self, timeout: Duration) -> Self {
+        self.configure(QueueTimeout(timeout))
+    }
+
     /// Configure how the use of HTTP versions should be negotiated with the
     /// server.
     ///
@@ -109,6 +268,14 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(negotiation)
     }
 
+    /// Set whether to retry requests that fail over HTTP/2 using HTTP/1.1.
+    ///
+    /// See [`VersionFallback`] for details. The default is
+    /// [`VersionFallback::disabled`].
+    fn version_fallback(self, fallback: VersionFallback) -> Self {
+        self.configure(fallback)
+    }
+
     /// Set a policy for automatically following server redirects.
     ///
     /// The default is to not follow redirects.
@@ -142,6 +309,16 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(redirect::AutoReferer)
     }
 
+    /// Set a policy for which sensitive headers to remove when following a
+    /// redirect to a different origin.
+    ///
+    /// By default, the `Authorization` and `Cookie` headers are removed
+    /// whenever a redirect changes the scheme, host, or port of the request.
+    /// See [`RedirectHeaderPolicy`] for details.
+    fn redirect_header_policy(self, policy: RedirectHeaderPolicy) -> Self {
+        self.configure(policy)
+    }
+
     /// Set a cookie jar to use to accept, store, and supply cookies for
     /// incoming responses and outgoing requests.
     ///
@@ -157,6 +334,20 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(cookie_jar)
     }
 
+    /// Set an HSTS store to use to learn which hosts must only be reached over
+    /// HTTPS, and to upgrade requests to those hosts accordingly.
+    ///
+    /// An HSTS store can be shared across multiple requests or with an entire
+    /// client, allowing hosts to be remembered across requests. If different
+    /// stores are assigned on both a request and the client sending the
+    /// request, the one assigned to the individual request will take
+    /// precedence. See the [`hsts`](crate::hsts) module for details.
+    ///
+    /// By default, HSTS is disabled.
+    fn hsts(self, store: crate::hsts::HstsStore) -> Self {
+        self.configure(store)
+    }
+
     /// Enable or disable automatic decompression of the response body for
     /// various compression algorithms as returned by the server in the
     /// [`Content-Encoding`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Encoding)
@@ -176,6 +367,92 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(AutomaticDecompression(decompress))
     }
 
+    /// Enable or disable automatic decompression of the response body.
+    ///
+    /// This is a shorter name for
+    /// [`automatic_decompression`](Configurable::automatic_decompression).
+    /// Disabling decompression for a request is useful when the exact bytes
+    /// sent by the server are needed, which can be accessed using
+    /// [`ResponseExt::raw_body`](crate::ResponseExt::raw_body).
+    fn decompress(self, decompress: bool) -> Self {
+        self.automatic_decompression(decompress)
+    }
+
+    /// Set the content encodings advertised to the server in the
+    /// `Accept-Encoding` header, in order of preference.
+    ///
+    /// By default the header lists every encoding supported by the curl
+    /// library in use, which differs between builds and platforms. Since
+    /// caches and proxies store responses separately for each distinct
+    /// `Accept-Encoding` value, an explicit list gives them predictable
+    /// behavior. An empty list sends no `Accept-Encoding` header at all.
+    ///
+    /// Responses in any of the given encodings that curl supports are still
+    /// decompressed automatically, unless
+    /// [automatic decompression](Configurable::automatic_decompression) is
+    /// disabled. An `Accept-Encoding` header set on the request itself takes
+    /// precedence over this option.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let request = Request::get("https://example.org")
+    ///     .accept_encoding(&["gzip"])
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn accept_encoding(self, encodings: &[&str]) -> Self {
+        self.configure(AcceptEncoding(encodings.join(", ")))
+    }
+
+    /// Set the maximum size in bytes of a response body after automatic
+    /// decompression.
+    ///
+    /// A small compressed response can decompress into an enormous amount of
+    /// data, which could be used to exhaust the memory or disk space of a
+    /// service. If the decoded body grows past this size, the transfer is
+    /// aborted with a
+    /// [`DecompressionLimitExceeded`](crate::error::ErrorKind::DecompressionLimitExceeded)
+    /// error.
+    ///
+    /// This only applies to responses that are decompressed automatically;
+    /// see [`Configurable::automatic_decompression`]. If not set, the size of
+    /// decoded responses is not limited.
+    fn max_decompressed_size(self, max: u64) -> Self {
+        self.configure(MaxDecompressedSize(max))
+    }
+
+    /// Set the maximum ratio between the size of a response body after
+    /// automatic decompression and the number of compressed bytes received.
+    ///
+    /// If the ratio is exceeded, the transfer is aborted with a
+    /// [`DecompressionLimitExceeded`](crate::error::ErrorKind::DecompressionLimitExceeded)
+    /// error. Since small responses can legitimately compress very well, the
+    /// ratio is only checked once at least 1 MiB of decoded data has been
+    /// received.
+    ///
+    /// This only applies to responses that are decompressed automatically;
+    /// see [`Configurable::automatic_decompression`]. If not set, the ratio is
+    /// not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// // Guard against decompression bombs.
+    /// let client = HttpClient::builder()
+    ///     .max_decompressed_size(100 * 1024 * 1024)
+    ///     .max_decompression_ratio(100.0)
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn max_decompression_ratio(self, ratio: f64) -> Self {
+        self.configure(MaxDecompressionRatio(ratio))
+    }
+
     /// Set one or more default HTTP authentication methods to attempt to use
     /// when authenticating with the server.
     ///
@@ -217,6 +494,60 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(TcpNoDelay)
     }
 
+    /// Check that cached HTTP/2 connections are still alive by sending `PING`
+    /// frames on them.
+    ///
+    /// A server or something on the network path can stop responding without
+    /// closing the connection, leaving a connection in the connection cache
+    /// that requests would be written into without ever getting a response.
+    /// With this option, a `PING` frame is sent on each cached HTTP/2
+    /// connection that has been quiet for the given interval, and connections
+    /// that the ping cannot be sent on are closed. Pings are sent while the
+    /// client has requests with this option in progress. Some versions of
+    /// libcurl only flush them along with the next data sent on the
+    /// connection.
+    ///
+    /// Curl does not report whether pings are answered, so in addition, a
+    /// cached connection that has been idle for longer than the interval plus
+    /// the given timeout is not reused, as it cannot be known to still work.
+    /// This replaces the time-to-live set with
+    /// [`HttpClientBuilder::connection_cache_ttl`](crate::HttpClientBuilder::connection_cache_ttl),
+    /// unless that is set as well. Connections using HTTP/1.x are only
+    /// affected by the latter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::VersionNegotiation, prelude::*, HttpClient};
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .version_negotiation(VersionNegotiation::http2())
+    ///     .http2_keepalive(Duration::from_secs(30), Duration::from_secs(10))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn http2_keepalive(self, interval: Duration, timeout: Duration) -> Self {
+        self.configure(Http2Keepalive {
+            interval,
+            timeout,
+        })
+    }
+
+    /// Send a [PROXY protocol](https://www.haproxy.org/download/2.3/doc/proxy-protocol.txt)
+    /// header at the start of each new connection.
+    ///
+    /// This is needed when connecting directly to a server that sits behind
+    /// HAProxy or another load balancer expecting the PROXY protocol, which
+    /// uses the header to learn the original client and server addresses of
+    /// the connection. Curl sends the human-readable version 1 of the header.
+    ///
+    /// This should not be enabled for servers that do not expect the header, as
+    /// they will reject the connection. The default is disabled.
+    fn haproxy_protocol(self, enable: bool) -> Self {
+        self.configure(HaproxyProtocol(enable))
+    }
+
     /// Bind local socket connections to a particular network interface.
     ///
     /// # Examples
@@ -303,6 +634,90 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(dialer.into())
     }
 
+    /// Connect to the given target host and port whenever a connection to the
+    /// given original host and port is needed, without changing the request.
+    ///
+    /// The request URI, the `Host` header and the name used for TLS server
+    /// name indication and certificate verification all stay the same, so
+    /// the target server sees the request as if it had been sent to the
+    /// original host. This makes it possible to send requests to a specific
+    /// backend instance behind a load balancer, such as when testing a new
+    /// deployment before switching traffic over to it. Requests to any other
+    /// host or port, for example after a redirect, are not affected.
+    ///
+    /// Only one mapping can be configured at a time, and a mapping set on a
+    /// request replaces the one set on the client. A custom
+    /// [`dial`](Configurable::dial) address takes precedence over the
+    /// mapping. This is equivalent to the `--connect-to` option of the curl
+    /// command line tool.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// // Check the green deployment before it goes live.
+    /// let response = Request::get("https://api.example.org/health")
+    ///     .connect_to("api.example.org", 443, "green.internal.example.org", 443)
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn connect_to(
+        self,
+        host: impl AsRef<str>,
+        port: u16,
+        target_host: impl AsRef<str>,
+        target_port: u16,
+    ) -> Self {
+        self.configure(dial::ConnectTo::new(
+            host.as_ref(),
+            port,
+            target_host.as_ref(),
+            target_port,
+        ))
+    }
+
+    /// Set a mapping of DNS resolve overrides.
+    ///
+    /// Entries in the given map will be used first before using the default DNS
+    /// resolver for host+port pairs. Unlike [`connect_to`](Configurable::connect_to),
+    /// which routes connections without regard to DNS, this makes the
+    /// connection go to the given addresses as if the name resolver had
+    /// returned them, trying each of them in turn. A map set on a request
+    /// replaces the one set on the client.
+    ///
+    /// Note that DNS resolving is only performed when establishing a new
+    /// connection. Curl keeps overrides in the DNS cache of the agent serving
+    /// the request, so requests with overrides other than those of the client
+    /// are served by a separate agent with a connection pool of its own for
+    /// each distinct map. Overrides are not kept apart from other clients
+    /// sharing a DNS cache through a [`ShareHandle`](crate::ShareHandle).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use isahc::config::ResolveMap;
+    /// # use isahc::prelude::*;
+    /// # use std::net::IpAddr;
+    /// #
+    /// let client = HttpClient::builder()
+    ///     .dns_resolve(ResolveMap::new()
+    ///         // Send requests for example.org on port 80 to 127.0.0.1.
+    ///         .add("example.org", 80, [127, 0, 0, 1]))
+    ///     .build()?;
+    ///
+    /// // Try out the blue deployment of a service without editing /etc/hosts.
+    /// let request = Request::get("https://api.example.org/health")
+    ///     .dns_resolve(ResolveMap::new()
+    ///         .add_all("api.example.org", 443, vec![[192, 0, 2, 10], [192, 0, 2, 11]]))
+    ///     .body(())?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn dns_resolve(self, map: ResolveMap) -> Self {
+        self.configure(map)
+    }
+
     /// Set a proxy to use for requests.
     ///
     /// The proxy protocol is specified by the URI scheme.
@@ -376,6 +791,10 @@ pub trait Configurable: internal::ConfigurableBase {
     /// set credentials to use for authentication using
     /// [`Configurable::proxy_credentials`].
     ///
+    /// Corporate proxies often require Kerberos or NTLM authentication, which
+    /// are available with the [`spnego`](../index.html#spnego) and
+    /// [`ntlm`](../index.html#ntlm) features respectively.
+    ///
     /// # Examples
     ///
     /// ```
@@ -402,6 +821,33 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(proxy::Proxy(credentials))
     }
 
+    /// Set additional headers to send to the proxy.
+    ///
+    /// The headers are sent in the `CONNECT` request used to open a tunnel
+    /// through the proxy, such as for HTTPS requests, and along with requests
+    /// that the proxy forwards itself. They are never sent to the server
+    /// behind the proxy. This is useful for proxies that expect custom
+    /// headers, for example to select an upstream or to identify the client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use http::{header::HeaderValue, HeaderMap};
+    /// use isahc::prelude::*;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("x-proxy-token", HeaderValue::from_static("abc123"));
+    ///
+    /// let client = HttpClient::builder()
+    ///     .proxy("http://proxy:80".parse::<http::Uri>()?)
+    ///     .proxy_headers(headers)
+    ///     .build()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn proxy_headers(self, headers: http::HeaderMap) -> Self {
+        self.configure(proxy::Proxy(headers))
+    }
+
     /// Set a maximum upload speed for the request body, in bytes per second.
     ///
     /// The default is unlimited.
@@ -456,6 +902,38 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(certificate)
     }
 
+    /// Select the OpenSSL engine, or the OpenSSL 3 provider, to use for
+    /// private keys that are kept in hardware such as an HSM or TPM.
+    ///
+    /// This is used together with [`PrivateKey::engine`] or
+    /// [`PrivateKey::provider`] to authenticate with a client certificate
+    /// without ever exporting its private key. The engine or provider must be
+    /// installed and configured in OpenSSL, for example using the
+    /// `OPENSSL_CONF` environment variable.
+    ///
+    /// If the engine cannot be found or initialized, requests fail with a
+    /// [`TlsEngine`](crate::error::ErrorKind::TlsEngine) error. This is only
+    /// supported if libcurl uses OpenSSL.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use isahc::config::*;
+    /// # use isahc::prelude::*;
+    /// #
+    /// let client = HttpClient::builder()
+    ///     .ssl_engine("pkcs11")
+    ///     .ssl_client_certificate(ClientCertificate::pem_file(
+    ///         "client.pem",
+    ///         PrivateKey::engine("pkcs11:token=device;object=client-key", String::from("1234")),
+    ///     ))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn ssl_engine(self, name: impl Into<String>) -> Self {
+        self.configure(ssl::Engine(name.into()))
+    }
+
     /// Set a custom SSL/TLS CA certificate bundle to use for client
     /// connections.
     ///
@@ -482,6 +960,46 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(certificate)
     }
 
+    /// Authenticate TLS connections using a pre-shared key (PSK) instead of
+    /// certificates.
+    ///
+    /// This is commonly used by devices for which provisioning certificates is
+    /// impractical. The server is authenticated by proving knowledge of the
+    /// same key, so certificate verification is disabled for the request and
+    /// only PSK cipher suites with TLS 1.2 or older are offered, which ensures
+    /// that the handshake cannot succeed without the key. Any ciphers set with
+    /// [`Configurable::ssl_ciphers`] are ignored.
+    ///
+    /// Connections are matched for reuse based on their TLS settings, but not
+    /// the identity used. If requests to the same server use different
+    /// identities, use [`Configurable::connection_pool_partition`] to keep
+    /// their connections separate.
+    ///
+    /// Sending a request with this option fails with a
+    /// [`TlsEngine`](crate::error::ErrorKind::TlsEngine) error unless libcurl
+    /// uses OpenSSL as its TLS engine.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the [`tls-psk`](index.html#tls-psk)
+    /// feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::PreSharedKey, prelude::*};
+    ///
+    /// let response = Request::get("https://device-gateway.example.org")
+    ///     .ssl_pre_shared_key(PreSharedKey::new("device-42", b"secret".to_vec()))
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[cfg(feature = "tls-psk")]
+    fn ssl_pre_shared_key(self, key: PreSharedKey) -> Self {
+        self.configure(key)
+    }
+
     /// Set a list of ciphers to use for SSL/TLS connections.
     ///
     /// The list of valid cipher names is dependent on the underlying SSL/TLS
@@ -537,6 +1055,40 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(options)
     }
 
+    /// Present the given name to the server and verify its certificate
+    /// against it during the TLS handshake, instead of using the host of the
+    /// request URI.
+    ///
+    /// This is useful when connecting to a server by IP address, or through
+    /// an address that is not listed in its certificate, if the server routes
+    /// connections based on the server name indication (SNI) or only has a
+    /// certificate for its DNS name. The connection is still made to the host
+    /// and port of the request URI, while the certificate is checked as if
+    /// the request had been sent to the given name. This is equivalent to the
+    /// `--connect-to` option of the curl command line tool.
+    ///
+    /// The name is also sent in the `Host` header, unless the header is set
+    /// explicitly, and the [effective URI](crate::ResponseExt::effective_uri)
+    /// of the response uses it. Requests whose name is not a valid host fail
+    /// with an [`InvalidRequest`](crate::error::ErrorKind::InvalidRequest)
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// // Talk to a specific backend, verifying it as api.example.org.
+    /// let response = Request::get("https://192.0.2.10/status")
+    ///     .tls_server_name("api.example.org")
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn tls_server_name(self, name: impl Into<String>) -> Self {
+        self.configure(ssl::TlsServerName(name.into()))
+    }
+
     /// Enable or disable sending HTTP header names in Title-Case instead of
     /// lowercase form.
     ///
@@ -551,32 +1103,597 @@ pub trait Configurable: internal::ConfigurableBase {
         self.configure(TitleCaseHeaders(enable))
     }
 
-    /// Enable or disable comprehensive per-request metrics collection.
+    /// Set the exact order in which request headers should be sent.
     ///
-    /// When enabled, detailed timing metrics will be tracked while a request is
-    /// in progress, such as bytes sent and received, estimated size, DNS lookup
-    /// time, etc. For a complete list of the available metrics that can be
-    /// inspected, see the [`Metrics`](crate::Metrics) documentation.
+    /// Headers named in the given list are sent first, in the order given.
+    /// Any other headers on the request are sent afterwards in their usual
+    /// order. Header names are matched case-insensitively.
     ///
-    /// When enabled, to access a view of the current metrics values you can use
-    /// [`ResponseExt::metrics`](crate::ResponseExt::metrics).
+    /// Some servers, firewalls, and test fixtures are sensitive to the order
+    /// of request headers. Since the goal of this option is precise control
+    /// over what is sent, setting an order also prevents curl from adding its
+    /// default `Accept` header; set an `Accept` header on the request yourself
+    /// if you still want one to be sent.
     ///
-    /// While effort is taken to optimize hot code in metrics collection, it is
-    /// likely that enabling it will have a small effect on overall throughput.
-    /// Disabling metrics may be necessary for absolute peak performance.
+    /// Low-level headers that curl generates itself, such as `Host`, are
+    /// always sent before any other headers unless you set them explicitly on
+    /// the request.
     ///
-    /// By default metrics are disabled.
-    fn metrics(self, enable: bool) -> Self {
-        self.configure(EnableMetrics(enable))
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let request = Request::get("https://example.org")
+    ///     .header("Host", "example.org")
+    ///     .header("Accept", "text/html")
+    ///     .header("User-Agent", "my-agent")
+    ///     .header_order(vec!["host", "user-agent", "accept"])
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn header_order<I, T>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.configure(HeaderOrder::from_iter(names.into_iter().map(T::into)))
     }
-}
 
-/// A strategy for selecting what HTTP versions should be used when
-/// communicating with a server.
-///
-/// You can set a version negotiation strategy on a given request or on a client
-/// with [`Configurable::version_negotiation`].
-///
+    /// Apply a client identity [`Profile`], which sets a user agent, default
+    /// headers, TLS options, and HTTP version preferences all at once.
+    ///
+    /// Settings in the profile replace any corresponding settings configured
+    /// before this call, and can be overridden again by calling the individual
+    /// configuration methods afterwards. Headers set explicitly on a request
+    /// always take precedence over headers in the profile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::Profile, prelude::*};
+    ///
+    /// let request = Request::get("https://example.org")
+    ///     .profile(Profile::browser())
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn profile(self, profile: Profile) -> Self {
+        let mut this = self;
+
+        if let Some(negotiation) = profile.version_negotiation.clone() {
+            this = this.configure(negotiation);
+        }
+
+        if let Some(ciphers) = profile.ssl_ciphers.clone() {
+            this = this.configure(ciphers);
+        }
+
+        if let Some(options) = profile.ssl_options {
+            this = this.configure(options);
+        }
+
+        this.configure(profile)
+    }
+
+    /// Enable or disable comprehensive per-request metrics collection.
+    ///
+    /// When enabled, detailed timing metrics will be tracked while a request is
+    /// in progress, such as bytes sent and received, estimated size, DNS lookup
+    /// time, etc. For a complete list of the available metrics that can be
+    /// inspected, see the [`Metrics`](crate::Metrics) documentation.
+    ///
+    /// When enabled, to access a view of the current metrics values you can use
+    /// [`ResponseExt::metrics`](crate::ResponseExt::metrics).
+    ///
+    /// While effort is taken to optimize hot code in metrics collection, it is
+    /// likely that enabling it will have a small effect on overall throughput.
+    /// Disabling metrics may be necessary for absolute peak performance.
+    ///
+    /// By default metrics are disabled.
+    fn metrics(self, enable: bool) -> Self {
+        self.configure(EnableMetrics(enable))
+    }
+
+    /// Enable or disable collection of a report describing how the connection
+    /// for each request was established.
+    ///
+    /// When enabled, the report can be accessed using
+    /// [`ResponseExt::connection_report`](crate::ResponseExt::connection_report).
+    /// See the [`ConnectionReport`](crate::ConnectionReport) documentation for
+    /// the details that are collected.
+    ///
+    /// Collecting a report requires curl to produce diagnostic messages for
+    /// the transfer, which adds a small amount of overhead.
+    ///
+    /// By default connection reports are disabled.
+    fn connection_report(self, enable: bool) -> Self {
+        self.configure(EnableConnectionReport(enable))
+    }
+
+    /// Enable or disable automatically retrying a request once if it fails on
+    /// a stale connection.
+    ///
+    /// Connections kept in the connection cache may be closed by the server at
+    /// any time while idle. When a request is sent over such a connection, it
+    /// usually fails with a connection reset or without any response. If this
+    /// option is enabled and a request fails on a reused connection before the
+    /// response headers are received in full, it is sent once more on a new
+    /// connection.
+    ///
+    /// A failure like this does not prove that the server never received the
+    /// request, as the connection may also have been closed while the server
+    /// was processing it. Only requests with an idempotent method, such as
+    /// `GET` or `PUT`, are therefore retried. Requests with a streaming body
+    /// that cannot be rewound are never retried either.
+    ///
+    /// Independently of this option, curl itself sends any request once more
+    /// on a new connection if a reused connection fails before a complete line
+    /// of the response is received on it.
+    ///
+    /// This option is enabled by default.
+    fn retry_stale_connections(self, enable: bool) -> Self {
+        self.configure(RetryStaleConnection(enable))
+    }
+
+    /// Configure whether connections may be reused from, or returned to, the
+    /// connection cache.
+    ///
+    /// This is most useful on individual requests, for example to make sure
+    /// that a request sent with different credentials does not share a
+    /// connection with any other request.
+    ///
+    /// The default is [`ReusePolicy::Reuse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::config::ReusePolicy;
+    /// use isahc::prelude::*;
+    ///
+    /// // Use a connection for this request only, and close it afterwards.
+    /// let request = Request::get("https://example.org")
+    ///     .connection_reuse(ReusePolicy::Isolated)
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn connection_reuse(self, policy: ReusePolicy) -> Self {
+        self.configure(policy)
+    }
+
+    /// Partition the connection pool by the given key, so that connections are
+    /// only ever shared between requests with the same key.
+    ///
+    /// Some authentication schemes, such as TLS client certificates or NTLM,
+    /// authenticate the connection rather than individual requests. When
+    /// requests are sent on behalf of different users, setting a key that
+    /// identifies the user, such as a user ID or a hash of their token,
+    /// ensures that a connection authenticated as one user is never reused for
+    /// a request made by another.
+    ///
+    /// Each partition keeps its own connections, DNS cache, and TLS sessions,
+    /// created the first time the key is used, in place of any shared using
+    /// [`HttpClientBuilder::share`](crate::HttpClientBuilder::share). Limits
+    /// such as
+    /// [`HttpClientBuilder::max_connections`](crate::HttpClientBuilder::max_connections)
+    /// apply to all partitions together. Only the most recently used
+    /// partitions are kept, so requests with a key that has not been used for
+    /// a while may have to open a new connection.
+    ///
+    /// Requests without a partition key use the default connection pool.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let request = Request::get("https://example.org")
+    ///     .connection_pool_partition("user-1234")
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn connection_pool_partition(self, key: impl Into<String>) -> Self {
+        self.configure(PoolPartition(key.into()))
+    }
+
+    /// Configure what happens to a request when its response future is
+    /// dropped before the response is received.
+    ///
+    /// By default, the request is aborted as soon as the agent notices that
+    /// nobody is waiting for the response anymore. For fire-and-forget
+    /// requests, such as sending a report or a webhook, it is often preferable
+    /// to let the request complete in the background instead, which can be
+    /// done with [`CancelPolicy::Detach`].
+    ///
+    /// Note that futures do nothing until they are polled, so a request is
+    /// only sent if its future has been polled at least once before being
+    /// dropped.
+    ///
+    /// The default is [`CancelPolicy::Abort`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::config::CancelPolicy;
+    /// use isahc::prelude::*;
+    ///
+    /// let request = Request::post("https://example.org/events")
+    ///     .cancel_policy(CancelPolicy::Detach)
+    ///     .body("something happened")?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn cancel_policy(self, policy: CancelPolicy) -> Self {
+        self.configure(policy)
+    }
+
+    /// Send a backup copy of the request if it is slow to receive a response,
+    /// and use whichever response arrives first.
+    ///
+    /// This is disabled by default. See [`HedgePolicy`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::HedgePolicy, prelude::*, HttpClient};
+    /// use std::time::Duration;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .hedge(HedgePolicy::after(Duration::from_millis(250)))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn hedge(self, policy: HedgePolicy) -> Self {
+        self.configure(policy)
+    }
+
+    /// Automatically send a request again if it fails in a way that the given
+    /// policy deems worth retrying, such as a connection being reset or the
+    /// server being temporarily unavailable.
+    ///
+    /// This is disabled by default. See [`RetryPolicy`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::ExponentialBackoff, prelude::*, HttpClient};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .retry_policy(ExponentialBackoff::new(3))
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn retry_policy(self, policy: impl RetryPolicy + 'static) -> Self {
+        self.configure(retry::Retry(std::sync::Arc::new(policy)))
+    }
+
+    /// Set which responses contribute headers to the response returned for a
+    /// request.
+    ///
+    /// By default, only the headers of the final response are returned. See
+    /// [`HeaderMergePolicy`] for the other options and how headers are merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::config::{HeaderMergePolicy, RedirectPolicy};
+    /// use isahc::prelude::*;
+    ///
+    /// // Collect cookies set by every redirect along the way.
+    /// let request = Request::get("https://example.org/login")
+    ///     .redirect_policy(RedirectPolicy::Follow)
+    ///     .header_merge_policy(HeaderMergePolicy::All)
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn header_merge_policy(self, policy: HeaderMergePolicy) -> Self {
+        self.configure(policy)
+    }
+
+    /// Set a callback that decides what to do with a response once its
+    /// headers have been received, before any of its body is read.
+    ///
+    /// This allows rejecting a response early, such as one with an unexpected
+    /// content type or one that is too large, without downloading its body.
+    /// See [`HeaderAction`] for what the callback can do. If the request is
+    /// aborted, it fails with an error of kind
+    /// [`ResponseRejected`](crate::error::ErrorKind::ResponseRejected).
+    ///
+    /// The callback is only invoked for the final response of a request, and
+    /// not for informational responses or redirects that are followed. It is
+    /// invoked on the thread sending requests for the client, so it should
+    /// return quickly.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::HeaderAction, prelude::*};
+    ///
+    /// // Refuse to download anything larger than 10 MB.
+    /// let response = Request::get("https://example.org/large-file.iso")
+    ///     .on_headers(|parts| {
+    ///         let len = parts
+    ///             .headers
+    ///             .get("content-length")
+    ///             .and_then(|value| value.to_str().ok())
+    ///             .and_then(|value| value.parse::<u64>().ok());
+    ///
+    ///         match len {
+    ///             Some(len) if len > 10_000_000 => HeaderAction::Abort,
+    ///             _ => HeaderAction::Accept,
+    ///         }
+    ///     })
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn on_headers<F>(self, callback: F) -> Self
+    where
+        F: Fn(&http::response::Parts) -> HeaderAction + Send + Sync + 'static,
+    {
+        self.configure(headers::OnHeaders(std::sync::Arc::new(callback)))
+    }
+
+    /// Set a callback to be invoked with the upload and download progress of
+    /// each request.
+    ///
+    /// The callback is invoked whenever more of the request body has been
+    /// sent or more of the response body has been received, for both
+    /// synchronous and asynchronous requests, without having to poll
+    /// [`ResponseExt::metrics`](crate::ResponseExt::metrics). If a request is
+    /// redirected or retried, the progress starts over for each new attempt.
+    ///
+    /// The callback is invoked on the thread sending requests for the client,
+    /// so it should return quickly.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = Request::get("https://example.org/large-file.iso")
+    ///     .progress_callback(|progress| {
+    ///         if let Some(total) = progress.download_total() {
+    ///             println!("downloaded {} of {} bytes", progress.downloaded(), total);
+    ///         }
+    ///     })
+    ///     .body(())?
+    ///     .send()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn progress_callback<F>(self, callback: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.configure(progress::OnProgress(std::sync::Arc::new(callback)))
+    }
+
+    /// Send a SHA-256 digest of the request body in the given header, as
+    /// described in [RFC 9530](https://www.rfc-editor.org/rfc/rfc9530), so
+    /// that the server can check that the body arrived intact.
+    ///
+    /// The digest of a body held in memory is sent as a regular header. Since
+    /// the digest of a streaming body is only known once all of it has been
+    /// sent, it is sent in a trailer after the body instead, which requires a
+    /// body of unknown length sent using chunked encoding. No digest is sent
+    /// for other streaming bodies, such as files, or if the request already
+    /// has the header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::DigestHeader, prelude::*};
+    ///
+    /// let request = Request::put("https://example.org/upload")
+    ///     .send_digest(DigestHeader::Content)
+    ///     .body("hello world")?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn send_digest(self, header: DigestHeader) -> Self {
+        self.configure(digest::SendDigest(header))
+    }
+
+    /// Enable or disable verifying the body of a response against the
+    /// SHA-256 digest in its `Content-Digest` or `Repr-Digest` header, if it
+    /// has one, as described in [RFC 9530](https://www.rfc-editor.org/rfc/rfc9530).
+    ///
+    /// The digest can only be checked once the whole body has been received,
+    /// so a mismatch is reported as an error of kind
+    /// [`DigestMismatch`](crate::error::ErrorKind::DigestMismatch) when
+    /// reaching the end of the body. Digests sent in trailers after the body
+    /// are checked as well.
+    ///
+    /// Digests using algorithms other than SHA-256 are ignored. Digests of
+    /// responses that are decompressed automatically are not checked either,
+    /// since they describe the body as it was before decompression.
+    ///
+    /// This is disabled by default.
+    fn verify_digest(self, enable: bool) -> Self {
+        self.configure(digest::VerifyDigest(enable))
+    }
+
+    /// Set the maximum number of bytes of the response body to include in an
+    /// error returned for an error status code.
+    ///
+    /// When checking a response with
+    /// [`ReadResponseExt::error_for_status`](crate::ReadResponseExt::error_for_status)
+    /// or its asynchronous equivalent, the start of the response body is
+    /// decoded into text and included in the returned error, since APIs
+    /// usually describe errors in the body. See
+    /// [`Error::response_body`](crate::Error::response_body).
+    ///
+    /// The default limit is 4 KiB. Setting the limit to zero excludes the
+    /// body entirely.
+    fn error_body_limit(self, limit: usize) -> Self {
+        self.configure(ErrorBodyLimit(limit))
+    }
+
+    /// Buffer response body data that is not yet read in a temporary file
+    /// once more than the given number of bytes are buffered in memory.
+    ///
+    /// Normally when the response body is read slower than it is received, the
+    /// transfer is paused until the reader catches up, which keeps memory usage
+    /// bounded. With this option the transfer is never paused. Up to
+    /// `threshold` bytes are buffered in memory, and any further data is
+    /// written to an anonymous temporary file created in `dir` until the reader
+    /// catches up. This keeps the connection busy and lets the server finish
+    /// sending as fast as possible, which can be useful when processing very
+    /// large responses in a single pass.
+    ///
+    /// The temporary file is deleted automatically once the response body is
+    /// dropped. If writing to it fails, the transfer is aborted and the error
+    /// is returned when reading the response body.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`spill-to-disk`](index.html#spill-to-disk) feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::Configurable, HttpClient};
+    ///
+    /// let client = HttpClient::builder()
+    ///     // Keep up to 16 MiB in memory before spilling to disk.
+    ///     .spill_to_disk(16 * 1024 * 1024, std::env::temp_dir())
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[cfg(feature = "spill-to-disk")]
+    fn spill_to_disk(self, threshold: usize, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.configure(SpillToDisk {
+            threshold,
+            dir: dir.into(),
+        })
+    }
+
+    /// Enable or disable strict validation of requests.
+    ///
+    /// Every request is validated before it is sent. Control characters such as
+    /// line breaks in the method, URI, or header values are always rejected, as
+    /// they could otherwise be used to inject additional headers into the
+    /// request. Header values must also be ASCII.
+    ///
+    /// Some characters that are not allowed in a URI by [RFC
+    /// 3986](https://tools.ietf.org/html/rfc3986), such as `{`, `|`, or `"`,
+    /// are accepted by [`http::Uri`]. By default these are percent-encoded
+    /// before the request is sent. If strict validation is enabled, requests
+    /// containing them are rejected instead.
+    ///
+    /// A request that fails validation returns an error of kind
+    /// [`InvalidRequest`](crate::error::ErrorKind::InvalidRequest), with a
+    /// [`RequestValidationError`](crate::error::RequestValidationError) as its
+    /// source identifying the offending part of the request.
+    ///
+    /// The default is disabled.
+    fn strict_validation(self, strict: bool) -> Self {
+        self.configure(StrictValidation(strict))
+    }
+
+    /// Set the maximum size of the request headers, counted the way HTTP/2
+    /// limits them with `SETTINGS_MAX_HEADER_LIST_SIZE`: the length of each
+    /// header name and value plus 32 bytes per header, including the
+    /// pseudo-headers for the method, scheme, authority and path.
+    ///
+    /// Servers reject requests with headers larger than they allow, which can
+    /// easily happen when sending large tokens such as JWTs. Some respond
+    /// with a `431 Request Header Fields Too Large` status, while others reset
+    /// the HTTP/2 stream or close the connection, which is hard to tell apart
+    /// from other failures. Setting this to the limit of the server makes
+    /// oversized requests fail right away with a
+    /// [`HeadersTooLarge`](crate::error::ErrorKind::HeadersTooLarge) error
+    /// instead, without being sent.
+    ///
+    /// This limit is only enforced by the client. libcurl neither advertises
+    /// it to the server nor reports the limit advertised by the server, so it
+    /// does not affect what the server accepts.
+    ///
+    /// The default is unlimited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .max_header_list_size(16 * 1024)
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn max_header_list_size(self, size: usize) -> Self {
+        self.configure(MaxHeaderListSize(size))
+    }
+
+    /// Set the maximum length of the request URI, in bytes.
+    ///
+    /// Servers reject requests whose URI is longer than they allow, usually
+    /// with a `414 URI Too Long` status, and many limit the request line to
+    /// around 8 KiB. Such long URIs are rarely intended, and are usually the
+    /// result of a query string that grew out of control, such as by appending
+    /// parameters in a loop. Setting this limit makes such requests fail right
+    /// away with a [`UriTooLong`](crate::error::ErrorKind::UriTooLong) error
+    /// instead, without being sent.
+    ///
+    /// The length of the complete URI is checked, including the scheme and
+    /// authority, after any characters are percent-encoded as described in
+    /// [`Configurable::strict_validation`]. The size of the request headers
+    /// can be limited as well using [`Configurable::max_header_list_size`].
+    ///
+    /// The default is unlimited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{error::ErrorKind, prelude::*};
+    ///
+    /// let client = HttpClient::builder()
+    ///     .max_uri_length(2048)
+    ///     .build()?;
+    ///
+    /// let uri = format!("https://example.org/search?q={}", "a".repeat(2048));
+    /// let error = client.get(uri).unwrap_err();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::UriTooLong);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn max_uri_length(self, length: usize) -> Self {
+        self.configure(MaxUriLength(length))
+    }
+
+    /// Set a fixed size for the buffer that response bodies are received
+    /// into, in bytes.
+    ///
+    /// By default the buffer is sized dynamically, starting at 16 KiB. It
+    /// grows after bulk downloads that fill it as fast as it can be read, up
+    /// to 256 KiB, and shrinks after responses that trickle in, down to 4 KiB.
+    /// This keeps the chunks of slow responses held in memory small without
+    /// slowing down large downloads. Since curl can only change the size
+    /// between transfers, a new size applies to the next request that reuses
+    /// the same internal curl handle, not to the response it was measured on.
+    ///
+    /// libcurl accepts sizes between 1 KiB and 10 MiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let client = HttpClient::builder()
+    ///     .response_buffer_size(256 * 1024)
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn response_buffer_size(self, size: usize) -> Self {
+        self.configure(ResponseBufferSize(size))
+    }
+}
+
+/// A strategy for selecting what HTTP versions should be used when
+/// communicating with a server.
+///
+/// You can set a version negotiation strategy on a given request or on a client
+/// with [`Configurable::version_negotiation`].
+///
 /// Attempting to use an HTTP version without client-side support at runtime
 /// will result in an error. For example, using the system libcurl on an old
 /// machine may not have an HTTP/2 implementation. Using static linking and the
@@ -619,6 +1736,17 @@ impl VersionNegotiation {
     }
 
     /// Connect via HTTP/1.0 and do not attempt to use a higher version.
+    ///
+    /// This is useful for talking to old servers, such as those of embedded
+    /// devices, that do not understand HTTP/1.1. Responses whose end is
+    /// signaled by the server closing the connection are read until the
+    /// connection is closed, and connections are not reused unless the server
+    /// asks for it. Since HTTP/1.0 has no chunked transfer encoding, request
+    /// bodies of unknown length are read into memory before the request is
+    /// sent, so that their length can be sent along. Such bodies are limited
+    /// to 16 MiB, and sending a larger one fails with an error of kind
+    /// [`InvalidRequest`](crate::error::ErrorKind::InvalidRequest); give
+    /// larger bodies a known length instead.
     pub const fn http10() -> Self {
         Self {
             flag: curl::easy::HttpVersion::V10,
@@ -658,6 +1786,50 @@ impl VersionNegotiation {
             strict: true,
         }
     }
+
+    /// Check whether this strategy asks for HTTP/1.0.
+    pub(crate) fn is_http10(&self) -> bool {
+        match self.flag {
+            curl::easy::HttpVersion::V10 => true,
+            _ => false,
+        }
+    }
+
+    /// Check whether this strategy asks for a specific HTTP version, rather
+    /// than leaving the choice to negotiation with the server.
+    pub(crate) fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Get the name of the curl command line option that selects this
+    /// strategy, if it is not the default.
+    pub(crate) fn curl_option(&self) -> Option<&'static str> {
+        match self.flag {
+            curl::easy::HttpVersion::V10 => Some("http1.0"),
+            curl::easy::HttpVersion::V11 => Some("http1.1"),
+            curl::easy::HttpVersion::V2PriorKnowledge => Some("http2-prior-knowledge"),
+            curl::easy::HttpVersion::V3 => Some("http3"),
+            _ => None,
+        }
+    }
+
+    /// Get a description of why this strategy cannot be used with the given
+    /// libcurl, if it cannot.
+    pub(crate) fn unsupported_reason(&self, version: &curl::Version) -> Option<&'static str> {
+        if !self.strict {
+            return None;
+        }
+
+        match self.flag {
+            curl::easy::HttpVersion::V2PriorKnowledge if !version.feature_http2() => {
+                Some("HTTP/2 was requested, but libcurl was built without HTTP/2 support")
+            }
+            curl::easy::HttpVersion::V3 if !version.feature_http3() => {
+                Some("HTTP/3 was requested, but libcurl was built without HTTP/3 support")
+            }
+            _ => None,
+        }
+    }
 }
 
 impl SetOpt for VersionNegotiation {
@@ -779,6 +1951,46 @@ impl SetOpt for ConnectTimeout {
     }
 }
 
+/// Maximum time to wait for the first byte of the response. This is enforced
+/// by the agent rather than by curl.
+#[derive(Clone, Debug)]
+pub(crate) struct HeadersTimeout(pub(crate) Duration);
+
+/// Maximum time to wait between chunks of the response body. This is enforced
+/// by the request handler rather than by curl.
+#[derive(Clone, Debug)]
+pub(crate) struct ReadTimeout(pub(crate) Duration);
+
+/// Maximum time a transfer may go without transferring any data. Curl enforces
+/// this as a low speed limit of one byte per second.
+#[derive(Clone, Debug)]
+pub(crate) struct IdleTimeout(pub(crate) Duration);
+
+impl IdleTimeout {
+    /// Get the timeout in whole seconds as required by curl, rounding up.
+    pub(crate) fn secs(&self) -> u64 {
+        let secs = self.0.as_secs();
+
+        if self.0.subsec_nanos() > 0 {
+            secs + 1
+        } else {
+            secs
+        }
+    }
+}
+
+impl SetOpt for IdleTimeout {
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        easy.low_speed_limit(1)?;
+        easy.low_speed_time(Duration::from_secs(self.secs()))
+    }
+}
+
+/// Maximum time to wait for a request to be started. This is enforced by the
+/// agent rather than by curl.
+#[derive(Clone, Debug)]
+pub(crate) struct QueueTimeout(pub(crate) Duration);
+
 #[derive(Clone, Debug)]
 pub(crate) struct TcpKeepAlive(pub(crate) Duration);
 
@@ -798,6 +2010,56 @@ impl SetOpt for TcpNoDelay {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HaproxyProtocol(pub(crate) bool);
+
+impl SetOpt for HaproxyProtocol {
+    #[allow(unsafe_code)]
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        // Not yet defined by curl-sys.
+        const CURLOPT_HAPROXYPROTOCOL: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 274;
+
+        let value = self.0 as std::os::raw::c_long;
+
+        // Use unsafe API, because the safe API doesn't expose this option.
+        unsafe {
+            match curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_HAPROXYPROTOCOL, value) {
+                curl_sys::CURLE_OK => Ok(()),
+                code => Err(curl::Error::new(code)),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Http2Keepalive {
+    pub(crate) interval: Duration,
+    pub(crate) timeout: Duration,
+}
+
+impl SetOpt for Http2Keepalive {
+    #[allow(unsafe_code)]
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        // Not yet defined by curl-sys.
+        const CURLOPT_UPKEEP_INTERVAL_MS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 281;
+
+        let value = self
+            .interval
+            .as_millis()
+            .min(std::os::raw::c_long::MAX as u128) as std::os::raw::c_long;
+
+        // Use unsafe API, because the safe API doesn't expose this option.
+        unsafe {
+            match curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_UPKEEP_INTERVAL_MS, value) {
+                curl_sys::CURLE_OK => {}
+                code => return Err(curl::Error::new(code)),
+            }
+        }
+
+        easy.maxage_conn(self.interval + self.timeout)
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct MaxUploadSpeed(pub(crate) u64);
 
@@ -860,6 +2122,11 @@ impl SetOpt for AutomaticDecompression {
     }
 }
 
+/// Explicit value of the `Accept-Encoding` header, which is empty to send no
+/// header at all.
+#[derive(Clone, Debug)]
+pub(crate) struct AcceptEncoding(pub(crate) String);
+
 #[derive(Clone, Debug)]
 pub(crate) struct EnableMetrics(pub(crate) bool);
 
@@ -869,6 +2136,50 @@ impl SetOpt for EnableMetrics {
     }
 }
 
+#[derive(Clone, Debug)]
+pub(crate) struct EnableConnectionReport(pub(crate) bool);
+
+/// Maximum number of bytes of an error response body to include in errors.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ErrorBodyLimit(pub(crate) usize);
+
+impl Default for ErrorBodyLimit {
+    fn default() -> Self {
+        Self(4096)
+    }
+}
+
+/// Buffer unread response body data on disk past a threshold instead of
+/// pausing the transfer.
+#[cfg(feature = "spill-to-disk")]
+#[derive(Clone, Debug)]
+pub(crate) struct SpillToDisk {
+    pub(crate) threshold: usize,
+    pub(crate) dir: std::path::PathBuf,
+}
+
+/// Maximum size of the request headers, as counted by HTTP/2.
+#[derive(Clone, Debug)]
+pub(crate) struct MaxHeaderListSize(pub(crate) usize);
+
+/// Maximum length of the request URI.
+#[derive(Clone, Debug)]
+pub(crate) struct MaxUriLength(pub(crate) usize);
+
+/// Fixed size of the buffer response bodies are received into.
+#[derive(Clone, Debug)]
+pub(crate) struct ResponseBufferSize(pub(crate) usize);
+
+impl SetOpt for ResponseBufferSize {
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        easy.buffer_size(self.0)
+    }
+}
+
+/// Reject requests with characters that would otherwise be percent-encoded.
+#[derive(Clone, Debug)]
+pub(crate) struct StrictValidation(pub(crate) bool);
+
 /// Supported IP versions that can be used.
 #[derive(Clone, Debug)]
 pub enum IpVersion {
@@ -901,6 +2212,203 @@ impl SetOpt for IpVersion {
     }
 }
 
+/// The `User-Agent` header to send with requests that do not set one
+/// explicitly, or `None` to not send one at all.
+#[derive(Clone, Debug)]
+pub(crate) struct UserAgent(pub(crate) Option<http::HeaderValue>);
+
+impl UserAgent {
+    /// The template used if no user agent is configured.
+    pub(crate) const DEFAULT_TEMPLATE: &'static str = "curl/{curl_version} isahc/{isahc_version}";
+
+    /// Expand the version tokens in the given user agent template.
+    pub(crate) fn expand(template: &str) -> String {
+        template
+            .replace("{isahc_version}", env!("CARGO_PKG_VERSION"))
+            .replace("{curl_version}", curl::Version::get().version())
+    }
+}
+
+/// Maximum size of a response body after decompression.
+#[derive(Clone, Debug)]
+pub(crate) struct MaxDecompressedSize(pub(crate) u64);
+
+/// Maximum ratio between the decompressed and compressed size of a response
+/// body.
+#[derive(Clone, Debug)]
+pub(crate) struct MaxDecompressionRatio(pub(crate) f64);
+
+/// Key selecting a separate connection pool for a request.
+#[derive(Clone, Debug)]
+pub(crate) struct PoolPartition(pub(crate) String);
+
+/// Policy for reusing connections from the connection cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReusePolicy {
+    /// Use a cached connection if one is available, and keep the connection
+    /// open for reuse by later requests afterwards.
+    Reuse,
+
+    /// Always open a new connection for the request, but keep it open
+    /// afterwards so that later requests may reuse it.
+    Fresh,
+
+    /// Use a cached connection if one is available, but close the connection
+    /// once the request is complete instead of returning it to the cache.
+    Never,
+
+    /// Always open a new connection for the request, and close it once the
+    /// request is complete. The connection is never shared with any other
+    /// request.
+    Isolated,
+}
+
+impl Default for ReusePolicy {
+    fn default() -> Self {
+        Self::Reuse
+    }
+}
+
+impl SetOpt for ReusePolicy {
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        // Only ever enable these options, so that disabling the connection
+        // cache for the whole client can't be overridden.
+        match self {
+            ReusePolicy::Reuse => {}
+            ReusePolicy::Fresh => easy.fresh_connect(true)?,
+            ReusePolicy::Never => easy.forbid_reuse(true)?,
+            ReusePolicy::Isolated => {
+                easy.fresh_connect(true)?;
+                easy.forbid_reuse(true)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Policy for requests whose response future is dropped before the response
+/// is received.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CancelPolicy {
+    /// Abort the request as soon as possible.
+    Abort,
+
+    /// Keep sending the request and receiving the response in the background.
+    /// The response body is discarded.
+    Detach,
+
+    /// Keep going in the background for at most the given duration, then
+    /// abort the request if it has not completed yet.
+    Linger(Duration),
+}
+
+impl Default for CancelPolicy {
+    fn default() -> Self {
+        Self::Abort
+    }
+}
+
+/// Policy for which responses contribute headers to the response returned for
+/// a request.
+///
+/// Besides the final response, a request may receive informational (`1xx`)
+/// responses, such as `100 Continue` or `103 Early Hints`, and redirect
+/// responses if redirects are followed. Headers from these are merged into the
+/// final response according to this policy.
+///
+/// Merged values are always appended as separate values after the values of
+/// the final response, and are never joined into a single comma-separated
+/// value, as doing so would break headers such as `Set-Cookie`. As a result,
+/// [`HeaderMap::get`](http::HeaderMap::get) still returns the value sent in the
+/// final response when there is one. Headers describing the body of a
+/// particular message, such as `Content-Length`, `Content-Type`, and
+/// `Location`, are never merged.
+///
+/// The headers of each redirect hop are available from
+/// [`RedirectHop::headers`](crate::RedirectHop::headers) regardless of this
+/// policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeaderMergePolicy {
+    /// Only keep the headers of the final response. This is the default.
+    FinalOnly,
+
+    /// Also keep the headers of informational responses received before the
+    /// final response.
+    Informational,
+
+    /// Keep the headers of informational responses and of every redirect
+    /// response that was followed. Values from redirect responses come after
+    /// the values of the final response, in the order the redirects were
+    /// followed.
+    All,
+}
+
+impl Default for HeaderMergePolicy {
+    fn default() -> Self {
+        Self::FinalOnly
+    }
+}
+
+impl HeaderMergePolicy {
+    pub(crate) fn merges_informational(self) -> bool {
+        self != Self::FinalOnly
+    }
+
+    pub(crate) fn merges_redirects(self) -> bool {
+        self == Self::All
+    }
+}
+
 /// Send header names as title case instead of lowercase.
 #[derive(Clone, Debug)]
 pub(crate) struct TitleCaseHeaders(pub(crate) bool);
+
+/// Explicit ordering of outgoing request headers.
+#[derive(Clone, Debug)]
+pub(crate) struct HeaderOrder(Vec<String>);
+
+impl HeaderOrder {
+    /// Sort the given list of headers according to this order. The sort is
+    /// stable, so multiple values for the same header keep their order.
+    pub(crate) fn sort<V>(&self, headers: &mut [(&http::header::HeaderName, V)]) {
+        headers.sort_by_key(|(name, _)| {
+            self.0
+                .iter()
+                .position(|ordered| ordered.eq_ignore_ascii_case(name.as_str()))
+                .unwrap_or(self.0.len())
+        });
+    }
+}
+
+impl FromIterator<String> for HeaderOrder {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        HeaderOrder(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderName;
+
+    #[test]
+    fn header_order_sorts_named_headers_first() {
+        let order = HeaderOrder::from_iter(vec!["User-Agent".into(), "host".into()]);
+        let accept = HeaderName::from_static("accept");
+        let host = HeaderName::from_static("host");
+        let user_agent = HeaderName::from_static("user-agent");
+
+        let mut headers = vec![(&accept, 1), (&host, 2), (&user_agent, 3), (&accept, 4)];
+        order.sort(&mut headers);
+
+        assert_eq!(
+            headers,
+            vec![(&user_agent, 3), (&host, 2), (&accept, 1), (&accept, 4)]
+        );
+    }
+}
+
+/// Retry a request once if it fails on a stale reused connection.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryStaleConnection(pub(crate) bool);
@@ -0,0 +1,167 @@
+//! Client identity profiles.
+
+use super::{ssl::Ciphers, SslOption, VersionNegotiation};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::iter::FromIterator;
+
+/// A preset bundle of request settings that together determine how a client
+/// presents itself to servers.
+///
+/// A profile groups together a user agent, a set of default headers such as
+/// `Accept`, TLS options, and HTTP version preferences. This can be handy for
+/// scraping or testing scenarios where a server behaves differently depending
+/// on the kind of client it thinks it is talking to.
+///
+/// A profile can be applied to a client or an individual request using
+/// [`Configurable::profile`](super::Configurable::profile). Headers set
+/// explicitly on a request, as well as default headers configured on a client,
+/// always take precedence over the headers in a profile.
+///
+/// # Examples
+///
+/// Using a built-in profile:
+///
+/// ```
+/// use isahc::{config::Profile, prelude::*};
+///
+/// let client = HttpClient::builder()
+///     .profile(Profile::curl())
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+///
+/// Defining a custom profile:
+///
+/// ```
+/// use isahc::{
+///     config::{Profile, VersionNegotiation},
+///     http::header::{HeaderName, HeaderValue},
+///     prelude::*,
+/// };
+///
+/// let profile = Profile::new()
+///     .user_agent(HeaderValue::from_static("my-crawler/1.0"))
+///     .header(
+///         HeaderName::from_static("accept"),
+///         HeaderValue::from_static("application/json"),
+///     )
+///     .version_negotiation(VersionNegotiation::http11());
+///
+/// let request = Request::get("https://example.org")
+///     .profile(profile)
+///     .body(())?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub(crate) headers: HeaderMap<HeaderValue>,
+    pub(crate) version_negotiation: Option<VersionNegotiation>,
+    pub(crate) ssl_ciphers: Option<Ciphers>,
+    pub(crate) ssl_options: Option<SslOption>,
+}
+
+impl Profile {
+    /// Create a new empty profile.
+    ///
+    /// An empty profile does not change any settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A profile that presents itself like the curl command line tool.
+    pub fn curl() -> Self {
+        let user_agent = format!("curl/{}", curl::Version::get().version());
+
+        Self::new()
+            .user_agent(HeaderValue::from_str(&user_agent).unwrap())
+            .header(http::header::ACCEPT, HeaderValue::from_static("*/*"))
+    }
+
+    /// A profile that presents itself like a typical modern desktop web
+    /// browser.
+    ///
+    /// Note that this only affects request headers and protocol preferences;
+    /// it does not make Isahc indistinguishable from a real browser.
+    pub fn browser() -> Self {
+        Self::new()
+            .user_agent(HeaderValue::from_static(
+                "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0",
+            ))
+            .header(
+                http::header::ACCEPT,
+                HeaderValue::from_static(
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                ),
+            )
+            .header(
+                http::header::ACCEPT_LANGUAGE,
+                HeaderValue::from_static("en-US,en;q=0.5"),
+            )
+            .version_negotiation(VersionNegotiation::latest_compatible())
+    }
+
+    /// Set the `User-Agent` header sent by this profile.
+    pub fn user_agent(self, user_agent: HeaderValue) -> Self {
+        self.header(http::header::USER_AGENT, user_agent)
+    }
+
+    /// Set a header sent by this profile, replacing any existing values for
+    /// the same header in this profile.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Set the HTTP version negotiation strategy used by this profile.
+    pub fn version_negotiation(mut self, negotiation: VersionNegotiation) -> Self {
+        self.version_negotiation = Some(negotiation);
+        self
+    }
+
+    /// Set the list of SSL/TLS ciphers used by this profile.
+    pub fn ssl_ciphers<I, T>(mut self, ciphers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.ssl_ciphers = Some(Ciphers::from_iter(ciphers.into_iter().map(T::into)));
+        self
+    }
+
+    /// Set the SSL/TLS options used by this profile.
+    pub fn ssl_options(mut self, options: SslOption) -> Self {
+        self.ssl_options = Some(options);
+        self
+    }
+
+    /// Add the headers in this profile to the given header map, unless a
+    /// header with the same name is already present.
+    pub(crate) fn apply_headers(&self, headers: &mut HeaderMap<HeaderValue>) {
+        for name in self.headers.keys() {
+            if !headers.contains_key(name) {
+                for value in self.headers.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_headers_do_not_override_existing_headers() {
+        let profile = Profile::new()
+            .user_agent(HeaderValue::from_static("profile"))
+            .header(http::header::ACCEPT, HeaderValue::from_static("text/html"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::USER_AGENT, HeaderValue::from_static("mine"));
+        profile.apply_headers(&mut headers);
+
+        assert_eq!(headers[http::header::USER_AGENT], "mine");
+        assert_eq!(headers[http::header::ACCEPT], "text/html");
+    }
+}
@@ -0,0 +1,62 @@
+use std::{fmt, sync::Arc};
+
+/// How far along the upload and download of a request are, as reported to a
+/// callback given to
+/// [`Configurable::progress_callback`](super::Configurable::progress_callback).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Progress {
+    uploaded: u64,
+    upload_total: u64,
+    downloaded: u64,
+    download_total: u64,
+}
+
+impl Progress {
+    pub(crate) fn new(
+        uploaded: u64,
+        upload_total: u64,
+        downloaded: u64,
+        download_total: u64,
+    ) -> Self {
+        Self {
+            uploaded,
+            upload_total,
+            downloaded,
+            download_total,
+        }
+    }
+
+    /// Get the number of bytes of the request body sent so far.
+    pub fn uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    /// Get the size of the request body in bytes, if known.
+    pub fn upload_total(&self) -> Option<u64> {
+        Some(self.upload_total).filter(|&total| total > 0)
+    }
+
+    /// Get the number of bytes of the response body received so far.
+    ///
+    /// This is the size of the body as it was transferred, before any
+    /// decompression.
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded
+    }
+
+    /// Get the size of the response body in bytes as it is transferred, if
+    /// known.
+    pub fn download_total(&self) -> Option<u64> {
+        Some(self.download_total).filter(|&total| total > 0)
+    }
+}
+
+/// Callback receiving the progress of a request.
+#[derive(Clone)]
+pub(crate) struct OnProgress(pub(crate) Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl fmt::Debug for OnProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnProgress")
+    }
+}
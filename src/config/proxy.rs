@@ -1,5 +1,6 @@
 use super::SetOpt;
-use curl::easy::Easy2;
+use crate::parsing::header_to_curl_string;
+use curl::easy::{Easy2, List};
 use std::iter::FromIterator;
 
 /// Decorator for marking certain configurations to apply to a proxy rather than
@@ -17,6 +18,19 @@ impl SetOpt for Proxy<Option<http::Uri>> {
     }
 }
 
+/// Extra headers sent to the proxy only.
+impl SetOpt for Proxy<http::HeaderMap> {
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        let mut list = List::new();
+
+        for (name, value) in self.0.iter() {
+            list.append(&header_to_curl_string(name, value, false))?;
+        }
+
+        easy.proxy_headers(list)
+    }
+}
+
 /// A list of host names that do not require a proxy to get reached, even if one
 /// is specified.
 ///
@@ -25,7 +39,7 @@ impl SetOpt for Proxy<Option<http::Uri>> {
 /// for configuring a client's no proxy list.
 #[derive(Clone, Debug)]
 pub(crate) struct Blacklist {
-    skip: String,
+    pub(crate) skip: String,
 }
 
 impl FromIterator<String> for Blacklist {
@@ -1,3 +1,5 @@
+use http::{header::HeaderName, HeaderMap, Uri};
+
 /// Describes a policy for handling server redirects.
 ///
 /// The default is to not follow redirects.
@@ -24,3 +26,236 @@ impl Default for RedirectPolicy {
 
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct AutoReferer;
+
+/// A change in the origin of a request that can occur when following a
+/// redirect.
+///
+/// Used with [`RedirectHeaderPolicy::allow`] to control which sensitive
+/// headers are kept when a redirect leads somewhere else.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OriginChange {
+    /// The scheme changed from `http` to `https`. A change from the default
+    /// port of one scheme to the default port of the other is considered part
+    /// of the upgrade.
+    Upgrade,
+
+    /// The scheme changed in any other way, such as from `https` to `http`.
+    Scheme,
+
+    /// The host changed.
+    Host,
+
+    /// The port changed.
+    Port,
+}
+
+impl OriginChange {
+    /// Get the changes in origin between two URIs.
+    pub(crate) fn between(from: &Uri, to: &Uri) -> Vec<Self> {
+        let mut changes = Vec::new();
+
+        let from_scheme = from.scheme_str().unwrap_or("http");
+        let to_scheme = to.scheme_str().unwrap_or("http");
+        let from_port = from.port_u16().or_else(|| default_port(from_scheme));
+        let to_port = to.port_u16().or_else(|| default_port(to_scheme));
+
+        let upgrade =
+            from_scheme.eq_ignore_ascii_case("http") && to_scheme.eq_ignore_ascii_case("https");
+
+        if upgrade {
+            changes.push(OriginChange::Upgrade);
+        } else if !from_scheme.eq_ignore_ascii_case(to_scheme) {
+            changes.push(OriginChange::Scheme);
+        }
+
+        let from_host = from.host().unwrap_or("");
+        let to_host = to.host().unwrap_or("");
+
+        if !from_host.eq_ignore_ascii_case(to_host) {
+            changes.push(OriginChange::Host);
+        }
+
+        let default_upgrade = upgrade && from_port == Some(80) && to_port == Some(443);
+
+        if from_port != to_port && !default_upgrade {
+            changes.push(OriginChange::Port);
+        }
+
+        changes
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    if scheme.eq_ignore_ascii_case("http") {
+        Some(80)
+    } else if scheme.eq_ignore_ascii_case("https") {
+        Some(443)
+    } else {
+        None
+    }
+}
+
+/// Describes which sensitive request headers are removed when a redirect
+/// changes the origin (scheme, host, or port) of a request.
+///
+/// Headers such as `Authorization` and `Cookie` usually contain credentials
+/// meant only for the server they were originally sent to. When following a
+/// redirect to a different origin, headers marked as sensitive are stripped
+/// from the redirected request unless the policy explicitly allows them to
+/// survive every change in origin that occurred.
+///
+/// By default, `Authorization` and `Cookie` are considered sensitive and are
+/// removed on any change in origin. If the `Authorization` header is removed,
+/// any [`Credentials`](crate::auth::Credentials) set on the request are not
+/// used for the redirected request either.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::{
+///     config::{OriginChange, RedirectHeaderPolicy, RedirectPolicy},
+///     prelude::*,
+/// };
+///
+/// let response = Request::get("http://example.org")
+///     .redirect_policy(RedirectPolicy::Follow)
+///     .redirect_header_policy(
+///         RedirectHeaderPolicy::default()
+///             // Also strip our own API key header.
+///             .sensitive_header("x-api-key".parse().unwrap())
+///             // Allow credentials to follow an upgrade to HTTPS.
+///             .allow(isahc::http::header::AUTHORIZATION, OriginChange::Upgrade),
+///     )
+///     .header("authorization", "Bearer my-token")
+///     .header("x-api-key", "secret")
+///     .body(())?
+///     .send()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RedirectHeaderPolicy {
+    /// Sensitive headers, along with the origin changes each may survive.
+    headers: Vec<(HeaderName, Vec<OriginChange>)>,
+}
+
+impl Default for RedirectHeaderPolicy {
+    fn default() -> Self {
+        Self::keep_all()
+            .sensitive_header(http::header::AUTHORIZATION)
+            .sensitive_header(http::header::COOKIE)
+    }
+}
+
+impl RedirectHeaderPolicy {
+    /// Create a policy that does not consider any headers sensitive, and keeps
+    /// all headers across redirects regardless of origin.
+    pub fn keep_all() -> Self {
+        Self {
+            headers: Vec::new(),
+        }
+    }
+
+    /// Mark a header as sensitive, causing it to be removed whenever a
+    /// redirect changes the origin of the request.
+    pub fn sensitive_header(mut self, name: HeaderName) -> Self {
+        self.entry(name);
+        self
+    }
+
+    /// Allow a sensitive header to survive a particular kind of change in
+    /// origin. The header is marked as sensitive if it is not already.
+    ///
+    /// A header is only kept if every change in origin caused by a redirect is
+    /// allowed.
+    pub fn allow(mut self, name: HeaderName, change: OriginChange) -> Self {
+        let allowed = self.entry(name);
+
+        if !allowed.contains(&change) {
+            allowed.push(change);
+        }
+
+        self
+    }
+
+    /// Check whether a header should be removed given the changes in origin
+    /// caused by a redirect.
+    pub(crate) fn is_stripped(&self, name: &HeaderName, changes: &[OriginChange]) -> bool {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, allowed)| changes.iter().any(|change| !allowed.contains(change)))
+            .unwrap_or(false)
+    }
+
+    /// Remove the headers that should not survive the given changes in origin.
+    pub(crate) fn strip(&self, headers: &mut HeaderMap, changes: &[OriginChange]) {
+        for (name, _) in &self.headers {
+            if self.is_stripped(name, changes) {
+                headers.remove(name);
+            }
+        }
+    }
+
+    fn entry(&mut self, name: HeaderName) -> &mut Vec<OriginChange> {
+        let index = match self.headers.iter().position(|(header, _)| *header == name) {
+            Some(index) => index,
+            None => {
+                self.headers.push((name, Vec::new()));
+                self.headers.len() - 1
+            }
+        };
+
+        &mut self.headers[index].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("http://a.com/x", "http://a.com/y", &[])]
+    #[test_case("http://a.com", "https://a.com", &[OriginChange::Upgrade])]
+    #[test_case("http://a.com:8080", "https://a.com", &[OriginChange::Upgrade, OriginChange::Port])]
+    #[test_case("https://a.com", "http://a.com", &[OriginChange::Scheme, OriginChange::Port])]
+    #[test_case("http://a.com", "http://B.com", &[OriginChange::Host])]
+    #[test_case("http://a.com", "http://A.com:80", &[])]
+    #[test_case("http://a.com:1", "http://a.com:2", &[OriginChange::Port])]
+    fn origin_changes(from: &str, to: &str, expected: &[OriginChange]) {
+        let changes = OriginChange::between(&from.parse().unwrap(), &to.parse().unwrap());
+
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn default_policy_strips_credentials_on_any_change() {
+        let policy = RedirectHeaderPolicy::default();
+
+        assert!(!policy.is_stripped(&http::header::AUTHORIZATION, &[]));
+        assert!(policy.is_stripped(&http::header::AUTHORIZATION, &[OriginChange::Upgrade]));
+        assert!(policy.is_stripped(&http::header::COOKIE, &[OriginChange::Port]));
+        assert!(!policy.is_stripped(&http::header::ACCEPT, &[OriginChange::Host]));
+    }
+
+    #[test]
+    fn allowed_changes_keep_header() {
+        let policy = RedirectHeaderPolicy::default()
+            .allow(http::header::AUTHORIZATION, OriginChange::Upgrade);
+
+        assert!(!policy.is_stripped(&http::header::AUTHORIZATION, &[OriginChange::Upgrade]));
+        assert!(policy.is_stripped(
+            &http::header::AUTHORIZATION,
+            &[OriginChange::Upgrade, OriginChange::Host]
+        ));
+    }
+
+    #[test]
+    fn keep_all_strips_nothing() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::AUTHORIZATION, "Bearer foo".parse().unwrap());
+
+        RedirectHeaderPolicy::keep_all().strip(&mut headers, &[OriginChange::Host]);
+
+        assert!(headers.contains_key(http::header::AUTHORIZATION));
+    }
+}
@@ -0,0 +1,588 @@
+use crate::{
+    body::AsyncBody,
+    error::{Error, ErrorKind},
+    headers,
+    random::random_fraction,
+};
+use http::{HeaderMap, Method, Response, StatusCode};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Decides whether a request that failed should be sent again, and how long
+/// to wait before doing so.
+///
+/// A retry policy is consulted after every attempt at sending a request that
+/// fails with an error or receives a response, and can look at the outcome of
+/// the attempt using the given [`RetryAttempt`]. Requests are only retried if
+/// their body is available in memory so that it can be sent again; requests
+/// with a streaming body are never retried, whatever the policy says.
+///
+/// Waiting between attempts does not block any thread, so retrying
+/// asynchronous requests is cheap. The response future of a request that is
+/// waiting to be retried can be dropped to stop retrying it.
+///
+/// [`ExponentialBackoff`] is a good choice of policy for most purposes, but a
+/// policy can also be implemented using a closure.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{config::RetryAttempt, http::StatusCode, prelude::*, HttpClient};
+/// use std::time::Duration;
+///
+/// // Retry rate-limited requests once, after however long the server asks.
+/// let client = HttpClient::builder()
+///     .retry_policy(|attempt: &RetryAttempt<'_>| {
+///         if attempt.number() == 1 && attempt.status() == Some(StatusCode::TOO_MANY_REQUESTS) {
+///             Some(attempt.retry_after().unwrap_or(Duration::from_secs(1)))
+///         } else {
+///             None
+///         }
+///     })
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+pub trait RetryPolicy: Send + Sync {
+    /// Get how long to wait before sending the request again after the given
+    /// attempt, or `None` if the outcome of the attempt should be returned
+    /// as it is.
+    fn retry_delay(&self, attempt: &RetryAttempt<'_>) -> Option<Duration>;
+
+    /// Get the total time allowed for all attempts at sending a request,
+    /// including the delays between them, if any.
+    ///
+    /// The default implementation returns `None`, which means retrying is
+    /// only limited by the policy itself.
+    fn total_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Limit the total time spent sending a request using this policy to the
+    /// given duration.
+    ///
+    /// The time spent on every attempt and the delays between them all count
+    /// towards the limit. Each attempt is given a timeout of whatever remains
+    /// of it, or the timeout of the request if that is shorter, and that
+    /// timeout is also shared with any backup requests sent by a
+    /// [`HedgePolicy`](super::HedgePolicy). A request is not retried if the
+    /// delay before retrying it would leave no time for another attempt, in
+    /// which case the outcome of the last attempt is returned.
+    ///
+    /// If the limit is reached while an attempt is still in progress, a
+    /// [`Timeout`](crate::error::ErrorKind::Timeout) error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::{config::{ExponentialBackoff, RetryPolicy}, prelude::*, HttpClient};
+    /// use std::time::Duration;
+    ///
+    /// // Give up on a request after 10 seconds, however many times it has
+    /// // been retried by then.
+    /// let client = HttpClient::builder()
+    ///     .retry_policy(
+    ///         ExponentialBackoff::new(5).with_total_timeout(Duration::from_secs(10)),
+    ///     )
+    ///     .build()?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn with_total_timeout(self, timeout: Duration) -> TotalTimeout<Self>
+    where
+        Self: Sized,
+    {
+        TotalTimeout {
+            policy: self,
+            timeout,
+        }
+    }
+}
+
+impl<F> RetryPolicy for F
+where
+    F: Fn(&RetryAttempt<'_>) -> Option<Duration> + Send + Sync,
+{
+    fn retry_delay(&self, attempt: &RetryAttempt<'_>) -> Option<Duration> {
+        (self)(attempt)
+    }
+}
+
+/// The outcome of an attempt at sending a request, given to a
+/// [`RetryPolicy`] to decide whether to retry the request.
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    number: u32,
+    method: &'a Method,
+    result: Result<&'a Response<AsyncBody>, &'a Error>,
+}
+
+impl<'a> RetryAttempt<'a> {
+    pub(crate) fn new(
+        number: u32,
+        method: &'a Method,
+        result: Result<&'a Response<AsyncBody>, &'a Error>,
+    ) -> Self {
+        Self {
+            number,
+            method,
+            result,
+        }
+    }
+
+    /// Get the number of this attempt, starting at 1 for the first time the
+    /// request was sent.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Get the method of the request.
+    pub fn method(&self) -> &Method {
+        self.method
+    }
+
+    /// Get the error the attempt failed with, or `None` if a response was
+    /// received.
+    pub fn error(&self) -> Option<&Error> {
+        self.result.err()
+    }
+
+    /// Get the status code of the response received, if any.
+    ///
+    /// This is also available if the response was turned into an error
+    /// because of its status code.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self.result {
+            Ok(response) => Some(response.status()),
+            Err(e) => e.status(),
+        }
+    }
+
+    /// Get the headers of the response received, if any.
+    pub fn headers(&self) -> Option<&HeaderMap> {
+        self.result.ok().map(Response::headers)
+    }
+
+    /// Get how long the server asked to wait before retrying, as indicated by
+    /// the `Retry-After` header of the response, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.headers().and_then(headers::retry_after)
+    }
+
+    /// Check if the attempt failed in a way that is likely to be temporary,
+    /// so that sending the request again could succeed.
+    ///
+    /// This includes failures to connect, timeouts, connections that were
+    /// reset while sending the request or receiving the response, and
+    /// responses with status `429 Too Many Requests` or `503 Service
+    /// Unavailable`.
+    pub fn is_transient(&self) -> bool {
+        if let Some(status) = self.status() {
+            return is_transient_status(status);
+        }
+
+        match self.result {
+            Err(e) => match e.kind() {
+                ErrorKind::ConnectionFailed
+                | ErrorKind::ConnectTimeout
+                | ErrorKind::HeadersTimeout
+                | ErrorKind::IdleTimeout
+                | ErrorKind::Io
+                | ErrorKind::ReadTimeout
+                | ErrorKind::Timeout => true,
+                _ => false,
+            },
+            Ok(_) => false,
+        }
+    }
+
+    /// Check if the request was certainly not processed by the server, so
+    /// that it is safe to send again even if its method is not idempotent.
+    ///
+    /// This is the case if a connection to the server could not be made, or
+    /// if the server refused the request with status `429 Too Many Requests`
+    /// or `503 Service Unavailable`.
+    fn is_unprocessed(&self) -> bool {
+        if let Some(status) = self.status() {
+            return is_transient_status(status);
+        }
+
+        match self.result {
+            Err(e) => match e.kind() {
+                ErrorKind::ConnectionFailed | ErrorKind::ConnectTimeout => true,
+                _ => false,
+            },
+            Ok(_) => false,
+        }
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// A [`RetryPolicy`] that retries transient failures a limited number of
+/// times, waiting exponentially longer between each attempt.
+///
+/// The delay before the first retry is the base delay, and it doubles for
+/// each retry after that up to the maximum delay. Some random
+/// [`Jitter`] is applied to the delay so that many clients failing at the
+/// same time do not all retry at the same time too.
+///
+/// If the server says how long to wait using the `Retry-After` header, that
+/// delay is used instead. If it asks to wait longer than the maximum delay,
+/// the request is not retried.
+///
+/// Which failures are retried is decided by
+/// [`RetryAttempt::is_transient`]. Requests whose method is not idempotent,
+/// such as `POST`, are only retried if the server certainly did not process
+/// them, because it could not be connected to or it responded with status
+/// `429 Too Many Requests` or `503 Service Unavailable`.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{config::{ExponentialBackoff, Jitter}, prelude::*, HttpClient};
+/// use std::time::Duration;
+///
+/// let client = HttpClient::builder()
+///     .retry_policy(
+///         ExponentialBackoff::new(3)
+///             .base_delay(Duration::from_millis(50))
+///             .max_delay(Duration::from_secs(5))
+///             .jitter(Jitter::Equal),
+///     )
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: Jitter,
+}
+
+impl ExponentialBackoff {
+    /// Create a policy that retries a request at most the given number of
+    /// times.
+    ///
+    /// The base delay defaults to 100 milliseconds, the maximum delay to 30
+    /// seconds, and [`Jitter::Full`] is applied.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            jitter: Jitter::Full,
+        }
+    }
+
+    /// Set the delay before the first retry.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Set the longest delay to wait before any retry.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set how the delay before each retry is randomized.
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Get the delay before the given retry, before jitter is applied.
+    fn backoff(&self, retry: u32) -> Duration {
+        // Doubling more often than this overflows any reasonable delay anyway.
+        let factor = 1u32 << retry.saturating_sub(1).min(31);
+
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn retry_delay(&self, attempt: &RetryAttempt<'_>) -> Option<Duration> {
+        if attempt.number() > self.max_retries || !attempt.is_transient() {
+            return None;
+        }
+
+        if !attempt.method().is_idempotent() && !attempt.is_unprocessed() {
+            return None;
+        }
+
+        match attempt.retry_after() {
+            Some(delay) if delay > self.max_delay => None,
+            Some(delay) => Some(delay),
+            None => Some(self.jitter.apply(self.backoff(attempt.number()))),
+        }
+    }
+}
+
+/// A [`RetryPolicy`] limited to a total amount of time, created using
+/// [`RetryPolicy::with_total_timeout`].
+#[derive(Clone, Debug)]
+pub struct TotalTimeout<P> {
+    policy: P,
+    timeout: Duration,
+}
+
+impl<P: RetryPolicy> RetryPolicy for TotalTimeout<P> {
+    fn retry_delay(&self, attempt: &RetryAttempt<'_>) -> Option<Duration> {
+        self.policy.retry_delay(attempt)
+    }
+
+    fn total_timeout(&self) -> Option<Duration> {
+        // Wrapping the policy again can only make the limit stricter.
+        Some(match self.policy.total_timeout() {
+            Some(timeout) => timeout.min(self.timeout),
+            None => self.timeout,
+        })
+    }
+}
+
+/// Describes how to randomize the delay before retrying a request.
+///
+/// See [this article](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/)
+/// for a comparison of the different kinds of jitter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Jitter {
+    /// Use the delay as it is.
+    None,
+
+    /// Wait a random delay between zero and the delay.
+    Full,
+
+    /// Wait at least half of the delay, plus a random delay up to the other
+    /// half.
+    Equal,
+}
+
+impl Jitter {
+    fn apply(self, delay: Duration) -> Duration {
+        match self {
+            Jitter::None => delay,
+            Jitter::Full => delay.mul_f64(random_fraction()),
+            Jitter::Equal => delay / 2 + (delay / 2).mul_f64(random_fraction()),
+        }
+    }
+}
+
+/// The retry policy of a request.
+#[derive(Clone)]
+pub(crate) struct Retry(pub(crate) Arc<dyn RetryPolicy>);
+
+/// Keeps track of the time left for sending a request with a retry policy
+/// that has a total timeout.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline(Instant);
+
+impl Deadline {
+    /// Start the total timeout of the given policy, if it has one.
+    pub(crate) fn start(policy: &dyn RetryPolicy) -> Option<Self> {
+        policy
+            .total_timeout()
+            .map(|timeout| Self(Instant::now() + timeout))
+    }
+
+    /// Get the time left before the deadline, or `None` if it has passed.
+    ///
+    /// A timeout of zero means no timeout at all to curl, so less than a
+    /// millisecond left counts as none.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.0
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| *remaining >= Duration::from_millis(1))
+    }
+
+    /// Get the timeout to give the next attempt, given the timeout of the
+    /// request itself, or `None` if there is no time left for one.
+    pub(crate) fn attempt_timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let remaining = self.remaining()?;
+
+        Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+    }
+
+    /// Check if waiting for the given delay leaves time for another attempt.
+    pub(crate) fn allows_delay(&self, delay: Duration) -> bool {
+        match self.remaining() {
+            Some(remaining) => delay + Duration::from_millis(1) < remaining,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for Retry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Retry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, retry_after: Option<&str>) -> Response<AsyncBody> {
+        let mut builder = Response::builder().status(status);
+
+        if let Some(retry_after) = retry_after {
+            builder = builder.header("retry-after", retry_after);
+        }
+
+        builder.body(AsyncBody::empty()).unwrap()
+    }
+
+    fn error(kind: ErrorKind) -> Error {
+        Error::from(kind)
+    }
+
+    fn delay(
+        policy: &ExponentialBackoff,
+        number: u32,
+        method: Method,
+        result: Result<&Response<AsyncBody>, &Error>,
+    ) -> Option<Duration> {
+        policy.retry_delay(&RetryAttempt::new(number, &method, result))
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay() {
+        let policy = ExponentialBackoff::new(100)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(Jitter::None);
+        let e = error(ErrorKind::ConnectionFailed);
+        let backoff = |number| delay(&policy, number, Method::GET, Err(&e));
+
+        assert_eq!(backoff(1), Some(Duration::from_millis(100)));
+        assert_eq!(backoff(2), Some(Duration::from_millis(200)));
+        assert_eq!(backoff(4), Some(Duration::from_millis(800)));
+        assert_eq!(backoff(5), Some(Duration::from_secs(1)));
+        assert_eq!(backoff(64), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let policy = ExponentialBackoff::new(2);
+        let e = error(ErrorKind::Timeout);
+
+        assert!(delay(&policy, 2, Method::GET, Err(&e)).is_some());
+        assert!(delay(&policy, 3, Method::GET, Err(&e)).is_none());
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        let policy = ExponentialBackoff::new(3);
+        let e = error(ErrorKind::InvalidRequest);
+        let ok = response(200, None);
+        let not_found = response(404, None);
+        let unavailable = response(503, None);
+
+        assert!(delay(&policy, 1, Method::GET, Err(&e)).is_none());
+        assert!(delay(&policy, 1, Method::GET, Ok(&ok)).is_none());
+        assert!(delay(&policy, 1, Method::GET, Ok(&not_found)).is_none());
+        assert!(delay(&policy, 1, Method::GET, Ok(&unavailable)).is_some());
+    }
+
+    #[test]
+    fn non_idempotent_requests_are_only_retried_if_unprocessed() {
+        let policy = ExponentialBackoff::new(3);
+        let reset = error(ErrorKind::Io);
+        let refused = error(ErrorKind::ConnectionFailed);
+        let rate_limited = response(429, None);
+
+        assert!(delay(&policy, 1, Method::POST, Err(&reset)).is_none());
+        assert!(delay(&policy, 1, Method::PUT, Err(&reset)).is_some());
+        assert!(delay(&policy, 1, Method::POST, Err(&refused)).is_some());
+        assert!(delay(&policy, 1, Method::POST, Ok(&rate_limited)).is_some());
+    }
+
+    #[test]
+    fn retry_after_is_respected() {
+        let policy = ExponentialBackoff::new(3).max_delay(Duration::from_secs(10));
+        let soon = response(429, Some("2"));
+        let later = response(503, Some("60"));
+
+        assert_eq!(
+            delay(&policy, 1, Method::GET, Ok(&soon)),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(delay(&policy, 1, Method::GET, Ok(&later)), None);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let delay = Duration::from_secs(1);
+
+        for _ in 0..100 {
+            assert!(Jitter::Full.apply(delay) <= delay);
+            assert!(Jitter::Equal.apply(delay) >= delay / 2);
+            assert!(Jitter::Equal.apply(delay) <= delay);
+        }
+    }
+
+    #[test]
+    fn total_timeout_wraps_policy() {
+        let policy = ExponentialBackoff::new(3)
+            .jitter(Jitter::None)
+            .with_total_timeout(Duration::from_secs(10));
+        let e = error(ErrorKind::ConnectionFailed);
+        let method = Method::GET;
+
+        assert_eq!(policy.total_timeout(), Some(Duration::from_secs(10)));
+        assert_eq!(
+            policy.retry_delay(&RetryAttempt::new(1, &method, Err(&e))),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(ExponentialBackoff::new(3).total_timeout(), None);
+    }
+
+    #[test]
+    fn shortest_total_timeout_applies() {
+        let policy = ExponentialBackoff::new(3)
+            .with_total_timeout(Duration::from_secs(5))
+            .with_total_timeout(Duration::from_secs(10));
+
+        assert_eq!(policy.total_timeout(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn deadline_caps_attempt_timeout() {
+        let policy = ExponentialBackoff::new(3).with_total_timeout(Duration::from_secs(10));
+        let deadline = Deadline::start(&policy).unwrap();
+
+        let timeout = deadline.attempt_timeout(None).unwrap();
+        assert!(timeout <= Duration::from_secs(10));
+        assert!(timeout > Duration::from_secs(9));
+
+        assert_eq!(
+            deadline.attempt_timeout(Some(Duration::from_secs(1))),
+            Some(Duration::from_secs(1))
+        );
+        assert!(
+            deadline.attempt_timeout(Some(Duration::from_secs(60))).unwrap()
+                <= Duration::from_secs(10)
+        );
+
+        assert!(deadline.allows_delay(Duration::from_secs(1)));
+        assert!(!deadline.allows_delay(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn passed_deadline_leaves_no_time() {
+        let policy = ExponentialBackoff::new(3).with_total_timeout(Duration::from_secs(0));
+        let deadline = Deadline::start(&policy).unwrap();
+
+        assert_eq!(deadline.remaining(), None);
+        assert_eq!(deadline.attempt_timeout(Some(Duration::from_secs(1))), None);
+        assert!(!deadline.allows_delay(Duration::from_secs(0)));
+    }
+}
@@ -1,9 +1,11 @@
 //! Configuration options related to SSL/TLS.
 
-use super::SetOpt;
+use super::{dial::ConnectTo, SetOpt};
 use curl::easy::{Easy2, SslOpt};
+use http::Uri;
 use std::{
     iter::FromIterator,
+    net::IpAddr,
     ops::{BitOr, BitOrAssign},
     path::PathBuf,
 };
@@ -12,16 +14,16 @@ use std::{
 #[derive(Clone, Debug)]
 pub struct ClientCertificate {
     /// Name of the cert format.
-    format: &'static str,
+    pub(crate) format: &'static str,
 
     /// Path to the certificate file.
-    path: PathBuf,
+    pub(crate) path: PathBuf,
 
     /// Private key corresponding to the SSL/TLS certificate.
-    private_key: Option<PrivateKey>,
+    pub(crate) private_key: Option<PrivateKey>,
 
     /// Password to decrypt the certificate file.
-    password: Option<String>,
+    pub(crate) password: Option<String>,
 }
 
 impl ClientCertificate {
@@ -54,6 +56,11 @@ impl ClientCertificate {
             password: password.into(),
         }
     }
+
+    /// Check whether this certificate is in a PKCS#12 archive.
+    pub(crate) fn is_pkcs12(&self) -> bool {
+        self.format == "P12"
+    }
 }
 
 impl SetOpt for ClientCertificate {
@@ -77,13 +84,13 @@ impl SetOpt for ClientCertificate {
 #[derive(Clone, Debug)]
 pub struct PrivateKey {
     /// Key format name.
-    format: &'static str,
+    pub(crate) format: &'static str,
 
     /// Path to the key file.
-    path: PathBuf,
+    pub(crate) path: PathBuf,
 
     /// Password to decrypt the key file.
-    password: Option<String>,
+    pub(crate) password: Option<String>,
 }
 
 impl PrivateKey {
@@ -104,6 +111,41 @@ impl PrivateKey {
             password: password.into(),
         }
     }
+
+    /// Use a private key stored in an OpenSSL engine, such as a PKCS#11 token
+    /// or a TPM, identified by a key ID or URI such as `pkcs11:object=mykey`.
+    ///
+    /// The key never leaves the engine. The engine to load the key from must
+    /// be selected using
+    /// [`Configurable::ssl_engine`](super::Configurable::ssl_engine). The
+    /// password, if any, is used as the PIN to unlock the key.
+    ///
+    /// This is only supported if libcurl uses OpenSSL with engine support.
+    pub fn engine(key_id: impl Into<String>, password: impl Into<Option<String>>) -> Self {
+        Self {
+            format: "ENG",
+            path: key_id.into().into(),
+            password: password.into(),
+        }
+    }
+
+    /// Use a private key loaded through an OpenSSL 3 provider, identified by a
+    /// URI such as `pkcs11:object=mykey` that the provider understands.
+    ///
+    /// The key never leaves the provider. The provider to load the key with
+    /// can be selected using
+    /// [`Configurable::ssl_engine`](super::Configurable::ssl_engine),
+    /// otherwise the URI is passed to all loaded providers. The password, if
+    /// any, is used as the PIN to unlock the key.
+    ///
+    /// This is only supported if libcurl uses OpenSSL 3 or newer.
+    pub fn provider(uri: impl Into<String>, password: impl Into<Option<String>>) -> Self {
+        Self {
+            format: "PROV",
+            path: uri.into().into(),
+            password: password.into(),
+        }
+    }
 }
 
 impl SetOpt for PrivateKey {
@@ -124,7 +166,7 @@ impl SetOpt for PrivateKey {
 pub struct CaCertificate {
     /// Path to the certificate bundle file. Currently only file paths are
     /// supported.
-    path: PathBuf,
+    pub(crate) path: PathBuf,
 }
 
 impl CaCertificate {
@@ -142,8 +184,67 @@ impl SetOpt for CaCertificate {
     }
 }
 
+/// A pre-shared key (PSK) for authenticating TLS connections without
+/// certificates.
+///
+/// See [`Configurable::ssl_pre_shared_key`](super::Configurable::ssl_pre_shared_key)
+/// for details.
+#[cfg(feature = "tls-psk")]
+#[derive(Clone)]
+pub struct PreSharedKey {
+    /// Identity sent to the server to select the key.
+    pub(crate) identity: String,
+
+    /// Callback returning the key given the identity hint of the server.
+    #[allow(clippy::type_complexity)]
+    pub(crate) key: std::sync::Arc<dyn Fn(Option<&str>) -> Option<Vec<u8>> + Send + Sync>,
+}
+
+#[cfg(feature = "tls-psk")]
+impl PreSharedKey {
+    /// Use a fixed key for the given identity.
+    pub fn new(identity: impl Into<String>, key: impl Into<Vec<u8>>) -> Self {
+        let key = key.into();
+
+        Self::with_callback(identity, move |_| Some(key.clone()))
+    }
+
+    /// Use a callback to look up the key for the given identity.
+    ///
+    /// The callback is invoked during each TLS handshake with the identity
+    /// hint sent by the server, if any. Returning `None` aborts the handshake.
+    pub fn with_callback<F>(identity: impl Into<String>, callback: F) -> Self
+    where
+        F: Fn(Option<&str>) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        Self {
+            identity: identity.into(),
+            key: std::sync::Arc::new(callback),
+        }
+    }
+}
+
+#[cfg(feature = "tls-psk")]
+impl std::fmt::Debug for PreSharedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreSharedKey")
+            .field("identity", &self.identity)
+            .finish()
+    }
+}
+
+/// Name of the OpenSSL engine or provider to use for loading keys.
 #[derive(Clone, Debug)]
-pub(crate) struct Ciphers(String);
+pub(crate) struct Engine(pub(crate) String);
+
+impl SetOpt for Engine {
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        easy.ssl_engine(&self.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Ciphers(pub(crate) String);
 
 impl FromIterator<String> for Ciphers {
     fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
@@ -201,7 +302,30 @@ impl SslOption {
     /// library).
     pub const DANGER_ACCEPT_REVOKED_CERTS: Self = SslOption(0b0100);
 
-    const fn contains(self, other: Self) -> bool {
+    /// Disables hostname verification on certificates, but only for requests
+    /// whose URI host is a literal IP address.
+    ///
+    /// When connecting to an IP address, the certificate presented by the
+    /// server must list that address as an IP address subject alternative
+    /// name. Many servers reached by IP address, such as services behind a
+    /// load balancer, only have certificates for their DNS names. With this
+    /// option the certificate chain is still validated, but the address is
+    /// not matched against the certificate. Requests to DNS names are verified
+    /// as usual.
+    ///
+    /// Consider using
+    /// [`Configurable::tls_server_name`](super::Configurable::tls_server_name)
+    /// instead, which keeps verification enabled by checking the certificate
+    /// against a name of your choosing.
+    ///
+    /// # Warning
+    ///
+    /// Any valid certificate for any site will be trusted for use by servers
+    /// reached by IP address. This introduces a significant vulnerability to
+    /// man-in-the-middle attacks on such requests.
+    pub const DANGER_ACCEPT_INVALID_IP_HOSTS: Self = SslOption(0b1000);
+
+    pub(crate) const fn contains(self, other: Self) -> bool {
         (self.0 & other.0) == other.0
     }
 }
@@ -232,9 +356,62 @@ impl SetOpt for SslOption {
     }
 }
 
+/// A name to present to the server and verify its certificate against during
+/// the TLS handshake instead of the host of the request URI.
+#[derive(Clone, Debug)]
+pub(crate) struct TlsServerName(pub(crate) String);
+
+impl TlsServerName {
+    /// Get the URI to hand to curl so that it uses this name, together with
+    /// the mapping that makes it connect to the host of the original URI
+    /// anyway.
+    ///
+    /// Returns `None` if the URI has no host or the name is not a valid host.
+    pub(crate) fn route(&self, uri: &Uri) -> Option<(Uri, ConnectTo)> {
+        let authority = uri.authority()?;
+        let host = authority.host();
+        let port = authority.port_u16().unwrap_or_else(|| {
+            if uri.scheme_str() == Some("http") {
+                80
+            } else {
+                443
+            }
+        });
+
+        // Keep any user info and explicit port of the original URI.
+        let user_info = match authority.as_str().rfind('@') {
+            Some(i) => &authority.as_str()[..=i],
+            None => "",
+        };
+        let server_authority = match authority.port_u16() {
+            Some(port) => format!("{}{}:{}", user_info, self.0, port),
+            None => format!("{}{}", user_info, self.0),
+        };
+
+        let mut parts = uri.clone().into_parts();
+        parts.authority = Some(server_authority.parse().ok()?);
+        let server_uri = Uri::from_parts(parts).ok()?;
+
+        // The name must not change where the request is sent otherwise.
+        if server_uri.host()? != self.0 {
+            return None;
+        }
+
+        Some((server_uri, ConnectTo::new(&self.0, port, host, port)))
+    }
+}
+
+/// Check whether the host of the given URI is a literal IP address.
+pub(crate) fn is_ip_host(uri: &Uri) -> bool {
+    uri.host()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .and_then(|host| host.parse::<IpAddr>().ok())
+        .is_some()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SslOption;
+    use super::*;
 
     #[test]
     fn default_ssl_options() {
@@ -257,4 +434,46 @@ mod tests {
         assert!(!options.contains(SslOption::DANGER_ACCEPT_INVALID_CERTS));
         assert!(options.contains(SslOption::DANGER_ACCEPT_INVALID_HOSTS));
     }
+
+    #[cfg(feature = "tls-psk")]
+    #[test]
+    fn pre_shared_key_is_not_debug_printed() {
+        let psk = super::PreSharedKey::new("device", b"hunter2".to_vec());
+
+        assert_eq!((psk.key)(None), Some(b"hunter2".to_vec()));
+        assert_eq!(
+            format!("{:?}", psk),
+            "PreSharedKey { identity: \"device\" }"
+        );
+    }
+
+    #[test]
+    fn tls_server_name_routes_to_original_host() {
+        let name = TlsServerName(String::from("api.example.org"));
+
+        let (uri, connect_to) = name
+            .route(&"https://192.0.2.1/a?b=c".parse().unwrap())
+            .unwrap();
+        assert_eq!(uri, "https://api.example.org/a?b=c");
+        assert_eq!(connect_to.to_string(), "api.example.org:443:192.0.2.1:443");
+
+        let (uri, connect_to) = name
+            .route(&"http://user@[2001:db8::1]:8080/".parse().unwrap())
+            .unwrap();
+        assert_eq!(uri, "http://user@api.example.org:8080/");
+        assert_eq!(
+            connect_to.to_string(),
+            "api.example.org:8080:[2001:db8::1]:8080"
+        );
+
+        let name = TlsServerName(String::from("evil.example/path"));
+        assert!(name.route(&"https://192.0.2.1/".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn ip_hosts_are_recognized() {
+        assert!(is_ip_host(&"https://192.0.2.1/".parse().unwrap()));
+        assert!(is_ip_host(&"https://[2001:db8::1]:8443/".parse().unwrap()));
+        assert!(!is_ip_host(&"https://example.org/".parse().unwrap()));
+    }
 }
@@ -0,0 +1,273 @@
+//! Validation of client configuration when a client is built.
+//!
+//! Most options are only handed over to curl when a request is sent, so an
+//! unsupported or contradictory combination of options would otherwise only
+//! surface as an obscure curl error on the first request, or not at all. This
+//! checks the options up front and collects every problem it finds, so that
+//! they can all be fixed at once.
+
+use super::{
+    proxy::Proxy,
+    AcceptEncoding,
+    AutomaticDecompression,
+    ClientCertificate,
+    Dialer,
+    MaxDecompressedSize,
+    MaxDecompressionRatio,
+    SslOption,
+    VersionNegotiation,
+};
+use crate::error::ConfigValidationError;
+use http::{Extensions, Uri};
+
+/// Proxy schemes understood by curl.
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+/// Validate the given client options against each other and against the
+/// features of the libcurl in use.
+pub(crate) fn validate(options: &Extensions) -> Result<(), ConfigValidationError> {
+    let version = curl::Version::get();
+    let mut problems = Vec::new();
+
+    if let Some(reason) = options
+        .get::<VersionNegotiation>()
+        .and_then(|negotiation| negotiation.unsupported_reason(&version))
+    {
+        problems.push(reason.to_owned());
+    }
+
+    let proxy = options
+        .get::<Proxy<Option<Uri>>>()
+        .and_then(|proxy| proxy.0.as_ref());
+
+    if let Some(proxy) = proxy {
+        if let Some(scheme) = proxy.scheme_str() {
+            if !PROXY_SCHEMES.contains(&scheme) {
+                problems.push(format!("proxy scheme `{}` is not supported", scheme));
+            }
+        }
+    }
+
+    if let Some(dialer) = options.get::<Dialer>() {
+        if dialer.is_unix_socket() {
+            if !version.feature_unix_domain_socket() {
+                problems.push(String::from(
+                    "Unix sockets are not supported by this build of libcurl",
+                ));
+            }
+
+            if proxy.is_some() {
+                problems.push(String::from(
+                    "a proxy cannot be used when connecting to a Unix socket",
+                ));
+            }
+        }
+
+        if dialer.is_custom() && proxy.is_some() {
+            problems.push(String::from(
+                "a proxy cannot be used together with a custom dialer",
+            ));
+        }
+    }
+
+    let decompression = options
+        .get::<AutomaticDecompression>()
+        .map(|d| d.0)
+        .unwrap_or(false);
+
+    if !decompression {
+        if options.get::<MaxDecompressedSize>().is_some() {
+            problems.push(String::from(
+                "max_decompressed_size requires automatic decompression to be enabled",
+            ));
+        }
+
+        if options.get::<MaxDecompressionRatio>().is_some() {
+            problems.push(String::from(
+                "max_decompression_ratio requires automatic decompression to be enabled",
+            ));
+        }
+    }
+
+    if let Some(AcceptEncoding(encodings)) = options.get() {
+        if http::HeaderValue::from_str(encodings).is_err() {
+            problems.push(format!(
+                "accept_encoding contains characters not allowed in a header: {:?}",
+                encodings
+            ));
+        }
+    }
+
+    if options.get::<super::ssl::Engine>().is_some() && !is_openssl(&version) {
+        problems.push(String::from(
+            "SSL engines and providers require libcurl to use OpenSSL",
+        ));
+    }
+
+    // With the `rustls` feature, libcurl is compiled from source to use
+    // rustls, so anything else means that some other libcurl was linked.
+    #[cfg(feature = "rustls")]
+    {
+        if !is_rustls(&version) {
+            problems.push(format!(
+                "isahc was built with the `rustls` feature, but libcurl uses {}",
+                version.ssl_version().unwrap_or("no TLS backend")
+            ));
+        }
+    }
+
+    if is_rustls(&version) {
+        check_rustls(options, &mut problems);
+    }
+
+    #[cfg(feature = "tls-psk")]
+    {
+        if options.get::<super::PreSharedKey>().is_some() {
+            if !crate::psk::is_supported() {
+                problems.push(String::from(
+                    "pre-shared keys require libcurl to use OpenSSL",
+                ));
+            }
+
+            if options.get::<super::ClientCertificate>().is_some() {
+                problems.push(String::from(
+                    "a client certificate cannot be used together with a pre-shared key",
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigValidationError::new(problems))
+    }
+}
+
+/// Check for TLS options that cannot be used when libcurl uses rustls.
+fn check_rustls(options: &Extensions, problems: &mut Vec<String>) {
+    if options
+        .get::<ClientCertificate>()
+        .filter(|certificate| certificate.is_pkcs12())
+        .is_some()
+    {
+        problems.push(String::from(
+            "PKCS#12 client certificates are not supported when libcurl uses rustls",
+        ));
+    }
+
+    if options
+        .get::<SslOption>()
+        .filter(|options| {
+            (options.contains(SslOption::DANGER_ACCEPT_INVALID_HOSTS)
+                || options.contains(SslOption::DANGER_ACCEPT_INVALID_IP_HOSTS))
+                && !options.contains(SslOption::DANGER_ACCEPT_INVALID_CERTS)
+        })
+        .is_some()
+    {
+        problems.push(String::from(
+            "host name verification cannot be disabled on its own when libcurl uses rustls",
+        ));
+    }
+}
+
+fn is_openssl(version: &curl::Version) -> bool {
+    version
+        .ssl_version()
+        .map(|version| version.starts_with("OpenSSL/"))
+        .unwrap_or(false)
+}
+
+fn is_rustls(version: &curl::Version) -> bool {
+    version
+        .ssl_version()
+        .map(|version| version.starts_with("rustls"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_options_are_valid() {
+        let mut options = Extensions::new();
+        options.insert(VersionNegotiation::default());
+        options.insert(AutomaticDecompression(true));
+
+        assert!(validate(&options).is_ok());
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let mut options = Extensions::new();
+        options.insert(Proxy(Some(
+            "ftp://proxy.example.org".parse::<Uri>().unwrap(),
+        )));
+        options.insert(AutomaticDecompression(false));
+        options.insert(MaxDecompressedSize(1024));
+        options.insert(MaxDecompressionRatio(10.0));
+
+        let error = validate(&options).unwrap_err();
+
+        assert_eq!(error.problems(), &[
+            "proxy scheme `ftp` is not supported",
+            "max_decompressed_size requires automatic decompression to be enabled",
+            "max_decompression_ratio requires automatic decompression to be enabled",
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn proxy_with_unix_socket_is_rejected() {
+        let mut options = Extensions::new();
+        options.insert(Proxy(Some("http://proxy:80".parse::<Uri>().unwrap())));
+        options.insert(Dialer::unix_socket("/var/run/docker.sock"));
+
+        let error = validate(&options).unwrap_err();
+
+        assert!(
+            error
+                .problems()
+                .iter()
+                .any(|problem| problem.contains("Unix socket"))
+        );
+    }
+
+    #[test]
+    fn proxy_with_custom_dialer_is_rejected() {
+        let mut options = Extensions::new();
+        options.insert(Proxy(Some("http://proxy:80".parse::<Uri>().unwrap())));
+        options.insert(Dialer::custom(|_: &crate::config::DialTarget<'_>| {
+            Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+        }));
+
+        let error = validate(&options).unwrap_err();
+
+        assert_eq!(error.problems(), &[
+            "a proxy cannot be used together with a custom dialer",
+        ]);
+    }
+
+    #[test]
+    fn tls_options_unsupported_by_rustls_are_rejected() {
+        let mut options = Extensions::new();
+        options.insert(ClientCertificate::pem_file("client.pem", None));
+        options.insert(
+            SslOption::DANGER_ACCEPT_INVALID_CERTS | SslOption::DANGER_ACCEPT_INVALID_HOSTS,
+        );
+
+        let mut problems = Vec::new();
+        check_rustls(&options, &mut problems);
+        assert!(problems.is_empty());
+
+        options.insert(ClientCertificate::p12_file("client.p12", None));
+        options.insert(SslOption::DANGER_ACCEPT_INVALID_HOSTS);
+
+        check_rustls(&options, &mut problems);
+        assert_eq!(problems, &[
+            "PKCS#12 client certificates are not supported when libcurl uses rustls",
+            "host name verification cannot be disabled on its own when libcurl uses rustls",
+        ]);
+    }
+}
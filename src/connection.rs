@@ -0,0 +1,264 @@
+//! Reporting on the connection used to perform a request.
+
+use http::Version;
+use std::net::{IpAddr, SocketAddr};
+
+/// A summary of how the connection used for a request was established.
+///
+/// A connection report can be useful for diagnosing network problems, such as
+/// a slow or flaky address returned by name resolution, an unexpected TLS
+/// negotiation, or whether a proxy was involved. To obtain a report, enable
+/// collection using
+/// [`Configurable::connection_report`](crate::config::Configurable::connection_report)
+/// and then use
+/// [`ResponseExt::connection_report`](crate::ResponseExt::connection_report)
+/// on the response.
+///
+/// Some of this information is gathered from diagnostic messages emitted by
+/// curl during the transfer, so details that a particular curl build or TLS
+/// backend does not report will be unavailable.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionReport {
+    ips_tried: Vec<IpAddr>,
+    pub(crate) remote_addr: Option<SocketAddr>,
+    tls_protocol: Option<String>,
+    tls_cipher: Option<String>,
+    pub(crate) http_version: Option<Version>,
+    pub(crate) proxy_used: Option<bool>,
+    pub(crate) reused: bool,
+}
+
+impl ConnectionReport {
+    /// Get the resolved IP addresses that a connection was attempted to, in
+    /// the order they were tried.
+    ///
+    /// This is empty if an existing connection was reused.
+    pub fn ips_tried(&self) -> &[IpAddr] {
+        &self.ips_tried
+    }
+
+    /// Get the number of connection attempts made before a connection was
+    /// established.
+    pub fn connect_attempts(&self) -> usize {
+        self.ips_tried.len()
+    }
+
+    /// Get the remote address of the connection that was ultimately used.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Get the IP address of the connection that was ultimately used.
+    pub fn chosen_ip(&self) -> Option<IpAddr> {
+        self.remote_addr.map(|addr| addr.ip())
+    }
+
+    /// Get the TLS protocol version negotiated for the connection, such as
+    /// `TLSv1.3`, if the connection was secured.
+    pub fn tls_protocol(&self) -> Option<&str> {
+        self.tls_protocol.as_deref()
+    }
+
+    /// Get the name of the TLS cipher suite negotiated for the connection, if
+    /// the connection was secured.
+    pub fn tls_cipher(&self) -> Option<&str> {
+        self.tls_cipher.as_deref()
+    }
+
+    /// Get the HTTP version used for the request.
+    pub fn http_version(&self) -> Option<Version> {
+        self.http_version
+    }
+
+    /// Check whether the request was sent through a proxy.
+    ///
+    /// Returns `None` if the version of curl in use is unable to report this.
+    pub fn proxy_used(&self) -> Option<bool> {
+        self.proxy_used
+    }
+
+    /// Check whether an existing connection from the connection cache was
+    /// reused for the request instead of opening a new one.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Update the report from a line of informational text produced by curl.
+    pub(crate) fn record_debug_text(&mut self, text: &str) {
+        const SSL_CONNECTION: &str = "SSL connection using ";
+
+        let text = text.trim();
+
+        if let Some(ip) = parse_trying(text) {
+            self.ips_tried.push(ip);
+        } else if text.starts_with(SSL_CONNECTION) {
+            let mut parts = text[SSL_CONNECTION.len()..].split(" / ").map(str::trim);
+
+            self.tls_protocol = parts.next().map(String::from);
+            self.tls_cipher = parts.next().map(String::from);
+        }
+    }
+}
+
+/// The outcome of an attempt to connect to one of the addresses a host name
+/// resolved to.
+///
+/// When connecting to a host fails, the attempts made for each address can be
+/// retrieved using [`Error::connect_attempts`](crate::Error::connect_attempts).
+/// This helps with diagnosing problems with hosts that have multiple
+/// addresses, such as a dual-stack host whose IPv6 address is unreachable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConnectAttempt {
+    ip: IpAddr,
+    error: Option<String>,
+}
+
+impl ConnectAttempt {
+    /// Get the IP address that a connection was attempted to.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Get the reason why connecting to this address failed, such as
+    /// `Connection refused`, as reported by curl.
+    ///
+    /// Returns `None` if no reason was reported, such as if the attempt was
+    /// still in progress when the connect timeout was reached.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Update a list of connection attempts from a line of informational text
+/// produced by curl.
+pub(crate) fn record_connect_attempt(attempts: &mut Vec<ConnectAttempt>, text: &str) {
+    // Newer versions of curl report a failure with a message like
+    // "connect to 192.0.2.1 port 443 failed: Connection refused", and older
+    // versions with "Immediate connect fail for 192.0.2.1: Connection refused".
+    const CONNECT_TO: &str = "connect to ";
+    const IMMEDIATE_FAIL: &str = "Immediate connect fail for ";
+
+    let text = text.trim();
+
+    if let Some(ip) = parse_trying(text) {
+        attempts.push(ConnectAttempt { ip, error: None });
+        return;
+    }
+
+    let (addr, reason) = if text.starts_with(CONNECT_TO) {
+        let rest = &text[CONNECT_TO.len()..];
+
+        match rest.find(" failed: ") {
+            Some(index) => (
+                rest.split_whitespace().next(),
+                &rest[index + " failed: ".len()..],
+            ),
+            None => return,
+        }
+    } else if text.starts_with(IMMEDIATE_FAIL) {
+        let mut parts = text[IMMEDIATE_FAIL.len()..].splitn(2, ": ");
+
+        match (parts.next(), parts.next()) {
+            (Some(addr), Some(reason)) => (Some(addr), reason),
+            _ => return,
+        }
+    } else {
+        return;
+    };
+
+    let ip = match addr.and_then(|addr| addr.parse::<IpAddr>().ok()) {
+        Some(ip) => ip,
+        None => return,
+    };
+
+    // Attribute the failure to the most recent pending attempt for the
+    // address.
+    if let Some(attempt) = attempts
+        .iter_mut()
+        .rev()
+        .find(|attempt| attempt.ip == ip && attempt.error.is_none())
+    {
+        attempt.error = Some(reason.trim().to_owned());
+    }
+}
+
+/// Parse the address from a line produced by curl when starting a connection
+/// attempt, such as "Trying 192.0.2.1:443...".
+fn parse_trying(text: &str) -> Option<IpAddr> {
+    const TRYING: &str = "Trying ";
+
+    if !text.starts_with(TRYING) {
+        return None;
+    }
+
+    let addr = text[TRYING.len()..].trim_end_matches('.');
+
+    // Older versions of curl do not include the port.
+    addr.parse::<SocketAddr>()
+        .map(|addr| addr.ip())
+        .or_else(|_| addr.parse::<IpAddr>())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_debug_text() {
+        let mut report = ConnectionReport::default();
+
+        report.record_debug_text("  Trying 192.0.2.1:443...\n");
+        report.record_debug_text("  Trying [2001:db8::1]:443...\n");
+        report.record_debug_text("  Trying 192.0.2.2...\n");
+        report.record_debug_text("Connected to example.org (192.0.2.2) port 443\n");
+        report.record_debug_text(
+            "SSL connection using TLSv1.3 / TLS_AES_256_GCM_SHA384 / x25519 / RSASSA-PSS\n",
+        );
+
+        assert_eq!(
+            report.ips_tried(),
+            &[
+                "192.0.2.1".parse::<IpAddr>().unwrap(),
+                "2001:db8::1".parse().unwrap(),
+                "192.0.2.2".parse().unwrap(),
+            ]
+        );
+        assert_eq!(report.connect_attempts(), 3);
+        assert_eq!(report.tls_protocol(), Some("TLSv1.3"));
+        assert_eq!(report.tls_cipher(), Some("TLS_AES_256_GCM_SHA384"));
+    }
+
+    #[test]
+    fn record_connect_attempts() {
+        let mut attempts = Vec::new();
+
+        for line in &[
+            "  Trying [2001:db8::1]:443...\n",
+            "  Trying 192.0.2.1:443...\n",
+            "connect to 192.0.2.1 port 443 from 0.0.0.0 port 0 failed: Connection refused\n",
+            "connect to 2001:db8::1 port 443 failed: Connection timed out\n",
+            "  Trying 192.0.2.2...\n",
+            "Immediate connect fail for 192.0.2.2: Network is unreachable\n",
+            "  Trying 192.0.2.3:443...\n",
+            "Failed to connect to example.org port 443: Couldn't connect to server\n",
+        ] {
+            record_connect_attempt(&mut attempts, line);
+        }
+
+        let summary = attempts
+            .iter()
+            .map(|attempt| (attempt.ip().to_string(), attempt.error()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("2001:db8::1".to_owned(), Some("Connection timed out")),
+                ("192.0.2.1".to_owned(), Some("Connection refused")),
+                ("192.0.2.2".to_owned(), Some("Network is unreachable")),
+                ("192.0.2.3".to_owned(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! Rendering of requests and configuration as options of the `curl` command
+//! line tool.
+
+use crate::{
+    auth::{Authentication, Credentials},
+    config::{
+        dial::ConnectTo,
+        dns::ResolveMap,
+        proxy::{Blacklist, Proxy},
+        ssl::{Ciphers, Engine, TlsServerName},
+        AcceptEncoding,
+        AutomaticDecompression,
+        CaCertificate,
+        ClientCertificate,
+        ConnectTimeout,
+        IdleTimeout,
+        IpVersion,
+        RedirectPolicy,
+        SslOption,
+        Timeout,
+        UserAgent,
+        VersionNegotiation,
+    },
+    response::PeekBody,
+};
+use http::{header::HeaderName, Extensions, HeaderValue, Method, Request};
+use std::{borrow::Cow, str, time::Duration};
+
+/// Short names of the curl options that are usually written that way on the
+/// command line.
+pub(crate) const SHORT_NAMES: &[(&str, &str)] = &[
+    ("4", "ipv4"),
+    ("6", "ipv6"),
+    ("A", "user-agent"),
+    ("H", "header"),
+    ("L", "location"),
+    ("U", "proxy-user"),
+    ("X", "request"),
+    ("k", "insecure"),
+    ("u", "user"),
+    ("x", "proxy"),
+];
+
+/// An option of the curl command line tool, identified by its long name.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CurlOption {
+    pub(crate) name: &'static str,
+    pub(crate) value: Option<String>,
+}
+
+impl CurlOption {
+    fn flag(name: &'static str) -> Self {
+        Self {
+            name,
+            value: None,
+        }
+    }
+
+    fn new(name: &'static str, value: impl Into<String>) -> Self {
+        Self {
+            name,
+            value: Some(value.into()),
+        }
+    }
+
+    /// Create an option that sends the given header.
+    pub(crate) fn header(name: &HeaderName, value: &HeaderValue) -> Self {
+        Self::header_with("header", name, value)
+    }
+
+    /// Create an option that sends the given header to the proxy only.
+    fn proxy_header(name: &HeaderName, value: &HeaderValue) -> Self {
+        Self::header_with("proxy-header", name, value)
+    }
+
+    fn header_with(option: &'static str, name: &HeaderName, value: &HeaderValue) -> Self {
+        let value = String::from_utf8_lossy(value.as_bytes());
+
+        // An empty header value is written with a semicolon, since a header
+        // without any value is removed by curl instead.
+        if value.is_empty() {
+            Self::new(option, format!("{};", name))
+        } else {
+            Self::new(option, format!("{}: {}", name, value))
+        }
+    }
+}
+
+/// A set of configuration options, such as the extensions of a request or the
+/// defaults of a client.
+pub(crate) trait Options {
+    fn option<T: Send + Sync + 'static>(&self) -> Option<&T>;
+}
+
+impl Options for Extensions {
+    fn option<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.get()
+    }
+}
+
+/// Render the given request as a `curl` command line.
+///
+/// Only configuration stored in the request itself is included, since the
+/// defaults of the client that will send it are not known here.
+pub(crate) fn to_curl_command<T: PeekBody>(request: &Request<T>) -> String {
+    let mut args = vec![String::from("curl")];
+
+    let body = request.body().buffered_body();
+    let has_body = body.map(|body| !body.is_empty()).unwrap_or(true);
+
+    // Curl sends a POST request when a body is given, and a GET request
+    // otherwise.
+    let default_method = if has_body { Method::POST } else { Method::GET };
+
+    if request.method() == Method::HEAD {
+        push_option(&mut args, CurlOption::flag("head"));
+    } else if request.method() != default_method {
+        push_option(
+            &mut args,
+            CurlOption::new("request", request.method().as_str()),
+        );
+    }
+
+    let mut options = Vec::new();
+
+    // A TLS server name is given to curl by sending the request to that name
+    // while connecting to the original host.
+    match request
+        .extensions()
+        .get::<TlsServerName>()
+        .and_then(|name| name.route(request.uri()))
+    {
+        Some((uri, route)) => {
+            let route = match request.extensions().get::<ConnectTo>() {
+                Some(connect_to) => route.followed_by(connect_to),
+                None => route,
+            };
+
+            args.push(quote(uri.to_string().into()));
+            options.push(CurlOption::new("connect-to", route.to_string()));
+        }
+        None => args.push(quote(request.uri().to_string().into())),
+    }
+
+    for (name, value) in request.headers() {
+        options.push(CurlOption::header(name, value));
+    }
+
+    if has_body {
+        options.push(match body.and_then(text) {
+            Some(text) => CurlOption::new("data-raw", text),
+            // A body that is not in memory or is binary data cannot be passed
+            // as an argument, so it has to be given on standard input.
+            None => CurlOption::new("data-binary", "@-"),
+        });
+    }
+
+    options.extend(config_options(request.extensions()));
+
+    for option in options {
+        push_option(&mut args, option);
+    }
+
+    args.join(" ")
+}
+
+/// Get the curl options equivalent to the given configuration.
+pub(crate) fn config_options(config: &impl Options) -> Vec<CurlOption> {
+    let mut options = Vec::new();
+
+    match config.option::<UserAgent>() {
+        Some(UserAgent(Some(user_agent))) => {
+            options.push(CurlOption::new(
+                "user-agent",
+                String::from_utf8_lossy(user_agent.as_bytes()),
+            ));
+        }
+        Some(UserAgent(None)) => {
+            options.push(CurlOption::new("user-agent", ""));
+        }
+        None => {}
+    }
+
+    if config
+        .option::<AutomaticDecompression>()
+        .map(|AutomaticDecompression(enabled)| *enabled)
+        .unwrap_or(true)
+    {
+        options.push(CurlOption::flag("compressed"));
+    }
+
+    match config.option::<AcceptEncoding>() {
+        Some(AcceptEncoding(encodings)) if encodings.is_empty() => {
+            options.push(CurlOption::new("header", "accept-encoding:"));
+        }
+        Some(AcceptEncoding(encodings)) => {
+            options.push(CurlOption::new(
+                "header",
+                format!("accept-encoding: {}", encodings),
+            ));
+        }
+        None => {}
+    }
+
+    if let Some(Timeout(timeout)) = config.option() {
+        options.push(CurlOption::new("max-time", seconds(*timeout)));
+    }
+
+    if let Some(ConnectTimeout(timeout)) = config.option() {
+        options.push(CurlOption::new("connect-timeout", seconds(*timeout)));
+    }
+
+    if let Some(timeout) = config.option::<IdleTimeout>() {
+        options.push(CurlOption::new("speed-limit", "1"));
+        options.push(CurlOption::new("speed-time", timeout.secs().to_string()));
+    }
+
+    match config.option::<RedirectPolicy>() {
+        Some(RedirectPolicy::Follow) => {
+            options.push(CurlOption::flag("location"));
+        }
+        Some(RedirectPolicy::Limit(max)) => {
+            options.push(CurlOption::flag("location"));
+            options.push(CurlOption::new("max-redirs", max.to_string()));
+        }
+        _ => {}
+    }
+
+    if let Some(name) = config
+        .option::<VersionNegotiation>()
+        .and_then(VersionNegotiation::curl_option)
+    {
+        options.push(CurlOption::flag(name));
+    }
+
+    match config.option::<IpVersion>() {
+        Some(IpVersion::V4) => options.push(CurlOption::flag("ipv4")),
+        Some(IpVersion::V6) => options.push(CurlOption::flag("ipv6")),
+        _ => {}
+    }
+
+    if let Some(connect_to) = config.option::<ConnectTo>() {
+        options.push(CurlOption::new("connect-to", connect_to.to_string()));
+    }
+
+    if let Some(map) = config.option::<ResolveMap>() {
+        options.extend(
+            map.entries()
+                .iter()
+                .map(|entry| CurlOption::new("resolve", entry.clone())),
+        );
+    }
+
+    if let Some(authentication) = config.option::<Authentication>() {
+        options.extend(authentication_option(authentication, false));
+    }
+
+    if let Some(credentials) = config.option::<Credentials>() {
+        options.push(CurlOption::new("user", user(credentials)));
+    }
+
+    match config.option::<Proxy<Option<http::Uri>>>() {
+        Some(Proxy(Some(proxy))) => options.push(CurlOption::new("proxy", proxy.to_string())),
+        Some(Proxy(None)) => options.push(CurlOption::new("noproxy", "*")),
+        None => {}
+    }
+
+    if let Some(blacklist) = config.option::<Blacklist>() {
+        options.push(CurlOption::new("noproxy", blacklist.skip.as_str()));
+    }
+
+    if let Some(Proxy(authentication)) = config.option::<Proxy<Authentication>>() {
+        options.extend(authentication_option(authentication, true));
+    }
+
+    if let Some(Proxy(credentials)) = config.option::<Proxy<Credentials>>() {
+        options.push(CurlOption::new("proxy-user", user(credentials)));
+    }
+
+    if let Some(Proxy(headers)) = config.option::<Proxy<http::HeaderMap>>() {
+        options.extend(
+            headers
+                .iter()
+                .map(|(name, value)| CurlOption::proxy_header(name, value)),
+        );
+    }
+
+    if let Some(ssl_options) = config.option::<SslOption>() {
+        // Curl only has a single option for disabling both checks.
+        if ssl_options.contains(SslOption::DANGER_ACCEPT_INVALID_CERTS)
+            || ssl_options.contains(SslOption::DANGER_ACCEPT_INVALID_HOSTS)
+        {
+            options.push(CurlOption::flag("insecure"));
+        }
+
+        if ssl_options.contains(SslOption::DANGER_ACCEPT_REVOKED_CERTS) {
+            options.push(CurlOption::flag("ssl-no-revoke"));
+        }
+    }
+
+    if let Some(certificate) = config.option::<CaCertificate>() {
+        options.push(CurlOption::new(
+            "cacert",
+            certificate.path.to_string_lossy(),
+        ));
+    }
+
+    if let Some(certificate) = config.option::<ClientCertificate>() {
+        options.push(CurlOption::new("cert", certificate.path.to_string_lossy()));
+        options.push(CurlOption::new("cert-type", certificate.format));
+
+        if let Some(key) = certificate.private_key.as_ref() {
+            options.push(CurlOption::new("key", key.path.to_string_lossy()));
+            options.push(CurlOption::new("key-type", key.format));
+        }
+
+        // Curl uses the same password for the certificate and the key.
+        let password = certificate
+            .password
+            .as_ref()
+            .or_else(|| certificate.private_key.as_ref()?.password.as_ref());
+
+        if let Some(password) = password {
+            options.push(CurlOption::new("pass", password.as_str()));
+        }
+    }
+
+    if let Some(Engine(engine)) = config.option() {
+        options.push(CurlOption::new("engine", engine.as_str()));
+    }
+
+    if let Some(Ciphers(ciphers)) = config.option() {
+        options.push(CurlOption::new("ciphers", ciphers.as_str()));
+    }
+
+    options
+}
+
+/// Get the curl option that enables the given authentication schemes, either
+/// for the server or for the proxy.
+fn authentication_option(authentication: &Authentication, proxy: bool) -> Option<CurlOption> {
+    #[allow(unused_mut)]
+    let mut schemes = vec![
+        (Authentication::basic(), "basic", "proxy-basic"),
+        (Authentication::digest(), "digest", "proxy-digest"),
+    ];
+
+    #[cfg(feature = "spnego")]
+    schemes.push((Authentication::negotiate(), "negotiate", "proxy-negotiate"));
+
+    #[cfg(feature = "ntlm")]
+    schemes.push((Authentication::ntlm(), "ntlm", "proxy-ntlm"));
+
+    let enabled = schemes
+        .into_iter()
+        .filter(|(scheme, ..)| authentication.contains(scheme.clone()))
+        .map(|(_, name, proxy_name)| if proxy { proxy_name } else { name })
+        .collect::<Vec<_>>();
+
+    match enabled.as_slice() {
+        [] => None,
+        [name] => Some(CurlOption::flag(name)),
+        _ if proxy => Some(CurlOption::flag("proxy-anyauth")),
+        _ => Some(CurlOption::flag("anyauth")),
+    }
+}
+
+/// Add the given option to a command line.
+fn push_option(args: &mut Vec<String>, option: CurlOption) {
+    match SHORT_NAMES.iter().find(|(_, name)| *name == option.name) {
+        Some((short, _)) => args.push(format!("-{}", short)),
+        None => args.push(format!("--{}", option.name)),
+    }
+
+    if let Some(value) = option.value {
+        args.push(quote(value.into()));
+    }
+}
+
+/// Quote the given argument for a POSIX shell, if necessary.
+fn quote(arg: Cow<'_, str>) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"%+,-./:=@_".contains(&b));
+
+    if safe {
+        arg.into_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r#"'\''"#))
+    }
+}
+
+fn user(credentials: &Credentials) -> String {
+    format!("{}:{}", credentials.username, credentials.password)
+}
+
+fn seconds(duration: Duration) -> String {
+    duration.as_secs_f64().to_string()
+}
+
+/// Get the given body as text, or `None` if it is binary data.
+fn text(body: &[u8]) -> Option<&str> {
+    str::from_utf8(body).ok().filter(|text| {
+        !text
+            .chars()
+            .any(|c| c.is_control() && !c.is_ascii_whitespace())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Configurable, AsyncBody, Body};
+
+    #[test]
+    fn get_request() {
+        let request = Request::get("https://example.org/search?q=it's")
+            .header("accept", "application/json")
+            .header("x-empty", "")
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl 'https://example.org/search?q=it'\\''s' -H 'accept: application/json' \
+             -H 'x-empty;' --compressed"
+        );
+    }
+
+    #[test]
+    fn request_bodies() {
+        let request = Request::post("https://example.org")
+            .body(r#"{"name":"@isahc"}"#)
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            r#"curl https://example.org/ --data-raw '{"name":"@isahc"}' --compressed"#
+        );
+
+        let request = Request::put("https://example.org")
+            .body(Body::from(vec![0, 1, 2]))
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl -X PUT https://example.org/ --data-binary @- --compressed"
+        );
+
+        let request = Request::post("https://example.org")
+            .body(AsyncBody::from_reader(futures_lite::io::empty()))
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl https://example.org/ --data-binary @- --compressed"
+        );
+
+        let request = Request::delete("https://example.org").body(()).unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl -X DELETE https://example.org/ --compressed"
+        );
+    }
+
+    #[test]
+    fn request_options() {
+        let request = Request::head("https://example.org")
+            .decompress(false)
+            .timeout(Duration::from_millis(2500))
+            .redirect_policy(RedirectPolicy::Limit(5))
+            .version_negotiation(VersionNegotiation::http11())
+            .credentials(Credentials::new("user", "secret"))
+            .authentication(Authentication::basic())
+            .proxy(Some("http://proxy:3128".parse().unwrap()))
+            .ssl_options(SslOption::DANGER_ACCEPT_INVALID_CERTS)
+            .ssl_client_certificate(ClientCertificate::p12_file("client.p12", "pass".to_owned()))
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl --head https://example.org/ --max-time 2.5 -L --max-redirs 5 --http1.1 \
+             --basic -u user:secret -x http://proxy:3128/ -k --cert client.p12 \
+             --cert-type P12 --pass pass"
+        );
+    }
+
+    #[test]
+    fn idle_timeout_is_a_speed_limit() {
+        let request = Request::get("https://example.org")
+            .idle_timeout(Duration::from_millis(1500))
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl https://example.org/ --compressed --speed-limit 1 --speed-time 2"
+        );
+    }
+
+    #[test]
+    fn accept_encoding_is_sent_as_header() {
+        let request = Request::get("https://example.org")
+            .accept_encoding(&["gzip", "br"])
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl https://example.org/ --compressed -H 'accept-encoding: gzip, br'"
+        );
+
+        let request = Request::get("https://example.org")
+            .accept_encoding(&[])
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl https://example.org/ --compressed -H accept-encoding:"
+        );
+    }
+
+    #[test]
+    fn tls_server_name_uses_connect_to() {
+        let request = Request::get("https://192.0.2.1:8443/status")
+            .tls_server_name("api.example.org")
+            .connect_to("192.0.2.1", 8443, "192.0.2.2", 443)
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            to_curl_command(&request),
+            "curl https://api.example.org:8443/status \
+             --connect-to api.example.org:8443:192.0.2.2:443 --compressed \
+             --connect-to 192.0.2.1:8443:192.0.2.2:443"
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Parsing and formatting of the dates used in HTTP headers.
+//!
+//! Headers such as `Date`, `Expires`, `Last-Modified`, and `Retry-After` carry
+//! dates in the format described in [RFC 7231, section
+//! 7.1.1.1](https://tools.ietf.org/html/rfc7231#section-7.1.1.1). Dates are
+//! always in UTC and have a precision of one second.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Parse a date in any of the formats allowed in HTTP headers.
+///
+/// The preferred format is `Sun, 06 Nov 1994 08:49:37 GMT`. The obsolete RFC
+/// 850 format `Sunday, 06-Nov-94 08:49:37 GMT` and the asctime format `Sun Nov
+/// 6 08:49:37 1994` are accepted as well. The day of the week is not checked.
+///
+/// Returns `None` if the date is invalid or earlier than 1970.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = isahc::date::parse("Sun, 06 Nov 1994 08:49:37 GMT");
+///
+/// assert_eq!(time, Some(UNIX_EPOCH + Duration::from_secs(784_111_777)));
+/// ```
+pub fn parse(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+
+    let (day, month, year, time) = if let Some(comma) = value.find(',') {
+        let mut parts = value[comma + 1..].split_whitespace();
+        let date = parts.next()?;
+
+        let (day, month, year) = if date.contains('-') {
+            let mut date = date.split('-');
+            let day = date.next()?;
+            let month = date.next()?;
+            let year = date.next()?;
+
+            if year.len() != 2 || date.next().is_some() {
+                return None;
+            }
+
+            // Two-digit years are ambiguous, but dates before 1970 are
+            // meaningless here anyway.
+            let year = match year.parse::<u64>().ok()? {
+                year if year < 70 => 2000 + year,
+                year => 1900 + year,
+            };
+
+            (day, month, year)
+        } else {
+            let month = parts.next()?;
+            let year = parts.next()?;
+
+            if year.len() != 4 {
+                return None;
+            }
+
+            (date, month, year.parse().ok()?)
+        };
+
+        let time = parts.next()?;
+
+        if parts.next()? != "GMT" || parts.next().is_some() {
+            return None;
+        }
+
+        (day, month, year, time)
+    } else {
+        let mut parts = value.split_whitespace().skip(1);
+        let month = parts.next()?;
+        let day = parts.next()?;
+        let time = parts.next()?;
+        let year = parts.next()?;
+
+        if year.len() != 4 || parts.next().is_some() {
+            return None;
+        }
+
+        (day, month, year.parse().ok()?, time)
+    };
+
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let day = parse_two_digits(day)?;
+
+    if day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let mut time = time.split(':');
+    let hour = parse_two_digits(time.next()?)?;
+    let minute = parse_two_digits(time.next()?)?;
+    let second = parse_two_digits(time.next()?)?;
+
+    // Allow a leap second, as the date format does.
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 || year < 1970 {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day);
+    let seconds = ((days * 24 + hour) * 60 + minute) * 60 + second;
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn parse_two_digits(s: &str) -> Option<u64> {
+    if !s.is_empty() && s.len() <= 2 && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Count the days from 1970-01-01 to the given date, which must not be
+/// earlier.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let years = (1970..year)
+        .map(|year| if is_leap_year(year) { 366 } else { 365 })
+        .sum::<u64>();
+    let months = (1..month)
+        .map(|month| days_in_month(year, month))
+        .sum::<u64>();
+
+    years + months + day - 1
+}
+
+/// Format a time as an HTTP date, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+///
+/// Fractions of a second are discarded, and times earlier than 1970 are
+/// formatted as the start of 1970.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+///
+/// assert_eq!(isahc::date::format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+pub fn format(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let days = seconds / 86_400;
+    let seconds = seconds % 86_400;
+
+    // The first of January 1970 was a Thursday.
+    let weekday = WEEKDAYS[((days + 3) % 7) as usize];
+    let (year, month, day) = civil_date(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        weekday,
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    )
+}
+
+/// Parse a date in the compact format used by curl in its HSTS cache files,
+/// such as `19941106 08:49:37`, which is always in UTC.
+pub(crate) fn parse_compact(value: &str) -> Option<SystemTime> {
+    let mut parts = value.trim().split(' ');
+    let date = parts.next()?;
+    let time = parts.next()?;
+
+    if parts.next().is_some()
+        || date.len() != 8
+        || time.len() != 8
+        || !date.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let year = date[..4].parse::<u64>().ok()?;
+    let month = parse_two_digits(&date[4..6])?;
+    let day = parse_two_digits(&date[6..])?;
+
+    if year < 1970 || month == 0 || month > 12 || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let mut time = time.split(':');
+    let hour = parse_two_digits(time.next()?)?;
+    let minute = parse_two_digits(time.next()?)?;
+    let second = parse_two_digits(time.next()?)?;
+
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day);
+    let seconds = ((days * 24 + hour) * 60 + minute) * 60 + second;
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Format a time in the compact format used by curl in its HSTS cache files,
+/// such as `19941106 08:49:37`.
+///
+/// Returns `None` for times after the year 9999, which the format cannot
+/// represent.
+pub(crate) fn format_compact(time: SystemTime) -> Option<String> {
+    // The end of 9999-12-31.
+    const MAX: u64 = 253_402_300_799;
+
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    if seconds > MAX {
+        return None;
+    }
+
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let seconds = seconds % 86_400;
+
+    Some(format!(
+        "{:04}{:02}{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    ))
+}
+
+/// Get the year, month and day of the date the given number of days after
+/// 1970-01-01.
+fn civil_date(mut days: u64) -> (u64, u64, u64) {
+    let mut year = 1970;
+    loop {
+        let days_in_year = if is_leap_year(year) { 366 } else { 365 };
+
+        if days < days_in_year {
+            break;
+        }
+
+        days -= days_in_year;
+        year += 1;
+    }
+
+    let mut month = 1;
+    while days >= days_in_month(year, month) {
+        days -= days_in_month(year, month);
+        month += 1;
+    }
+
+    (year, month, days + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        let expected = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(expected));
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), Some(expected));
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), Some(expected));
+        assert_eq!(
+            parse("Thu, 29 Feb 2024 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_251_199))
+        );
+        assert_eq!(parse("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_dates() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("120"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49 GMT"), None);
+        assert_eq!(parse("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Wed, 29 Feb 2023 00:00:00 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse("Mon, 01 Jan 1900 00:00:00 GMT"), None);
+    }
+
+    #[test]
+    fn format_round_trips() {
+        for &seconds in &[0, 784_111_777, 951_782_400, 1_709_251_199, 4_102_444_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+
+            assert_eq!(parse(&format(time)), Some(time));
+        }
+
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_secs(1_709_251_199)),
+            "Thu, 29 Feb 2024 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn compact_format_round_trips() {
+        for &seconds in &[0, 784_111_777, 951_782_400, 1_709_251_199, 253_402_300_799] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+
+            assert_eq!(parse_compact(&format_compact(time).unwrap()), Some(time));
+        }
+
+        assert_eq!(
+            format_compact(UNIX_EPOCH + Duration::from_secs(784_111_777)).unwrap(),
+            "19941106 08:49:37"
+        );
+        assert_eq!(
+            format_compact(UNIX_EPOCH + Duration::from_secs(253_402_300_800)),
+            None
+        );
+        assert_eq!(parse_compact("1994116 08:49:37"), None);
+        assert_eq!(parse_compact("19941306 08:49:37"), None);
+        assert_eq!(parse_compact("unlimited"), None);
+    }
+}
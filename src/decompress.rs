@@ -0,0 +1,334 @@
+//! Decoding of response bodies compressed with encodings that libcurl may not
+//! have been built with.
+//!
+//! Curl sets up its decoders as soon as it parses the `Content-Encoding`
+//! response header, and fails the transfer on the first byte of the body if it
+//! does not know one of the encodings. Whether a response uses an encoding
+//! curl does not support is only known after that point, so if curl lacks any
+//! of the encodings Isahc can decode, curl's own decoding is turned off for the
+//! request and every encoding of the response is undone here instead.
+
+use flate2::write::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use once_cell::sync::Lazy;
+use std::{
+    fmt,
+    io::{self, Write},
+    mem,
+};
+
+/// The encodings to advertise in the `Accept-Encoding` header when decoding
+/// responses ourselves, in order of preference.
+#[cfg(all(feature = "brotli", feature = "zstd"))]
+pub(crate) const ACCEPT_ENCODING: &str = "zstd, br, gzip, deflate";
+
+#[cfg(all(feature = "brotli", not(feature = "zstd")))]
+pub(crate) const ACCEPT_ENCODING: &str = "br, gzip, deflate";
+
+#[cfg(all(feature = "zstd", not(feature = "brotli")))]
+pub(crate) const ACCEPT_ENCODING: &str = "zstd, gzip, deflate";
+
+/// Check whether response bodies need to be decoded by us instead of curl,
+/// because curl lacks an encoding that we support.
+pub(crate) fn is_needed() -> bool {
+    static NEEDED: Lazy<bool> = Lazy::new(|| {
+        let version = curl::Version::get();
+
+        (cfg!(feature = "brotli") && !version.feature_brotli())
+            || (cfg!(feature = "zstd") && !version.feature_zstd())
+    });
+
+    *NEEDED
+}
+
+/// A decoder for the body of a response, undoing each of the encodings listed
+/// in its `Content-Encoding` header.
+pub(crate) struct ContentDecoder {
+    /// Decoders for each encoding, in the order they are applied to the body.
+    stages: Vec<Stage>,
+}
+
+impl ContentDecoder {
+    /// Create a decoder for a body with the given encodings, in the order
+    /// they were applied by the server.
+    ///
+    /// Like in curl, unknown encodings are only an error once there is a body
+    /// to decode, since responses without a body may still declare one.
+    pub(crate) fn new<'a>(encodings: impl DoubleEndedIterator<Item = &'a str>) -> Self {
+        Self {
+            stages: encodings.rev().map(Stage::new).collect(),
+        }
+    }
+
+    /// Decode the given chunk of the body, returning all of the decoded data
+    /// that it produced.
+    pub(crate) fn decode(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut chunk = data.to_vec();
+
+        for stage in &mut self.stages {
+            let mut decoded = Vec::new();
+            stage.decode(&chunk, &mut decoded)?;
+            chunk = decoded;
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl fmt::Debug for ContentDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentDecoder")
+            .field("stages", &self.stages.len())
+            .finish()
+    }
+}
+
+/// A decoder for a single content encoding.
+enum Stage {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(Inflate),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli_decompressor::DecompressorWriter<Vec<u8>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd_safe::DCtx<'static>),
+    Unsupported(String),
+}
+
+impl Stage {
+    fn new(encoding: &str) -> Self {
+        match encoding.to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Stage::Gzip(GzDecoder::new(Vec::new())),
+            "deflate" => Stage::Deflate(Inflate::Unknown(Vec::new())),
+            #[cfg(feature = "brotli")]
+            "br" => Stage::Brotli(Box::new(brotli_decompressor::DecompressorWriter::new(
+                Vec::new(),
+                4096,
+            ))),
+            #[cfg(feature = "zstd")]
+            "zstd" => Stage::Zstd(zstd_safe::DCtx::create()),
+            _ => Stage::Unsupported(encoding.to_owned()),
+        }
+    }
+
+    /// Decode the given input, appending the decoded data to the output.
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            // This decoder holds on to decoded data until the next write
+            // unless flushed.
+            Stage::Gzip(decoder) => {
+                decoder.write_all(input)?;
+                decoder.flush()?;
+                output.append(decoder.get_mut());
+            }
+            Stage::Deflate(inflate) => inflate.decode(input, output)?,
+            #[cfg(feature = "brotli")]
+            Stage::Brotli(decoder) => {
+                decoder.write_all(input)?;
+                output.append(decoder.get_mut());
+            }
+            #[cfg(feature = "zstd")]
+            Stage::Zstd(context) => {
+                let mut input = zstd_safe::InBuffer::around(input);
+
+                // Keep going until all of the input has been consumed and the
+                // decoder has no more output left to give.
+                loop {
+                    output.reserve(zstd_safe::DCtx::out_size());
+
+                    let capacity = output.capacity();
+                    let mut out = zstd_safe::OutBuffer::around_pos(output, output.len());
+
+                    context
+                        .decompress_stream(&mut out, &mut input)
+                        .map_err(|code| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                zstd_safe::get_error_name(code),
+                            )
+                        })?;
+
+                    if input.pos() == input.src.len() && out.pos() < capacity {
+                        break;
+                    }
+                }
+            }
+            Stage::Unsupported(encoding) => {
+                if !input.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unsupported content encoding `{}`", encoding),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A decoder for the `deflate` encoding. This is meant to be a zlib stream,
+/// but some servers send a raw deflate stream instead, which curl accepts as
+/// well.
+enum Inflate {
+    /// Not enough of the body has been received yet to tell which it is.
+    Unknown(Vec<u8>),
+    Zlib(ZlibDecoder<Vec<u8>>),
+    Raw(DeflateDecoder<Vec<u8>>),
+}
+
+impl Inflate {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        let start;
+
+        let input = match self {
+            Inflate::Unknown(buffer) => {
+                buffer.extend_from_slice(input);
+
+                if buffer.len() < 2 {
+                    return Ok(());
+                }
+
+                start = mem::take(buffer);
+
+                // A zlib header uses deflate as its compression method, and
+                // is a multiple of 31 when read as a 16-bit number.
+                let header = u16::from(start[0]) << 8 | u16::from(start[1]);
+
+                *self = if start[0] & 0x0f == 8 && header % 31 == 0 {
+                    Inflate::Zlib(ZlibDecoder::new(Vec::new()))
+                } else {
+                    Inflate::Raw(DeflateDecoder::new(Vec::new()))
+                };
+
+                &start[..]
+            }
+            _ => input,
+        };
+
+        // These decoders hold on to decoded data until the next write unless
+        // flushed.
+        match self {
+            Inflate::Zlib(decoder) => {
+                decoder.write_all(input)?;
+                decoder.flush()?;
+                output.append(decoder.get_mut());
+            }
+            Inflate::Raw(decoder) => {
+                decoder.write_all(input)?;
+                decoder.flush()?;
+                output.append(decoder.get_mut());
+            }
+            Inflate::Unknown(_) => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn raw_deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode_in_chunks(decoder: &mut ContentDecoder, data: &[u8]) -> Vec<u8> {
+        let mut decoded = Vec::new();
+
+        for chunk in data.chunks(7) {
+            decoded.extend(decoder.decode(chunk).unwrap());
+        }
+
+        decoded
+    }
+
+    #[test]
+    fn decode_gzip() {
+        let mut decoder = ContentDecoder::new(vec!["gzip"].into_iter());
+
+        assert_eq!(
+            decode_in_chunks(&mut decoder, &gzip(b"hello world")),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn decode_gzip_in_one_chunk() {
+        let mut decoder = ContentDecoder::new(vec!["gzip"].into_iter());
+
+        assert_eq!(
+            decoder.decode(&gzip(b"hello world")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn encodings_are_undone_in_reverse_order() {
+        let mut decoder = ContentDecoder::new(vec!["deflate", "gzip"].into_iter());
+
+        assert_eq!(
+            decode_in_chunks(&mut decoder, &gzip(&deflate(b"hello world"))),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn decode_raw_deflate() {
+        let mut decoder = ContentDecoder::new(vec!["deflate"].into_iter());
+
+        assert_eq!(
+            decode_in_chunks(&mut decoder, &raw_deflate(b"hello world")),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn unsupported_encoding_fails_with_body() {
+        let mut decoder = ContentDecoder::new(vec!["compress"].into_iter());
+
+        assert!(decoder.decode(b"").unwrap().is_empty());
+        assert_eq!(
+            decoder.decode(b"data").unwrap_err().to_string(),
+            "unsupported content encoding `compress`"
+        );
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn decode_brotli() {
+        // "hello world" compressed with brotli.
+        let encoded = b"\x0b\x05\x80\x68\x65\x6c\x6c\x6f\x20\x77\x6f\x72\x6c\x64\x03";
+        let mut decoder = ContentDecoder::new(vec!["br"].into_iter());
+
+        assert_eq!(decode_in_chunks(&mut decoder, encoded), b"hello world");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decode_zstd() {
+        let data = "hello world ".repeat(10_000);
+        let mut encoded = Vec::with_capacity(zstd_safe::compress_bound(data.len()));
+        zstd_safe::compress(&mut encoded, data.as_bytes(), 3).unwrap();
+        let mut decoder = ContentDecoder::new(vec!["zstd"].into_iter());
+
+        assert_eq!(decode_in_chunks(&mut decoder, &encoded), data.as_bytes());
+    }
+}
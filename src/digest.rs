@@ -0,0 +1,597 @@
+//! Digests of request and response bodies, as described in
+//! [RFC 9530](https://www.rfc-editor.org/rfc/rfc9530).
+//!
+//! Only SHA-256 is supported, which is implemented here along with the bits
+//! of base64 needed to encode digests, rather than pulling in dependencies
+//! for them.
+
+use crate::{
+    config::DigestHeader,
+    error::{Error, ErrorKind},
+    handler::RequestHandler,
+};
+use curl::easy::Easy2;
+use http::{header::HeaderName, HeaderMap, StatusCode};
+use std::{
+    ffi::CString,
+    io,
+    os::raw::{c_int, c_void},
+};
+
+/// Incremental SHA-256 hash function.
+#[derive(Clone)]
+pub(crate) struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffered: usize,
+    len: u64,
+}
+
+impl Sha256 {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            buffer: [0; 64],
+            buffered: 0,
+            len: 0,
+        }
+    }
+
+    /// Compute the hash of the given data in one go.
+    pub(crate) fn digest(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+
+        // Complete a partial block left over from before first.
+        if self.buffered > 0 {
+            let len = (64 - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+
+            if self.buffered < 64 {
+                return;
+            }
+
+            let block = self.buffer;
+            compress(&mut self.state, &block);
+            self.buffered = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+
+        for block in &mut blocks {
+            compress(&mut self.state, block);
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffered = remainder.len();
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.len.wrapping_mul(8);
+
+        // Pad the message so that the length fits at the end of the last
+        // block.
+        self.update(&[0x80]);
+
+        while self.buffered != 56 {
+            self.update(&[0]);
+        }
+
+        self.update(&bit_len.to_be_bytes());
+
+        let mut hash = [0; 32];
+
+        for (bytes, word) in hash.chunks_exact_mut(4).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+
+        hash
+    }
+}
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Process a single 64-byte block of input.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut schedule = [0u32; 64];
+
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    for i in 16..64 {
+        let w15 = schedule[i - 15];
+        let w2 = schedule[i - 2];
+        let s0 = w15.rotate_right(7) ^ w15.rotate_right(18) ^ (w15 >> 3);
+        let s1 = w2.rotate_right(17) ^ w2.rotate_right(19) ^ (w2 >> 10);
+
+        schedule[i] = schedule[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(schedule[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let mut v = *state;
+
+    for (k, w) in ROUND_CONSTANTS.iter().zip(schedule.iter()) {
+        let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let t1 = v[7]
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(*k)
+            .wrapping_add(*w);
+        let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let t2 = s0.wrapping_add(maj);
+
+        v = [
+            t1.wrapping_add(t2),
+            v[0],
+            v[1],
+            v[2],
+            v[3].wrapping_add(t1),
+            v[4],
+            v[5],
+            v[6],
+        ];
+    }
+
+    for (word, value) in state.iter_mut().zip(v.iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes using standard base64 with padding.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len() * 4 / 3 + 4);
+
+    for chunk in data.chunks(3) {
+        let mut bytes = [0; 3];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Decode standard base64 with padding, returning `None` if the input is not
+/// valid.
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let mut chunks = encoded.chunks(4).peekable();
+
+    while let Some(chunk) = chunks.next() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+
+        // Padding is only allowed at the very end.
+        if padding > 2 || (padding > 0 && chunks.peek().is_some()) {
+            return None;
+        }
+
+        let mut n = 0;
+
+        for &c in &chunk[..4 - padding] {
+            n = n << 6 | u32::from(base64_value(c)?);
+        }
+
+        n <<= 6 * padding;
+
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        decoded.extend_from_slice(&bytes[..3 - padding]);
+    }
+
+    Some(decoded)
+}
+
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Format a SHA-256 hash as the value of a digest header.
+pub(crate) fn header_value(hash: &[u8; 32]) -> String {
+    format!("sha-256=:{}:", base64_encode(hash))
+}
+
+/// Get the SHA-256 digests listed in the value of a digest header.
+///
+/// The value is a structured field dictionary mapping algorithms to byte
+/// sequences, such as `sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:`.
+/// Digests that are not valid byte sequences are returned as `None`.
+fn sha256_digests(value: &str) -> impl Iterator<Item = Option<Vec<u8>>> + '_ {
+    value.split(',').filter_map(|member| {
+        // Ignore any parameters of the member.
+        let mut parts = member.split(';').next()?.splitn(2, '=');
+
+        if parts.next()?.trim() != "sha-256" {
+            return None;
+        }
+
+        let value = parts.next().unwrap_or("").trim();
+
+        let digest = if value.len() >= 2 && value.starts_with(':') && value.ends_with(':') {
+            base64_decode(&value[1..value.len() - 1])
+        } else {
+            None
+        };
+
+        Some(digest)
+    })
+}
+
+/// Computes the digest of a streaming request body while it is sent, to send
+/// it in a trailer afterwards.
+pub(crate) struct RequestDigest {
+    header: HeaderName,
+    hasher: Sha256,
+}
+
+impl RequestDigest {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    /// Start over because the body is sent again from the beginning.
+    pub(crate) fn reset(&mut self) {
+        self.hasher = Sha256::new();
+    }
+
+    fn trailer(&self) -> Option<CString> {
+        let hash = self.hasher.clone().finish();
+        CString::new(format!("{}: {}", self.header, header_value(&hash))).ok()
+    }
+}
+
+/// Send the digest of the body of the request handled by the given handle in
+/// a trailer once the body has been sent. The request must use chunked
+/// encoding for curl to send trailers.
+#[allow(unsafe_code)]
+pub(crate) fn send_trailer(
+    easy: &mut Easy2<RequestHandler>,
+    header: DigestHeader,
+) -> Result<(), curl::Error> {
+    // Not yet defined by curl-sys.
+    const CURLOPT_TRAILERFUNCTION: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_FUNCTIONPOINT + 283;
+    const CURLOPT_TRAILERDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 284;
+
+    let digest = Box::new(RequestDigest {
+        header: header.name(),
+        hasher: Sha256::new(),
+    });
+
+    // The digest is owned by the handler, which lives as long as the handle,
+    // and it does not move since it is boxed.
+    let data = &*digest as *const RequestDigest as *mut c_void;
+    easy.get_mut().set_request_digest(digest);
+
+    let callback: extern "C" fn(*mut *mut curl_sys::curl_slist, *mut c_void) -> c_int =
+        trailer_callback;
+
+    // Use unsafe API, because the safe API doesn't expose this option.
+    unsafe {
+        match curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_TRAILERFUNCTION, callback) {
+            curl_sys::CURLE_OK => {}
+            code => return Err(curl::Error::new(code)),
+        }
+
+        match curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_TRAILERDATA, data) {
+            curl_sys::CURLE_OK => Ok(()),
+            code => Err(curl::Error::new(code)),
+        }
+    }
+}
+
+/// Called by curl to get the trailers to send after a chunked request body.
+/// Curl takes care of freeing the list.
+#[allow(unsafe_code)]
+extern "C" fn trailer_callback(list: *mut *mut curl_sys::curl_slist, data: *mut c_void) -> c_int {
+    const CURL_TRAILERFUNC_OK: c_int = 0;
+    const CURL_TRAILERFUNC_ABORT: c_int = 1;
+
+    let digest = unsafe { &*(data as *const RequestDigest) };
+
+    let trailer = match digest.trailer() {
+        Some(trailer) => trailer,
+        None => return CURL_TRAILERFUNC_ABORT,
+    };
+
+    unsafe {
+        let appended = curl_sys::curl_slist_append(*list, trailer.as_ptr());
+
+        if appended.is_null() {
+            return CURL_TRAILERFUNC_ABORT;
+        }
+
+        *list = appended;
+    }
+
+    CURL_TRAILERFUNC_OK
+}
+
+/// Computes the digest of a response body while it is received, to check it
+/// against the digest headers of the response at the end.
+pub(crate) struct ResponseDigest {
+    /// Whether curl decodes the response body.
+    decoding: bool,
+    hasher: Sha256,
+    skip: bool,
+
+    /// Names of the headers holding digests that describe the body.
+    names: &'static [&'static str],
+
+    /// Digest headers of the response, kept since the headers are handed
+    /// over to the response before the body has been received.
+    headers: HeaderMap,
+}
+
+impl ResponseDigest {
+    pub(crate) fn new(decoding: bool) -> Self {
+        Self {
+            decoding,
+            hasher: Sha256::new(),
+            skip: false,
+            names: &[],
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Start over for a new response, whose headers were just received.
+    pub(crate) fn begin(&mut self, status: Option<StatusCode>, headers: &HeaderMap, encoded: bool) {
+        self.hasher = Sha256::new();
+
+        // A decoded body is not what the digest was computed over, and these
+        // responses do not include the content that the digest describes.
+        self.skip = (self.decoding && encoded)
+            || status == Some(StatusCode::NO_CONTENT)
+            || status == Some(StatusCode::NOT_MODIFIED);
+
+        // The representation digest of a partial response covers the whole
+        // representation, not just the part received.
+        self.names = if status == Some(StatusCode::PARTIAL_CONTENT) {
+            &["content-digest"]
+        } else {
+            &["content-digest", "repr-digest"]
+        };
+
+        self.headers.clear();
+
+        for name in self.names {
+            for value in headers.get_all(*name) {
+                self.headers.append(*name, value.clone());
+            }
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        if !self.skip {
+            self.hasher.update(data);
+        }
+    }
+
+    /// Check the body received against the digest headers of the response
+    /// and the given trailers.
+    pub(crate) fn verify(self, trailers: &HeaderMap) -> Result<(), Error> {
+        if self.skip {
+            return Ok(());
+        }
+
+        let hash = self.hasher.finish();
+
+        for name in self.names {
+            let values = self
+                .headers
+                .get_all(*name)
+                .iter()
+                .chain(trailers.get_all(*name));
+
+            for value in values {
+                let value = match value.to_str() {
+                    Ok(value) => value,
+                    Err(_) => return Err(mismatch(name)),
+                };
+
+                for digest in sha256_digests(value) {
+                    if digest.as_deref() != Some(&hash[..]) {
+                        return Err(mismatch(name));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn mismatch(header: &str) -> Error {
+    Error::new(
+        ErrorKind::DigestMismatch,
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("response body does not match the {} header", header),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha256_test_vectors() {
+        assert_eq!(
+            hex(&Sha256::digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&Sha256::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&Sha256::digest(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn sha256_incremental_matches_one_shot() {
+        let data = (0..1000).map(|i| i as u8).collect::<Vec<_>>();
+
+        for split in &[0, 1, 55, 56, 63, 64, 65, 500, 1000] {
+            let mut hasher = Sha256::new();
+            hasher.update(&data[..*split]);
+            hasher.update(&data[*split..]);
+
+            assert_eq!(hasher.finish(), Sha256::digest(&data));
+        }
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for (decoded, encoded) in &[
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xfb\xff", "+/8="),
+        ] {
+            assert_eq!(base64_encode(decoded), *encoded);
+            assert_eq!(base64_decode(encoded).as_deref(), Some(*decoded));
+        }
+
+        assert_eq!(base64_decode("Zm9"), None);
+        assert_eq!(base64_decode("Zg==Zm9v"), None);
+        assert_eq!(base64_decode("Z==="), None);
+        assert_eq!(base64_decode("Zm-v"), None);
+    }
+
+    #[test]
+    fn header_value_matches_rfc_example() {
+        assert_eq!(
+            header_value(&Sha256::digest(br#"{"hello": "world"}"#)),
+            "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:"
+        );
+    }
+
+    fn verify(body: &[u8], name: &'static str, value: &'static str) -> Result<(), Error> {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+
+        let mut digest = ResponseDigest::new(false);
+        digest.begin(Some(StatusCode::OK), &headers, false);
+        digest.update(body);
+        digest.verify(&HeaderMap::new())
+    }
+
+    #[test]
+    fn verify_matching_digests() {
+        let body = br#"{"hello": "world"}"#;
+
+        let value = "sha-512=:YQ==:, sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:";
+
+        assert!(verify(body, "content-digest", value).is_ok());
+        assert!(verify(body, "repr-digest", "unixsum=:AQ==:").is_ok());
+    }
+
+    #[test]
+    fn verify_mismatched_digests() {
+        let body = br#"{"hello": "world"}"#;
+
+        let error = verify(
+            b"tampered",
+            "content-digest",
+            "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:",
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::DigestMismatch);
+        assert!(verify(body, "repr-digest", "sha-256=:not base64:").is_err());
+        assert!(verify(body, "repr-digest", "sha-256=\"X48E9q\"").is_err());
+    }
+
+    #[test]
+    fn verify_digest_in_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("content-digest", HeaderValue::from_static("sha-256=:AAAA:"));
+
+        let mut digest = ResponseDigest::new(false);
+        digest.begin(Some(StatusCode::OK), &HeaderMap::new(), false);
+        digest.update(b"body");
+
+        assert!(digest.verify(&trailers).is_err());
+    }
+
+    #[test]
+    fn representation_digest_of_partial_response_is_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert("repr-digest", HeaderValue::from_static("sha-256=:AAAA:"));
+
+        let mut digest = ResponseDigest::new(false);
+        digest.begin(Some(StatusCode::PARTIAL_CONTENT), &headers, false);
+        digest.update(b"part");
+
+        assert!(digest.verify(&headers).is_ok());
+    }
+
+    #[test]
+    fn decoded_response_is_not_verified() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-digest", HeaderValue::from_static("sha-256=:AAAA:"));
+
+        let mut digest = ResponseDigest::new(true);
+        digest.begin(Some(StatusCode::OK), &headers, true);
+        digest.update(b"decoded");
+
+        assert!(digest.verify(&headers).is_ok());
+    }
+}
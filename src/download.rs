@@ -0,0 +1,385 @@
+//! Downloading response bodies into files.
+//!
+//! Writing a large response body to a file by appending to it lets the file
+//! grow in many small steps, which can cause fragmentation. When the size of
+//! the body is known in advance, the file can be sized up front instead and the
+//! body written at explicit offsets. Positioned writes do not depend on a
+//! shared cursor, so several parts of a file can also be downloaded at the same
+//! time into the same file.
+//!
+//! Files that are downloaded over and over again, such as configuration or
+//! assets that are kept in sync with a server, can instead be downloaded only
+//! when they have changed, by remembering the validators of the last response
+//! in a sidecar file and making conditional requests.
+
+use crate::{parsing::parse_header, Error, HttpClient, ReadResponseExt};
+use http::{
+    header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Request,
+    StatusCode,
+    Uri,
+};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The result of
+/// [`HttpClient::download_if_changed`](crate::HttpClient::download_if_changed).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DownloadOutcome {
+    /// The server reported that the resource has not changed since it was last
+    /// downloaded, and the file was left as it is.
+    Unchanged,
+
+    /// The resource was downloaded and the file was replaced with it. Contains
+    /// the number of bytes written.
+    Updated(u64),
+}
+
+/// A file that a response body is downloaded into, preallocated to the
+/// expected size of the body.
+///
+/// On Linux, Android and FreeBSD, disk space for the whole file is reserved
+/// up front using `posix_fallocate`, so that running out of space is
+/// reported when the file is created rather than halfway through a download.
+/// Elsewhere, or if the file system does not support reserving space, the
+/// file is only resized, which may leave it sparse until it is written to.
+///
+/// Data is written at explicit offsets using [`write_at`](Self::write_at),
+/// which only requires a shared reference. A single `DownloadedFile` can
+/// therefore be shared between threads, for example to download separate
+/// byte ranges of the same resource in parallel.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::DownloadedFile;
+///
+/// let file = DownloadedFile::create("large.iso", 4096)?;
+///
+/// file.write_at(2048, b"second half")?;
+/// file.write_at(0, b"first half")?;
+///
+/// let file = file.finish()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DownloadedFile {
+    file: File,
+    len: u64,
+}
+
+impl DownloadedFile {
+    /// Create a new file at the given path, truncating it if it already
+    /// exists, and preallocate it to the given length.
+    pub fn create<P: AsRef<Path>>(path: P, len: u64) -> io::Result<Self> {
+        let file = File::create(path)?;
+
+        Self::from_file(file, len)
+    }
+
+    /// Use an existing file for a download, resizing it to the given length
+    /// and reserving disk space for it where supported.
+    ///
+    /// The file must be opened for writing.
+    pub fn from_file(file: File, len: u64) -> io::Result<Self> {
+        file.set_len(len)?;
+        allocate(&file, len)?;
+
+        Ok(Self {
+            file,
+            len,
+        })
+    }
+
+    /// Get the length the file was preallocated to.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check whether the file was preallocated to a length of zero.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write all of the given bytes into the file, starting at the given
+    /// offset.
+    ///
+    /// Writing past the preallocated length is allowed and grows the file.
+    pub fn write_at(&self, mut offset: u64, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match write_at(&self.file, buf, offset) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ));
+                }
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get a reference to the underlying file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Flush all written data to disk and return the underlying file.
+    pub fn finish(self) -> io::Result<File> {
+        self.file.sync_data()?;
+
+        Ok(self.file)
+    }
+
+    /// Truncate the file to the given length, flush it to disk, and return the
+    /// underlying file.
+    ///
+    /// This is useful if fewer bytes were downloaded than were preallocated.
+    pub(crate) fn finish_with_len(self, len: u64) -> io::Result<File> {
+        if len != self.len {
+            self.file.set_len(len)?;
+        }
+
+        self.finish()
+    }
+}
+
+/// Download the given URI into a file, unless the file is already up to date.
+pub(crate) fn download_if_changed(
+    client: &HttpClient,
+    uri: Uri,
+    path: &Path,
+) -> Result<DownloadOutcome, Error> {
+    let validators_path = with_suffix(path, ".validators");
+
+    // Validators are only useful while the file they describe still exists.
+    let validators = if path.exists() {
+        Validators::read(&validators_path)?
+    } else {
+        Validators::default()
+    };
+
+    let mut request = Request::get(uri);
+
+    if let Some(etag) = validators.etag.clone() {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    if let Some(last_modified) = validators.last_modified.clone() {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let mut response = client.send(request.body(()).map_err(Error::from_any)?)?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(DownloadOutcome::Unchanged);
+    }
+
+    response.error_for_status()?;
+
+    // Download into a separate file first, so that a failed download never
+    // leaves a partially written file behind in place of the previous one.
+    let partial_path = with_suffix(path, ".part");
+
+    let len = match response.copy_to_file(&partial_path) {
+        Ok(len) => len,
+        Err(e) => {
+            let _ = fs::remove_file(&partial_path);
+            return Err(e.into());
+        }
+    };
+
+    fs::rename(&partial_path, path)?;
+    Validators::from_headers(response.headers()).write(&validators_path)?;
+
+    Ok(DownloadOutcome::Updated(len))
+}
+
+/// Validators of a downloaded resource, used to ask the server whether it has
+/// changed since.
+#[derive(Debug, Default, PartialEq)]
+struct Validators {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            etag: headers.get(ETAG).cloned(),
+            last_modified: headers.get(LAST_MODIFIED).cloned(),
+        }
+    }
+
+    /// Read validators from a file containing them as headers, one per line.
+    /// A missing file contains no validators.
+    fn read(path: &Path) -> io::Result<Self> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+
+        let headers = contents
+            .split(|&byte| byte == b'\n')
+            .filter_map(parse_header)
+            .collect::<HeaderMap>();
+
+        Ok(Self::from_headers(&headers))
+    }
+
+    /// Write the validators to the given file, or remove the file if there
+    /// are none.
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let mut contents = Vec::new();
+
+        for (name, value) in &[("etag", &self.etag), ("last-modified", &self.last_modified)] {
+            if let Some(value) = value {
+                contents.extend_from_slice(name.as_bytes());
+                contents.extend_from_slice(b": ");
+                contents.extend_from_slice(value.as_bytes());
+                contents.push(b'\n');
+            }
+        }
+
+        if !contents.is_empty() {
+            fs::write(path, contents)
+        } else {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+/// Append a suffix to the file name of the given path.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    path.into()
+}
+
+/// Reserve disk space for the first `len` bytes of the given file.
+///
+/// File systems that cannot reserve space are left with a sparse file
+/// instead, which still works, just without the guarantee.
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+#[allow(unsafe_code)]
+fn allocate(file: &File, len: u64) -> io::Result<()> {
+    use std::{convert::TryFrom, os::unix::io::AsRawFd};
+
+    // Reserving nothing is an error, and lengths that do not fit in an off_t
+    // are rejected by set_len already.
+    let len = match libc::off_t::try_from(len) {
+        Ok(0) | Err(_) => return Ok(()),
+        Ok(len) => len,
+    };
+
+    // posix_fallocate returns the error number instead of setting errno.
+    match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, len) } {
+        0 => Ok(()),
+        libc::EINVAL | libc::EOPNOTSUPP => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
+fn allocate(_file: &File, _len: u64) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_write(file, buf, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn writes_out_of_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("download");
+        let file = DownloadedFile::create(&path, 10).unwrap();
+
+        assert_eq!(file.file().metadata().unwrap().len(), 10);
+
+        file.write_at(5, b"world").unwrap();
+        file.write_at(0, b"hello").unwrap();
+
+        file.finish().unwrap();
+
+        let mut contents = String::new();
+        File::open(&path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        assert_eq!(contents, "helloworld");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn create_reserves_disk_space() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = DownloadedFile::create(dir.path().join("download"), 1024 * 1024).unwrap();
+        let metadata = file.file().metadata().unwrap();
+
+        assert_eq!(metadata.len(), 1024 * 1024);
+
+        // Blocks are counted in units of 512 bytes, and a sparse file would
+        // have none allocated yet.
+        assert!(metadata.blocks() * 512 >= 1024 * 1024);
+    }
+
+    #[test]
+    fn finish_with_shorter_len_truncates() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = DownloadedFile::create(dir.path().join("download"), 100).unwrap();
+
+        file.write_at(0, b"abc").unwrap();
+
+        let file = file.finish_with_len(3).unwrap();
+
+        assert_eq!(file.metadata().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn validators_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json.validators");
+
+        assert_eq!(Validators::read(&path).unwrap(), Validators::default());
+
+        let validators = Validators {
+            etag: Some(HeaderValue::from_static("\"v1\"")),
+            last_modified: Some(HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT")),
+        };
+        validators.write(&path).unwrap();
+        assert_eq!(Validators::read(&path).unwrap(), validators);
+
+        Validators::default().write(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
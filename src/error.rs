@@ -1,5 +1,7 @@
 //! Types for error handling.
 
+use crate::connection::ConnectAttempt;
+use http::{header::HeaderName, StatusCode};
 use std::{error::Error as StdError, fmt, io, sync::Arc};
 
 /// A non-exhaustive list of error types that can occur while sending an HTTP
@@ -18,12 +20,71 @@ pub enum ErrorKind {
     /// The server certificate could not be validated.
     BadServerCertificate,
 
+    /// The server responded with a client error (4xx) or server error (5xx)
+    /// status code.
+    ///
+    /// This error is only returned when explicitly checking the status of a
+    /// response, such as with
+    /// [`ReadResponseExt::error_for_status`](crate::ReadResponseExt::error_for_status).
+    /// A `431 Request Header Fields Too Large` status is reported as
+    /// [`HeadersTooLarge`](ErrorKind::HeadersTooLarge) instead, and a `414 URI
+    /// Too Long` status as [`UriTooLong`](ErrorKind::UriTooLong).
+    BadStatus,
+
     /// The HTTP client failed to initialize.
     ClientInitialization,
 
     /// Failed to connect to the server.
     ConnectionFailed,
 
+    /// A connection to the server could not be established within the
+    /// configured connect timeout.
+    ///
+    /// See also [`Configurable::connect_timeout`](crate::config::Configurable::connect_timeout).
+    ConnectTimeout,
+
+    /// The response body was aborted because it grew larger than allowed
+    /// while being decompressed.
+    ///
+    /// See also
+    /// [`Configurable::max_decompressed_size`](crate::config::Configurable::max_decompressed_size)
+    /// and
+    /// [`Configurable::max_decompression_ratio`](crate::config::Configurable::max_decompression_ratio).
+    DecompressionLimitExceeded,
+
+    /// The response body did not match the digest sent along with it by the
+    /// server, which means it was corrupted or tampered with on the way.
+    ///
+    /// See also
+    /// [`Configurable::verify_digest`](crate::config::Configurable::verify_digest).
+    DigestMismatch,
+
+    /// The server did not start sending a response within the configured
+    /// headers timeout.
+    ///
+    /// See also
+    /// [`Configurable::headers_timeout`](crate::config::Configurable::headers_timeout).
+    HeadersTimeout,
+
+    /// The request headers were larger than allowed, either by the server or
+    /// by the configured limit. Large `Authorization` or `Cookie` headers are
+    /// a common cause.
+    ///
+    /// Servers report this with a `431 Request Header Fields Too Large` status,
+    /// which is only turned into an error when explicitly checking the status
+    /// of a response. Requests exceeding the client-side limit are never sent.
+    ///
+    /// See also
+    /// [`Configurable::max_header_list_size`](crate::config::Configurable::max_header_list_size).
+    HeadersTooLarge,
+
+    /// No data was sent or received for longer than the configured idle
+    /// timeout.
+    ///
+    /// See also
+    /// [`Configurable::idle_timeout`](crate::config::Configurable::idle_timeout).
+    IdleTimeout,
+
     /// The server either returned a response using an unknown or unsupported
     /// encoding format, or the response encoding was malformed.
     InvalidContentEncoding,
@@ -59,12 +120,35 @@ pub enum ErrorKind {
     /// likely to produce the same error.
     ProtocolViolation,
 
+    /// The request could not be started within the configured queue timeout,
+    /// such as because connection limits were reached.
+    ///
+    /// See also
+    /// [`Configurable::queue_timeout`](crate::config::Configurable::queue_timeout).
+    QueueTimeout,
+
     /// Request processing could not continue because the client needed to
     /// re-send the request body, but was unable to rewind the body stream to
     /// the beginning in order to do so.
     RequestBodyNotRewindable,
 
+    /// The response was rejected after its headers were received, before its
+    /// body was read.
+    ///
+    /// See also
+    /// [`Configurable::on_headers`](crate::config::Configurable::on_headers).
+    ResponseRejected,
+
+    /// No response body data was received for longer than the configured read
+    /// timeout.
+    ///
+    /// See also
+    /// [`Configurable::read_timeout`](crate::config::Configurable::read_timeout).
+    ReadTimeout,
+
     /// A request or operation took longer than the configured timeout time.
+    ///
+    /// See also [`Configurable::timeout`](crate::config::Configurable::timeout).
     Timeout,
 
     /// An error ocurred in the secure socket engine.
@@ -73,6 +157,18 @@ pub enum ErrorKind {
     /// Number of redirects hit the maximum amount.
     TooManyRedirects,
 
+    /// The request URI was longer than allowed, either by the server or by the
+    /// configured limit. This is usually caused by a query string that grew
+    /// out of control.
+    ///
+    /// Servers report this with a `414 URI Too Long` status, which is only
+    /// turned into an error when explicitly checking the status of a response.
+    /// Requests exceeding the client-side limit are never sent.
+    ///
+    /// See also
+    /// [`Configurable::max_uri_length`](crate::config::Configurable::max_uri_length).
+    UriTooLong,
+
     /// An unknown error occurred. This likely indicates a problem in the HTTP
     /// client or in a dependency, but the client was able to recover instead of
     /// panicking. Subsequent requests will likely succeed.
@@ -88,8 +184,21 @@ impl ErrorKind {
         match self {
             Self::BadClientCertificate => Some("a problem occurred with the local certificate"),
             Self::BadServerCertificate => Some("the server certificate could not be validated"),
+            Self::BadStatus => Some("the server responded with an error status code"),
             Self::ClientInitialization => Some("failed to initialize client"),
             Self::ConnectionFailed => Some("failed to connect to the server"),
+            Self::ConnectTimeout => {
+                Some("failed to connect to the server within the configured connect timeout")
+            }
+            Self::DecompressionLimitExceeded => {
+                Some("the decompressed response body exceeded the configured limits")
+            }
+            Self::DigestMismatch => Some("the response body did not match its digest"),
+            Self::HeadersTimeout => {
+                Some("the server did not respond within the configured headers timeout")
+            }
+            Self::HeadersTooLarge => Some("the request headers were too large"),
+            Self::IdleTimeout => Some("transfer stalled without making progress"),
             Self::InvalidContentEncoding => Some(
                 "the server either returned a response using an unknown or unsupported encoding format, or the response encoding was malformed",
             ),
@@ -101,14 +210,20 @@ impl ErrorKind {
             Self::ProtocolViolation => {
                 Some("the server made an unrecoverable HTTP protocol violation")
             }
+            Self::QueueTimeout => {
+                Some("request could not be started within the configured queue timeout")
+            }
             Self::RequestBodyNotRewindable => {
                 Some("request body could not be re-sent because it is not rewindable")
             }
+            Self::ResponseRejected => Some("the response was rejected after receiving its headers"),
+            Self::ReadTimeout => Some("response body stalled without making progress"),
             Self::Timeout => {
                 Some("request or operation took longer than the configured timeout time")
             }
             Self::TlsEngine => Some("error ocurred in the secure socket engine"),
             Self::TooManyRedirects => Some("number of redirects hit the maximum amount"),
+            Self::UriTooLong => Some("the request URI was too long"),
             _ => None,
         }
     }
@@ -146,6 +261,11 @@ struct Inner {
     kind: ErrorKind,
     context: Option<String>,
     source: Option<Box<dyn StdError + Send + Sync>>,
+    bytes_transferred: Option<(u64, u64)>,
+    status: Option<StatusCode>,
+    body: Option<String>,
+    connect_attempts: Vec<ConnectAttempt>,
+    connection_reused: Option<bool>,
 }
 
 impl Error {
@@ -167,9 +287,131 @@ impl Error {
             kind,
             context,
             source: Some(Box::new(source)),
+            bytes_transferred: None,
+            status: None,
+            body: None,
+            connect_attempts: Vec::new(),
+            connection_reused: None,
         }))
     }
 
+    /// Create an error for a transfer that failed because a timeout was
+    /// reached, distinguishing between the different kinds of timeouts.
+    pub(crate) fn from_timeout(
+        error: curl::Error,
+        connected: bool,
+        bytes_sent: u64,
+        bytes_received: u64,
+    ) -> Self {
+        let context = error.extra_description().map(String::from);
+
+        // Curl's low speed limit is only used to enforce the idle timeout.
+        let kind = if !connected {
+            ErrorKind::ConnectTimeout
+        } else if context
+            .as_ref()
+            .map(|s| s.contains("too slow"))
+            .unwrap_or(false)
+        {
+            ErrorKind::IdleTimeout
+        } else {
+            ErrorKind::Timeout
+        };
+
+        let mut error = Self::with_context(kind, context, error);
+
+        if let Some(inner) = Arc::get_mut(&mut error.0) {
+            inner.bytes_transferred = Some((bytes_sent, bytes_received));
+        }
+
+        error
+    }
+
+    /// Create an error for a response with an error status code, including a
+    /// snippet of the response body.
+    pub(crate) fn from_status(status: StatusCode, body: String) -> Self {
+        let kind = match status {
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE => ErrorKind::HeadersTooLarge,
+            StatusCode::URI_TOO_LONG => ErrorKind::UriTooLong,
+            _ => ErrorKind::BadStatus,
+        };
+
+        Self(Arc::new(Inner {
+            kind,
+            context: Some(status.to_string()),
+            source: None,
+            bytes_transferred: None,
+            status: Some(status),
+            body: Some(body),
+            connect_attempts: Vec::new(),
+            connection_reused: None,
+        }))
+    }
+
+    /// Create an error for a request whose headers are larger than the
+    /// configured limit.
+    pub(crate) fn headers_too_large(size: usize, limit: usize) -> Self {
+        Self::limit_exceeded(ErrorKind::HeadersTooLarge, size, limit)
+    }
+
+    /// Create an error for a request whose URI is longer than the configured
+    /// limit.
+    pub(crate) fn uri_too_long(length: usize, limit: usize) -> Self {
+        Self::limit_exceeded(ErrorKind::UriTooLong, length, limit)
+    }
+
+    fn limit_exceeded(kind: ErrorKind, size: usize, limit: usize) -> Self {
+        Self(Arc::new(Inner {
+            kind,
+            context: Some(format!(
+                "{} bytes exceeds the limit of {} bytes",
+                size, limit
+            )),
+            source: None,
+            bytes_transferred: None,
+            status: None,
+            body: None,
+            connect_attempts: Vec::new(),
+            connection_reused: None,
+        }))
+    }
+
+    /// Attach the connection attempts made before this error occurred.
+    pub(crate) fn with_connect_attempts(mut self, attempts: Vec<ConnectAttempt>) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.0) {
+            inner.connect_attempts = attempts;
+        }
+
+        self
+    }
+
+    /// Record whether the request that caused this error was sent over a
+    /// connection that was reused from an earlier request.
+    pub(crate) fn with_connection_reused(mut self, reused: bool) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.0) {
+            inner.connection_reused = Some(reused);
+        }
+
+        self
+    }
+
+    /// Get whether the request that caused this error was sent over a reused
+    /// connection, if known.
+    pub(crate) fn connection_reused(&self) -> Option<bool> {
+        self.0.connection_reused
+    }
+
+    /// Returns true if this error was caused by an HTTP/2 protocol or stream
+    /// error.
+    pub(crate) fn is_http2_error(&self) -> bool {
+        self.0
+            .source
+            .as_ref()
+            .and_then(|source| source.downcast_ref::<curl::Error>())
+            .filter(|e| e.is_http2_error() || e.is_http2_stream_error())
+            .is_some()
+    }
+
     /// Statically cast a given error into an Isahc error, converting if
     /// necessary.
     pub(crate) fn from_any<E>(error: E) -> Self
@@ -194,6 +436,72 @@ impl Error {
         &self.0.kind
     }
 
+    /// Returns true if this error was caused by any kind of timeout being
+    /// reached.
+    pub fn is_timeout(&self) -> bool {
+        match self.kind() {
+            ErrorKind::ConnectTimeout
+            | ErrorKind::HeadersTimeout
+            | ErrorKind::IdleTimeout
+            | ErrorKind::QueueTimeout
+            | ErrorKind::ReadTimeout
+            | ErrorKind::Timeout => true,
+            _ => false,
+        }
+    }
+
+    /// Get the number of bytes of the request body that were sent before the
+    /// error occurred, if known.
+    ///
+    /// This is currently only available for timeout errors, and can help with
+    /// figuring out whether a timeout needs to be increased or whether the
+    /// server simply stopped responding.
+    pub fn bytes_sent(&self) -> Option<u64> {
+        self.0.bytes_transferred.map(|(sent, _)| sent)
+    }
+
+    /// Get the number of bytes of the response body that were received before
+    /// the error occurred, if known.
+    ///
+    /// This is currently only available for timeout errors.
+    pub fn bytes_received(&self) -> Option<u64> {
+        self.0.bytes_transferred.map(|(_, received)| received)
+    }
+
+    /// Get the status code of the response that caused this error, if the
+    /// error was caused by an error status code.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.0.status
+    }
+
+    /// Get the start of the body of the response that caused this error, if
+    /// the error was caused by an error status code.
+    ///
+    /// APIs usually describe the reason for an error in the response body, so
+    /// this is useful for logging. The body is decoded into text and truncated
+    /// to a limit that can be set with
+    /// [`Configurable::error_body_limit`](crate::config::Configurable::error_body_limit).
+    pub fn response_body(&self) -> Option<&str> {
+        self.0.body.as_deref()
+    }
+
+    /// Get the connection attempts made to each address of the server before
+    /// failing to connect.
+    ///
+    /// When a host name resolves to multiple addresses, each is tried in turn
+    /// and may fail for a different reason, such as one being refused while
+    /// another times out. Only the last of these failures is reflected in the
+    /// [`kind`](Error::kind) of the error, so this can help with debugging
+    /// problems such as an unreachable IPv6 address on a dual-stack host.
+    ///
+    /// Connection attempts are only recorded if enabled using
+    /// [`Configurable::connection_report`](crate::config::Configurable::connection_report),
+    /// and only for [`ErrorKind::ConnectionFailed`] and
+    /// [`ErrorKind::ConnectTimeout`] errors. Otherwise this is empty.
+    pub fn connect_attempts(&self) -> &[ConnectAttempt] {
+        &self.0.connect_attempts
+    }
+
     /// Returns true if this error was likely caused by the client.
     ///
     /// Usually indicates that the client was misconfigured or used to send
@@ -204,10 +512,12 @@ impl Error {
         match self.kind() {
             ErrorKind::BadClientCertificate
             | ErrorKind::ClientInitialization
+            | ErrorKind::HeadersTooLarge
             | ErrorKind::InvalidCredentials
             | ErrorKind::InvalidRequest
             | ErrorKind::RequestBodyNotRewindable
-            | ErrorKind::TlsEngine => true,
+            | ErrorKind::TlsEngine
+            | ErrorKind::UriTooLong => true,
             _ => false,
         }
     }
@@ -259,6 +569,11 @@ impl fmt::Debug for Error {
             .field("kind", &self.kind())
             .field("context", &self.0.context)
             .field("source", &self.source())
+            .field("bytes_transferred", &self.0.bytes_transferred)
+            .field("status", &self.0.status)
+            .field("body", &self.0.body)
+            .field("connect_attempts", &self.0.connect_attempts)
+            .field("connection_reused", &self.0.connection_reused)
             .finish()
     }
 }
@@ -279,6 +594,11 @@ impl From<ErrorKind> for Error {
             kind,
             context: None,
             source: None,
+            bytes_transferred: None,
+            status: None,
+            body: None,
+            connect_attempts: Vec::new(),
+            connection_reused: None,
         }))
     }
 }
@@ -307,7 +627,15 @@ impl From<Error> for io::Error {
     fn from(error: Error) -> Self {
         let kind = match error.kind() {
             ErrorKind::ConnectionFailed => io::ErrorKind::ConnectionRefused,
-            ErrorKind::Timeout => io::ErrorKind::TimedOut,
+            ErrorKind::ConnectTimeout
+            | ErrorKind::HeadersTimeout
+            | ErrorKind::IdleTimeout
+            | ErrorKind::QueueTimeout
+            | ErrorKind::ReadTimeout
+            | ErrorKind::Timeout => io::ErrorKind::TimedOut,
+            ErrorKind::DecompressionLimitExceeded | ErrorKind::DigestMismatch => {
+                io::ErrorKind::InvalidData
+            }
             _ => io::ErrorKind::Other,
         };
 
@@ -406,9 +734,259 @@ impl From<curl::MultiError> for Error {
     }
 }
 
+/// A part of a request that can fail validation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RequestComponent {
+    /// The request method.
+    Method,
+
+    /// The request URI.
+    Uri,
+
+    /// The value of the header with the given name.
+    HeaderValue(HeaderName),
+}
+
+impl fmt::Display for RequestComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestComponent::Method => f.write_str("request method"),
+            RequestComponent::Uri => f.write_str("request URI"),
+            RequestComponent::HeaderValue(name) => write!(f, "value of header `{}`", name),
+        }
+    }
+}
+
+/// An error identifying the part of a request that contained a character that
+/// is not allowed, such as a line break that could be used to inject headers.
+///
+/// Requests are validated before they are sent, and a request that fails
+/// validation returns an [`Error`] of kind [`ErrorKind::InvalidRequest`] with
+/// this error as its [source](std::error::Error::source).
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{
+///     error::{RequestComponent, RequestValidationError},
+///     http::HeaderValue,
+///     prelude::*,
+/// };
+/// use std::error::Error;
+///
+/// let request = Request::get("https://example.org")
+///     .header("X-Name", HeaderValue::from_bytes(b"caf\xC3\xA9")?)
+///     .body(())?;
+///
+/// let error = isahc::send(request).unwrap_err();
+/// let source = error
+///     .source()
+///     .and_then(|e| e.downcast_ref::<RequestValidationError>())
+///     .unwrap();
+///
+/// assert_eq!(source.component(), &RequestComponent::HeaderValue("x-name".parse()?));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestValidationError {
+    component: RequestComponent,
+    byte: u8,
+}
+
+impl RequestValidationError {
+    pub(crate) fn new(component: RequestComponent, byte: u8) -> Self {
+        Self { component, byte }
+    }
+
+    /// Get the part of the request that failed validation.
+    pub fn component(&self) -> &RequestComponent {
+        &self.component
+    }
+
+    /// Get the first byte that was not allowed.
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+}
+
+impl fmt::Display for RequestValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid byte 0x{:02x} in {}", self.byte, self.component)
+    }
+}
+
+impl StdError for RequestValidationError {}
+
+impl From<RequestValidationError> for Error {
+    fn from(error: RequestValidationError) -> Error {
+        Self::with_context(ErrorKind::InvalidRequest, Some(error.to_string()), error)
+    }
+}
+
+/// An error listing every problem found when validating the configuration of
+/// a client.
+///
+/// Building a client with options that cannot work together, or that are not
+/// supported by the libcurl in use, returns an [`Error`] of kind
+/// [`ErrorKind::ClientInitialization`] with this error as its
+/// [source](std::error::Error::source).
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{error::ConfigValidationError, prelude::*, HttpClient};
+/// use std::error::Error;
+///
+/// let error = HttpClient::builder()
+///     .proxy(Some("ftp://proxy.example.org".parse()?))
+///     .build()
+///     .unwrap_err();
+///
+/// let source = error
+///     .source()
+///     .and_then(|e| e.downcast_ref::<ConfigValidationError>())
+///     .unwrap();
+///
+/// for problem in source.problems() {
+///     println!("{}", problem);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConfigValidationError {
+    problems: Vec<String>,
+}
+
+impl ConfigValidationError {
+    pub(crate) fn new(problems: Vec<String>) -> Self {
+        Self {
+            problems,
+        }
+    }
+
+    /// Get a description of each problem that was found.
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+}
+
+impl fmt::Display for ConfigValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid client configuration: ")?;
+        f.write_str(&self.problems.join("; "))
+    }
+}
+
+impl StdError for ConfigValidationError {}
+
+impl From<ConfigValidationError> for Error {
+    fn from(error: ConfigValidationError) -> Error {
+        Self::with_context(
+            ErrorKind::ClientInitialization,
+            Some(error.to_string()),
+            error,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     static_assertions::assert_impl_all!(Error: Send, Sync);
+
+    fn timeout_error(extra: &str) -> curl::Error {
+        let mut error = curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT);
+        error.set_extra(extra.into());
+        error
+    }
+
+    #[test]
+    fn timeout_before_connecting_is_connect_timeout() {
+        let error = Error::from_timeout(
+            timeout_error("Connection timed out after 1000 milliseconds"),
+            false,
+            0,
+            0,
+        );
+
+        assert_eq!(error.kind(), ErrorKind::ConnectTimeout);
+        assert!(error.is_timeout());
+        assert_eq!(error.bytes_received(), Some(0));
+    }
+
+    #[test]
+    fn timeout_while_stalled_is_idle_timeout() {
+        let error = Error::from_timeout(
+            timeout_error(
+                "Operation too slow. Less than 1 bytes/sec transferred the last 5 seconds",
+            ),
+            true,
+            12,
+            1024,
+        );
+
+        assert_eq!(error.kind(), ErrorKind::IdleTimeout);
+        assert!(error.is_timeout());
+        assert_eq!(error.bytes_sent(), Some(12));
+        assert_eq!(error.bytes_received(), Some(1024));
+    }
+
+    #[test]
+    fn timeout_after_connecting_is_total_timeout() {
+        let error = Error::from_timeout(
+            timeout_error("Operation timed out after 500 milliseconds with 100 bytes received"),
+            true,
+            0,
+            100,
+        );
+
+        assert_eq!(error.kind(), ErrorKind::Timeout);
+        assert_eq!(error.bytes_received(), Some(100));
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn status_error_includes_status_and_body() {
+        let error = Error::from_status(StatusCode::NOT_FOUND, "no such thing".into());
+
+        assert_eq!(error.kind(), ErrorKind::BadStatus);
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(error.response_body(), Some("no such thing"));
+        assert_eq!(
+            error.to_string(),
+            "the server responded with an error status code: 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn header_fields_too_large_status_has_its_own_kind() {
+        let error = Error::from_status(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, String::new());
+
+        assert_eq!(error.kind(), ErrorKind::HeadersTooLarge);
+        assert_eq!(error.status(), Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE));
+    }
+
+    #[test]
+    fn uri_too_long_status_has_its_own_kind() {
+        let error = Error::from_status(StatusCode::URI_TOO_LONG, String::new());
+
+        assert_eq!(error.kind(), ErrorKind::UriTooLong);
+        assert!(error.is_client());
+        assert_eq!(error.status(), Some(StatusCode::URI_TOO_LONG));
+    }
+
+    #[test]
+    fn http2_errors_are_recognized() {
+        let error = Error::from(curl::Error::new(curl_sys::CURLE_HTTP2_STREAM));
+
+        assert_eq!(error.kind(), ErrorKind::ProtocolViolation);
+        assert!(error.is_http2_error());
+
+        let error = Error::from(curl::Error::new(curl_sys::CURLE_GOT_NOTHING));
+
+        assert_eq!(error.kind(), ErrorKind::ProtocolViolation);
+        assert!(!error.is_http2_error());
+    }
 }
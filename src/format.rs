@@ -0,0 +1,328 @@
+//! Human-readable formatting of requests and responses for debugging.
+
+use crate::response::PeekBody;
+use http::{header::HeaderName, HeaderMap, Method, StatusCode, Uri, Version};
+use std::{fmt, str};
+
+/// Headers whose values are hidden unless configured otherwise, since they
+/// usually contain credentials.
+const REDACTED: [HeaderName; 4] = [
+    http::header::AUTHORIZATION,
+    http::header::COOKIE,
+    http::header::PROXY_AUTHORIZATION,
+    http::header::SET_COOKIE,
+];
+
+/// A request or response formatted for debugging.
+///
+/// This is created using
+/// [`RequestExt::format`](crate::RequestExt::format) or
+/// [`ResponseExt::format`](crate::ResponseExt::format), and implements
+/// [`Display`](fmt::Display). By default only the start line of the message is
+/// shown, such as `HTTP/1.1 200 OK`. The headers and the start of the body can
+/// be included as well, which produces output similar to that of
+/// [HTTPie](https://httpie.io), or that of `curl --verbose` when using
+/// [`curl_style`](Format::curl_style).
+///
+/// The values of headers that usually contain credentials, such as
+/// `Authorization` and `Cookie`, are replaced with `<redacted>`, so that the
+/// output can be logged safely. More headers can be added to this list using
+/// [`redact`](Format::redact).
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::prelude::*;
+///
+/// let mut response = isahc::get("https://example.org")?;
+///
+/// println!("{}", response.format().with_headers().with_body_preview(512));
+/// # Ok::<(), isahc::Error>(())
+/// ```
+pub struct Format<'a> {
+    start_line: StartLine,
+    headers: HeaderMap,
+    body: &'a mut dyn PeekBody,
+    preview: Option<Result<Preview, String>>,
+    show_headers: bool,
+    curl_style: bool,
+    redacted: Vec<HeaderName>,
+}
+
+enum StartLine {
+    Request(Method, Uri, Version),
+    Response(Version, StatusCode),
+}
+
+struct Preview {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl<'a> Format<'a> {
+    pub(crate) fn request<T: PeekBody>(request: &'a mut http::Request<T>) -> Self {
+        let start_line = StartLine::Request(
+            request.method().clone(),
+            request.uri().clone(),
+            request.version(),
+        );
+
+        Self::new(start_line, request.headers().clone(), request.body_mut())
+    }
+
+    pub(crate) fn response<T: PeekBody>(response: &'a mut http::Response<T>) -> Self {
+        let start_line = StartLine::Response(response.version(), response.status());
+
+        Self::new(start_line, response.headers().clone(), response.body_mut())
+    }
+
+    fn new(start_line: StartLine, headers: HeaderMap, body: &'a mut dyn PeekBody) -> Self {
+        Self {
+            start_line,
+            headers,
+            body,
+            preview: None,
+            show_headers: false,
+            curl_style: false,
+            redacted: REDACTED.to_vec(),
+        }
+    }
+
+    /// Include the headers of the message.
+    pub fn with_headers(mut self) -> Self {
+        self.show_headers = true;
+        self
+    }
+
+    /// Include up to the given number of bytes from the start of the body.
+    ///
+    /// The bytes are peeked at without consuming them, so the whole body can
+    /// still be read afterwards. Bodies that are not valid UTF-8 text are
+    /// summarized instead of being shown.
+    ///
+    /// The body of an asynchronous response cannot be peeked at without
+    /// blocking, so it can only be previewed if it is stored in memory.
+    pub fn with_body_preview(mut self, limit: usize) -> Self {
+        // Peek at one more byte than shown, to know whether the preview is
+        // the entire body.
+        self.preview = Some(
+            self.body
+                .peek_body(limit.saturating_add(1))
+                .map(|bytes| Preview {
+                    bytes: bytes[..limit.min(bytes.len())].to_vec(),
+                    truncated: bytes.len() > limit,
+                })
+                .map_err(|e| e.to_string()),
+        );
+        self
+    }
+
+    /// Format the message like `curl --verbose` does, prefixing each line of
+    /// a request with `> ` and each line of a response with `< `.
+    pub fn curl_style(mut self) -> Self {
+        self.curl_style = true;
+        self
+    }
+
+    /// Hide the value of the given header in addition to those hidden by
+    /// default.
+    pub fn redact(mut self, name: HeaderName) -> Self {
+        self.redacted.push(name);
+        self
+    }
+
+    fn prefix(&self) -> &'static str {
+        match (self.curl_style, &self.start_line) {
+            (false, _) => "",
+            (true, StartLine::Request(..)) => "> ",
+            (true, StartLine::Response(..)) => "< ",
+        }
+    }
+}
+
+impl fmt::Display for Format<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = self.prefix();
+
+        match &self.start_line {
+            StartLine::Request(method, uri, version) => {
+                let target = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+                write!(f, "{}{} {} {:?}", prefix, method, target, version)?;
+
+                // The host is sent in a header, even if it is only given in
+                // the URI here.
+                if self.show_headers && !self.headers.contains_key(http::header::HOST) {
+                    if let Some(authority) = uri.authority() {
+                        write!(f, "\n{}host: {}", prefix, authority.as_str())?;
+                    }
+                }
+            }
+            StartLine::Response(version, status) => {
+                write!(f, "{}{:?} {}", prefix, version, status)?;
+            }
+        }
+
+        if self.show_headers {
+            for (name, value) in self.headers.iter() {
+                if self.redacted.contains(name) {
+                    write!(f, "\n{}{}: <redacted>", prefix, name)?;
+                } else {
+                    write!(
+                        f,
+                        "\n{}{}: {}",
+                        prefix,
+                        name,
+                        String::from_utf8_lossy(value.as_bytes())
+                    )?;
+                }
+            }
+        }
+
+        match &self.preview {
+            Some(Ok(preview)) => {
+                write!(f, "\n{}\n", prefix.trim_end())?;
+
+                match text(&preview.bytes) {
+                    Some(text) => {
+                        f.write_str(text)?;
+
+                        if preview.truncated {
+                            f.write_str("…")?;
+                        }
+                    }
+                    None if preview.truncated => {
+                        write!(f, "[binary data, more than {} bytes]", preview.bytes.len())?;
+                    }
+                    None => {
+                        write!(f, "[binary data, {} bytes]", preview.bytes.len())?;
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                write!(f, "\n{}\n[body not available: {}]", prefix.trim_end(), e)?;
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Format<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Format")
+            .field("show_headers", &self.show_headers)
+            .field("curl_style", &self.curl_style)
+            .field("redacted", &self.redacted)
+            .finish()
+    }
+}
+
+/// Get the given bytes as text, or `None` if they look like binary data.
+fn text(bytes: &[u8]) -> Option<&str> {
+    let text = match str::from_utf8(bytes) {
+        Ok(text) => text,
+
+        // The preview may end in the middle of a character.
+        Err(e) if e.error_len().is_none() => str::from_utf8(&bytes[..e.valid_up_to()]).ok()?,
+
+        Err(_) => return None,
+    };
+
+    if text
+        .chars()
+        .any(|c| c.is_control() && !c.is_ascii_whitespace())
+    {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use http::{Request, Response};
+    use std::io;
+
+    #[test]
+    fn format_request_with_headers_and_body() {
+        let mut request = Request::post("https://example.org/items?a=b")
+            .header("content-type", "application/json")
+            .header("authorization", "Bearer secret")
+            .body(r#"{"name":"isahc"}"#)
+            .unwrap();
+
+        assert_eq!(
+            Format::request(&mut request).to_string(),
+            "POST /items?a=b HTTP/1.1"
+        );
+        assert_eq!(
+            Format::request(&mut request)
+                .with_headers()
+                .with_body_preview(9)
+                .to_string(),
+            "POST /items?a=b HTTP/1.1\n\
+             host: example.org\n\
+             content-type: application/json\n\
+             authorization: <redacted>\n\
+             \n\
+             {\"name\":\"…"
+        );
+    }
+
+    #[test]
+    fn format_response_curl_style() {
+        let mut response = Response::builder()
+            .status(404)
+            .header("set-cookie", "session=secret")
+            .header("x-request-id", "42")
+            .body(Body::from("not found"))
+            .unwrap();
+
+        assert_eq!(
+            Format::response(&mut response)
+                .with_headers()
+                .with_body_preview(512)
+                .redact(HeaderName::from_static("x-request-id"))
+                .curl_style()
+                .to_string(),
+            "< HTTP/1.1 404 Not Found\n\
+             < set-cookie: <redacted>\n\
+             < x-request-id: <redacted>\n\
+             <\n\
+             not found"
+        );
+
+        // The body can still be read in full.
+        let mut body = String::new();
+        io::Read::read_to_string(response.body_mut(), &mut body).unwrap();
+        assert_eq!(body, "not found");
+    }
+
+    #[test]
+    fn binary_bodies_are_summarized() {
+        let mut response = Response::new(vec![0xff, 0x00, 0x01, 0x02]);
+
+        assert_eq!(
+            Format::response(&mut response)
+                .with_body_preview(2)
+                .to_string(),
+            "HTTP/1.1 200 OK\n\n[binary data, more than 2 bytes]"
+        );
+    }
+
+    #[test]
+    fn streaming_async_bodies_are_not_previewed() {
+        let mut response = Response::new(crate::AsyncBody::from_reader(futures_lite::io::empty()));
+
+        assert_eq!(
+            Format::response(&mut response)
+                .with_body_preview(10)
+                .to_string(),
+            "HTTP/1.1 200 OK\n\n[body not available: asynchronous body is not stored in memory]"
+        );
+    }
+}
@@ -2,10 +2,32 @@
 
 use crate::{
     body::AsyncBody,
+    client::DetachedRecorder,
+    config::{
+        dial::SocketOpener,
+        headers::OnHeaders,
+        progress::OnProgress,
+        CancelPolicy,
+        HeaderAction,
+        HeaderMergePolicy,
+        Progress,
+    },
+    connection::{record_connect_attempt, ConnectAttempt, ConnectionReport},
+    digest::{RequestDigest, ResponseDigest},
     error::{Error, ErrorKind},
+    headers::HasHeaders,
+    hsts::transfer::HstsTransfer,
     metrics::Metrics,
     parsing::{parse_header, parse_status_line},
-    response::{LocalAddr, RemoteAddr},
+    push::{PushedResponse, PushedResponses},
+    recv_buffer::RecvBufferSize,
+    response::{ConnectionReused, LocalAddr, RemoteAddr, ResponseTimes},
+    share::ShareHandle,
+};
+#[cfg(feature = "spill-to-disk")]
+use crate::{
+    config::SpillToDisk,
+    spill::{self, SpillReader, SpillWriter},
 };
 use crossbeam_utils::atomic::AtomicCell;
 use curl::easy::{InfoType, ReadError, SeekResult, WriteError};
@@ -26,8 +48,9 @@ use std::{
     os::raw::{c_char, c_long},
     pin::Pin,
     ptr,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll, Waker},
+    time::{Duration, Instant, SystemTime},
 };
 
 pub(crate) struct RequestBody(pub(crate) AsyncBody);
@@ -78,16 +101,169 @@ pub(crate) struct RequestHandler {
     /// Response headers received so far.
     response_headers: http::HeaderMap,
 
-    /// Writing end of the pipe where the response body is written.
-    response_body_writer: pipe::PipeWriter,
+    /// Which intermediate responses to keep headers from.
+    header_merge_policy: HeaderMergePolicy,
+
+    /// Headers of informational responses received before the final response,
+    /// if they are being kept.
+    informational_headers: http::HeaderMap,
+
+    /// Callback deciding whether to continue with the final response once its
+    /// headers have been received, if any.
+    on_headers: Option<OnHeaders>,
+
+    /// Callback to report the progress of the transfer to, if any.
+    on_progress: Option<OnProgress>,
+
+    /// The progress last reported to the callback, so that it is only
+    /// invoked when something changed.
+    last_progress: Progress,
+
+    /// Opens sockets in place of curl, if a connected socket or custom dialer
+    /// is used. Curl holds a pointer to it, so it is boxed.
+    socket_opener: Option<Box<SocketOpener>>,
+
+    /// Exchanges entries between an HSTS store and the HSTS cache of curl, if
+    /// HSTS is enabled. Curl holds a pointer to it, so it is boxed.
+    hsts: Option<Box<HstsTransfer>>,
+
+    /// Size of the buffer curl receives the response body into, if it is
+    /// adjusted as the body is received.
+    recv_buffer: Option<RecvBufferSize>,
+
+    /// Used by the agent to send responses pushed along with the response,
+    /// if pushed responses are accepted.
+    push_sender: Option<Sender<PushedResponse>>,
+
+    /// Receives the pushed responses, until it is included in the response.
+    pushed_responses: Option<PushedResponses>,
+
+    /// Caches shared with other clients that the transfer uses, if any.
+    share_handle: Option<ShareHandle>,
+
+    /// Key of the connection pool partition to use, if any.
+    pool_partition: Option<String>,
+
+    /// When the request was sent, according to the system clock.
+    requested_at: Option<SystemTime>,
+
+    /// Extensions of the request, which are carried over to the response.
+    request_extensions: http::Extensions,
+
+    /// Writing end of the buffer where the response body is written, unless
+    /// the handler has been released.
+    response_body_writer: Option<ResponseBodyWriter>,
 
     /// A waker used with writing the response body asynchronously. Populated by
     /// an agent when the request is initialized.
     response_body_waker: Option<Waker>,
 
-    /// Metrics object for publishing metrics data to. Lazily initialized.
+    /// Metrics object for publishing metrics data to, if enabled.
     metrics: Option<Metrics>,
 
+    /// What to do if the response future is dropped before it completes.
+    cancel_policy: CancelPolicy,
+
+    /// When we noticed that the response future was dropped, if the transfer
+    /// is continuing without it. Any response body received afterwards is
+    /// discarded.
+    detached_at: Option<Instant>,
+
+    /// Records the outcome of the transfer if it was sent detached.
+    detached_recorder: Option<DetachedRecorder>,
+
+    /// Timeout for the entire transfer that curl was configured with, if any.
+    timeout: Option<Duration>,
+
+    /// When the agent gives up on curl reporting the timeout of the current
+    /// attempt and aborts the transfer itself.
+    deadline: Option<Instant>,
+
+    /// When the agent gives up on curl starting the transfer, if ever.
+    queue_deadline: Option<Instant>,
+
+    /// Whether curl has started the transfer, as opposed to keeping it
+    /// pending until a connection becomes available.
+    started: bool,
+
+    /// Maximum time to wait for the response to start arriving, if any.
+    headers_timeout: Option<Duration>,
+
+    /// When the agent gives up on receiving the response, unless it has
+    /// started arriving by then.
+    headers_deadline: Option<Instant>,
+
+    /// Maximum time to wait between chunks of the response body, if any.
+    read_timeout: Option<Duration>,
+
+    /// Interval at which the agent should ping cached HTTP/2 connections
+    /// while this request is active, if at all.
+    upkeep_interval: Option<Duration>,
+
+    /// When the response body last made progress. Only set while we are
+    /// waiting on the server for more of the response body, and cleared while
+    /// the transfer is paused waiting on the response body reader.
+    last_read: Option<Instant>,
+
+    /// Maximum size of the response body after decompression, if any.
+    max_decompressed_size: Option<u64>,
+
+    /// Maximum ratio between the decompressed and compressed size of the
+    /// response body, if any.
+    max_decompression_ratio: Option<f64>,
+
+    /// Whether the response body is being decompressed by curl. Determined
+    /// once the response header is complete.
+    response_encoded: bool,
+
+    /// Number of bytes of the decompressed response body received so far.
+    decoded_len: u64,
+
+    /// Whether the response body is decoded by us instead of by curl.
+    #[cfg(any(feature = "brotli", feature = "zstd"))]
+    decode_content: bool,
+
+    /// Decoder for the current response body, if it is encoded and decoded
+    /// by us. Set up once the response header is complete.
+    #[cfg(any(feature = "brotli", feature = "zstd"))]
+    content_decoder: Option<crate::decompress::ContentDecoder>,
+
+    /// Decoded response body data that has not been written yet because the
+    /// reader was not ready. Curl passes the same encoded data again once the
+    /// transfer resumes, which must not be decoded a second time.
+    #[cfg(any(feature = "brotli", feature = "zstd"))]
+    decoded_pending: Option<Vec<u8>>,
+
+    /// Report on the connection used, if enabled.
+    connection_report: Option<ConnectionReport>,
+
+    /// Pre-shared key to authenticate TLS connections with, if any.
+    #[cfg(feature = "tls-psk")]
+    pre_shared_key: Option<crate::config::PreSharedKey>,
+
+    /// Attempts made to connect to each address of the server, recorded if
+    /// connection reports are enabled. Included in the error if the request
+    /// fails to connect.
+    connect_attempts: Vec<ConnectAttempt>,
+
+    /// Whether the request may be retried once if it fails on a stale
+    /// connection. Cleared after the first retry.
+    retry_stale_connection: bool,
+
+    /// Set once any bytes of the response have been received from the server.
+    response_started: bool,
+
+    /// Set once the headers of a response have been received in full.
+    response_head_received: bool,
+
+    /// Digest of the request body computed while sending it, if it is to be
+    /// sent in a trailer. Curl holds a pointer to it, so it is boxed.
+    request_digest: Option<Box<RequestDigest>>,
+
+    /// Digest of the response body computed while receiving it, if it is to
+    /// be verified.
+    response_digest: Option<ResponseDigest>,
+
     /// Raw pointer to the associated curl easy handle. The pointer is not owned
     /// by this struct, but the parent struct to this one, so we know it will be
     /// valid at least for the lifetime of this struct (assuming all other
@@ -113,12 +289,50 @@ struct Shared {
     /// this communicates to the handler to stop running since the user has lost
     /// interest in this request.
     response_body_dropped: AtomicCell<bool>,
+
+    /// Provided by the agent to be woken when the response future or body is
+    /// dropped before the transfer completed, so that the transfer can be
+    /// aborted right away.
+    abandoned_waker: Mutex<Option<Waker>>,
+
+    /// When the response future was dropped before it completed, if it was.
+    canceled_at: OnceCell<Instant>,
+}
+
+impl Shared {
+    /// Let the agent know that nobody may be interested in the rest of the
+    /// transfer anymore.
+    fn notify_abandoned(&self) {
+        if self.result.get().is_none() {
+            if let Some(waker) = self.abandoned_waker.lock().unwrap().as_ref() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// Notifies the agent if the response future is dropped before it completes.
+/// Curl gives the handler no chance to notice this while it is resolving the
+/// host name, connecting, or waiting for the response.
+struct CancelGuard(Option<Arc<Shared>>);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take() {
+            let _ = shared.canceled_at.set(Instant::now());
+            shared.notify_abandoned();
+        }
+    }
 }
 
 impl RequestHandler {
     /// Create a new request handler and an associated response future.
+    ///
+    /// If spilling to disk is enabled, the response body is buffered using a
+    /// spill buffer instead of a pipe.
     pub(crate) fn new(
         request_body: AsyncBody,
+        #[cfg(feature = "spill-to-disk")] spill_to_disk: Option<&SpillToDisk>,
     ) -> (
         Self,
         impl Future<Output = Result<Response<ResponseBodyReader>, Error>>,
@@ -127,31 +341,41 @@ impl RequestHandler {
         let shared = Arc::new(Shared {
             result: OnceCell::new(),
             response_body_dropped: AtomicCell::new(false),
+            abandoned_waker: Mutex::new(None),
+            canceled_at: OnceCell::new(),
         });
-        let (response_body_reader, response_body_writer) = pipe::pipe();
+        #[cfg(feature = "spill-to-disk")]
+        let (response_body_reader, response_body_writer) = match spill_to_disk {
+            Some(config) => {
+                let (reader, writer) = spill::buffer(config.threshold, config.dir.clone());
+                (BodyReader::Spill(reader), ResponseBodyWriter::Spill(writer))
+            }
+            None => {
+                let (reader, writer) = pipe::pipe();
+                (BodyReader::Pipe(reader), ResponseBodyWriter::Pipe(writer))
+            }
+        };
 
-        let handler = Self {
-            span: tracing::debug_span!("handler", id = tracing::field::Empty),
-            sender: Some(sender),
-            shared: shared.clone(),
-            request_body,
-            request_body_waker: None,
-            response_status_code: None,
-            response_version: None,
-            response_headers: http::HeaderMap::new(),
-            response_body_writer,
-            response_body_waker: None,
-            metrics: None,
-            handle: ptr::null_mut(),
+        #[cfg(not(feature = "spill-to-disk"))]
+        let (response_body_reader, response_body_writer) = {
+            let (reader, writer) = pipe::pipe();
+            (BodyReader::Pipe(reader), ResponseBodyWriter::Pipe(writer))
         };
 
+        let mut handler = Self::idle(shared.clone());
+        handler.span = tracing::debug_span!("handler", id = tracing::field::Empty);
+        handler.sender = Some(sender);
+        handler.request_body = request_body;
+        handler.response_body_writer = Some(response_body_writer);
+
         // Create a future that resolves when the handler receives the response
         // headers.
         let future = async move {
-            let builder = receiver
-                .recv_async()
-                .await
-                .map_err(|e| Error::new(ErrorKind::Unknown, e))??;
+            let mut guard = CancelGuard(Some(shared.clone()));
+            let result = receiver.recv_async().await;
+            guard.0 = None;
+
+            let builder = result.map_err(|e| Error::new(ErrorKind::Unknown, e))??;
 
             let reader = ResponseBodyReader {
                 inner: response_body_reader,
@@ -166,6 +390,92 @@ impl RequestHandler {
         (handler, future)
     }
 
+    /// Create a handler that is not associated with any request, which only
+    /// holds on to the given shared state.
+    fn idle(shared: Arc<Shared>) -> Self {
+        Self {
+            span: tracing::Span::none(),
+            sender: None,
+            shared,
+            request_body: AsyncBody::empty(),
+            request_body_waker: None,
+            response_status_code: None,
+            response_version: None,
+            response_headers: http::HeaderMap::new(),
+            header_merge_policy: HeaderMergePolicy::default(),
+            informational_headers: http::HeaderMap::new(),
+            on_headers: None,
+            on_progress: None,
+            last_progress: Progress::default(),
+            socket_opener: None,
+            hsts: None,
+            recv_buffer: None,
+            push_sender: None,
+            pushed_responses: None,
+            share_handle: None,
+            pool_partition: None,
+            requested_at: None,
+            request_extensions: http::Extensions::new(),
+            response_body_writer: None,
+            response_body_waker: None,
+            metrics: None,
+            cancel_policy: CancelPolicy::default(),
+            detached_at: None,
+            detached_recorder: None,
+            timeout: None,
+            deadline: None,
+            queue_deadline: None,
+            started: false,
+            headers_timeout: None,
+            headers_deadline: None,
+            read_timeout: None,
+            upkeep_interval: None,
+            last_read: None,
+            max_decompressed_size: None,
+            max_decompression_ratio: None,
+            response_encoded: false,
+            decoded_len: 0,
+            #[cfg(any(feature = "brotli", feature = "zstd"))]
+            decode_content: false,
+            #[cfg(any(feature = "brotli", feature = "zstd"))]
+            content_decoder: None,
+            #[cfg(any(feature = "brotli", feature = "zstd"))]
+            decoded_pending: None,
+            connection_report: None,
+            #[cfg(feature = "tls-psk")]
+            pre_shared_key: None,
+            connect_attempts: Vec::new(),
+            retry_stale_connection: false,
+            response_started: false,
+            response_head_received: false,
+            request_digest: None,
+            response_digest: None,
+            handle: ptr::null_mut(),
+        }
+    }
+
+    /// Check whether the curl handle of this handler can be reused for
+    /// another request once this one is complete.
+    ///
+    /// Resetting a curl handle keeps its HSTS cache and share handle, which
+    /// belong to the request that used it.
+    pub(crate) fn is_reusable(&self) -> bool {
+        self.hsts.is_none() && self.share_handle.is_none()
+    }
+
+    /// Drop everything related to the request that this handler handled, as
+    /// if the handler itself was dropped, so that its curl handle can be
+    /// reused for another request.
+    pub(crate) fn release(&mut self) {
+        let mut idle = Self::idle(self.shared.clone());
+
+        // The buffer size is not tied to the request, so keep it for the next
+        // transfer of the handle.
+        idle.recv_buffer = self.recv_buffer.take();
+
+        drop(mem::replace(self, idle));
+    }
+
     /// Check whether debug info should be generated. This function is used to
     /// determine whether to set the `verbose` curl option to true.
     pub(crate) fn is_debug_enabled(&self) -> bool {
@@ -184,11 +494,42 @@ impl RequestHandler {
         }
     }
 
-    fn is_future_canceled(&self) -> bool {
-        self.sender
+    /// Check whether the transfer should be aborted because the response
+    /// future was dropped, taking the cancel policy into account.
+    pub(crate) fn is_future_canceled(&mut self) -> bool {
+        let disconnected = self
+            .sender
             .as_ref()
             .map(Sender::is_disconnected)
-            .unwrap_or(false)
+            .unwrap_or(false);
+
+        if disconnected && self.detached_at.is_none() {
+            self.detach();
+        }
+
+        match (self.cancel_policy, self.detached_at) {
+            (_, None) => false,
+            (CancelPolicy::Abort, Some(_)) => true,
+            (CancelPolicy::Detach, Some(_)) => false,
+            (CancelPolicy::Linger(grace), Some(detached_at)) => {
+                let expired = detached_at.elapsed() > grace;
+
+                if expired {
+                    tracing::debug!("request still running {:?} after being dropped", grace);
+                }
+
+                expired
+            }
+        }
+    }
+
+    /// Note that nobody is waiting for the response anymore.
+    fn detach(&mut self) {
+        if self.cancel_policy != CancelPolicy::Abort {
+            tracing::debug!("response future dropped, continuing request in the background");
+        }
+
+        self.detached_at = Some(Instant::now());
     }
 
     /// Initialize the handler and prepare it for the request to begin.
@@ -202,6 +543,7 @@ impl RequestHandler {
         handle: *mut CURL,
         request_waker: Waker,
         response_waker: Waker,
+        abandoned_waker: Waker,
     ) {
         let _enter = self.span.enter();
 
@@ -213,16 +555,355 @@ impl RequestHandler {
         self.handle = handle;
         self.request_body_waker = Some(request_waker);
         self.response_body_waker = Some(response_waker);
+        *self.shared.abandoned_waker.lock().unwrap() = Some(abandoned_waker);
+        self.requested_at = Some(SystemTime::now());
+
+        if let Some(recorder) = self.detached_recorder.as_mut() {
+            recorder.set_started();
+        }
+    }
+
+    /// Set the extensions of the request being sent, which will be included in
+    /// the extensions of the response.
+    pub(crate) fn set_request_extensions(&mut self, extensions: http::Extensions) {
+        self.request_extensions = extensions;
+    }
+
+    /// Enable collection of metrics for this request, labeled with the given
+    /// route template.
+    pub(crate) fn enable_metrics(&mut self, route: Option<String>) {
+        self.metrics = Some(Metrics::new(route));
+    }
+
+    /// Set what to do if the response future is dropped before it completes.
+    pub(crate) fn set_cancel_policy(&mut self, policy: CancelPolicy) {
+        self.cancel_policy = policy;
+    }
+
+    /// Record the outcome of the transfer using the given recorder.
+    pub(crate) fn set_detached_recorder(&mut self, recorder: DetachedRecorder) {
+        self.detached_recorder = Some(recorder);
+    }
+
+    /// Set which intermediate responses to keep headers from.
+    pub(crate) fn set_header_merge_policy(&mut self, policy: HeaderMergePolicy) {
+        self.header_merge_policy = policy;
+    }
+
+    /// Keep the given share handle alive for as long as this transfer.
+    pub(crate) fn set_share_handle(&mut self, share_handle: ShareHandle) {
+        self.share_handle = Some(share_handle);
+    }
+
+    /// Use the connection pool partition with the given key, which the agent
+    /// sets up before starting the transfer.
+    pub(crate) fn set_pool_partition(&mut self, key: String) {
+        self.pool_partition = Some(key);
+    }
+
+    /// Take the key of the connection pool partition to use, if any.
+    pub(crate) fn take_pool_partition(&mut self) -> Option<String> {
+        self.pool_partition.take()
+    }
+
+    /// Check whether the response body was dropped by its reader.
+    pub(crate) fn is_response_body_dropped(&self) -> bool {
+        self.shared.response_body_dropped.load()
+    }
+
+    /// Get when the response future was dropped before it completed, if it
+    /// was.
+    pub(crate) fn canceled_at(&self) -> Option<Instant> {
+        self.shared.canceled_at.get().copied()
+    }
+
+    /// Record the timeout for the entire transfer, so that the agent can
+    /// enforce it should curl fail to.
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub(crate) fn set_on_headers(&mut self, on_headers: OnHeaders) {
+        self.on_headers = Some(on_headers);
+    }
+
+    /// Report progress to the given callback. Requires progress callbacks to
+    /// be enabled.
+    pub(crate) fn set_on_progress(&mut self, on_progress: OnProgress) {
+        self.on_progress = Some(on_progress);
+    }
+
+    /// Receive responses pushed along with the response.
+    pub(crate) fn enable_push(&mut self) {
+        let (sender, receiver) = flume::unbounded();
+
+        self.push_sender = Some(sender);
+        self.pushed_responses = Some(PushedResponses::new(receiver));
+    }
+
+    /// Get a sender for responses pushed along with the response, if they are
+    /// accepted.
+    pub(crate) fn push_sender(&self) -> Option<Sender<PushedResponse>> {
+        self.push_sender.clone()
+    }
+
+    /// Keep the socket opener used by curl for this handle.
+    pub(crate) fn set_socket_opener(&mut self, opener: Box<SocketOpener>) {
+        self.socket_opener = Some(opener);
+    }
+
+    /// Keep the HSTS exchange used by curl for this handle.
+    pub(crate) fn set_hsts(&mut self, hsts: Box<HstsTransfer>) {
+        self.hsts = Some(hsts);
+    }
+
+    /// Track how fast the response body is received to size the receive
+    /// buffer of the next transfer of this handle, starting at the size of
+    /// the given buffer.
+    pub(crate) fn set_recv_buffer(&mut self, buffer: RecvBufferSize) {
+        self.recv_buffer = Some(buffer);
+    }
+
+    /// Take the receive buffer size learned by the last transfer of this
+    /// handle, to start the next transfer with.
+    pub(crate) fn take_recv_buffer(&mut self) -> Option<RecvBufferSize> {
+        self.recv_buffer.take()
+    }
+
+    /// Compute the digest of the request body while sending it. Use
+    /// [`crate::digest::send_trailer`] to set this up.
+    pub(crate) fn set_request_digest(&mut self, digest: Box<RequestDigest>) {
+        self.request_digest = Some(digest);
+    }
+
+    /// Verify the response body against the digest headers of the response.
+    pub(crate) fn set_verify_digest(&mut self, decoding: bool) {
+        self.response_digest = Some(ResponseDigest::new(decoding));
+    }
+
+    /// Have the agent abort the transfer if curl does not start it within the
+    /// given duration from now. Requires progress callbacks to be enabled.
+    pub(crate) fn set_queue_timeout(&mut self, timeout: Duration) {
+        self.queue_deadline = Some(Instant::now() + timeout);
+    }
+
+    /// Get the deadline for curl to start the transfer by, unless it already
+    /// has.
+    pub(crate) fn queue_deadline(&self) -> Option<Instant> {
+        if self.started {
+            None
+        } else {
+            self.queue_deadline
+        }
+    }
+
+    /// Have the agent abort the transfer if the response does not start to
+    /// arrive within the given duration from when the transfer is started.
+    pub(crate) fn set_headers_timeout(&mut self, timeout: Duration) {
+        self.headers_timeout = Some(timeout);
+    }
+
+    pub(crate) fn headers_timeout(&self) -> Option<Duration> {
+        self.headers_timeout
+    }
+
+    pub(crate) fn set_headers_deadline(&mut self, deadline: Instant) {
+        self.headers_deadline = Some(deadline);
+    }
+
+    /// Get the deadline for the response to start arriving by, unless it
+    /// already has.
+    pub(crate) fn headers_deadline(&self) -> Option<Instant> {
+        if self.response_started {
+            None
+        } else {
+            self.headers_deadline
+        }
+    }
+
+    /// Abort the transfer if no response body data is received for longer
+    /// than the given duration. Requires progress callbacks to be enabled.
+    pub(crate) fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = Some(timeout);
+    }
+
+    /// Have the agent perform connection upkeep at the given interval while
+    /// this request is active. Curl must be configured with the same interval.
+    pub(crate) fn set_upkeep_interval(&mut self, interval: Duration) {
+        self.upkeep_interval = Some(interval);
+    }
+
+    pub(crate) fn upkeep_interval(&self) -> Option<Duration> {
+        self.upkeep_interval
+    }
+
+    /// Abort the transfer if the response body grows past the given limits
+    /// while being decompressed.
+    pub(crate) fn set_decompression_limits(
+        &mut self,
+        max_size: Option<u64>,
+        max_ratio: Option<f64>,
+    ) {
+        self.max_decompressed_size = max_size;
+        self.max_decompression_ratio = max_ratio;
+    }
+
+    /// Decode response bodies ourselves, since curl has been told not to.
+    #[cfg(any(feature = "brotli", feature = "zstd"))]
+    pub(crate) fn set_decode_content(&mut self) {
+        self.decode_content = true;
+    }
+
+    /// Authenticate new TLS connections using the given pre-shared key.
+    #[cfg(feature = "tls-psk")]
+    pub(crate) fn set_pre_shared_key(&mut self, key: crate::config::PreSharedKey) {
+        self.pre_shared_key = Some(key);
+    }
+
+    /// Enable collection of a connection report for this request.
+    pub(crate) fn enable_connection_report(&mut self) {
+        self.connection_report = Some(ConnectionReport::default());
+    }
+
+    /// Enable or disable a single automatic retry of this request when it
+    /// fails on a stale connection.
+    pub(crate) fn set_retry_stale_connection(&mut self, enable: bool) {
+        self.retry_stale_connection = enable;
+    }
+
+    /// Check whether the transfer should be retried after failing with the
+    /// given error, and if so, prepare the handler for another attempt.
+    ///
+    /// Connections taken from the connection cache may have been closed by the
+    /// server while idle without us noticing. If such a connection fails before
+    /// any of the response is received, the server most likely never saw our
+    /// request, so we send it again on a fresh connection. Curl already does
+    /// this itself until the first complete line of the response arrives, so
+    /// this mostly covers connections that fail partway through the response
+    /// headers. Since the server may have seen the request, retries are only
+    /// enabled for idempotent requests. We only do this once, and only if the
+    /// request body can be rewound.
+    pub(crate) fn prepare_stale_connection_retry(
+        &mut self,
+        error: &curl::Error,
+        connection_reused: bool,
+    ) -> bool {
+        let canceled = self.is_future_canceled();
+        let _enter = self.span.enter();
+
+        if !self.retry_stale_connection
+            || !connection_reused
+            || self.response_head_received
+            || canceled
+            || self.shared.result.get().is_some()
+        {
+            return false;
+        }
+
+        if !(error.is_send_error() || error.is_recv_error() || error.is_got_nothing()) {
+            return false;
+        }
+
+        if !self.request_body.reset() {
+            tracing::debug!("stale connection detected, but request body is not rewindable");
+            return false;
+        }
+
+        tracing::debug!("request failed on a stale connection, retrying: {}", error);
+
+        if let Some(digest) = self.request_digest.as_mut() {
+            digest.reset();
+        }
+
+        // The agent will initialize us again once the retry begins.
+        self.retry_stale_connection = false;
+        self.response_started = false;
+        self.request_body_waker = None;
+        self.response_body_waker = None;
+        self.response_status_code = None;
+        self.response_version = None;
+        self.response_headers.clear();
+        self.informational_headers.clear();
+
+        true
     }
 
     /// Set the final result for this transfer.
     pub(crate) fn set_result(&mut self, result: Result<(), Error>) {
+        let result = result.and_then(|()| self.verify_response_digest());
+        let result = result.map_err(|e| {
+            // Surface the reason a custom dialer failed to connect.
+            match self
+                .socket_opener
+                .as_mut()
+                .and_then(|opener| opener.take_error())
+            {
+                Some(source) if e.kind() == ErrorKind::ConnectionFailed => {
+                    Error::new(ErrorKind::ConnectionFailed, source)
+                }
+                _ => e,
+            }
+        });
+        let result = result.map_err(|e| match e.kind() {
+            ErrorKind::ConnectionFailed | ErrorKind::ConnectTimeout => e
+                .with_connect_attempts(mem::take(&mut self.connect_attempts))
+                .with_connection_reused(false),
+            ErrorKind::NameResolution => e.with_connection_reused(false),
+            _ => match self.get_num_connects() {
+                Some(count) => e.with_connection_reused(count == 0),
+                None => e,
+            },
+        });
+
+        // No more responses can be pushed once the transfer is complete, and
+        // pushed responses still in progress hold senders of their own.
+        self.push_sender = None;
+
         if self.shared.result.set(result).is_err() {
             tracing::debug!("attempted to set error multiple times");
         }
 
         // Complete the response future, if we haven't already.
         self.complete_response_future();
+
+        if let (Some(recorder), Some(result)) =
+            (self.detached_recorder.as_mut(), self.shared.result.get())
+        {
+            recorder.record(result);
+        }
+    }
+
+    /// Check the response body received against its digest, if asked to.
+    fn verify_response_digest(&mut self) -> Result<(), Error> {
+        match self.response_digest.take() {
+            // Once the response is built, only trailers are left in the
+            // headers, which may hold a digest as well.
+            Some(digest) if self.detached_at.is_none() => {
+                let result = digest.verify(&self.response_headers);
+
+                if let Err(e) = &result {
+                    tracing::warn!("{}", e);
+                }
+
+                result
+            }
+
+            // Nobody reads the body of a detached response, so not all of it
+            // has been hashed.
+            _ => Ok(()),
+        }
     }
 
     /// Mark the future as completed successfully with the response headers
@@ -235,29 +916,113 @@ impl RequestHandler {
             let result = if let Some(Err(e)) = self.shared.result.get() {
                 tracing::warn!("request completed with error: {}", e);
                 Err(e.clone())
+            } else if let Err(e) = self.check_response_headers() {
+                tracing::debug!("{}", e);
+
+                // Fail the transfer as well, so that curl stops receiving the
+                // response body.
+                let _ = self.shared.result.set(Err(e.clone()));
+
+                Err(e)
             } else {
                 Ok(self.build_response())
             };
 
             if sender.send(result).is_err() {
                 tracing::debug!("request canceled by user");
+
+                if self.detached_at.is_none() {
+                    self.detach();
+                }
+            }
+        }
+    }
+
+    /// Let the header callback decide whether to continue with the final
+    /// response, if there is one.
+    fn check_response_headers(&mut self) -> Result<(), Error> {
+        let on_headers = match self.on_headers.take() {
+            Some(on_headers) => on_headers,
+            None => return Ok(()),
+        };
+
+        // Lend the response so far to the callback, starting with the
+        // extensions of the request like the response itself does.
+        let (mut parts, ()) = http::Response::new(()).into_parts();
+
+        if let Some(status) = self.response_status_code {
+            parts.status = status;
+        }
+
+        if let Some(version) = self.response_version {
+            parts.version = version;
+        }
+
+        parts.headers = mem::take(&mut self.response_headers);
+        parts.extensions = mem::take(&mut self.request_extensions);
+
+        let action = (on_headers.0)(&parts);
+
+        self.response_headers = parts.headers;
+        self.request_extensions = parts.extensions;
+
+        match action {
+            HeaderAction::Accept => Ok(()),
+            HeaderAction::Modify(modify) => {
+                modify(&mut self.request_extensions);
+                Ok(())
             }
+            HeaderAction::Abort => Err(Error::new(
+                ErrorKind::ResponseRejected,
+                io::Error::new(io::ErrorKind::Other, "response rejected by the header callback"),
+            )),
         }
     }
 
     fn build_response(&mut self) -> http::response::Builder {
         let mut builder = http::Response::builder();
 
+        // Start out with the extensions of the request, so that any values
+        // attached to the request are preserved. Values describing the
+        // response are inserted on top.
+        if let Some(extensions) = builder.extensions_mut() {
+            *extensions = mem::take(&mut self.request_extensions);
+        }
+
         if let Some(status) = self.response_status_code.take() {
+            if let Some(recorder) = self.detached_recorder.as_mut() {
+                recorder.set_status(status);
+            }
+
             builder = builder.status(status);
         }
 
+        if let Some(mut report) = self.connection_report.take() {
+            report.remote_addr = self.get_primary_addr();
+            report.http_version = self.response_version;
+            report.proxy_used = self.get_used_proxy();
+            report.reused = self.get_num_connects() == Some(0);
+            builder = builder.extension(report);
+        }
+
+        if let Some(count) = self.get_num_connects() {
+            builder = builder.extension(ConnectionReused(count == 0));
+        }
+
         if let Some(version) = self.response_version.take() {
             builder = builder.version(version);
         }
 
         if let Some(headers) = builder.headers_mut() {
             headers.extend(self.response_headers.drain());
+            crate::headers::merge(headers, &mem::take(&mut self.informational_headers));
+        }
+
+        if let Some(requested) = self.requested_at {
+            builder = builder.extension(ResponseTimes {
+                requested,
+                received: SystemTime::now(),
+            });
         }
 
         if let Some(addr) = self.get_local_addr() {
@@ -268,20 +1033,315 @@ impl RequestHandler {
             builder = builder.extension(RemoteAddr(addr));
         }
 
+        if let Some(pushed_responses) = self.pushed_responses.take() {
+            builder = builder.extension(pushed_responses);
+        }
+
         // Keep the request body around in case interceptors need access to
         // it. Otherwise we're just going to drop it later.
         builder = builder.extension(RequestBody(mem::take(&mut self.request_body)));
 
         // Include metrics in response, but only if it was created. If
-        // metrics are disabled then it won't have been created.
+        // metrics are disabled then it won't have been created. Refresh them
+        // first so that timings up to the response are always included.
         if let Some(metrics) = self.metrics.clone() {
+            scrape_metrics(self.handle, &metrics);
             builder = builder.extension(metrics);
         }
 
         builder
     }
 
+    /// Write a chunk of the response body for the response body reader.
+    fn write_body(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        let span = tracing::trace_span!(parent: &self.span, "write");
+        let _enter = span.enter();
+        tracing::trace!("received {} bytes of data", data.len());
+
+        self.response_started = true;
+
+        // Abort the request if it has been canceled.
+        if self.shared.response_body_dropped.load() || self.is_future_canceled() {
+            return Ok(0);
+        }
+
+        // Sniff the content type from the first chunk of the body before the
+        // response is built. Extensions of the request are carried over to
+        // the response, so the result is stored there.
+        #[cfg(feature = "mime-sniff")]
+        {
+            if self.sender.is_some() && crate::sniff::should_sniff(&self.response_headers) {
+                let content_type = crate::sniff::sniff(data);
+                tracing::debug!("sniffed response content type: {}", content_type);
+                self.request_extensions
+                    .insert(crate::sniff::SniffedContentType(content_type));
+            }
+        }
+
+        if let Err(e) = self.check_decompression_limits(data.len()) {
+            tracing::warn!("{}", e);
+            self.set_result(Err(e));
+            return Ok(0);
+        }
+
+        // Now that we've started receiving the response body, we know no more
+        // redirects can happen and we can complete the future safely.
+        self.complete_response_future();
+
+        // The response may have been rejected by the header callback.
+        if let Some(Err(_)) = self.shared.result.get() {
+            return Ok(0);
+        }
+
+        // If the response future was dropped, nobody will read the response
+        // body, so discard it unless the request should be aborted.
+        if self.detached_at.is_some() {
+            return if self.is_future_canceled() {
+                Ok(0)
+            } else {
+                Ok(data.len())
+            };
+        }
+
+        // Create a task context using a waker provided by the agent so we can
+        // do an asynchronous write.
+        if let Some(waker) = self.response_body_waker.as_ref() {
+            let mut context = Context::from_waker(waker);
+
+            let result = match &mut self.response_body_writer {
+                Some(ResponseBodyWriter::Pipe(writer)) => {
+                    match Pin::new(writer).poll_write(&mut context, data) {
+                        Poll::Pending => {
+                            // Waiting on the reader does not count towards
+                            // the read timeout.
+                            self.last_read = None;
+
+                            return Err(WriteError::Pause);
+                        }
+                        Poll::Ready(result) => result,
+                    }
+                }
+
+                // Spill buffers never pause the transfer.
+                #[cfg(feature = "spill-to-disk")]
+                Some(ResponseBodyWriter::Spill(writer)) => writer.write(data),
+
+                None => Err(io::ErrorKind::BrokenPipe.into()),
+            };
+
+            if self.read_timeout.is_some() {
+                self.last_read = Some(Instant::now());
+            }
+
+            match result {
+                Ok(len) => {
+                    if let Some(digest) = self.response_digest.as_mut() {
+                        digest.update(&data[..len]);
+                    }
+
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.inner.buffer_copies.fetch_add(1);
+                        metrics.inner.buffered_bytes.fetch_add(len as u64);
+                    }
+
+                    // The size of decompressed chunks says little about how
+                    // fast data arrives, so only plain bodies are measured.
+                    if !self.response_encoded {
+                        if let Some(size) = self
+                            .recv_buffer
+                            .as_mut()
+                            .and_then(|buffer| buffer.record(len))
+                        {
+                            tracing::trace!(
+                                "receive buffer of next transfer resized to {} bytes",
+                                size
+                            );
+                        }
+                    }
+
+                    Ok(len)
+                }
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::BrokenPipe {
+                        tracing::warn!(
+                            "failed to write response body because the response reader was dropped"
+                        );
+                    } else {
+                        tracing::error!("error writing response body to buffer: {}", e);
+                    }
+                    Ok(0)
+                }
+            }
+        } else {
+            // The request should never be started without calling init first.
+            tracing::error!("request has not been initialized!");
+            Ok(0)
+        }
+    }
+
+    /// Decode a chunk of the encoded response body and write the decoded
+    /// data. Curl is told that the whole chunk was written once all of the
+    /// data decoded from it has been.
+    #[cfg(any(feature = "brotli", feature = "zstd"))]
+    fn decode_body(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        // If the transfer was paused, curl passes the same chunk again, which
+        // has already been decoded.
+        let decoded = match self.decoded_pending.take() {
+            Some(decoded) => decoded,
+            None => match self.content_decoder.as_mut() {
+                Some(decoder) => match decoder.decode(data) {
+                    Ok(decoded) => decoded,
+                    Err(e) => {
+                        tracing::warn!("failed to decode response body: {}", e);
+                        self.set_result(Err(Error::new(ErrorKind::InvalidContentEncoding, e)));
+                        return Ok(0);
+                    }
+                },
+                None => return self.write_body(data),
+            },
+        };
+
+        // Not every chunk decodes into any data, such as one holding just
+        // the header of a gzip stream.
+        if decoded.is_empty() {
+            return Ok(data.len());
+        }
+
+        match self.write_body(&decoded) {
+            Ok(len) if len == decoded.len() => Ok(data.len()),
+            Ok(_) => Ok(0),
+            Err(WriteError::Pause) => {
+                self.decoded_pending = Some(decoded);
+                Err(WriteError::Pause)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Account for a chunk of the response body of the given length, and
+    /// check whether any decompression limits have been exceeded.
+    fn check_decompression_limits(&mut self, len: usize) -> Result<(), Error> {
+        // Only check the ratio once this many bytes have been decoded, since
+        // small bodies can compress extremely well.
+        const MIN_RATIO_CHECK_LEN: u64 = 1024 * 1024;
+
+        if !self.response_encoded {
+            return Ok(());
+        }
+
+        self.decoded_len += len as u64;
+
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics.inner.decompressed_bytes.store(self.decoded_len);
+        }
+
+        if let Some(max) = self.max_decompressed_size {
+            if self.decoded_len > max {
+                return Err(Error::new(
+                    ErrorKind::DecompressionLimitExceeded,
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("decompressed response body exceeded {} bytes", max),
+                    ),
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_decompression_ratio {
+            if self.decoded_len >= MIN_RATIO_CHECK_LEN {
+                if let Some(encoded_len) = self.get_download_size().filter(|&len| len > 0.0) {
+                    let ratio = self.decoded_len as f64 / encoded_len;
+
+                    if ratio > max {
+                        return Err(Error::new(
+                            ErrorKind::DecompressionLimitExceeded,
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "response body decompression ratio of {:.1} exceeded {}",
+                                    ratio, max
+                                ),
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the number of bytes of the response body received from the server
+    /// so far, before any decoding.
+    fn get_download_size(&mut self) -> Option<f64> {
+        if self.handle.is_null() {
+            return None;
+        }
+
+        let mut size: f64 = 0.0;
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, curl_sys::CURLINFO_SIZE_DOWNLOAD, &mut size)
+                != curl_sys::CURLE_OK
+            {
+                return None;
+            }
+        }
+
+        Some(size)
+    }
+
+    fn get_num_connects(&mut self) -> Option<c_long> {
+        if self.handle.is_null() {
+            return None;
+        }
+
+        let mut count: c_long = 0;
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, curl_sys::CURLINFO_NUM_CONNECTS, &mut count)
+                != curl_sys::CURLE_OK
+            {
+                return None;
+            }
+        }
+
+        Some(count)
+    }
+
+    fn get_used_proxy(&mut self) -> Option<bool> {
+        // Only available since curl 8.7.0, which is newer than the curl-sys
+        // versions we support, so the constant is defined here. Older
+        // versions of curl will return an error for the unknown option.
+        const CURLINFO_USED_PROXY: curl_sys::CURLINFO = curl_sys::CURLINFO_LONG + 66;
+
+        if self.handle.is_null() {
+            return None;
+        }
+
+        let mut used: c_long = 0;
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, CURLINFO_USED_PROXY, &mut used)
+                != curl_sys::CURLE_OK
+            {
+                return None;
+            }
+        }
+
+        Some(used != 0)
+    }
+
     fn get_primary_addr(&mut self) -> Option<SocketAddr> {
+        // Curl only knows the address it would have connected to itself.
+        if let Some(addr) = self
+            .socket_opener
+            .as_ref()
+            .and_then(|opener| opener.peer_addr())
+        {
+            return Some(addr);
+        }
+
         let ip = self.get_primary_ip()?.parse().ok()?;
         let port = self.get_primary_port()?;
 
@@ -387,6 +1447,8 @@ impl curl::easy::Handler for RequestHandler {
         let span = tracing::trace_span!(parent: &self.span, "header");
         let _enter = span.enter();
 
+        self.response_started = true;
+
         // Curl calls this function for all lines in the response not part of
         // the response body, not just for headers. We need to inspect the
         // contents of the string in order to determine what it is and how to
@@ -396,11 +1458,24 @@ impl curl::easy::Handler for RequestHandler {
         // Is this the status line?
         if let Some((version, status)) = parse_status_line(data) {
             self.response_version = Some(version);
-            self.response_status_code = Some(status);
 
-            // Also clear any pre-existing headers that might be left over from
-            // a previous intermediate response.
-            self.response_headers.clear();
+            let previous_status = self.response_status_code.replace(status);
+
+            // Keep the headers of a previous informational response if asked
+            // to, otherwise clear any pre-existing headers that might be left
+            // over from a previous intermediate response.
+            if self.header_merge_policy.merges_informational()
+                && previous_status
+                    .filter(|status| status.is_informational())
+                    .is_some()
+            {
+                let headers = mem::take(&mut self.response_headers);
+                crate::headers::merge(&mut self.informational_headers, &headers);
+            } else {
+                self.response_headers.clear();
+            }
+
+            self.last_read = None;
 
             return true;
         }
@@ -420,6 +1495,39 @@ impl curl::easy::Handler for RequestHandler {
             //
             // Instead, we will complete the future when curl marks the transfer
             // as complete, or when we start receiving a response body.
+            //
+            // Either way, start waiting for the response body now.
+            if self.read_timeout.is_some() {
+                self.last_read = Some(Instant::now());
+            }
+
+            self.response_head_received = true;
+
+            self.response_encoded = !self.response_headers.content_encodings().is_empty();
+
+            #[cfg(any(feature = "brotli", feature = "zstd"))]
+            {
+                self.content_decoder = if self.decode_content && self.response_encoded {
+                    Some(crate::decompress::ContentDecoder::new(
+                        self.response_headers.content_encodings().into_iter(),
+                    ))
+                } else {
+                    None
+                };
+            }
+
+            // The end of trailers received after the body is not the start
+            // of a new response.
+            if self.sender.is_some() {
+                if let Some(digest) = self.response_digest.as_mut() {
+                    digest.begin(
+                        self.response_status_code,
+                        &self.response_headers,
+                        self.response_encoded,
+                    );
+                }
+            }
+
             return true;
         }
 
@@ -444,7 +1552,13 @@ impl curl::easy::Handler for RequestHandler {
 
             match Pin::new(&mut self.request_body).poll_read(&mut context, data) {
                 Poll::Pending => Err(ReadError::Pause),
-                Poll::Ready(Ok(len)) => Ok(len),
+                Poll::Ready(Ok(len)) => {
+                    if let Some(digest) = self.request_digest.as_mut() {
+                        digest.update(&data[..len]);
+                    }
+
+                    Ok(len)
+                }
                 Poll::Ready(Err(e)) => {
                     tracing::error!("error reading request body: {}", e);
 
@@ -479,6 +1593,10 @@ impl curl::easy::Handler for RequestHandler {
         // If curl wants to seek to the beginning, there's a chance that we
         // can do that.
         if whence == io::SeekFrom::Start(0) && self.request_body.reset() {
+            if let Some(digest) = self.request_digest.as_mut() {
+                digest.reset();
+            }
+
             SeekResult::Ok
         } else {
             tracing::warn!("seek requested for request body, but it is not supported");
@@ -489,112 +1607,81 @@ impl curl::easy::Handler for RequestHandler {
 
     /// Gets called by curl when bytes from the response body are received.
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        let span = tracing::trace_span!(parent: &self.span, "write");
-        let _enter = span.enter();
-        tracing::trace!("received {} bytes of data", data.len());
-
-        // Abort the request if it has been canceled.
-        if self.shared.response_body_dropped.load() {
-            return Ok(0);
+        #[cfg(any(feature = "brotli", feature = "zstd"))]
+        {
+            if self.content_decoder.is_some() {
+                return self.decode_body(data);
+            }
         }
 
-        // Now that we've started receiving the response body, we know no more
-        // redirects can happen and we can complete the future safely.
-        self.complete_response_future();
-
-        // Create a task context using a waker provided by the agent so we can
-        // do an asynchronous write.
-        if let Some(waker) = self.response_body_waker.as_ref() {
-            let mut context = Context::from_waker(waker);
+        self.write_body(data)
+    }
 
-            match Pin::new(&mut self.response_body_writer).poll_write(&mut context, data) {
-                Poll::Pending => Err(WriteError::Pause),
-                Poll::Ready(Ok(len)) => Ok(len),
-                Poll::Ready(Err(e)) => {
-                    if e.kind() == io::ErrorKind::BrokenPipe {
-                        tracing::warn!(
-                            "failed to write response body because the response reader was dropped"
-                        );
-                    } else {
-                        tracing::error!("error writing response body to buffer: {}", e);
-                    }
-                    Ok(0)
-                }
+    /// Gets called by curl with the SSL context of each new connection.
+    ///
+    /// This is only overridden when PSK support is enabled, since it replaces
+    /// the default behavior of the curl crate.
+    #[cfg(feature = "tls-psk")]
+    fn ssl_ctx(&mut self, ctx: *mut std::os::raw::c_void) -> Result<(), curl::Error> {
+        if let Some(key) = self.pre_shared_key.as_ref() {
+            // The client only sets a key if curl is using OpenSSL, so the
+            // context is an OpenSSL context.
+            if !unsafe { crate::psk::configure_ssl_ctx(ctx, key) } {
+                tracing::error!("failed to configure pre-shared key");
+                return Err(curl::Error::new(curl_sys::CURLE_SSL_CONNECT_ERROR));
             }
-        } else {
-            // The request should never be started without calling init first.
-            tracing::error!("request has not been initialized!");
-            Ok(0)
         }
+
+        Ok(())
     }
 
     /// Capture transfer progress updates from curl.
     fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
-        // Initialize metrics if required.
-        let metrics = self.metrics.get_or_insert_with(Metrics::new);
-
-        // Store the progress values given.
-        metrics.inner.upload_progress.store(ulnow);
-        metrics.inner.upload_total.store(ultotal);
-        metrics.inner.download_progress.store(dlnow);
-        metrics.inner.download_total.store(dltotal);
-
-        // Also scrape additional metrics.
-        if !self.handle.is_null() {
-            unsafe {
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_SPEED_UPLOAD,
-                    metrics.inner.upload_speed.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_SPEED_DOWNLOAD,
-                    metrics.inner.download_speed.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_NAMELOOKUP_TIME,
-                    metrics.inner.namelookup_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_CONNECT_TIME,
-                    metrics.inner.connect_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_APPCONNECT_TIME,
-                    metrics.inner.appconnect_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_PRETRANSFER_TIME,
-                    metrics.inner.pretransfer_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_STARTTRANSFER_TIME,
-                    metrics.inner.starttransfer_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_TOTAL_TIME,
-                    metrics.inner.total_time.as_ptr(),
-                );
-
-                curl_sys::curl_easy_getinfo(
-                    self.handle,
-                    curl_sys::CURLINFO_REDIRECT_TIME,
-                    metrics.inner.redirect_time.as_ptr(),
-                );
+        // Curl does not call this for transfers that are still waiting for a
+        // connection to become available.
+        self.started = true;
+
+        if let Some(metrics) = self.metrics.as_ref() {
+            // Store the progress values given.
+            metrics.inner.upload_progress.store(ulnow);
+            metrics.inner.upload_total.store(ultotal);
+            metrics.inner.download_progress.store(dlnow);
+            metrics.inner.download_total.store(dltotal);
+
+            // Also scrape additional metrics.
+            scrape_metrics(self.handle, metrics);
+        }
+
+        if let Some(on_progress) = self.on_progress.as_ref() {
+            let progress =
+                Progress::new(ulnow as u64, ultotal as u64, dlnow as u64, dltotal as u64);
+
+            if progress != self.last_progress {
+                self.last_progress = progress;
+                (on_progress.0)(progress);
+            }
+        }
+
+        // Abort the request if it has been canceled.
+        if self.is_future_canceled() {
+            return false;
+        }
+
+        // Curl calls this function periodically even if no data is being
+        // transferred, so this is where we check for stalled response bodies.
+        if let (Some(timeout), Some(last_read)) = (self.read_timeout, self.last_read) {
+            if last_read.elapsed() > timeout {
+                tracing::debug!("no response body data received within {:?}", timeout);
+
+                self.set_result(Err(Error::new(
+                    ErrorKind::ReadTimeout,
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for response body data",
+                    ),
+                )));
+
+                return false;
             }
         }
 
@@ -621,7 +1708,14 @@ impl curl::easy::Handler for RequestHandler {
 
         match kind {
             InfoType::Text => {
-                tracing::debug!("{}", String::from_utf8_lossy(data).trim_end())
+                let text = String::from_utf8_lossy(data);
+
+                if let Some(report) = self.connection_report.as_mut() {
+                    report.record_debug_text(&text);
+                    record_connect_attempt(&mut self.connect_attempts, &text);
+                }
+
+                tracing::debug!("{}", text.trim_end())
             }
             InfoType::HeaderIn | InfoType::DataIn => {
                 tracing::trace!(target: "isahc::wire", "<< {}", FormatAscii(data))
@@ -634,16 +1728,105 @@ impl curl::easy::Handler for RequestHandler {
     }
 }
 
+/// Update the given metrics with the latest values available from curl.
+fn scrape_metrics(handle: *mut CURL, metrics: &Metrics) {
+    if !handle.is_null() {
+        unsafe {
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_SPEED_UPLOAD,
+                metrics.inner.upload_speed.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_SPEED_DOWNLOAD,
+                metrics.inner.download_speed.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_NAMELOOKUP_TIME,
+                metrics.inner.namelookup_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_CONNECT_TIME,
+                metrics.inner.connect_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_APPCONNECT_TIME,
+                metrics.inner.appconnect_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_PRETRANSFER_TIME,
+                metrics.inner.pretransfer_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_STARTTRANSFER_TIME,
+                metrics.inner.starttransfer_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_TOTAL_TIME,
+                metrics.inner.total_time.as_ptr(),
+            );
+
+            curl_sys::curl_easy_getinfo(
+                handle,
+                curl_sys::CURLINFO_REDIRECT_TIME,
+                metrics.inner.redirect_time.as_ptr(),
+            );
+        }
+    }
+}
+
 impl fmt::Debug for RequestHandler {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "RequestHandler")
     }
 }
 
+/// Writing end of the buffer where a response body is written.
+enum ResponseBodyWriter {
+    Pipe(pipe::PipeWriter),
+    #[cfg(feature = "spill-to-disk")]
+    Spill(SpillWriter),
+}
+
+/// Reading end of the buffer where a response body is written.
+enum BodyReader {
+    Pipe(pipe::PipeReader),
+    #[cfg(feature = "spill-to-disk")]
+    Spill(SpillReader),
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            BodyReader::Pipe(reader) => Pin::new(reader).poll_read(cx, buf),
+            #[cfg(feature = "spill-to-disk")]
+            BodyReader::Spill(reader) => Pin::new(reader).poll_read(cx, buf),
+        }
+    }
+}
+
 /// Wrapper around a pipe reader that returns an error that tracks transfer
 /// cancellation.
 pub(crate) struct ResponseBodyReader {
-    inner: pipe::PipeReader,
+    inner: BodyReader,
     shared: Arc<Shared>,
 }
 
@@ -676,5 +1859,31 @@ impl AsyncRead for ResponseBodyReader {
 impl Drop for ResponseBodyReader {
     fn drop(&mut self) {
         self.shared.response_body_dropped.store(true);
+
+        // Curl only gives the handler a chance to abort the transfer when more
+        // data arrives, which for a stalled or long-lived response may be
+        // never. Have the agent abort it instead.
+        self.shared.notify_abandoned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_handler_keeps_recv_buffer_size() {
+        #[cfg(feature = "spill-to-disk")]
+        let (mut handler, _future) = RequestHandler::new(AsyncBody::empty(), None);
+        #[cfg(not(feature = "spill-to-disk"))]
+        let (mut handler, _future) = RequestHandler::new(AsyncBody::empty());
+        let mut buffer = RecvBufferSize::default();
+        let size = buffer.record(buffer.size()).unwrap();
+
+        handler.set_recv_buffer(buffer);
+        handler.release();
+
+        assert_eq!(handler.take_recv_buffer().map(|buffer| buffer.size()), Some(size));
+        assert!(handler.take_recv_buffer().is_none());
     }
 }
@@ -1,4 +1,35 @@
-use http::header::HeaderMap;
+use crate::{date, parsing::split_header_list, response::ResponseTimes};
+use http::header::{self, HeaderMap, HeaderName};
+use std::{
+    cmp,
+    time::{Duration, SystemTime},
+};
+
+/// Headers describing the framing or representation of one particular message.
+/// These are never merged in from other responses, as they would contradict
+/// the values of the response they are merged into.
+const UNMERGEABLE: [HeaderName; 6] = [
+    header::CONTENT_ENCODING,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::CONTENT_TYPE,
+    header::LOCATION,
+    header::TRANSFER_ENCODING,
+];
+
+/// Headers whose values may contain commas but are not comma-separated lists,
+/// and so must never be split.
+const NOT_LISTS: [HeaderName; 9] = [
+    header::DATE,
+    header::EXPIRES,
+    header::IF_MODIFIED_SINCE,
+    header::IF_UNMODIFIED_SINCE,
+    header::LAST_MODIFIED,
+    header::PROXY_AUTHENTICATE,
+    header::RETRY_AFTER,
+    header::SET_COOKIE,
+    header::WWW_AUTHENTICATE,
+];
 
 /// Extension trait for HTTP requests and responses for accessing common headers
 /// in a typed way.
@@ -20,6 +51,16 @@ pub(crate) trait HasHeaders {
             .get(http::header::CONTENT_TYPE)
             .and_then(|v| v.to_str().ok())
     }
+
+    /// Get the content codings applied to the body, in the order in which they
+    /// were applied. Several codings may be listed in a single
+    /// `Content-Encoding` header or spread over several of them, and
+    /// `identity` is left out since it does not change the body.
+    fn content_encodings(&self) -> Vec<&str> {
+        let mut encodings = values(self.headers(), "content-encoding");
+        encodings.retain(|encoding| !encoding.eq_ignore_ascii_case("identity"));
+        encodings
+    }
 }
 
 impl HasHeaders for HeaderMap {
@@ -39,3 +80,241 @@ impl<T> HasHeaders for http::Response<T> {
         self.headers()
     }
 }
+
+/// Append the headers of another response to the given headers, as separate
+/// values. Headers that only make sense for the response they were sent with
+/// are skipped.
+pub(crate) fn merge(target: &mut HeaderMap, source: &HeaderMap) {
+    for (name, value) in source {
+        if !UNMERGEABLE.contains(name) {
+            target.append(name, value.clone());
+        }
+    }
+}
+
+/// Get all values of a header, splitting comma-separated lists into their
+/// elements. Values of headers that are not lists, such as `Set-Cookie`, are
+/// returned as is. Values that are not valid UTF-8 are skipped.
+pub(crate) fn values<'a>(headers: &'a HeaderMap, name: &str) -> Vec<&'a str> {
+    let is_list = !NOT_LISTS
+        .iter()
+        .any(|header| header.as_str().eq_ignore_ascii_case(name));
+
+    let mut values = Vec::new();
+
+    for value in headers.get_all(name).iter().filter_map(|v| v.to_str().ok()) {
+        if is_list {
+            values.extend(split_header_list(value));
+        } else {
+            values.push(value);
+        }
+    }
+
+    values
+}
+
+/// Get how long the server asked to wait before making another request, from
+/// the `Retry-After` header.
+///
+/// If the header contains a date, the delay is measured from the `Date` header
+/// of the same response if there is one, so that the clocks of the client and
+/// server do not need to agree. Otherwise it is measured from now. A date in
+/// the past results in a delay of zero.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        // Absurdly large delays overflow and are treated as no delay given.
+        return value.parse().ok().map(Duration::from_secs);
+    }
+
+    let retry_at = date::parse(value)?;
+    let now = date(headers, header::DATE).unwrap_or_else(SystemTime::now);
+
+    Some(
+        retry_at
+            .duration_since(now)
+            .unwrap_or_else(|_| Duration::from_secs(0)),
+    )
+}
+
+/// Get the value of a header containing an HTTP date.
+pub(crate) fn date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(date::parse)
+}
+
+/// Compute the current age of a response as of the given time, as described
+/// in [RFC 7234, section 4.2.3](https://tools.ietf.org/html/rfc7234#section-4.2.3).
+pub(crate) fn age(
+    headers: &HeaderMap,
+    times: Option<&ResponseTimes>,
+    now: SystemTime,
+) -> Option<Duration> {
+    let age_value = headers
+        .get(header::AGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs);
+    let date_value = date(headers, header::DATE);
+
+    let elapsed =
+        |earlier: SystemTime, later: SystemTime| later.duration_since(earlier).unwrap_or_default();
+
+    match times {
+        Some(times) => {
+            let apparent_age = date_value
+                .map(|date| elapsed(date, times.received))
+                .unwrap_or_default();
+            let response_delay = elapsed(times.requested, times.received);
+            let corrected_age_value = age_value.unwrap_or_default() + response_delay;
+            let corrected_initial_age = cmp::max(apparent_age, corrected_age_value);
+            let resident_time = elapsed(times.received, now);
+
+            Some(corrected_initial_age + resident_time)
+        }
+
+        // Without knowing when the response was received, the best we can do
+        // is to assume it was just now.
+        None if age_value.is_none() && date_value.is_none() => None,
+        None => {
+            let apparent_age = date_value
+                .map(|date| elapsed(date, now))
+                .unwrap_or_default();
+
+            Some(cmp::max(apparent_age, age_value.unwrap_or_default()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_skips_framing_headers() {
+        let mut target = HeaderMap::new();
+        target.insert(header::CONTENT_LENGTH, "5".parse().unwrap());
+        target.insert(header::SET_COOKIE, "a=1".parse().unwrap());
+
+        let mut source = HeaderMap::new();
+        source.insert(header::CONTENT_LENGTH, "0".parse().unwrap());
+        source.insert(header::LOCATION, "/next".parse().unwrap());
+        source.insert(header::SET_COOKIE, "b=2".parse().unwrap());
+
+        merge(&mut target, &source);
+
+        assert_eq!(target.get_all(header::CONTENT_LENGTH).iter().count(), 1);
+        assert!(!target.contains_key(header::LOCATION));
+        assert_eq!(
+            target
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["a=1", "b=2"]
+        );
+    }
+
+    #[test]
+    fn values_splits_lists_only() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::VARY, "accept, accept-encoding".parse().unwrap());
+        headers.append(header::VARY, "origin".parse().unwrap());
+        headers.append(
+            header::SET_COOKIE,
+            "id=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"
+                .parse()
+                .unwrap(),
+        );
+
+        assert_eq!(values(&headers, "vary"), vec![
+            "accept",
+            "accept-encoding",
+            "origin"
+        ]);
+        assert_eq!(values(&headers, "Set-Cookie"), vec![
+            "id=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"
+        ]);
+        assert!(values(&headers, "missing").is_empty());
+    }
+
+    #[test]
+    fn content_encodings_are_listed_in_order_applied() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::CONTENT_ENCODING, "identity".parse().unwrap());
+        headers.append(header::CONTENT_ENCODING, "gzip, Identity, br".parse().unwrap());
+        headers.append(header::CONTENT_ENCODING, "deflate".parse().unwrap());
+
+        assert_eq!(headers.content_encodings(), vec!["gzip", "br", "deflate"]);
+
+        headers.clear();
+        headers.insert(header::CONTENT_ENCODING, "identity".parse().unwrap());
+
+        assert!(headers.content_encodings().is_empty());
+    }
+
+    #[test]
+    fn retry_after_delay_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(header::RETRY_AFTER, "-1".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn retry_after_date_is_relative_to_date_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::RETRY_AFTER,
+            "Fri, 31 Dec 1999 23:59:59 GMT".parse().unwrap(),
+        );
+        headers.insert(
+            header::DATE,
+            "Fri, 31 Dec 1999 23:58:29 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(90)));
+
+        // Without a date, the delay is relative to now, which is later.
+        headers.remove(header::DATE);
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn age_accounts_for_delay_and_resident_time() {
+        let at = |seconds| std::time::UNIX_EPOCH + Duration::from_secs(seconds);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::DATE, date::format(at(1000)).parse().unwrap());
+        headers.insert(header::AGE, "30".parse().unwrap());
+
+        let times = ResponseTimes {
+            requested: at(1000),
+            received: at(1002),
+        };
+
+        // Age plus the time the request took, plus the time held since.
+        assert_eq!(
+            age(&headers, Some(&times), at(1010)),
+            Some(Duration::from_secs(40))
+        );
+
+        // A date far in the past outweighs the age header.
+        headers.insert(header::DATE, date::format(at(900)).parse().unwrap());
+        assert_eq!(
+            age(&headers, Some(&times), at(1002)),
+            Some(Duration::from_secs(102))
+        );
+
+        assert_eq!(
+            age(&headers, None, at(1000)),
+            Some(Duration::from_secs(100))
+        );
+        assert_eq!(age(&HeaderMap::new(), None, at(1000)), None);
+    }
+}
@@ -0,0 +1,380 @@
+//! Telling heartbeats apart from data in a streaming response body, and
+//! noticing when the body stalls.
+
+use crate::task::Delay;
+use futures_lite::{io::AsyncRead, stream::Stream};
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Lines larger than this are rejected unless configured otherwise.
+const DEFAULT_MAX_LINE_SIZE: usize = 1024 * 1024;
+
+/// Size of the chunks read from the body at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// Decides whether a line is a heartbeat.
+type Detector = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// Something that happened on a streaming response body, as yielded by
+/// [`Heartbeats`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Activity {
+    /// A line of data, without its line ending.
+    Data(Vec<u8>),
+
+    /// A line that the server sent only to keep the connection alive, without
+    /// its line ending.
+    Heartbeat(Vec<u8>),
+
+    /// Nothing has been received for at least the idle timeout. The duration
+    /// is the time since anything was last received.
+    ///
+    /// This is yielded again each time another idle timeout passes without
+    /// anything being received, with a growing duration.
+    Idle(Duration),
+}
+
+/// A stream of the lines in a streaming response body, with heartbeats told
+/// apart from data, that also reports when the body stalls.
+///
+/// This is created by
+/// [`AsyncReadResponseExt::heartbeats`](crate::AsyncReadResponseExt::heartbeats).
+/// Each line is passed to a detector that decides whether it is a heartbeat.
+/// By default, lines that are empty or only contain whitespace and lines that
+/// start with `:`, such as comments in server-sent events, are heartbeats. A
+/// different detector can be set with [`detector`](Heartbeats::detector).
+///
+/// An [`Activity::Heartbeat`] means that the connection is alive, even though
+/// no data is being sent, while an [`Activity::Idle`] means that nothing at
+/// all has been received for a while and the connection may have stalled. The
+/// stream keeps going after an idle event, so it is up to the consumer to give
+/// up on the response.
+///
+/// Like [`Split`](crate::Split), lines larger than a maximum size of 1 MiB by
+/// default are rejected with an error of kind
+/// [`InvalidData`](io::ErrorKind::InvalidData), which ends the stream.
+#[must_use = "streams do nothing unless polled"]
+pub struct Heartbeats<'a, T> {
+    reader: &'a mut T,
+    detector: Detector,
+    idle_timeout: Duration,
+    max_line_size: usize,
+    buf: Vec<u8>,
+
+    /// Number of bytes at the start of the buffer that are known to not
+    /// contain a line ending.
+    searched: usize,
+
+    /// When anything was last received.
+    last_received: Instant,
+
+    /// When the next idle event is due, unless something is received before.
+    idle_at: Instant,
+
+    /// Timer for the next idle check. This is only replaced when it fires, so
+    /// that receiving data does not start a new timer each time.
+    delay: Option<Delay>,
+
+    done: bool,
+}
+
+impl<'a, T: AsyncRead + Unpin> Heartbeats<'a, T> {
+    pub(crate) fn new(reader: &'a mut T, idle_timeout: Duration) -> Self {
+        let now = Instant::now();
+
+        Self {
+            reader,
+            detector: Box::new(is_default_heartbeat),
+            idle_timeout,
+            max_line_size: DEFAULT_MAX_LINE_SIZE,
+            buf: Vec::new(),
+            searched: 0,
+            last_received: now,
+            idle_at: now + idle_timeout,
+            delay: None,
+            done: false,
+        }
+    }
+
+    /// Set the function that decides whether a line is a heartbeat.
+    ///
+    /// The function is given each line without its line ending, including any
+    /// carriage return before it.
+    ///
+    /// # Examples
+    ///
+    /// Blank lines end events in server-sent events, so only comments should
+    /// count as heartbeats:
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let mut response = isahc::get_async("https://example.org/events").await?;
+    /// let activity = response
+    ///     .heartbeats(Duration::from_secs(30))
+    ///     .detector(|line| line.starts_with(b":"));
+    /// # Ok(()) }
+    /// ```
+    pub fn detector<F>(mut self, detector: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.detector = Box::new(detector);
+        self
+    }
+
+    /// Set the maximum size of a line in bytes, not counting the line ending.
+    pub fn max_line_size(mut self, max: usize) -> Self {
+        self.max_line_size = max;
+        self
+    }
+
+    /// Take the next complete line out of the buffer, if there is one.
+    fn next_line(&mut self) -> Option<io::Result<Activity>> {
+        match self.buf[self.searched..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+        {
+            Some(i) => {
+                let len = self.searched + i;
+
+                if len > self.max_line_size {
+                    return Some(Err(self.too_large()));
+                }
+
+                let mut line = self.buf.split_off(len + 1);
+                std::mem::swap(&mut line, &mut self.buf);
+                line.pop();
+                self.searched = 0;
+
+                Some(Ok(self.classify(line)))
+            }
+            None if self.buf.len() > self.max_line_size => Some(Err(self.too_large())),
+            None => {
+                self.searched = self.buf.len();
+                None
+            }
+        }
+    }
+
+    fn classify(&self, line: Vec<u8>) -> Activity {
+        if (self.detector)(&line) {
+            Activity::Heartbeat(line)
+        } else {
+            Activity::Data(line)
+        }
+    }
+
+    /// Check whether the body has been idle for too long, once the reader has
+    /// nothing to offer.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<Activity> {
+        loop {
+            let idle_at = self.idle_at;
+            let delay = self.delay.get_or_insert_with(|| {
+                Delay::new(idle_at.saturating_duration_since(Instant::now()))
+            });
+
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+
+            self.delay = None;
+
+            let now = Instant::now();
+
+            if now >= self.idle_at {
+                self.idle_at = now + self.idle_timeout;
+
+                return Poll::Ready(Activity::Idle(now - self.last_received));
+            }
+        }
+    }
+
+    fn received(&mut self) {
+        self.last_received = Instant::now();
+        self.idle_at = self.last_received + self.idle_timeout;
+    }
+
+    fn too_large(&mut self) -> io::Error {
+        self.done = true;
+        self.buf = Vec::new();
+        self.delay = None;
+
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "line exceeds the maximum size of {} bytes",
+                self.max_line_size
+            ),
+        )
+    }
+}
+
+impl<T: AsyncRead + Unpin> Stream for Heartbeats<'_, T> {
+    type Item = io::Result<Activity>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(line) = this.next_line() {
+                return Poll::Ready(Some(line));
+            }
+
+            if this.done {
+                return Poll::Ready(if this.buf.is_empty() {
+                    None
+                } else {
+                    this.searched = 0;
+                    let line = std::mem::take(&mut this.buf);
+                    Some(Ok(this.classify(line)))
+                });
+            }
+
+            let filled = this.buf.len();
+            this.buf.resize(filled + READ_SIZE, 0);
+
+            let result = Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf[filled..]);
+
+            match result {
+                Poll::Ready(Ok(n)) => {
+                    this.buf.truncate(filled + n);
+                    this.done = n == 0;
+
+                    if this.done {
+                        this.delay = None;
+                    } else {
+                        this.received();
+                    }
+                }
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {
+                    this.buf.truncate(filled);
+                }
+                Poll::Ready(Err(e)) => {
+                    this.buf.truncate(filled);
+                    this.done = true;
+                    this.delay = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Pending => {
+                    this.buf.truncate(filled);
+                    return this.poll_idle(cx).map(|activity| Some(Ok(activity)));
+                }
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Heartbeats<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heartbeats")
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_line_size", &self.max_line_size)
+            .finish()
+    }
+}
+
+/// Lines that are blank or are comments in server-sent events are
+/// heartbeats by default.
+fn is_default_heartbeat(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace) || line.first() == Some(&b':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::{future::block_on, stream::StreamExt};
+
+    fn activity(body: &[u8]) -> Vec<Activity> {
+        let mut reader = body;
+
+        block_on(
+            Heartbeats::new(&mut reader, Duration::from_secs(60))
+                .map(Result::unwrap)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn heartbeats_are_told_apart_from_data() {
+        assert_eq!(activity(b"{\"a\":1}\n\n: ping\r\n \r\n{\"b\":2}"), vec![
+            Activity::Data(b"{\"a\":1}".to_vec()),
+            Activity::Heartbeat(b"".to_vec()),
+            Activity::Heartbeat(b": ping\r".to_vec()),
+            Activity::Heartbeat(b" \r".to_vec()),
+            Activity::Data(b"{\"b\":2}".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn custom_detector_is_used() {
+        let mut reader = &b"data: 1\n\n:keep-alive\n"[..];
+
+        let activity = block_on(
+            Heartbeats::new(&mut reader, Duration::from_secs(60))
+                .detector(|line| line.starts_with(b":"))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(activity, vec![
+            Activity::Data(b"data: 1".to_vec()),
+            Activity::Data(b"".to_vec()),
+            Activity::Heartbeat(b":keep-alive".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn stalled_body_is_reported_as_idle() {
+        // A reader that never produces anything.
+        struct Stalled;
+
+        impl AsyncRead for Stalled {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Pending
+            }
+        }
+
+        let mut reader = Stalled;
+        let mut stream = Heartbeats::new(&mut reader, Duration::from_millis(20));
+
+        for i in 1..=2 {
+            match block_on(stream.next()) {
+                Some(Ok(Activity::Idle(idle))) => {
+                    assert!(idle >= Duration::from_millis(20) * i);
+                }
+                other => panic!("unexpected activity: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_line_ends_stream_with_error() {
+        let mut reader = &b"short\nthis is much too long\nshort\n"[..];
+
+        let activity = block_on(
+            Heartbeats::new(&mut reader, Duration::from_secs(60))
+                .max_line_size(8)
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(activity.len(), 2);
+        assert_eq!(
+            activity[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! HTTP Strict Transport Security (HSTS).
+//!
+//! Servers can use the `Strict-Transport-Security` response header to tell
+//! clients to only ever reach them over HTTPS from now on, as described in
+//! [RFC 6797](https://tools.ietf.org/html/rfc6797). Once a client has
+//! received this header over HTTPS, it upgrades any later `http://` request to
+//! that host to `https://` before sending it, which protects against attacks
+//! that strip TLS from a connection.
+//!
+//! Isahc does not do this by default. To enable it, assign an [`HstsStore`] to
+//! a client or a request using
+//! [`Configurable::hsts`](crate::config::Configurable::hsts). The store keeps
+//! track of the hosts learned from responses, and can persist them to a file
+//! so that they are remembered across restarts. Requests to hosts in the store
+//! are upgraded by libcurl, including each request made while following
+//! redirects. The [effective URI](crate::ResponseExt::effective_uri) of a
+//! response still refers to the URI as it was requested, before the upgrade.
+//!
+//! A host must have been visited over HTTPS at least once before its requests
+//! are upgraded. To protect the very first request too, hosts can be preloaded
+//! into the store. With the [`hsts-preload`](../index.html#hsts-preload)
+//! feature enabled, a list of top-level domains that are known to be HTTPS
+//! only can be preloaded using [`HstsStore::with_preload_list`].
+
+#[cfg(feature = "hsts-preload")]
+mod preload;
+mod store;
+pub(crate) mod transfer;
+
+pub use self::store::{HstsEntry, HstsStore};
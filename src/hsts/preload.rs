@@ -0,0 +1,15 @@
+//! The embedded HSTS preload list.
+
+/// Top-level domains whose hosts are all preloaded as HTTPS only, including
+/// subdomains.
+///
+/// Browsers ship with a preload list of many thousands of individual hosts,
+/// maintained at <https://hstspreload.org>. Embedding all of them would add
+/// megabytes to every binary and go stale quickly, so only the top-level
+/// domains that are preloaded in their entirety are included here. Individual
+/// hosts can be added with [`HstsStore::preload`](super::HstsStore::preload).
+pub(super) static TOP_LEVEL_DOMAINS: &[&str] = &[
+    "android", "app", "boo", "channel", "chrome", "dad", "day", "dev", "eat", "esq", "fly", "foo",
+    "gle", "gmail", "google", "hangout", "how", "ing", "meme", "mov", "new", "nexus", "page",
+    "phd", "prof", "rsvp", "search", "soy", "youtube", "zip",
+];
@@ -0,0 +1,515 @@
+use crate::date;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+/// Header of the files written by a store, in the same format as the HSTS
+/// cache files of curl.
+const FILE_HEADER: &str = "\
+# Your HSTS cache. https://curl.se/docs/hsts.html
+# This file was generated by isahc! Edit at your own risk.
+";
+
+/// A host that must only be reached over HTTPS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HstsEntry {
+    host: String,
+    include_subdomains: bool,
+    expires: Option<SystemTime>,
+}
+
+impl HstsEntry {
+    pub(crate) fn new(host: &str, include_subdomains: bool, expires: Option<SystemTime>) -> Self {
+        Self {
+            host: normalize_host(host),
+            include_subdomains,
+            expires,
+        }
+    }
+
+    /// Get the host this entry applies to.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Check whether this entry applies to the subdomains of its host as well.
+    pub fn include_subdomains(&self) -> bool {
+        self.include_subdomains
+    }
+
+    /// Get the time this entry expires, or `None` if it never does, as is the
+    /// case for preloaded entries.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.map(|expires| expires <= now).unwrap_or(false)
+    }
+
+    /// Parse a line of an HSTS cache file, such as
+    /// `.example.org "20300101 00:00:00"`.
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let host = parts.next()?;
+        let expires = parts.next()?.trim();
+
+        if expires.len() < 2 || !expires.starts_with('"') || !expires.ends_with('"') {
+            return None;
+        }
+
+        let expires = match &expires[1..expires.len() - 1] {
+            "unlimited" => None,
+            expires => Some(date::parse_compact(expires)?),
+        };
+
+        let (host, include_subdomains) = if host.starts_with('.') {
+            (&host[1..], true)
+        } else {
+            (host, false)
+        };
+
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(Self::new(host, include_subdomains, expires))
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}{} \"{}\"",
+            if self.include_subdomains { "." } else { "" },
+            self.host,
+            self.expires
+                .and_then(date::format_compact)
+                .as_deref()
+                .unwrap_or("unlimited")
+        )
+    }
+}
+
+/// Keeps track of the hosts that must only be reached over HTTPS.
+///
+/// Hosts are learned from the `Strict-Transport-Security` headers of
+/// responses received over HTTPS by the requests using the store. Stores are
+/// designed to be shared across many concurrent requests, so cloning a store
+/// simply returns a new reference to the same store.
+///
+/// Stores opened from a file are kept in sync with it. The file uses the same
+/// format as the HSTS cache of curl, so it can be shared with the `--hsts`
+/// option of the curl command line tool.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{hsts::HstsStore, prelude::*};
+/// use std::time::Duration;
+///
+/// let store = HstsStore::new();
+/// store.insert("example.org", true, Duration::from_secs(31_536_000));
+///
+/// let client = HttpClient::builder().hsts(store.clone()).build()?;
+///
+/// assert!(store.get("www.example.org").unwrap().include_subdomains());
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HstsStore {
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Entries learned from responses or inserted, by host.
+    entries: HashMap<String, HstsEntry>,
+
+    /// Preloaded entries, by host. These are never persisted.
+    preloaded: HashMap<String, HstsEntry>,
+
+    /// The file the learned entries are persisted to, if any.
+    path: Option<PathBuf>,
+
+    /// Whether any entry changed since the file was last written.
+    dirty: bool,
+}
+
+impl HstsStore {
+    /// Create a new, empty store that is only kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a store persisted to the given file, reading the entries it
+    /// already contains. The file is created once there is anything to store
+    /// in it.
+    ///
+    /// The file is written whenever a host is added to or removed from the
+    /// store, and when the last reference to the store is dropped. Lines of
+    /// the file that cannot be parsed, as well as expired entries, are
+    /// skipped.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let now = SystemTime::now();
+
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(HstsEntry::parse)
+                .filter(|entry| !entry.is_expired(now))
+                .map(|entry| (entry.host.clone(), entry))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            inner: Arc::new(RwLock::new(Inner {
+                entries,
+                preloaded: HashMap::new(),
+                path: Some(path),
+                dirty: false,
+            })),
+        })
+    }
+
+    /// Preload the top-level domains whose hosts are all known to be HTTPS
+    /// only, such as `.dev` and `.app`.
+    ///
+    /// The embedded list only contains such top-level domains, which are a
+    /// small part of the preload list used by browsers. Other hosts can be
+    /// preloaded individually with [`HstsStore::preload`].
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`hsts-preload`](../index.html#hsts-preload) feature is enabled.
+    #[cfg(feature = "hsts-preload")]
+    pub fn with_preload_list(self) -> Self {
+        for domain in super::preload::TOP_LEVEL_DOMAINS {
+            self.preload(domain, true);
+        }
+
+        self
+    }
+
+    /// Preload a host, so that requests to it are upgraded to HTTPS without
+    /// ever having to visit it first.
+    ///
+    /// Preloaded entries never expire, are not persisted, and are kept even
+    /// if the host sends a `Strict-Transport-Security` header with a
+    /// `max-age` of zero or the store is cleared.
+    pub fn preload(&self, host: &str, include_subdomains: bool) {
+        let entry = HstsEntry::new(host, include_subdomains, None);
+
+        self.inner
+            .write()
+            .unwrap()
+            .preloaded
+            .insert(entry.host.clone(), entry);
+    }
+
+    /// Add a host to the store, as if it had sent a `Strict-Transport-Security`
+    /// header with the given `max-age`. A `max-age` of zero removes the host
+    /// instead.
+    pub fn insert(&self, host: &str, include_subdomains: bool, max_age: Duration) {
+        if max_age == Duration::from_secs(0) {
+            self.remove(host);
+            return;
+        }
+
+        let expires = SystemTime::now().checked_add(max_age);
+        let entry = HstsEntry::new(host, include_subdomains, expires);
+        let mut inner = self.inner.write().unwrap();
+
+        inner.entries.insert(entry.host.clone(), entry);
+        inner.changed(true);
+    }
+
+    /// Get the entry that applies to the given host, if any.
+    ///
+    /// This is either an entry for the host itself, or an entry that includes
+    /// subdomains for one of its parent domains.
+    pub fn get(&self, host: &str) -> Option<HstsEntry> {
+        self.applicable(host).into_iter().next()
+    }
+
+    /// Get a copy of all the entries learned from responses or inserted,
+    /// sorted by host. Preloaded entries are not included.
+    pub fn entries(&self) -> Vec<HstsEntry> {
+        let now = SystemTime::now();
+        let mut entries = self
+            .inner
+            .read()
+            .unwrap()
+            .entries
+            .values()
+            .filter(|entry| !entry.is_expired(now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        entries.sort_by(|a, b| a.host.cmp(&b.host));
+
+        entries
+    }
+
+    /// Remove the entry learned for the given host, returning it if there was
+    /// one. Entries for parent domains and preloaded entries are kept.
+    pub fn remove(&self, host: &str) -> Option<HstsEntry> {
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner.entries.remove(&normalize_host(host));
+
+        if entry.is_some() {
+            inner.changed(true);
+        }
+
+        entry
+    }
+
+    /// Remove all entries learned from responses or inserted. Preloaded
+    /// entries are kept.
+    pub fn clear(&self) {
+        let mut inner = self.inner.write().unwrap();
+
+        if !inner.entries.is_empty() {
+            inner.entries.clear();
+            inner.changed(true);
+        }
+    }
+
+    /// Write the entries to the file the store was opened from right away.
+    /// Does nothing for stores that are only kept in memory.
+    pub fn save(&self) -> io::Result<()> {
+        self.inner.write().unwrap().save()
+    }
+
+    /// Get the entries that apply to the given host, the most specific one
+    /// first.
+    pub(crate) fn applicable(&self, host: &str) -> Vec<HstsEntry> {
+        let host = normalize_host(host);
+        let inner = self.inner.read().unwrap();
+        let now = SystemTime::now();
+        let mut entries = Vec::new();
+        let mut domain = host.as_str();
+
+        loop {
+            for map in &[&inner.entries, &inner.preloaded] {
+                if let Some(entry) = map.get(domain) {
+                    if (domain.len() == host.len() || entry.include_subdomains)
+                        && !entry.is_expired(now)
+                    {
+                        entries.push(entry.clone());
+                    }
+                }
+            }
+
+            match domain.find('.') {
+                Some(dot) => domain = &domain[dot + 1..],
+                None => break,
+            }
+        }
+
+        entries
+    }
+
+    /// Update the store with the entries curl had in its cache after a
+    /// transfer, given the entries that were loaded into the cache before the
+    /// transfer. Entries that are gone were removed by the server.
+    pub(crate) fn merge(&self, loaded: &[HstsEntry], saved: Vec<HstsEntry>) {
+        let mut inner = self.inner.write().unwrap();
+        let mut hosts_changed = false;
+
+        for entry in loaded {
+            if !saved.iter().any(|saved| saved.host == entry.host)
+                && inner.entries.remove(&entry.host).is_some()
+            {
+                hosts_changed = true;
+            }
+        }
+
+        for entry in saved {
+            // Preloaded entries are handed back unchanged.
+            if entry.expires.is_none() && inner.preloaded.contains_key(&entry.host) {
+                continue;
+            }
+
+            match inner.entries.insert(entry.host.clone(), entry.clone()) {
+                Some(previous) if previous == entry => continue,
+                Some(previous) if previous.include_subdomains == entry.include_subdomains => {
+                    inner.dirty = true;
+                }
+                _ => hosts_changed = true,
+            }
+        }
+
+        if hosts_changed {
+            inner.changed(true);
+        }
+    }
+}
+
+impl Inner {
+    fn changed(&mut self, save: bool) {
+        self.dirty = true;
+
+        if save {
+            if let Err(e) = self.save() {
+                tracing::warn!("failed to save HSTS entries: {}", e);
+            }
+        }
+    }
+
+    fn save(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let now = SystemTime::now();
+        let mut entries = self
+            .entries
+            .values()
+            .filter(|entry| !entry.is_expired(now))
+            .collect::<Vec<_>>();
+
+        entries.sort_by(|a, b| a.host.cmp(&b.host));
+
+        let mut buf = FILE_HEADER.as_bytes().to_vec();
+
+        for entry in entries {
+            entry.write(&mut buf)?;
+        }
+
+        // Write to a temporary file next to it first, so that the file is
+        // never left partially written. The name includes the process ID so
+        // that processes sharing the file do not write to the same one.
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".{}.tmp", std::process::id()));
+
+        fs::write(&temp_path, &buf)?;
+
+        if let Err(e) = fs::rename(&temp_path, path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        self.dirty = false;
+
+        Ok(())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if self.dirty {
+            if let Err(e) = self.save() {
+                tracing::warn!("failed to save HSTS entries: {}", e);
+            }
+        }
+    }
+}
+
+/// Hosts are case-insensitive, and may have a trailing dot.
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_apply_to_subdomains_only_if_included() {
+        let store = HstsStore::new();
+        store.insert("example.org", false, Duration::from_secs(60));
+        store.insert("example.com", true, Duration::from_secs(60));
+
+        assert!(store.get("example.org").is_some());
+        assert!(store.get("www.example.org").is_none());
+        assert_eq!(store.get("WWW.Example.COM.").unwrap().host(), "example.com");
+        assert!(store.get("notexample.com").is_none());
+    }
+
+    #[test]
+    fn most_specific_entry_comes_first() {
+        let store = HstsStore::new();
+        store.preload("org", true);
+        store.insert("example.org", true, Duration::from_secs(60));
+        store.insert("www.example.org", false, Duration::from_secs(60));
+
+        let hosts = store
+            .applicable("www.example.org")
+            .into_iter()
+            .map(|entry| entry.host)
+            .collect::<Vec<_>>();
+
+        assert_eq!(hosts, ["www.example.org", "example.org", "org"]);
+    }
+
+    #[test]
+    fn clear_keeps_preloaded_entries() {
+        let store = HstsStore::new();
+        store.preload("dev", true);
+        store.insert("example.org", false, Duration::from_secs(60));
+        store.clear();
+
+        assert!(store.entries().is_empty());
+        assert!(store.get("example.org").is_none());
+        assert_eq!(store.get("example.dev").unwrap().expires(), None);
+    }
+
+    #[test]
+    fn merge_removes_entries_gone_from_curl() {
+        let store = HstsStore::new();
+        store.preload("dev", true);
+        store.insert("example.org", false, Duration::from_secs(60));
+        store.insert("example.dev", false, Duration::from_secs(60));
+
+        let loaded = store.applicable("example.dev");
+        let expires = store.get("example.org").unwrap().expires;
+        let learned = HstsEntry::new("new.example.org", false, expires);
+
+        // The server removed example.dev with a max-age of zero.
+        store.merge(&loaded, vec![HstsEntry::new("dev", true, None), learned.clone()]);
+
+        assert_eq!(store.entries(), vec![store.get("example.org").unwrap(), learned]);
+        assert_eq!(store.get("example.dev").unwrap().host(), "dev");
+    }
+
+    #[test]
+    fn file_format_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hsts.txt");
+
+        fs::write(
+            &path,
+            "# comment\n\
+             .example.org \"29991231 23:59:59\"\n\
+             example.com \"unlimited\"\n\
+             expired.org \"20000101 00:00:00\"\n\
+             invalid.org \"yesterday\"\n",
+        )
+        .unwrap();
+
+        let store = HstsStore::open(&path).unwrap();
+
+        assert_eq!(store.entries().len(), 2);
+        assert!(store.get("www.example.org").is_some());
+        assert_eq!(store.get("example.com").unwrap().expires(), None);
+
+        store.remove("example.com");
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}.example.org \"29991231 23:59:59\"\n", FILE_HEADER)
+        );
+    }
+}
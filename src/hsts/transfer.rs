@@ -0,0 +1,226 @@
+//! Exchange of entries between a store and the HSTS cache of a curl handle.
+//!
+//! Every curl handle has an HSTS cache of its own. Before a transfer starts,
+//! curl asks for entries to fill it with using a read callback, so we hand it
+//! the entries that apply to the host of the request. Curl upgrades the
+//! request if any of them does, and updates its cache with the
+//! `Strict-Transport-Security` headers it receives. Once the handle is
+//! cleaned up, curl passes the entries in its cache to a write callback, so
+//! that we can update the store with them.
+
+#![allow(unsafe_code)]
+
+use super::{HstsEntry, HstsStore};
+use crate::date;
+use curl::easy::Easy2;
+use std::{
+    mem,
+    os::raw::{c_char, c_int, c_long, c_void},
+    ptr,
+    slice,
+    str,
+};
+
+// Not yet defined by curl-sys.
+const CURLOPT_HSTS_CTRL: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 299;
+const CURLOPT_HSTSREADFUNCTION: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_FUNCTIONPOINT + 301;
+const CURLOPT_HSTSREADDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 302;
+const CURLOPT_HSTSWRITEFUNCTION: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_FUNCTIONPOINT + 303;
+const CURLOPT_HSTSWRITEDATA: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_OBJECTPOINT + 304;
+const CURLHSTS_ENABLE: c_long = 1;
+
+const CURLSTS_OK: c_int = 0;
+const CURLSTS_DONE: c_int = 1;
+
+/// The `includeSubDomains` bit field of `struct curl_hstsentry`. MSVC gives it
+/// a whole `unsigned int`, while other compilers share its storage with the
+/// field after it.
+#[cfg(target_env = "msvc")]
+type Flags = std::os::raw::c_uint;
+#[cfg(not(target_env = "msvc"))]
+type Flags = u8;
+
+#[cfg(target_endian = "big")]
+const INCLUDE_SUBDOMAINS: Flags = 1 << (mem::size_of::<Flags>() * 8 - 1);
+#[cfg(target_endian = "little")]
+const INCLUDE_SUBDOMAINS: Flags = 1;
+
+/// An entry of the HSTS cache of curl, `struct curl_hstsentry`.
+#[repr(C)]
+struct RawEntry {
+    name: *mut c_char,
+    namelen: usize,
+    flags: Flags,
+    expire: [c_char; 18],
+}
+
+/// Exchanges entries between a store and the HSTS cache of a single curl
+/// handle.
+pub(crate) struct HstsTransfer {
+    store: HstsStore,
+
+    /// The entries handed to curl before the transfer started.
+    loaded: Vec<HstsEntry>,
+
+    /// The next entry to hand to curl.
+    next: usize,
+
+    /// Whether curl filled its cache with the entries at least once.
+    started: bool,
+
+    /// The entries curl had in its cache once the handle was cleaned up.
+    saved: Vec<HstsEntry>,
+}
+
+impl HstsTransfer {
+    /// Create an exchange for a request to the given host.
+    pub(crate) fn new(store: HstsStore, host: Option<&str>) -> Box<Self> {
+        Box::new(Self {
+            loaded: host.map(|host| store.applicable(host)).unwrap_or_default(),
+            store,
+            next: 0,
+            started: false,
+            saved: Vec::new(),
+        })
+    }
+
+    /// Enable HSTS for the given handle, using this exchange.
+    ///
+    /// Curl holds a pointer to the exchange, so it must be kept alive and in
+    /// place for as long as the handle is.
+    pub(crate) fn set_callbacks<H>(&mut self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        let data = self as *mut Self as *mut c_void;
+        let read_callback: extern "C" fn(*mut curl_sys::CURL, *mut RawEntry, *mut c_void) -> c_int =
+            read_callback;
+        let write_callback: extern "C" fn(
+            *mut curl_sys::CURL,
+            *mut RawEntry,
+            *mut c_void,
+            *mut c_void,
+        ) -> c_int = write_callback;
+
+        // Use unsafe API, because the safe API does not expose these options.
+        unsafe {
+            match curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_HSTS_CTRL, CURLHSTS_ENABLE) {
+                curl_sys::CURLE_OK => {}
+                code => return Err(curl::Error::new(code)),
+            }
+
+            for &(option, value) in &[
+                (CURLOPT_HSTSREADFUNCTION, read_callback as *mut c_void),
+                (CURLOPT_HSTSREADDATA, data),
+                (CURLOPT_HSTSWRITEFUNCTION, write_callback as *mut c_void),
+                (CURLOPT_HSTSWRITEDATA, data),
+            ] {
+                match curl_sys::curl_easy_setopt(easy.raw(), option, value) {
+                    curl_sys::CURLE_OK => {}
+                    code => return Err(curl::Error::new(code)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, raw: &mut RawEntry) -> c_int {
+        let entry = loop {
+            match self.loaded.get(self.next) {
+                Some(entry) => {
+                    self.next += 1;
+
+                    // Curl provides room for the longest host it accepts.
+                    if entry.host().len() < raw.namelen {
+                        break entry;
+                    }
+                }
+                None => {
+                    // Start over if curl fills its cache again, such as when
+                    // retrying the request.
+                    self.next = 0;
+                    self.started = true;
+
+                    return CURLSTS_DONE;
+                }
+            }
+        };
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                entry.host().as_ptr() as *const c_char,
+                raw.name,
+                entry.host().len(),
+            );
+            *raw.name.add(entry.host().len()) = 0;
+        }
+
+        if entry.include_subdomains() {
+            raw.flags |= INCLUDE_SUBDOMAINS;
+        }
+
+        // An empty expiry date means the entry never expires.
+        let expire = entry
+            .expires()
+            .and_then(date::format_compact)
+            .unwrap_or_default();
+
+        for (dest, &byte) in raw.expire.iter_mut().zip(expire.as_bytes()) {
+            *dest = byte as c_char;
+        }
+        raw.expire[expire.len()] = 0;
+
+        CURLSTS_OK
+    }
+
+    fn write(&mut self, raw: &RawEntry) -> c_int {
+        let name = unsafe { slice::from_raw_parts(raw.name as *const u8, raw.namelen) };
+        let expire = unsafe { slice::from_raw_parts(raw.expire.as_ptr() as *const u8, 18) };
+        let expire = &expire[..expire.iter().position(|&b| b == 0).unwrap_or(18)];
+
+        let expires = match str::from_utf8(expire) {
+            Ok("unlimited") => Some(None),
+            Ok(expire) => date::parse_compact(expire).map(Some),
+            Err(_) => None,
+        };
+
+        if let (Ok(host), Some(expires)) = (str::from_utf8(name), expires) {
+            let include_subdomains = raw.flags & INCLUDE_SUBDOMAINS != 0;
+
+            self.saved
+                .push(HstsEntry::new(host, include_subdomains, expires));
+        }
+
+        CURLSTS_OK
+    }
+}
+
+impl Drop for HstsTransfer {
+    fn drop(&mut self) {
+        // If curl never filled its cache, it has nothing to tell.
+        if self.started {
+            self.store.merge(&self.loaded, mem::take(&mut self.saved));
+        }
+    }
+}
+
+/// Called by curl to get the next entry to fill its cache with.
+extern "C" fn read_callback(
+    _easy: *mut curl_sys::CURL,
+    raw: *mut RawEntry,
+    data: *mut c_void,
+) -> c_int {
+    let transfer = unsafe { &mut *(data as *mut HstsTransfer) };
+
+    transfer.read(unsafe { &mut *raw })
+}
+
+/// Called by curl with each entry in its cache when the handle is cleaned up.
+extern "C" fn write_callback(
+    _easy: *mut curl_sys::CURL,
+    raw: *mut RawEntry,
+    _index: *mut c_void,
+    data: *mut c_void,
+) -> c_int {
+    let transfer = unsafe { &mut *(data as *mut HstsTransfer) };
+
+    transfer.write(unsafe { &*raw })
+}
@@ -1,34 +1,117 @@
 use super::{Interceptor, InterceptorFuture, InterceptorObj};
-use crate::{body::AsyncBody, error::Error};
+use crate::{body::AsyncBody, error::Error, response::ConnectionReused};
 use http::{Request, Response};
-use std::{fmt, sync::Arc};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 /// Execution context for an interceptor.
 pub struct Context<'a> {
     pub(crate) invoker: Arc<dyn Invoke + Send + Sync + 'a>,
     pub(crate) interceptors: &'a [InterceptorObj],
+    pub(crate) last_attempt: Mutex<Option<Attempt>>,
 }
 
 impl<'a> Context<'a> {
     /// Send a request asynchronously, executing the next interceptor in the
     /// chain, if any.
     pub async fn send(&self, request: Request<AsyncBody>) -> Result<Response<AsyncBody>, Error> {
-        if let Some(interceptor) = self.interceptors.first() {
+        let result = if let Some(interceptor) = self.interceptors.first() {
             let inner_context = Self {
                 invoker: self.invoker.clone(),
                 interceptors: &self.interceptors[1..],
+                last_attempt: Mutex::new(None),
             };
 
             interceptor.intercept(request, inner_context).await
         } else {
             self.invoker.invoke(request).await
-        }
+        };
+
+        let mut last_attempt = self.last_attempt.lock().unwrap_or_else(|e| e.into_inner());
+        let number = last_attempt.as_ref().map_or(0, |attempt| attempt.number) + 1;
+
+        *last_attempt = Some(match &result {
+            Ok(response) => Attempt {
+                number,
+                connection_reused: response
+                    .extensions()
+                    .get::<ConnectionReused>()
+                    .map(|reused| reused.0),
+                error: None,
+            },
+            Err(e) => Attempt {
+                number,
+                connection_reused: e.connection_reused(),
+                error: Some(e.clone()),
+            },
+        });
+
+        result
+    }
+
+    /// Get the number of requests sent using [`send`](Context::send) so far.
+    ///
+    /// An interceptor that retries requests can use this to limit the number
+    /// of attempts made.
+    pub fn attempts(&self) -> u32 {
+        self.last_attempt().map_or(0, |attempt| attempt.number)
+    }
+
+    /// Get information about the most recent request sent using
+    /// [`send`](Context::send), or `None` if no request has been sent yet.
+    pub fn last_attempt(&self) -> Option<Attempt> {
+        self.last_attempt
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 }
 
 impl fmt::Debug for Context<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Context").finish()
+        f.debug_struct("Context")
+            .field("last_attempt", &self.last_attempt())
+            .finish()
+    }
+}
+
+/// Information about a request sent by an interceptor using
+/// [`Context::send`].
+///
+/// This can help an interceptor decide whether a failed request is worth
+/// retrying. For example, a request sent over a connection reused from an
+/// earlier request can fail because the server closed the connection while it
+/// was idle, and is usually safe to retry on a new connection, while the same
+/// failure on a new connection is more likely to be a problem with the server.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    number: u32,
+    connection_reused: Option<bool>,
+    error: Option<Error>,
+}
+
+impl Attempt {
+    /// Get the number of this attempt, starting at 1 for the first request
+    /// sent.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Get whether the request was sent over a connection that was reused from
+    /// an earlier request, if known.
+    ///
+    /// This is not known if the request failed before a connection was chosen,
+    /// or if the response or error was created by another interceptor.
+    pub fn is_connection_reused(&self) -> Option<bool> {
+        self.connection_reused
+    }
+
+    /// Get the error the request failed with, or `None` if a response was
+    /// received.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
     }
 }
 
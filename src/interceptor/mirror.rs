@@ -0,0 +1,272 @@
+use super::{Context, Interceptor, InterceptorFuture};
+use crate::{body::AsyncBody, error::Error, HttpClient};
+use http::{uri::PathAndQuery, Request, Uri};
+use once_cell::sync::OnceCell;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+};
+
+/// An interceptor that mirrors a percentage of requests to a secondary server.
+///
+/// Mirroring, also known as shadow traffic, is useful for testing a new
+/// backend with real requests without affecting the responses returned to the
+/// caller. Each selected request is copied and sent to the mirror in the
+/// background, and the response from the mirror is discarded. The original
+/// request is always sent as usual, and is never delayed by the mirror.
+///
+/// Mirrored requests keep the method, path, query, and headers of the original
+/// request, but are sent to the scheme and authority of the mirror URI. If the
+/// mirror URI has a path, it is prepended to the path of the request. Requests
+/// with a streaming body cannot be copied, and are never mirrored.
+///
+/// Mirrored requests are sent by a separate client from a fixed number of
+/// background threads, which limits how many can be in flight at once. As many
+/// requests again can wait in a queue, and once that is full further requests
+/// are not mirrored until the mirror catches up.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::{interceptor::MirrorInterceptor, prelude::*, HttpClient};
+///
+/// let client = HttpClient::builder()
+///     .interceptor(
+///         MirrorInterceptor::new("http://canary.example.org".parse()?)
+///             .percentage(10.0)
+///             .max_concurrency(8),
+///     )
+///     .build()?;
+///
+/// // About one in ten requests is also sent to the canary.
+/// let response = client.get("http://example.org/api/users")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct MirrorInterceptor {
+    uri: Uri,
+    percentage: f64,
+    max_concurrency: usize,
+    client: Option<HttpClient>,
+    sender: OnceCell<Option<flume::Sender<Request<AsyncBody>>>>,
+    count: AtomicU64,
+}
+
+impl MirrorInterceptor {
+    /// Create a new interceptor that mirrors all requests to the given URI.
+    pub fn new(uri: Uri) -> Self {
+        Self {
+            uri,
+            percentage: 100.0,
+            max_concurrency: 4,
+            client: None,
+            sender: OnceCell::new(),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the percentage of requests to mirror, between 0 and 100.
+    ///
+    /// Requests are selected at an even interval rather than randomly, so for
+    /// example with 25 percent every fourth request is mirrored. The default
+    /// is 100.
+    pub fn percentage(mut self, percentage: f64) -> Self {
+        self.percentage = if percentage > 0.0 {
+            percentage.min(100.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Set the maximum number of mirrored requests that can be in flight at
+    /// once. The default is 4.
+    pub fn max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = max.max(1);
+        self
+    }
+
+    /// Set the client to use for sending mirrored requests.
+    ///
+    /// This can be used to configure options such as timeouts for mirrored
+    /// requests. By default a new client with the default configuration is
+    /// created when the first request is mirrored.
+    pub fn client(mut self, client: HttpClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Decide whether the next request should be mirrored.
+    fn sample(&self) -> bool {
+        let n = self.count.fetch_add(1, Ordering::Relaxed) as f64;
+
+        // Mirror a request whenever the running total of the requested
+        // fraction passes the next whole number.
+        ((n + 1.0) * self.percentage / 100.0).floor() > (n * self.percentage / 100.0).floor()
+    }
+
+    /// Create a copy of the given request to send to the mirror.
+    fn mirror_request(&self, request: &Request<AsyncBody>) -> Option<Request<AsyncBody>> {
+        let body = request.body().try_clone()?;
+
+        let mut path = self.uri.path().trim_end_matches('/').to_owned();
+        path.push_str(
+            request
+                .uri()
+                .path_and_query()
+                .map(PathAndQuery::as_str)
+                .unwrap_or("/"),
+        );
+
+        let mut parts = self.uri.clone().into_parts();
+        parts.path_and_query = Some(path.parse().ok()?);
+
+        let mut mirror = Request::new(body);
+        *mirror.method_mut() = request.method().clone();
+        *mirror.uri_mut() = Uri::from_parts(parts).ok()?;
+        *mirror.version_mut() = request.version();
+        *mirror.headers_mut() = request.headers().clone();
+        mirror.headers_mut().remove(http::header::HOST);
+
+        Some(mirror)
+    }
+
+    /// Get the queue for mirrored requests, starting the background threads
+    /// that send them if necessary.
+    fn sender(&self) -> Option<&flume::Sender<Request<AsyncBody>>> {
+        self.sender
+            .get_or_init(|| {
+                let client = match self.client.clone() {
+                    Some(client) => client,
+                    None => match HttpClient::new() {
+                        Ok(client) => client,
+                        Err(e) => {
+                            tracing::warn!("failed to create client for mirroring: {}", e);
+                            return None;
+                        }
+                    },
+                };
+
+                let (sender, receiver) = flume::bounded(self.max_concurrency);
+
+                for i in 0..self.max_concurrency {
+                    let client = client.clone();
+                    let receiver = receiver.clone();
+
+                    let result = thread::Builder::new()
+                        .name(format!("isahc-mirror-{}", i))
+                        .spawn(move || {
+                            // Runs until the interceptor is dropped.
+                            for request in receiver.iter() {
+                                futures_lite::future::block_on(send_mirrored(&client, request));
+                            }
+                        });
+
+                    if let Err(e) = result {
+                        tracing::warn!("failed to spawn mirroring thread: {}", e);
+                    }
+                }
+
+                Some(sender)
+            })
+            .as_ref()
+    }
+}
+
+async fn send_mirrored(client: &HttpClient, request: Request<AsyncBody>) {
+    let uri = request.uri().clone();
+
+    match client.send_async(request).await {
+        Ok(mut response) => {
+            let result =
+                futures_lite::io::copy(response.body_mut(), futures_lite::io::sink()).await;
+
+            if let Err(e) = result {
+                tracing::debug!(%uri, "failed to read mirrored response: {}", e);
+            }
+        }
+        Err(e) => tracing::debug!(%uri, "mirrored request failed: {}", e),
+    }
+}
+
+impl Interceptor for MirrorInterceptor {
+    type Err = Error;
+
+    fn intercept<'a>(
+        &'a self,
+        request: Request<AsyncBody>,
+        ctx: Context<'a>,
+    ) -> InterceptorFuture<'a, Self::Err> {
+        Box::pin(async move {
+            if self.sample() {
+                if let Some(mirror) = self.mirror_request(&request) {
+                    if let Some(sender) = self.sender() {
+                        // Never wait for the mirror; if the queue is full then
+                        // this request is simply not mirrored.
+                        if sender.try_send(mirror).is_err() {
+                            tracing::debug!("mirroring queue is full, skipping request");
+                        }
+                    }
+                }
+            }
+
+            ctx.send(request).await
+        })
+    }
+}
+
+impl fmt::Debug for MirrorInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MirrorInterceptor")
+            .field("uri", &self.uri)
+            .field("percentage", &self.percentage)
+            .field("max_concurrency", &self.max_concurrency)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(100.0, 10)]
+    #[test_case(50.0, 5)]
+    #[test_case(25.0, 2)]
+    #[test_case(0.0, 0)]
+    fn sample_selects_percentage_of_requests(percentage: f64, expected: usize) {
+        let interceptor =
+            MirrorInterceptor::new(Uri::from_static("http://mirror")).percentage(percentage);
+
+        assert_eq!((0..10).filter(|_| interceptor.sample()).count(), expected);
+    }
+
+    #[test_case("http://mirror", "http://example.org/a?b=c", "http://mirror/a?b=c")]
+    #[test_case("http://mirror:8080/v2/", "http://example.org/a", "http://mirror:8080/v2/a")]
+    fn mirror_request_uri(mirror: &str, uri: &str, expected: &str) {
+        let interceptor = MirrorInterceptor::new(mirror.parse().unwrap());
+        let request = Request::post(uri)
+            .header("host", "example.org")
+            .header("x-foo", "bar")
+            .body(AsyncBody::from("hello"))
+            .unwrap();
+
+        let mirror = interceptor.mirror_request(&request).unwrap();
+
+        assert_eq!(mirror.uri(), expected);
+        assert_eq!(mirror.method(), http::Method::POST);
+        assert_eq!(mirror.headers()["x-foo"], "bar");
+        assert!(!mirror.headers().contains_key("host"));
+        assert_eq!(mirror.body().len(), Some(5));
+    }
+
+    #[test]
+    fn streaming_request_is_not_mirrored() {
+        let interceptor = MirrorInterceptor::new(Uri::from_static("http://mirror"));
+        let request = Request::post("http://example.org")
+            .body(AsyncBody::from_reader(futures_lite::io::empty()))
+            .unwrap();
+
+        assert!(interceptor.mirror_request(&request).is_none());
+    }
+}
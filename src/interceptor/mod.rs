@@ -2,38 +2,79 @@
 //!
 //! This module provides the core types and functions for defining and working
 //! with interceptors. Interceptors are handlers that augment HTTP client
-//! functionality by decorating HTTP calls with custom logic.
+//! functionality by decorating HTTP calls with custom logic, such as adding
+//! authentication tokens, signing requests, or logging responses.
+//!
+//! An interceptor receives each outgoing request along with a [`Context`],
+//! which it uses to pass the request on to the rest of the chain and
+//! eventually to the server. Since this happens asynchronously, the
+//! interceptor can modify the request before sending it, modify the response
+//! before returning it, send the request more than once, or even respond
+//! without sending the request at all.
+//!
+//! Interceptors are registered on a client using
+//! [`HttpClientBuilder::interceptor`](crate::HttpClientBuilder::interceptor).
+//!
+//! # Examples
+//!
+//! ```
+//! use isahc::{
+//!     interceptor::{Context, Interceptor, InterceptorFuture},
+//!     prelude::*,
+//!     AsyncBody,
+//!     HttpClient,
+//! };
+//!
+//! /// Adds a bearer token to every request, and logs responses.
+//! struct BearerAuth(String);
+//!
+//! impl Interceptor for BearerAuth {
+//!     type Err = isahc::Error;
+//!
+//!     fn intercept<'a>(
+//!         &'a self,
+//!         mut request: Request<AsyncBody>,
+//!         ctx: Context<'a>,
+//!     ) -> InterceptorFuture<'a, Self::Err> {
+//!         Box::pin(async move {
+//!             let value = format!("Bearer {}", self.0).parse().unwrap();
+//!             request.headers_mut().insert("authorization", value);
+//!
+//!             let response = ctx.send(request).await?;
+//!             println!("received {}", response.status());
+//!
+//!             Ok(response)
+//!         })
+//!     }
+//! }
+//!
+//! let client = HttpClient::builder()
+//!     .interceptor(BearerAuth(String::from("secret")))
+//!     .build()?;
+//! # Ok::<(), isahc::Error>(())
+//! ```
 //!
 //! Known issues:
 //!
 //! - [`from_fn`] doesn't work as desired. The trait bounds are too ambiguous
 //!   for the compiler to infer for closures, and since the return type is
 //!   generic over a lifetime, there's no way to give the return type the
-//!   correct name using current Rust syntax.
+//!   correct name using current Rust syntax. The [`interceptor!`] macro can
+//!   be used to define an interceptor inline instead.
 //! - [`InterceptorObj`] wraps the returned future in an extra box.
-//! - If an interceptor returns a custom error, it is stringified and wrapped in
-//!   `Error::Curl`. We should introduce a new error variant that boxes the
-//!   error and also records the type of the interceptor that created the error
-//!   for visibility. But we can't add a new variant right now without a BC
-//!   break. See [#182](https://github.com/sagebind/isahc/issues/182).
-//! - Automatic redirect following currently bypasses interceptors for
-//!   subsequent requests. This will be fixed when redirect handling is
-//!   rewritten as an interceptor itself. See
-//!   [#232](https://github.com/sagebind/isahc/issues/232).
-///
-/// # Availability
-///
-/// This module is only available when the
-/// [`unstable-interceptors`](../index.html#unstable-interceptors) feature is
-/// enabled.
+
 use crate::body::AsyncBody;
 use http::{Request, Response};
 use std::{error::Error, fmt, future::Future, pin::Pin};
 
 mod context;
+mod mirror;
 mod obj;
 
-pub use self::context::Context;
+pub use self::{
+    context::{Attempt, Context},
+    mirror::MirrorInterceptor,
+};
 pub(crate) use self::{context::Invoke, obj::InterceptorObj};
 
 type InterceptorResult<E> = Result<Response<AsyncBody>, E>;
@@ -41,7 +82,6 @@ type InterceptorResult<E> = Result<Response<AsyncBody>, E>;
 /// Defines an inline interceptor using a closure-like syntax.
 ///
 /// Closures are not supported due to a limitation in Rust's type inference.
-#[cfg(feature = "unstable-interceptors")]
 #[macro_export]
 macro_rules! interceptor {
     ($request:ident, $ctx:ident, $body:expr) => {{
@@ -173,8 +213,6 @@ mod private {
     }
 
     impl_async_fn! {
-        (AsyncFnOnce0, AsyncFnMut0, AsyncFn0, ()),
-        (AsyncFnOnce1, AsyncFnMut1, AsyncFn1, (a0:A0, )),
         (AsyncFnOnce2, AsyncFnMut2, AsyncFn2, (a0:A0, a1:A1, )),
     }
 }
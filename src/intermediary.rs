@@ -0,0 +1,281 @@
+//! Information about the intermediaries and alternative services that a
+//! response passed through on its way to the client.
+
+use crate::parsing::{split_header_list, split_params, unquote};
+use http::{StatusCode, Version};
+
+/// The alternative service that a request was sent to, as indicated by the
+/// `Alt-Used` request header.
+#[derive(Clone, Debug)]
+pub(crate) struct AltUsed(pub(crate) String);
+
+/// An intermediary that forwarded a response, as listed in the `Via` header.
+///
+/// Proxies and gateways add an entry to the `Via` header when forwarding a
+/// message, as described in [RFC 7230, section
+/// 5.7.1](https://tools.ietf.org/html/rfc7230#section-5.7.1). Use
+/// [`ResponseExt::via`](crate::ResponseExt::via) to get the entries of a
+/// response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Via {
+    protocol: String,
+    received_by: String,
+    comment: Option<String>,
+}
+
+impl Via {
+    /// Get the protocol that the intermediary received the message with, as
+    /// written in the header, such as `1.1` or `HTTP/2`. The protocol name is
+    /// omitted for HTTP.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Get the HTTP version that the intermediary received the message with,
+    /// or `None` if the message was received using another protocol.
+    pub fn version(&self) -> Option<Version> {
+        let (name, version) = match self.protocol.find('/') {
+            Some(i) => (&self.protocol[..i], &self.protocol[i + 1..]),
+            None => ("HTTP", self.protocol.as_str()),
+        };
+
+        if !name.eq_ignore_ascii_case("HTTP") {
+            return None;
+        }
+
+        match version {
+            "0.9" => Some(Version::HTTP_09),
+            "1.0" => Some(Version::HTTP_10),
+            "1.1" => Some(Version::HTTP_11),
+            "2" | "2.0" => Some(Version::HTTP_2),
+            "3" | "3.0" => Some(Version::HTTP_3),
+            _ => None,
+        }
+    }
+
+    /// Get the host and optional port of the intermediary, or a pseudonym if
+    /// it chose not to reveal its host.
+    pub fn received_by(&self) -> &str {
+        &self.received_by
+    }
+
+    /// Get the comment identifying the software of the intermediary, such as
+    /// `Squid/3.1`, without the surrounding parentheses.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+}
+
+/// The status of a response as reported by a proxy, as listed in the
+/// `Proxy-Status` header.
+///
+/// Proxies use the `Proxy-Status` header described in [RFC
+/// 9209](https://tools.ietf.org/html/rfc9209) to explain how they handled a
+/// response, and in particular why they generated an error response instead
+/// of forwarding one from the next hop. Use
+/// [`ResponseExt::proxy_status`](crate::ResponseExt::proxy_status) to get the
+/// entries of a response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProxyStatus {
+    proxy: String,
+    params: Vec<(String, String)>,
+}
+
+impl ProxyStatus {
+    /// Get the name identifying the proxy.
+    pub fn proxy(&self) -> &str {
+        &self.proxy
+    }
+
+    /// Get the type of error the proxy encountered, such as
+    /// `connection_timeout` or `dns_error`, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.param("error")
+    }
+
+    /// Get the host name or address of the next hop that the proxy forwarded
+    /// the request to, if revealed.
+    pub fn next_hop(&self) -> Option<&str> {
+        self.param("next-hop")
+    }
+
+    /// Get the status code that the proxy received from the next hop, if it
+    /// received a response.
+    pub fn received_status(&self) -> Option<StatusCode> {
+        self.param("received-status")?.parse().ok()
+    }
+
+    /// Get additional human-readable details about the error, if any.
+    pub fn details(&self) -> Option<&str> {
+        self.param("details")
+    }
+
+    /// Get the value of a parameter of the entry by name. Parameters without
+    /// a value are returned as `?1`, the boolean true of structured header
+    /// fields.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse the entries of a `Via` header value. Malformed entries are skipped.
+pub(crate) fn parse_via(value: &str) -> Vec<Via> {
+    split_via_list(value)
+        .into_iter()
+        .filter_map(|entry| {
+            let mut parts = entry.splitn(2, |c: char| c.is_ascii_whitespace());
+            let protocol = parts.next()?;
+            let rest = parts.next()?.trim_start();
+
+            let end = rest
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            let received_by = &rest[..end];
+            let rest = rest[end..].trim();
+
+            if protocol.is_empty() || received_by.is_empty() {
+                return None;
+            }
+
+            let comment = if rest.starts_with('(') && rest.ends_with(')') {
+                Some(rest[1..rest.len() - 1].to_owned())
+            } else {
+                None
+            };
+
+            Some(Via {
+                protocol: protocol.to_owned(),
+                received_by: received_by.to_owned(),
+                comment,
+            })
+        })
+        .collect()
+}
+
+/// Split a `Via` header value into its entries. Unlike most lists, entries may
+/// contain comments in parentheses, which may themselves contain commas.
+fn split_via_list(value: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if depth > 0 => escaped = true,
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                entries.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    entries.push(value[start..].trim());
+    entries.retain(|entry| !entry.is_empty());
+    entries
+}
+
+/// Parse the entries of a `Proxy-Status` header value. Malformed entries are
+/// skipped.
+pub(crate) fn parse_proxy_status(value: &str) -> Vec<ProxyStatus> {
+    split_header_list(value)
+        .into_iter()
+        .filter_map(|entry| {
+            let mut parts = split_params(entry).into_iter();
+            let proxy = unquote(parts.next()?.trim());
+
+            if proxy.is_empty() {
+                return None;
+            }
+
+            let params = parts
+                .filter_map(|param| {
+                    let mut param = param.splitn(2, '=');
+                    let key = param.next()?.trim();
+
+                    if key.is_empty() {
+                        return None;
+                    }
+
+                    let value = match param.next() {
+                        Some(value) => unquote(value.trim()),
+                        None => String::from("?1"),
+                    };
+
+                    Some((key.to_owned(), value))
+                })
+                .collect();
+
+            Some(ProxyStatus {
+                proxy,
+                params,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_via_entries() {
+        let via = parse_via("1.0 fred, 1.1 p.example.net (Squid/3.1, like Apache), HTTP/2 cdn");
+
+        assert_eq!(via.len(), 3);
+
+        assert_eq!(via[0].protocol(), "1.0");
+        assert_eq!(via[0].version(), Some(Version::HTTP_10));
+        assert_eq!(via[0].received_by(), "fred");
+        assert_eq!(via[0].comment(), None);
+
+        assert_eq!(via[1].version(), Some(Version::HTTP_11));
+        assert_eq!(via[1].received_by(), "p.example.net");
+        assert_eq!(via[1].comment(), Some("Squid/3.1, like Apache"));
+
+        assert_eq!(via[2].protocol(), "HTTP/2");
+        assert_eq!(via[2].version(), Some(Version::HTTP_2));
+        assert_eq!(via[2].received_by(), "cdn");
+    }
+
+    #[test]
+    fn malformed_via_entries_are_skipped() {
+        let via = parse_via("1.1, , RTSP/1.0 media:554");
+
+        assert_eq!(via.len(), 1);
+        assert_eq!(via[0].version(), None);
+        assert_eq!(via[0].received_by(), "media:554");
+    }
+
+    #[test]
+    fn parse_proxy_status_entries() {
+        let statuses = parse_proxy_status(concat!(
+            "origin-lb; received-status=503, ",
+            r#""ExampleCDN"; error=connection_timeout; next-hop="lb.example.net"; "#,
+            r#"details="gave up; \"slow\""; cached"#,
+        ));
+
+        assert_eq!(statuses.len(), 2);
+
+        assert_eq!(statuses[0].proxy(), "origin-lb");
+        assert_eq!(
+            statuses[0].received_status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(statuses[0].error(), None);
+
+        assert_eq!(statuses[1].proxy(), "ExampleCDN");
+        assert_eq!(statuses[1].error(), Some("connection_timeout"));
+        assert_eq!(statuses[1].next_hop(), Some("lb.example.net"));
+        assert_eq!(statuses[1].details(), Some("gave up; \"slow\""));
+        assert_eq!(statuses[1].param("cached"), Some("?1"));
+        assert_eq!(statuses[1].received_status(), None);
+    }
+}
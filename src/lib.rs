@@ -149,10 +149,27 @@
 //!
 //! Below is a list of all available feature flags and their meanings.
 //!
+//! ## `brotli`
+//!
+//! Enable decompressing responses compressed with brotli, even if libcurl was
+//! built without support for it. Disabled by default.
+//!
 //! ## `cookies`
 //!
 //! Enable persistent HTTP cookie support. Disabled by default.
 //!
+//! ## `hsts-preload`
+//!
+//! Embed a list of top-level domains that are known to be HTTPS only, which
+//! can be preloaded into an [`HstsStore`](hsts::HstsStore) using
+//! [`HstsStore::with_preload_list`](hsts::HstsStore::with_preload_list).
+//! Disabled by default.
+//!
+//! ## `http-cache`
+//!
+//! Enable the [`cache`] module for caching responses in memory or on disk,
+//! following the rules of RFC 7234. Disabled by default.
+//!
 //! ## `http2`
 //!
 //! Enable compile-time support for HTTP/2 in libcurl via libnghttp2. This does
@@ -167,11 +184,56 @@
 //! Additional serialization and deserialization of JSON bodies via
 //! [serde](https://serde.rs). Disabled by default.
 //!
+//! ## `mail`
+//!
+//! Enable the [`mail`] module for sending email using SMTP. This requires a
+//! libcurl with SMTP support, which the bundled libcurl does not include, so
+//! `static-curl` must be disabled. Disabled by default.
+//!
+//! ## `mime-sniff`
+//!
+//! Enable sniffing the content type of responses that do not declare a
+//! specific one from the first bytes of the response body, using the [WHATWG
+//! MIME Sniffing Standard](https://mimesniff.spec.whatwg.org). Disabled by
+//! default.
+//!
+//! ## `ntlm`
+//!
+//! Enable support for NTLM authentication, as used by Windows servers and
+//! corporate proxies. This makes the `ntlm` scheme available in the API and, if
+//! `static-curl` is enabled, compiles libcurl with NTLM support, which also
+//! requires a TLS backend. Disabled by default.
+//!
 //! ## `psl`
 //!
 //! Enable use of the Public Suffix List to filter out potentially malicious
 //! cross-domain cookies. Implies `cookies`, disabled by default.
 //!
+//! ## `rustls`
+//!
+//! Build the bundled libcurl with [rustls](https://github.com/rustls/rustls)
+//! as its TLS backend instead of OpenSSL. Implies `static-curl`.
+//!
+//! OpenSSL is still built as a dependency for now, as described for `ssl`
+//! below. Starting with the next major version, disabling the default
+//! features and enabling this one instead of `ssl` will avoid OpenSSL
+//! entirely:
+//!
+//! ```toml
+//! [dependencies]
+//! isahc = { version = "1", default-features = false, features = ["http2", "rustls"] }
+//! ```
+//!
+//! Building a client returns an error if the libcurl in use turns out not to
+//! use rustls, or if it is configured with TLS options that rustls does not
+//! support. Disabled by default.
+//!
+//! ## `spill-to-disk`
+//!
+//! Enable
+//! [`Configurable::spill_to_disk`](config::Configurable::spill_to_disk) for
+//! buffering unread response bodies in temporary files. Disabled by default.
+//!
 //! ## `spnego`
 //!
 //! Enable support for [SPNEGO-based HTTP
@@ -181,25 +243,58 @@
 //! Kerberos](https://web.mit.edu/kerberos/) headers must be pre-installed at
 //! compile time.
 //!
+//! ## `ssl`
+//!
+//! Enable TLS support in libcurl using the native TLS library of the platform,
+//! which is OpenSSL on most Unix-like systems. Enabled by default.
+//!
+//! For compatibility, this support is currently built in even if this feature
+//! is disabled, though libcurl uses rustls instead if `rustls` is enabled.
+//! Starting with the next major version, it will only be built in with this
+//! feature, so enable it explicitly if you disable the default features and
+//! want to keep it.
+//!
 //! ## `static-curl`
 //!
 //! Use a bundled libcurl version and statically link to it. Enabled by default.
 //!
+//! ## `testing`
+//!
+//! Enable the [`testing`] module, which provides a minimal HTTP server that can
+//! be started from tests to send requests to. Disabled by default.
+//!
+//! ## `tls-psk`
+//!
+//! Enable authenticating TLS connections using pre-shared keys with
+//! [`ssl_pre_shared_key`](config::Configurable::ssl_pre_shared_key). This
+//! requires libcurl to use OpenSSL. Disabled by default.
+//!
 //! ## `text-decoding`
 //!
 //! Enable support for decoding text-based responses in various charsets into
 //! strings. Enabled by default.
 //!
+//! ## `websocket`
+//!
+//! Enable the [`websocket`] module for opening WebSocket connections with
+//! [`HttpClient::websocket`]. Disabled by default.
+//!
+//! ## `zstd`
+//!
+//! Enable decompressing responses compressed with Zstandard, even if libcurl
+//! was built without support for it. Disabled by default.
+//!
 //! ## Unstable APIs
 //!
 //! There are also some features that enable new incubating APIs that do not
 //! have stability guarantees:
 //!
-//! ### `unstable-interceptors`
+//! ### `unstable-agent`
 //!
-//! Enable the new interceptors API (replaces the old unstable middleware API).
-//! Unstable until the API is finalized. This an unstable feature whose
-//! interface may change between patch releases.
+//! Enable the [`agent`] API, which allows custom curl transfers to be executed
+//! on the same kind of background event loop that Isahc uses for HTTP
+//! requests. This an unstable feature whose interface may change between patch
+//! releases.
 //!
 //! # Logging and tracing
 //!
@@ -230,7 +325,12 @@
 )]
 
 // These lints suggest to use features not available in our MSRV.
-#![allow(clippy::manual_strip, clippy::match_like_matches_macro)]
+#![allow(
+    clippy::derivable_impls,
+    clippy::io_other_error,
+    clippy::manual_strip,
+    clippy::match_like_matches_macro
+)]
 
 use http::{Request, Response};
 use once_cell::sync::Lazy;
@@ -242,42 +342,118 @@ mod macros;
 #[cfg(feature = "cookies")]
 pub mod cookies;
 
-mod agent;
+#[cfg(feature = "http-cache")]
+pub mod cache;
+
+#[cfg(feature = "mail")]
+pub mod mail;
+
+#[cfg(feature = "unstable-agent")]
+pub mod agent;
+#[cfg(not(feature = "unstable-agent"))]
+#[allow(unreachable_pub, unused)]
+pub(crate) mod agent;
 mod body;
+mod capabilities;
 mod client;
+mod connection;
+mod curl_command;
+#[cfg(any(feature = "brotli", feature = "zstd"))]
+mod decompress;
 mod default_headers;
+mod digest;
+mod download;
+mod format;
 mod handler;
 mod headers;
+mod heartbeat;
+mod intermediary;
+mod link;
 mod metrics;
+mod multipart;
 mod parsing;
+mod pool;
+mod proxy_check;
+mod push;
+#[cfg(feature = "tls-psk")]
+mod psk;
+mod random;
+mod range;
+mod recv_buffer;
 mod redirect;
 mod request;
 mod response;
+mod share;
+mod sniff;
+
+#[cfg(feature = "spill-to-disk")]
+mod spill;
+mod split;
 mod task;
 mod text;
+mod tunnel;
+mod validate;
 
 pub mod auth;
 pub mod config;
+pub mod date;
 pub mod error;
+pub mod hsts;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
-#[cfg(feature = "unstable-interceptors")]
 pub mod interceptor;
-#[cfg(not(feature = "unstable-interceptors"))]
-#[allow(unreachable_pub, unused)]
-pub(crate) mod interceptor;
 
 pub use crate::{
-    body::{AsyncBody, Body},
-    client::{HttpClient, HttpClientBuilder, ResponseFuture},
+    body::{AsyncBody, Body, BodySink, BodyWriter, FormPart, MultipartBody},
+    capabilities::Capabilities,
+    client::{
+        DetachedHandle,
+        HttpClient,
+        HttpClientBuilder,
+        Paginate,
+        ResponseFuture,
+        SiblingClientBuilder,
+    },
+    connection::{ConnectAttempt, ConnectionReport},
+    download::{DownloadOutcome, DownloadedFile},
     error::Error,
-    metrics::Metrics,
-    request::RequestExt,
-    response::{AsyncReadResponseExt, ReadResponseExt, ResponseExt},
+    format::Format,
+    heartbeat::{Activity, Heartbeats},
+    intermediary::{ProxyStatus, Via},
+    link::Link,
+    metrics::{AgentStats, Metrics, PoolStats},
+    multipart::{BodyPart, Multipart},
+    push::{PushedResponse, PushedResponses},
+    range::{ContentRange, RangeOutcome},
+    redirect::RedirectHop,
+    request::{RequestBuilderExt, RequestExt},
+    response::{
+        AsyncReadResponseExt,
+        CopyFuture,
+        ErrorForStatusFuture,
+        ReadResponseExt,
+        ResponseExt,
+    },
+    share::{ShareHandle, ShareHandleBuilder},
+    split::Split,
+    tunnel::Tunnel,
 };
 
+#[cfg(feature = "text-decoding")]
+pub use crate::text::TextFuture;
+
 /// Re-export of the standard HTTP types.
 pub use http;
 
+/// Re-export of the media type used by
+/// [`ResponseExt::content_type`](crate::ResponseExt::content_type).
+pub use mime;
+
 /// A "prelude" for importing common Isahc types.
 ///
 /// # Example
@@ -292,6 +468,7 @@ pub mod prelude {
         AsyncReadResponseExt,
         HttpClient,
         ReadResponseExt,
+        RequestBuilderExt,
         RequestExt,
         ResponseExt,
     };
@@ -466,6 +643,96 @@ pub fn send_async<B: Into<AsyncBody>>(request: Request<B>) -> ResponseFuture<'st
     HttpClient::shared().send_async(request)
 }
 
+/// Set the client used to send requests with the free functions in this
+/// crate, such as [`get`] and [`send`], as well as with
+/// [`RequestExt::send`].
+///
+/// By default, a client with the default configuration is created when the
+/// first such request is sent. This allows small programs to configure
+/// proxies, timeouts and the like once on startup without having to pass a
+/// client around. See also [`configure_default_client`].
+///
+/// This must be called before the first request is sent with the default
+/// client. Otherwise, the default client cannot be changed anymore, and the
+/// given client is returned as an error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::{prelude::*, HttpClient};
+/// use std::time::Duration;
+///
+/// let client = HttpClient::builder()
+///     .proxy(Some("http://proxy.example.org:3128".parse()?))
+///     .timeout(Duration::from_secs(10))
+///     .build()?;
+///
+/// isahc::set_default_client(client).expect("default client already in use");
+///
+/// let response = isahc::get("https://example.org")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn set_default_client(client: HttpClient) -> Result<(), HttpClient> {
+    HttpClient::set_shared(client)
+}
+
+/// Configure the client used to send requests with the free functions in
+/// this crate, such as [`get`] and [`send`], as well as with
+/// [`RequestExt::send`].
+///
+/// Unlike [`set_default_client`], the given function is only run once the
+/// default client is needed for the first time, so nothing is created if it
+/// never is. The function is given a builder with the default configuration,
+/// and the client built from the builder it returns is used. If the client
+/// fails to build, sending the request panics.
+///
+/// Calling this again before the default client is created replaces the
+/// previous function. This must be called before the first request is sent
+/// with the default client. Otherwise, the default client cannot be changed
+/// anymore, and the given function is returned as an error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::prelude::*;
+/// use std::time::Duration;
+///
+/// isahc::configure_default_client(|builder| {
+///     builder
+///         .connect_timeout(Duration::from_secs(5))
+///         .timeout(Duration::from_secs(30))
+/// })
+/// .ok()
+/// .expect("default client already in use");
+///
+/// let response = isahc::get("https://example.org")?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+pub fn configure_default_client<F>(configure: F) -> Result<(), F>
+where
+    F: FnOnce(HttpClientBuilder) -> HttpClientBuilder + Send + 'static,
+{
+    HttpClient::configure_shared(configure)
+}
+
+/// Get a report of the features supported by the linked libcurl and its TLS
+/// backend.
+///
+/// This can be used to check at runtime whether a feature such as HTTP/2 is
+/// available before relying on it. See [`Capabilities`] for details.
+///
+/// # Examples
+///
+/// ```
+/// let capabilities = isahc::capabilities();
+///
+/// println!("HTTP/2 supported: {}", capabilities.http2());
+/// println!("TLS backend: {:?}", capabilities.ssl_version());
+/// ```
+pub fn capabilities() -> &'static Capabilities {
+    capabilities::get()
+}
+
 /// Gets a human-readable string with the version number of Isahc and its
 /// dependencies.
 ///
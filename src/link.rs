@@ -0,0 +1,243 @@
+//! Parsing of the `Link` header.
+
+use crate::{
+    parsing::{split_header_list, split_params, unquote},
+    redirect::resolve,
+};
+use http::Uri;
+
+/// A link from a response to another resource, as listed in the `Link`
+/// header.
+///
+/// Links are described in [RFC 8288](https://tools.ietf.org/html/rfc8288).
+/// Each link has a target, one or more relation types describing how the
+/// target relates to the response, such as `next` or `alternate`, and
+/// optionally other parameters such as a title or a media type. Use
+/// [`ResponseExt::links`](crate::ResponseExt::links) to get the links of a
+/// response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Link {
+    target: String,
+    uri: Option<Uri>,
+    params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Get the target of the link as written in the header, which may be a
+    /// relative reference.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Get the target of the link as an absolute URI, resolved against the
+    /// [effective URI](crate::ResponseExt::effective_uri) of the response.
+    ///
+    /// Returns `None` if the target is relative and the effective URI of the
+    /// response is not known, or if the target is not a valid URI.
+    pub fn uri(&self) -> Option<&Uri> {
+        self.uri.as_ref()
+    }
+
+    /// Get the relation types of the link, such as `next` or `stylesheet`.
+    ///
+    /// A link can have several relation types separated by spaces. Relation
+    /// types are compared case-insensitively, so registered types are
+    /// returned in lowercase. Extension types, which are URIs, are returned
+    /// as they are.
+    pub fn relations(&self) -> Vec<String> {
+        self.param("rel")
+            .unwrap_or("")
+            .split_ascii_whitespace()
+            .map(|rel| {
+                if rel.contains(':') {
+                    rel.to_owned()
+                } else {
+                    rel.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    }
+
+    /// Check if the link has the given relation type, compared
+    /// case-insensitively.
+    pub fn has_relation(&self, rel: &str) -> bool {
+        self.param("rel")
+            .unwrap_or("")
+            .split_ascii_whitespace()
+            .any(|value| value.eq_ignore_ascii_case(rel))
+    }
+
+    /// Get the anchor of the link, if any.
+    ///
+    /// The anchor overrides the context of the link, which is otherwise the
+    /// response itself. For example, a link with an anchor of `#comments`
+    /// describes a relation of that section of the response rather than of
+    /// the whole response.
+    pub fn anchor(&self) -> Option<&str> {
+        self.param("anchor")
+    }
+
+    /// Get the human-readable title of the link, if any.
+    pub fn title(&self) -> Option<&str> {
+        self.param("title")
+    }
+
+    /// Get the media type that the target of the link is expected to have,
+    /// as given by the `type` parameter, if any.
+    pub fn media_type(&self) -> Option<&str> {
+        self.param("type")
+    }
+
+    /// Get the value of a parameter of the link by name, compared
+    /// case-insensitively. Parameters without a value are returned as an
+    /// empty string.
+    ///
+    /// If a parameter occurs more than once, only the first occurrence is
+    /// returned, as described by the RFC.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get all parameters of the link in the order they appear in, as pairs
+    /// of names and values.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// Parse the links in a `Link` header value, resolving their targets against
+/// the given base URI, if any. Malformed links are skipped.
+pub(crate) fn parse_links(value: &str, base: Option<&Uri>) -> Vec<Link> {
+    split_header_list(value)
+        .into_iter()
+        .filter_map(|element| {
+            if !element.starts_with('<') {
+                return None;
+            }
+
+            let end = element.find('>')?;
+            let target = element[1..end].trim();
+            let mut parts = split_params(&element[end + 1..]).into_iter();
+
+            // Nothing but whitespace may come between the target and the
+            // first parameter.
+            if !parts.next()?.trim().is_empty() {
+                return None;
+            }
+
+            let params = parts
+                .filter_map(|param| {
+                    let mut param = param.splitn(2, '=');
+                    let key = param.next()?.trim();
+
+                    if key.is_empty() {
+                        return None;
+                    }
+
+                    let value = match param.next() {
+                        Some(value) => unquote(value.trim()),
+                        None => String::new(),
+                    };
+
+                    Some((key.to_owned(), value))
+                })
+                .collect();
+
+            let uri = match base {
+                Some(base) => resolve(base, target).ok(),
+                None => target.parse::<Uri>().ok(),
+            };
+
+            Some(Link {
+                target: target.to_owned(),
+                // Without a base URI, only absolute links can be resolved.
+                uri: uri.filter(|uri| uri.scheme().is_some()),
+                params,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_links_with_params() {
+        let value = concat!(
+            r#"<https://api.example.com/items?page=1>; rel="prev first", "#,
+            r#"<https://api.example.com/items?page=3,4>; title="a;b,c"; rel=next"#,
+        );
+        let links = parse_links(value, None);
+
+        assert_eq!(links.len(), 2);
+
+        assert_eq!(links[0].target(), "https://api.example.com/items?page=1");
+        assert_eq!(links[0].relations(), vec!["prev", "first"]);
+        assert!(links[0].has_relation("first"));
+        assert!(!links[0].has_relation("next"));
+
+        assert_eq!(links[1].target(), "https://api.example.com/items?page=3,4");
+        assert_eq!(links[1].title(), Some("a;b,c"));
+        assert_eq!(links[1].relations(), vec!["next"]);
+        assert_eq!(
+            links[1].uri(),
+            Some(&Uri::from_static("https://api.example.com/items?page=3,4"))
+        );
+    }
+
+    #[test]
+    fn relations_are_case_insensitive() {
+        let links = parse_links(r#"</next>; REL="Next http://example.org/Rel""#, None);
+
+        assert!(links[0].has_relation("next"));
+        assert_eq!(links[0].relations(), vec!["next", "http://example.org/Rel"]);
+    }
+
+    #[test]
+    fn only_first_occurrence_of_param_counts() {
+        let links = parse_links("</a>; rel=next; rel=prev; anchor=\"#x\"; hidden", None);
+
+        assert_eq!(links[0].relations(), vec!["next"]);
+        assert_eq!(links[0].anchor(), Some("#x"));
+        assert_eq!(links[0].param("hidden"), Some(""));
+        assert_eq!(links[0].params().count(), 4);
+    }
+
+    #[test]
+    fn relative_targets_are_resolved_against_base() {
+        let base = Uri::from_static("https://example.org/items/?page=2");
+        let links = parse_links("<?page=3>; rel=next, </other>; rel=related", Some(&base));
+
+        assert_eq!(
+            links[0].uri(),
+            Some(&Uri::from_static("https://example.org/items/?page=3"))
+        );
+        assert_eq!(
+            links[1].uri(),
+            Some(&Uri::from_static("https://example.org/other"))
+        );
+
+        let links = parse_links("</other>; rel=related", None);
+
+        assert_eq!(links[0].target(), "/other");
+        assert_eq!(links[0].uri(), None);
+    }
+
+    #[test]
+    fn malformed_links_are_skipped() {
+        assert!(parse_links("", None).is_empty());
+        assert!(parse_links("garbage", None).is_empty());
+        assert!(parse_links("<unterminated; rel=next", None).is_empty());
+
+        let links = parse_links("</a> junk; rel=next, </b>; rel=next", None);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target(), "/b");
+    }
+}
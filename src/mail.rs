@@ -0,0 +1,413 @@
+//! Sending email using SMTP.
+//!
+//! Messages are sent by a [`MailClient`], which executes transfers using the
+//! same kind of background agent as an [`HttpClient`](crate::HttpClient) and
+//! accepts the same SSL/TLS configuration types.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use isahc::{auth::Credentials, mail::{MailClient, Message}};
+//!
+//! let client = MailClient::builder("smtp://mail.example.org:587")
+//!     .credentials(Credentials::new("clark", "qwerty"))
+//!     .require_tls(true)
+//!     .build()?;
+//!
+//! let message = Message::new(
+//!     "clark@example.org",
+//!     "From: Clark <clark@example.org>\r\n\
+//!      To: Lois <lois@example.org>\r\n\
+//!      Subject: Lunch\r\n\
+//!      \r\n\
+//!      Are you free today?\r\n",
+//! )
+//! .to("lois@example.org");
+//!
+//! client.send(message)?;
+//! # Ok::<(), isahc::Error>(())
+//! ```
+//!
+//! # Availability
+//!
+//! This module is only available when the [`mail`](../index.html#mail) feature
+//! is enabled. Sending email also requires a libcurl with SMTP support. The
+//! bundled libcurl used by the `static-curl` feature is compiled without SMTP,
+//! so that feature must be disabled in order to link against a system libcurl
+//! instead.
+
+use crate::{
+    agent::Agent,
+    auth::Credentials,
+    config::{internal::SetOpt, ssl::Ciphers, CaCertificate, ClientCertificate, SslOption},
+    error::{Error, ErrorKind},
+};
+use curl::easy::{Easy2, Handler, ReadError};
+use futures_lite::future::block_on;
+use std::{
+    ffi::CString,
+    fmt,
+    io::{self, Read},
+    iter::FromIterator,
+};
+
+/// An email message to be sent.
+///
+/// The message content must be a complete message in [RFC
+/// 5322](https://tools.ietf.org/html/rfc5322) format, including its headers,
+/// as it is sent as-is. The sender and recipients given here are only used for
+/// the SMTP envelope, and are not added to the message headers.
+#[derive(Clone, Debug)]
+pub struct Message {
+    from: String,
+    recipients: Vec<String>,
+    content: Vec<u8>,
+}
+
+impl Message {
+    /// Create a new message from the given sender address and content.
+    pub fn new(from: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        Self {
+            from: from.into(),
+            recipients: Vec::new(),
+            content: content.into(),
+        }
+    }
+
+    /// Add a recipient address to send the message to.
+    pub fn to(mut self, recipient: impl Into<String>) -> Self {
+        self.recipients.push(recipient.into());
+        self
+    }
+}
+
+/// A builder for configuring and creating a [`MailClient`].
+#[derive(Debug)]
+pub struct MailClientBuilder {
+    url: String,
+    credentials: Option<Credentials>,
+    require_tls: bool,
+    ca_certificate: Option<CaCertificate>,
+    client_certificate: Option<ClientCertificate>,
+    ciphers: Option<Ciphers>,
+    ssl_options: Option<SslOption>,
+}
+
+impl MailClientBuilder {
+    /// Set the credentials to authenticate with the mail server.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Require the connection to the mail server to be encrypted.
+    ///
+    /// When enabled, sending fails if the server does not support upgrading an
+    /// `smtp://` connection using STARTTLS. When disabled, STARTTLS is still
+    /// used if the server supports it.
+    ///
+    /// This has no effect for `smtps://` servers, which always use SSL/TLS.
+    /// The default is disabled.
+    pub fn require_tls(mut self, require: bool) -> Self {
+        self.require_tls = require;
+        self
+    }
+
+    /// Set a custom SSL/TLS CA certificate bundle to use.
+    ///
+    /// See
+    /// [`Configurable::ssl_ca_certificate`](crate::config::Configurable::ssl_ca_certificate)
+    /// for details.
+    pub fn ssl_ca_certificate(mut self, certificate: CaCertificate) -> Self {
+        self.ca_certificate = Some(certificate);
+        self
+    }
+
+    /// Set a custom SSL/TLS client certificate to use for all connections.
+    ///
+    /// See
+    /// [`Configurable::ssl_client_certificate`](crate::config::Configurable::ssl_client_certificate)
+    /// for details.
+    pub fn ssl_client_certificate(mut self, certificate: ClientCertificate) -> Self {
+        self.client_certificate = Some(certificate);
+        self
+    }
+
+    /// Set a list of ciphers to use for SSL/TLS connections.
+    ///
+    /// See
+    /// [`Configurable::ssl_ciphers`](crate::config::Configurable::ssl_ciphers)
+    /// for details.
+    pub fn ssl_ciphers<I, T>(mut self, ciphers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.ciphers = Some(Ciphers::from_iter(ciphers.into_iter().map(T::into)));
+        self
+    }
+
+    /// Set various options that control SSL/TLS behavior.
+    ///
+    /// See
+    /// [`Configurable::ssl_options`](crate::config::Configurable::ssl_options)
+    /// for details.
+    pub fn ssl_options(mut self, options: SslOption) -> Self {
+        self.ssl_options = Some(options);
+        self
+    }
+
+    /// Build a [`MailClient`] using the configured options.
+    ///
+    /// If the client fails to initialize, an error will be returned.
+    pub fn build(self) -> Result<MailClient, Error> {
+        Ok(MailClient {
+            agent: Agent::spawn()?,
+            config: self,
+        })
+    }
+}
+
+/// A client for sending email messages to an SMTP server.
+///
+/// Like an [`HttpClient`](crate::HttpClient), a mail client owns a background
+/// agent thread and can reuse connections between messages, so it is best to
+/// create one client and reuse it.
+#[derive(Debug)]
+pub struct MailClient {
+    agent: Agent,
+    config: MailClientBuilder,
+}
+
+impl MailClient {
+    /// Create a new builder for a client that sends messages using the mail
+    /// server at the given URL.
+    ///
+    /// The URL should use the `smtp` scheme, or `smtps` for servers that
+    /// expect SSL/TLS from the start of the connection.
+    pub fn builder(url: impl Into<String>) -> MailClientBuilder {
+        MailClientBuilder {
+            url: url.into(),
+            credentials: None,
+            require_tls: false,
+            ca_certificate: None,
+            client_certificate: None,
+            ciphers: None,
+            ssl_options: None,
+        }
+    }
+
+    /// Send a message, blocking the current thread until the server has
+    /// accepted it.
+    pub fn send(&self, message: Message) -> Result<(), Error> {
+        block_on(self.send_async(message))
+    }
+
+    /// Send a message asynchronously.
+    ///
+    /// The returned future resolves once the server has accepted the message.
+    pub async fn send_async(&self, message: Message) -> Result<(), Error> {
+        let easy = self.create_easy_handle(message)?;
+
+        self.agent.submit2(easy).await.map(|_| ())
+    }
+
+    fn create_easy_handle(&self, message: Message) -> Result<Easy2<MailHandler>, Error> {
+        if message.recipients.is_empty() {
+            return Err(Error::with_context(
+                ErrorKind::InvalidRequest,
+                Some(String::from("message has no recipients")),
+                InvalidAddress,
+            ));
+        }
+
+        let mut recipients = Recipients::default();
+
+        for recipient in &message.recipients {
+            recipients.append(recipient)?;
+        }
+
+        let from = CString::new(message.from).map_err(|_| invalid_address())?;
+        let content_len = message.content.len() as u64;
+
+        let mut easy = Easy2::new(MailHandler {
+            content: io::Cursor::new(message.content),
+            recipients,
+        });
+
+        easy.signal(false)?;
+        easy.url(&self.config.url)?;
+        easy.upload(true)?;
+        easy.in_filesize(content_len)?;
+        set_envelope(&easy, &from)?;
+        set_use_ssl(&easy, self.config.require_tls)?;
+
+        if let Some(credentials) = self.config.credentials.as_ref() {
+            credentials.set_opt(&mut easy)?;
+        }
+
+        if let Some(certificate) = self.config.ca_certificate.as_ref() {
+            certificate.set_opt(&mut easy)?;
+        }
+
+        if let Some(certificate) = self.config.client_certificate.as_ref() {
+            certificate.set_opt(&mut easy)?;
+        }
+
+        if let Some(ciphers) = self.config.ciphers.as_ref() {
+            ciphers.set_opt(&mut easy)?;
+        }
+
+        if let Some(options) = self.config.ssl_options.as_ref() {
+            options.set_opt(&mut easy)?;
+        }
+
+        Ok(easy)
+    }
+}
+
+/// Curl handler for sending the content of a message.
+struct MailHandler {
+    content: io::Cursor<Vec<u8>>,
+
+    /// Recipient list given to curl, which must live as long as the handle.
+    recipients: Recipients,
+}
+
+impl Handler for MailHandler {
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        Ok(self.content.read(data).unwrap_or(0))
+    }
+}
+
+impl fmt::Debug for MailHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MailHandler").finish()
+    }
+}
+
+/// Set the sender and recipients of the SMTP envelope.
+#[allow(unsafe_code)]
+fn set_envelope(easy: &Easy2<MailHandler>, from: &CString) -> Result<(), curl::Error> {
+    let recipients = easy.get_ref().recipients.0;
+
+    // The safe API does not expose the SMTP envelope options. Curl makes its
+    // own copy of the sender address, but not of the recipient list, which is
+    // why the handler owns it.
+    unsafe {
+        match curl_sys::curl_easy_setopt(easy.raw(), curl_sys::CURLOPT_MAIL_FROM, from.as_ptr()) {
+            curl_sys::CURLE_OK => {}
+            code => return Err(curl::Error::new(code)),
+        }
+
+        match curl_sys::curl_easy_setopt(easy.raw(), curl_sys::CURLOPT_MAIL_RCPT, recipients) {
+            curl_sys::CURLE_OK => Ok(()),
+            code => Err(curl::Error::new(code)),
+        }
+    }
+}
+
+/// Set whether SSL/TLS is required when connecting to the mail server.
+#[allow(unsafe_code)]
+fn set_use_ssl(easy: &Easy2<MailHandler>, require: bool) -> Result<(), curl::Error> {
+    let value = if require {
+        curl_sys::CURLUSESSL_ALL
+    } else {
+        curl_sys::CURLUSESSL_TRY
+    };
+
+    // Use unsafe API, because the safe API doesn't expose this option.
+    unsafe {
+        match curl_sys::curl_easy_setopt(easy.raw(), curl_sys::CURLOPT_USE_SSL, value) {
+            curl_sys::CURLE_OK => Ok(()),
+            code => Err(curl::Error::new(code)),
+        }
+    }
+}
+
+/// An owned curl string list of recipient addresses.
+struct Recipients(*mut curl_sys::curl_slist);
+
+impl Recipients {
+    #[allow(unsafe_code)]
+    fn append(&mut self, address: &str) -> Result<(), Error> {
+        let address = CString::new(address).map_err(|_| invalid_address())?;
+
+        unsafe {
+            let list = curl_sys::curl_slist_append(self.0, address.as_ptr());
+
+            if list.is_null() {
+                return Err(Error::new(
+                    ErrorKind::Unknown,
+                    io::Error::from(io::ErrorKind::Other),
+                ));
+            }
+
+            self.0 = list;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Recipients {
+    fn default() -> Self {
+        Recipients(std::ptr::null_mut())
+    }
+}
+
+// The list is never shared, and is only read by curl during a transfer.
+#[allow(unsafe_code)]
+unsafe impl Send for Recipients {}
+
+impl Drop for Recipients {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            curl_sys::curl_slist_free_all(self.0);
+        }
+    }
+}
+
+/// Error returned for an address that cannot be given to curl.
+#[derive(Debug)]
+struct InvalidAddress;
+
+impl fmt::Display for InvalidAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid email address")
+    }
+}
+
+impl std::error::Error for InvalidAddress {}
+
+fn invalid_address() -> Error {
+    Error::new(ErrorKind::InvalidRequest, InvalidAddress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static_assertions::assert_impl_all!(MailClient: Send, Sync);
+    static_assertions::assert_impl_all!(Message: Send, Sync);
+
+    #[test]
+    fn message_without_recipients_is_rejected() {
+        let client = MailClient::builder("smtp://localhost").build().unwrap();
+        let error = client.send(Message::new("a@example.org", "")).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::InvalidRequest);
+    }
+
+    #[test]
+    fn address_with_nul_byte_is_rejected() {
+        let client = MailClient::builder("smtp://localhost").build().unwrap();
+        let message = Message::new("a@example.org", "").to("b\0@example.org");
+
+        assert_eq!(
+            client.send(message).unwrap_err().kind(),
+            ErrorKind::InvalidRequest
+        );
+    }
+}
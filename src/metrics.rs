@@ -1,7 +1,7 @@
-//! Request and response metrics tracking.
+//! Request, response and agent metrics tracking.
 
 use crossbeam_utils::atomic::AtomicCell;
-use std::{fmt, sync::Arc, time::Duration};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
 
 /// An object that holds status updates and progress statistics on a particular
 /// request. A [`Metrics`] can be shared between threads, which allows an agent
@@ -45,15 +45,38 @@ pub(crate) struct Inner {
     pub(crate) starttransfer_time: AtomicCell<f64>,
     pub(crate) total_time: AtomicCell<f64>,
     pub(crate) redirect_time: AtomicCell<f64>,
+
+    // Overhead of the response body pipeline, counted by isahc itself rather
+    // than by curl.
+    pub(crate) decompressed_bytes: AtomicCell<u64>,
+    pub(crate) buffer_copies: AtomicCell<u64>,
+    pub(crate) buffered_bytes: AtomicCell<u64>,
+    pub(crate) decode_replacements: AtomicCell<u64>,
+
+    /// Route template of the request, if one was given.
+    pub(crate) route: Option<String>,
 }
 
 impl Metrics {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(route: Option<String>) -> Self {
         Self {
-            inner: Arc::default(),
+            inner: Arc::new(Inner {
+                route,
+                ..Inner::default()
+            }),
         }
     }
 
+    /// Get the route template of the request these metrics belong to, if one
+    /// was set using [`RequestExt::route`](crate::RequestExt::route).
+    ///
+    /// Unlike the request URI, the route is the same for all requests to the
+    /// same kind of resource, which makes it suitable as a label when
+    /// aggregating metrics.
+    pub fn route(&self) -> Option<&str> {
+        self.inner.route.as_deref()
+    }
+
     /// Number of bytes uploaded / estimated total.
     pub fn upload_progress(&self) -> (u64, u64) {
         (
@@ -149,11 +172,49 @@ impl Metrics {
     pub fn redirect_time(&self) -> Duration {
         Duration::from_secs_f64(self.inner.redirect_time.load())
     }
+
+    /// Number of bytes the response body was decompressed into so far, or
+    /// zero if the response was not compressed.
+    ///
+    /// Compare with [`download_progress`](Metrics::download_progress), which
+    /// counts the compressed bytes received, to see how much work
+    /// decompression does.
+    pub fn decompressed_bytes(&self) -> u64 {
+        self.inner.decompressed_bytes.load()
+    }
+
+    /// Number of times a chunk of the response body was copied into the
+    /// buffer it is read from, such as the in-memory pipe between the agent
+    /// thread and the response body or a file spilled to disk.
+    ///
+    /// Each chunk is normally copied once, so many copies of small chunks
+    /// point to a slow reader of the response body.
+    pub fn buffer_copies(&self) -> u64 {
+        self.inner.buffer_copies.load()
+    }
+
+    /// Total number of bytes copied into response body buffers, as counted by
+    /// [`buffer_copies`](Metrics::buffer_copies).
+    pub fn buffered_bytes(&self) -> u64 {
+        self.inner.buffered_bytes.load()
+    }
+
+    /// Number of invalid byte sequences that were replaced with U+FFFD while
+    /// decoding the response body as text, using
+    /// [`ReadResponseExt::text`](crate::ReadResponseExt::text) or one of its
+    /// siblings.
+    ///
+    /// A non-zero value usually means that the response declares the wrong
+    /// character encoding.
+    pub fn decode_replacements(&self) -> u64 {
+        self.inner.decode_replacements.load()
+    }
 }
 
 impl fmt::Debug for Metrics {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Metrics")
+            .field("route", &self.route())
             .field("upload_progress", &self.upload_progress())
             .field("upload_speed", &self.upload_speed())
             .field("download_progress", &self.download_progress())
@@ -165,6 +226,242 @@ impl fmt::Debug for Metrics {
             .field("transfer_time", &self.transfer_time())
             .field("total_time", &self.total_time())
             .field("redirect_time", &self.redirect_time())
+            .field("decompressed_bytes", &self.decompressed_bytes())
+            .field("buffer_copies", &self.buffer_copies())
+            .field("buffered_bytes", &self.buffered_bytes())
+            .field("decode_replacements", &self.decode_replacements())
             .finish()
     }
 }
+
+/// A snapshot of statistics about the agent thread of an [`HttpClient`][crate::HttpClient].
+///
+/// All requests sent by a client are driven by a single background thread, so
+/// if that thread becomes saturated, every request made by the client slows
+/// down. These statistics can be used to detect when that happens.
+///
+/// The values are updated by the agent thread once per iteration of its event
+/// loop, and are not guaranteed to be consistent with one another.
+#[derive(Clone, Debug)]
+pub struct AgentStats {
+    pub(crate) iterations: u64,
+    pub(crate) loop_latency: Duration,
+    pub(crate) max_loop_latency: Duration,
+    pub(crate) poll_wait: Duration,
+    pub(crate) queue_depth: usize,
+    pub(crate) queued_requests: usize,
+    pub(crate) active_transfers: usize,
+    pub(crate) forced_timeouts: u64,
+    pub(crate) reaped_transfers: u64,
+    pub(crate) canceled_transfers: u64,
+    pub(crate) max_cancel_latency: Duration,
+}
+
+impl AgentStats {
+    /// Number of event loop iterations completed so far.
+    pub fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    /// Time spent doing work during the most recent event loop iteration,
+    /// excluding any time spent waiting for activity.
+    ///
+    /// While the agent is doing work it cannot respond to socket activity or
+    /// new requests, so high values indicate that the agent is saturated.
+    pub fn loop_latency(&self) -> Duration {
+        self.loop_latency
+    }
+
+    /// The highest [`loop_latency`](AgentStats::loop_latency) seen so far.
+    pub fn max_loop_latency(&self) -> Duration {
+        self.max_loop_latency
+    }
+
+    /// Time spent waiting for socket activity or a wakeup during the most
+    /// recent event loop iteration.
+    pub fn poll_wait(&self) -> Duration {
+        self.poll_wait
+    }
+
+    /// Number of messages, such as new requests, waiting to be received by
+    /// the agent.
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// Number of requests waiting to be started because the limit set with
+    /// [`HttpClientBuilder::max_concurrent_requests`](crate::HttpClientBuilder::max_concurrent_requests)
+    /// has been reached.
+    ///
+    /// Unlike the other values, this is updated as soon as requests enter or
+    /// leave the queue, which makes it suitable for applying backpressure.
+    pub fn queued_requests(&self) -> usize {
+        self.queued_requests
+    }
+
+    /// Number of transfers currently being executed by the agent.
+    pub fn active_transfers(&self) -> usize {
+        self.active_transfers
+    }
+
+    /// Number of requests that the agent aborted itself because their
+    /// [timeout](crate::config::Configurable::timeout) elapsed without curl
+    /// reporting it.
+    ///
+    /// Curl normally enforces timeouts on its own, so this should always be
+    /// zero. A non-zero value points to a bug in curl or in the agent that
+    /// would otherwise have left those requests hanging.
+    pub fn forced_timeouts(&self) -> u64 {
+        self.forced_timeouts
+    }
+
+    /// Number of transfers that were aborted because their response body was
+    /// dropped before it was read to the end.
+    ///
+    /// Dropping a response body is the usual way to stop receiving a response,
+    /// so this is expected to grow in services that do so. It shows how often
+    /// transfers were cleaned up that would otherwise have kept running in
+    /// the background.
+    pub fn reaped_transfers(&self) -> u64 {
+        self.reaped_transfers
+    }
+
+    /// Number of requests that were aborted because their response future was
+    /// dropped before the response arrived.
+    ///
+    /// Only requests using [`CancelPolicy::Abort`](crate::config::CancelPolicy::Abort)
+    /// are aborted right away, so others are not counted.
+    pub fn canceled_transfers(&self) -> u64 {
+        self.canceled_transfers
+    }
+
+    /// The longest time seen so far between dropping the response future of
+    /// a request and the agent aborting the request.
+    ///
+    /// Requests are aborted no matter whether they are resolving the host
+    /// name, connecting, or waiting for the response, so this should stay
+    /// small. High values mean that the agent is too busy to react quickly.
+    pub fn max_cancel_latency(&self) -> Duration {
+        self.max_cancel_latency
+    }
+}
+
+/// A snapshot of the connection pool of an [`HttpClient`][crate::HttpClient],
+/// as returned by [`HttpClient::pool_stats`][crate::HttpClient::pool_stats].
+///
+/// Connections are kept open after a response is received so that they can
+/// be reused by later requests to the same host. A connection is idle while
+/// no transfer is using it. Connections to a proxy are counted under the host
+/// of the request that last used them.
+#[derive(Clone, Debug, Default)]
+pub struct PoolStats {
+    pub(crate) open_connections: usize,
+    pub(crate) idle_connections: HashMap<String, usize>,
+    pub(crate) active_transfers: usize,
+}
+
+impl PoolStats {
+    /// Number of connections currently open, whether in use or idle.
+    pub fn open_connections(&self) -> usize {
+        self.open_connections
+    }
+
+    /// Number of open connections that are not in use by any transfer.
+    pub fn idle_connections(&self) -> usize {
+        self.idle_connections.values().sum()
+    }
+
+    /// Number of idle connections to each host, keyed by host and port, such
+    /// as `example.org:443`. Hosts without idle connections are left out.
+    pub fn idle_connections_by_host(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.idle_connections
+            .iter()
+            .map(|(host, count)| (host.as_str(), *count))
+    }
+
+    /// Number of transfers currently being executed, including those still
+    /// waiting for a connection.
+    pub fn active_transfers(&self) -> usize {
+        self.active_transfers
+    }
+}
+
+/// Statistics shared between an agent thread and its handles.
+#[derive(Debug, Default)]
+pub(crate) struct AgentStatsInner {
+    iterations: AtomicCell<u64>,
+    loop_latency: AtomicCell<Duration>,
+    max_loop_latency: AtomicCell<Duration>,
+    poll_wait: AtomicCell<Duration>,
+    queued_requests: AtomicCell<usize>,
+    active_transfers: AtomicCell<usize>,
+    forced_timeouts: AtomicCell<u64>,
+    reaped_transfers: AtomicCell<u64>,
+    canceled_transfers: AtomicCell<u64>,
+    max_cancel_latency: AtomicCell<Duration>,
+}
+
+impl AgentStatsInner {
+    /// Record the results of a single event loop iteration.
+    pub(crate) fn record_iteration(
+        &self,
+        loop_latency: Duration,
+        poll_wait: Duration,
+        active_transfers: usize,
+    ) {
+        self.iterations.fetch_add(1);
+        self.loop_latency.store(loop_latency);
+        self.poll_wait.store(poll_wait);
+        self.active_transfers.store(active_transfers);
+
+        // Only the agent thread writes to these, so there is no race here.
+        if loop_latency > self.max_loop_latency.load() {
+            self.max_loop_latency.store(loop_latency);
+        }
+    }
+
+    /// Record the number of requests waiting for the concurrent request limit.
+    pub(crate) fn set_queued_requests(&self, count: usize) {
+        self.queued_requests.store(count);
+    }
+
+    /// Record that a request was aborted by the agent after its timeout
+    /// elapsed.
+    pub(crate) fn record_forced_timeout(&self) {
+        self.forced_timeouts.fetch_add(1);
+    }
+
+    /// Record that a transfer was aborted because its response body was
+    /// dropped.
+    pub(crate) fn record_reaped_transfer(&self) {
+        self.reaped_transfers.fetch_add(1);
+    }
+
+    /// Record that a request was aborted the given amount of time after its
+    /// response future was dropped.
+    pub(crate) fn record_canceled_transfer(&self, latency: Duration) {
+        self.canceled_transfers.fetch_add(1);
+
+        // Only the agent thread writes to this, so there is no race here.
+        if latency > self.max_cancel_latency.load() {
+            self.max_cancel_latency.store(latency);
+        }
+    }
+
+    /// Take a snapshot of the current values.
+    pub(crate) fn snapshot(&self, queue_depth: usize) -> AgentStats {
+        AgentStats {
+            iterations: self.iterations.load(),
+            loop_latency: self.loop_latency.load(),
+            max_loop_latency: self.max_loop_latency.load(),
+            poll_wait: self.poll_wait.load(),
+            queue_depth,
+            queued_requests: self.queued_requests.load(),
+            active_transfers: self.active_transfers.load(),
+            forced_timeouts: self.forced_timeouts.load(),
+            reaped_transfers: self.reaped_transfers.load(),
+            canceled_transfers: self.canceled_transfers.load(),
+            max_cancel_latency: self.max_cancel_latency.load(),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Parsing of multipart response bodies, such as `multipart/byteranges` and
+//! `multipart/mixed`.
+
+use crate::parsing::parse_header;
+use http::{header::CONTENT_TYPE, HeaderMap, Response, StatusCode, Version};
+use std::{
+    fmt,
+    io::{self, Read},
+};
+
+/// Size of the chunks read from the body at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// Maximum size of the header section of a single part.
+const MAX_HEADERS_SIZE: usize = 64 * 1024;
+
+/// A reader for the parts of a multipart response body.
+///
+/// This is created by
+/// [`ReadResponseExt::multipart`](crate::ReadResponseExt::multipart). The
+/// body is parsed incrementally as described in [RFC
+/// 2046](https://tools.ietf.org/html/rfc2046#section-5.1.1), so parts are
+/// available as soon as they start arriving and are never buffered in memory
+/// as a whole.
+///
+/// Each part is returned by [`next_part`](Multipart::next_part) as a response
+/// of its own, with the headers of the part and the status code and version
+/// of the enclosing response. The body of a part can be read using any of the
+/// methods of [`ReadResponseExt`](crate::ReadResponseExt). Any part of the
+/// body that has not been read when the next part is requested is skipped.
+pub struct Multipart<R> {
+    reader: R,
+    boundary: String,
+
+    /// The byte sequence separating parts: a line break, two dashes and the
+    /// boundary.
+    delimiter: Vec<u8>,
+
+    /// Data read from the body but not consumed yet.
+    buf: Vec<u8>,
+
+    state: State,
+    status: StatusCode,
+    version: Version,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    /// Reading the preamble before the first delimiter.
+    Preamble,
+
+    /// Reading the body of a part.
+    Body,
+
+    /// A delimiter has just been read.
+    Boundary,
+
+    /// The closing delimiter has been read.
+    End,
+}
+
+impl<R: Read> Multipart<R> {
+    pub(crate) fn new(reader: R, boundary: String, status: StatusCode, version: Version) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+
+        Self {
+            reader,
+            boundary,
+            delimiter,
+
+            // The first delimiter may appear at the very start of the body,
+            // without the line break that normally precedes it.
+            buf: b"\r\n".to_vec(),
+
+            state: State::Preamble,
+            status,
+            version,
+        }
+    }
+
+    /// Get the boundary separating the parts, as given in the `Content-Type`
+    /// header of the response.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Get the next part of the body, or `None` if the closing boundary has
+    /// been reached.
+    ///
+    /// An error of kind [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) is
+    /// returned if the body ends before the closing boundary, and an error of
+    /// kind [`InvalidData`](io::ErrorKind::InvalidData) if a part is
+    /// malformed.
+    pub fn next_part(&mut self) -> io::Result<Option<Response<BodyPart<'_, R>>>> {
+        // Skip the preamble or whatever is left of the previous part.
+        if let State::Preamble | State::Body = self.state {
+            let mut rest = BodyPart {
+                multipart: self,
+            };
+
+            io::copy(&mut rest, &mut io::sink())?;
+        }
+
+        if self.state == State::End {
+            return Ok(None);
+        }
+
+        // The delimiter is followed by two dashes if it is the closing one,
+        // and optional whitespace up to the end of the line otherwise.
+        while self.buf.len() < 2 {
+            if self.fill()? == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+
+        if self.buf.starts_with(b"--") {
+            self.state = State::End;
+            self.buf = Vec::new();
+            return Ok(None);
+        }
+
+        let line = self.read_line(MAX_HEADERS_SIZE)?;
+
+        if !line.iter().all(|&byte| byte == b' ' || byte == b'\t') {
+            return Err(invalid_data("malformed multipart boundary"));
+        }
+
+        let mut headers = HeaderMap::new();
+        let mut headers_size = 0;
+
+        loop {
+            let line = self.read_line(MAX_HEADERS_SIZE - headers_size)?;
+            headers_size += line.len() + 2;
+
+            if line.is_empty() {
+                break;
+            }
+
+            let (name, value) =
+                parse_header(&line).ok_or_else(|| invalid_data("malformed multipart header"))?;
+
+            headers.append(name, value);
+        }
+
+        self.state = State::Body;
+
+        let status = self.status;
+        let version = self.version;
+        let mut part = Response::new(BodyPart {
+            multipart: self,
+        });
+
+        *part.status_mut() = status;
+        *part.version_mut() = version;
+        *part.headers_mut() = headers;
+
+        Ok(Some(part))
+    }
+
+    /// Read a line without the line break, failing if it is longer than the
+    /// given limit.
+    fn read_line(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let mut searched = 0;
+
+        loop {
+            if let Some(i) = self.buf[searched..].iter().position(|&byte| byte == b'\n') {
+                let mut line = self.buf.drain(..searched + i + 1).collect::<Vec<_>>();
+                line.pop();
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+
+                return Ok(line);
+            }
+
+            searched = self.buf.len();
+
+            if searched > limit {
+                return Err(invalid_data("multipart headers are too large"));
+            }
+
+            if self.fill()? == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+
+    /// Read more data from the body into the buffer, returning the number of
+    /// bytes read.
+    fn fill(&mut self) -> io::Result<usize> {
+        let filled = self.buf.len();
+        self.buf.resize(filled + READ_SIZE, 0);
+
+        loop {
+            match self.reader.read(&mut self.buf[filled..]) {
+                Ok(n) => {
+                    self.buf.truncate(filled + n);
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buf.truncate(filled);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Move up to `len` bytes from the start of the buffer into `out`.
+    fn consume(&mut self, out: &mut [u8], len: usize) -> usize {
+        let len = len.min(out.len());
+        out[..len].copy_from_slice(&self.buf[..len]);
+        self.buf.drain(..len);
+        len
+    }
+}
+
+impl<R> fmt::Debug for Multipart<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("boundary", &self.boundary)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+/// The body of a single part of a multipart response.
+///
+/// This is returned by [`Multipart::next_part`] and reads the part up to the
+/// boundary that follows it.
+pub struct BodyPart<'a, R> {
+    multipart: &'a mut Multipart<R>,
+}
+
+impl<R: Read> Read for BodyPart<'_, R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let multipart = &mut *self.multipart;
+
+        loop {
+            match multipart.state {
+                State::Preamble | State::Body => {}
+                _ => return Ok(0),
+            }
+
+            let delimiter_len = multipart.delimiter.len();
+
+            if let Some(i) = multipart
+                .buf
+                .windows(delimiter_len)
+                .position(|window| window == &multipart.delimiter[..])
+            {
+                if i > 0 {
+                    return Ok(multipart.consume(out, i));
+                }
+
+                multipart.buf.drain(..delimiter_len);
+                multipart.state = State::Boundary;
+
+                return Ok(0);
+            }
+
+            // The end of the buffer might be the start of a delimiter, so it
+            // must not be returned until more data has arrived.
+            let safe = multipart.buf.len().saturating_sub(delimiter_len - 1);
+
+            if safe > 0 {
+                return Ok(multipart.consume(out, safe));
+            }
+
+            if multipart.fill()? == 0 {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+}
+
+impl<R> fmt::Debug for BodyPart<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyPart").finish()
+    }
+}
+
+/// Get the boundary of a multipart message from its headers, if it is one.
+pub(crate) fn boundary(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers
+        .get(CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .parse::<mime::Mime>()
+        .ok()?;
+
+    if content_type.type_() != mime::MULTIPART {
+        return None;
+    }
+
+    Some(content_type.get_param(mime::BOUNDARY)?.as_str().to_owned())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "multipart body ended before the closing boundary",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader returning a single byte at a time, so that delimiters are
+    /// split across reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(1);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    fn parts<R: Read>(multipart: &mut Multipart<R>) -> io::Result<Vec<(HeaderMap, Vec<u8>)>> {
+        let mut parts = Vec::new();
+
+        while let Some(mut part) = multipart.next_part()? {
+            let mut body = Vec::new();
+            part.body_mut().read_to_end(&mut body)?;
+            parts.push((part.headers().clone(), body));
+        }
+
+        Ok(parts)
+    }
+
+    const BYTERANGES: &[u8] = b"\
+        --THIS_STRING_SEPARATES\r\n\
+        Content-Type: application/pdf\r\n\
+        Content-Range: bytes 500-999/8000\r\n\
+        \r\n\
+        ...the first range...\r\n\
+        --THIS_STRING_SEPARATES  \r\n\
+        Content-Type: application/pdf\r\n\
+        Content-Range: bytes 7000-7999/8000\r\n\
+        \r\n\
+        ...the second range\r\n--THIS_STRING_SEPARATES--\r\n\
+        epilogue";
+
+    fn check_byteranges<R: Read>(reader: R) {
+        let mut multipart = Multipart::new(
+            reader,
+            String::from("THIS_STRING_SEPARATES"),
+            StatusCode::PARTIAL_CONTENT,
+            Version::HTTP_11,
+        );
+
+        let parts = parts(&mut multipart).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].0["content-range"], "bytes 500-999/8000");
+        assert_eq!(parts[0].1, b"...the first range...");
+        assert_eq!(parts[1].0["content-range"], "bytes 7000-7999/8000");
+        assert_eq!(parts[1].1, b"...the second range");
+    }
+
+    #[test]
+    fn parse_byteranges() {
+        check_byteranges(BYTERANGES);
+    }
+
+    #[test]
+    fn parse_byteranges_read_one_byte_at_a_time() {
+        check_byteranges(Trickle(BYTERANGES));
+    }
+
+    #[test]
+    fn unread_parts_and_preamble_are_skipped() {
+        let body: &[u8] = b"preamble\r\n--b\r\n\r\nfirst\r\n--b\r\nx-id: 2\r\n\r\nsecond\r\n--b--";
+        let mut multipart = Multipart::new(body, "b".into(), StatusCode::OK, Version::HTTP_2);
+
+        assert!(multipart.next_part().unwrap().unwrap().headers().is_empty());
+
+        let part = multipart.next_part().unwrap().unwrap();
+        assert_eq!(part.status(), StatusCode::OK);
+        assert_eq!(part.version(), Version::HTTP_2);
+        assert_eq!(part.headers()["x-id"], "2");
+
+        assert!(multipart.next_part().unwrap().is_none());
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_closing_boundary_is_an_error() {
+        let body: &[u8] = b"--b\r\n\r\ntruncated";
+        let mut multipart = Multipart::new(body, "b".into(), StatusCode::OK, Version::HTTP_11);
+
+        assert_eq!(
+            parts(&mut multipart).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn boundary_from_content_type() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(boundary(&headers), None);
+
+        headers.insert(CONTENT_TYPE, "text/plain; boundary=x".parse().unwrap());
+        assert_eq!(boundary(&headers), None);
+
+        headers.insert(
+            CONTENT_TYPE,
+            r#"multipart/mixed; boundary="batch_36522ad7""#.parse().unwrap(),
+        );
+        assert_eq!(boundary(&headers).as_deref(), Some("batch_36522ad7"));
+    }
+}
@@ -92,6 +92,87 @@ pub(crate) fn header_to_curl_string(
     string
 }
 
+/// Split a header value containing a comma-separated list into its elements,
+/// as described in [RFC 7230, section
+/// 7](https://tools.ietf.org/html/rfc7230#section-7).
+///
+/// Commas inside quoted strings or inside angle brackets, as used by `Link`,
+/// do not separate elements. Empty elements are skipped.
+pub(crate) fn split_header_list(value: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_brackets => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_brackets = true,
+            '>' if !in_quotes => in_brackets = false,
+            ',' if !in_quotes && !in_brackets => {
+                elements.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    elements.push(&value[start..]);
+    elements.retain(|element| !element.trim().is_empty());
+
+    elements.into_iter().map(str::trim).collect()
+}
+
+/// Split a list element into the item and its parameters, which are
+/// separated by semicolons outside of quoted strings.
+pub(crate) fn split_params(element: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in element.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                parts.push(&element[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&element[start..]);
+    parts
+}
+
+/// Remove the quotes and escapes from a quoted string, or return the value as
+/// is if it is not quoted.
+pub(crate) fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_owned();
+    }
+
+    let mut unquoted = String::with_capacity(value.len() - 2);
+    let mut escaped = false;
+
+    for c in value[1..value.len() - 1].chars() {
+        if escaped || c != '\\' {
+            unquoted.push(c);
+            escaped = false;
+        } else {
+            escaped = true;
+        }
+    }
+
+    unquoted
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,6 +251,25 @@ mod tests {
         assert_eq!(parse_header(b"foo : bar\r"), None);
     }
 
+    #[test]
+    fn split_header_list_respects_quotes_and_brackets() {
+        assert_eq!(split_header_list("gzip, br"), vec!["gzip", "br"]);
+        assert_eq!(split_header_list(" a ,, b ,"), vec!["a", "b"]);
+        assert_eq!(
+            split_header_list(r#"no-cache="set-cookie, x", max-age=0"#),
+            vec![r#"no-cache="set-cookie, x""#, "max-age=0"]
+        );
+        assert_eq!(
+            split_header_list(r#"</a?x=1,2>; rel="next", </b>; rel="last""#),
+            vec![r#"</a?x=1,2>; rel="next""#, r#"</b>; rel="last""#]
+        );
+        assert_eq!(split_header_list(r#""a \" , b", c"#), vec![
+            r#""a \" , b""#,
+            "c"
+        ]);
+        assert!(split_header_list("").is_empty());
+    }
+
     #[test]
     fn normal_header_to_curl_string() {
         let name = "User-Agent".parse().unwrap();
@@ -0,0 +1,231 @@
+//! Tracking of the connections kept open by an agent.
+//!
+//! Curl does not report when it opens or reuses connections, so the agent
+//! keeps track of them on its own. Once a transfer completes, the connection
+//! it used is looked up, and if curl keeps it open, it is remembered until
+//! curl closes its socket. Curl reports that to a close socket callback,
+//! which is installed on every transfer and inherited by the connections the
+//! transfer opens. A connection is idle while no active transfer uses it.
+//!
+//! Requests with a connection pool partition key keep their connections in a
+//! share handle for each key instead, which the agent hands out to them.
+
+#![allow(unsafe_code)]
+
+use crate::{error::Error, metrics::PoolStats, share::ShareHandle};
+use curl_sys::{curl_socket_t, CURL};
+use http::Uri;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+    time::Instant,
+};
+
+/// The maximum number of connection pool partitions kept by an agent. Once
+/// there are more, the least recently used partition is dropped.
+const MAX_PARTITIONS: usize = 256;
+
+/// The `CURLINFO_ACTIVESOCKET` option, which is missing from curl-sys.
+const CURLINFO_ACTIVESOCKET: curl_sys::CURLINFO = 0x500000 + 44;
+
+/// The connections known to be kept open by curl.
+#[derive(Debug, Default)]
+pub(crate) struct Connections {
+    /// Host of each connection, by socket.
+    hosts: HashMap<curl_socket_t, String>,
+}
+
+impl Connections {
+    /// Get told when the connections opened by the given transfer are
+    /// closed.
+    ///
+    /// Curl holds a pointer to this struct for as long as those connections
+    /// are open, so it must be kept in place until the multi handle is
+    /// dropped.
+    pub(crate) fn watch(&mut self, handle: *mut CURL) -> Result<(), curl::Error> {
+        let callback: extern "C" fn(*mut c_void, curl_socket_t) -> c_int = close_callback;
+        let data = self as *mut Self as *mut c_void;
+
+        unsafe {
+            for &(option, value) in &[
+                (
+                    curl_sys::CURLOPT_CLOSESOCKETFUNCTION,
+                    callback as *mut c_void,
+                ),
+                (curl_sys::CURLOPT_CLOSESOCKETDATA, data),
+            ] {
+                match curl_sys::curl_easy_setopt(handle, option, value) {
+                    curl_sys::CURLE_OK => {}
+                    code => return Err(curl::Error::new(code)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remember the connection used by the given transfer, which has just
+    /// completed, if curl keeps it open.
+    pub(crate) fn record(&mut self, handle: *mut CURL) {
+        if let Some(socket) = active_socket(handle).ok().flatten() {
+            if let Some(host) = effective_host(handle) {
+                self.hosts.insert(socket, host);
+            }
+        }
+    }
+
+    /// Take a snapshot of the connections, given the transfers in progress.
+    pub(crate) fn stats(&self, active: impl Iterator<Item = *mut CURL>) -> PoolStats {
+        let mut stats = PoolStats::default();
+        let mut in_use = HashSet::new();
+
+        for handle in active {
+            stats.active_transfers += 1;
+
+            // Multiple transfers may share a connection.
+            if let Some(socket) = active_socket(handle).ok().flatten() {
+                in_use.insert(socket);
+            }
+        }
+
+        stats.open_connections = in_use.len();
+
+        for (socket, host) in &self.hosts {
+            if !in_use.contains(socket) {
+                stats.open_connections += 1;
+                *stats.idle_connections.entry(host.clone()).or_insert(0) += 1;
+            }
+        }
+
+        stats
+    }
+
+    fn closed(&mut self, socket: curl_socket_t) {
+        self.hosts.remove(&socket);
+    }
+}
+
+/// Separate connection pools for each connection pool partition key.
+///
+/// Curl keeps the connections, DNS entries, and TLS sessions of transfers that
+/// use a share handle in the share handle instead of the multi handle, so each
+/// partition gets a share handle of its own. Only the agent that owns them
+/// uses them, so dropping a partition closes its idle connections on the
+/// agent thread as usual.
+#[derive(Default)]
+pub(crate) struct Partitions {
+    shares: HashMap<String, Partition>,
+}
+
+struct Partition {
+    share: ShareHandle,
+    last_used: Instant,
+}
+
+impl Partitions {
+    /// Get the share handle holding the connections of the given partition,
+    /// creating it if necessary.
+    pub(crate) fn get(&mut self, key: &str) -> Result<ShareHandle, Error> {
+        if let Some(partition) = self.shares.get_mut(key) {
+            partition.last_used = Instant::now();
+
+            return Ok(partition.share.clone());
+        }
+
+        if self.shares.len() >= MAX_PARTITIONS {
+            self.evict();
+        }
+
+        tracing::debug!("creating connection pool partition {:?}", key);
+
+        let share = ShareHandle::new()?;
+
+        self.shares.insert(
+            key.to_owned(),
+            Partition {
+                share: share.clone(),
+                last_used: Instant::now(),
+            },
+        );
+
+        Ok(share)
+    }
+
+    /// Drop the least recently used partition. Transfers still using it keep
+    /// its share handle alive until they complete.
+    fn evict(&mut self) {
+        let oldest = self
+            .shares
+            .iter()
+            .min_by_key(|(_, partition)| partition.last_used)
+            .map(|(key, _)| key.clone());
+
+        if let Some(key) = oldest {
+            tracing::debug!("dropping connection pool partition {:?}", key);
+            self.shares.remove(&key);
+        }
+    }
+}
+
+/// Get the socket of the connection most recently used by the given
+/// transfer, if it is still open.
+pub(crate) fn active_socket(handle: *mut CURL) -> Result<Option<curl_socket_t>, curl::Error> {
+    let mut socket = curl_sys::CURL_SOCKET_BAD;
+
+    // Use unsafe API, because the safe API does not expose this option.
+    let code = unsafe { curl_sys::curl_easy_getinfo(handle, CURLINFO_ACTIVESOCKET, &mut socket) };
+
+    match code {
+        curl_sys::CURLE_OK if socket != curl_sys::CURL_SOCKET_BAD => Ok(Some(socket)),
+        curl_sys::CURLE_OK => Ok(None),
+        code => Err(curl::Error::new(code)),
+    }
+}
+
+/// Get the host and port the given transfer was sent to, such as
+/// `example.org:443`.
+fn effective_host(handle: *mut CURL) -> Option<String> {
+    let mut url: *const c_char = ptr::null();
+
+    unsafe {
+        if curl_sys::curl_easy_getinfo(handle, curl_sys::CURLINFO_EFFECTIVE_URL, &mut url)
+            != curl_sys::CURLE_OK
+            || url.is_null()
+        {
+            return None;
+        }
+    }
+
+    let uri = unsafe { CStr::from_ptr(url) }
+        .to_str()
+        .ok()?
+        .parse::<Uri>()
+        .ok()?;
+
+    let port = uri.port_u16().unwrap_or_else(|| match uri.scheme_str() {
+        Some("http") | Some("ws") => 80,
+        _ => 443,
+    });
+
+    Some(format!("{}:{}", uri.host()?, port))
+}
+
+/// Called by curl to close the socket of a connection.
+extern "C" fn close_callback(data: *mut c_void, socket: curl_socket_t) -> c_int {
+    let connections = unsafe { &mut *(data as *mut Connections) };
+
+    connections.closed(socket);
+
+    // Curl leaves closing the socket to us.
+    #[cfg(unix)]
+    drop(unsafe { <std::net::TcpStream as std::os::unix::io::FromRawFd>::from_raw_fd(socket) });
+
+    #[cfg(windows)]
+    drop(unsafe {
+        <std::net::TcpStream as std::os::windows::io::FromRawSocket>::from_raw_socket(socket as _)
+    });
+
+    0
+}
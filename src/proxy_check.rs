@@ -0,0 +1,94 @@
+//! Checking that a configured proxy can be used, without sending a request.
+//!
+//! Curl can be told to stop once a connection is established, which includes
+//! any handshake with the proxy. For HTTP proxies, a tunnel is requested with
+//! `CONNECT` even for insecure targets, so that the proxy has to accept the
+//! credentials before the check succeeds.
+
+use crate::{
+    auth::{Authentication, Credentials},
+    client::Defaults,
+    config::{
+        internal::SetOpt,
+        proxy::Proxy,
+        CaCertificate,
+        ConnectTimeout,
+        IpVersion,
+        NetworkInterface,
+        SslOption,
+        Timeout,
+    },
+    error::{Error, ErrorKind},
+};
+use curl::easy::{Easy2, Handler};
+use http::Uri;
+use std::io;
+
+/// Connect to the given target through the proxy configured in the given
+/// client defaults.
+pub(crate) fn check(target: &Uri, options: Defaults<'_>) -> Result<(), Error> {
+    match options.get::<Proxy<Option<Uri>>>() {
+        Some(Proxy(Some(_))) => {}
+        _ => {
+            return Err(Error::with_context(
+                ErrorKind::InvalidRequest,
+                Some(String::from("no proxy is configured")),
+                io::Error::new(io::ErrorKind::Other, "no proxy is configured"),
+            ));
+        }
+    }
+
+    let mut easy = Easy2::new(NullHandler);
+
+    easy.signal(false)?;
+    easy.url(&target.to_string())?;
+    easy.connect_only(true)?;
+    easy.http_proxy_tunnel(true)?;
+
+    macro_rules! set_opts {
+        ($easy:expr, $options:expr, [$($option:ty,)*]) => {{
+            $(
+                if let Some(extension) = $options.get::<$option>() {
+                    extension.set_opt($easy)?;
+                }
+            )*
+        }};
+    }
+
+    set_opts!(&mut easy, options, [
+        Timeout,
+        ConnectTimeout,
+        NetworkInterface,
+        IpVersion,
+        Proxy<Option<Uri>>,
+        Proxy<Authentication>,
+        Proxy<Credentials>,
+        CaCertificate,
+        SslOption,
+    ]);
+
+    if let Err(e) = easy.perform() {
+        let status = easy.http_connectcode().unwrap_or(0);
+
+        return Err(match status {
+            0 | 200..=299 => e.into(),
+            407 => Error::with_context(
+                ErrorKind::InvalidCredentials,
+                Some(String::from("the proxy rejected the credentials")),
+                e,
+            ),
+            status => Error::with_context(
+                ErrorKind::ConnectionFailed,
+                Some(format!("the proxy refused to connect with status {}", status)),
+                e,
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Handler for a transfer that never sends or receives any data.
+struct NullHandler;
+
+impl Handler for NullHandler {}
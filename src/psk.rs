@@ -0,0 +1,137 @@
+//! Support for TLS pre-shared keys (PSK) when libcurl uses OpenSSL.
+//!
+//! Curl has no options for PSK, but it does give us access to the OpenSSL
+//! context of each new connection, where we can install a PSK callback. The
+//! callback does not receive any user data, so the key is stored in the ex
+//! data of the context instead, which also takes care of freeing it once the
+//! context is no longer used. This is necessary because the context is owned
+//! by the connection, which may outlive the request that created it.
+
+#![allow(unsafe_code)]
+
+use crate::config::PreSharedKey;
+use once_cell::sync::Lazy;
+use openssl_sys::{SSL_CTX_get_ex_data, SSL_CTX_set_ex_data, SSL_get_SSL_CTX, SSL, SSL_CTX};
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_long, c_uchar, c_uint, c_void},
+    ptr,
+};
+
+/// Index of the ex data slot in which the pre-shared key is stored.
+static EX_INDEX: Lazy<c_int> = Lazy::new(|| unsafe {
+    openssl_sys::init();
+    openssl_sys::SSL_CTX_get_ex_new_index(0, ptr::null_mut(), None, None, Some(free_ex_data))
+});
+
+/// Check whether libcurl is using an SSL backend that we know how to install
+/// a PSK callback for.
+pub(crate) fn is_supported() -> bool {
+    curl::Version::get()
+        .ssl_version()
+        .map(|version| version.starts_with("OpenSSL/"))
+        .unwrap_or(false)
+}
+
+/// Configure the given OpenSSL context to authenticate using the given
+/// pre-shared key.
+///
+/// # Safety
+///
+/// The given pointer must point to a valid `SSL_CTX`, which is only the case
+/// if libcurl is using OpenSSL.
+pub(crate) unsafe fn configure_ssl_ctx(ctx: *mut c_void, psk: &PreSharedKey) -> bool {
+    let ctx = ctx as *mut SSL_CTX;
+    let index = *EX_INDEX;
+
+    if index < 0 {
+        return false;
+    }
+
+    let data = Box::into_raw(Box::new(psk.clone())) as *mut c_void;
+
+    // If curl reused a context we configured before, replace the key stored
+    // in it. OpenSSL does not free the old value when it is replaced.
+    let previous = SSL_CTX_get_ex_data(ctx, index);
+
+    if SSL_CTX_set_ex_data(ctx, index, data) != 1 {
+        drop(Box::from_raw(data as *mut PreSharedKey));
+        return false;
+    }
+
+    if !previous.is_null() {
+        drop(Box::from_raw(previous as *mut PreSharedKey));
+    }
+
+    openssl_sys::SSL_CTX_set_psk_client_callback(ctx, Some(psk_client_callback));
+
+    true
+}
+
+/// Called by OpenSSL during the handshake to get the identity and key to use.
+extern "C" fn psk_client_callback(
+    ssl: *mut SSL,
+    hint: *const c_char,
+    identity: *mut c_char,
+    max_identity_len: c_uint,
+    psk: *mut c_uchar,
+    max_psk_len: c_uint,
+) -> c_uint {
+    let result = std::panic::catch_unwind(|| unsafe {
+        let data = SSL_CTX_get_ex_data(SSL_get_SSL_CTX(ssl), *EX_INDEX) as *const PreSharedKey;
+
+        if data.is_null() {
+            return 0;
+        }
+
+        let config = &*data;
+        let hint = if hint.is_null() {
+            None
+        } else {
+            CStr::from_ptr(hint).to_str().ok()
+        };
+
+        let key = match (config.key)(hint) {
+            Some(key) => key,
+            None => {
+                tracing::warn!("no pre-shared key available for identity hint {:?}", hint);
+                return 0;
+            }
+        };
+
+        // The identity must be written with a terminating NUL byte.
+        let identity_bytes = config.identity.as_bytes();
+
+        if identity_bytes.len() >= max_identity_len as usize || key.len() > max_psk_len as usize {
+            tracing::warn!("pre-shared key identity or key is too long");
+            return 0;
+        }
+
+        ptr::copy_nonoverlapping(
+            identity_bytes.as_ptr(),
+            identity as *mut u8,
+            identity_bytes.len(),
+        );
+        *identity.add(identity_bytes.len()) = 0;
+
+        ptr::copy_nonoverlapping(key.as_ptr(), psk, key.len());
+
+        key.len() as c_uint
+    });
+
+    result.unwrap_or(0)
+}
+
+/// Free the pre-shared key stored in a context when the context is freed.
+unsafe extern "C" fn free_ex_data(
+    _parent: *mut c_void,
+    ptr: *mut c_void,
+    _ad: *mut openssl_sys::CRYPTO_EX_DATA,
+    _idx: c_int,
+    _argl: c_long,
+    _argp: *mut c_void,
+) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr as *mut PreSharedKey));
+    }
+}
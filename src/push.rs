@@ -0,0 +1,493 @@
+//! Receiving responses pushed by HTTP/2 servers.
+//!
+//! When a server promises to push a response, curl creates a new easy handle
+//! for it and asks the agent whether to accept it. The handle is not one of
+//! ours, so the agent keeps track of it separately from regular requests,
+//! identifying it by a token that cannot be confused with the token of a
+//! request. The response is buffered in memory and sent to whoever is
+//! listening on the response that the push belongs to once it is complete.
+
+#![allow(unsafe_code)]
+
+use crate::{
+    body::Body,
+    error::Error,
+    parsing::{parse_header, parse_status_line},
+};
+use curl_sys::CURL;
+use flume::{Receiver, Sender};
+use http::{Request, Response, StatusCode, Uri, Version};
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    fmt,
+    os::raw::{c_char, c_int, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    slice,
+};
+
+/// Tokens of pushed transfers have the highest bit set, which is never the
+/// case for the tokens of requests.
+const PUSH_TOKEN: usize = !(usize::MAX >> 1);
+
+const CURL_PUSH_OK: c_int = 0;
+const CURL_PUSH_DENY: c_int = 1;
+
+extern "C" {
+    // Not yet defined by curl-sys.
+    fn curl_pushheader_bynum(headers: *mut c_void, num: usize) -> *mut c_char;
+}
+
+/// The responses pushed by the server along with a response, as returned by
+/// [`ResponseExt::pushed_responses`](crate::ResponseExt::pushed_responses).
+///
+/// Pushed responses are received in the order they complete. Once the
+/// response they were pushed along with and all pushed responses are
+/// complete, no more responses are received. The body of the original
+/// response should therefore be read before waiting for pushed responses,
+/// since otherwise the transfer may never complete.
+///
+/// This can be used as a blocking iterator, or asynchronously using
+/// [`recv_async`](PushedResponses::recv_async).
+#[derive(Clone, Debug)]
+pub struct PushedResponses {
+    receiver: Receiver<PushedResponse>,
+}
+
+impl PushedResponses {
+    pub(crate) fn new(receiver: Receiver<PushedResponse>) -> Self {
+        Self {
+            receiver,
+        }
+    }
+
+    /// Wait for the next pushed response, blocking the current thread.
+    ///
+    /// Returns `None` if no more responses are going to be pushed.
+    pub fn recv(&self) -> Option<PushedResponse> {
+        self.receiver.recv().ok()
+    }
+
+    /// Wait for the next pushed response asynchronously.
+    ///
+    /// Returns `None` if no more responses are going to be pushed.
+    pub async fn recv_async(&self) -> Option<PushedResponse> {
+        self.receiver.recv_async().await.ok()
+    }
+
+    /// Get the next pushed response if one has already been received, without
+    /// waiting.
+    pub fn try_recv(&self) -> Option<PushedResponse> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Iterator for PushedResponses {
+    type Item = PushedResponse;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+/// A response pushed by the server, along with the request the server
+/// promised it for.
+///
+/// The response body is received completely before the pushed response is
+/// made available, and is decompressed if it was sent compressed.
+#[derive(Debug)]
+pub struct PushedResponse {
+    request: Request<()>,
+    response: Response<Body>,
+}
+
+impl PushedResponse {
+    /// Get the request the server sent the response for, made up of the
+    /// headers of the push promise.
+    pub fn request(&self) -> &Request<()> {
+        &self.request
+    }
+
+    /// Get the pushed response.
+    pub fn response(&self) -> &Response<Body> {
+        &self.response
+    }
+
+    /// Get a mutable reference to the pushed response, such as to read its
+    /// body.
+    pub fn response_mut(&mut self) -> &mut Response<Body> {
+        &mut self.response
+    }
+
+    /// Split into the promised request and the pushed response.
+    pub fn into_parts(self) -> (Request<()>, Response<Body>) {
+        (self.request, self.response)
+    }
+}
+
+/// Pushed transfers in progress on an agent.
+#[derive(Default)]
+pub(crate) struct Pushes {
+    next_id: usize,
+
+    /// Transfers by token. Curl holds pointers to them, so they are boxed.
+    transfers: HashMap<usize, Box<PushedTransfer>>,
+
+    /// Tokens of transfers accepted since they were last claimed.
+    unclaimed: Vec<usize>,
+}
+
+impl Pushes {
+    /// Check whether the given token belongs to a pushed transfer.
+    pub(crate) fn is_push_token(token: usize) -> bool {
+        token & PUSH_TOKEN != 0
+    }
+
+    /// Check whether there are no pushed transfers in progress.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    /// Have the given multi handle accept pushed responses.
+    ///
+    /// Curl holds a pointer to this struct, so it must be kept in place for
+    /// as long as the multi handle is performing transfers.
+    pub(crate) fn register(&mut self, multi: &curl::multi::Multi) -> Result<(), Error> {
+        let callback: extern "C" fn(
+            *mut CURL,
+            *mut CURL,
+            usize,
+            *mut c_void,
+            *mut c_void,
+        ) -> c_int = push_callback;
+        let data = self as *mut Self as *mut c_void;
+
+        unsafe {
+            for &(option, value) in &[
+                (curl_sys::CURLMOPT_PUSHFUNCTION, callback as *mut c_void),
+                (curl_sys::CURLMOPT_PUSHDATA, data),
+            ] {
+                match curl_sys::curl_multi_setopt(multi.raw(), option, value) {
+                    curl_sys::CURLM_OK => {}
+                    code => return Err(curl::MultiError::new(code).into()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hand the transfers accepted since the last call to the responses they
+    /// were pushed along with, given the token of each parent request.
+    ///
+    /// This must be called before the parents can complete, since curl only
+    /// tells which request a push belongs to while it is still active.
+    pub(crate) fn claim(
+        &mut self,
+        mut sender: impl FnMut(usize) -> Option<Sender<PushedResponse>>,
+    ) {
+        for token in self.unclaimed.drain(..) {
+            if let Some(transfer) = self.transfers.get_mut(&token) {
+                transfer.sender = sender(transfer.parent);
+            }
+        }
+    }
+
+    /// Complete the pushed transfer with the given token, delivering the
+    /// response if it succeeded.
+    pub(crate) fn complete(&mut self, token: usize, result: Result<(), curl::Error>) {
+        let mut transfer = match self.transfers.remove(&token) {
+            Some(transfer) => transfer,
+            None => {
+                tracing::warn!("pushed transfer completed with unknown token: {}", token);
+                return;
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::debug!(
+                "pushed transfer for {} failed: {}",
+                transfer.request.uri(),
+                e
+            );
+            return;
+        }
+
+        let sender = match transfer.sender.take() {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        let mut builder = Response::builder()
+            .status(transfer.status.unwrap_or(StatusCode::OK))
+            .version(transfer.version);
+
+        if let Some(headers) = builder.headers_mut() {
+            *headers = std::mem::take(&mut transfer.headers);
+        }
+
+        let body = Body::from(std::mem::take(&mut transfer.body));
+        let request = std::mem::replace(&mut transfer.request, Request::new(()));
+
+        if let Ok(response) = builder.body(body) {
+            if sender
+                .send(PushedResponse {
+                    request,
+                    response,
+                })
+                .is_err()
+            {
+                tracing::debug!("pushed response dropped since nobody is listening");
+            }
+        }
+    }
+
+    /// Accept a pushed transfer, returning whether it was accepted.
+    fn accept(
+        &mut self,
+        parent: *mut CURL,
+        easy: *mut CURL,
+        headers: *mut c_void,
+        count: usize,
+    ) -> bool {
+        let request = match promised_request(headers, count) {
+            Some(request) => request,
+            None => return false,
+        };
+
+        let parent = match get_token(parent) {
+            Some(token) => token,
+            None => return false,
+        };
+
+        let token = (self.next_id & !PUSH_TOKEN) | PUSH_TOKEN;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let mut transfer = Box::new(PushedTransfer {
+            handle: ptr::null_mut(),
+            parent,
+            request,
+            sender: None,
+            status: None,
+            version: Version::HTTP_2,
+            headers: http::HeaderMap::new(),
+            body: Vec::new(),
+        });
+
+        if let Err(e) = transfer.set_callbacks(easy, token) {
+            tracing::debug!("failed to accept pushed transfer: {}", e);
+            return false;
+        }
+
+        // Only clean up the handle once it is known to be ours.
+        transfer.handle = easy;
+        self.transfers.insert(token, transfer);
+        self.unclaimed.push(token);
+
+        true
+    }
+}
+
+impl fmt::Debug for Pushes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pushes")
+            .field("active", &self.transfers.len())
+            .finish()
+    }
+}
+
+/// A pushed response being received.
+struct PushedTransfer {
+    /// The easy handle curl created for the transfer, owned by this struct.
+    handle: *mut CURL,
+
+    /// Token of the request the response was pushed along with.
+    parent: usize,
+
+    request: Request<()>,
+    sender: Option<Sender<PushedResponse>>,
+    status: Option<StatusCode>,
+    version: Version,
+    headers: http::HeaderMap,
+    body: Vec<u8>,
+}
+
+impl PushedTransfer {
+    fn set_callbacks(&mut self, easy: *mut CURL, token: usize) -> Result<(), curl::Error> {
+        let header_callback: extern "C" fn(*mut c_char, usize, usize, *mut c_void) -> usize =
+            header_callback;
+        let write_callback: extern "C" fn(*mut c_char, usize, usize, *mut c_void) -> usize =
+            write_callback;
+        let data = self as *mut Self as *mut c_void;
+
+        unsafe {
+            for &(option, value) in &[
+                (
+                    curl_sys::CURLOPT_HEADERFUNCTION,
+                    header_callback as *mut c_void,
+                ),
+                (curl_sys::CURLOPT_HEADERDATA, data),
+                (
+                    curl_sys::CURLOPT_WRITEFUNCTION,
+                    write_callback as *mut c_void,
+                ),
+                (curl_sys::CURLOPT_WRITEDATA, data),
+                (curl_sys::CURLOPT_PRIVATE, token as *mut c_void),
+                (
+                    curl_sys::CURLOPT_ACCEPT_ENCODING,
+                    b"\0".as_ptr() as *mut c_void,
+                ),
+            ] {
+                match curl_sys::curl_easy_setopt(easy, option, value) {
+                    curl_sys::CURLE_OK => {}
+                    code => return Err(curl::Error::new(code)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn header(&mut self, line: &[u8]) {
+        if let Some((version, status)) = parse_status_line(line) {
+            self.version = version;
+            self.status = Some(status);
+            self.headers.clear();
+        } else if let Some((name, value)) = parse_header(line) {
+            self.headers.append(name, value);
+        }
+    }
+}
+
+impl Drop for PushedTransfer {
+    fn drop(&mut self) {
+        // Cleaning up the handle also removes it from the multi handle.
+        if !self.handle.is_null() {
+            unsafe {
+                curl_sys::curl_easy_cleanup(self.handle);
+            }
+        }
+    }
+}
+
+/// Build the request a push promise was made for from its headers.
+fn promised_request(headers: *mut c_void, count: usize) -> Option<Request<()>> {
+    let mut builder = Request::builder();
+    let mut scheme = None;
+    let mut authority = None;
+    let mut path = None;
+
+    for i in 0..count {
+        let header = unsafe { curl_pushheader_bynum(headers, i) };
+
+        if header.is_null() {
+            return None;
+        }
+
+        let header = unsafe { CStr::from_ptr(header) }.to_bytes();
+
+        // Pseudo headers start with a colon, so look for the separator after
+        // the first byte.
+        let split = header.iter().skip(1).position(|&byte| byte == b':')? + 1;
+        let (name, value) = (&header[..split], &header[split + 1..]);
+
+        match name {
+            b":method" => builder = builder.method(value),
+            b":scheme" => scheme = Some(value),
+            b":authority" => authority = Some(value),
+            b":path" => path = Some(value),
+            _ => {
+                if let Some((name, value)) = parse_header(header) {
+                    builder = builder.header(name, value);
+                }
+            }
+        }
+    }
+
+    let mut uri = Vec::new();
+    uri.extend_from_slice(scheme?);
+    uri.extend_from_slice(b"://");
+    uri.extend_from_slice(authority?);
+    uri.extend_from_slice(path?);
+
+    builder
+        .uri(Uri::from_maybe_shared(uri).ok()?)
+        .version(Version::HTTP_2)
+        .body(())
+        .ok()
+}
+
+/// Get the token of the request using the given handle.
+fn get_token(handle: *mut CURL) -> Option<usize> {
+    let mut token: *mut c_void = ptr::null_mut();
+
+    unsafe {
+        match curl_sys::curl_easy_getinfo(handle, curl_sys::CURLINFO_PRIVATE, &mut token) {
+            curl_sys::CURLE_OK => Some(token as usize),
+            _ => None,
+        }
+    }
+}
+
+/// Called by curl when a server promises to push a response.
+extern "C" fn push_callback(
+    parent: *mut CURL,
+    easy: *mut CURL,
+    count: usize,
+    headers: *mut c_void,
+    data: *mut c_void,
+) -> c_int {
+    let pushes = unsafe { &mut *(data as *mut Pushes) };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| {
+        pushes.accept(parent, easy, headers, count)
+    })) {
+        Ok(true) => CURL_PUSH_OK,
+        _ => CURL_PUSH_DENY,
+    }
+}
+
+/// Called by curl with each header line of a pushed response.
+extern "C" fn header_callback(
+    buffer: *mut c_char,
+    size: usize,
+    count: usize,
+    data: *mut c_void,
+) -> usize {
+    let transfer = unsafe { &mut *(data as *mut PushedTransfer) };
+    let line = unsafe { slice::from_raw_parts(buffer as *const u8, size * count) };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| transfer.header(line))) {
+        Ok(()) => size * count,
+        Err(_) => 0,
+    }
+}
+
+/// Called by curl with each chunk of the body of a pushed response.
+extern "C" fn write_callback(
+    buffer: *mut c_char,
+    size: usize,
+    count: usize,
+    data: *mut c_void,
+) -> usize {
+    let transfer = unsafe { &mut *(data as *mut PushedTransfer) };
+    let chunk = unsafe { slice::from_raw_parts(buffer as *const u8, size * count) };
+
+    transfer.body.extend_from_slice(chunk);
+
+    size * count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_tokens_are_told_apart_from_request_tokens() {
+        assert!(!Pushes::is_push_token(0));
+        assert!(!Pushes::is_push_token(usize::MAX >> 1));
+        assert!(Pushes::is_push_token(PUSH_TOKEN));
+        assert!(Pushes::is_push_token(PUSH_TOKEN | 42));
+    }
+}
@@ -0,0 +1,40 @@
+//! Random numbers for things like boundaries and jitter, which only need to
+//! be hard to guess by accident.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Get a random 64-bit number.
+///
+/// The standard library already seeds the hasher used by hash maps randomly,
+/// which is good enough for our needs without pulling in a dependency.
+pub(crate) fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// Get a random number between 0 (inclusive) and 1 (exclusive).
+pub(crate) fn random_fraction() -> f64 {
+    // Use the 53 most significant bits, which is as many as an f64 can hold.
+    (random() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_are_in_range() {
+        for _ in 0..1000 {
+            let fraction = random_fraction();
+
+            assert!((0.0..1.0).contains(&fraction));
+        }
+    }
+}
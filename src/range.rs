@@ -0,0 +1,310 @@
+//! Checking whether a server honored the `Range` header of a request.
+//!
+//! Servers are free to ignore a `Range` header and respond with the entire
+//! representation instead, and an `If-Range` precondition that fails makes them
+//! do so deliberately. Appending such a response to a partially downloaded file
+//! silently corrupts it, so the status code and `Content-Range` header of a
+//! response must be compared with what was requested before using the body.
+
+use http::{
+    header::{HeaderMap, CONTENT_RANGE, CONTENT_TYPE},
+    StatusCode,
+};
+
+/// The value of the `Range` header of the request that produced a response.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestedRange(pub(crate) String);
+
+/// A range of bytes sent in a response, as described by the `Content-Range`
+/// header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ContentRange {
+    start: u64,
+    end: u64,
+    complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse a `Content-Range` header value describing a satisfied range,
+    /// such as `bytes 0-499/1234` or `bytes 0-499/*`.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let (range, complete_length) = split_content_range(value)?;
+
+        let mut range = range.splitn(2, '-');
+        let start = range.next()?.parse::<u64>().ok()?;
+        let end = range.next()?.parse::<u64>().ok()?;
+
+        let complete_length = match complete_length {
+            "*" => None,
+            length => Some(length.parse::<u64>().ok()?),
+        };
+
+        if start > end || complete_length.map(|len| end >= len).unwrap_or(false) {
+            return None;
+        }
+
+        Some(Self {
+            start,
+            end,
+            complete_length,
+        })
+    }
+
+    /// Get the offset of the first byte of the range.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Get the offset of the last byte of the range. Like in the header, the
+    /// range includes this byte.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Get the number of bytes in the range.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Get the length of the entire representation that the range is a part
+    /// of, if the server reported it.
+    pub fn complete_length(&self) -> Option<u64> {
+        self.complete_length
+    }
+
+    /// Check whether this range is one the server may send in response to
+    /// the given `Range` header value.
+    fn satisfies(&self, requested: &str) -> bool {
+        let specs = match requested.trim().splitn(2, '=').collect::<Vec<_>>()[..] {
+            [unit, specs] if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+
+            // Ranges in other units cannot be checked.
+            _ => return true,
+        };
+
+        let specs = specs
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .collect::<Vec<_>>();
+
+        // Servers may coalesce several requested ranges into a single one.
+        if specs.len() != 1 {
+            return true;
+        }
+
+        let mut spec = specs[0].splitn(2, '-');
+        let first = spec.next().unwrap_or("").trim();
+        let last = spec.next().unwrap_or("").trim();
+
+        match (first.parse::<u64>().ok(), last.parse::<u64>().ok()) {
+            // A range from an offset up to and including another. The range
+            // is cut short if the representation ends before.
+            (Some(first), Some(last)) => self.start == first && self.end <= last,
+
+            // A range from an offset up to the end.
+            (Some(first), None) if last.is_empty() => {
+                self.start == first
+                    && self
+                        .complete_length
+                        .map(|len| self.end == len - 1)
+                        .unwrap_or(true)
+            }
+
+            // A range of the given number of bytes at the end.
+            (None, Some(suffix)) if first.is_empty() => {
+                self.len() <= suffix
+                    && self
+                        .complete_length
+                        .map(|len| self.end == len - 1)
+                        .unwrap_or(true)
+            }
+
+            _ => false,
+        }
+    }
+}
+
+/// How a server responded to the `Range` header of a request.
+///
+/// This is returned by
+/// [`ResponseExt::range_outcome`](crate::ResponseExt::range_outcome).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RangeOutcome {
+    /// The request did not have a `Range` header.
+    NotRequested,
+
+    /// The server responded with `206 Partial Content` and the body contains
+    /// the given range, which matches the one requested.
+    Partial(ContentRange),
+
+    /// The server responded with `206 Partial Content` and a
+    /// `multipart/byteranges` body containing several ranges. The ranges can
+    /// be read using
+    /// [`ReadResponseExt::multipart`](crate::ReadResponseExt::multipart).
+    Multipart,
+
+    /// The server did not apply the range and did not respond with `206
+    /// Partial Content`. If the response is successful, such as `200 OK`, the
+    /// body contains the entire representation instead of the requested
+    /// range.
+    ///
+    /// This is also the expected outcome when an `If-Range` precondition
+    /// fails because the representation has changed.
+    Ignored,
+
+    /// The server responded with `416 Range Not Satisfiable`, usually because
+    /// the range starts past the end of the representation. Contains the
+    /// length of the representation, if the server reported it.
+    NotSatisfiable(Option<u64>),
+
+    /// The server responded with `206 Partial Content`, but the
+    /// `Content-Range` header is missing, invalid or describes a different
+    /// range than the one requested. The body must not be used as the
+    /// requested range.
+    Mismatched,
+}
+
+impl RangeOutcome {
+    /// Determine the outcome of a response with the given status and
+    /// headers to a request with the given `Range` header value.
+    pub(crate) fn new(requested: Option<&str>, status: StatusCode, headers: &HeaderMap) -> Self {
+        let requested = match requested {
+            Some(requested) => requested,
+            None => return RangeOutcome::NotRequested,
+        };
+
+        match status {
+            StatusCode::PARTIAL_CONTENT => match content_range(headers) {
+                Some(range) if range.satisfies(requested) => RangeOutcome::Partial(range),
+                Some(_) => RangeOutcome::Mismatched,
+                None if is_byteranges(headers) => RangeOutcome::Multipart,
+                None => RangeOutcome::Mismatched,
+            },
+            StatusCode::RANGE_NOT_SATISFIABLE => RangeOutcome::NotSatisfiable(
+                headers
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(split_content_range)
+                    .filter(|(range, _)| *range == "*")
+                    .and_then(|(_, length)| length.parse().ok()),
+            ),
+            _ => RangeOutcome::Ignored,
+        }
+    }
+}
+
+/// Get the range described by the `Content-Range` header, if any.
+pub(crate) fn content_range(headers: &HeaderMap) -> Option<ContentRange> {
+    ContentRange::parse(headers.get(CONTENT_RANGE)?.to_str().ok()?)
+}
+
+/// Split a `Content-Range` value in bytes into the range and the complete
+/// length.
+fn split_content_range(value: &str) -> Option<(&str, &str)> {
+    let value = value.trim();
+    let space = value.find(' ')?;
+
+    if !value[..space].eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut parts = value[space + 1..].trim_start().splitn(2, '/');
+
+    Some((parts.next()?, parts.next()?))
+}
+
+fn is_byteranges(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .map(|content_type| content_type.essence_str() == "multipart/byteranges")
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(requested: &str, status: u16, headers: &[(&str, &str)]) -> RangeOutcome {
+        let mut map = HeaderMap::new();
+
+        for (name, value) in headers {
+            map.append(
+                http::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+
+        RangeOutcome::new(Some(requested), StatusCode::from_u16(status).unwrap(), &map)
+    }
+
+    #[test]
+    fn parse_content_range() {
+        let range = ContentRange::parse("bytes 500-999/8000").unwrap();
+
+        assert_eq!(range.start(), 500);
+        assert_eq!(range.end(), 999);
+        assert_eq!(range.len(), 500);
+        assert_eq!(range.complete_length(), Some(8000));
+
+        let range = ContentRange::parse("bytes 0-0/*").unwrap();
+        assert_eq!(range.complete_length(), None);
+
+        assert_eq!(ContentRange::parse("bytes */8000"), None);
+        assert_eq!(ContentRange::parse("bytes 10-5/8000"), None);
+        assert_eq!(ContentRange::parse("bytes 0-8000/8000"), None);
+        assert_eq!(ContentRange::parse("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn resumed_download_outcomes() {
+        let range = ContentRange::parse("bytes 100-199/200").unwrap();
+
+        assert_eq!(
+            outcome("bytes=100-", 206, &[("content-range", "bytes 100-199/200")]),
+            RangeOutcome::Partial(range)
+        );
+        assert_eq!(
+            outcome("bytes=100-", 206, &[("content-range", "bytes 0-199/200")]),
+            RangeOutcome::Mismatched
+        );
+        assert_eq!(
+            outcome("bytes=100-", 206, &[("content-range", "bytes 100-149/200")]),
+            RangeOutcome::Mismatched
+        );
+        assert_eq!(outcome("bytes=100-", 206, &[]), RangeOutcome::Mismatched);
+        assert_eq!(outcome("bytes=100-", 200, &[]), RangeOutcome::Ignored);
+        assert_eq!(
+            outcome("bytes=200-", 416, &[("content-range", "bytes */200")]),
+            RangeOutcome::NotSatisfiable(Some(200))
+        );
+    }
+
+    #[test]
+    fn bounded_and_suffix_ranges() {
+        let tail = ContentRange::parse("bytes 50-99/100").unwrap();
+
+        // The range may be cut short by the end of the representation.
+        assert_eq!(
+            outcome("bytes=0-499", 206, &[("content-range", "bytes 0-99/100")]),
+            RangeOutcome::Partial(ContentRange::parse("bytes 0-99/100").unwrap())
+        );
+        assert_eq!(
+            outcome("bytes=-50", 206, &[("content-range", "bytes 50-99/100")]),
+            RangeOutcome::Partial(tail)
+        );
+        assert_eq!(
+            outcome("bytes=-50", 206, &[("content-range", "bytes 0-49/100")]),
+            RangeOutcome::Mismatched
+        );
+        let multipart = [("content-type", "multipart/byteranges; boundary=x")];
+        assert_eq!(
+            outcome("bytes=0-9,20-29", 206, &multipart),
+            RangeOutcome::Multipart
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Adaptive sizing of the buffer that curl receives response bodies into.
+//!
+//! Curl reads at most one buffer worth of data from a connection at a time and
+//! hands it to the handler in chunks of up to 16 KiB, which are then kept in
+//! the response body pipe until they are read. A large buffer lets bulk
+//! downloads keep up with fast connections using fewer reads, while a small
+//! buffer keeps the chunks of responses that trickle in small. Rather than
+//! using a single fixed size for all responses, the buffer starts out at the
+//! curl default and is adjusted as chunks are received:
+//!
+//! - If a chunk fills the entire buffer, or is as large as curl hands out at
+//!   once, more data was likely waiting to be read, so the buffer is doubled.
+//! - If two chunks in a row fill no more than a quarter of the buffer, data is
+//!   arriving slower than it can be read, so the buffer is halved.
+//!
+//! Curl only supports setting the buffer size before a transfer starts, so a
+//! new size is not applied to the transfer it was measured on. Instead it is
+//! kept with the curl handle, and applied to the next transfer that reuses
+//! the handle.
+
+/// Smallest size of the buffer, which fits most API responses in one chunk.
+const MIN_SIZE: usize = 4 * 1024;
+
+/// Initial size of the buffer, which is the default used by curl.
+const INITIAL_SIZE: usize = 16 * 1024;
+
+/// Largest size of the buffer.
+const MAX_SIZE: usize = 256 * 1024;
+
+/// Largest chunk curl passes to the handler at once, `CURL_MAX_WRITE_SIZE`.
+/// Data read into a larger buffer is passed on in chunks of this size.
+const MAX_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Tracks the size of the receive buffer of a curl handle.
+#[derive(Debug)]
+pub(crate) struct RecvBufferSize {
+    size: usize,
+
+    /// Whether the previous chunk was small enough to shrink the buffer.
+    shrink_pending: bool,
+}
+
+impl Default for RecvBufferSize {
+    fn default() -> Self {
+        Self {
+            size: INITIAL_SIZE,
+            shrink_pending: false,
+        }
+    }
+}
+
+impl RecvBufferSize {
+    /// Get the current size of the buffer.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Account for a chunk of the given length being received, returning the
+    /// new size of the buffer if it should change.
+    pub(crate) fn record(&mut self, len: usize) -> Option<usize> {
+        if len >= self.size.min(MAX_CHUNK_SIZE) {
+            self.shrink_pending = false;
+            self.resize((self.size * 2).min(MAX_SIZE))
+        } else if len <= self.size / 4 {
+            if self.shrink_pending {
+                self.shrink_pending = false;
+                self.resize((self.size / 2).max(MIN_SIZE))
+            } else {
+                self.shrink_pending = true;
+                None
+            }
+        } else {
+            self.shrink_pending = false;
+            None
+        }
+    }
+
+    fn resize(&mut self, size: usize) -> Option<usize> {
+        if size == self.size {
+            None
+        } else {
+            self.size = size;
+            Some(size)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_chunks_grow_buffer_up_to_limit() {
+        let mut buffer = RecvBufferSize::default();
+
+        assert_eq!(buffer.record(INITIAL_SIZE / 2), None);
+        assert_eq!(buffer.record(INITIAL_SIZE), Some(INITIAL_SIZE * 2));
+        assert_eq!(buffer.record(MAX_CHUNK_SIZE / 2), None);
+        assert_eq!(buffer.record(MAX_CHUNK_SIZE), Some(INITIAL_SIZE * 4));
+
+        while buffer.record(MAX_CHUNK_SIZE).is_some() {}
+
+        assert_eq!(buffer.size(), MAX_SIZE);
+    }
+
+    #[test]
+    fn consecutive_small_chunks_shrink_buffer_down_to_limit() {
+        let mut buffer = RecvBufferSize::default();
+
+        assert_eq!(buffer.record(100), None);
+        assert_eq!(buffer.record(INITIAL_SIZE / 2), None);
+        assert_eq!(buffer.record(100), None);
+        assert_eq!(buffer.record(100), Some(INITIAL_SIZE / 2));
+        assert_eq!(buffer.record(100), None);
+        assert_eq!(buffer.record(100), Some(MIN_SIZE));
+        assert_eq!(buffer.record(100), None);
+        assert_eq!(buffer.record(100), None);
+        assert_eq!(buffer.size(), MIN_SIZE);
+    }
+}
@@ -1,13 +1,17 @@
 use crate::{
+    auth::Credentials,
     body::AsyncBody,
-    config::RedirectPolicy,
+    config::{HeaderMergePolicy, OriginChange, RedirectHeaderPolicy, RedirectPolicy},
+    connection::ConnectionReport,
     error::{Error, ErrorKind},
     handler::RequestBody,
     interceptor::{Context, Interceptor, InterceptorFuture},
+    metrics::Metrics,
     request::RequestExt,
+    response::{ConnectionReused, LocalAddr, RemoteAddr, ResponseTimes},
 };
-use http::{Request, Response, Uri};
-use std::convert::TryFrom;
+use http::{HeaderMap, Request, Response, StatusCode, Uri};
+use std::{convert::TryFrom, mem};
 use url::Url;
 
 /// How many redirects to follow by default if a limit is not specified. We
@@ -19,6 +23,50 @@ const DEFAULT_REDIRECT_LIMIT: u32 = 1024;
 /// following any redirects.
 pub(crate) struct EffectiveUri(pub(crate) Uri);
 
+/// Extension containing the redirect hops that were followed before the final
+/// response was received.
+pub(crate) struct RedirectHops(pub(crate) Vec<RedirectHop>);
+
+/// Information about a single redirect that was followed while performing a
+/// request.
+///
+/// The redirects followed for a request can be inspected using
+/// [`ResponseExt::redirect_hops`](crate::ResponseExt::redirect_hops).
+#[derive(Clone, Debug)]
+pub struct RedirectHop {
+    uri: Uri,
+    status: StatusCode,
+    headers: HeaderMap,
+    metrics: Option<Metrics>,
+}
+
+impl RedirectHop {
+    /// Get the URI that was requested in this hop.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Get the redirect status code returned by the server for this hop.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the redirect response returned by the server for
+    /// this hop.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the timing metrics for this hop, such as name lookup, connect, and
+    /// time to first byte.
+    ///
+    /// Metrics are only available if they were enabled for the request using
+    /// [`Configurable::metrics`](crate::config::Configurable::metrics).
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+}
+
 /// Interceptor that implements automatic following of HTTP redirects.
 pub(crate) struct RedirectInterceptor;
 
@@ -56,6 +104,19 @@ impl Interceptor for RedirectInterceptor {
                 .get::<crate::config::redirect::AutoReferer>()
                 .is_some();
 
+            // Get the policy for removing sensitive headers on redirects.
+            let header_policy = request
+                .extensions()
+                .get::<RedirectHeaderPolicy>()
+                .cloned()
+                .unwrap_or_default();
+
+            let merge_policy = request
+                .extensions()
+                .get::<HeaderMergePolicy>()
+                .cloned()
+                .unwrap_or_default();
+
             let limit = match policy {
                 RedirectPolicy::Limit(limit) => limit,
                 _ => DEFAULT_REDIRECT_LIMIT,
@@ -64,6 +125,9 @@ impl Interceptor for RedirectInterceptor {
             // Keep track of how many redirects we've done.
             let mut redirect_count: u32 = 0;
 
+            // Keep track of each redirect followed along the way.
+            let mut hops = Vec::new();
+
             loop {
                 // Preserve a clone of the request before sending it.
                 let mut request_builder = request.to_builder();
@@ -114,19 +178,67 @@ impl Interceptor for RedirectInterceptor {
                         return Err(ErrorKind::RequestBodyNotRewindable.into());
                     }
 
+                    tracing::debug!(
+                        status = response.status().as_u16(),
+                        location = %location,
+                        "following redirect",
+                    );
+
+                    let changes = OriginChange::between(&effective_uri, &location);
+
+                    hops.push(RedirectHop {
+                        uri: effective_uri,
+                        status: response.status(),
+                        headers: mem::take(response.headers_mut()),
+                        metrics: response.extensions().get::<Metrics>().cloned(),
+                    });
+
+                    // The extensions of the original request are preserved on
+                    // the response, so carry them over to the next request,
+                    // minus the ones describing this particular response.
+                    let mut extensions = mem::take(response.extensions_mut());
+                    extensions.remove::<ConnectionReused>();
+                    extensions.remove::<LocalAddr>();
+                    extensions.remove::<RemoteAddr>();
+                    extensions.remove::<ResponseTimes>();
+                    extensions.remove::<Metrics>();
+                    extensions.remove::<ConnectionReport>();
+                    #[cfg(feature = "mime-sniff")]
+                    extensions.remove::<crate::sniff::SniffedContentType>();
+
+                    // Remove any sensitive headers that should not be sent to
+                    // the new location.
+                    if !changes.is_empty() {
+                        if let Some(headers) = request_builder.headers_mut() {
+                            header_policy.strip(headers, &changes);
+                        }
+
+                        if header_policy.is_stripped(&http::header::AUTHORIZATION, &changes) {
+                            extensions.remove::<Credentials>();
+                        }
+                    }
+
                     // Update the request to point to the new URI.
                     effective_uri = location.clone();
                     request = request_builder
                         .uri(location)
                         .body(request_body)
                         .map_err(|e| Error::new(ErrorKind::InvalidRequest, e))?;
+                    *request.extensions_mut() = extensions;
                     redirect_count += 1;
                 }
                 // No more redirects; set the effective URI we finally settled on and return.
                 else {
+                    if merge_policy.merges_redirects() {
+                        for hop in &hops {
+                            crate::headers::merge(response.headers_mut(), &hop.headers);
+                        }
+                    }
+
                     response
                         .extensions_mut()
                         .insert(EffectiveUri(effective_uri));
+                    response.extensions_mut().insert(RedirectHops(hops));
 
                     return Ok(response);
                 }
@@ -156,7 +268,7 @@ fn get_redirect_location<T>(request_uri: &Uri, response: &Response<T>) -> Option
 }
 
 /// Resolve one URI in terms of another.
-fn resolve(base: &Uri, target: &str) -> Result<Uri, Box<dyn std::error::Error>> {
+pub(crate) fn resolve(base: &Uri, target: &str) -> Result<Uri, Box<dyn std::error::Error>> {
     // Optimistically check if this is an absolute URI.
     match Url::parse(target) {
         Ok(url) => Ok(Uri::try_from(url.as_str())?),
@@ -1,10 +1,13 @@
 use crate::{
     body::{AsyncBody, Body},
     client::ResponseFuture,
-    config::{internal::ConfigurableBase, Configurable},
+    config::{internal::ConfigurableBase, Configurable, UserAgent},
     error::Error,
+    format::Format,
+    response::PeekBody,
 };
 use http::{Request, Response};
+use std::collections::BTreeMap;
 
 /// Extension methods on an HTTP request.
 pub trait RequestExt<T> {
@@ -14,6 +17,131 @@ pub trait RequestExt<T> {
     /// Note that third-party extensions are not cloned.
     fn to_builder(&self) -> http::request::Builder;
 
+    /// Attach a named tag to this request.
+    ///
+    /// Tags have no effect on how the request is sent, but are included in
+    /// debug logs and carried through to the response, where they can be
+    /// retrieved using [`ResponseExt::tag`](crate::ResponseExt::tag). This
+    /// makes it easy to correlate a response with whatever caused the request
+    /// to be made.
+    ///
+    /// More generally, any value inserted into the request's
+    /// [`extensions`](http::Request::extensions) is also preserved and can be
+    /// found in the response's extensions.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = Request::get("https://example.org")
+    ///     .body(())?
+    ///     .tag("operation", "fetch-homepage")
+    ///     .send()?;
+    ///
+    /// assert_eq!(response.tag("operation"), Some("fetch-homepage"));
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn tag<K, V>(self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>;
+
+    /// Set the route template of this request, such as `/users/{id}`.
+    ///
+    /// Like a [tag](RequestExt::tag), the route has no effect on how the
+    /// request is sent. It is meant to identify the kind of resource being
+    /// requested without the identifiers contained in the actual URI. When a
+    /// route is set, it is recorded in tracing spans instead of the URI, and
+    /// it is included in the request's [`Metrics`](crate::Metrics), so that
+    /// requests to the same route can be grouped together without creating a
+    /// separate series for every URI.
+    ///
+    /// The route is carried through to the response, where it can be
+    /// retrieved using [`ResponseExt::route`](crate::ResponseExt::route).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = Request::get("https://example.org/users/42")
+    ///     .metrics(true)
+    ///     .body(())?
+    ///     .route("/users/{id}")
+    ///     .send()?;
+    ///
+    /// assert_eq!(response.route(), Some("/users/{id}"));
+    ///
+    /// if let Some(metrics) = response.metrics() {
+    ///     println!("{:?}: {:?}", metrics.route(), metrics.total_time());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn route(self, template: impl Into<String>) -> Self;
+
+    /// Format the request for debugging.
+    ///
+    /// Only the request line is shown by default. The returned [`Format`] can
+    /// be used to include the headers and a preview of the body as well, with
+    /// the values of sensitive headers such as `Authorization` redacted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let mut request = Request::post("https://example.org/items")
+    ///     .header("Authorization", "Bearer secret")
+    ///     .body("hello")?;
+    ///
+    /// assert_eq!(
+    ///     request.format().with_headers().with_body_preview(512).to_string(),
+    ///     "POST /items HTTP/1.1\nhost: example.org\nauthorization: <redacted>\n\nhello",
+    /// );
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn format(&mut self) -> Format<'_>
+    where
+        T: PeekBody;
+
+    /// Get a `curl` command line that sends an equivalent request, for
+    /// reproducing a problem manually or including in a bug report.
+    ///
+    /// The method, URI, headers and body of the request are included, along
+    /// with options configured on the request such as timeouts, redirects,
+    /// proxies and TLS settings. Options set on the client the request is sent
+    /// with are not known to the request, and are not included.
+    ///
+    /// A body is only included if it is stored in memory and is text. Other
+    /// bodies are passed to curl on standard input, which has to be supplied
+    /// when running the command.
+    ///
+    /// Header values and credentials are included as they are, so make sure
+    /// to remove any secrets before sharing the command with others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let request = Request::post("https://example.org/items")
+    ///     .header("Content-Type", "application/json")
+    ///     .timeout(Duration::from_secs(5))
+    ///     .body(r#"{"name":"isahc"}"#)?;
+    ///
+    /// assert_eq!(
+    ///     request.to_curl_command(),
+    ///     "curl https://example.org/items -H 'content-type: application/json' \
+    ///      --data-raw '{\"name\":\"isahc\"}' --compressed --max-time 5",
+    /// );
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn to_curl_command(&self) -> String
+    where
+        T: PeekBody;
+
     /// Send the HTTP request synchronously using the default client.
     ///
     /// This is a convenience method that is equivalent to
@@ -73,38 +201,117 @@ impl<T> RequestExt<T> for Request<T> {
             [
                 crate::config::Timeout,
                 crate::config::ConnectTimeout,
+                crate::config::HeadersTimeout,
+                crate::config::ReadTimeout,
+                crate::config::IdleTimeout,
+                crate::config::QueueTimeout,
                 crate::config::TcpKeepAlive,
                 crate::config::TcpNoDelay,
+                crate::config::HaproxyProtocol,
                 crate::config::NetworkInterface,
                 crate::config::Dialer,
+                crate::config::Http2Keepalive,
                 crate::config::RedirectPolicy,
                 crate::config::redirect::AutoReferer,
+                crate::config::RedirectHeaderPolicy,
                 crate::config::AutomaticDecompression,
+                crate::config::AcceptEncoding,
                 crate::auth::Authentication,
                 crate::auth::Credentials,
                 crate::config::MaxAgeConn,
                 crate::config::MaxUploadSpeed,
                 crate::config::MaxDownloadSpeed,
                 crate::config::VersionNegotiation,
+                crate::config::VersionFallback,
                 crate::config::proxy::Proxy<Option<http::Uri>>,
                 crate::config::proxy::Blacklist,
                 crate::config::proxy::Proxy<crate::auth::Authentication>,
                 crate::config::proxy::Proxy<crate::auth::Credentials>,
+                crate::config::proxy::Proxy<http::HeaderMap>,
                 crate::config::DnsCache,
                 crate::config::dns::ResolveMap,
                 crate::config::ssl::Ciphers,
+                crate::config::ssl::Engine,
                 crate::config::ClientCertificate,
                 crate::config::CaCertificate,
                 crate::config::SslOption,
+                crate::config::ssl::TlsServerName,
+                crate::config::dial::ConnectTo,
                 crate::config::CloseConnection,
+                crate::config::ReusePolicy,
+                crate::config::CancelPolicy,
+                crate::config::HeaderMergePolicy,
+                crate::config::headers::OnHeaders,
+                crate::config::progress::OnProgress,
+                crate::config::HedgePolicy,
+                crate::config::retry::Retry,
+                crate::config::digest::SendDigest,
+                crate::config::digest::VerifyDigest,
+                crate::config::PoolPartition,
+                crate::config::MaxDecompressedSize,
+                crate::config::MaxDecompressionRatio,
+                crate::config::UserAgent,
                 crate::config::EnableMetrics,
+                crate::config::EnableConnectionReport,
+                crate::config::ErrorBodyLimit,
+                crate::config::StrictValidation,
+                crate::config::MaxHeaderListSize,
+                crate::config::MaxUriLength,
+                crate::config::ResponseBufferSize,
+                crate::hsts::HstsStore,
                 crate::config::IpVersion,
+                crate::config::RetryStaleConnection,
+                crate::config::HeaderOrder,
+                crate::config::Profile,
+                Tags,
+                Route,
             ]
         );
 
+        #[cfg(feature = "spill-to-disk")]
+        try_clone_extension!(self.extensions(), builder, [crate::config::SpillToDisk,]);
+
+        #[cfg(feature = "tls-psk")]
+        try_clone_extension!(self.extensions(), builder, [crate::config::PreSharedKey,]);
+
         builder
     }
 
+    fn tag<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        if self.extensions().get::<Tags>().is_none() {
+            self.extensions_mut().insert(Tags::default());
+        }
+
+        if let Some(tags) = self.extensions_mut().get_mut::<Tags>() {
+            tags.0.insert(key.into(), value.into());
+        }
+
+        self
+    }
+
+    fn route(mut self, template: impl Into<String>) -> Self {
+        self.extensions_mut().insert(Route(template.into()));
+        self
+    }
+
+    fn format(&mut self) -> Format<'_>
+    where
+        T: PeekBody,
+    {
+        Format::request(self)
+    }
+
+    fn to_curl_command(&self) -> String
+    where
+        T: PeekBody,
+    {
+        crate::curl_command::to_curl_command(self)
+    }
+
     fn send(self) -> Result<Response<Body>, Error>
     where
         T: Into<Body>,
@@ -120,6 +327,122 @@ impl<T> RequestExt<T> for Request<T> {
     }
 }
 
+/// Extension methods on an HTTP request builder.
+pub trait RequestBuilderExt {
+    /// Add a media range to the `Accept` header of the request with the given
+    /// quality value.
+    ///
+    /// This can be called multiple times to list several acceptable media
+    /// ranges, which are joined into a single `Accept` header in the order
+    /// given. The quality value `q` indicates the relative preference for the
+    /// media range and is clamped to the range `0.0` to `1.0`; a value of `1.0`
+    /// is omitted from the header since it is the default.
+    ///
+    /// The representation chosen by the server can be found using
+    /// [`ResponseExt::content_type`](crate::ResponseExt::content_type).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// // Sends `Accept: application/json, text/plain;q=0.5`.
+    /// let response = Request::get("https://httpbin.org/get")
+    ///     .accept("application/json", 1.0)
+    ///     .accept("text/plain", 0.5)
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// if let Some(content_type) = response.content_type() {
+    ///     println!("got {}", content_type.essence_str());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn accept(self, media_range: &str, q: f32) -> Self;
+
+    /// Indicate that JSON responses are accepted by adding `application/json`
+    /// to the `Accept` header of the request.
+    ///
+    /// This is equivalent to `accept("application/json", 1.0)`.
+    fn accept_json(self) -> Self;
+
+    /// Set the `User-Agent` header of the request, overriding the user agent
+    /// configured for the client.
+    ///
+    /// The same version tokens as in
+    /// [`HttpClientBuilder::user_agent`](crate::HttpClientBuilder::user_agent)
+    /// are supported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use isahc::prelude::*;
+    ///
+    /// let request = Request::get("https://example.org")
+    ///     .user_agent("my-crawler/2.0 curl/{curl_version}")
+    ///     .body(())?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn user_agent(self, template: &str) -> Self;
+
+    /// Do not send a `User-Agent` header with this request, even if the client
+    /// is configured with one.
+    fn no_user_agent(self) -> Self;
+}
+
+impl RequestBuilderExt for http::request::Builder {
+    fn accept(mut self, media_range: &str, q: f32) -> Self {
+        let mut value = media_range.to_owned();
+
+        // This comparison also maps NaN to zero.
+        let q = if q > 0.0 { q } else { 0.0 };
+
+        // Format the quality value with at most three decimal places, as
+        // required by RFC 7231.
+        if q < 1.0 {
+            let formatted = format!("{:.3}", q);
+
+            value.push_str(";q=");
+            value.push_str(formatted.trim_end_matches('0').trim_end_matches('.'));
+        }
+
+        // Append to any existing value so that only one header is sent.
+        if let Some(headers) = self.headers_mut() {
+            if let Some(existing) = headers.remove(http::header::ACCEPT) {
+                if let Ok(existing) = existing.to_str() {
+                    value = format!("{}, {}", existing, value);
+                }
+            }
+        }
+
+        self.header(http::header::ACCEPT, value)
+    }
+
+    fn accept_json(self) -> Self {
+        self.accept("application/json", 1.0)
+    }
+
+    fn user_agent(self, template: &str) -> Self {
+        self.header(http::header::USER_AGENT, UserAgent::expand(template))
+    }
+
+    fn no_user_agent(mut self) -> Self {
+        if let Some(headers) = self.headers_mut() {
+            headers.remove(http::header::USER_AGENT);
+        }
+
+        self.extension(UserAgent(None))
+    }
+}
+
+/// Extension containing the tags attached to a request.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Tags(pub(crate) BTreeMap<String, String>);
+
+/// Extension containing the route template of a request.
+#[derive(Clone, Debug)]
+pub(crate) struct Route(pub(crate) String);
+
 impl Configurable for http::request::Builder {}
 
 impl ConfigurableBase for http::request::Builder {
@@ -127,3 +450,41 @@ impl ConfigurableBase for http::request::Builder {
         self.extension(option)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1.0, "text/html")]
+    #[test_case(7.0, "text/html")]
+    #[test_case(0.5, "text/html;q=0.5")]
+    #[test_case(0.123_45, "text/html;q=0.123")]
+    #[test_case(0.0, "text/html;q=0")]
+    #[test_case(-1.0, "text/html;q=0")]
+    #[test_case(f32::NAN, "text/html;q=0")]
+    fn accept_formats_quality_value(q: f32, expected: &str) {
+        let request = Request::builder().accept("text/html", q).body(()).unwrap();
+
+        assert_eq!(request.headers()[http::header::ACCEPT], expected);
+    }
+
+    #[test]
+    fn accept_joins_media_ranges() {
+        let request = Request::builder()
+            .accept_json()
+            .accept("text/*", 0.8)
+            .accept("*/*", 0.1)
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            request.headers().get_all(http::header::ACCEPT).iter().count(),
+            1
+        );
+        assert_eq!(
+            request.headers()[http::header::ACCEPT],
+            "application/json, text/*;q=0.8, */*;q=0.1"
+        );
+    }
+}
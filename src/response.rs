@@ -1,4 +1,22 @@
-use crate::{metrics::Metrics, redirect::EffectiveUri};
+use crate::{
+    config::ErrorBodyLimit,
+    connection::ConnectionReport,
+    date,
+    download::DownloadedFile,
+    error::Error,
+    format::Format,
+    headers::{self, HasHeaders},
+    heartbeat::Heartbeats,
+    intermediary::{parse_proxy_status, parse_via, AltUsed, ProxyStatus, Via},
+    link::{parse_links, Link},
+    metrics::Metrics,
+    multipart::Multipart,
+    push::PushedResponses,
+    range::{ContentRange, RangeOutcome, RequestedRange},
+    redirect::{EffectiveUri, RedirectHop, RedirectHops},
+    request::{Route, Tags},
+    split::Split,
+};
 use futures_lite::io::{AsyncRead, AsyncWrite};
 use http::{Response, Uri};
 use std::{
@@ -9,6 +27,7 @@ use std::{
     path::Path,
     pin::Pin,
     task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 /// Provides extension methods for working with HTTP responses.
@@ -61,6 +80,20 @@ pub trait ResponseExt<T> {
     #[cfg(feature = "cookies")]
     fn cookie_jar(&self) -> Option<&crate::cookies::CookieJar>;
 
+    /// Get whether this response was served from the client's cache, if the
+    /// client has one.
+    ///
+    /// Returns `None` if the request was not sent through a client with a
+    /// cache configured with
+    /// [`HttpClientBuilder::cache`](crate::HttpClientBuilder::cache).
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`http-cache`](index.html#http-cache) feature is enabled.
+    #[cfg(feature = "http-cache")]
+    fn cache_status(&self) -> Option<crate::cache::CacheStatus>;
+
     /// If request metrics are enabled for this particular transfer, return a
     /// metrics object containing a live view of currently available data.
     ///
@@ -68,6 +101,446 @@ pub trait ResponseExt<T> {
     /// metrics you can use
     /// [`Configurable::metrics`](crate::config::Configurable::metrics).
     fn metrics(&self) -> Option<&Metrics>;
+
+    /// If connection reports are enabled for this particular transfer, return
+    /// a report describing how the connection used for the request was
+    /// established.
+    ///
+    /// By default connection reports are disabled and `None` will be returned.
+    /// To enable them you can use
+    /// [`Configurable::connection_report`](crate::config::Configurable::connection_report).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = Request::get("https://example.org")
+    ///     .connection_report(true)
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// if let Some(report) = response.connection_report() {
+    ///     println!("connected to {:?} using {:?}", report.chosen_ip(), report.tls_protocol());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn connection_report(&self) -> Option<&ConnectionReport>;
+
+    /// Get the responses pushed by the server along with this response.
+    ///
+    /// This returns `None` unless pushed responses were enabled with
+    /// [`HttpClientBuilder::enable_push`](crate::HttpClientBuilder::enable_push),
+    /// in which case responses may be pushed if HTTP/2 is used.
+    fn pushed_responses(&self) -> Option<&PushedResponses>;
+
+    /// Get the redirects that were followed before this response was received,
+    /// in the order they were followed.
+    ///
+    /// Each hop records the URI that was requested, the redirect status
+    /// returned, and if metrics are enabled, the timings for that hop. This
+    /// makes it possible to find out which part of a slow redirect chain is
+    /// responsible for the delay.
+    ///
+    /// Returns an empty slice if no redirects were followed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{config::RedirectPolicy, prelude::*};
+    ///
+    /// let response = Request::get("https://httpbin.org/redirect/3")
+    ///     .redirect_policy(RedirectPolicy::Follow)
+    ///     .metrics(true)
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// for hop in response.redirect_hops() {
+    ///     println!("{} {}: {:?}", hop.status(), hop.uri(), hop.metrics());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn redirect_hops(&self) -> &[RedirectHop];
+
+    /// Get the value of a tag attached to the request that produced this
+    /// response using [`RequestExt::tag`](crate::RequestExt::tag), if any.
+    fn tag(&self, key: &str) -> Option<&str>;
+
+    /// Get the route template of the request that produced this response, as
+    /// set using [`RequestExt::route`](crate::RequestExt::route), if any.
+    fn route(&self) -> Option<&str>;
+
+    /// Get the content type of the response as determined by sniffing the
+    /// first bytes of the response body.
+    ///
+    /// Sniffing only takes place if the server did not declare a specific
+    /// content type, which is if the `Content-Type` header is missing or set to
+    /// `application/octet-stream`. This is useful for tools such as download
+    /// managers that need to pick a file type for whatever they receive. If
+    /// the server did declare a content type, or if the response has no body,
+    /// then `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://example.org/download")?;
+    ///
+    /// // Prefer the sniffed type, since it is only present if the declared
+    /// // type was not useful.
+    /// let content_type = response.sniffed_content_type().or_else(|| {
+    ///     response
+    ///         .headers()
+    ///         .get("content-type")
+    ///         .and_then(|v| v.to_str().ok())
+    /// });
+    ///
+    /// println!("content type: {:?}", content_type);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`mime-sniff`](index.html#mime-sniff) feature is enabled.
+    #[cfg(feature = "mime-sniff")]
+    fn sniffed_content_type(&self) -> Option<&str>;
+
+    /// Get the URI of the next page of a paginated resource, as indicated by a
+    /// `Link` header with a relation type of `next`.
+    ///
+    /// Many REST APIs paginate large collections this way, as described in
+    /// [RFC 8288](https://tools.ietf.org/html/rfc8288). Relative links are
+    /// resolved against the [effective URI](ResponseExt::effective_uri) of the
+    /// response. Returns `None` if there is no next page.
+    ///
+    /// To fetch all pages of a resource, see also
+    /// [`HttpClient::paginate`](crate::HttpClient::paginate).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://api.github.com/repos/sagebind/isahc/issues")?;
+    ///
+    /// if let Some(uri) = response.next_page_uri() {
+    ///     println!("next page: {}", uri);
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn next_page_uri(&self) -> Option<Uri>;
+
+    /// Get the links to other resources listed in the `Link` headers of the
+    /// response, in the order they appear in.
+    ///
+    /// Hypermedia APIs use links described in [RFC
+    /// 8288](https://tools.ietf.org/html/rfc8288) to point to related
+    /// resources, such as other pages of a collection or alternate
+    /// representations. Relative link targets are resolved against the
+    /// [effective URI](ResponseExt::effective_uri) of the response. Malformed
+    /// links are skipped.
+    ///
+    /// Returns an empty list if the header is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://api.github.com/repos/sagebind/isahc/issues")?;
+    ///
+    /// for link in response.links() {
+    ///     println!("{:?} -> {}", link.relations(), link.target());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn links(&self) -> Vec<Link>;
+
+    /// Get all values of a response header, with comma-separated lists split
+    /// into their individual elements.
+    ///
+    /// A header may be sent several times, and each value may itself be a
+    /// comma-separated list, so `Vary: accept` followed by `Vary: origin,
+    /// accept-encoding` describes three values. Commas inside quoted strings
+    /// and inside the `<...>` of a `Link` do not separate elements. Headers
+    /// that are not lists but can contain commas, such as `Set-Cookie` and
+    /// dates, are never split.
+    ///
+    /// Values that are not valid UTF-8 are skipped. Returns an empty list if
+    /// the header is not present.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://example.org")?;
+    ///
+    /// for value in response.header_values("vary") {
+    ///     println!("varies on {}", value);
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn header_values(&self, name: &str) -> Vec<&str>;
+
+    /// Get how long the server asked the client to wait before making another
+    /// request, as indicated by the `Retry-After` header.
+    ///
+    /// Servers commonly send this header with a `429 Too Many Requests` or
+    /// `503 Service Unavailable` response. The header may contain either a
+    /// number of seconds or an HTTP date, and both are converted into a
+    /// duration to wait. A date is compared to the `Date` header of the
+    /// response if present, so that a difference between the clocks of the
+    /// client and the server does not affect the delay. A date in the past
+    /// results in a delay of zero.
+    ///
+    /// Returns `None` if the header is missing or cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut response = isahc::get("https://httpbin.org/status/429")?;
+    ///
+    /// if response.status() == 429 {
+    ///     let delay = response.retry_after().unwrap_or(Duration::from_secs(1));
+    ///     thread::sleep(delay);
+    ///     response = isahc::get("https://httpbin.org/status/429")?;
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn retry_after(&self) -> Option<Duration>;
+
+    /// Get the time at which the response was generated by the server, as
+    /// indicated by the `Date` header.
+    ///
+    /// Returns `None` if the header is missing or cannot be parsed.
+    fn date(&self) -> Option<SystemTime>;
+
+    /// Get the time after which the response is considered stale, as
+    /// indicated by the `Expires` header.
+    ///
+    /// An invalid date, such as the commonly used `0`, means that the response
+    /// has already expired, and is returned as the Unix epoch. Note that a
+    /// `max-age` directive in the `Cache-Control` header takes precedence over
+    /// this header.
+    ///
+    /// Returns `None` if the header is missing.
+    fn expires(&self) -> Option<SystemTime>;
+
+    /// Get the current age of the response, which is the time that has passed
+    /// since the response was generated by the server or successfully
+    /// validated with it.
+    ///
+    /// The age is computed as described in [RFC 7234, section
+    /// 4.2.3](https://tools.ietf.org/html/rfc7234#section-4.2.3), from the
+    /// `Age` and `Date` headers and the times at which the request was sent and
+    /// the response was received. This accounts for time that the response
+    /// spent in caches along the way, and keeps increasing while the response
+    /// is held onto.
+    ///
+    /// For responses that were not produced by an HTTP client, the age is
+    /// estimated from the headers alone. Returns `None` if neither the `Age`
+    /// nor the `Date` header is present in such a response.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://example.org")?;
+    ///
+    /// if let Some(age) = response.age() {
+    ///     println!("response is {} seconds old", age.as_secs());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn age(&self) -> Option<Duration>;
+
+    /// Get the intermediaries that forwarded this response, as listed in the
+    /// `Via` header.
+    ///
+    /// Each proxy or gateway that forwards a response appends itself to the
+    /// list, so the first entry is the intermediary closest to the server and
+    /// the last one is closest to the client. Proxies that do not add
+    /// themselves, including the proxy configured for the client in many
+    /// cases, are not listed. To check whether a proxy was used to connect to
+    /// the server, see [`ConnectionReport::proxy_used`].
+    ///
+    /// Returns an empty list if the header is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = isahc::get("https://example.org")?;
+    ///
+    /// for via in response.via() {
+    ///     println!("forwarded by {} using {}", via.received_by(), via.protocol());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn via(&self) -> Vec<Via>;
+
+    /// Get the status reported by the proxies that handled this response, as
+    /// listed in the `Proxy-Status` header.
+    ///
+    /// This explains why a proxy generated an error response instead of
+    /// forwarding one from the server, such as a failed DNS lookup or a
+    /// timeout while connecting to the next hop. The first entry is the proxy
+    /// closest to the server.
+    ///
+    /// Returns an empty list if the header is missing.
+    fn proxy_status(&self) -> Vec<ProxyStatus>;
+
+    /// Get the alternative service that the request was sent to, as indicated
+    /// by the `Alt-Used` header of the request, such as `alt.example.org:443`.
+    ///
+    /// Clients send this header when connecting to an alternative service
+    /// advertised by the server with the `Alt-Svc` header instead of the host
+    /// in the request URI. libcurl does not report which service it connected
+    /// to, so this is only available when the header was set on the request,
+    /// for example by an [interceptor](crate::interceptor).
+    fn alt_used(&self) -> Option<&str>;
+
+    /// Get the media type of the response body, as declared by the server in
+    /// the `Content-Type` header.
+    ///
+    /// This is useful for finding out which representation the server chose
+    /// when requesting several with
+    /// [`RequestBuilderExt::accept`](crate::RequestBuilderExt::accept).
+    /// Returns `None` if the header is missing or cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let response = Request::get("https://httpbin.org/json")
+    ///     .accept_json()
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// let content_type = response.content_type().unwrap();
+    /// assert_eq!(content_type.essence_str(), "application/json");
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn content_type(&self) -> Option<mime::Mime>;
+
+    /// Get the range of bytes contained in the response body, as described by
+    /// the `Content-Range` header.
+    ///
+    /// Returns `None` if the header is missing or cannot be parsed. This is
+    /// also available on the parts of a `multipart/byteranges` response read
+    /// with [`ReadResponseExt::multipart`].
+    fn content_range(&self) -> Option<ContentRange>;
+
+    /// Check whether the server honored the `Range` header of the request.
+    ///
+    /// A server may ignore the requested range and send the entire
+    /// representation with a `200 OK` status instead, or send a different
+    /// range than the one requested. When resuming a download, writing such a
+    /// body at the offset the download stopped at would corrupt the file, so
+    /// the outcome should always be checked first. See [`RangeOutcome`] for
+    /// the possible outcomes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::{prelude::*, RangeOutcome};
+    /// use std::fs::OpenOptions;
+    ///
+    /// let mut file = OpenOptions::new().append(true).open("large.iso")?;
+    /// let offset = file.metadata()?.len();
+    ///
+    /// let mut response = Request::get("https://example.org/large.iso")
+    ///     .header("range", format!("bytes={}-", offset))
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// match response.range_outcome() {
+    ///     RangeOutcome::Partial(_) => {
+    ///         response.copy_to(&mut file)?;
+    ///     }
+    ///     RangeOutcome::NotSatisfiable(Some(len)) if len == offset => {
+    ///         println!("already complete");
+    ///     }
+    ///     outcome => {
+    ///         eprintln!("cannot resume: {:?}", outcome);
+    ///         response.copy_to_file("large.iso")?;
+    ///     }
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn range_outcome(&self) -> RangeOutcome;
+
+    /// Check whether the response body was automatically decompressed.
+    ///
+    /// Returns true if the server sent the body compressed according to the
+    /// `Content-Encoding` header and it was decoded because
+    /// [automatic decompression](crate::config::Configurable::decompress) was
+    /// enabled. The `Content-Encoding` and `Content-Length` headers of the
+    /// response still describe the body as it was sent by the server.
+    fn is_decoded(&self) -> bool;
+
+    /// Get a mutable reference to the response body exactly as it was sent by
+    /// the server, if it has not been decoded.
+    ///
+    /// This returns `None` if the body was automatically decompressed. To
+    /// receive the raw compressed bytes of a response, for example to proxy it
+    /// or to verify a checksum of the encoded form, disable decompression for
+    /// the request with
+    /// [`Configurable::decompress`](crate::config::Configurable::decompress).
+    ///
+    /// Note that the transfer encoding of the response, such as chunked
+    /// encoding, is always removed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    /// use std::io;
+    ///
+    /// let mut response = Request::get("https://example.org")
+    ///     .header("Accept-Encoding", "gzip")
+    ///     .decompress(false)
+    ///     .body(())?
+    ///     .send()?;
+    ///
+    /// if let Some(body) = response.raw_body() {
+    ///     io::copy(body, &mut io::sink())?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn raw_body(&mut self) -> Option<&mut T>;
+
+    /// Format the response for debugging.
+    ///
+    /// Only the status line is shown by default. The returned [`Format`] can
+    /// be used to include the headers and a preview of the body as well, with
+    /// the values of sensitive headers redacted. Previewing the body does not
+    /// consume it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let mut response = isahc::get("https://example.org")?;
+    ///
+    /// eprintln!("{}", response.format().with_headers().with_body_preview(512));
+    ///
+    /// let text = response.text()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn format(&mut self) -> Format<'_>
+    where
+        T: PeekBody;
 }
 
 impl<T> ResponseExt<T> for Response<T> {
@@ -88,9 +561,163 @@ impl<T> ResponseExt<T> for Response<T> {
         self.extensions().get()
     }
 
+    #[cfg(feature = "http-cache")]
+    fn cache_status(&self) -> Option<crate::cache::CacheStatus> {
+        self.extensions().get().copied()
+    }
+
     fn metrics(&self) -> Option<&Metrics> {
         self.extensions().get()
     }
+
+    fn connection_report(&self) -> Option<&ConnectionReport> {
+        self.extensions().get()
+    }
+
+    fn pushed_responses(&self) -> Option<&PushedResponses> {
+        self.extensions().get()
+    }
+
+    fn redirect_hops(&self) -> &[RedirectHop] {
+        self.extensions()
+            .get::<RedirectHops>()
+            .map(|v| v.0.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.extensions()
+            .get::<Tags>()
+            .and_then(|tags| tags.0.get(key))
+            .map(String::as_str)
+    }
+
+    fn route(&self) -> Option<&str> {
+        self.extensions().get::<Route>().map(|route| route.0.as_str())
+    }
+
+    #[cfg(feature = "mime-sniff")]
+    fn sniffed_content_type(&self) -> Option<&str> {
+        self.extensions()
+            .get::<crate::sniff::SniffedContentType>()
+            .map(|v| v.0)
+    }
+
+    fn next_page_uri(&self) -> Option<Uri> {
+        self.links()
+            .into_iter()
+            .find(|link| link.has_relation("next"))?
+            .uri()
+            .cloned()
+    }
+
+    fn links(&self) -> Vec<Link> {
+        let base = self.effective_uri();
+
+        self.headers()
+            .get_all(http::header::LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| parse_links(value, base))
+            .collect()
+    }
+
+    fn header_values(&self, name: &str) -> Vec<&str> {
+        headers::values(self.headers(), name)
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        headers::retry_after(self.headers())
+    }
+
+    fn date(&self) -> Option<SystemTime> {
+        headers::date(self.headers(), http::header::DATE)
+    }
+
+    fn expires(&self) -> Option<SystemTime> {
+        self.headers().get(http::header::EXPIRES).map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(date::parse)
+                .unwrap_or(UNIX_EPOCH)
+        })
+    }
+
+    fn age(&self) -> Option<Duration> {
+        headers::age(
+            self.headers(),
+            self.extensions().get::<ResponseTimes>(),
+            SystemTime::now(),
+        )
+    }
+
+    fn via(&self) -> Vec<Via> {
+        self.headers()
+            .get_all(http::header::VIA)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_via)
+            .collect()
+    }
+
+    fn proxy_status(&self) -> Vec<ProxyStatus> {
+        self.headers()
+            .get_all("proxy-status")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_proxy_status)
+            .collect()
+    }
+
+    fn alt_used(&self) -> Option<&str> {
+        self.extensions().get::<AltUsed>().map(|v| v.0.as_str())
+    }
+
+    fn content_type(&self) -> Option<mime::Mime> {
+        self.headers()
+            .get(http::header::CONTENT_TYPE)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn content_range(&self) -> Option<ContentRange> {
+        crate::range::content_range(self.headers())
+    }
+
+    fn range_outcome(&self) -> RangeOutcome {
+        let requested = self.extensions().get::<RequestedRange>();
+
+        RangeOutcome::new(
+            requested.map(|range| range.0.as_str()),
+            self.status(),
+            self.headers(),
+        )
+    }
+
+    fn is_decoded(&self) -> bool {
+        self.extensions()
+            .get::<ContentDecoded>()
+            .map(|v| v.0)
+            .unwrap_or(false)
+    }
+
+    fn raw_body(&mut self) -> Option<&mut T> {
+        if self.is_decoded() {
+            None
+        } else {
+            Some(self.body_mut())
+        }
+    }
+
+    fn format(&mut self) -> Format<'_>
+    where
+        T: PeekBody,
+    {
+        Format::response(self)
+    }
 }
 
 /// Provides extension methods for consuming HTTP response streams.
@@ -113,6 +740,31 @@ pub trait ReadResponseExt<T: Read> {
     /// ```
     fn copy_to<W: Write>(&mut self, writer: W) -> io::Result<u64>;
 
+    /// Read up to `len` bytes from the start of the response body without
+    /// consuming them.
+    ///
+    /// The bytes are buffered internally, so the entire response body can
+    /// still be read afterwards, including the bytes returned here. Fewer than
+    /// `len` bytes are returned only if the body ends first. This is useful
+    /// for sniffing the content of a response before deciding how to process
+    /// it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let mut response = isahc::get("https://example.org")?;
+    ///
+    /// if response.peek(15)?.starts_with(b"<!doctype html>") {
+    ///     println!("{}", response.text()?);
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn peek(&mut self, len: usize) -> io::Result<&[u8]>
+    where
+        T: PeekBody;
+
     /// Write the response body to a file.
     ///
     /// This method makes it convenient to download a file using a GET request
@@ -133,6 +785,31 @@ pub trait ReadResponseExt<T: Read> {
         File::create(path).and_then(|f| self.copy_to(f))
     }
 
+    /// Write the response body to a file that is preallocated to the length
+    /// of the body.
+    ///
+    /// If the response has a `Content-Length` header, the file is sized to
+    /// that length before any data is written, which reduces fragmentation
+    /// when downloading very large files. The body is then written using
+    /// positioned writes. If the body turns out to be shorter than announced,
+    /// the file is truncated to the number of bytes actually received.
+    ///
+    /// If the length of the body is not known in advance, this behaves the
+    /// same as [`copy_to_file`](ReadResponseExt::copy_to_file).
+    ///
+    /// Returns the number of bytes that were written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// isahc::get("https://example.org/large.iso")?
+    ///     .copy_to_file_preallocated("large.iso")?;
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn copy_to_file_preallocated<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64>;
+
     /// Read the response body as a string.
     ///
     /// The encoding used to decode the response body into a string depends on
@@ -167,6 +844,63 @@ pub trait ReadResponseExt<T: Read> {
     #[cfg(feature = "text-decoding")]
     fn text(&mut self) -> io::Result<String>;
 
+    /// Read the response body as a string, decoding it using the given
+    /// character encoding instead of the one indicated by the response.
+    ///
+    /// This is useful when a server is known to mislabel the encoding of its
+    /// responses. The encoding is given as a label as used in the `charset`
+    /// parameter of a `Content-Type` header, such as `"iso-8859-1"` or
+    /// `"shift_jis"`. An error is returned if the label is not recognized.
+    ///
+    /// This method consumes the entire response body stream and can only be
+    /// called once.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`text-decoding`](index.html#text-decoding) feature is enabled, which it
+    /// is by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let text = isahc::get("https://example.org")?.text_with_charset("iso-8859-1")?;
+    /// println!("{}", text);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[cfg(feature = "text-decoding")]
+    fn text_with_charset(&mut self, charset: &str) -> io::Result<String>;
+
+    /// Read the beginning of the response body as a string, stopping once at
+    /// most `limit` bytes of text have been decoded.
+    ///
+    /// Decoding works the same as with [`text`](ReadResponseExt::text),
+    /// including replacing malformed characters, but the body is only read as
+    /// far as needed to produce the requested amount of text. The result is
+    /// truncated on a character boundary, so it may be slightly shorter than
+    /// the limit. This protects against huge or mislabeled response bodies
+    /// when only a preview of the text is needed.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`text-decoding`](index.html#text-decoding) feature is enabled, which it
+    /// is by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let preview = isahc::get("https://example.org")?.text_lossy_limit(100)?;
+    /// println!("{}...", preview);
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    #[cfg(feature = "text-decoding")]
+    fn text_lossy_limit(&mut self, limit: usize) -> io::Result<String>;
+
     /// Deserialize the response body as JSON into a given type.
     ///
     /// # Availability
@@ -188,6 +922,57 @@ pub trait ReadResponseExt<T: Read> {
     fn json<D>(&mut self) -> Result<D, serde_json::Error>
     where
         D: serde::de::DeserializeOwned;
+
+    /// Return an error if the response has a client error (4xx) or server
+    /// error (5xx) status code.
+    ///
+    /// The start of the response body is read and included in the error, as
+    /// the reason for an error is often described there. Use
+    /// [`Error::response_body`] to get it. The number of bytes read can be
+    /// configured with
+    /// [`Configurable::error_body_limit`](crate::config::Configurable::error_body_limit).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let mut response = isahc::get("https://httpbin.org/status/404")?;
+    ///
+    /// if let Err(e) = response.error_for_status() {
+    ///     eprintln!("request failed: {} {:?}", e, e.response_body());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn error_for_status(&mut self) -> Result<(), Error>;
+
+    /// Read the response body as a multipart message, such as a
+    /// `multipart/byteranges` response to a request for several ranges or a
+    /// `multipart/mixed` response of a batch API.
+    ///
+    /// Returns an error of kind [`InvalidData`](io::ErrorKind::InvalidData) if
+    /// the `Content-Type` of the response is not a multipart type with a
+    /// boundary. See [`Multipart`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// let mut response = Request::get("https://example.org/file.pdf")
+    ///     .header("range", "bytes=0-99,500-599")
+    ///     .body(())?
+    ///     .send()?;
+    /// let mut multipart = response.multipart()?;
+    ///
+    /// while let Some(mut part) = multipart.next_part()? {
+    ///     let mut bytes = Vec::new();
+    ///     part.copy_to(&mut bytes)?;
+    ///     println!("{:?}: {} bytes", part.headers().get("content-range"), bytes.len());
+    /// }
+    /// # Ok::<(), isahc::Error>(())
+    /// ```
+    fn multipart(&mut self) -> io::Result<Multipart<&mut T>>;
 }
 
 impl<T: Read> ReadResponseExt<T> for Response<T> {
@@ -195,11 +980,61 @@ impl<T: Read> ReadResponseExt<T> for Response<T> {
         io::copy(self.body_mut(), &mut writer)
     }
 
+    fn peek(&mut self, len: usize) -> io::Result<&[u8]>
+    where
+        T: PeekBody,
+    {
+        self.body_mut().peek_body(len)
+    }
+
+    fn copy_to_file_preallocated<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64> {
+        // Content-Length describes the encoded body, so it cannot be used if
+        // the body is being decoded.
+        let len = match self.content_length().filter(|_| !self.is_decoded()) {
+            Some(len) => len,
+            None => return self.copy_to_file(path),
+        };
+
+        let file = DownloadedFile::create(path, len)?;
+        let mut buf = vec![0; 64 * 1024];
+        let mut offset = 0;
+
+        loop {
+            let n = match self.body_mut().read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            file.write_at(offset, &buf[..n])?;
+            offset += n as u64;
+        }
+
+        file.finish_with_len(offset)?;
+
+        Ok(offset)
+    }
+
     #[cfg(feature = "text-decoding")]
     fn text(&mut self) -> io::Result<String> {
         crate::text::Decoder::for_response(&self).decode_reader(self.body_mut())
     }
 
+    #[cfg(feature = "text-decoding")]
+    fn text_with_charset(&mut self, charset: &str) -> io::Result<String> {
+        crate::text::Decoder::for_label(charset)?
+            .with_metrics(self.metrics())
+            .decode_reader(self.body_mut())
+    }
+
+    #[cfg(feature = "text-decoding")]
+    fn text_lossy_limit(&mut self, limit: usize) -> io::Result<String> {
+        crate::text::Decoder::for_response(self)
+            .with_limit(limit)
+            .decode_reader(self.body_mut())
+    }
+
     #[cfg(feature = "json")]
     fn json<D>(&mut self) -> Result<D, serde_json::Error>
     where
@@ -207,6 +1042,126 @@ impl<T: Read> ReadResponseExt<T> for Response<T> {
     {
         serde_json::from_reader(self.body_mut())
     }
+
+    fn error_for_status(&mut self) -> Result<(), Error> {
+        if !is_error_status(self) {
+            return Ok(());
+        }
+
+        let limit = error_body_limit(self);
+
+        #[cfg(feature = "text-decoding")]
+        let body = self.text_lossy_limit(limit)?;
+
+        #[cfg(not(feature = "text-decoding"))]
+        let body = {
+            let mut buf = Vec::new();
+            self.body_mut().take(limit as u64).read_to_end(&mut buf)?;
+            String::from_utf8_lossy(&buf).into_owned()
+        };
+
+        Err(Error::from_status(self.status(), body))
+    }
+
+    fn multipart(&mut self) -> io::Result<Multipart<&mut T>> {
+        let boundary = crate::multipart::boundary(self.headers()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response is not a multipart message",
+            )
+        })?;
+
+        let status = self.status();
+        let version = self.version();
+
+        Ok(Multipart::new(self.body_mut(), boundary, status, version))
+    }
+}
+
+/// Helper trait for body types that support peeking at their contents.
+///
+/// This trait is sealed and cannot be implemented for types outside of Isahc.
+pub trait PeekBody {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]>;
+
+    /// Get the entire remaining body without reading from it, or `None` if it
+    /// is not stored in memory.
+    fn buffered_body(&self) -> Option<&[u8]>;
+}
+
+impl PeekBody for crate::Body {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        self.peek(len)
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        self.peek_buffered(usize::MAX)
+    }
+}
+
+impl PeekBody for crate::AsyncBody {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        self.peek_buffered(len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "asynchronous body is not stored in memory",
+            )
+        })
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        self.peek_buffered(usize::MAX)
+    }
+}
+
+impl PeekBody for () {
+    fn peek_body(&mut self, _len: usize) -> io::Result<&[u8]> {
+        Ok(&[])
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        Some(&[])
+    }
+}
+
+impl PeekBody for Vec<u8> {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        Ok(&self[..len.min(self.len())])
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+impl PeekBody for String {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        Ok(&self.as_bytes()[..len.min(self.len())])
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        Some(self.as_bytes())
+    }
+}
+
+impl PeekBody for &[u8] {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        Ok(&self[..len.min(self.len())])
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+impl PeekBody for &str {
+    fn peek_body(&mut self, len: usize) -> io::Result<&[u8]> {
+        Ok(&self.as_bytes()[..len.min(self.len())])
+    }
+
+    fn buffered_body(&self) -> Option<&[u8]> {
+        Some(self.as_bytes())
+    }
 }
 
 /// Provides extension methods for consuming asynchronous HTTP response streams.
@@ -231,7 +1186,8 @@ pub trait AsyncReadResponseExt<T: AsyncRead + Unpin> {
     /// ```
     fn copy_to<'a, W>(&'a mut self, writer: W) -> CopyFuture<'a>
     where
-        W: AsyncWrite + Unpin + 'a;
+        T: Send,
+        W: AsyncWrite + Unpin + Send + 'a;
 
     /// Read the response body as a string asynchronously.
     ///
@@ -257,12 +1213,120 @@ pub trait AsyncReadResponseExt<T: AsyncRead + Unpin> {
     /// ```
     #[cfg(feature = "text-decoding")]
     fn text(&mut self) -> crate::text::TextFuture<'_, &mut T>;
+
+    /// Read the response body as a string asynchronously, decoding it using
+    /// the given character encoding instead of the one indicated by the
+    /// response.
+    ///
+    /// See [`ReadResponseExt::text_with_charset`] for details.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`text-decoding`](index.html#text-decoding) feature is enabled, which it
+    /// is by default.
+    #[cfg(feature = "text-decoding")]
+    fn text_with_charset(&mut self, charset: &str) -> crate::text::TextFuture<'_, &mut T>;
+
+    /// Read the beginning of the response body as a string asynchronously,
+    /// stopping once at most `limit` bytes of text have been decoded.
+    ///
+    /// See [`ReadResponseExt::text_lossy_limit`] for details.
+    ///
+    /// # Availability
+    ///
+    /// This method is only available when the
+    /// [`text-decoding`](index.html#text-decoding) feature is enabled, which it
+    /// is by default.
+    #[cfg(feature = "text-decoding")]
+    fn text_lossy_limit(&mut self, limit: usize) -> crate::text::TextFuture<'_, &mut T>;
+
+    /// Return an error if the response has a client error (4xx) or server
+    /// error (5xx) status code.
+    ///
+    /// This is the asynchronous equivalent of
+    /// [`ReadResponseExt::error_for_status`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use isahc::prelude::*;
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let mut response = isahc::get_async("https://httpbin.org/status/404").await?;
+    ///
+    /// if let Err(e) = response.error_for_status().await {
+    ///     eprintln!("request failed: {} {:?}", e, e.response_body());
+    /// }
+    /// # Ok(()) }
+    /// ```
+    fn error_for_status(&mut self) -> ErrorForStatusFuture<'_>;
+
+    /// Get a stream of the records in the response body that are separated by
+    /// the given delimiter byte, such as the lines of a CSV file or of
+    /// newline-delimited JSON.
+    ///
+    /// Records are yielded without the delimiter as soon as they have been
+    /// received, so long-lived streaming responses can be processed
+    /// incrementally. Records larger than 1 MiB produce an error by default;
+    /// see [`Split`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures_lite::stream::StreamExt;
+    /// use isahc::prelude::*;
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let mut response = isahc::get_async("https://example.org/events.ndjson").await?;
+    /// let mut records = response.split(b'\n').max_record_size(64 * 1024);
+    ///
+    /// while let Some(record) = records.next().await {
+    ///     println!("{}", String::from_utf8_lossy(&record?));
+    /// }
+    /// # Ok(()) }
+    /// ```
+    fn split(&mut self, delimiter: u8) -> Split<'_, T>;
+
+    /// Get a stream of the lines in the response body that tells heartbeats
+    /// sent to keep the connection alive apart from data, and that reports
+    /// when nothing has been received for the given idle timeout.
+    ///
+    /// This makes it possible to tell a connection that is alive but has no
+    /// data to send apart from one that has stalled. See [`Heartbeats`] for
+    /// which lines count as heartbeats and how to change that.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures_lite::stream::StreamExt;
+    /// use isahc::{prelude::*, Activity};
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> Result<(), isahc::Error> {
+    /// let mut response = isahc::get_async("https://example.org/feed.ndjson").await?;
+    /// let mut activity = response.heartbeats(Duration::from_secs(30));
+    ///
+    /// while let Some(activity) = activity.next().await {
+    ///     match activity? {
+    ///         Activity::Data(line) => println!("{}", String::from_utf8_lossy(&line)),
+    ///         Activity::Idle(idle) => {
+    ///             eprintln!("nothing received for {:?}, giving up", idle);
+    ///             break;
+    ///         }
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    fn heartbeats(&mut self, idle_timeout: Duration) -> Heartbeats<'_, T>;
 }
 
 impl<T: AsyncRead + Unpin> AsyncReadResponseExt<T> for Response<T> {
     fn copy_to<'a, W>(&'a mut self, writer: W) -> CopyFuture<'a>
     where
-        W: AsyncWrite + Unpin + 'a,
+        T: Send,
+        W: AsyncWrite + Unpin + Send + 'a,
     {
         CopyFuture(Box::pin(async move {
             futures_lite::io::copy(self.body_mut(), writer).await
@@ -273,21 +1337,124 @@ impl<T: AsyncRead + Unpin> AsyncReadResponseExt<T> for Response<T> {
     fn text(&mut self) -> crate::text::TextFuture<'_, &mut T> {
         crate::text::Decoder::for_response(&self).decode_reader_async(self.body_mut())
     }
+
+    #[cfg(feature = "text-decoding")]
+    fn text_with_charset(&mut self, charset: &str) -> crate::text::TextFuture<'_, &mut T> {
+        match crate::text::Decoder::for_label(charset) {
+            Ok(decoder) => decoder
+                .with_metrics(self.metrics())
+                .decode_reader_async(self.body_mut()),
+            Err(e) => crate::text::Decoder::error_async(e),
+        }
+    }
+
+    #[cfg(feature = "text-decoding")]
+    fn text_lossy_limit(&mut self, limit: usize) -> crate::text::TextFuture<'_, &mut T> {
+        crate::text::Decoder::for_response(self)
+            .with_limit(limit)
+            .decode_reader_async(self.body_mut())
+    }
+
+    fn error_for_status(&mut self) -> ErrorForStatusFuture<'_> {
+        ErrorForStatusFuture(Box::pin(async move {
+            if !is_error_status(self) {
+                return Ok(());
+            }
+
+            let limit = error_body_limit(self);
+
+            #[cfg(feature = "text-decoding")]
+            let body = self.text_lossy_limit(limit).await?;
+
+            #[cfg(not(feature = "text-decoding"))]
+            let body = {
+                use futures_lite::io::AsyncReadExt;
+
+                let mut buf = Vec::new();
+                self.body_mut()
+                    .take(limit as u64)
+                    .read_to_end(&mut buf)
+                    .await?;
+                String::from_utf8_lossy(&buf).into_owned()
+            };
+
+            Err(Error::from_status(self.status(), body))
+        }))
+    }
+
+    fn split(&mut self, delimiter: u8) -> Split<'_, T> {
+        Split::new(self.body_mut(), delimiter)
+    }
+
+    fn heartbeats(&mut self, idle_timeout: Duration) -> Heartbeats<'_, T> {
+        Heartbeats::new(self.body_mut(), idle_timeout)
+    }
+}
+
+fn is_error_status<T>(response: &Response<T>) -> bool {
+    response.status().is_client_error() || response.status().is_server_error()
+}
+
+fn error_body_limit<T>(response: &Response<T>) -> usize {
+    response
+        .extensions()
+        .get::<ErrorBodyLimit>()
+        .cloned()
+        .unwrap_or_default()
+        .0
 }
 
 /// A future which copies all the response body bytes into a sink.
 #[allow(missing_debug_implementations)]
 #[must_use = "futures do nothing unless you `.await` or poll them"]
-pub struct CopyFuture<'a>(Pin<Box<dyn Future<Output = io::Result<u64>> + 'a>>);
+pub struct CopyFuture<'a>(Pin<Box<dyn Future<Output = io::Result<u64>> + Send + 'a>>);
 
 impl Future for CopyFuture<'_> {
     type Output = io::Result<u64>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.as_mut().poll(cx)
+        self.0.as_mut().poll(cx)
     }
 }
 
+/// A future which checks the status of a response, returning an error
+/// including the start of the response body if it is an error status.
+#[allow(missing_debug_implementations)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ErrorForStatusFuture<'a>(Pin<Box<dyn Future<Output = Result<(), Error>> + 'a>>);
+
+impl Future for ErrorForStatusFuture<'_> {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+/// Whether the response was received over a connection that was reused from
+/// an earlier request.
+pub(crate) struct ConnectionReused(pub(crate) bool);
+
 pub(crate) struct LocalAddr(pub(crate) SocketAddr);
 
+/// Whether the response body was decompressed from the encodings given in the
+/// `Content-Encoding` headers.
+pub(crate) struct ContentDecoded(pub(crate) bool);
+
 pub(crate) struct RemoteAddr(pub(crate) SocketAddr);
+
+/// Extension containing when the request was sent and when its response was
+/// received, according to the clock of the client.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResponseTimes {
+    pub(crate) requested: SystemTime,
+    pub(crate) received: SystemTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::AsyncBody;
+
+    static_assertions::assert_impl_all!(CopyFuture<'_>: Send);
+}
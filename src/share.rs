@@ -0,0 +1,350 @@
+//! Sharing of caches between clients using curl's share interface.
+//!
+//! Each client has its own agent with its own curl multi handle, and so its own
+//! DNS cache, TLS session cache, and connection pool. A share handle lets curl
+//! keep these in a single place instead, which any number of easy handles can
+//! use at the same time. Since the agents of different clients run on separate
+//! threads, curl is given lock callbacks to synchronize access to the shared
+//! data.
+
+use crate::{
+    config::internal::SetOpt,
+    error::{Error, ErrorKind},
+};
+use curl::easy::Easy2;
+use curl_sys::{
+    curl_lock_access,
+    curl_lock_data,
+    CURLSHcode,
+    CURL,
+    CURLSH,
+    CURLSHE_OK,
+    CURL_LOCK_DATA_DNS,
+    CURL_LOCK_DATA_SSL_SESSION,
+};
+use std::{
+    ffi::CStr,
+    fmt,
+    io,
+    os::raw::{c_int, c_void},
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Not defined by all versions of curl-sys that we support.
+const CURL_LOCK_DATA_CONNECT: curl_lock_data = 5;
+
+/// Number of kinds of data that curl may ask us to lock. Later versions of
+/// libcurl may know about more kinds than we do, so leave some room.
+const LOCK_COUNT: usize = 8;
+
+/// A set of caches that can be shared between multiple clients.
+///
+/// By default, every [`HttpClient`](crate::HttpClient) keeps its own DNS cache,
+/// TLS session cache, and pool of open connections. An application that creates
+/// many clients, for example one per tenant or per service with different
+/// settings, therefore repeats DNS lookups and TLS handshakes that another
+/// client already did. Giving all of these clients the same share handle using
+/// [`HttpClientBuilder::share`](crate::HttpClientBuilder::share) lets them use
+/// the same caches instead.
+///
+/// Share handles are cheap to clone, and all clones refer to the same caches.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::{HttpClient, ShareHandle};
+///
+/// let share = ShareHandle::new()?;
+///
+/// let client_a = HttpClient::builder().share(share.clone()).build()?;
+/// let client_b = HttpClient::builder().share(share).build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct ShareHandle {
+    inner: Arc<Inner>,
+}
+
+impl ShareHandle {
+    /// Create a new share handle that shares DNS entries, TLS sessions, and
+    /// connections.
+    pub fn new() -> Result<Self, Error> {
+        Self::builder().build()
+    }
+
+    /// Create a builder for choosing which caches to share.
+    pub fn builder() -> ShareHandleBuilder {
+        ShareHandleBuilder::default()
+    }
+
+    /// Check whether this handle shares the pool of open connections.
+    pub(crate) fn shares_connections(&self) -> bool {
+        self.inner.connections
+    }
+}
+
+impl SetOpt for ShareHandle {
+    #[allow(unsafe_code)]
+    fn set_opt<H>(&self, easy: &mut Easy2<H>) -> Result<(), curl::Error> {
+        unsafe {
+            match curl_sys::curl_easy_setopt(easy.raw(), curl_sys::CURLOPT_SHARE, self.inner.raw) {
+                curl_sys::CURLE_OK => Ok(()),
+                code => Err(curl::Error::new(code)),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ShareHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShareHandle")
+            .field("dns_cache", &self.inner.dns_cache)
+            .field("ssl_sessions", &self.inner.ssl_sessions)
+            .field("connections", &self.inner.connections)
+            .finish()
+    }
+}
+
+/// A builder for a [`ShareHandle`], choosing which caches are shared.
+///
+/// All caches are shared unless disabled.
+///
+/// # Examples
+///
+/// ```
+/// use isahc::ShareHandle;
+///
+/// // Share DNS and TLS sessions, but let each client keep its own connections.
+/// let share = ShareHandle::builder()
+///     .connections(false)
+///     .build()?;
+/// # Ok::<(), isahc::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ShareHandleBuilder {
+    dns_cache: bool,
+    ssl_sessions: bool,
+    connections: bool,
+}
+
+impl Default for ShareHandleBuilder {
+    fn default() -> Self {
+        Self {
+            dns_cache: true,
+            ssl_sessions: true,
+            connections: true,
+        }
+    }
+}
+
+impl ShareHandleBuilder {
+    /// Share resolved host names.
+    ///
+    /// The DNS cache timeout configured for each client still applies to the
+    /// requests it sends.
+    pub fn dns_cache(mut self, enable: bool) -> Self {
+        self.dns_cache = enable;
+        self
+    }
+
+    /// Share TLS session IDs, allowing connections opened by one client to
+    /// resume a TLS session started by another, skipping most of the
+    /// handshake.
+    pub fn ssl_sessions(mut self, enable: bool) -> Self {
+        self.ssl_sessions = enable;
+        self
+    }
+
+    /// Share the pool of open connections, allowing a client to reuse a
+    /// connection opened by another.
+    ///
+    /// Requires libcurl 7.57.0 or newer.
+    pub fn connections(mut self, enable: bool) -> Self {
+        self.connections = enable;
+        self
+    }
+
+    /// Create the share handle.
+    ///
+    /// Returns an error if libcurl does not support sharing one of the chosen
+    /// caches.
+    #[allow(unsafe_code)]
+    pub fn build(self) -> Result<ShareHandle, Error> {
+        let raw = unsafe { curl_sys::curl_share_init() };
+
+        if raw.is_null() {
+            return Err(share_error(curl_sys::CURLSHE_NOMEM));
+        }
+
+        // Create the handle right away, so that the raw handle is cleaned up
+        // if anything goes wrong below.
+        let inner = Arc::new(Inner {
+            raw,
+            locks: Default::default(),
+            dns_cache: self.dns_cache,
+            ssl_sessions: self.ssl_sessions,
+            connections: self.connections,
+        });
+
+        let user_data = &*inner as *const Inner as *mut c_void;
+
+        unsafe {
+            check(curl_sys::curl_share_setopt(
+                raw,
+                curl_sys::CURLSHOPT_LOCKFUNC,
+                lock_cb as curl_sys::curl_lock_function,
+            ))?;
+            check(curl_sys::curl_share_setopt(
+                raw,
+                curl_sys::CURLSHOPT_UNLOCKFUNC,
+                unlock_cb as curl_sys::curl_unlock_function,
+            ))?;
+            check(curl_sys::curl_share_setopt(
+                raw,
+                curl_sys::CURLSHOPT_USERDATA,
+                user_data,
+            ))?;
+
+            let data = [
+                (self.dns_cache, CURL_LOCK_DATA_DNS),
+                (self.ssl_sessions, CURL_LOCK_DATA_SSL_SESSION),
+                (self.connections, CURL_LOCK_DATA_CONNECT),
+            ];
+
+            for &(enabled, data) in &data {
+                if enabled {
+                    check(curl_sys::curl_share_setopt(
+                        raw,
+                        curl_sys::CURLSHOPT_SHARE,
+                        data as c_int,
+                    ))?;
+                }
+            }
+        }
+
+        Ok(ShareHandle {
+            inner,
+        })
+    }
+}
+
+struct Inner {
+    raw: *mut CURLSH,
+    locks: [Lock; LOCK_COUNT],
+    dns_cache: bool,
+    ssl_sessions: bool,
+    connections: bool,
+}
+
+// Curl only accesses the shared data while holding the corresponding lock,
+// which we provide.
+#[allow(unsafe_code)]
+unsafe impl Send for Inner {}
+#[allow(unsafe_code)]
+unsafe impl Sync for Inner {}
+
+impl Drop for Inner {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        // Every easy handle using the share keeps a reference to it, so it is
+        // no longer in use by now.
+        unsafe {
+            curl_sys::curl_share_cleanup(self.raw);
+        }
+    }
+}
+
+/// A lock that can be released from a different scope than it was acquired
+/// in, as curl requires.
+#[derive(Default)]
+struct Lock {
+    locked: Mutex<bool>,
+    unlocked: Condvar,
+}
+
+impl Lock {
+    fn lock(&self) {
+        let mut locked = self.locked.lock().unwrap_or_else(|e| e.into_inner());
+
+        while *locked {
+            locked = self
+                .unlocked
+                .wait(locked)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+
+        *locked = true;
+    }
+
+    fn unlock(&self) {
+        *self.locked.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        self.unlocked.notify_one();
+    }
+}
+
+#[allow(unsafe_code)]
+extern "C" fn lock_cb(
+    _handle: *mut CURL,
+    data: curl_lock_data,
+    _access: curl_lock_access,
+    user_data: *mut c_void,
+) {
+    let inner = unsafe { &*(user_data as *const Inner) };
+
+    if let Some(lock) = inner.locks.get(data as usize) {
+        lock.lock();
+    }
+}
+
+#[allow(unsafe_code)]
+extern "C" fn unlock_cb(_handle: *mut CURL, data: curl_lock_data, user_data: *mut c_void) {
+    let inner = unsafe { &*(user_data as *const Inner) };
+
+    if let Some(lock) = inner.locks.get(data as usize) {
+        lock.unlock();
+    }
+}
+
+fn check(code: CURLSHcode) -> Result<(), Error> {
+    if code == CURLSHE_OK {
+        Ok(())
+    } else {
+        Err(share_error(code))
+    }
+}
+
+#[allow(unsafe_code)]
+fn share_error(code: CURLSHcode) -> Error {
+    let message = unsafe { CStr::from_ptr(curl_sys::curl_share_strerror(code)) }
+        .to_string_lossy()
+        .into_owned();
+
+    Error::with_context(
+        ErrorKind::ClientInitialization,
+        Some(String::from("failed to create share handle")),
+        io::Error::new(io::ErrorKind::Other, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static_assertions::assert_impl_all!(ShareHandle: Send, Sync);
+
+    #[test]
+    fn lock_can_be_released_from_another_thread() {
+        let lock = Arc::new(Lock::default());
+
+        lock.lock();
+
+        let thread = {
+            let lock = lock.clone();
+            std::thread::spawn(move || lock.unlock())
+        };
+
+        thread.join().unwrap();
+        lock.lock();
+        lock.unlock();
+    }
+}
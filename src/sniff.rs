@@ -0,0 +1,246 @@
+//! Content type sniffing of response bodies.
+//!
+//! This implements the rules for identifying an unknown MIME type from the
+//! [WHATWG MIME Sniffing Standard](https://mimesniff.spec.whatwg.org), which
+//! looks at the first bytes of a resource to determine what kind of data it
+//! most likely contains.
+
+#![cfg(feature = "mime-sniff")]
+
+use crate::headers::HasHeaders;
+
+/// Maximum number of bytes of a resource that are examined when sniffing.
+const RESOURCE_HEADER_LEN: usize = 1445;
+
+/// The sniffed content type of a response, stored as a response extension.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SniffedContentType(pub(crate) &'static str);
+
+/// How the bytes of a pattern are compared against a resource.
+enum Match {
+    /// The bytes must match exactly.
+    Exact,
+    /// The bytes must match with the given mask applied to the resource.
+    Masked(&'static [u8]),
+    /// An HTML tag, matched case-insensitively after any leading whitespace
+    /// and followed by a tag-terminating byte.
+    Tag,
+    /// The bytes must match exactly after any leading whitespace.
+    Whitespace,
+}
+
+/// Mask for RIFF and IFF containers, which ignores the chunk size.
+const CHUNK_MASK: &[u8] = b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF\xFF\xFF";
+
+/// Patterns that identify text-based content types, in order of precedence.
+const TEXT_PATTERNS: &[(Match, &[u8], &str)] = &[
+    (Match::Tag, b"<!DOCTYPE HTML", "text/html"),
+    (Match::Tag, b"<HTML", "text/html"),
+    (Match::Tag, b"<HEAD", "text/html"),
+    (Match::Tag, b"<SCRIPT", "text/html"),
+    (Match::Tag, b"<IFRAME", "text/html"),
+    (Match::Tag, b"<H1", "text/html"),
+    (Match::Tag, b"<DIV", "text/html"),
+    (Match::Tag, b"<FONT", "text/html"),
+    (Match::Tag, b"<TABLE", "text/html"),
+    (Match::Tag, b"<A", "text/html"),
+    (Match::Tag, b"<STYLE", "text/html"),
+    (Match::Tag, b"<TITLE", "text/html"),
+    (Match::Tag, b"<B", "text/html"),
+    (Match::Tag, b"<BODY", "text/html"),
+    (Match::Tag, b"<BR", "text/html"),
+    (Match::Tag, b"<P", "text/html"),
+    (Match::Tag, b"<!--", "text/html"),
+    (Match::Whitespace, b"<?xml", "text/xml"),
+    (Match::Exact, b"%PDF-", "application/pdf"),
+    (Match::Exact, b"%!PS-Adobe-", "application/postscript"),
+    (Match::Exact, b"\xFE\xFF", "text/plain"),
+    (Match::Exact, b"\xFF\xFE", "text/plain"),
+    (Match::Exact, b"\xEF\xBB\xBF", "text/plain"),
+];
+
+/// Patterns that identify binary content types, in order of precedence.
+const BINARY_PATTERNS: &[(Match, &[u8], &str)] = &[
+    // Images
+    (Match::Exact, b"\x00\x00\x01\x00", "image/x-icon"),
+    (Match::Exact, b"\x00\x00\x02\x00", "image/x-icon"),
+    (Match::Exact, b"BM", "image/bmp"),
+    (Match::Exact, b"GIF87a", "image/gif"),
+    (Match::Exact, b"GIF89a", "image/gif"),
+    (Match::Masked(CHUNK_MASK), b"RIFF\x00\x00\x00\x00WEBPVP", "image/webp"),
+    (Match::Exact, b"\x89PNG\r\n\x1A\n", "image/png"),
+    (Match::Exact, b"\xFF\xD8\xFF", "image/jpeg"),
+    // Audio and video
+    (Match::Masked(CHUNK_MASK), b"FORM\x00\x00\x00\x00AIFF", "audio/aiff"),
+    (Match::Exact, b"ID3", "audio/mpeg"),
+    (Match::Exact, b"OggS\x00", "application/ogg"),
+    (Match::Exact, b"MThd\x00\x00\x00\x06", "audio/midi"),
+    (Match::Masked(CHUNK_MASK), b"RIFF\x00\x00\x00\x00AVI ", "video/avi"),
+    (Match::Masked(CHUNK_MASK), b"RIFF\x00\x00\x00\x00WAVE", "audio/wave"),
+    // Fonts
+    (Match::Exact, b"\x00\x01\x00\x00", "font/ttf"),
+    (Match::Exact, b"OTTO", "font/otf"),
+    (Match::Exact, b"ttcf", "font/collection"),
+    (Match::Exact, b"wOFF", "font/woff"),
+    (Match::Exact, b"wOF2", "font/woff2"),
+    // Archives
+    (Match::Exact, b"\x1F\x8B\x08", "application/x-gzip"),
+    (Match::Exact, b"PK\x03\x04", "application/zip"),
+    (Match::Exact, b"Rar!\x1A\x07\x00", "application/x-rar-compressed"),
+];
+
+impl Match {
+    fn matches(&self, pattern: &[u8], data: &[u8]) -> bool {
+        let data = match self {
+            Match::Tag | Match::Whitespace => skip_whitespace(data),
+            _ => data,
+        };
+
+        if data.len() < pattern.len() {
+            return false;
+        }
+
+        match self {
+            Match::Exact | Match::Whitespace => data.starts_with(pattern),
+            Match::Masked(mask) => pattern
+                .iter()
+                .zip(mask.iter())
+                .zip(data)
+                .all(|((byte, mask), data)| data & mask == *byte),
+            Match::Tag => {
+                data[..pattern.len()].eq_ignore_ascii_case(pattern)
+                    && match data.get(pattern.len()) {
+                        Some(b' ') | Some(b'>') => true,
+                        _ => false,
+                    }
+            }
+        }
+    }
+}
+
+/// Check whether the content type declared in the given headers should be
+/// ignored in favor of sniffing.
+pub(crate) fn should_sniff(headers: &impl HasHeaders) -> bool {
+    match headers.content_type() {
+        None => true,
+        Some(content_type) => {
+            let essence = content_type.split(';').next().unwrap_or("").trim();
+
+            essence.is_empty()
+                || essence.eq_ignore_ascii_case("application/octet-stream")
+                || essence.eq_ignore_ascii_case("unknown/unknown")
+                || essence.eq_ignore_ascii_case("application/unknown")
+                || essence == "*/*"
+        }
+    }
+}
+
+/// Determine the content type of a resource from its first bytes.
+pub(crate) fn sniff(data: &[u8]) -> &'static str {
+    let data = &data[..data.len().min(RESOURCE_HEADER_LEN)];
+
+    for (kind, pattern, mime) in TEXT_PATTERNS.iter().chain(BINARY_PATTERNS) {
+        if kind.matches(pattern, data) {
+            return mime;
+        }
+    }
+
+    if is_mp4(data) {
+        return "video/mp4";
+    }
+
+    if data.iter().any(|&byte| is_binary(byte)) {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Check whether the data matches the signature for MP4, which is a box
+/// header of type `ftyp` with an `mp4` major or compatible brand.
+fn is_mp4(data: &[u8]) -> bool {
+    if data.len() < 12 {
+        return false;
+    }
+
+    let box_size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+
+    if box_size > data.len() || box_size & 3 != 0 || &data[4..8] != b"ftyp" {
+        return false;
+    }
+
+    if &data[8..11] == b"mp4" {
+        return true;
+    }
+
+    // Skip the minor version and check the compatible brands.
+    data[16.min(box_size)..box_size]
+        .chunks(4)
+        .any(|brand| brand.starts_with(b"mp4"))
+}
+
+fn skip_whitespace(data: &[u8]) -> &[u8] {
+    let start = data
+        .iter()
+        .position(|&byte| !is_whitespace(byte))
+        .unwrap_or(data.len());
+
+    &data[start..]
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    match byte {
+        b'\t' | b'\n' | b'\x0C' | b'\r' | b' ' => true,
+        _ => false,
+    }
+}
+
+fn is_binary(byte: u8) -> bool {
+    match byte {
+        0x00..=0x08 | 0x0B | 0x0E..=0x1A | 0x1C..=0x1F => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(b"<!DOCTYPE html><html></html>", "text/html")]
+    #[test_case(b"  \n<HtMl lang=\"en\">", "text/html")]
+    #[test_case(b"<p>hello</p>", "text/html")]
+    #[test_case(b"<pre>hello</pre>", "text/plain")]
+    #[test_case(b"<?xml version=\"1.0\"?>", "text/xml")]
+    #[test_case(b"%PDF-1.7", "application/pdf")]
+    #[test_case(b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR", "image/png")]
+    #[test_case(b"GIF89a\x01\x00", "image/gif")]
+    #[test_case(b"\xFF\xD8\xFF\xE0", "image/jpeg")]
+    #[test_case(b"RIFF\x24\x00\x00\x00WEBPVP8 ", "image/webp")]
+    #[test_case(b"RIFF\x24\x00\x00\x00WAVEfmt ", "audio/wave")]
+    #[test_case(b"\x00\x00\x00\x18ftypmp42\x00\x00\x00\x00isommp42", "video/mp4")]
+    #[test_case(b"\x1F\x8B\x08\x00", "application/x-gzip")]
+    #[test_case(b"PK\x03\x04\x14\x00", "application/zip")]
+    #[test_case(b"\xEF\xBB\xBF\x00\x01", "text/plain")]
+    #[test_case(b"hello world", "text/plain")]
+    #[test_case(b"", "text/plain")]
+    #[test_case(b"\x00\x01\x02\x03\x04", "application/octet-stream")]
+    fn sniff_content_type(data: &[u8], expected: &str) {
+        assert_eq!(sniff(data), expected);
+    }
+
+    #[test_case(None, true)]
+    #[test_case(Some("application/octet-stream"), true)]
+    #[test_case(Some("Application/Octet-Stream; charset=binary"), true)]
+    #[test_case(Some("text/plain"), false)]
+    #[test_case(Some("image/png"), false)]
+    fn should_sniff_content_type(content_type: Option<&str>, expected: bool) {
+        let mut headers = http::HeaderMap::new();
+
+        if let Some(content_type) = content_type {
+            headers.insert(http::header::CONTENT_TYPE, content_type.parse().unwrap());
+        }
+
+        assert_eq!(should_sniff(&headers), expected);
+    }
+}
@@ -0,0 +1,254 @@
+//! A buffer for response bodies that spills over into a temporary file on disk
+//! once the amount of unread data grows past a threshold.
+//!
+//! Data is always read in the order it was written. Data is buffered in memory
+//! until the threshold is reached, after which it is written to the file
+//! instead. While the file contains unread data, all new data is appended to
+//! the file as well, so that the file always contains newer data than memory.
+//! Once the reader has fully drained the file, it is truncated and reused.
+
+use futures_lite::io::AsyncRead;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Create a new spill buffer, returning its reading and writing halves.
+pub(crate) fn buffer(threshold: usize, dir: PathBuf) -> (SpillReader, SpillWriter) {
+    let state = Arc::new(Mutex::new(State {
+        memory: VecDeque::new(),
+        file: None,
+        read_pos: 0,
+        write_pos: 0,
+        closed: false,
+        reader_dropped: false,
+        reader_waker: None,
+    }));
+
+    let reader = SpillReader {
+        state: state.clone(),
+    };
+
+    let writer = SpillWriter {
+        state,
+        threshold,
+        dir,
+    };
+
+    (reader, writer)
+}
+
+/// State shared between the reader and the writer.
+struct State {
+    /// Data buffered in memory, which is always older than any data in the
+    /// file.
+    memory: VecDeque<u8>,
+
+    /// Temporary file for data past the threshold. Created the first time
+    /// the threshold is exceeded.
+    file: Option<File>,
+
+    /// Offset in the file of the next byte to read.
+    read_pos: u64,
+
+    /// Offset in the file where the next byte will be written.
+    write_pos: u64,
+
+    /// Set once the writer is dropped and no more data will be written.
+    closed: bool,
+
+    /// Set once the reader is dropped and data should no longer be buffered.
+    reader_dropped: bool,
+
+    /// Waker for a reader waiting for more data.
+    reader_waker: Option<Waker>,
+}
+
+impl State {
+    fn has_unread_file_data(&self) -> bool {
+        self.read_pos < self.write_pos
+    }
+
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.reader_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Writing half of a spill buffer.
+pub(crate) struct SpillWriter {
+    state: Arc<Mutex<State>>,
+    threshold: usize,
+    dir: PathBuf,
+}
+
+impl SpillWriter {
+    /// Write the given data into the buffer. This never blocks waiting for
+    /// the reader, and either buffers all of the data or fails.
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.reader_dropped {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        if !state.has_unread_file_data() && state.memory.len() + data.len() <= self.threshold {
+            state.memory.extend(data);
+        } else {
+            if state.file.is_none() {
+                tracing::debug!(
+                    "response body buffer exceeded {} bytes, spilling to disk",
+                    self.threshold
+                );
+                state.file = Some(tempfile::tempfile_in(&self.dir)?);
+            }
+
+            let write_pos = state.write_pos;
+            let file = state.file.as_mut().unwrap();
+
+            file.seek(SeekFrom::Start(write_pos))?;
+            file.write_all(data)?;
+            state.write_pos += data.len() as u64;
+        }
+
+        state.wake_reader();
+
+        Ok(data.len())
+    }
+}
+
+impl Drop for SpillWriter {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.wake_reader();
+    }
+}
+
+/// Reading half of a spill buffer.
+pub(crate) struct SpillReader {
+    state: Arc<Mutex<State>>,
+}
+
+impl AsyncRead for SpillReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.memory.is_empty() {
+            let len = buf.len().min(state.memory.len());
+
+            for (dest, byte) in buf.iter_mut().zip(state.memory.drain(..len)) {
+                *dest = byte;
+            }
+
+            return Poll::Ready(Ok(len));
+        }
+
+        if state.has_unread_file_data() {
+            let unread = state.write_pos - state.read_pos;
+            let len = (buf.len() as u64).min(unread) as usize;
+            let read_pos = state.read_pos;
+            let file = state.file.as_mut().unwrap();
+
+            file.seek(SeekFrom::Start(read_pos))?;
+            let len = file.read(&mut buf[..len])?;
+            state.read_pos += len as u64;
+
+            // Once the file is drained, start over from the beginning so that
+            // it does not grow forever.
+            if !state.has_unread_file_data() {
+                state.file.as_mut().unwrap().set_len(0)?;
+                state.read_pos = 0;
+                state.write_pos = 0;
+            }
+
+            return Poll::Ready(Ok(len));
+        }
+
+        if state.closed {
+            return Poll::Ready(Ok(0));
+        }
+
+        state.reader_waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+impl Drop for SpillReader {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.reader_dropped = true;
+        state.memory.clear();
+        state.file = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::{future::block_on, io::AsyncReadExt};
+
+    #[test]
+    fn data_past_threshold_spills_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut reader, mut writer) = buffer(4, dir.path().into());
+
+        writer.write(b"abc").unwrap();
+        writer.write(b"defg").unwrap();
+        writer.write(b"h").unwrap();
+
+        assert!(reader.state.lock().unwrap().file.is_some());
+        assert_eq!(reader.state.lock().unwrap().memory.len(), 3);
+
+        let mut buf = [0; 2];
+        block_on(reader.read_exact(&mut buf)).unwrap();
+        assert_eq!(&buf, b"ab");
+
+        drop(writer);
+
+        let mut rest = Vec::new();
+        block_on(reader.read_to_end(&mut rest)).unwrap();
+        assert_eq!(rest, b"cdefgh");
+    }
+
+    #[test]
+    fn file_is_reused_once_drained() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut reader, mut writer) = buffer(0, dir.path().into());
+
+        writer.write(b"hello").unwrap();
+
+        let mut buf = [0; 5];
+        block_on(reader.read_exact(&mut buf)).unwrap();
+        assert_eq!(&buf, b"hello");
+        assert_eq!(reader.state.lock().unwrap().write_pos, 0);
+
+        writer.write(b"world").unwrap();
+        block_on(reader.read_exact(&mut buf)).unwrap();
+        assert_eq!(&buf, b"world");
+    }
+
+    #[test]
+    fn write_fails_after_reader_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (reader, mut writer) = buffer(16, dir.path().into());
+
+        drop(reader);
+
+        assert_eq!(
+            writer.write(b"hello").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Splitting a streaming response body into records.
+
+use futures_lite::{io::AsyncRead, stream::Stream};
+use std::{
+    fmt,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Records larger than this are rejected unless configured otherwise.
+const DEFAULT_MAX_RECORD_SIZE: usize = 1024 * 1024;
+
+/// Size of the chunks read from the body at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// A stream of the records in a response body that are separated by a
+/// delimiter byte.
+///
+/// This is created by
+/// [`AsyncReadResponseExt::split`](crate::AsyncReadResponseExt::split). Each
+/// record is yielded as soon as its delimiter has been received, without the
+/// delimiter itself. A final record that is not followed by a delimiter is
+/// yielded once the body ends, while an empty final record is not.
+///
+/// To protect against a body that never contains a delimiter, records larger
+/// than a maximum size of 1 MiB by default are rejected with an error of kind
+/// [`InvalidData`](io::ErrorKind::InvalidData), which ends the stream. The
+/// limit can be changed with [`max_record_size`](Split::max_record_size).
+#[must_use = "streams do nothing unless polled"]
+pub struct Split<'a, T> {
+    reader: &'a mut T,
+    delimiter: u8,
+    max_record_size: usize,
+    buf: Vec<u8>,
+
+    /// Number of bytes at the start of the buffer that are known to not
+    /// contain a delimiter.
+    searched: usize,
+
+    done: bool,
+}
+
+impl<'a, T: AsyncRead + Unpin> Split<'a, T> {
+    pub(crate) fn new(reader: &'a mut T, delimiter: u8) -> Self {
+        Self {
+            reader,
+            delimiter,
+            max_record_size: DEFAULT_MAX_RECORD_SIZE,
+            buf: Vec::new(),
+            searched: 0,
+            done: false,
+        }
+    }
+
+    /// Set the maximum size of a record in bytes, not counting the delimiter.
+    pub fn max_record_size(mut self, max: usize) -> Self {
+        self.max_record_size = max;
+        self
+    }
+
+    /// Take the next complete record out of the buffer, if there is one.
+    fn next_record(&mut self) -> Option<io::Result<Vec<u8>>> {
+        match self.buf[self.searched..]
+            .iter()
+            .position(|&byte| byte == self.delimiter)
+        {
+            Some(i) => {
+                let len = self.searched + i;
+
+                if len > self.max_record_size {
+                    return Some(Err(self.too_large()));
+                }
+
+                let mut record = self.buf.split_off(len + 1);
+                std::mem::swap(&mut record, &mut self.buf);
+                record.pop();
+                self.searched = 0;
+
+                Some(Ok(record))
+            }
+            None if self.buf.len() > self.max_record_size => Some(Err(self.too_large())),
+            None => {
+                self.searched = self.buf.len();
+                None
+            }
+        }
+    }
+
+    fn too_large(&mut self) -> io::Error {
+        self.done = true;
+        self.buf = Vec::new();
+
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "record exceeds the maximum size of {} bytes",
+                self.max_record_size
+            ),
+        )
+    }
+}
+
+impl<T: AsyncRead + Unpin> Stream for Split<'_, T> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(record) = this.next_record() {
+                return Poll::Ready(Some(record));
+            }
+
+            if this.done {
+                return Poll::Ready(if this.buf.is_empty() {
+                    None
+                } else {
+                    this.searched = 0;
+                    Some(Ok(std::mem::take(&mut this.buf)))
+                });
+            }
+
+            let filled = this.buf.len();
+            this.buf.resize(filled + READ_SIZE, 0);
+
+            let result = Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf[filled..]);
+
+            match result {
+                Poll::Ready(Ok(n)) => {
+                    this.buf.truncate(filled + n);
+                    this.done = n == 0;
+                }
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {
+                    this.buf.truncate(filled);
+                }
+                Poll::Ready(Err(e)) => {
+                    this.buf.truncate(filled);
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Pending => {
+                    this.buf.truncate(filled);
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Split<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Split")
+            .field("delimiter", &self.delimiter)
+            .field("max_record_size", &self.max_record_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::{future::block_on, stream::StreamExt};
+
+    fn records(body: &[u8], max_record_size: usize) -> Vec<io::Result<Vec<u8>>> {
+        let mut reader = body;
+
+        block_on(
+            Split::new(&mut reader, b'\n')
+                .max_record_size(max_record_size)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn records_are_split_by_delimiter() {
+        let records = records(b"a,1\nb,2\n\nc,3", 16)
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(records, vec![
+            b"a,1".to_vec(),
+            b"b,2".to_vec(),
+            b"".to_vec(),
+            b"c,3".to_vec(),
+        ]);
+    }
+
+    #[test]
+    fn trailing_delimiter_does_not_produce_empty_record() {
+        assert_eq!(records(b"a\nb\n", 16).len(), 2);
+    }
+
+    #[test]
+    fn oversized_record_ends_stream_with_error() {
+        let records = records(b"short\nthis is much too long\nshort\n", 8);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap(), b"short");
+        assert_eq!(
+            records[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
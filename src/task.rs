@@ -1,9 +1,19 @@
 //! Helpers for working with tasks and futures.
 
+use once_cell::sync::OnceCell;
 use std::{
+    future::Future,
     io,
     net::{SocketAddr, UdpSocket},
-    task::Waker,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
 };
 
 /// Helper methods for working with wakers.
@@ -52,3 +62,172 @@ impl From<UdpWaker> for Waker {
         })
     }
 }
+
+/// Block the current thread until the given future completes, or until the
+/// timeout passes, whichever comes first. Returns `None` if the timeout passed.
+pub(crate) fn block_on_timeout<F: Future>(future: F, timeout: Duration) -> Option<F::Output> {
+    let deadline = match Instant::now().checked_add(timeout) {
+        Some(deadline) => deadline,
+        None => return Some(futures_lite::future::block_on(future)),
+    };
+
+    let thread = thread::current();
+    let waker = waker_fn::waker_fn(move || thread.unpark());
+    let mut cx = Context::from_waker(&waker);
+
+    futures_lite::pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+
+        let now = Instant::now();
+
+        if now >= deadline {
+            return None;
+        }
+
+        // Being woken up early is harmless, the future is just polled again.
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// A future that completes once the given duration has passed.
+///
+/// There is no timer to rely on outside of the agent, so this uses a thread
+/// that sleeps for the duration. If the future is dropped before it completes,
+/// the thread is woken up and exits early.
+pub(crate) struct Delay {
+    receiver: flume::r#async::RecvFut<'static, ()>,
+    canceled: Arc<AtomicBool>,
+    thread: Option<thread::Thread>,
+}
+
+impl Delay {
+    pub(crate) fn new(duration: Duration) -> Self {
+        let (sender, receiver) = flume::bounded(1);
+        let canceled = Arc::new(AtomicBool::new(false));
+        let deadline = Instant::now() + duration;
+
+        // If the thread cannot be spawned, the sender is dropped, which
+        // completes the future right away.
+        let thread = thread::Builder::new()
+            .name(String::from("isahc-delay"))
+            .spawn({
+                let canceled = canceled.clone();
+
+                move || loop {
+                    if canceled.load(Ordering::SeqCst) {
+                        return;
+                    }
+
+                    let now = Instant::now();
+
+                    if now >= deadline {
+                        let _ = sender.send(());
+                        return;
+                    }
+
+                    thread::park_timeout(deadline - now);
+                }
+            })
+            .map(|handle| handle.thread().clone())
+            .ok();
+
+        Self {
+            receiver: receiver.into_recv_async(),
+            canceled,
+            thread,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|_| ())
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        self.canceled.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.as_ref() {
+            thread.unpark();
+        }
+    }
+}
+
+/// A job run by a [`ThreadPool`].
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of threads for running jobs that may block.
+///
+/// Jobs wait in a queue while all threads are busy, so a burst of jobs does not
+/// spawn a thread for each of them. The threads are spawned when the first job
+/// is run, and run jobs for the rest of the process.
+pub(crate) struct ThreadPool {
+    name: &'static str,
+    size: usize,
+    sender: OnceCell<flume::Sender<Job>>,
+}
+
+impl ThreadPool {
+    pub(crate) const fn new(name: &'static str, size: usize) -> Self {
+        Self {
+            name,
+            size,
+            sender: OnceCell::new(),
+        }
+    }
+
+    /// Run a job on one of the threads of the pool.
+    ///
+    /// A job that panics does not take its thread down with it. Anything it
+    /// owns is dropped, so a channel it would have sent its result to is
+    /// disconnected instead.
+    pub(crate) fn execute(&self, job: impl FnOnce() + Send + 'static) -> io::Result<()> {
+        let sender = self.sender.get_or_try_init(|| self.spawn())?;
+
+        sender.send(Box::new(job)).map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, format!("{} threads have exited", self.name))
+        })
+    }
+
+    fn spawn(&self) -> io::Result<flume::Sender<Job>> {
+        let (sender, receiver) = flume::unbounded::<Job>();
+        let mut error = None;
+        let mut spawned = 0;
+
+        for i in 0..self.size {
+            let receiver = receiver.clone();
+
+            let result = thread::Builder::new()
+                .name(format!("{}-{}", self.name, i))
+                .spawn(move || {
+                    for job in receiver.iter() {
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            tracing::debug!("job panicked on a pool thread");
+                        }
+                    }
+                });
+
+            match result {
+                Ok(_) => spawned += 1,
+                Err(e) => {
+                    tracing::warn!("failed to spawn {} thread: {}", self.name, e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        // A smaller pool still works, just not as well.
+        match error {
+            Some(e) if spawned == 0 => Err(e),
+            _ => Ok(sender),
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! A minimal HTTP server for testing code that sends requests.
+//!
+//! Integration tests for code that uses an HTTP client usually need a server
+//! to talk to. The [`Server`] in this module runs in background threads of the
+//! test process itself, listening on an ephemeral port on the loopback
+//! interface, so no external binaries or network access are needed. Responses
+//! are set up per route, and can be delayed or sent using chunked transfer
+//! encoding to exercise timeouts and streaming.
+//!
+//! Only HTTP/1.1 is implemented, and only as much of it as is needed for
+//! testing. This server is not meant to be used for anything else.
+//!
+//! # Examples
+//!
+//! ```
+//! use isahc::{prelude::*, testing::{Response, Server}};
+//! use http::Method;
+//!
+//! let server = Server::new()?;
+//! server.route(Method::GET, "/hello", Response::new().body("hello world"));
+//!
+//! let mut response = isahc::get(server.url("/hello"))?;
+//! assert_eq!(response.text()?, "hello world");
+//!
+//! let requests = server.requests();
+//! assert_eq!(requests.len(), 1);
+//! assert_eq!(requests[0].uri(), "/hello");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! # Availability
+//!
+//! This module is only available when the [`testing`](../index.html#testing)
+//! feature is enabled.
+
+use http::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap,
+    Method,
+    Request,
+    StatusCode,
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// A test HTTP/1.1 server listening on an ephemeral port.
+///
+/// Requests are answered with the response of the route matching their method
+/// and path, ignoring any query string, or with a `404 Not Found` response if
+/// no route matches. Every request received is recorded, and can be inspected
+/// using [`requests`](Server::requests).
+///
+/// The server is shut down when dropped.
+pub struct Server {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    /// Start a new server on an ephemeral port of the loopback interface.
+    pub fn new() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+
+        thread::Builder::new()
+            .name(format!("isahc-test-server-{}", addr.port()))
+            .spawn({
+                let shared = shared.clone();
+                move || accept(listener, shared)
+            })?;
+
+        Ok(Self {
+            addr,
+            shared,
+        })
+    }
+
+    /// Get the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Get the URL of the given path on this server.
+    pub fn url(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            format!("http://{}{}", self.addr, path)
+        } else {
+            format!("http://{}/{}", self.addr, path)
+        }
+    }
+
+    /// Respond to requests with the given method and path with the given
+    /// response.
+    ///
+    /// Setting a route for a method and path that already has one replaces
+    /// the previous response.
+    pub fn route(&self, method: Method, path: &str, response: Response) -> &Self {
+        let mut routes = self.shared.routes.lock().unwrap();
+
+        routes.retain(|route| route.method != method || route.path != path);
+        routes.push(Route {
+            method,
+            path: path.to_owned(),
+            response,
+        });
+
+        self
+    }
+
+    /// Get all requests received by this server so far, in the order they
+    /// were received.
+    pub fn requests(&self) -> Vec<Request<Vec<u8>>> {
+        self.shared
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(ReceivedRequest::to_request)
+            .collect()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+
+        // Wake up the accepting thread so that it notices.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server").field("addr", &self.addr).finish()
+    }
+}
+
+/// A response for a [`Server`] to send.
+///
+/// Responses are `200 OK` with an empty body unless configured otherwise. A
+/// `Content-Length` header is added automatically, unless the body is sent
+/// using chunked transfer encoding.
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    delay: Duration,
+    chunk_size: Option<usize>,
+    chunk_delay: Duration,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Response {
+    /// Create a new `200 OK` response with an empty body.
+    pub fn new() -> Self {
+        Self {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            delay: Duration::from_secs(0),
+            chunk_size: None,
+            chunk_delay: Duration::from_secs(0),
+        }
+    }
+
+    /// Set the status code of the response.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Append a header to the response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is not a valid header name or value.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::try_from(name).expect("invalid header name"),
+            HeaderValue::try_from(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// Set the body of the response.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Wait for the given amount of time before sending the response, which
+    /// simulates a slow server.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Send the body using chunked transfer encoding, in chunks of at most the
+    /// given number of bytes.
+    pub fn chunked(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    /// Wait for the given amount of time after sending each chunk of a chunked
+    /// body, which simulates a slow stream.
+    pub fn chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    fn write_to(&self, mut writer: impl Write, include_body: bool) -> io::Result<()> {
+        if self.delay > Duration::from_secs(0) {
+            thread::sleep(self.delay);
+        }
+
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\n",
+            self.status.as_str(),
+            self.status.canonical_reason().unwrap_or("")
+        )?;
+
+        for (name, value) in self.headers.iter() {
+            write!(writer, "{}: ", name)?;
+            writer.write_all(value.as_bytes())?;
+            writer.write_all(b"\r\n")?;
+        }
+
+        if self.chunk_size.is_some() {
+            writer.write_all(b"transfer-encoding: chunked\r\n")?;
+        } else if !self.headers.contains_key(http::header::CONTENT_LENGTH) {
+            write!(writer, "content-length: {}\r\n", self.body.len())?;
+        }
+
+        writer.write_all(b"\r\n")?;
+
+        if include_body {
+            match self.chunk_size {
+                Some(chunk_size) => {
+                    for chunk in self.body.chunks(chunk_size) {
+                        write!(writer, "{:x}\r\n", chunk.len())?;
+                        writer.write_all(chunk)?;
+                        writer.write_all(b"\r\n")?;
+                        writer.flush()?;
+
+                        if self.chunk_delay > Duration::from_secs(0) {
+                            thread::sleep(self.chunk_delay);
+                        }
+                    }
+
+                    writer.write_all(b"0\r\n\r\n")?;
+                }
+                None => writer.write_all(&self.body)?,
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    routes: Mutex<Vec<Route>>,
+    requests: Mutex<Vec<ReceivedRequest>>,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn response_for(&self, method: &Method, target: &str) -> Response {
+        let path = target.split('?').next().unwrap_or(target);
+
+        self.routes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|route| route.method == method && route.path == path)
+            .map(|route| route.response.clone())
+            .unwrap_or_else(|| Response::new().status(StatusCode::NOT_FOUND))
+    }
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    response: Response,
+}
+
+struct ReceivedRequest {
+    method: Method,
+    target: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl ReceivedRequest {
+    fn to_request(&self) -> Request<Vec<u8>> {
+        let mut builder = Request::builder()
+            .method(self.method.clone())
+            .uri(self.target.as_str());
+
+        if let Some(headers) = builder.headers_mut() {
+            *headers = self.headers.clone();
+        }
+
+        builder
+            .body(self.body.clone())
+            .expect("received request is invalid")
+    }
+}
+
+fn accept(listener: TcpListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+
+        if let Ok(stream) = stream {
+            let shared = shared.clone();
+
+            thread::spawn(move || {
+                let _ = serve(stream, &shared);
+            });
+        }
+    }
+}
+
+/// Serve requests sent over a connection until it is closed.
+fn serve(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    while !shared.shutdown.load(Ordering::SeqCst) {
+        let request = match read_request(&mut reader, &mut writer)? {
+            Some(request) => request,
+            None => break,
+        };
+
+        let close = request
+            .headers
+            .get(http::header::CONNECTION)
+            .filter(|value| value.as_bytes().eq_ignore_ascii_case(b"close"))
+            .is_some();
+
+        let response = shared.response_for(&request.method, &request.target);
+        let include_body = request.method != Method::HEAD;
+
+        shared.requests.lock().unwrap().push(request);
+
+        response.write_to(&mut writer, include_body)?;
+
+        if close {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the next request from a connection, or `None` if the connection was
+/// closed.
+fn read_request(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> io::Result<Option<ReceivedRequest>> {
+    let request_line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    let mut parts = request_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            let method = Method::from_bytes(method.as_bytes()).map_err(invalid_data)?;
+            (method, target.to_owned())
+        }
+        _ => return Err(invalid_data("malformed request line")),
+    };
+
+    let mut headers = HeaderMap::new();
+
+    loop {
+        let line = read_line(reader)?.ok_or_else(|| invalid_data("unexpected end of headers"))?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        let mut split = line.splitn(2, ':');
+        let name = split.next().unwrap_or_default();
+        let value = split
+            .next()
+            .ok_or_else(|| invalid_data("malformed header"))?
+            .trim();
+
+        headers.append(
+            HeaderName::try_from(name).map_err(invalid_data)?,
+            HeaderValue::try_from(value).map_err(invalid_data)?,
+        );
+    }
+
+    let expects_continue = headers
+        .get(http::header::EXPECT)
+        .filter(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+        .is_some();
+
+    if expects_continue {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        writer.flush()?;
+    }
+
+    let is_chunked = headers
+        .get(http::header::TRANSFER_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.to_ascii_lowercase().contains("chunked"))
+        .is_some();
+
+    let body = if is_chunked {
+        read_chunked_body(reader)?
+    } else {
+        let len = headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        let mut body = Vec::new();
+        reader.take(len).read_to_end(&mut body)?;
+        body
+    };
+
+    Ok(Some(ReceivedRequest {
+        method,
+        target,
+        headers,
+        body,
+    }))
+}
+
+fn read_chunked_body(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+
+    loop {
+        let line = read_line(reader)?.ok_or_else(|| invalid_data("unexpected end of body"))?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16).map_err(invalid_data)?;
+
+        if size == 0 {
+            // Skip any trailers.
+            while read_line(reader)?.filter(|line| !line.is_empty()).is_some() {}
+
+            return Ok(body);
+        }
+
+        reader.take(size).read_to_end(&mut body)?;
+        read_line(reader)?;
+    }
+}
+
+/// Read a line without its line ending, or `None` at the end of the stream.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(line.trim_end_matches(&['\r', '\n'][..]).to_owned()))
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, HttpClient, ReadResponseExt};
+    use std::time::Instant;
+
+    #[test]
+    fn routes_are_matched_by_method_and_path() {
+        let server = Server::new().unwrap();
+        server
+            .route(Method::GET, "/a", Response::new().body("a"))
+            .route(
+                Method::POST,
+                "/a",
+                Response::new()
+                    .status(StatusCode::CREATED)
+                    .header("x-test", "1"),
+            );
+
+        let client = HttpClient::new().unwrap();
+
+        let mut response = client.get(server.url("/a?q=1")).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().unwrap(), "a");
+
+        let response = client.post(server.url("a"), "hello").unwrap();
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.headers()["x-test"], "1");
+
+        let response = client.get(server.url("/b")).unwrap();
+        assert_eq!(response.status(), 404);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].uri(), "/a?q=1");
+        assert_eq!(requests[1].method(), Method::POST);
+        assert_eq!(requests[1].body(), b"hello");
+        assert_eq!(requests[2].uri(), "/b");
+    }
+
+    #[test]
+    fn chunked_request_and_response_bodies() {
+        let server = Server::new().unwrap();
+        server.route(
+            Method::PUT,
+            "/",
+            Response::new().body("hello world").chunked(3),
+        );
+
+        let body = Body::from_reader(io::Cursor::new(b"abc".to_vec()));
+        let mut response = HttpClient::new()
+            .unwrap()
+            .send(Request::put(server.url("/")).body(body).unwrap())
+            .unwrap();
+
+        assert_eq!(response.headers()["transfer-encoding"], "chunked");
+        assert_eq!(response.text().unwrap(), "hello world");
+        assert_eq!(server.requests()[0].body(), b"abc");
+    }
+
+    #[test]
+    fn responses_can_be_delayed() {
+        let server = Server::new().unwrap();
+        server.route(
+            Method::GET,
+            "/",
+            Response::new().delay(Duration::from_millis(200)),
+        );
+
+        let start = Instant::now();
+        crate::get(server.url("/")).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
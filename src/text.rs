@@ -2,8 +2,8 @@
 
 #![cfg(feature = "text-decoding")]
 
-use crate::headers::HasHeaders;
-use encoding_rs::{CoderResult, Encoding};
+use crate::{headers::HasHeaders, metrics::Metrics};
+use encoding_rs::{DecoderResult, Encoding};
 use futures_lite::io::{AsyncRead, AsyncReadExt};
 use http::Response;
 use std::{
@@ -32,6 +32,11 @@ macro_rules! decode_reader {
             };
 
             unread = decoder.push(&buf[..unread + len]).len();
+
+            // Stop reading once we've decoded as much as was asked for.
+            if decoder.is_full() {
+                break;
+            }
         }
 
         Ok(decoder.finish(&buf[..unread]))
@@ -69,6 +74,15 @@ pub(crate) struct Decoder {
 
     /// The output string that characters are accumulated to.
     output: String,
+
+    /// Maximum number of bytes of decoded text to produce, if any.
+    limit: Option<usize>,
+
+    /// Number of invalid sequences replaced so far.
+    replacements: u64,
+
+    /// Metrics of the response being decoded, if enabled.
+    metrics: Option<Metrics>,
 }
 
 impl Decoder {
@@ -77,11 +91,52 @@ impl Decoder {
         Self {
             decoder: encoding.new_decoder(),
             output: String::new(),
+            limit: None,
+            replacements: 0,
+            metrics: None,
+        }
+    }
+
+    /// Create a new decoder for the encoding with the given label, such as
+    /// `iso-8859-1`, returning an error if the label is not recognized.
+    pub(crate) fn for_label(label: &str) -> io::Result<Self> {
+        match Encoding::for_label(label.trim().as_bytes()) {
+            Some(encoding) => Ok(Self::new(encoding)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown character encoding '{}'", label),
+            )),
         }
     }
 
+    /// Limit the decoded text to at most the given number of bytes. Decoding
+    /// stops once the limit is reached, and the text is truncated to the
+    /// limit on a character boundary.
+    pub(crate) fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Count the replacements made by the decoder in the given metrics.
+    pub(crate) fn with_metrics(mut self, metrics: Option<&Metrics>) -> Self {
+        self.metrics = metrics.cloned();
+        self
+    }
+
+    /// Check whether the decoder has produced as much text as it is limited
+    /// to.
+    fn is_full(&self) -> bool {
+        self.limit
+            .map(|limit| self.output.len() >= limit)
+            .unwrap_or(false)
+    }
+
     /// Create a new encoder suitable for decoding the given response.
     pub(crate) fn for_response<T>(response: &Response<T>) -> Self {
+        Self::for_content_type(response).with_metrics(response.extensions().get())
+    }
+
+    fn for_content_type<T>(response: &Response<T>) -> Self {
         if let Some(content_type) = response
             .content_type()
             .and_then(|header| header.parse::<mime::Mime>().ok())
@@ -105,6 +160,14 @@ impl Decoder {
         decode_reader!(self, buf, reader.read(buf))
     }
 
+    /// Create a future that immediately fails with the given error.
+    pub(crate) fn error_async<'r, R>(error: io::Error) -> TextFuture<'r, R> {
+        TextFuture {
+            inner: Box::pin(async move { Err(error) }),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Consume this decoder to decode text from a given asynchronous reader.
     pub(crate) fn decode_reader_async<'r, R>(self, mut reader: R) -> TextFuture<'r, R>
     where
@@ -126,19 +189,49 @@ impl Decoder {
     /// the resulting string.
     pub(crate) fn finish(mut self, buf: &[u8]) -> String {
         self.decode(buf, true);
+
+        if let Some(metrics) = self.metrics.as_ref() {
+            metrics
+                .inner
+                .decode_replacements
+                .fetch_add(self.replacements);
+        }
+
+        if let Some(limit) = self.limit {
+            if self.output.len() > limit {
+                let mut end = limit;
+
+                while !self.output.is_char_boundary(end) {
+                    end -= 1;
+                }
+
+                self.output.truncate(end);
+            }
+        }
+
         self.output
     }
 
     fn decode<'b>(&mut self, mut buf: &'b [u8], last: bool) -> &'b [u8] {
         loop {
-            let (result, consumed, _) = self.decoder.decode_to_string(buf, &mut self.output, last);
+            let (result, consumed) =
+                self.decoder
+                    .decode_to_string_without_replacement(buf, &mut self.output, last);
             buf = &buf[consumed..];
 
             match result {
-                CoderResult::InputEmpty => break,
-                CoderResult::OutputFull => self
-                    .output
-                    .reserve(self.decoder.max_utf8_buffer_length(buf.len()).unwrap()),
+                DecoderResult::InputEmpty => break,
+                DecoderResult::OutputFull => self.output.reserve(
+                    self.decoder
+                        .max_utf8_buffer_length_without_replacement(buf.len())
+                        .unwrap(),
+                ),
+                // Replace malformed sequences ourselves so that they can be
+                // counted.
+                DecoderResult::Malformed(..) => {
+                    self.output.push('\u{fffd}');
+                    self.replacements += 1;
+                }
             }
         }
 
@@ -167,6 +260,43 @@ mod tests {
         assert_eq!(decoder.finish(b"world"), "hello world");
     }
 
+    #[test]
+    fn decode_with_charset_label() {
+        let decoder = Decoder::for_label("iso-8859-1").unwrap();
+
+        assert_eq!(decoder.decode_reader(&b"caf\xe9"[..]).unwrap(), "café");
+        assert!(Decoder::for_label("not-a-charset").is_err());
+    }
+
+    #[test]
+    fn decode_with_limit_stops_on_char_boundary() {
+        let decoder = Decoder::new(encoding_rs::UTF_8).with_limit(4);
+
+        // "é" is two bytes, so it cannot fit within the limit.
+        assert_eq!(decoder.decode_reader("abcé and more".as_bytes()).unwrap(), "abc");
+    }
+
+    #[test]
+    fn decode_with_limit_does_not_read_entire_body() {
+        let mut body = io::Cursor::new(vec![b'a'; 100_000]);
+        let text = Decoder::new(encoding_rs::UTF_8)
+            .with_limit(10)
+            .decode_reader(&mut body)
+            .unwrap();
+
+        assert_eq!(text, "aaaaaaaaaa");
+        assert!(body.position() < 100_000);
+    }
+
+    #[test]
+    fn invalid_sequences_are_replaced_and_counted() {
+        let mut decoder = Decoder::new(encoding_rs::UTF_8);
+
+        assert_eq!(decoder.push(b"a\xffb\xc3"), b"");
+        assert_eq!(decoder.replacements, 1);
+        assert_eq!(decoder.finish(b"c"), "a\u{fffd}b\u{fffd}c");
+    }
+
     #[test]
     fn utf16_decode() {
         let bytes = encoding_rs::UTF_16BE.encode("hello world!").0.into_owned();
@@ -0,0 +1,433 @@
+//! Raw tunnels through HTTP proxies.
+//!
+//! Curl establishes the tunnel by sending a `CONNECT` request to the proxy
+//! using its `CONNECT_ONLY` mode. After that, data is sent and received using
+//! `curl_easy_send` and `curl_easy_recv`, so that curl takes care of any TLS
+//! used to talk to the proxy.
+//!
+//! A connection made with `CONNECT_ONLY` is closed when its easy handle is
+//! removed from a multi handle, so the connection cannot be driven by an
+//! agent. Instead, each tunnel gets a thread of its own, which owns the easy
+//! handle for as long as the tunnel is open, just like WebSocket connections.
+
+use crate::{
+    auth::{Authentication, Credentials},
+    client::Defaults,
+    config::{
+        dial::{Dialer, SocketOpener},
+        dns::ResolveMap,
+        internal::SetOpt,
+        proxy::{Blacklist, Proxy},
+        ConnectTimeout,
+        IpVersion,
+        NetworkInterface,
+        SslOption,
+        TcpKeepAlive,
+        TcpNoDelay,
+        Timeout,
+    },
+    error::{Error, ErrorKind},
+    pool,
+    task::UdpWaker,
+};
+use curl::{easy::Easy2, multi::WaitFd};
+use flume::{Receiver, Sender, TryRecvError};
+use futures_lite::{
+    future::{block_on, poll_fn, Future},
+    io::{AsyncRead, AsyncWrite},
+    ready,
+    stream::Stream,
+};
+use http::{uri::Authority, Uri};
+use std::{
+    fmt,
+    io::{self, Cursor, Read, Write},
+    net::UdpSocket,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum number of chunks that may be queued for writing before the writer
+/// must wait for the tunnel to catch up.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Handler for a connection whose data is sent and received directly.
+pub(crate) struct NullHandler;
+
+impl curl::easy::Handler for NullHandler {}
+
+/// Open a tunnel to the given authority using the given client defaults,
+/// establishing it on a background thread.
+pub(crate) async fn connect(authority: &Authority, options: Defaults<'_>) -> Result<Tunnel, Error> {
+    if authority.port_u16().is_none() {
+        return Err(Error::with_context(
+            ErrorKind::InvalidRequest,
+            Some(String::from("tunnel authorities must include a port")),
+            io::Error::new(io::ErrorKind::InvalidInput, "missing port"),
+        ));
+    }
+
+    let uri = Uri::builder()
+        .scheme("http")
+        .authority(authority.clone())
+        .path_and_query("/")
+        .build()?;
+
+    let mut easy = Easy2::new(NullHandler);
+
+    easy.signal(false)?;
+    easy.url(&uri.to_string())?;
+    easy.connect_only(true)?;
+    easy.http_proxy_tunnel(true)?;
+
+    macro_rules! set_opts {
+        ($easy:expr, $options:expr, [$($option:ty,)*]) => {{
+            $(
+                if let Some(extension) = $options.get::<$option>() {
+                    extension.set_opt($easy)?;
+                }
+            )*
+        }};
+    }
+
+    set_opts!(&mut easy, options, [
+        Timeout,
+        ConnectTimeout,
+        TcpKeepAlive,
+        TcpNoDelay,
+        NetworkInterface,
+        Dialer,
+        IpVersion,
+        ResolveMap,
+        Proxy<Option<Uri>>,
+        Blacklist,
+        Proxy<Authentication>,
+        Proxy<Credentials>,
+        SslOption,
+    ]);
+
+    // Connected sockets and custom dialers open sockets in place of curl. The
+    // connection keeps the opener alive for as long as curl may call it.
+    let mut socket_opener = options
+        .get::<Dialer>()
+        .and_then(|dialer| dialer.socket_opener(&uri));
+
+    if let Some(opener) = &mut socket_opener {
+        opener.set_callbacks(&mut easy)?;
+    }
+
+    let wake_socket = UdpSocket::bind("127.0.0.1:0")?;
+    wake_socket.set_nonblocking(true)?;
+    let waker = Waker::from(UdpWaker::connect(wake_socket.local_addr()?)?);
+
+    let (ready_tx, ready_rx) = flume::bounded(1);
+    let (outgoing_tx, outgoing_rx) = flume::bounded(CHANNEL_CAPACITY);
+    let (incoming_tx, incoming_rx) = flume::unbounded();
+
+    thread::Builder::new()
+        .name(String::from("isahc-tunnel"))
+        .spawn(move || {
+            let result = easy
+                .perform()
+                .map_err(Error::from)
+                .and_then(|()| active_socket(&easy));
+
+            let socket = match result {
+                Ok(socket) => socket,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let mut connection = Connection {
+                easy,
+                socket,
+                _socket_opener: socket_opener,
+                multi: curl::multi::Multi::new(),
+                wake_socket,
+                outgoing: outgoing_rx,
+                incoming: incoming_tx,
+                write_buffer: Vec::new(),
+            };
+
+            if ready_tx.send(Ok(())).is_ok() {
+                if let Err(e) = connection.run() {
+                    tracing::debug!("tunnel failed: {}", e);
+                    let _ = connection.incoming.send(Err(e.into()));
+                }
+            }
+        })?;
+
+    ready_rx
+        .recv_async()
+        .await
+        .map_err(|e| Error::new(ErrorKind::Unknown, e))??;
+
+    Ok(Tunnel {
+        outgoing: Some(outgoing_tx),
+        pending: None,
+        incoming: incoming_rx.into_stream(),
+        chunk: Cursor::new(Vec::new()),
+        waker,
+    })
+}
+
+/// A raw, bidirectional byte stream through a tunnel established by an HTTP
+/// proxy, as returned by
+/// [`HttpClient::connect_tunnel`](crate::HttpClient::connect_tunnel).
+///
+/// Bytes written are sent to the other end of the tunnel as-is, and bytes
+/// received from it can be read, so any protocol can be spoken over the
+/// tunnel. Written data is sent in the background; once a small internal
+/// buffer is full, writes wait for the tunnel to catch up.
+///
+/// Closing the tunnel, or dropping it, closes the connection once all data
+/// written before has been sent.
+pub struct Tunnel {
+    outgoing: Option<Sender<Vec<u8>>>,
+    pending: Option<flume::r#async::SendFut<'static, Vec<u8>>>,
+    incoming: flume::r#async::RecvStream<'static, io::Result<Vec<u8>>>,
+    chunk: Cursor<Vec<u8>>,
+
+    /// Wakes up the tunnel thread when there is data to send.
+    waker: Waker,
+}
+
+impl AsyncRead for Tunnel {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let len = self.chunk.read(buf)?;
+
+            if len > 0 || buf.is_empty() {
+                return Poll::Ready(Ok(len));
+            }
+
+            match ready!(Pin::new(&mut self.incoming).poll_next(cx)) {
+                Some(Ok(chunk)) => self.chunk = Cursor::new(chunk),
+                Some(Err(e)) => return Poll::Ready(Err(e)),
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Tunnel {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        let sender = match self.outgoing.as_ref() {
+            Some(sender) => sender.clone(),
+            None => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        };
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        // The data is copied into the send future, so we can report it as
+        // written right away and wait for the send to complete on the next
+        // write or flush.
+        let mut send = sender.into_send_async(buf.to_vec());
+
+        match Pin::new(&mut send).poll(cx) {
+            Poll::Ready(Ok(())) => self.waker.wake_by_ref(),
+            Poll::Ready(Err(_)) => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+            Poll::Pending => self.pending = Some(send),
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(send) = self.pending.as_mut() {
+            let result = ready!(Pin::new(send).poll(cx));
+            self.pending = None;
+
+            if result.is_err() {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+
+            self.waker.wake_by_ref();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+
+        if self.outgoing.take().is_some() {
+            self.waker.wake_by_ref();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Read for Tunnel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        block_on(poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)))
+    }
+}
+
+impl Write for Tunnel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(poll_fn(|cx| Pin::new(&mut *self).poll_write(cx, buf)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        block_on(poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)))
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        // Let the tunnel thread notice that the tunnel is gone.
+        self.waker.wake_by_ref();
+    }
+}
+
+impl fmt::Debug for Tunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tunnel").finish()
+    }
+}
+
+struct Connection {
+    easy: Easy2<NullHandler>,
+    socket: curl_sys::curl_socket_t,
+
+    /// Opens sockets in place of curl, if a connected socket or custom dialer
+    /// is used.
+    _socket_opener: Option<Box<SocketOpener>>,
+
+    /// An empty multi handle, only used for waiting on sockets.
+    multi: curl::multi::Multi,
+
+    /// Used to wake up the thread when there is data to send.
+    wake_socket: UdpSocket,
+
+    outgoing: Receiver<Vec<u8>>,
+    incoming: Sender<io::Result<Vec<u8>>>,
+    write_buffer: Vec<u8>,
+}
+
+impl Connection {
+    /// Pass data through the tunnel until either side closes it.
+    fn run(&mut self) -> Result<(), Error> {
+        loop {
+            // Only take more data once the previous chunk has been sent, so
+            // that writers wait for a slow connection.
+            if self.write_buffer.is_empty() {
+                match self.outgoing.try_recv() {
+                    Ok(chunk) => self.write_buffer = chunk,
+                    Err(TryRecvError::Empty) => {}
+
+                    // The tunnel was closed and everything written was sent.
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+
+            let mut chunk = [0; 16384];
+
+            loop {
+                match self.easy.recv(&mut chunk) {
+                    Ok(0) => {
+                        tracing::debug!("tunnel closed by peer");
+                        return Ok(());
+                    }
+
+                    // The reader may have been dropped, which is fine.
+                    Ok(len) => {
+                        let _ = self.incoming.send(Ok(chunk[..len].to_vec()));
+                    }
+                    Err(e) if e.is_again() => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            while !self.write_buffer.is_empty() {
+                match self.easy.send(&self.write_buffer) {
+                    Ok(len) => {
+                        self.write_buffer.drain(..len);
+                    }
+                    Err(e) if e.is_again() => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            wait(
+                &self.multi,
+                Some(self.socket),
+                !self.write_buffer.is_empty(),
+                &self.wake_socket,
+            )?;
+        }
+    }
+}
+
+/// Get the socket of a connection made with `CONNECT_ONLY`.
+pub(crate) fn active_socket(easy: &Easy2<NullHandler>) -> Result<curl_sys::curl_socket_t, Error> {
+    pool::active_socket(easy.raw())?.ok_or_else(|| {
+        Error::new(
+            ErrorKind::ConnectionFailed,
+            io::Error::from(io::ErrorKind::NotConnected),
+        )
+    })
+}
+
+/// Wait until the given socket can make progress, or until woken up using
+/// the given wake socket.
+pub(crate) fn wait(
+    multi: &curl::multi::Multi,
+    socket: Option<curl_sys::curl_socket_t>,
+    write: bool,
+    wake_socket: &UdpSocket,
+) -> Result<(), Error> {
+    let mut socket_fd = WaitFd::new();
+
+    if let Some(socket) = socket {
+        socket_fd.set_fd(socket);
+    }
+
+    socket_fd.poll_on_read(true);
+    socket_fd.poll_on_write(write);
+
+    let mut wake_fd = WaitFd::new();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        wake_fd.set_fd(wake_socket.as_raw_fd());
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawSocket;
+        wake_fd.set_fd(wake_socket.as_raw_socket());
+    }
+
+    wake_fd.poll_on_read(true);
+
+    let mut fds = [socket_fd, wake_fd];
+    multi.wait(&mut fds, WAIT_TIMEOUT)?;
+
+    if fds[1].received_read() {
+        let _ = wake_socket.recv_from(&mut [0; 1024]);
+    }
+
+    Ok(())
+}
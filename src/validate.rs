@@ -0,0 +1,249 @@
+//! Validation of requests before they are handed over to curl.
+//!
+//! The types from the `http` crate already reject most invalid input, but
+//! values can be constructed unchecked, and some characters that are not valid
+//! in a URI are accepted anyway. Since curl writes the request mostly as-is, a
+//! line break in any part of the request could be used to inject headers or
+//! even additional requests, so we check everything once more.
+
+use crate::error::{RequestComponent, RequestValidationError};
+use http::{uri::PathAndQuery, Request, Uri};
+use std::fmt::Write;
+
+/// Validate the given request, rejecting any control characters.
+///
+/// Characters in the URI path and query that are not allowed by RFC 3986 but
+/// are otherwise harmless are percent-encoded, unless `strict` is enabled, in
+/// which case they are rejected as well.
+pub(crate) fn validate_request<T>(
+    request: &mut Request<T>,
+    strict: bool,
+) -> Result<(), RequestValidationError> {
+    if let Some(&byte) = request
+        .method()
+        .as_str()
+        .as_bytes()
+        .iter()
+        .find(|&&byte| !is_token_byte(byte))
+    {
+        return Err(RequestValidationError::new(RequestComponent::Method, byte));
+    }
+
+    if let Some(uri) = encode_uri(request.uri(), strict)? {
+        *request.uri_mut() = uri;
+    }
+
+    for (name, value) in request.headers() {
+        if let Some(&byte) = value
+            .as_bytes()
+            .iter()
+            .find(|&&byte| !is_field_value_byte(byte))
+        {
+            return Err(RequestValidationError::new(
+                RequestComponent::HeaderValue(name.clone()),
+                byte,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the size of the headers of the given request, counted the way HTTP/2
+/// limits them with `SETTINGS_MAX_HEADER_LIST_SIZE`: the length of each name
+/// and value plus 32 bytes of overhead per field, including the pseudo-header
+/// fields for the method, scheme, authority and path.
+pub(crate) fn header_list_size<T>(request: &Request<T>) -> usize {
+    const OVERHEAD: usize = 32;
+
+    let uri = request.uri();
+    let pseudo_headers = [
+        (":method".len(), request.method().as_str().len()),
+        (":scheme".len(), uri.scheme_str().unwrap_or("").len()),
+        (
+            ":authority".len(),
+            uri.authority().map(|a| a.as_str().len()).unwrap_or(0),
+        ),
+        (
+            ":path".len(),
+            uri.path_and_query().map(|p| p.as_str().len()).unwrap_or(1),
+        ),
+    ];
+
+    let pseudo_headers_size = pseudo_headers
+        .iter()
+        .map(|(name, value)| name + value + OVERHEAD)
+        .sum::<usize>();
+
+    let headers_size = request
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + OVERHEAD)
+        .sum::<usize>();
+
+    pseudo_headers_size + headers_size
+}
+
+/// Check the given URI, returning a percent-encoded copy if it contains any
+/// characters that need to be encoded.
+fn encode_uri(uri: &Uri, strict: bool) -> Result<Option<Uri>, RequestValidationError> {
+    let invalid = |byte| RequestValidationError::new(RequestComponent::Uri, byte);
+
+    if let Some(authority) = uri.authority() {
+        if let Some(&byte) = authority
+            .as_str()
+            .as_bytes()
+            .iter()
+            .find(|&&byte| !byte.is_ascii_graphic())
+        {
+            return Err(invalid(byte));
+        }
+    }
+
+    let path_and_query = match uri.path_and_query() {
+        Some(path_and_query) => path_and_query.as_str(),
+        None => return Ok(None),
+    };
+
+    let mut needs_encoding = false;
+
+    for &byte in path_and_query.as_bytes() {
+        if !byte.is_ascii_graphic() {
+            return Err(invalid(byte));
+        }
+
+        if needs_percent_encoding(byte) {
+            if strict {
+                return Err(invalid(byte));
+            }
+
+            needs_encoding = true;
+        }
+    }
+
+    if !needs_encoding {
+        return Ok(None);
+    }
+
+    let mut encoded = String::with_capacity(path_and_query.len() + 8);
+
+    for &byte in path_and_query.as_bytes() {
+        if needs_percent_encoding(byte) {
+            write!(encoded, "%{:02X}", byte).unwrap();
+        } else {
+            encoded.push(byte as char);
+        }
+    }
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(
+        encoded
+            .parse::<PathAndQuery>()
+            .map_err(|_| invalid(b'%'))?,
+    );
+
+    Ok(Uri::from_parts(parts).ok())
+}
+
+/// Characters that are accepted in a path by [`Uri`], but are not allowed by
+/// RFC 3986 and need to be percent-encoded.
+fn needs_percent_encoding(byte: u8) -> bool {
+    match byte {
+        b'"' | b'<' | b'>' | b'\\' | b'^' | b'`' | b'{' | b'|' | b'}' => true,
+        _ => false,
+    }
+}
+
+/// Characters allowed in a token, such as a method name.
+fn is_token_byte(byte: u8) -> bool {
+    match byte {
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_'
+        | b'`' | b'|' | b'~' => true,
+        _ => byte.is_ascii_alphanumeric(),
+    }
+}
+
+/// Characters allowed in a header value. Besides control characters, we also
+/// reject non-ASCII bytes, since header values are passed to curl as strings.
+fn is_field_value_byte(byte: u8) -> bool {
+    byte == b'\t' || byte == b' ' || byte.is_ascii_graphic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use test_case::test_case;
+
+    #[test_case("http://example.org/a/b?c=d", "http://example.org/a/b?c=d")]
+    #[test_case("http://example.org/{id}", "http://example.org/%7Bid%7D")]
+    #[test_case("http://example.org/a|b\"c", "http://example.org/a%7Cb%22c")]
+    #[test_case("http://example.org/a%20b", "http://example.org/a%20b")]
+    fn uri_is_percent_encoded(uri: &str, expected: &str) {
+        let mut request = Request::get(uri).body(()).unwrap();
+
+        validate_request(&mut request, false).unwrap();
+
+        assert_eq!(request.uri(), expected);
+    }
+
+    #[test]
+    fn strict_mode_rejects_unencoded_uri() {
+        let mut request = Request::get("http://example.org/{id}").body(()).unwrap();
+
+        let error = validate_request(&mut request, true).unwrap_err();
+
+        assert_eq!(error.component(), &RequestComponent::Uri);
+        assert_eq!(error.byte(), b'{');
+    }
+
+    #[test_case(b'\r', false)]
+    #[test_case(b'\n', false)]
+    #[test_case(b'\0', false)]
+    #[test_case(b'\x7F', false)]
+    #[test_case(0xC3, false)]
+    #[test_case(b'\t', true)]
+    #[test_case(b' ', true)]
+    #[test_case(b'a', true)]
+    fn field_value_bytes(byte: u8, allowed: bool) {
+        assert_eq!(is_field_value_byte(byte), allowed);
+    }
+
+    #[test]
+    fn non_ascii_header_value_is_rejected() {
+        let mut request = Request::get("http://example.org")
+            .header("X-Name", HeaderValue::from_bytes(b"caf\xC3\xA9").unwrap())
+            .body(())
+            .unwrap();
+
+        let error = validate_request(&mut request, false).unwrap_err();
+
+        assert_eq!(
+            error.component(),
+            &RequestComponent::HeaderValue(http::header::HeaderName::from_static("x-name"))
+        );
+        assert_eq!(error.byte(), 0xC3);
+    }
+
+    #[test]
+    fn valid_header_value_is_accepted() {
+        let mut request = Request::get("http://example.org")
+            .header("X-Name", "hello\tworld; q=1")
+            .body(())
+            .unwrap();
+
+        assert!(validate_request(&mut request, true).is_ok());
+    }
+
+    #[test]
+    fn header_list_size_includes_pseudo_headers_and_overhead() {
+        let request = Request::get("https://example.org/a")
+            .header("x-token", "abc")
+            .body(())
+            .unwrap();
+
+        // :method GET, :scheme https, :authority example.org, :path /a and
+        // x-token abc, each with 32 bytes of overhead.
+        assert_eq!(header_list_size(&request), 42 + 44 + 53 + 39 + 42);
+    }
+}
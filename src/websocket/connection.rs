@@ -0,0 +1,394 @@
+//! The background thread driving a WebSocket connection.
+//!
+//! Curl establishes the connection, including any TLS handshake and proxy
+//! tunnel, using its `CONNECT_ONLY` mode. After that, the handshake and the
+//! frames of the connection are sent and received using `curl_easy_send` and
+//! `curl_easy_recv`, so that curl takes care of TLS.
+//!
+//! A connection made with `CONNECT_ONLY` is closed when its easy handle is
+//! removed from a multi handle, so the connection cannot be driven by an
+//! agent. Instead, each connection gets a thread of its own, which owns the
+//! easy handle for as long as the connection is open. An empty multi handle is
+//! used to wait for the socket to become ready, along with a socket to wake
+//! the thread up when there are messages to send, just like an agent does.
+
+use super::{
+    frame::{self, Decoder, CLOSE_NORMAL},
+    handshake,
+    CloseFrame,
+    Message,
+    WebSocket,
+};
+use crate::{
+    auth::{Authentication, Credentials},
+    client::Defaults,
+    config::{
+        dial::{Dialer, SocketOpener},
+        dns::ResolveMap,
+        internal::SetOpt,
+        proxy::{Blacklist, Proxy},
+        CaCertificate,
+        ClientCertificate,
+        ConnectTimeout,
+        IpVersion,
+        NetworkInterface,
+        PrivateKey,
+        SslOption,
+        TcpKeepAlive,
+        TcpNoDelay,
+        Timeout,
+    },
+    error::{Error, ErrorKind},
+    random::random,
+    task::UdpWaker,
+    tunnel::{self, NullHandler},
+};
+use curl::easy::{Easy2, HttpVersion};
+use flume::{Receiver, Sender, TryRecvError};
+use http::{HeaderMap, Uri};
+use std::{io, net::UdpSocket, task::Waker, thread, time::Instant};
+
+/// Open a WebSocket connection to the given URI using the given client
+/// defaults, performing the handshake on a background thread.
+pub(crate) async fn connect(
+    uri: &Uri,
+    options: Defaults<'_>,
+    headers: &HeaderMap,
+) -> Result<WebSocket, Error> {
+    let scheme = match uri.scheme_str() {
+        Some("ws") | Some("http") => "http",
+        Some("wss") | Some("https") => "https",
+        _ => {
+            return Err(Error::with_context(
+                ErrorKind::InvalidRequest,
+                Some(String::from("WebSocket URIs must use the ws or wss scheme")),
+                io::Error::new(io::ErrorKind::InvalidInput, "unsupported scheme"),
+            ));
+        }
+    };
+
+    let authority = uri.authority().ok_or_else(|| {
+        Error::with_context(
+            ErrorKind::InvalidRequest,
+            Some(String::from("WebSocket URIs must include a host")),
+            io::Error::new(io::ErrorKind::InvalidInput, "missing host"),
+        )
+    })?;
+
+    let mut easy = Easy2::new(NullHandler);
+
+    easy.signal(false)?;
+    easy.url(&format!("{}://{}/", scheme, authority))?;
+    easy.connect_only(true)?;
+    easy.http_proxy_tunnel(true)?;
+
+    // The upgrade is only defined for HTTP/1.1, so ALPN must not negotiate
+    // anything else.
+    easy.http_version(HttpVersion::V11)?;
+
+    macro_rules! set_opts {
+        ($easy:expr, $options:expr, [$($option:ty,)*]) => {{
+            $(
+                if let Some(extension) = $options.get::<$option>() {
+                    extension.set_opt($easy)?;
+                }
+            )*
+        }};
+    }
+
+    set_opts!(&mut easy, options, [
+        Timeout,
+        ConnectTimeout,
+        TcpKeepAlive,
+        TcpNoDelay,
+        NetworkInterface,
+        Dialer,
+        IpVersion,
+        ResolveMap,
+        Proxy<Option<Uri>>,
+        Blacklist,
+        Proxy<Authentication>,
+        Proxy<Credentials>,
+        ClientCertificate,
+        PrivateKey,
+        CaCertificate,
+        SslOption,
+    ]);
+
+    // Connected sockets and custom dialers open sockets in place of curl. The
+    // connection keeps the opener alive for as long as curl may call it.
+    let mut socket_opener = options
+        .get::<Dialer>()
+        .and_then(|dialer| dialer.socket_opener(uri));
+
+    if let Some(opener) = &mut socket_opener {
+        opener.set_callbacks(&mut easy)?;
+    }
+
+    let timeout = options.get::<Timeout>().map(|timeout| timeout.0);
+    let key = handshake::key();
+    let request = handshake::request(uri, &key, headers);
+
+    let wake_socket = UdpSocket::bind("127.0.0.1:0")?;
+    wake_socket.set_nonblocking(true)?;
+    let waker = Waker::from(UdpWaker::connect(wake_socket.local_addr()?)?);
+
+    let (ready_tx, ready_rx) = flume::bounded(1);
+    let (outgoing_tx, outgoing_rx) = flume::unbounded();
+    let (incoming_tx, incoming_rx) = flume::unbounded();
+
+    thread::Builder::new()
+        .name(String::from("isahc-websocket"))
+        .spawn(move || {
+            let mut connection = Connection {
+                easy,
+                socket: None,
+                _socket_opener: socket_opener,
+                multi: curl::multi::Multi::new(),
+                wake_socket,
+                outgoing: outgoing_rx,
+                incoming: incoming_tx,
+                decoder: Decoder::default(),
+                write_buffer: Vec::new(),
+                close_sent: false,
+                close_received: false,
+            };
+
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let result = connection.handshake(request, &key, deadline);
+            let ok = result.is_ok();
+
+            if ready_tx.send(result).is_ok() && ok {
+                if let Err(e) = connection.run() {
+                    tracing::debug!("WebSocket connection failed: {}", e);
+                    let _ = connection.incoming.send(Err(e));
+                }
+            }
+        })?;
+
+    ready_rx
+        .recv_async()
+        .await
+        .map_err(|e| Error::new(ErrorKind::Unknown, e))??;
+
+    Ok(WebSocket {
+        outgoing: outgoing_tx,
+        incoming: incoming_rx,
+        waker,
+    })
+}
+
+struct Connection {
+    easy: Easy2<NullHandler>,
+    socket: Option<curl_sys::curl_socket_t>,
+
+    /// Opens sockets in place of curl, if a connected socket or custom dialer
+    /// is used.
+    _socket_opener: Option<Box<SocketOpener>>,
+
+    /// An empty multi handle, only used for waiting on sockets.
+    multi: curl::multi::Multi,
+
+    /// Used to wake up the thread when there are messages to send.
+    wake_socket: UdpSocket,
+
+    outgoing: Receiver<Message>,
+    incoming: Sender<Result<Message, Error>>,
+    decoder: Decoder,
+    write_buffer: Vec<u8>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl Connection {
+    /// Connect to the server and upgrade the connection.
+    fn handshake(
+        &mut self,
+        request: Vec<u8>,
+        key: &str,
+        deadline: Option<Instant>,
+    ) -> Result<(), Error> {
+        self.easy.perform()?;
+        self.socket = Some(tunnel::active_socket(&self.easy)?);
+        self.write_buffer = request;
+
+        let mut response = Vec::new();
+
+        loop {
+            self.flush()?;
+
+            if self.read(&mut response)? {
+                return Err(Error::with_context(
+                    ErrorKind::ProtocolViolation,
+                    Some(String::from("connection closed during WebSocket handshake")),
+                    io::Error::from(io::ErrorKind::UnexpectedEof),
+                ));
+            }
+
+            if let Some((len, status, headers)) = handshake::parse_response(&response)? {
+                handshake::verify(status, &headers, key)?;
+
+                // The server may start sending frames right away.
+                self.decoder.push(&response[len..]);
+
+                return Ok(());
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return Err(Error::new(
+                        ErrorKind::Timeout,
+                        io::Error::new(io::ErrorKind::TimedOut, "WebSocket handshake timed out"),
+                    ));
+                }
+            }
+
+            self.wait()?;
+        }
+    }
+
+    /// Exchange messages until the connection is closed.
+    fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.queue_outgoing();
+
+            let mut received = Vec::new();
+            let eof = self.read(&mut received)?;
+            self.decoder.push(&received);
+            self.receive_messages()?;
+
+            self.flush()?;
+
+            if self.close_sent && self.close_received && self.write_buffer.is_empty() {
+                tracing::debug!("WebSocket connection closed");
+                return Ok(());
+            }
+
+            if eof {
+                if self.close_sent && self.write_buffer.is_empty() {
+                    return Ok(());
+                }
+
+                return Err(Error::with_context(
+                    ErrorKind::ConnectionFailed,
+                    Some(String::from(
+                        "WebSocket connection closed without a close frame",
+                    )),
+                    io::Error::from(io::ErrorKind::UnexpectedEof),
+                ));
+            }
+
+            self.wait()?;
+        }
+    }
+
+    /// Encode messages to send for writing.
+    fn queue_outgoing(&mut self) {
+        loop {
+            match self.outgoing.try_recv() {
+                Ok(message) => self.queue(&message),
+                Err(TryRecvError::Empty) => break,
+
+                // The WebSocket was dropped, so close the connection.
+                Err(TryRecvError::Disconnected) => {
+                    if !self.close_sent {
+                        self.queue(&Message::Close(Some(CloseFrame::new(CLOSE_NORMAL, ""))));
+                    }
+
+                    // Nobody is left to wait for the reply of the server.
+                    self.close_received = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn queue(&mut self, message: &Message) {
+        // Nothing may be sent after a close frame.
+        if self.close_sent {
+            return;
+        }
+
+        if let Message::Close(_) = message {
+            self.close_sent = true;
+        }
+
+        let mask = (random() as u32).to_ne_bytes();
+        self.write_buffer.extend(frame::encode(message, mask));
+    }
+
+    /// Handle the messages received from the server.
+    fn receive_messages(&mut self) -> Result<(), Error> {
+        loop {
+            let message = match self.decoder.next_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(()),
+                Err((code, error)) => {
+                    // Tell the server why the connection is closed, if
+                    // possible.
+                    self.queue(&Message::Close(Some(CloseFrame::new(code, ""))));
+                    let _ = self.flush();
+
+                    return Err(error);
+                }
+            };
+
+            match &message {
+                Message::Ping(data) => self.queue(&Message::Pong(data.clone())),
+                Message::Close(frame) => {
+                    self.close_received = true;
+
+                    // Reply with the same code, as suggested by the RFC.
+                    if !self.close_sent {
+                        let code = frame.as_ref().map_or(CLOSE_NORMAL, CloseFrame::code);
+                        self.queue(&Message::Close(Some(CloseFrame::new(code, ""))));
+                    }
+                }
+                _ => {}
+            }
+
+            // The receiver may have been dropped, which is fine.
+            let _ = self.incoming.send(Ok(message));
+        }
+    }
+
+    /// Read all data available from the connection. Returns `true` if the
+    /// server closed the connection.
+    fn read(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Error> {
+        let mut chunk = [0; 16384];
+
+        loop {
+            match self.easy.recv(&mut chunk) {
+                Ok(0) => return Ok(true),
+                Ok(len) => buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.is_again() => return Ok(false),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Write as much of the pending data as possible.
+    fn flush(&mut self) -> Result<(), Error> {
+        while !self.write_buffer.is_empty() {
+            match self.easy.send(&self.write_buffer) {
+                Ok(len) => {
+                    self.write_buffer.drain(..len);
+                }
+                Err(e) if e.is_again() => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait until the connection can make progress, or until woken up.
+    fn wait(&mut self) -> Result<(), Error> {
+        tunnel::wait(
+            &self.multi,
+            self.socket,
+            !self.write_buffer.is_empty(),
+            &self.wake_socket,
+        )
+    }
+}
@@ -0,0 +1,383 @@
+//! Encoding and decoding of WebSocket frames, as described in [RFC 6455,
+//! section 5](https://tools.ietf.org/html/rfc6455#section-5).
+
+use super::{CloseFrame, Message};
+use crate::error::{Error, ErrorKind};
+use std::io;
+
+/// The largest message accepted from the server, to avoid running out of
+/// memory because of a misbehaving server.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// Close code for a connection closed normally.
+pub(crate) const CLOSE_NORMAL: u16 = 1000;
+
+/// Close code for a connection closed because of a protocol error.
+pub(crate) const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Close code for a connection closed because a message was too big.
+pub(crate) const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Encode a message as a single masked frame, as sent by clients.
+pub(crate) fn encode(message: &Message, mask: [u8; 4]) -> Vec<u8> {
+    let close_payload;
+    let (opcode, payload) = match message {
+        Message::Text(text) => (OPCODE_TEXT, text.as_bytes()),
+        Message::Binary(data) => (OPCODE_BINARY, &data[..]),
+        Message::Ping(data) => (OPCODE_PING, &data[..]),
+        Message::Pong(data) => (OPCODE_PONG, &data[..]),
+        Message::Close(frame) => {
+            close_payload = frame
+                .as_ref()
+                .map(|frame| {
+                    let mut payload = frame.code().to_be_bytes().to_vec();
+                    payload.extend_from_slice(frame.reason().as_bytes());
+                    payload
+                })
+                .unwrap_or_default();
+
+            (OPCODE_CLOSE, &close_payload[..])
+        }
+    };
+
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+
+    // Messages are never fragmented when sending.
+    frame.push(0x80 | opcode);
+
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= 0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .zip(mask.iter().cycle())
+            .map(|(byte, mask)| byte ^ mask),
+    );
+
+    frame
+}
+
+/// Incremental decoder of frames received from the server.
+#[derive(Debug, Default)]
+pub(crate) struct Decoder {
+    buffer: Vec<u8>,
+
+    /// The opcode and payload of a fragmented message received so far.
+    fragmented: Option<(u8, Vec<u8>)>,
+}
+
+/// A single frame parsed from the buffer.
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+impl Decoder {
+    /// Add data received from the server.
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Decode the next complete message received, if any.
+    ///
+    /// If an error is returned, the close code to send to the server before
+    /// closing the connection is returned along with it.
+    pub(crate) fn next_message(&mut self) -> Result<Option<Message>, (u16, Error)> {
+        while let Some(frame) = self.next_frame()? {
+            match frame.opcode {
+                OPCODE_CLOSE => return parse_close(&frame.payload).map(Some),
+                OPCODE_PING => return Ok(Some(Message::Ping(frame.payload))),
+                OPCODE_PONG => return Ok(Some(Message::Pong(frame.payload))),
+                OPCODE_CONTINUATION => {
+                    let (opcode, mut payload) = self
+                        .fragmented
+                        .take()
+                        .ok_or_else(|| protocol_error("unexpected continuation frame"))?;
+
+                    if payload.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                        return Err(too_big());
+                    }
+
+                    payload.extend_from_slice(&frame.payload);
+
+                    if frame.fin {
+                        return data_message(opcode, payload).map(Some);
+                    }
+
+                    self.fragmented = Some((opcode, payload));
+                }
+                opcode => {
+                    if self.fragmented.is_some() {
+                        return Err(protocol_error("expected continuation frame"));
+                    }
+
+                    if frame.fin {
+                        return data_message(opcode, frame.payload).map(Some);
+                    }
+
+                    self.fragmented = Some((opcode, frame.payload));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parse the next complete frame in the buffer, if any.
+    fn next_frame(&mut self) -> Result<Option<Frame>, (u16, Error)> {
+        if self.buffer.len() < 2 {
+            return Ok(None);
+        }
+
+        let fin = self.buffer[0] & 0x80 != 0;
+        let opcode = self.buffer[0] & 0x0f;
+
+        // No extensions are negotiated, so the reserved bits must be unset.
+        if self.buffer[0] & 0x70 != 0 {
+            return Err(protocol_error("reserved bits are set"));
+        }
+
+        match opcode {
+            OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => {}
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => {
+                if !fin || self.buffer[1] & 0x7f > 125 {
+                    return Err(protocol_error("invalid control frame"));
+                }
+            }
+            _ => return Err(protocol_error("unknown opcode")),
+        }
+
+        if self.buffer[1] & 0x80 != 0 {
+            return Err(protocol_error("server sent a masked frame"));
+        }
+
+        let (len, header_len) = match self.buffer[1] & 0x7f {
+            126 if self.buffer.len() >= 4 => {
+                let len = u16::from_be_bytes([self.buffer[2], self.buffer[3]]);
+                (len as u64, 4)
+            }
+            127 if self.buffer.len() >= 10 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&self.buffer[2..10]);
+                (u64::from_be_bytes(bytes), 10)
+            }
+            126 | 127 => return Ok(None),
+            len => (len as u64, 2),
+        };
+
+        if len > MAX_MESSAGE_SIZE as u64 {
+            return Err(too_big());
+        }
+
+        let end = header_len + len as usize;
+
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+
+        let payload = self.buffer[header_len..end].to_vec();
+        self.buffer.drain(..end);
+
+        Ok(Some(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+}
+
+fn data_message(opcode: u8, payload: Vec<u8>) -> Result<Message, (u16, Error)> {
+    if opcode == OPCODE_TEXT {
+        String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| protocol_error("text message is not valid UTF-8"))
+    } else {
+        Ok(Message::Binary(payload))
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<Message, (u16, Error)> {
+    match payload.len() {
+        0 => Ok(Message::Close(None)),
+        1 => Err(protocol_error("invalid close frame")),
+        _ => {
+            let code = u16::from_be_bytes([payload[0], payload[1]]);
+            let reason = std::str::from_utf8(&payload[2..])
+                .map_err(|_| protocol_error("close reason is not valid UTF-8"))?;
+
+            Ok(Message::Close(Some(CloseFrame::new(code, reason))))
+        }
+    }
+}
+
+fn protocol_error(message: &'static str) -> (u16, Error) {
+    let error = Error::with_context(
+        ErrorKind::ProtocolViolation,
+        Some(format!("invalid WebSocket frame: {}", message)),
+        io::Error::new(io::ErrorKind::InvalidData, message),
+    );
+
+    (CLOSE_PROTOCOL_ERROR, error)
+}
+
+fn too_big() -> (u16, Error) {
+    let error = Error::with_context(
+        ErrorKind::ProtocolViolation,
+        Some(String::from("WebSocket message is too big")),
+        io::Error::new(io::ErrorKind::InvalidData, "message is too big"),
+    );
+
+    (CLOSE_TOO_BIG, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    fn decode(data: &[u8]) -> Result<Option<Message>, (u16, Error)> {
+        let mut decoder = Decoder::default();
+        decoder.push(data);
+        decoder.next_message()
+    }
+
+    #[test]
+    fn encode_masked_text() {
+        // Example from RFC 6455, section 5.7.
+        let expected = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+
+        assert_eq!(encode(&Message::Text("Hello".into()), MASK), expected);
+    }
+
+    #[test]
+    fn encode_extended_lengths() {
+        let frame = encode(&Message::Binary(vec![0; 256]), MASK);
+        assert_eq!(&frame[..4], &[0x82, 0xfe, 0x01, 0x00]);
+        assert_eq!(frame.len(), 4 + 4 + 256);
+
+        let frame = encode(&Message::Binary(vec![0; 65536]), MASK);
+        assert_eq!(&frame[..2], &[0x82, 0xff]);
+        assert_eq!(&frame[2..10], &65536u64.to_be_bytes());
+    }
+
+    #[test]
+    fn encode_close_with_reason() {
+        let frame = encode(&Message::Close(Some(CloseFrame::new(1000, "bye"))), [0; 4]);
+
+        let expected = [0x88, 0x85, 0, 0, 0, 0, 0x03, 0xe8, b'b', b'y', b'e'];
+
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn decode_unmasked_text() {
+        let message = decode(&[0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]).unwrap();
+
+        assert_eq!(message, Some(Message::Text("Hello".into())));
+    }
+
+    #[test]
+    fn decode_partial_frames() {
+        let mut decoder = Decoder::default();
+
+        decoder.push(&[0x82, 0x7e, 0x01]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+
+        decoder.push(&[0x00]);
+        decoder.push(&[7; 255]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+
+        decoder.push(&[7, 0x89, 0x00]);
+        let message = Message::Binary(vec![7; 256]);
+        assert_eq!(decoder.next_message().unwrap(), Some(message));
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Some(Message::Ping(Vec::new()))
+        );
+        assert_eq!(decoder.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn decode_fragmented_message_with_interleaved_ping() {
+        let mut decoder = Decoder::default();
+
+        // Examples from RFC 6455, section 5.7.
+        decoder.push(&[0x01, 0x03, 0x48, 0x65, 0x6c]);
+        decoder.push(&[0x89, 0x01, 0x21]);
+        decoder.push(&[0x80, 0x02, 0x6c, 0x6f]);
+
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Some(Message::Ping(vec![0x21]))
+        );
+        assert_eq!(
+            decoder.next_message().unwrap(),
+            Some(Message::Text("Hello".into()))
+        );
+    }
+
+    #[test]
+    fn decode_close() {
+        let message = decode(&[0x88, 0x04, 0x03, 0xe9, b'o', b'k']).unwrap();
+        assert_eq!(
+            message,
+            Some(Message::Close(Some(CloseFrame::new(1001, "ok"))))
+        );
+
+        assert_eq!(decode(&[0x88, 0x00]).unwrap(), Some(Message::Close(None)));
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        for frame in &[
+            // Masked frame.
+            &[0x81, 0x81, 0, 0, 0, 0, 0][..],
+            // Reserved bit set.
+            &[0xc1, 0x00],
+            // Unknown opcode.
+            &[0x83, 0x00],
+            // Fragmented control frame.
+            &[0x09, 0x00],
+            // Continuation without a start.
+            &[0x80, 0x00],
+            // Invalid UTF-8.
+            &[0x81, 0x01, 0xff],
+            // Close frame with a partial code.
+            &[0x88, 0x01, 0x03],
+        ] {
+            let (code, error) = decode(frame).unwrap_err();
+
+            assert_eq!(code, CLOSE_PROTOCOL_ERROR);
+            assert_eq!(error.kind(), ErrorKind::ProtocolViolation);
+        }
+    }
+
+    #[test]
+    fn too_big_messages_are_rejected() {
+        let (code, _) = decode(&[0x82, 0x7f, 0, 0, 0, 1, 0, 0, 0, 0]).unwrap_err();
+
+        assert_eq!(code, CLOSE_TOO_BIG);
+    }
+}
@@ -0,0 +1,284 @@
+//! The opening handshake of a WebSocket connection, as described in [RFC
+//! 6455, section 4](https://tools.ietf.org/html/rfc6455#section-4).
+
+use crate::{
+    digest::base64_encode,
+    error::{Error, ErrorKind},
+    parsing::{parse_header, parse_status_line},
+    random::random,
+};
+use http::{header, HeaderMap, StatusCode, Uri};
+use std::io;
+
+/// Appended to the key sent by the client to compute the accept value of the
+/// server.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Headers set by the handshake itself, which are not taken from the default
+/// headers of a client.
+const HANDSHAKE_HEADERS: [header::HeaderName; 6] = [
+    header::CONNECTION,
+    header::HOST,
+    header::SEC_WEBSOCKET_ACCEPT,
+    header::SEC_WEBSOCKET_KEY,
+    header::SEC_WEBSOCKET_VERSION,
+    header::UPGRADE,
+];
+
+/// Generate a random key for a handshake request.
+pub(crate) fn key() -> String {
+    let mut nonce = [0; 16];
+    nonce[..8].copy_from_slice(&random().to_ne_bytes());
+    nonce[8..].copy_from_slice(&random().to_ne_bytes());
+
+    base64_encode(&nonce)
+}
+
+/// Create the HTTP/1.1 upgrade request to send to the server.
+pub(crate) fn request(uri: &Uri, key: &str, headers: &HeaderMap) -> Vec<u8> {
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let host = uri.authority().map_or("", |authority| authority.as_str());
+    let mut request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n",
+        path, host, key
+    )
+    .into_bytes();
+
+    for (name, value) in headers {
+        if !HANDSHAKE_HEADERS.contains(name) {
+            request.extend_from_slice(name.as_str().as_bytes());
+            request.extend_from_slice(b": ");
+            request.extend_from_slice(value.as_bytes());
+            request.extend_from_slice(b"\r\n");
+        }
+    }
+
+    request.extend_from_slice(b"\r\n");
+    request
+}
+
+/// Parse the response of the server, once all of its headers have been
+/// received.
+///
+/// Returns the number of bytes the response took up, as any bytes after it
+/// already belong to the WebSocket connection.
+pub(crate) fn parse_response(data: &[u8]) -> Result<Option<(usize, StatusCode, HeaderMap)>, Error> {
+    let end = match data.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+
+    // Trailing carriage returns are trimmed as whitespace when parsing.
+    let mut lines = data[..end].split(|&b| b == b'\n');
+
+    let (_, status) = lines
+        .next()
+        .and_then(parse_status_line)
+        .ok_or_else(|| invalid_response("invalid status line"))?;
+
+    let mut headers = HeaderMap::new();
+
+    for line in lines {
+        let (name, value) = parse_header(line).ok_or_else(|| invalid_response("invalid header"))?;
+        headers.append(name, value);
+    }
+
+    Ok(Some((end + 4, status, headers)))
+}
+
+/// Check that the response of the server accepts the upgrade requested with
+/// the given key.
+pub(crate) fn verify(status: StatusCode, headers: &HeaderMap, key: &str) -> Result<(), Error> {
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(Error::with_context(
+            ErrorKind::BadStatus,
+            Some(format!("server rejected the WebSocket upgrade with status {}", status)),
+            io::Error::new(io::ErrorKind::ConnectionRefused, "upgrade rejected"),
+        ));
+    }
+
+    let has_token = |name, token: &str| {
+        crate::headers::values(headers, name)
+            .iter()
+            .any(|value| value.eq_ignore_ascii_case(token))
+    };
+
+    if !has_token("upgrade", "websocket") || !has_token("connection", "upgrade") {
+        return Err(invalid_response("missing upgrade headers"));
+    }
+
+    if headers.get(header::SEC_WEBSOCKET_ACCEPT).map(|v| v.as_bytes())
+        != Some(accept(key).as_bytes())
+    {
+        return Err(invalid_response("invalid Sec-WebSocket-Accept header"));
+    }
+
+    // No extensions are requested, so the server must not use any.
+    if headers.contains_key(header::SEC_WEBSOCKET_EXTENSIONS) {
+        return Err(invalid_response("unexpected extensions"));
+    }
+
+    Ok(())
+}
+
+/// Compute the accept value the server must respond with for a key.
+fn accept(key: &str) -> String {
+    base64_encode(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+fn invalid_response(message: &'static str) -> Error {
+    Error::with_context(
+        ErrorKind::ProtocolViolation,
+        Some(format!("invalid WebSocket handshake response: {}", message)),
+        io::Error::new(io::ErrorKind::InvalidData, message),
+    )
+}
+
+/// Compute the SHA-1 hash of some data.
+///
+/// SHA-1 is not suitable for anything security related, but it is what the
+/// handshake uses, and only ever for short inputs.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut schedule = [0u32; 80];
+
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        for i in 16..80 {
+            schedule[i] =
+                (schedule[i - 3] ^ schedule[i - 8] ^ schedule[i - 14] ^ schedule[i - 16])
+                    .rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, word) in schedule.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *word = word.wrapping_add(*value);
+        }
+    }
+
+    let mut hash = [0; 20];
+
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn accept_matches_rfc_example() {
+        assert_eq!(accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn sha1_of_multiple_blocks() {
+        let hash = sha1(&[b'a'; 1000]);
+        let hex = hash.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        assert_eq!(hex, "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+    }
+
+    #[test]
+    fn keys_are_random() {
+        assert_eq!(key().len(), 24);
+        assert_ne!(key(), key());
+    }
+
+    #[test]
+    fn request_includes_handshake_headers() {
+        let uri = "ws://example.org:8080/chat?room=1".parse::<Uri>().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("test"));
+        headers.insert("connection", HeaderValue::from_static("close"));
+
+        let request = String::from_utf8(request(&uri, "abc", &headers)).unwrap();
+
+        assert_eq!(
+            request,
+            "GET /chat?room=1 HTTP/1.1\r\n\
+             Host: example.org:8080\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: abc\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             user-agent: test\r\n\
+             \r\n"
+        );
+    }
+
+    #[test]
+    fn parse_complete_response() {
+        let data = b"HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                     \r\n\
+                     \x81\x00";
+
+        assert!(parse_response(&data[..20]).unwrap().is_none());
+
+        let (len, status, headers) = parse_response(data).unwrap().unwrap();
+
+        assert_eq!(len, data.len() - 2);
+        assert!(verify(status, &headers, "dGhlIHNhbXBsZSBub25jZQ==").is_ok());
+
+        let error = verify(status, &headers, "c29tZXRoaW5nIGVsc2U=").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ProtocolViolation);
+    }
+
+    #[test]
+    fn rejected_upgrade_is_an_error() {
+        let data = b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n";
+        let (_, status, headers) = parse_response(data).unwrap().unwrap();
+
+        let error = verify(status, &headers, "abc").unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadStatus);
+    }
+}
@@ -0,0 +1,206 @@
+//! WebSocket connections, as described in [RFC
+//! 6455](https://tools.ietf.org/html/rfc6455).
+//!
+//! A connection is opened using
+//! [`HttpClient::websocket`](crate::HttpClient::websocket), which upgrades an
+//! HTTP/1.1 connection to the server and returns a [`WebSocket`] that messages
+//! can be sent and received on. Connections use the proxy, TLS and timeout
+//! settings of the client they are opened with.
+//!
+//! Pings from the server are answered automatically, and the closing
+//! handshake is completed once either side sends a close message.
+//!
+//! This module is only available when the
+//! [`websocket`](../index.html#websocket) feature is enabled.
+
+use crate::error::{Error, ErrorKind};
+use flume::{Receiver, Sender};
+use futures_lite::future::block_on;
+use std::{fmt, io, task::Waker};
+
+mod connection;
+mod frame;
+mod handshake;
+
+pub(crate) use connection::connect;
+
+/// A message sent or received over a WebSocket.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Message {
+    /// A text message.
+    Text(String),
+
+    /// A binary message.
+    Binary(Vec<u8>),
+
+    /// A ping, which the other side answers with a pong containing the same
+    /// data.
+    ///
+    /// Pings received from the server are answered automatically.
+    Ping(Vec<u8>),
+
+    /// A pong, either answering a ping or sent on its own as a heartbeat.
+    Pong(Vec<u8>),
+
+    /// A request to close the connection, optionally with a reason.
+    ///
+    /// After sending a close message, no more messages can be sent. Messages
+    /// can still be received until the server replies with a close message
+    /// of its own.
+    Close(Option<CloseFrame>),
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Message::Text(text.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Message::Binary(data)
+    }
+}
+
+/// The reason a WebSocket connection is closed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloseFrame {
+    code: u16,
+    reason: String,
+}
+
+impl CloseFrame {
+    /// Create a close frame with the given status code and reason.
+    ///
+    /// See [RFC 6455, section
+    /// 7.4](https://tools.ietf.org/html/rfc6455#section-7.4) for the meaning
+    /// of status codes. For example, `1000` means that the connection is
+    /// closed normally.
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    /// Get the status code of the close frame.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// Get the reason given for closing the connection, which may be empty.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// An open WebSocket connection.
+///
+/// Dropping the WebSocket closes the connection.
+///
+/// # Examples
+///
+/// ```no_run
+/// use isahc::{prelude::*, websocket::Message};
+///
+/// let client = HttpClient::new()?;
+/// let socket = client.websocket("wss://echo.example.org")?;
+///
+/// socket.send("hello")?;
+///
+/// while let Some(message) = socket.recv()? {
+///     match message {
+///         Message::Text(text) => println!("received {}", text),
+///         Message::Close(_) => break,
+///         _ => {}
+///     }
+/// }
+/// # Ok::<(), isahc::Error>(())
+/// ```
+pub struct WebSocket {
+    outgoing: Sender<Message>,
+    incoming: Receiver<Result<Message, Error>>,
+
+    /// Wakes up the connection thread when there are messages to send.
+    waker: Waker,
+}
+
+impl WebSocket {
+    /// Send a message, blocking the current thread until it is queued for
+    /// sending.
+    ///
+    /// Messages are sent in the order they are queued. Sending a message
+    /// after a close message was sent, or after the connection was closed,
+    /// returns an error.
+    pub fn send(&self, message: impl Into<Message>) -> Result<(), Error> {
+        block_on(self.send_async(message))
+    }
+
+    /// Send a message asynchronously.
+    ///
+    /// This is the async version of [`WebSocket::send`].
+    pub async fn send_async(&self, message: impl Into<Message>) -> Result<(), Error> {
+        self.outgoing
+            .send_async(message.into())
+            .await
+            .map_err(|_| closed())?;
+        self.waker.wake_by_ref();
+
+        Ok(())
+    }
+
+    /// Receive the next message, blocking the current thread until one
+    /// arrives.
+    ///
+    /// Returns `None` once the connection has been closed. If the connection
+    /// fails, the error is returned once all messages received before have
+    /// been returned.
+    pub fn recv(&self) -> Result<Option<Message>, Error> {
+        block_on(self.recv_async())
+    }
+
+    /// Receive the next message asynchronously.
+    ///
+    /// This is the async version of [`WebSocket::recv`].
+    pub async fn recv_async(&self) -> Result<Option<Message>, Error> {
+        match self.incoming.recv_async().await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Start closing the connection with a normal status code.
+    ///
+    /// This is the same as sending a close message. Messages can still be
+    /// received until the server replies with a close message of its own.
+    pub fn close(&self) -> Result<(), Error> {
+        self.send(Message::Close(Some(CloseFrame::new(frame::CLOSE_NORMAL, ""))))
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        // Let the connection thread notice that the WebSocket is gone.
+        self.waker.wake_by_ref();
+    }
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket").finish()
+    }
+}
+
+fn closed() -> Error {
+    Error::new(
+        ErrorKind::Io,
+        io::Error::new(io::ErrorKind::NotConnected, "WebSocket connection is closed"),
+    )
+}
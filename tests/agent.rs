@@ -0,0 +1,47 @@
+#![cfg(feature = "unstable-agent")]
+
+use futures_lite::future::block_on;
+use isahc::agent::Agent;
+use std::sync::{Arc, Mutex};
+use testserver::mock;
+
+#[test]
+fn custom_transfer_returns_easy_handle() {
+    let m = mock! {
+        status: 201,
+        body: "hello world",
+    };
+
+    let agent = Agent::spawn().unwrap();
+    let body = Arc::new(Mutex::new(Vec::new()));
+
+    let mut easy = curl::easy::Easy::new();
+    easy.url(&m.url()).unwrap();
+    easy.write_function({
+        let body = body.clone();
+        move |data| {
+            body.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+    })
+    .unwrap();
+
+    let easy = block_on(agent.submit(easy)).unwrap();
+
+    assert_eq!(easy.response_code().unwrap(), 201);
+    assert_eq!(body.lock().unwrap().as_slice(), b"hello world");
+
+    agent.shutdown();
+}
+
+#[test]
+fn custom_transfer_error_is_returned() {
+    let agent = Agent::spawn().unwrap();
+
+    let mut easy = curl::easy::Easy::new();
+    easy.url("http://127.0.0.1:1").unwrap();
+
+    let error = block_on(agent.submit(easy)).unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::ConnectionFailed);
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "http-cache")]
+
+use isahc::{
+    cache::{CacheConfig, CacheStatus},
+    prelude::*,
+    HttpClient,
+};
+use testserver::mock;
+
+fn client() -> HttpClient {
+    HttpClient::builder()
+        .cache(CacheConfig::in_memory())
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn fresh_response_is_served_from_cache() {
+    let m = mock! {
+        headers {
+            "cache-control": "max-age=60",
+        }
+        body: "hello",
+    };
+    let client = client();
+
+    let mut response = client.get(m.url()).unwrap();
+    assert_eq!(response.cache_status(), Some(CacheStatus::Miss));
+    assert_eq!(response.text().unwrap(), "hello");
+
+    let mut response = client.get(m.url()).unwrap();
+    assert_eq!(response.cache_status(), Some(CacheStatus::Hit));
+    assert_eq!(response.text().unwrap(), "hello");
+
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn stale_response_is_revalidated_with_etag() {
+    let m = mock! {
+        headers {
+            "cache-control": "no-cache",
+            "etag": "\"v1\"",
+        }
+        body: "hello",
+    };
+    let client = client();
+
+    client.get(m.url()).unwrap().text().unwrap();
+    client.get(m.url()).unwrap();
+
+    let requests = m.requests();
+    assert_eq!(requests.len(), 2);
+    requests[1].expect_header("if-none-match", "\"v1\"");
+}
+
+#[test]
+fn no_store_responses_are_not_cached() {
+    let m = mock! {
+        headers {
+            "cache-control": "no-store, max-age=60",
+        }
+        body: "hello",
+    };
+    let client = client();
+
+    client.get(m.url()).unwrap().text().unwrap();
+    let response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.cache_status(), Some(CacheStatus::Miss));
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn unsafe_requests_invalidate_cached_response() {
+    let m = mock! {
+        headers {
+            "cache-control": "max-age=60",
+        }
+        body: "hello",
+    };
+    let client = client();
+
+    client.get(m.url()).unwrap().text().unwrap();
+    client.post(m.url(), ()).unwrap();
+    let response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.cache_status(), Some(CacheStatus::Miss));
+    assert_eq!(m.requests().len(), 3);
+}
+
+#[test]
+fn responses_are_cached_on_disk() {
+    let m = mock! {
+        headers {
+            "cache-control": "max-age=60",
+        }
+        body: "hello",
+    };
+    let dir = tempfile::tempdir().unwrap();
+
+    let client = HttpClient::builder()
+        .cache(CacheConfig::on_disk(dir.path()))
+        .build()
+        .unwrap();
+    client.get(m.url()).unwrap().text().unwrap();
+
+    // A new client using the same directory sees the stored response.
+    let client = HttpClient::builder()
+        .cache(CacheConfig::on_disk(dir.path()))
+        .build()
+        .unwrap();
+    let mut response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.cache_status(), Some(CacheStatus::Hit));
+    assert_eq!(response.text().unwrap(), "hello");
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn responses_without_cache_have_no_status() {
+    let m = mock!();
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.cache_status(), None);
+}
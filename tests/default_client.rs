@@ -0,0 +1,31 @@
+use isahc::{prelude::*, HttpClient};
+use testserver::mock;
+
+// The default client is global, so everything is checked within a single test
+// to not depend on the order in which tests run.
+#[test]
+fn default_client_can_be_configured_before_first_use() {
+    let m = mock!();
+
+    let result =
+        isahc::configure_default_client(|builder| builder.default_header("x-configured", "first"));
+    assert!(result.is_ok());
+
+    // Configuring again replaces the previous configuration.
+    let result =
+        isahc::configure_default_client(|builder| builder.default_header("x-configured", "second"));
+    assert!(result.is_ok());
+
+    isahc::get(m.url()).unwrap();
+    Request::get(m.url()).body(()).unwrap().send().unwrap();
+
+    assert_eq!(m.requests().len(), 2);
+
+    for request in m.requests() {
+        request.expect_header("x-configured", "second");
+    }
+
+    // The default client cannot be changed once in use.
+    assert!(isahc::set_default_client(HttpClient::new().unwrap()).is_err());
+    assert!(isahc::configure_default_client(|builder| builder).is_err());
+}
@@ -0,0 +1,110 @@
+use isahc::{config::DigestHeader, error::ErrorKind, prelude::*, Body};
+use std::io::Read;
+use testserver::mock;
+
+const HELLO_DIGEST: &str = "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:";
+
+#[test]
+fn digest_of_buffered_body_is_sent_in_header() {
+    let m = mock!();
+
+    Request::put(m.url())
+        .send_digest(DigestHeader::Content)
+        .body(r#"{"hello": "world"}"#)
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request().expect_header("content-digest", HELLO_DIGEST);
+}
+
+#[test]
+fn existing_digest_header_is_kept() {
+    let m = mock!();
+
+    Request::put(m.url())
+        .send_digest(DigestHeader::Representation)
+        .header("repr-digest", "sha-512=:AAAA:")
+        .body("hello")
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request().expect_header("repr-digest", "sha-512=:AAAA:");
+}
+
+#[test]
+fn digest_of_streaming_body_is_sent_in_trailer() {
+    let m = mock!();
+
+    Request::put(m.url())
+        .send_digest(DigestHeader::Content)
+        .body(Body::from_reader(r#"{"hello": "world"}"#.as_bytes()))
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request().expect_header("trailer", "content-digest");
+    m.request().expect_trailer("content-digest", HELLO_DIGEST);
+}
+
+#[test]
+fn matching_response_digest_is_accepted() {
+    let m = mock! {
+        headers {
+            "content-digest": HELLO_DIGEST,
+        }
+        body: r#"{"hello": "world"}"#,
+    };
+
+    let mut response = Request::get(m.url())
+        .verify_digest(true)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), r#"{"hello": "world"}"#);
+}
+
+#[test]
+fn mismatched_response_digest_fails_at_end_of_body() {
+    let m = mock! {
+        headers {
+            "repr-digest": HELLO_DIGEST,
+        }
+        body: r#"{"hello": "there"}"#,
+    };
+
+    let mut response = Request::get(m.url())
+        .verify_digest(true)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let error = response
+        .body_mut()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    let error = error.get_ref().unwrap().downcast_ref::<isahc::Error>();
+
+    assert_eq!(error.unwrap().kind(), ErrorKind::DigestMismatch);
+}
+
+#[test]
+fn response_digest_is_not_verified_by_default() {
+    let m = mock! {
+        headers {
+            "content-digest": HELLO_DIGEST,
+        }
+        body: "something else",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.text().unwrap(), "something else");
+}
@@ -6,6 +6,9 @@ use isahc::prelude::*;
 use std::io::Read;
 use testserver::mock;
 
+#[macro_use]
+mod utils;
+
 #[test]
 fn gzip_encoded_response_is_decoded_automatically() {
     let body = "hello world";
@@ -24,7 +27,12 @@ fn gzip_encoded_response_is_decoded_automatically() {
 
     let mut response = isahc::get(m.url()).unwrap();
 
+    assert!(response.is_decoded());
+    assert!(response.raw_body().is_none());
     assert_eq!(response.text().unwrap(), body);
+
+    // More encodings are accepted if supported by Isahc itself.
+    #[cfg(not(any(feature = "brotli", feature = "zstd")))]
     m.request()
         .expect_header("Accept-Encoding", "deflate, gzip");
 
@@ -69,6 +77,57 @@ fn request_gzip_without_automatic_decompression() {
     assert_eq!(response.body().len(), Some(31));
 }
 
+#[test]
+fn raw_body_is_available_with_decompression_disabled() {
+    let body = "hello world";
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(body.as_bytes(), Compression::default())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let m = {
+        let body_encoded = body_encoded.clone();
+        mock! {
+            headers {
+                "Content-Encoding": "gzip",
+            }
+            body: body_encoded.clone(),
+        }
+    };
+
+    let mut response = Request::get(m.url())
+        .header("Accept-Encoding", "gzip")
+        .decompress(false)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert!(!response.is_decoded());
+
+    let mut body_received = Vec::new();
+    response
+        .raw_body()
+        .unwrap()
+        .read_to_end(&mut body_received)
+        .unwrap();
+
+    assert_eq!(body_received, body_encoded);
+}
+
+#[test]
+fn response_without_content_encoding_is_not_decoded() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(!response.is_decoded());
+    assert!(response.raw_body().is_some());
+}
+
 #[test]
 fn deflate_encoded_response_is_decoded_automatically() {
     let body = "hello world";
@@ -88,6 +147,9 @@ fn deflate_encoded_response_is_decoded_automatically() {
     let mut response = isahc::get(m.url()).unwrap();
 
     assert_eq!(response.text().unwrap(), body);
+
+    // More encodings are accepted if supported by Isahc itself.
+    #[cfg(not(any(feature = "brotli", feature = "zstd")))]
     m.request()
         .expect_header("Accept-Encoding", "deflate, gzip");
 
@@ -96,6 +158,59 @@ fn deflate_encoded_response_is_decoded_automatically() {
     assert_eq!(response.body().len(), None);
 }
 
+#[test]
+fn stacked_encodings_are_decoded_in_reverse_order() {
+    let body = "hello world";
+    let mut body_gzipped = Vec::new();
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(body.as_bytes(), Compression::default())
+        .read_to_end(&mut body_gzipped)
+        .unwrap();
+
+    DeflateEncoder::new(&body_gzipped[..], Compression::default())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "gzip, deflate",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(response.is_decoded());
+    assert_eq!(response.text().unwrap(), body);
+    assert_eq!(response.body().len(), None);
+}
+
+#[test]
+fn encodings_spread_over_several_headers_are_decoded() {
+    let body = "hello world";
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(body.as_bytes(), Compression::default())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "identity",
+            "Content-Encoding": "gzip",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(response.is_decoded());
+    assert!(response.raw_body().is_none());
+    assert_eq!(response.text().unwrap(), body);
+    assert_eq!(response.body().len(), None);
+}
+
 #[test]
 fn content_is_decoded_even_if_not_listed_as_accepted() {
     let body = "hello world";
@@ -145,3 +260,127 @@ fn unknown_content_encoding_returns_error() {
 
     m.request().expect_header("Accept-Encoding", "deflate");
 }
+
+#[test]
+fn decompressed_size_limit_aborts_response() {
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(&[0; 1024 * 1024][..], Compression::best())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "gzip",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let result = Request::get(m.url())
+        .max_decompressed_size(1000)
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_matches!(
+        result,
+        Err(e) if e == isahc::error::ErrorKind::DecompressionLimitExceeded
+    );
+}
+
+#[test]
+fn decompression_ratio_limit_aborts_response() {
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(&[0; 4 * 1024 * 1024][..], Compression::best())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "gzip",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let mut response = Request::get(m.url())
+        .max_decompression_ratio(100.0)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(
+        std::io::copy(response.body_mut(), &mut std::io::sink())
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn decompression_limits_ignore_raw_responses() {
+    let m = mock! {
+        headers {
+            "Content-Encoding": "gzip",
+        }
+        body: vec![0; 4096],
+    };
+
+    let mut response = Request::get(m.url())
+        .automatic_decompression(false)
+        .max_decompressed_size(1000)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(
+        std::io::copy(response.body_mut(), &mut std::io::sink()).unwrap(),
+        4096
+    );
+}
+
+#[cfg(feature = "brotli")]
+#[test]
+fn brotli_encoded_response_is_decoded_automatically() {
+    // "hello world" compressed with brotli.
+    let body_encoded = b"\x0b\x05\x80\x68\x65\x6c\x6c\x6f\x20\x77\x6f\x72\x6c\x64\x03".to_vec();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "br",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(response.is_decoded());
+    assert_eq!(response.text().unwrap(), "hello world");
+    assert_eq!(response.body().len(), None);
+    m.request()
+        .expect_header_matches("Accept-Encoding", |value| value.contains("br"));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_encoded_response_is_decoded_automatically() {
+    let body = "hello world ".repeat(10_000);
+    let mut body_encoded = Vec::with_capacity(zstd_safe::compress_bound(body.len()));
+    zstd_safe::compress(&mut body_encoded, body.as_bytes(), 3).unwrap();
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "zstd",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(response.is_decoded());
+    assert_eq!(response.text().unwrap(), body);
+    m.request()
+        .expect_header_matches("Accept-Encoding", |value| value.contains("zstd"));
+}
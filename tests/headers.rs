@@ -1,6 +1,9 @@
-use isahc::prelude::*;
+use isahc::{http::HeaderValue, prelude::*};
 use testserver::mock;
 
+#[macro_use]
+mod utils;
+
 #[test]
 fn accept_headers_populated_by_default() {
     let m = mock!();
@@ -67,6 +70,80 @@ fn override_client_default_user_agent() {
     m.request().expect_header("user-agent", "foo");
 }
 
+#[test]
+fn client_user_agent_template_is_expanded() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .user_agent("my-app/1.0 isahc/{isahc_version} (curl {curl_version})")
+        .build()
+        .unwrap();
+
+    client.get(m.url()).unwrap();
+
+    m.request().expect_header_regex(
+        "user-agent",
+        r"^my-app/1\.0 isahc/\d+\.\d+\.\d+\S* \(curl \d+\.\d+\.\d+\S*\)$",
+    );
+}
+
+#[test]
+fn request_user_agent_overrides_client() {
+    let m = mock!();
+
+    let client = HttpClient::builder().user_agent("client").build().unwrap();
+
+    client
+        .send(Request::get(m.url()).user_agent("request").body(()).unwrap())
+        .unwrap();
+
+    m.request().expect_header("user-agent", "request");
+}
+
+#[test]
+fn user_agent_can_be_suppressed() {
+    let m = mock!();
+
+    let client = HttpClient::builder().no_user_agent().build().unwrap();
+
+    client.get(m.url()).unwrap();
+    Request::get(m.url())
+        .no_user_agent()
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    for request in m.requests() {
+        assert_eq!(request.get_header("user-agent").count(), 0);
+    }
+}
+
+#[test]
+fn accept_encoding_can_be_set_per_request() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .accept_encoding(&["gzip"])
+        .build()
+        .unwrap();
+
+    client.get(m.url()).unwrap();
+    client
+        .send(
+            Request::get(m.url())
+                .accept_encoding(&[])
+                .body(())
+                .unwrap(),
+        )
+        .unwrap();
+
+    let requests = m.requests();
+
+    requests[0].expect_header("accept-encoding", "gzip");
+    assert_eq!(requests[1].get_header("accept-encoding").count(), 0);
+}
+
 // Issue [#205](https://github.com/sagebind/isahc/issues/205)
 #[test]
 fn set_title_case_headers_to_true() {
@@ -185,3 +262,287 @@ fn headers_in_request_builder_must_override_multiple_headers_in_httpclient_build
         .expect_header("accept-encoding", "deflate, gzip");
     m.request().expect_header("X-header", "some-value3");
 }
+
+#[test]
+fn header_order_controls_order_of_request_headers() {
+    let m = mock!();
+
+    Request::get(m.url())
+        .header("x-first", "1")
+        .header("x-second", "2")
+        .header("x-third", "3")
+        .header_order(vec!["X-Third", "x-first"])
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let names = m
+        .request()
+        .headers
+        .iter()
+        .map(|(name, _)| name.to_lowercase())
+        .filter(|name| name.starts_with("x-"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["x-third", "x-first", "x-second"]);
+}
+
+#[test]
+fn header_order_omits_default_accept_header() {
+    let m = mock!();
+
+    Request::get(m.url())
+        .header_order(vec!["user-agent"])
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(m.request().get_header("accept").count(), 0);
+}
+
+#[test]
+fn sibling_client_overrides_default_headers() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .default_header("X-Shared", "shared")
+        .default_header("X-Layer", "base")
+        .build()
+        .unwrap();
+
+    let sibling = client
+        .try_clone_with(|builder| builder.default_header("X-Layer", "sibling"))
+        .unwrap();
+
+    sibling.get(m.url()).unwrap();
+
+    m.request().expect_header("X-Shared", "shared");
+    m.request().expect_header("X-Layer", "sibling");
+
+    client.get(m.url()).unwrap();
+
+    m.requests()[1].expect_header("X-Layer", "base");
+}
+
+#[test]
+fn sibling_client_with_invalid_header_returns_error() {
+    let client = HttpClient::new().unwrap();
+
+    let result = client.try_clone_with(|builder| builder.default_header("X-Bad", "bad\nvalue"));
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn accept_header_is_built_from_media_ranges() {
+    let m = mock! {
+        headers {
+            "Content-Type": "application/json; charset=utf-8",
+        }
+    };
+
+    let response = Request::get(m.url())
+        .accept_json()
+        .accept("text/plain", 0.5)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request()
+        .expect_header("accept", "application/json, text/plain;q=0.5");
+
+    let content_type = response.content_type().unwrap();
+    assert_eq!(content_type.essence_str(), "application/json");
+    assert_eq!(content_type.get_param("charset").unwrap(), "utf-8");
+}
+
+#[test]
+fn non_ascii_header_value_is_rejected() {
+    let m = mock!();
+
+    let result = Request::get(m.url())
+        .header("X-Name", HeaderValue::from_bytes(b"caf\xC3\xA9").unwrap())
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_matches!(&result, Err(e) if e.kind() == isahc::error::ErrorKind::InvalidRequest);
+    assert!(m.requests().is_empty());
+}
+
+#[test]
+fn uri_characters_are_percent_encoded_unless_strict() {
+    let m = mock!();
+
+    Request::get(format!("{}{{id}}", m.url()))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(m.request().url, "/%7Bid%7D");
+
+    let result = Request::get(format!("{}{{id}}", m.url()))
+        .strict_validation(true)
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_matches!(&result, Err(e) if e.kind() == isahc::error::ErrorKind::InvalidRequest);
+}
+
+#[test]
+fn retry_after_is_parsed_from_response() {
+    let m = mock! {
+        status: 429,
+        headers {
+            "Retry-After": "30",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.retry_after(), Some(std::time::Duration::from_secs(30)));
+}
+
+#[test]
+fn intermediaries_are_parsed_from_response() {
+    let m = mock! {
+        status: 502,
+        headers {
+            "Via": "1.1 gateway (Example/1.0)",
+            "Proxy-Status": "gateway; error=connection_refused",
+        }
+    };
+
+    let response = Request::get(m.url())
+        .header("alt-used", "alt.example.org:443")
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let via = response.via();
+    assert_eq!(via.len(), 1);
+    assert_eq!(via[0].received_by(), "gateway");
+    assert_eq!(via[0].comment(), Some("Example/1.0"));
+
+    let proxy_status = response.proxy_status();
+    assert_eq!(proxy_status.len(), 1);
+    assert_eq!(proxy_status[0].proxy(), "gateway");
+    assert_eq!(proxy_status[0].error(), Some("connection_refused"));
+
+    assert_eq!(response.alt_used(), Some("alt.example.org:443"));
+}
+
+#[test]
+fn age_includes_age_header() {
+    let m = mock! {
+        headers {
+            "Age": "60",
+            "Expires": "0",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert!(response.age().unwrap() >= std::time::Duration::from_secs(60));
+    assert_eq!(response.expires(), Some(std::time::UNIX_EPOCH));
+}
+
+#[test]
+fn request_with_headers_over_limit_is_not_sent() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .max_header_list_size(1024)
+        .build()
+        .unwrap();
+
+    let result = client.send(
+        Request::get(m.url())
+            .header("authorization", format!("Bearer {}", "x".repeat(1024)))
+            .body(())
+            .unwrap(),
+    );
+
+    assert_matches!(&result, Err(e) if e.kind() == isahc::error::ErrorKind::HeadersTooLarge);
+    assert!(m.requests().is_empty());
+
+    client.get(m.url()).unwrap();
+
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn request_with_uri_over_limit_is_not_sent() {
+    let m = mock!();
+
+    let client = HttpClient::builder().max_uri_length(1024).build().unwrap();
+    let result = client.get(format!("{}?q={}", m.url(), "x".repeat(1024)));
+
+    assert_matches!(&result, Err(e) if e.kind() == isahc::error::ErrorKind::UriTooLong);
+    assert!(m.requests().is_empty());
+
+    client.get(format!("{}?q=x", m.url())).unwrap();
+
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn on_headers_abort_fails_request_before_body_is_read() {
+    let m = mock! {
+        headers {
+            "content-type": "application/octet-stream",
+        }
+        body: "x".repeat(100_000),
+    };
+
+    let result = Request::get(m.url())
+        .on_headers(|parts| {
+            if parts.headers["content-type"] == "application/octet-stream" {
+                isahc::config::HeaderAction::Abort
+            } else {
+                isahc::config::HeaderAction::Accept
+            }
+        })
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_matches!(&result, Err(e) if e.kind() == isahc::error::ErrorKind::ResponseRejected);
+}
+
+#[test]
+fn on_headers_modify_adds_response_extensions() {
+    #[derive(Debug, PartialEq)]
+    struct ContentType(String);
+
+    let m = mock! {
+        headers {
+            "content-type": "text/plain",
+        }
+        body: "hello",
+    };
+
+    let mut response = Request::get(m.url())
+        .on_headers(|parts| {
+            let content_type = parts.headers["content-type"].to_str().unwrap().to_owned();
+
+            isahc::config::HeaderAction::insert(ContentType(content_type))
+        })
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(
+        response.extensions().get::<ContentType>(),
+        Some(&ContentType("text/plain".into()))
+    );
+    assert_eq!(response.text().unwrap(), "hello");
+}
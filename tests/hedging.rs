@@ -0,0 +1,94 @@
+use isahc::{
+    config::{ExponentialBackoff, HedgePolicy, RetryPolicy},
+    prelude::*,
+};
+use std::time::{Duration, Instant};
+use testserver::mock;
+
+#[test]
+fn slow_request_is_hedged() {
+    let m = mock! {
+        delay: 500ms,
+        body: "hello",
+    };
+
+    let mut response = Request::get(m.url())
+        .hedge(HedgePolicy::after(Duration::from_millis(50)))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "hello");
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn fast_request_is_not_hedged() {
+    let m = mock! {
+        body: "hello",
+    };
+
+    let mut response = Request::get(m.url())
+        .hedge(HedgePolicy::after(Duration::from_secs(5)))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "hello");
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn non_idempotent_request_is_not_hedged() {
+    let m = mock! {
+        delay: 200ms,
+    };
+
+    Request::post(m.url())
+        .hedge(HedgePolicy::after(Duration::from_millis(10)))
+        .body("data")
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn request_is_not_hedged_without_time_left() {
+    let m = mock! {
+        delay: 300ms,
+    };
+
+    let result = Request::get(m.url())
+        .timeout(Duration::from_millis(100))
+        .hedge(HedgePolicy::after(Duration::from_millis(100)))
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_eq!(result.unwrap_err().kind(), isahc::error::ErrorKind::Timeout);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn hedged_request_respects_total_timeout() {
+    let m = mock! {
+        delay: 2s,
+    };
+
+    let start = Instant::now();
+
+    let result = Request::get(m.url())
+        .hedge(HedgePolicy::after(Duration::from_millis(50)))
+        .retry_policy(ExponentialBackoff::new(3).with_total_timeout(Duration::from_millis(300)))
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_eq!(result.unwrap_err().kind(), isahc::error::ErrorKind::Timeout);
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(m.requests().len(), 2);
+}
@@ -0,0 +1,61 @@
+use isahc::{hsts::HstsStore, prelude::*};
+use std::time::Duration;
+use testserver::mock;
+
+#[test]
+fn requests_to_known_hosts_are_upgraded_to_https() {
+    let m = mock!();
+
+    let store = HstsStore::new();
+    store.insert("localhost", false, Duration::from_secs(60));
+
+    // Our test server does not speak TLS, so the upgraded request fails once
+    // the handshake times out.
+    let client = HttpClient::builder()
+        .hsts(store)
+        .timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+
+    let result = client.get(format!("http://localhost:{}/", m.addr().port()));
+
+    assert!(result.is_err());
+    assert!(m.requests().is_empty());
+}
+
+#[test]
+fn requests_to_unknown_hosts_are_not_upgraded() {
+    let m = mock!();
+
+    let store = HstsStore::new();
+    store.insert("example.org", true, Duration::from_secs(60));
+
+    let client = HttpClient::builder().hsts(store).build().unwrap();
+    let response = client
+        .get(format!("http://localhost:{}/", m.addr().port()))
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn hsts_header_over_plain_http_is_ignored() {
+    let m = mock! {
+        headers {
+            "strict-transport-security": "max-age=60",
+        }
+    };
+
+    let store = HstsStore::new();
+
+    Request::get(format!("http://localhost:{}/", m.addr().port()))
+        .hsts(store.clone())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert!(store.entries().is_empty());
+    assert!(store.get("localhost").is_none());
+}
@@ -1,6 +1,5 @@
-#![cfg(feature = "unstable-interceptors")]
-
-use isahc::HttpClient;
+use isahc::{error::ErrorKind, interceptor::MirrorInterceptor, prelude::*, AsyncBody, HttpClient};
+use std::{thread, time::Duration};
 use testserver::mock;
 
 #[test]
@@ -19,3 +18,74 @@ fn change_http_method_with_interceptor() {
 
     assert_eq!(m.request().method, "HEAD");
 }
+
+#[test]
+fn context_records_each_attempt() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .interceptor(isahc::interceptor!(request, cx, {
+            assert_eq!(cx.attempts(), 0);
+            assert!(cx.last_attempt().is_none());
+
+            let response = cx.send(request).await;
+
+            let attempt = cx.last_attempt().unwrap();
+            assert_eq!(attempt.number(), 1);
+            assert_eq!(attempt.is_connection_reused(), Some(false));
+            assert!(attempt.error().is_none());
+
+            // Nothing is listening on port 1.
+            let unreachable = Request::get("http://127.0.0.1:1")
+                .body(AsyncBody::empty())
+                .unwrap();
+            assert!(cx.send(unreachable).await.is_err());
+
+            let attempt = cx.last_attempt().unwrap();
+            assert_eq!(cx.attempts(), 2);
+            assert_eq!(attempt.is_connection_reused(), Some(false));
+            assert_eq!(
+                attempt.error().map(|e| e.kind()),
+                Some(&ErrorKind::ConnectionFailed)
+            );
+
+            response
+        }))
+        .build()
+        .unwrap();
+
+    client.get(m.url()).unwrap();
+}
+
+#[test]
+fn mirror_interceptor_sends_copy_of_request() {
+    let m = mock! {
+        body: "primary",
+    };
+    let mirror = mock! {
+        body: "mirror",
+    };
+
+    let client = HttpClient::builder()
+        .interceptor(MirrorInterceptor::new(mirror.url().parse().unwrap()))
+        .build()
+        .unwrap();
+
+    let mut response = client.post(format!("{}items?a=b", m.url()), "hello").unwrap();
+
+    assert_eq!(response.text().unwrap(), "primary");
+    assert_eq!(m.request().url, "/items?a=b");
+
+    // The mirrored request is sent in the background.
+    for _ in 0..50 {
+        if !mirror.requests().is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let request = mirror.request();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.url, "/items?a=b");
+    assert_eq!(request.body.as_deref(), Some(b"hello".as_ref()));
+}
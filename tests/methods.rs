@@ -60,3 +60,25 @@ fn arbitrary_foobar_request() {
 
     assert_eq!(m.request().method, "FOOBAR");
 }
+
+#[test]
+fn reused_handle_does_not_carry_over_previous_request() {
+    let m = mock!();
+    let client = HttpClient::new().unwrap();
+
+    client
+        .send(
+            Request::post(m.url())
+                .header("x-first", "yes")
+                .body("hello")
+                .unwrap(),
+        )
+        .unwrap();
+    client.get(m.url()).unwrap();
+
+    let requests = m.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].method, "GET");
+    assert_eq!(requests[1].get_header("x-first").count(), 0);
+    assert_eq!(requests[1].get_header("content-length").count(), 0);
+}
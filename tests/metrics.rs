@@ -1,5 +1,12 @@
-use isahc::prelude::*;
-use std::{io, time::Duration};
+use flate2::{read::GzEncoder, Compression};
+use futures_lite::future::{block_on, poll_once};
+use isahc::{config::CancelPolicy, prelude::*};
+use std::{
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 use testserver::mock;
 
 #[test]
@@ -34,3 +41,338 @@ fn enabling_metrics_causes_metrics_to_be_collected() {
     assert_eq!(metrics.download_progress().0, 11);
     assert!(metrics.total_time() > Duration::default());
 }
+
+#[test]
+fn agent_stats_are_recorded() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let client = isahc::HttpClient::new().unwrap();
+
+    assert_eq!(client.agent_stats().active_transfers(), 0);
+
+    client.get(m.url()).unwrap().text().unwrap();
+
+    let stats = client.agent_stats();
+
+    assert!(stats.iterations() > 0);
+    assert!(stats.max_loop_latency() >= stats.loop_latency());
+}
+
+#[test]
+fn body_pipeline_is_included_in_metrics() {
+    let body = "hello world".repeat(100);
+    let mut body_encoded = Vec::new();
+
+    GzEncoder::new(body.as_bytes(), Compression::default())
+        .read_to_end(&mut body_encoded)
+        .unwrap();
+
+    let encoded_len = body_encoded.len() as u64;
+
+    let m = mock! {
+        headers {
+            "Content-Encoding": "gzip",
+        }
+        body: body_encoded.clone(),
+    };
+
+    let client = isahc::HttpClient::builder().metrics(true).build().unwrap();
+    let mut response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.text().unwrap(), body);
+
+    let metrics = response.metrics().unwrap();
+
+    assert_eq!(metrics.download_progress().0, encoded_len);
+    assert_eq!(metrics.decompressed_bytes(), body.len() as u64);
+    assert!(metrics.buffer_copies() > 0);
+    assert_eq!(metrics.buffered_bytes(), body.len() as u64);
+    assert_eq!(metrics.decode_replacements(), 0);
+}
+
+#[test]
+fn decode_replacements_are_included_in_metrics() {
+    let m = mock! {
+        body: b"caf\xe9 cr\xe8me".to_vec(),
+    };
+
+    let client = isahc::HttpClient::builder().metrics(true).build().unwrap();
+    let mut response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.text().unwrap(), "caf\u{fffd} cr\u{fffd}me");
+
+    let metrics = response.metrics().unwrap();
+
+    assert_eq!(metrics.decompressed_bytes(), 0);
+    assert_eq!(metrics.decode_replacements(), 2);
+}
+
+#[test]
+fn route_is_included_in_metrics_and_response() {
+    let m = mock!();
+
+    let client = isahc::HttpClient::builder().metrics(true).build().unwrap();
+
+    let response = client
+        .send(
+            Request::get(format!("{}/users/42", m.url()))
+                .body(())
+                .unwrap()
+                .route("/users/{id}"),
+        )
+        .unwrap();
+
+    assert_eq!(response.route(), Some("/users/{id}"));
+    assert_eq!(response.metrics().unwrap().route(), Some("/users/{id}"));
+}
+
+#[test]
+fn dropped_future_aborts_request_waiting_for_response() {
+    let m = mock! {
+        delay: 5s,
+    };
+
+    let client = isahc::HttpClient::new().unwrap();
+    let mut future = client.get_async(m.url());
+
+    // Give the request enough time to be sent, so that curl is waiting for the
+    // response when the future is dropped.
+    assert!(block_on(poll_once(&mut future)).is_none());
+    thread::sleep(Duration::from_millis(200));
+    drop(future);
+
+    let dropped_at = Instant::now();
+
+    while client.agent_stats().canceled_transfers() == 0 {
+        assert!(dropped_at.elapsed() < Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    assert!(client.agent_stats().max_cancel_latency() < Duration::from_millis(100));
+}
+
+#[test]
+fn dropped_future_does_not_abort_detached_request() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let client = isahc::HttpClient::new().unwrap();
+    let mut future = client.send_async(
+        Request::get(m.url())
+            .cancel_policy(CancelPolicy::Detach)
+            .body(())
+            .unwrap(),
+    );
+
+    assert!(block_on(poll_once(&mut future)).is_none());
+    thread::sleep(Duration::from_millis(200));
+    drop(future);
+    thread::sleep(Duration::from_millis(200));
+
+    let stats = client.agent_stats();
+
+    assert_eq!(stats.canceled_transfers(), 0);
+    assert_eq!(stats.max_cancel_latency(), Duration::default());
+}
+
+#[test]
+fn progress_callback_receives_upload_and_download_progress() {
+    let m = mock! {
+        body: "hello world",
+    };
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let mut response = Request::post(m.url())
+        .progress_callback({
+            let events = events.clone();
+            move |progress| events.lock().unwrap().push(progress)
+        })
+        .body("hello server")
+        .unwrap()
+        .send()
+        .unwrap();
+
+    response.text().unwrap();
+
+    let events = events.lock().unwrap();
+    let last = events.last().unwrap();
+
+    assert_eq!(last.uploaded(), 12);
+    assert_eq!(last.upload_total(), Some(12));
+    assert_eq!(last.downloaded(), 11);
+    assert_eq!(last.download_total(), Some(11));
+
+    // The callback is only invoked when the progress changes.
+    assert!(events.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn client_progress_callback_is_used_for_async_requests() {
+    let m = mock! {
+        body: "hello world",
+    };
+    let downloaded = Arc::new(Mutex::new(0));
+
+    let client = isahc::HttpClient::builder()
+        .progress_callback({
+            let downloaded = downloaded.clone();
+            move |progress| *downloaded.lock().unwrap() = progress.downloaded()
+        })
+        .build()
+        .unwrap();
+
+    block_on(async {
+        let mut response = client.get_async(m.url()).await.unwrap();
+        response.text().await.unwrap();
+    });
+
+    assert_eq!(*downloaded.lock().unwrap(), 11);
+}
+
+#[test]
+fn pool_stats_count_idle_connections_by_host() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let client = isahc::HttpClient::new().unwrap();
+    let stats = client.pool_stats().unwrap();
+
+    assert_eq!(stats.open_connections(), 0);
+    assert_eq!(stats.idle_connections(), 0);
+
+    client.get(m.url()).unwrap().text().unwrap();
+    client.get(m.url()).unwrap().text().unwrap();
+
+    let stats = client.pool_stats().unwrap();
+    let host = m.addr().to_string();
+
+    assert_eq!(stats.open_connections(), 1);
+    assert_eq!(
+        stats.idle_connections_by_host().collect::<Vec<_>>(),
+        vec![(host.as_str(), 1)]
+    );
+    assert_eq!(stats.active_transfers(), 0);
+}
+
+#[test]
+fn pool_stats_count_active_transfers() {
+    let m = mock! {
+        delay: 500ms,
+    };
+
+    let client = isahc::HttpClient::new().unwrap();
+    let mut future = client.get_async(m.url());
+
+    assert!(block_on(poll_once(&mut future)).is_none());
+    thread::sleep(Duration::from_millis(100));
+
+    let stats = client.pool_stats().unwrap();
+
+    assert_eq!(stats.open_connections(), 1);
+    assert_eq!(stats.idle_connections(), 0);
+    assert_eq!(stats.active_transfers(), 1);
+
+    block_on(future).unwrap();
+}
+
+#[test]
+fn requests_over_concurrent_limit_are_queued_in_order() {
+    let m = mock! {
+        delay: 200ms,
+    };
+
+    let client = isahc::HttpClient::builder()
+        .max_concurrent_requests(1)
+        .build()
+        .unwrap();
+
+    let mut futures = (0..3)
+        .map(|i| client.get_async(format!("{}{}", m.url(), i)))
+        .collect::<Vec<_>>();
+
+    for future in &mut futures {
+        assert!(block_on(poll_once(future)).is_none());
+    }
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert_eq!(client.agent_stats().queued_requests(), 2);
+
+    for future in futures {
+        block_on(future).unwrap();
+    }
+
+    assert_eq!(client.agent_stats().queued_requests(), 0);
+    assert_eq!(
+        m.requests()
+            .into_iter()
+            .map(|request| request.url)
+            .collect::<Vec<_>>(),
+        vec!["/0", "/1", "/2"]
+    );
+}
+
+#[test]
+fn queued_request_fails_after_queue_timeout() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let client = isahc::HttpClient::builder()
+        .max_concurrent_requests(1)
+        .queue_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    let mut first = client.get_async(m.url());
+    assert!(block_on(poll_once(&mut first)).is_none());
+
+    let error = client.get(m.url()).unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::QueueTimeout);
+
+    block_on(first).unwrap();
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn concurrent_limit_applies_across_pool_partitions() {
+    let m = mock! {
+        delay: 200ms,
+    };
+
+    let client = isahc::HttpClient::builder()
+        .max_concurrent_requests(1)
+        .build()
+        .unwrap();
+
+    let mut futures = ["alice", "bob", "carol"]
+        .iter()
+        .map(|key| {
+            client.send_async(
+                Request::get(m.url())
+                    .connection_pool_partition(*key)
+                    .body(())
+                    .unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for future in &mut futures {
+        assert!(block_on(poll_once(future)).is_none());
+    }
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert_eq!(client.agent_stats().queued_requests(), 2);
+
+    for future in futures {
+        block_on(future).unwrap();
+    }
+
+    assert_eq!(m.requests().len(), 3);
+}
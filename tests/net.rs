@@ -1,5 +1,20 @@
-use isahc::prelude::*;
-use std::net::Ipv4Addr;
+use isahc::{
+    config::{Dialer, ResolveMap, ReusePolicy},
+    prelude::*,
+    ShareHandle,
+};
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 use testserver::mock;
 
 #[test]
@@ -22,3 +37,509 @@ fn remote_addr_returns_expected_address_expected_address() {
     assert!(!m.requests().is_empty());
     assert_eq!(response.remote_addr(), Some(m.addr()));
 }
+
+#[test]
+fn connect_attempts_are_included_in_connection_errors() {
+    // Find a port that nothing is listening on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let error = Request::get(format!("http://127.0.0.1:{}", port))
+        .connection_report(true)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::ConnectionFailed);
+    assert_eq!(error.connect_attempts().len(), 1);
+    assert_eq!(error.connect_attempts()[0].ip(), Ipv4Addr::LOCALHOST);
+}
+
+#[test]
+fn fresh_reuse_policy_opens_new_connection() {
+    let m = mock!();
+
+    let client = HttpClient::builder().connection_report(true).build().unwrap();
+
+    client.get(m.url()).unwrap().text().unwrap();
+
+    for &policy in &[ReusePolicy::Fresh, ReusePolicy::Isolated] {
+        let mut response = client
+            .send(
+                Request::get(m.url())
+                    .connection_reuse(policy)
+                    .body(())
+                    .unwrap(),
+            )
+            .unwrap();
+
+        response.text().unwrap();
+
+        assert!(!response.connection_report().unwrap().is_reused());
+    }
+
+    assert_eq!(m.requests().len(), 3);
+}
+
+#[test]
+fn http2_keepalive_does_not_reuse_connections_idle_past_timeout() {
+    let m = mock!();
+
+    let client = HttpClient::builder()
+        .connection_report(true)
+        .http2_keepalive(Duration::from_millis(500), Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    client.get(m.url()).unwrap().text().unwrap();
+
+    let mut response = client.get(m.url()).unwrap();
+    response.text().unwrap();
+    assert!(response.connection_report().unwrap().is_reused());
+
+    thread::sleep(Duration::from_millis(1500));
+
+    let mut response = client.get(m.url()).unwrap();
+    response.text().unwrap();
+    assert!(!response.connection_report().unwrap().is_reused());
+}
+
+#[test]
+fn pool_partitions_do_not_share_connections() {
+    let m = mock!();
+
+    let client = HttpClient::builder().connection_report(true).build().unwrap();
+
+    client.get(m.url()).unwrap().text().unwrap();
+
+    for key in &["alice", "bob"] {
+        let mut response = client
+            .send(
+                Request::get(m.url())
+                    .connection_pool_partition(*key)
+                    .body(())
+                    .unwrap(),
+            )
+            .unwrap();
+
+        response.text().unwrap();
+
+        assert!(!response.connection_report().unwrap().is_reused());
+    }
+
+    // Connections are still reused within a partition.
+    let mut response = client
+        .send(
+            Request::get(m.url())
+                .connection_pool_partition("alice")
+                .body(())
+                .unwrap(),
+        )
+        .unwrap();
+
+    response.text().unwrap();
+
+    assert!(response.connection_report().unwrap().is_reused());
+    assert_eq!(m.requests().len(), 4);
+
+    // Connections of partitions are pooled by the same agent.
+    assert_eq!(client.pool_stats().unwrap().open_connections(), 3);
+}
+
+// Curl itself retries requests on a reused connection that fails before a
+// complete line of the response is received, so this resets the connection
+// after the status line instead.
+#[test]
+#[cfg(unix)]
+fn request_is_retried_when_pooled_connection_is_reset() {
+    for &retry in &[false, true] {
+        let requests = AtomicUsize::new(0);
+
+        // Reset the pooled connection when the second request arrives on it.
+        let m = mock! {
+            reset_after: if requests.fetch_add(1, Ordering::SeqCst) == 1 {
+                Some("HTTP/1.1 200 OK\r\n")
+            } else {
+                None
+            },
+        };
+
+        let client = HttpClient::builder()
+            .retry_stale_connections(retry)
+            .build()
+            .unwrap();
+
+        client.get(m.url()).unwrap().text().unwrap();
+
+        let result = client.get(m.url());
+
+        if retry {
+            assert_eq!(result.unwrap().status(), 200);
+            assert_eq!(m.requests().len(), 3);
+        } else {
+            assert!(result.is_err());
+            assert_eq!(m.requests().len(), 2);
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn post_request_is_not_retried_when_pooled_connection_is_reset() {
+    let requests = AtomicUsize::new(0);
+
+    let m = mock! {
+        reset_after: if requests.fetch_add(1, Ordering::SeqCst) == 1 {
+            Some("HTTP/1.1 200 OK\r\n")
+        } else {
+            None
+        },
+    };
+
+    let client = HttpClient::new().unwrap();
+
+    client.get(m.url()).unwrap().text().unwrap();
+
+    assert!(client.post(m.url(), "event").is_err());
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn detached_request_is_sent_even_if_handle_is_dropped() {
+    let m = mock! {
+        delay: 50ms,
+        status: 202,
+    };
+
+    let client = HttpClient::new().unwrap();
+
+    drop(
+        client
+            .send_detached(Request::post(m.url()).body("beacon").unwrap())
+            .unwrap(),
+    );
+
+    let handle = client
+        .send_detached(Request::post(m.url()).body("beacon").unwrap())
+        .unwrap();
+
+    assert_eq!(handle.wait().unwrap(), 202);
+
+    // The first request may still be in flight.
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while m.requests().len() < 2 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn detached_request_failure_is_recorded() {
+    // Bind a port and close it again so that nothing is listening on it.
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let handle = HttpClient::new()
+        .unwrap()
+        .send_detached(
+            Request::post(format!("http://{}", addr))
+                .body("beacon")
+                .unwrap(),
+        )
+        .unwrap();
+
+    let error = handle.wait().unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::ConnectionFailed);
+}
+
+#[test]
+fn send_detached_does_not_wait_for_connection() {
+    let m = mock! {
+        status: 202,
+    };
+    let addr = m.addr();
+    let (sender, receiver) = mpsc::channel::<()>();
+    let receiver = Mutex::new(receiver);
+
+    // Only connect once the request has been handed off.
+    let dialer = Dialer::custom(move |_: &isahc::config::DialTarget<'_>| {
+        receiver
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        TcpStream::connect(addr)
+    });
+
+    let client = HttpClient::new().unwrap();
+    let handle = client
+        .send_detached(
+            Request::post("http://example.invalid/beacon")
+                .dial(dialer)
+                .body("beacon")
+                .unwrap(),
+        )
+        .unwrap();
+
+    assert!(m.requests().is_empty());
+    sender.send(()).unwrap();
+
+    assert_eq!(handle.wait().unwrap(), 202);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn clients_with_share_handle_share_connections() {
+    let m = mock!();
+
+    let share = ShareHandle::new().unwrap();
+    let build = || {
+        HttpClient::builder()
+            .share(share.clone())
+            .connection_report(true)
+            .build()
+            .unwrap()
+    };
+
+    let client_a = build();
+    let client_b = build();
+
+    client_a.get(m.url()).unwrap().text().unwrap();
+
+    let mut response = client_b.get(m.url()).unwrap();
+    response.text().unwrap();
+
+    assert!(response.connection_report().unwrap().is_reused());
+}
+
+#[test]
+fn connect_to_sends_request_to_target_with_original_host() {
+    let m = mock!();
+
+    let response = Request::get("http://backend.example:8080/a")
+        .connect_to(
+            "backend.example",
+            8080,
+            m.addr().ip().to_string(),
+            m.addr().port(),
+        )
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.remote_addr(), Some(m.addr()));
+    m.request().expect_header("host", "backend.example:8080");
+}
+
+#[test]
+fn dns_resolve_on_request_does_not_affect_other_requests() {
+    let m = mock!();
+    let client = HttpClient::new().unwrap();
+    let uri = format!("http://backend.invalid:{}/", m.addr().port());
+
+    let response = client
+        .send(
+            Request::get(&uri)
+                .dns_resolve(ResolveMap::new().add_all(
+                    "backend.invalid",
+                    m.addr().port(),
+                    vec![m.addr().ip()],
+                ))
+                .body(())
+                .unwrap(),
+        )
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.remote_addr(), Some(m.addr()));
+
+    let error = client.get(&uri).unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::NameResolution);
+}
+
+#[test]
+fn prefetched_host_can_be_requested() {
+    let m = mock!();
+    let client = HttpClient::new().unwrap();
+
+    client.prefetch_dns("localhost").unwrap();
+
+    let response = client
+        .get(format!("http://localhost:{}", m.addr().port()))
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn prefetching_unknown_host_fails() {
+    let client = HttpClient::new().unwrap();
+
+    let error = client.prefetch_dns("nonexistent.invalid").unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::NameResolution);
+}
+
+#[test]
+fn connected_socket_is_used_for_one_connection() {
+    let m = mock!();
+    let dialer = Dialer::connected_socket(TcpStream::connect(m.addr()).unwrap());
+
+    // The host in the URI is not connected to.
+    let response = Request::get("http://127.0.0.1:1/connected")
+        .dial(dialer.clone())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(m.request().url, "/connected");
+
+    let error = Request::get(m.url())
+        .dial(dialer)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::ConnectionFailed);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn custom_dialer_opens_connections() {
+    let m = mock!();
+    let addr = m.addr();
+    let dialer = Dialer::custom(move |target: &isahc::config::DialTarget<'_>| {
+        assert_eq!(target.host(), "example.invalid");
+        assert_eq!(target.port(), 8080);
+
+        TcpStream::connect(addr)
+    });
+
+    for _ in 0..2 {
+        let response = Request::get("http://example.invalid:8080/dialed")
+            .dial(dialer.clone())
+            .body(())
+            .unwrap()
+            .send()
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.remote_addr(), Some(addr));
+    }
+
+    assert_eq!(m.requests().len(), 2);
+    assert_eq!(m.request().url, "/dialed");
+}
+
+#[test]
+fn slow_custom_dialer_does_not_block_other_requests() {
+    let m = mock!();
+    let addr = m.addr();
+    let (sender, receiver) = mpsc::channel::<()>();
+    let receiver = Mutex::new(receiver);
+
+    // Only connect once the other request has completed.
+    let dialer = Dialer::custom(move |_: &isahc::config::DialTarget<'_>| {
+        receiver
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        TcpStream::connect(addr)
+    });
+
+    let client = HttpClient::new().unwrap();
+
+    let slow = thread::spawn({
+        let client = client.clone();
+
+        move || {
+            client.send(
+                Request::get("http://example.invalid/slow")
+                    .dial(dialer)
+                    .body(())
+                    .unwrap(),
+            )
+        }
+    });
+
+    let response = client.get(m.url()).unwrap();
+    assert_eq!(response.status(), 200);
+    sender.send(()).unwrap();
+
+    assert_eq!(slow.join().unwrap().unwrap().status(), 200);
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn custom_dialer_error_is_returned() {
+    let dialer = Dialer::custom(|_: &isahc::config::DialTarget<'_>| {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "tunnel unavailable"))
+    });
+
+    let error = Request::get("http://example.invalid")
+        .dial(dialer)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::ConnectionFailed);
+    assert_eq!(
+        std::error::Error::source(&error).unwrap().to_string(),
+        "tunnel unavailable"
+    );
+}
+
+#[test]
+fn custom_dialer_threads_are_reused() {
+    let m = mock!();
+    let addr = m.addr();
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+
+    let dialer = Dialer::custom({
+        let threads = threads.clone();
+
+        move |_: &isahc::config::DialTarget<'_>| {
+            threads.lock().unwrap().insert(thread::current().id());
+
+            TcpStream::connect(addr)
+        }
+    });
+
+    let client = HttpClient::new().unwrap();
+
+    for _ in 0..20 {
+        let response = client
+            .send(
+                Request::get("http://example.invalid")
+                    .dial(dialer.clone())
+                    .body(())
+                    .unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+    }
+
+    assert_eq!(m.requests().len(), 20);
+    assert!(threads.lock().unwrap().len() < 20);
+}
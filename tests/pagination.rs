@@ -0,0 +1,183 @@
+use futures_lite::{future::block_on, stream::StreamExt};
+use isahc::{prelude::*, HttpClient};
+use testserver::mock;
+
+#[test]
+fn next_page_uri_follows_link_header() {
+    let m = mock! {
+        headers {
+            "Link": "</items?page=2>; rel=\"next\", </items?page=9>; rel=\"last\"",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(
+        response.next_page_uri().unwrap().to_string(),
+        format!("{}items?page=2", m.url())
+    );
+}
+
+#[test]
+fn links_are_parsed_from_all_link_headers() {
+    let m = mock! {
+        headers {
+            "Link": "</items?page=2>; rel=\"next\"",
+            "Link": "<https://example.org/docs>; rel=\"help describedby\"; title=\"Docs\"",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+    let links = response.links();
+
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].target(), "/items?page=2");
+    assert_eq!(
+        links[0].uri().unwrap().to_string(),
+        format!("{}items?page=2", m.url())
+    );
+    assert_eq!(links[1].relations(), vec!["help", "describedby"]);
+    assert_eq!(links[1].title(), Some("Docs"));
+}
+
+#[test]
+fn next_page_uri_is_none_without_link() {
+    let m = mock!();
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.next_page_uri(), None);
+}
+
+#[test]
+fn paginate_follows_next_links_until_exhausted() {
+    let m3 = mock! {
+        body: "3",
+    };
+
+    let m2 = {
+        let link = format!("<{}>; rel=next", m3.url());
+        mock! {
+            body: "2",
+            headers {
+                "Link": link,
+            }
+        }
+    };
+
+    let m1 = {
+        let link = format!("<{}>; rel=next", m2.url());
+        mock! {
+            body: "1",
+            headers {
+                "Link": link,
+            }
+        }
+    };
+
+    let client = HttpClient::new().unwrap();
+    let request = Request::get(m1.url())
+        .header("X-Token", "abc")
+        .body(())
+        .unwrap();
+
+    let bodies = block_on(async {
+        let mut pages = client.paginate(request);
+        let mut bodies = Vec::new();
+
+        while let Some(response) = pages.next().await {
+            bodies.push(response.unwrap().text().await.unwrap());
+        }
+
+        bodies
+    });
+
+    assert_eq!(bodies, vec!["1", "2", "3"]);
+    m3.request().expect_header("X-Token", "abc");
+}
+
+#[test]
+fn paginate_stops_at_max_pages() {
+    let m2 = mock! {
+        body: "2",
+    };
+
+    let m1 = {
+        let link = format!("<{}>; rel=next", m2.url());
+        mock! {
+            body: "1",
+            headers {
+                "Link": link,
+            }
+        }
+    };
+
+    let client = HttpClient::new().unwrap();
+    let request = Request::get(m1.url()).body(()).unwrap();
+
+    let count = block_on(client.paginate(request).max_pages(1).count());
+
+    assert_eq!(count, 1);
+    assert!(m2.requests().is_empty());
+}
+
+#[test]
+fn paginate_strips_sensitive_headers_on_origin_change() {
+    // Every mock listens on its own port, so this is another origin.
+    let m2 = mock! {
+        body: "2",
+    };
+
+    let m1 = {
+        let link = format!("<{}>; rel=next", m2.url());
+        mock! {
+            body: "1",
+            headers {
+                "Link": link,
+            }
+        }
+    };
+
+    let client = HttpClient::new().unwrap();
+    let request = Request::get(m1.url())
+        .header("Authorization", "Bearer secret")
+        .header("Cookie", "session=secret")
+        .header("X-Token", "abc")
+        .body(())
+        .unwrap();
+
+    let count = block_on(client.paginate(request).count());
+
+    assert_eq!(count, 2);
+    m1.request().expect_header("Authorization", "Bearer secret");
+    m1.request().expect_header("Cookie", "session=secret");
+    assert_eq!(m2.request().get_header("Authorization").count(), 0);
+    assert_eq!(m2.request().get_header("Cookie").count(), 0);
+    m2.request().expect_header("X-Token", "abc");
+}
+
+#[test]
+fn paginate_keeps_sensitive_headers_on_same_origin() {
+    let m = {
+        let link = "</?page=2>; rel=next";
+        mock! {
+            headers {
+                "Link": link,
+            }
+        }
+    };
+
+    let client = HttpClient::new().unwrap();
+    let request = Request::get(m.url())
+        .header("Authorization", "Bearer secret")
+        .body(())
+        .unwrap();
+
+    let count = block_on(client.paginate(request).count());
+
+    assert_eq!(count, 2);
+
+    for request in m.requests() {
+        request.expect_header("Authorization", "Bearer secret");
+    }
+}
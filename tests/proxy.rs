@@ -1,4 +1,4 @@
-use isahc::prelude::*;
+use isahc::{error::ErrorKind, prelude::*};
 use testserver::{mock, socks4::Socks4Server};
 
 #[test]
@@ -95,3 +95,55 @@ fn proxy_blacklist_works() {
 
     assert_eq!(m.requests().len(), 1);
 }
+
+#[test]
+#[cfg_attr(tarpaulin, ignore)]
+fn check_proxy_succeeds_through_socks4_proxy() {
+    let proxy_server = Socks4Server::new("127.0.0.1:0").unwrap();
+
+    let proxy_uri = http::Uri::builder()
+        .scheme("socks4")
+        .authority(proxy_server.addr().to_string().as_str())
+        .path_and_query("/")
+        .build()
+        .unwrap();
+
+    proxy_server.spawn();
+
+    let m = mock!();
+
+    let client = HttpClient::builder().proxy(proxy_uri).build().unwrap();
+
+    client.check_proxy(m.url()).unwrap();
+
+    // Only a connection was made, no request was sent.
+    assert!(m.requests().is_empty());
+}
+
+#[test]
+fn check_proxy_without_proxy_is_an_error() {
+    let client = HttpClient::builder().proxy(None).build().unwrap();
+
+    let error = client.check_proxy("http://127.0.0.2:1234/").unwrap_err();
+
+    assert_eq!(error.kind(), &ErrorKind::InvalidRequest);
+}
+
+#[test]
+fn check_proxy_reports_unreachable_proxy() {
+    // Bind and immediately drop a listener to get a port that is not in use.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let client = HttpClient::builder()
+        .proxy(Some(format!("http://127.0.0.1:{}", port).parse().unwrap()))
+        .build()
+        .unwrap();
+
+    let error = client.check_proxy("http://127.0.0.2:1234/").unwrap_err();
+
+    assert_eq!(error.kind(), &ErrorKind::ConnectionFailed);
+}
@@ -0,0 +1,53 @@
+use futures_lite::future::block_on;
+use isahc::{prelude::*, HttpClient};
+use testserver::mock;
+
+#[test]
+fn pushed_responses_are_not_available_unless_enabled() {
+    let m = mock! {
+        body: "hello",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.text().unwrap(), "hello");
+    assert!(response.pushed_responses().is_none());
+}
+
+#[test]
+fn pushed_responses_end_when_nothing_is_pushed() {
+    let m = mock! {
+        body: "hello",
+    };
+
+    let client = HttpClient::builder().enable_push().build().unwrap();
+    let mut response = client.get(m.url()).unwrap();
+
+    assert_eq!(response.text().unwrap(), "hello");
+
+    let pushed = response.pushed_responses().unwrap();
+
+    assert!(pushed.recv().is_none());
+    assert!(pushed.try_recv().is_none());
+}
+
+#[test]
+fn pushed_responses_end_asynchronously_when_nothing_is_pushed() {
+    let m = mock! {
+        body: "hello",
+    };
+
+    let client = HttpClient::builder().enable_push().build().unwrap();
+
+    block_on(async {
+        let mut response = client.get_async(m.url()).await.unwrap();
+
+        assert_eq!(response.text().await.unwrap(), "hello");
+        assert!(response
+            .pushed_responses()
+            .unwrap()
+            .recv_async()
+            .await
+            .is_none());
+    });
+}
@@ -1,4 +1,9 @@
-use isahc::{config::RedirectPolicy, prelude::*, Body};
+use isahc::{
+    config::{HeaderMergePolicy, OriginChange, RedirectHeaderPolicy, RedirectPolicy},
+    prelude::*,
+    Body,
+};
+use std::time::Duration;
 use test_case::test_case;
 use testserver::mock;
 
@@ -283,3 +288,180 @@ fn auto_referer_sets_expected_header() {
     m2.request().expect_header("Referer", m1.url());
     m3.request().expect_header("Referer", m2.url());
 }
+
+#[test]
+fn redirect_hops_are_recorded_with_metrics() {
+    let m3 = mock! {
+        status: 200,
+        body: "ok",
+    };
+    let location = m3.url();
+
+    let m2 = mock! {
+        status: 302,
+        headers {
+            "Location": location,
+        }
+    };
+    let location = m2.url();
+
+    let m1 = mock! {
+        status: 301,
+        headers {
+            "Location": location,
+        }
+    };
+
+    let response = Request::get(m1.url())
+        .redirect_policy(RedirectPolicy::Follow)
+        .metrics(true)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+
+    let hops = response.redirect_hops();
+    assert_eq!(hops.len(), 2);
+    assert_eq!(hops[0].status(), 301);
+    assert_eq!(hops[0].uri().to_string(), m1.url());
+    assert_eq!(hops[1].status(), 302);
+    assert_eq!(hops[1].uri().to_string(), m2.url());
+
+    for hop in hops {
+        assert!(hop.metrics().unwrap().transfer_start_time() > Duration::default());
+    }
+}
+
+#[test]
+fn request_extensions_and_tags_are_preserved_across_redirects() {
+    #[derive(Debug, PartialEq)]
+    struct Marker(u32);
+
+    let m2 = mock! {
+        status: 200,
+        body: "ok",
+    };
+    let location = m2.url();
+
+    let m1 = mock! {
+        status: 302,
+        headers {
+            "Location": location,
+        }
+    };
+
+    let mut request = Request::get(m1.url())
+        .redirect_policy(RedirectPolicy::Follow)
+        .body(())
+        .unwrap()
+        .tag("caller", "test");
+    request.extensions_mut().insert(Marker(42));
+
+    let response = request.send().unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.tag("caller"), Some("test"));
+    assert_eq!(response.tag("missing"), None);
+    assert_eq!(response.extensions().get::<Marker>(), Some(&Marker(42)));
+}
+
+#[test]
+fn sensitive_headers_are_stripped_on_cross_origin_redirect() {
+    let m2 = mock!();
+
+    let m1 = {
+        let location = m2.url();
+        mock! {
+            status: 301,
+            headers {
+                "Location": location,
+            }
+        }
+    };
+
+    Request::get(m1.url())
+        .redirect_policy(RedirectPolicy::Follow)
+        .header("Authorization", "Bearer secret")
+        .header("Cookie", "session=secret")
+        .header("X-Foo", "bar")
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m1.request().expect_header("Authorization", "Bearer secret");
+
+    let request = m2.request();
+    assert_eq!(request.get_header("Authorization").count(), 0);
+    assert_eq!(request.get_header("Cookie").count(), 0);
+    request.expect_header("X-Foo", "bar");
+}
+
+#[test]
+fn redirect_header_policy_can_keep_sensitive_headers() {
+    let m2 = mock!();
+
+    let m1 = {
+        let location = m2.url();
+        mock! {
+            status: 301,
+            headers {
+                "Location": location,
+            }
+        }
+    };
+
+    Request::get(m1.url())
+        .redirect_policy(RedirectPolicy::Follow)
+        .redirect_header_policy(
+            RedirectHeaderPolicy::default()
+                .allow(isahc::http::header::AUTHORIZATION, OriginChange::Port)
+                .sensitive_header("x-api-key".parse().unwrap()),
+        )
+        .header("Authorization", "Bearer secret")
+        .header("X-Api-Key", "secret")
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let request = m2.request();
+    request.expect_header("Authorization", "Bearer secret");
+    assert_eq!(request.get_header("X-Api-Key").count(), 0);
+}
+
+#[test_case(HeaderMergePolicy::FinalOnly, &["b=2"])]
+#[test_case(HeaderMergePolicy::All, &["b=2", "a=1"])]
+fn redirect_headers_are_merged_by_policy(policy: HeaderMergePolicy, cookies: &[&str]) {
+    let m2 = mock! {
+        status: 200,
+        headers {
+            "Set-Cookie": "b=2",
+        }
+        body: "ok",
+    };
+    let location = m2.url();
+
+    let m1 = mock! {
+        status: 302,
+        headers {
+            "Location": location,
+            "Set-Cookie": "a=1",
+        }
+    };
+
+    let response = Request::get(m1.url())
+        .redirect_policy(RedirectPolicy::Follow)
+        .header_merge_policy(policy)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header_values("set-cookie"), cookies);
+    assert_eq!(response.headers().get_all("location").iter().count(), 0);
+    assert_eq!(response.redirect_hops()[0].headers()["set-cookie"], "a=1");
+}
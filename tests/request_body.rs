@@ -1,10 +1,20 @@
-use futures_lite::{future::block_on, AsyncRead};
-use isahc::{prelude::*, AsyncBody, Body};
+use futures_lite::{
+    future::{block_on, poll_once},
+    AsyncRead,
+};
+use isahc::{
+    config::{CancelPolicy, VersionNegotiation},
+    prelude::*,
+    AsyncBody,
+    Body,
+};
 use std::{
     error::Error,
     io::{self, Read},
     pin::Pin,
     task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
 };
 use test_case::test_case;
 use testserver::mock;
@@ -68,6 +78,41 @@ fn request_with_body_of_unknown_size_uses_chunked_encoding(method: &str) {
     m.request().expect_body(body);
 }
 
+#[test]
+fn request_with_body_of_unknown_size_over_http10_is_sent_with_known_length() {
+    let body = "foo";
+
+    let m = mock!();
+
+    Request::post(m.url())
+        .version_negotiation(VersionNegotiation::http10())
+        .body(Body::from_reader(body.as_bytes()))
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request().expect_header("content-length", "3");
+    assert_eq!(m.request().get_header("transfer-encoding").count(), 0);
+    m.request().expect_body(body);
+}
+
+#[test]
+fn request_with_large_body_of_unknown_size_over_http10_is_rejected() {
+    let m = mock!();
+
+    let result = Request::post(m.url())
+        .version_negotiation(VersionNegotiation::http10())
+        .body(Body::from_reader(io::repeat(b'a')))
+        .unwrap()
+        .send();
+
+    assert_eq!(
+        result.unwrap_err().kind(),
+        isahc::error::ErrorKind::InvalidRequest
+    );
+    assert!(m.requests().is_empty());
+}
+
 #[ignore]
 #[test_case("GET")]
 #[test_case("HEAD")]
@@ -121,6 +166,46 @@ fn upload_from_bad_reader_returns_error_with_original_cause() {
     );
 }
 
+#[test]
+fn upload_from_async_reader_of_unknown_size_uses_chunked_encoding() {
+    let body = vec![b'a'; 200 * 1024];
+
+    let m = mock!();
+
+    block_on(async {
+        let reader = futures_lite::io::Cursor::new(body.clone());
+
+        isahc::put_async(m.url(), AsyncBody::from_reader(reader))
+            .await
+            .unwrap();
+    });
+
+    m.request().expect_header("transfer-encoding", "chunked");
+    m.request().expect_body(body);
+}
+
+#[test]
+fn upload_from_async_reader_of_known_size_sends_content_length() {
+    let body = vec![b'a'; 200 * 1024];
+
+    let m = mock!();
+
+    block_on(async {
+        let reader = futures_lite::io::Cursor::new(body.clone());
+
+        isahc::put_async(
+            m.url(),
+            AsyncBody::from_reader_sized(reader, body.len() as u64),
+        )
+        .await
+        .unwrap();
+    });
+
+    m.request().expect_header("content-length", "204800");
+    assert_eq!(m.request().get_header("transfer-encoding").count(), 0);
+    m.request().expect_body(body);
+}
+
 #[test]
 fn upload_from_bad_async_reader_returns_error_with_original_cause() {
     let m = mock!();
@@ -152,3 +237,75 @@ fn upload_from_bad_async_reader_returns_error_with_original_cause() {
         io::ErrorKind::UnexpectedEof
     );
 }
+
+#[test]
+fn detached_request_completes_after_future_is_dropped() {
+    let m = mock! {
+        delay: 100ms,
+        body: "hello world",
+    };
+
+    let client = HttpClient::new().unwrap();
+    let mut future = client.send_async(
+        Request::post(m.url())
+            .cancel_policy(CancelPolicy::Detach)
+            .body(vec![b'a'; 64 * 1024])
+            .unwrap(),
+    );
+
+    // Poll the future once so that the request is started, then lose interest
+    // in the response.
+    assert!(block_on(poll_once(&mut future)).is_none());
+    drop(future);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while m.requests().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(m.request().body.as_deref().map(<[u8]>::len), Some(64 * 1024));
+}
+
+#[test]
+fn multipart_body_sets_content_type_and_streams_parts() {
+    let m = mock!();
+
+    let body = isahc::MultipartBody::new()
+        .text("title", "hello")
+        .part(isahc::FormPart::bytes("upload", "contents").filename("a.txt"));
+    let boundary = body.boundary().to_owned();
+
+    Request::post(m.url()).body(body).unwrap().send().unwrap();
+
+    m.request().expect_header(
+        "content-type",
+        format!("multipart/form-data; boundary={}", boundary),
+    );
+    m.request().expect_body(format!(
+        "--{0}\r\n\
+         Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+         hello\r\n\
+         --{0}\r\n\
+         Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\r\n\
+         contents\r\n\
+         --{0}--\r\n",
+        boundary
+    ));
+}
+
+#[test]
+fn multipart_body_keeps_explicit_content_type() {
+    let m = mock!();
+
+    let body = isahc::MultipartBody::new().text("title", "hello");
+
+    Request::post(m.url())
+        .header("content-type", "multipart/mixed")
+        .body(body)
+        .unwrap()
+        .send()
+        .unwrap();
+
+    m.request().expect_header("content-type", "multipart/mixed");
+}
@@ -30,6 +30,85 @@ fn large_response_body() {
     assert_eq!(response_text, body);
 }
 
+#[cfg(feature = "spill-to-disk")]
+#[test]
+fn large_response_body_spilled_to_disk() {
+    let body = "wow so large ".repeat(100_000);
+    let dir = tempfile::tempdir().unwrap();
+
+    let m = {
+        let body = body.clone();
+        mock! {
+            body: body.clone(),
+        }
+    };
+
+    let mut response = Request::get(m.url())
+        .spill_to_disk(1024, dir.path())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    // Give the transfer a chance to get ahead of the reader.
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let response_text = response.text().unwrap();
+
+    assert_eq!(response_text, body);
+}
+
+#[test]
+fn large_response_body_with_fixed_buffer_size() {
+    let body = "wow so large ".repeat(100_000);
+
+    let m = {
+        let body = body.clone();
+        mock! {
+            body: body.clone(),
+        }
+    };
+
+    let mut response = Request::get(m.url())
+        .response_buffer_size(1024)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+    let response_text = response.text().unwrap();
+
+    assert_eq!(response_text, body);
+}
+
+#[test]
+fn responses_are_received_in_full_when_handles_are_reused() {
+    let large = "wow so large ".repeat(100_000);
+
+    let large_mock = {
+        let large = large.clone();
+        mock! {
+            body: large.clone(),
+        }
+    };
+    let small_mock = mock! {
+        body: "small",
+    };
+
+    // Handles of earlier requests, whose receive buffer grew while receiving
+    // the large bodies, are reused by the later ones.
+    let client = HttpClient::new().unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(client.get(large_mock.url()).unwrap().text().unwrap(), large);
+    }
+
+    for _ in 0..3 {
+        assert_eq!(client.get(small_mock.url()).unwrap().text().unwrap(), "small");
+    }
+
+    assert_eq!(client.get(large_mock.url()).unwrap().text().unwrap(), large);
+}
+
 #[test]
 fn response_body_with_content_length_knows_its_size() {
     let m = mock! {
@@ -92,3 +171,294 @@ fn reading_from_response_body_after_eof_continues_to_return_eof() {
         assert_eq!(body.read(&mut buf).unwrap(), 0);
     }
 }
+
+#[test]
+fn response_text_with_charset_override() {
+    let m = mock! {
+        headers {
+            "Content-Type": "text/plain; charset=utf-8",
+        }
+        body: b"caf\xe9".to_vec(),
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.text_with_charset("iso-8859-1").unwrap(), "café");
+}
+
+#[test]
+fn response_text_lossy_limit_returns_prefix() {
+    let body = "wow so large ".repeat(1000);
+
+    let m = {
+        let body = body.clone();
+        mock! {
+            body: body.clone(),
+        }
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.text_lossy_limit(12).unwrap(), "wow so large");
+}
+
+#[test]
+fn peek_does_not_consume_response_body() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.peek(5).unwrap(), b"hello");
+    assert_eq!(response.text().unwrap(), "hello world");
+}
+
+#[cfg(feature = "mime-sniff")]
+#[test]
+fn content_type_is_sniffed_if_not_declared() {
+    let m = mock! {
+        body: "%PDF-1.7 not really a pdf",
+        headers {
+            "content-type": "application/octet-stream",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.sniffed_content_type(), Some("application/pdf"));
+}
+
+#[cfg(feature = "mime-sniff")]
+#[test]
+fn declared_content_type_is_not_sniffed() {
+    let m = mock! {
+        body: "%PDF-1.7 not really a pdf",
+        headers {
+            "content-type": "text/plain",
+        }
+    };
+
+    let response = isahc::get(m.url()).unwrap();
+
+    assert_eq!(response.sniffed_content_type(), None);
+}
+
+#[test]
+fn error_for_status_includes_response_body() {
+    let m = mock! {
+        status: 404,
+        body: "no such widget",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+    let error = response.error_for_status().unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::BadStatus);
+    assert_eq!(error.status(), Some(isahc::http::StatusCode::NOT_FOUND));
+    assert_eq!(error.response_body(), Some("no such widget"));
+}
+
+#[test]
+fn error_for_status_respects_body_limit() {
+    let m = mock! {
+        status: 500,
+        body: "something went terribly wrong",
+    };
+
+    let mut response = Request::get(m.url())
+        .error_body_limit(9)
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+    let error = response.error_for_status().unwrap_err();
+
+    assert_eq!(error.response_body(), Some("something"));
+}
+
+#[test]
+fn error_for_status_ignores_success() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+
+    assert!(response.error_for_status().is_ok());
+    assert_eq!(response.text().unwrap(), "hello world");
+}
+
+#[test]
+fn download_if_changed_sends_stored_validators() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    let client = HttpClient::new().unwrap();
+
+    let m = mock! {
+        headers {
+            "ETag": "\"v1\"",
+        }
+        body: "{}",
+    };
+
+    assert_eq!(
+        client.download_if_changed(m.url(), &path).unwrap(),
+        isahc::DownloadOutcome::Updated(2)
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+    let m = mock! {
+        status: 304,
+    };
+
+    assert_eq!(
+        client.download_if_changed(m.url(), &path).unwrap(),
+        isahc::DownloadOutcome::Unchanged
+    );
+    m.request().expect_header("if-none-match", "\"v1\"");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+}
+
+#[test]
+fn response_futures_can_be_stored_and_sent() {
+    use futures_lite::future::block_on;
+    use isahc::{CopyFuture, ResponseFuture};
+
+    fn spawn<F: std::future::Future + Send + 'static>(future: F) -> F::Output
+    where
+        F::Output: Send + 'static,
+    {
+        std::thread::spawn(move || block_on(future)).join().unwrap()
+    }
+
+    let m = mock! {
+        body: "hello world",
+    };
+
+    // The future does not borrow from the client.
+    let future: ResponseFuture<'static> = HttpClient::new().unwrap().get_async(m.url());
+    let mut response = spawn(future).unwrap();
+
+    let mut buf = Vec::new();
+    let copy: CopyFuture<'_> = response.copy_to(&mut buf);
+
+    assert_eq!(block_on(copy).unwrap(), 11);
+    assert_eq!(buf, b"hello world");
+}
+
+#[test]
+fn split_response_body_into_records() {
+    use futures_lite::{future::block_on, stream::StreamExt};
+
+    let m = mock! {
+        body: "id,name\n1,curl\n2,isahc",
+    };
+
+    let records = block_on(async {
+        let mut response = isahc::get_async(m.url()).await.unwrap();
+
+        response
+            .split(b'\n')
+            .map(|record| String::from_utf8(record.unwrap()).unwrap())
+            .collect::<Vec<_>>()
+            .await
+    });
+
+    assert_eq!(records, vec!["id,name", "1,curl", "2,isahc"]);
+}
+
+#[test]
+fn heartbeats_in_response_body_are_told_apart_from_data() {
+    use futures_lite::{future::block_on, stream::StreamExt};
+    use isahc::Activity;
+    use std::time::Duration;
+
+    let m = mock! {
+        body: "{\"id\":1}\n: keep-alive\n\n{\"id\":2}\n",
+    };
+
+    let activity = block_on(async {
+        let mut response = isahc::get_async(m.url()).await.unwrap();
+
+        response
+            .heartbeats(Duration::from_secs(10))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await
+    });
+
+    assert_eq!(activity, vec![
+        Activity::Data(b"{\"id\":1}".to_vec()),
+        Activity::Heartbeat(b": keep-alive".to_vec()),
+        Activity::Heartbeat(b"".to_vec()),
+        Activity::Data(b"{\"id\":2}".to_vec()),
+    ]);
+}
+
+#[test]
+fn multipart_byteranges_response() {
+    let m = mock! {
+        status: 206,
+        headers {
+            "content-type": "multipart/byteranges; boundary=SEPARATOR",
+        }
+        body: "--SEPARATOR\r\ncontent-range: bytes 0-4/20\r\n\r\nhello\r\n\
+               --SEPARATOR\r\ncontent-range: bytes 15-19/20\r\n\r\nworld\r\n\
+               --SEPARATOR--\r\n",
+    };
+
+    let mut response = isahc::get(m.url()).unwrap();
+    let mut multipart = response.multipart().unwrap();
+    let mut parts = Vec::new();
+
+    while let Some(mut part) = multipart.next_part().unwrap() {
+        assert_eq!(part.status(), 206);
+        parts.push((part.headers()["content-range"].clone(), part.text().unwrap()));
+    }
+
+    assert_eq!(parts, vec![
+        ("bytes 0-4/20".parse().unwrap(), String::from("hello")),
+        ("bytes 15-19/20".parse().unwrap(), String::from("world")),
+    ]);
+}
+
+#[test]
+fn range_ignored_by_server_is_detected() {
+    let m = mock! {
+        body: "the entire body",
+    };
+
+    let response = Request::get(m.url())
+        .header("range", "bytes=4-")
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.range_outcome(), isahc::RangeOutcome::Ignored);
+}
+
+#[test]
+fn partial_content_matching_range() {
+    let m = mock! {
+        status: 206,
+        headers {
+            "content-range": "bytes 4-14/15",
+        }
+        body: "entire body",
+    };
+
+    let response = Request::get(m.url())
+        .header("range", "bytes=4-")
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let range = response.content_range().unwrap();
+
+    assert_eq!(response.range_outcome(), isahc::RangeOutcome::Partial(range));
+    assert_eq!(range.start(), 4);
+    assert_eq!(range.complete_length(), Some(15));
+}
@@ -0,0 +1,244 @@
+use isahc::{
+    config::{ExponentialBackoff, Jitter, RetryAttempt, RetryPolicy},
+    http::StatusCode,
+    prelude::*,
+    Body,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use testserver::mock;
+
+fn backoff() -> ExponentialBackoff {
+    ExponentialBackoff::new(3)
+        .base_delay(Duration::from_millis(10))
+        .jitter(Jitter::None)
+}
+
+#[test]
+fn unavailable_response_is_retried() {
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let m = {
+        let count = count.clone();
+
+        mock! {
+            status: if count.fetch_add(1, Ordering::SeqCst) < 2 { 503 } else { 200 },
+            body: "hello",
+        }
+    };
+
+    let mut response = Request::get(m.url())
+        .retry_policy(backoff())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().unwrap(), "hello");
+    assert_eq!(m.requests().len(), 3);
+}
+
+#[test]
+fn last_response_is_returned_once_retries_are_exhausted() {
+    let m = mock! {
+        status: 503,
+    };
+
+    let response = Request::get(m.url())
+        .retry_policy(backoff())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 503);
+    assert_eq!(m.requests().len(), 4);
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let m = mock! {
+        status: 404,
+    };
+
+    let response = Request::get(m.url())
+        .retry_policy(backoff())
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn request_with_streaming_body_is_not_retried() {
+    let m = mock! {
+        status: 503,
+    };
+
+    let response = Request::put(m.url())
+        .retry_policy(backoff())
+        .body(Body::from_reader("hello".as_bytes()))
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 503);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn retried_request_is_sent_again_in_full() {
+    let m = mock! {
+        status: 429,
+        headers {
+            "retry-after": "0",
+        }
+    };
+
+    Request::post(m.url())
+        .header("x-test", "1")
+        .retry_policy(backoff())
+        .body("hello")
+        .unwrap()
+        .send()
+        .unwrap();
+
+    let requests = m.requests();
+
+    assert_eq!(requests.len(), 4);
+
+    for request in requests {
+        request.expect_header("x-test", "1");
+        request.expect_body("hello");
+    }
+}
+
+#[test]
+fn custom_retry_policy() {
+    let m = mock! {
+        status: 500,
+    };
+
+    let response = Request::get(m.url())
+        .retry_policy(|attempt: &RetryAttempt<'_>| {
+            if attempt.number() < 2 && attempt.status() == Some(StatusCode::INTERNAL_SERVER_ERROR) {
+                Some(Duration::from_millis(1))
+            } else {
+                None
+            }
+        })
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 500);
+    assert_eq!(m.requests().len(), 2);
+}
+
+#[test]
+fn connection_failure_is_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+
+    let result = Request::get("http://127.0.0.1:1")
+        .retry_policy({
+            let attempts = attempts.clone();
+            let backoff = backoff();
+
+            move |attempt: &RetryAttempt<'_>| {
+                attempts.store(attempt.number() as usize, Ordering::SeqCst);
+                backoff.retry_delay(attempt)
+            }
+        })
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_eq!(
+        result.unwrap_err().kind(),
+        isahc::error::ErrorKind::ConnectionFailed
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn retries_stop_at_total_timeout() {
+    let m = mock! {
+        status: 503,
+        delay: 100ms,
+    };
+
+    let start = Instant::now();
+
+    let result = Request::get(m.url())
+        .retry_policy(
+            ExponentialBackoff::new(100)
+                .base_delay(Duration::from_millis(10))
+                .max_delay(Duration::from_millis(10))
+                .jitter(Jitter::None)
+                .with_total_timeout(Duration::from_millis(500)),
+        )
+        .body(())
+        .unwrap()
+        .send();
+
+    // Depending on timing, the deadline is either reached while waiting to
+    // retry or during the last attempt, which may not have connected yet.
+    match result {
+        Ok(response) => assert_eq!(response.status(), 503),
+        Err(e) => assert!(e.is_timeout()),
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(m.requests().len() >= 2);
+    assert!(m.requests().len() < 100);
+}
+
+#[test]
+fn retry_is_not_attempted_if_delay_exceeds_total_timeout() {
+    let m = mock! {
+        status: 503,
+    };
+
+    let response = Request::get(m.url())
+        .retry_policy(
+            ExponentialBackoff::new(3)
+                .base_delay(Duration::from_secs(5))
+                .jitter(Jitter::None)
+                .with_total_timeout(Duration::from_secs(1)),
+        )
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 503);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn attempt_is_cut_off_at_total_timeout() {
+    let m = mock! {
+        delay: 2s,
+    };
+
+    let start = Instant::now();
+
+    let result = Request::get(m.url())
+        .timeout(Duration::from_secs(10))
+        .retry_policy(backoff().with_total_timeout(Duration::from_millis(300)))
+        .body(())
+        .unwrap()
+        .send();
+
+    assert_eq!(result.unwrap_err().kind(), isahc::error::ErrorKind::Timeout);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
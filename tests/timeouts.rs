@@ -60,3 +60,236 @@ fn timeout_during_response_body_produces_error() {
         std::io::ErrorKind::TimedOut
     );
 }
+
+#[test]
+fn timeout_error_includes_bytes_received() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let error = Request::get(m.url())
+        .timeout(Duration::from_millis(500))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::Timeout);
+    assert!(error.is_timeout());
+    assert_eq!(error.bytes_received(), Some(0));
+}
+
+#[test]
+fn read_timeout_aborts_stalled_response_body() {
+    struct StalledReader;
+
+    impl Read for StalledReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_secs(3));
+            Ok(0)
+        }
+    }
+
+    let m = mock! {
+        body_reader: Cursor::new(vec![0; 1000]).chain(StalledReader),
+    };
+
+    let mut response = Request::get(m.url())
+        .read_timeout(Duration::from_millis(500))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(
+        response.copy_to(io::sink()).unwrap_err().kind(),
+        io::ErrorKind::TimedOut
+    );
+}
+
+#[test]
+fn read_timeout_does_not_limit_total_time() {
+    struct SlowReader(usize);
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Ok(0);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+            self.0 -= 1;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+
+    let m = mock! {
+        body_reader: SlowReader(10),
+    };
+
+    let mut response = Request::get(m.url())
+        .read_timeout(Duration::from_secs(1))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "aaaaaaaaaa");
+    assert!(response.metrics().is_none());
+}
+
+#[test]
+fn queue_timeout_fails_requests_waiting_for_a_connection() {
+    let m = mock! {
+        delay: 2s,
+    };
+
+    let client = HttpClient::builder()
+        .max_connections_per_host(1)
+        .build()
+        .unwrap();
+
+    // Occupy the only connection allowed.
+    let first = {
+        let client = client.clone();
+        let url = m.url();
+        thread::spawn(move || client.get(url))
+    };
+
+    thread::sleep(Duration::from_millis(500));
+
+    let error = client
+        .send(
+            Request::get(m.url())
+                .queue_timeout(Duration::from_millis(200))
+                .body(())
+                .unwrap(),
+        )
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::QueueTimeout);
+    assert!(error.is_timeout());
+
+    assert_eq!(first.join().unwrap().unwrap().status(), 200);
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn queue_timeout_does_not_limit_started_requests() {
+    let m = mock! {
+        delay: 500ms,
+    };
+
+    let response = Request::get(m.url())
+        .queue_timeout(Duration::from_millis(100))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn send_with_timeout_gives_up_waiting_for_a_slow_response() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let result = isahc::HttpClient::new().unwrap().send_with_timeout(
+        Request::get(m.url()).body(()).unwrap(),
+        Duration::from_millis(100),
+    );
+
+    assert_matches!(result, Err(e) if e == isahc::error::ErrorKind::Timeout);
+}
+
+#[test]
+fn wait_timeout_returns_response_received_in_time() {
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let mut response = isahc::get_async(m.url())
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "hello world");
+}
+
+#[test]
+fn wait_timeout_gives_up_waiting_for_a_slow_response() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let result = isahc::get_async(m.url()).wait_timeout(Duration::from_millis(100));
+
+    assert_matches!(result, Err(e) if e == isahc::error::ErrorKind::Timeout);
+}
+
+#[test]
+fn headers_timeout_aborts_request_without_response() {
+    let m = mock! {
+        delay: 1s,
+    };
+
+    let error = Request::get(m.url())
+        .headers_timeout(Duration::from_millis(200))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::HeadersTimeout);
+    assert!(error.is_timeout());
+}
+
+#[test]
+fn headers_timeout_does_not_limit_response_body() {
+    struct SlowReader(usize);
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Ok(0);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+            self.0 -= 1;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+
+    let m = mock! {
+        body_reader: SlowReader(5),
+    };
+
+    let mut response = Request::get(m.url())
+        .headers_timeout(Duration::from_millis(500))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "aaaaa");
+}
+
+#[test]
+fn idle_timeout_aborts_request_waiting_for_response() {
+    let m = mock! {
+        delay: 3s,
+    };
+
+    let error = Request::get(m.url())
+        .idle_timeout(Duration::from_secs(1))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    assert_eq!(error.kind(), isahc::error::ErrorKind::IdleTimeout);
+    assert!(error.is_timeout());
+}
@@ -0,0 +1,62 @@
+use isahc::{error::ErrorKind, prelude::*, HttpClient};
+use std::io::{Read, Write};
+use testserver::{mock, socks4::Socks4Server};
+
+#[test]
+fn tunnel_is_requested_from_http_proxy() {
+    // Our test server pretends to be a proxy that refuses to open tunnels.
+    let m = mock! {
+        status: 403,
+    };
+
+    let client = HttpClient::builder()
+        .proxy(Some(m.url().parse().unwrap()))
+        .build()
+        .unwrap();
+
+    let error = client.connect_tunnel("127.0.0.2:1234").unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::ConnectionFailed);
+    assert_eq!(m.request().method, "CONNECT");
+    assert_eq!(m.request().url, "127.0.0.2:1234");
+}
+
+#[test]
+#[cfg_attr(tarpaulin, ignore)]
+fn data_is_passed_through_tunnel() {
+    let proxy_server = Socks4Server::new("127.0.0.1:0").unwrap();
+    let proxy_uri = format!("socks4://{}/", proxy_server.addr());
+    proxy_server.spawn();
+
+    let m = mock! {
+        body: "hello world",
+    };
+
+    let client = HttpClient::builder()
+        .proxy(Some(proxy_uri.parse().unwrap()))
+        .build()
+        .unwrap();
+
+    let mut tunnel = client.connect_tunnel(m.addr().to_string()).unwrap();
+
+    tunnel
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    tunnel.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("hello world"));
+    assert_eq!(m.requests().len(), 1);
+}
+
+#[test]
+fn tunnel_authority_without_port_is_rejected() {
+    let error = HttpClient::new()
+        .unwrap()
+        .connect_tunnel("example.org")
+        .unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::InvalidRequest);
+}
@@ -3,7 +3,7 @@
 use isahc::{config::Dialer, prelude::*};
 use std::{
     io::{self, Write},
-    os::unix::net::UnixListener,
+    os::unix::net::{UnixListener, UnixStream},
     thread,
 };
 use tempfile::TempDir;
@@ -40,3 +40,33 @@ fn send_request_to_unix_socket() {
 
     assert_eq!(response.text().unwrap(), "success\n");
 }
+
+#[test]
+#[rustfmt::skip]
+fn send_request_over_connected_socket() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    thread::spawn(move || {
+        let mut reader = server.try_clone().unwrap();
+
+        thread::spawn(move || {
+            io::copy(&mut reader, &mut io::sink()).unwrap();
+        });
+
+        server.write_all(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 8\r\n\
+            \r\n\
+            success\n\
+        ").unwrap();
+    });
+
+    let mut response = Request::get("http://localhost")
+        .dial(Dialer::connected_socket(client))
+        .body(())
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.text().unwrap(), "success\n");
+}
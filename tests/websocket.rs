@@ -0,0 +1,42 @@
+#![cfg(feature = "websocket")]
+
+use isahc::{error::ErrorKind, HttpClient};
+use testserver::mock;
+
+#[test]
+fn upgrade_request_has_handshake_headers() {
+    let m = mock! {
+        status: 403,
+    };
+    let client = HttpClient::new().unwrap();
+
+    let result = client.websocket(format!("ws://{}/chat", m.addr()));
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::BadStatus);
+
+    let request = m.request();
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.url, "/chat");
+    request.expect_header("upgrade", "websocket");
+    request.expect_header("connection", "Upgrade");
+    request.expect_header("sec-websocket-version", "13");
+}
+
+#[test]
+fn ok_response_is_not_an_upgrade() {
+    let m = mock!();
+    let client = HttpClient::new().unwrap();
+
+    let result = client.websocket(format!("ws://{}", m.addr()));
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::BadStatus);
+}
+
+#[test]
+fn unsupported_scheme_is_rejected() {
+    let client = HttpClient::new().unwrap();
+
+    let result = client.websocket("ftp://localhost");
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidRequest);
+}
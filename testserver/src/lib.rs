@@ -1,3 +1,6 @@
+// These lints suggest to use features not available in our MSRV.
+#![allow(clippy::match_like_matches_macro)]
+
 mod mock;
 mod request;
 mod responder;
@@ -47,6 +50,22 @@ macro_rules! mock {
         $crate::mock!(@response(response) $($tail)*)
     }};
 
+    (@response($response:expr) hang_up: $value:expr, $($tail:tt)*) => {{
+        let mut response = $response;
+
+        response.hang_up = $value;
+
+        $crate::mock!(@response(response) $($tail)*)
+    }};
+
+    (@response($response:expr) reset_after: $value:expr, $($tail:tt)*) => {{
+        let mut response = $response;
+
+        response.reset_after = $value.map(Into::into);
+
+        $crate::mock!(@response(response) $($tail)*)
+    }};
+
     (@response($response:expr) delay: $delay:tt, $($tail:tt)*) => {{
         let duration = $crate::helpers::parse_duration(stringify!($delay));
         ::std::thread::sleep(duration);
@@ -10,25 +10,26 @@
 use crate::{request::Request, responder::*, response::Response};
 use std::{
     collections::VecDeque,
-    io::{Cursor, Read, Write},
-    net::{SocketAddr, TcpStream},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
     sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
-use tiny_http::Server;
 
 /// A mock HTTP endpoint.
 pub struct Mock<R> {
-    server: Arc<Server>,
+    addr: SocketAddr,
     requests: Arc<Mutex<VecDeque<Request>>>,
     responder: Arc<R>,
 }
 
 impl<R: Responder> Mock<R> {
     pub fn new(responder: R) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
         let mock = Self {
-            server: Arc::new(Server::http("127.0.0.1:0").unwrap()),
+            addr: listener.local_addr().unwrap(),
             requests: Default::default(),
             responder: Arc::new(responder),
         };
@@ -37,19 +38,22 @@ impl<R: Responder> Mock<R> {
             let mock = mock.clone();
 
             move || {
-                for request in mock.server.incoming_requests() {
-                    mock.handle_request(request);
+                for stream in listener.incoming().flatten() {
+                    let mock = mock.clone();
+
+                    thread::spawn(move || {
+                        // Errors just mean that the client went away.
+                        let _ = mock.handle_connection(stream);
+                    });
                 }
             }
         });
 
-        mock.wait_until_ready();
-
         mock
     }
 
     pub fn addr(&self) -> SocketAddr {
-        self.server.server_addr()
+        self.addr
     }
 
     pub fn url(&self) -> String {
@@ -58,7 +62,7 @@ impl<R: Responder> Mock<R> {
 
     /// Get the first request received by this mock.
     pub fn request(&self) -> Request {
-        let request = self.requests.lock().unwrap().get(0).cloned();
+        let request = self.requests.lock().unwrap().front().cloned();
 
         request.expect("no request received")
     }
@@ -68,106 +72,105 @@ impl<R: Responder> Mock<R> {
         self.requests.lock().unwrap().iter().cloned().collect()
     }
 
-    #[rustfmt::skip]
-    fn is_ready(&self) -> bool {
-        TcpStream::connect(self.addr())
-            .and_then(|mut stream| {
-                stream.write_all(b"\
-                    GET /health HTTP/1.1\r\n\
-                    host: api.mock.local\r\n\
-                    connection: close\r\n\
-                    \r\n\
-                ")?;
-
-                let mut response = Vec::new();
-                stream.read_to_end(&mut response)?;
-
-                Ok(response.ends_with(b"\r\nOK"))
-            })
-            .unwrap_or(false)
-    }
-
-    fn wait_until_ready(&self) {
-        for _ in 0..9 {
-            if self.is_ready() {
-                return;
-            }
-            thread::sleep(Duration::from_millis(50));
-        }
-
-        panic!("mock server did not become ready after 9 tries");
-    }
-
     fn respond(&self, request: Request) -> Response {
-        if let Some(response) = self.responder.respond(request.clone()) {
+        if let Some(response) = self.responder.respond(request) {
             return response;
         }
 
         Response {
             status_code: 404,
-            headers: Vec::new(),
-            body: Box::new(std::io::empty()),
-            body_len: Some(0),
+            ..Response::default()
         }
     }
 
-    fn handle_request(&self, mut request: tiny_http::Request) {
-        if request
-            .headers()
-            .iter()
-            .find(|h| h.field.as_str() == "host" && h.value == "api.mock.local")
-            .is_some()
-        {
-            if let Some(response) = self.handle_api_request(&request) {
-                request.respond(response).unwrap();
-                return;
+    /// Handle requests sent over a connection until the client closes it or
+    /// asks for it to be closed.
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+
+        loop {
+            let request_line = match read_line(&mut reader)? {
+                Some(line) if !line.is_empty() => line,
+                _ => return Ok(()),
+            };
+
+            let mut parts = request_line.split(' ');
+            let method = parts.next().unwrap_or_default().to_string();
+            let url = parts.next().unwrap_or_default().to_string();
+            let version = parts.next().unwrap_or("HTTP/1.1").to_string();
+
+            let headers = read_headers(&mut reader)?;
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.to_ascii_lowercase())
+            };
+
+            let chunked = header("transfer-encoding")
+                .map(|v| v.contains("chunked"))
+                .unwrap_or(false);
+            let content_length = header("content-length").and_then(|v| v.parse::<u64>().ok());
+
+            if header("expect").as_deref() == Some("100-continue")
+                && (chunked || content_length.unwrap_or(0) > 0)
+            {
+                writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                writer.flush()?;
             }
-        }
 
-        let mut body = Vec::new();
+            let mut body = Vec::new();
+            let mut trailers = Vec::new();
 
-        if let Some(len) = request.body_length() {
-            body.reserve(len);
-        }
+            if chunked {
+                read_chunked(&mut reader, &mut body, &mut trailers)?;
+            } else if let Some(len) = content_length {
+                reader.by_ref().take(len).read_to_end(&mut body)?;
+            }
 
-        request.as_reader().read_to_end(&mut body).unwrap();
-
-        // Build a record of the request received.
-        let mock_request = Request {
-            method: request.method().to_string(),
-            url: request.url().to_string(),
-            headers: request
-                .headers()
-                .iter()
-                .map(|header| (header.field.to_string(), header.value.to_string()))
-                .collect(),
-            body: Some(body),
-        };
+            let keep_alive = match header("connection") {
+                Some(value) if value.contains("close") => false,
+                Some(value) if value.contains("keep-alive") => true,
+                _ => version == "HTTP/1.1",
+            };
+
+            // Build a record of the request received.
+            let mock_request = Request {
+                method,
+                url,
+                headers,
+                body: Some(body),
+                trailers,
+            };
 
-        self.requests
-            .lock()
-            .unwrap()
-            .push_back(mock_request.clone());
-
-        let response = self.respond(mock_request);
-
-        request.respond(response.into_http_response()).unwrap();
-    }
-
-    fn handle_api_request(
-        &self,
-        request: &tiny_http::Request,
-    ) -> Option<tiny_http::Response<Cursor<Vec<u8>>>> {
-        if request.url() == "/health" {
-            Some(tiny_http::Response::new(
-                200.into(),
-                vec![],
-                Cursor::new((&b"OK"[..]).into()),
-                Some(2),
-                None,
-            ))
-        } else {
-            None
+            self.requests
+                .lock()
+                .unwrap()
+                .push_back(mock_request.clone());
+
+            let head = mock_request.method == "HEAD";
+            let response = self.respond(mock_request);
+
+            if response.hang_up {
+                return Ok(());
+            }
+
+            if let Some(bytes) = response.reset_after {
+                writer.write_all(&bytes)?;
+                writer.flush()?;
+
+                // Give the client a moment to receive the bytes, as they are
+                // discarded if they are still queued up when the connection
+                // is reset.
+                thread::sleep(Duration::from_millis(50));
+
+                return set_reset_on_close(&writer);
+            }
+
+            if !write_response(&mut writer, &version, head, keep_alive, response)? {
+                return Ok(());
+            }
         }
     }
 }
@@ -181,9 +184,214 @@ impl Default for Mock<DefaultResponder> {
 impl<R> Clone for Mock<R> {
     fn clone(&self) -> Self {
         Self {
-            server: self.server.clone(),
+            addr: self.addr,
             requests: self.requests.clone(),
             responder: self.responder.clone(),
         }
     }
 }
+
+/// Make closing the given stream reset the connection instead of closing it
+/// gracefully.
+#[cfg(unix)]
+fn set_reset_on_close(stream: &TcpStream) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let linger = libc::linger {
+        l_onoff: 1,
+        l_linger: 0,
+    };
+
+    let result = unsafe {
+        libc::setsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &linger as *const libc::linger as *const libc::c_void,
+            std::mem::size_of::<libc::linger>() as libc::socklen_t,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn set_reset_on_close(_: &TcpStream) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "resetting connections is only supported on Unix",
+    ))
+}
+
+/// Read a line without its line ending, or `None` at the end of the stream.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()))
+}
+
+/// Read header lines up to and including the empty line that ends them.
+fn read_headers(reader: &mut impl BufRead) -> io::Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+
+    while let Some(line) = read_line(reader)? {
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(colon) = line.find(':') {
+            headers.push((
+                line[..colon].trim().to_string(),
+                line[colon + 1..].trim().to_string(),
+            ));
+        }
+    }
+
+    Ok(headers)
+}
+
+/// Read a chunked body along with any trailers sent after it.
+fn read_chunked(
+    reader: &mut impl BufRead,
+    body: &mut Vec<u8>,
+    trailers: &mut Vec<(String, String)>,
+) -> io::Result<()> {
+    loop {
+        let line = read_line(reader)?.unwrap_or_default();
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+
+        if size == 0 {
+            trailers.extend(read_headers(reader)?);
+            return Ok(());
+        }
+
+        reader.by_ref().take(size as u64).read_to_end(body)?;
+        read_line(reader)?;
+    }
+}
+
+/// Write a response, flushing each part of the body as soon as it is read so
+/// that slow or stalled bodies reach the client as they are produced.
+///
+/// Returns whether the connection can be used for another request.
+fn write_response(
+    writer: &mut impl Write,
+    version: &str,
+    head: bool,
+    keep_alive: bool,
+    mut response: Response,
+) -> io::Result<bool> {
+    let has_body = !head
+        && match response.status_code {
+            100..=199 | 204 | 304 => false,
+            _ => true,
+        };
+    let chunked = response.body_len.is_none() && version == "HTTP/1.1";
+
+    // A body of unknown length can only be delimited by closing the
+    // connection over HTTP/1.0.
+    let keep_alive = keep_alive && (response.body_len.is_some() || chunked || !has_body);
+
+    write!(
+        writer,
+        "{} {} {}\r\n",
+        version,
+        response.status_code,
+        reason_phrase(response.status_code)
+    )?;
+
+    if !response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("date"))
+    {
+        write!(writer, "date: {}\r\n", httpdate::fmt_http_date(SystemTime::now()))?;
+    }
+
+    for (name, value) in &response.headers {
+        write!(writer, "{}: {}\r\n", name, value)?;
+    }
+
+    match response.body_len {
+        Some(len) if has_body || head => write!(writer, "content-length: {}\r\n", len)?,
+        None if chunked && has_body => writer.write_all(b"transfer-encoding: chunked\r\n")?,
+        _ => {}
+    }
+
+    if !keep_alive {
+        writer.write_all(b"connection: close\r\n")?;
+    }
+
+    writer.write_all(b"\r\n")?;
+    writer.flush()?;
+
+    if has_body {
+        let mut buf = [0; 8192];
+
+        loop {
+            let len = response.body.read(&mut buf)?;
+
+            if len == 0 {
+                break;
+            }
+
+            if chunked {
+                write!(writer, "{:X}\r\n", len)?;
+                writer.write_all(&buf[..len])?;
+                writer.write_all(b"\r\n")?;
+            } else {
+                writer.write_all(&buf[..len])?;
+            }
+
+            writer.flush()?;
+        }
+
+        if chunked {
+            writer.write_all(b"0\r\n\r\n")?;
+            writer.flush()?;
+        }
+    }
+
+    Ok(keep_alive)
+}
+
+fn reason_phrase(status_code: u16) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        416 => "Range Not Satisfiable",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Unknown",
+    }
+}
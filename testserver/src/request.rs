@@ -6,6 +6,7 @@ pub struct Request {
     pub url: String,
     pub headers: Vec<(String, String)>,
     pub body: Option<Vec<u8>>,
+    pub trailers: Vec<(String, String)>,
 }
 
 impl Request {
@@ -35,6 +36,15 @@ impl Request {
             .unwrap_or_else(|| panic!("no header named `{}` with value expected found", name));
     }
 
+    pub fn expect_trailer(&self, name: impl AsRef<str>, value: impl AsRef<str>) {
+        let name = name.as_ref();
+
+        self.trailers
+            .iter()
+            .find(|(n, v)| n.eq_ignore_ascii_case(name) && v == value.as_ref())
+            .unwrap_or_else(|| panic!("no trailer named `{}` with value expected found", name));
+    }
+
     pub fn expect_body(&self, expected: impl AsRef<[u8]>) {
         if let Some(body) = self.body.as_ref() {
             assert_eq!(expected.as_ref(), body.as_slice());
@@ -5,6 +5,13 @@ pub struct Response {
     pub headers: Vec<(String, String)>,
     pub body: Box<dyn Read>,
     pub body_len: Option<usize>,
+
+    /// Close the connection instead of sending the response.
+    pub hang_up: bool,
+
+    /// Send these bytes and then reset the connection instead of sending the
+    /// response. Only supported on Unix.
+    pub reset_after: Option<Vec<u8>>,
 }
 
 impl Response {
@@ -12,6 +19,15 @@ impl Response {
         Self::default()
     }
 
+    /// Close the connection without sending anything, like a server closing
+    /// an idle connection just as a request arrives on it.
+    pub fn hang_up() -> Self {
+        Self {
+            hang_up: true,
+            ..Self::default()
+        }
+    }
+
     pub fn with_body_buf(mut self, buf: impl Into<Vec<u8>>) -> Self {
         let buf = buf.into();
         self.body_len = Some(buf.len());
@@ -24,21 +40,6 @@ impl Response {
         self.body = Box::new(reader);
         self
     }
-
-    pub(crate) fn into_http_response(self) -> tiny_http::Response<Box<dyn Read>> {
-        tiny_http::Response::new(
-            self.status_code.into(),
-            self.headers
-                .into_iter()
-                .map(|(name, value)| {
-                    tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
-                })
-                .collect(),
-            self.body,
-            self.body_len,
-            None,
-        )
-    }
 }
 
 impl Default for Response {
@@ -48,6 +49,8 @@ impl Default for Response {
             headers: Vec::new(),
             body: Box::new(std::io::empty()),
             body_len: Some(0),
+            hang_up: false,
+            reset_after: None,
         }
     }
 }